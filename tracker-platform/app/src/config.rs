@@ -2,6 +2,7 @@ use anyhow::{Context, Result};
 use config::{Config as ConfigBuilder, Environment, File};
 use serde::Deserialize;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
@@ -15,6 +16,10 @@ pub struct Config {
     pub telemetry: TelemetryConfig,
     pub cors: CorsConfig,
     pub rate_limit: RateLimitConfig,
+    pub bonus: BonusConfig,
+    pub classes: ClassesConfig,
+    pub email: EmailConfig,
+    pub tracker: TrackerConfig,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -69,13 +74,28 @@ pub struct KafkaTopics {
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AuthConfig {
-    pub jwt_secret: String,
     pub jwt_expiration_hours: u64,
     pub refresh_token_expiration_days: u64,
     pub password_reset_expiration_hours: u64,
     pub email_verification_expiration_hours: u64,
     pub max_login_attempts: u32,
     pub lockout_duration_minutes: u64,
+    /// Days each JWT signing key is used before the next one takes over
+    pub jwt_key_rotation_days: u64,
+    /// Hours a new signing key is published in the JWKS before it signs
+    pub jwt_key_publish_ahead_hours: u64,
+    /// Days a replaced signing key keeps verifying tokens
+    pub jwt_key_overlap_days: u64,
+}
+
+impl AuthConfig {
+    pub fn key_rotation_policy(&self) -> auth::RotationPolicy {
+        auth::RotationPolicy {
+            rotation_interval: chrono::Duration::days(self.jwt_key_rotation_days as i64),
+            publish_ahead: chrono::Duration::hours(self.jwt_key_publish_ahead_hours as i64),
+            overlap: chrono::Duration::days(self.jwt_key_overlap_days as i64),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -111,6 +131,86 @@ pub struct RateLimitConfig {
     pub burst_size: u32,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct BonusConfig {
+    pub accrual_enabled: bool,
+    pub accrual_interval_secs: u64,
+    /// Log the payouts each run would make without crediting anyone
+    pub accrual_dry_run: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClassesConfig {
+    pub promotion_enabled: bool,
+    pub promotion_interval_secs: u64,
+    /// Log the promotions and demotions each run would make without applying them
+    pub promotion_dry_run: bool,
+    /// Account promotion and demotion PMs are sent from
+    pub system_user_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailConfig {
+    /// Run the dispatcher that delivers queued email
+    pub dispatch_enabled: bool,
+    pub dispatch_interval_secs: u64,
+    pub dispatch_batch_size: i64,
+    /// "smtp", or "file" to write .eml files to `file_dir` instead
+    pub transport: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    /// "tls", "starttls" or "none"
+    pub smtp_security: String,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub file_dir: PathBuf,
+    /// Sender, e.g. "Tracker <noreply@tracker.example>"
+    pub from: String,
+    pub site_name: String,
+    /// Public URL of the site, for links in emails
+    pub site_url: String,
+    /// Key for signing unsubscribe links
+    pub unsubscribe_secret: String,
+    /// Directory of per-locale templates overriding the built-in English ones
+    pub templates_dir: Option<PathBuf>,
+}
+
+impl EmailConfig {
+    pub fn service_config(&self) -> auth::EmailConfig {
+        auth::EmailConfig::new(
+            self.from.as_str(),
+            self.site_name.as_str(),
+            self.site_url.as_str(),
+            self.unsubscribe_secret.as_bytes(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrackerConfig {
+    /// Reject scrapes without a valid passkey
+    pub scrape_require_passkey: bool,
+    /// Serve a full scrape when no info_hash is given
+    pub full_scrape_enabled: bool,
+    /// How often the scrape visibility set and full scrape are rebuilt
+    pub scrape_rebuild_interval_secs: u64,
+    /// Scrapes allowed per user, or per address for anonymous scrapes
+    pub scrapes_per_window: u32,
+    pub scrape_rate_window_secs: u64,
+}
+
+impl TrackerConfig {
+    pub fn scrape_config(&self) -> tracker::scrape_policy::ScrapeConfig {
+        tracker::scrape_policy::ScrapeConfig {
+            require_passkey: self.scrape_require_passkey,
+            full_scrape_enabled: self.full_scrape_enabled,
+            rebuild_interval: Duration::from_secs(self.scrape_rebuild_interval_secs),
+            scrapes_per_window: self.scrapes_per_window,
+            rate_window: Duration::from_secs(self.scrape_rate_window_secs),
+        }
+    }
+}
+
 impl Config {
     pub fn load() -> Result<Self> {
         // Load .env file if it exists
@@ -138,6 +238,9 @@ impl Config {
             .set_default("auth.email_verification_expiration_hours", 24)?
             .set_default("auth.max_login_attempts", 5)?
             .set_default("auth.lockout_duration_minutes", 15)?
+            .set_default("auth.jwt_key_rotation_days", 30)?
+            .set_default("auth.jwt_key_publish_ahead_hours", 24)?
+            .set_default("auth.jwt_key_overlap_days", 8)?
             .set_default("storage.upload_dir", "/tmp/uploads")?
             .set_default("storage.max_upload_size_mb", 100)?
             .set_default(
@@ -166,6 +269,29 @@ impl Config {
             .set_default("rate_limit.enabled", true)?
             .set_default("rate_limit.requests_per_second", 100)?
             .set_default("rate_limit.burst_size", 200)?
+            .set_default("bonus.accrual_enabled", true)?
+            .set_default("bonus.accrual_interval_secs", 3600)?
+            .set_default("bonus.accrual_dry_run", false)?
+            .set_default("classes.promotion_enabled", false)?
+            .set_default("classes.promotion_interval_secs", 24 * 3600)?
+            .set_default("classes.promotion_dry_run", false)?
+            .set_default("email.dispatch_enabled", false)?
+            .set_default("email.dispatch_interval_secs", 30)?
+            .set_default("email.dispatch_batch_size", 50)?
+            .set_default("email.transport", "smtp")?
+            .set_default("email.smtp_host", "localhost")?
+            .set_default("email.smtp_port", 587)?
+            .set_default("email.smtp_security", "starttls")?
+            .set_default("email.file_dir", "/tmp/tracker-mail")?
+            .set_default("email.from", "Tracker <noreply@localhost>")?
+            .set_default("email.site_name", "Tracker")?
+            .set_default("email.site_url", "http://localhost:8080")?
+            .set_default("email.unsubscribe_secret", "")?
+            .set_default("tracker.scrape_require_passkey", true)?
+            .set_default("tracker.full_scrape_enabled", false)?
+            .set_default("tracker.scrape_rebuild_interval_secs", 300)?
+            .set_default("tracker.scrapes_per_window", 30)?
+            .set_default("tracker.scrape_rate_window_secs", 60)?
             // Load config file if it exists
             .add_source(File::with_name("config/default").required(false))
             .add_source(File::with_name(&format!("config/{}", environment)).required(false))
@@ -212,11 +338,17 @@ impl Config {
         }
 
         // Validate auth config
-        if self.auth.jwt_secret.is_empty() {
-            anyhow::bail!("JWT secret is required");
+        if self.auth.jwt_key_rotation_days == 0 {
+            anyhow::bail!("JWT key rotation interval must be greater than 0");
+        }
+        let policy = self.auth.key_rotation_policy();
+        if policy.publish_ahead >= policy.rotation_interval {
+            anyhow::bail!(
+                "JWT key publish-ahead window must be shorter than the rotation interval"
+            );
         }
-        if self.auth.jwt_secret.len() < 32 {
-            anyhow::bail!("JWT secret must be at least 32 characters");
+        if policy.overlap < auth::jwt::TokenType::Refresh.expiration_duration() {
+            anyhow::bail!("JWT key overlap must be at least the refresh token lifetime (7 days)");
         }
 
         // Validate storage config
@@ -240,6 +372,50 @@ impl Config {
             );
         }
 
+        // Validate bonus config
+        if self.bonus.accrual_enabled && self.bonus.accrual_interval_secs == 0 {
+            anyhow::bail!("Bonus accrual interval must be greater than 0");
+        }
+
+        // Validate class promotion config
+        if self.classes.promotion_enabled {
+            if self.classes.promotion_interval_secs == 0 {
+                anyhow::bail!("Class promotion interval must be greater than 0");
+            }
+            if self.classes.system_user_id.is_none() {
+                anyhow::bail!("Class promotion needs classes.system_user_id to send PMs from");
+            }
+        }
+
+        // Validate email config
+        if self.email.dispatch_enabled {
+            if self.email.dispatch_interval_secs == 0 || self.email.dispatch_batch_size <= 0 {
+                anyhow::bail!("Email dispatch interval and batch size must be greater than 0");
+            }
+            if !["smtp", "file"].contains(&self.email.transport.as_str()) {
+                anyhow::bail!(
+                    "Invalid email transport: {}. Must be one of: smtp, file",
+                    self.email.transport
+                );
+            }
+            if auth::SmtpSecurity::parse(&self.email.smtp_security).is_none() {
+                anyhow::bail!(
+                    "Invalid SMTP security: {}. Must be one of: tls, starttls, none",
+                    self.email.smtp_security
+                );
+            }
+            if self.email.unsubscribe_secret.len() < 32 {
+                anyhow::bail!("Email unsubscribe secret must be at least 32 characters");
+            }
+        }
+
+        // Validate tracker config
+        if self.tracker.scrape_rebuild_interval_secs == 0
+            || self.tracker.scrape_rate_window_secs == 0
+        {
+            anyhow::bail!("Scrape rebuild interval and rate window must be greater than 0");
+        }
+
         Ok(())
     }
 
@@ -285,13 +461,15 @@ impl Default for Config {
             },
             kafka: None,
             auth: AuthConfig {
-                jwt_secret: "change-me-in-production-min-32-chars".to_string(),
                 jwt_expiration_hours: 24,
                 refresh_token_expiration_days: 30,
                 password_reset_expiration_hours: 1,
                 email_verification_expiration_hours: 24,
                 max_login_attempts: 5,
                 lockout_duration_minutes: 15,
+                jwt_key_rotation_days: 30,
+                jwt_key_publish_ahead_hours: 24,
+                jwt_key_overlap_days: 8,
             },
             storage: StorageConfig {
                 upload_dir: PathBuf::from("/tmp/uploads"),
@@ -337,6 +515,41 @@ impl Default for Config {
                 requests_per_second: 100,
                 burst_size: 200,
             },
+            bonus: BonusConfig {
+                accrual_enabled: true,
+                accrual_interval_secs: 3600,
+                accrual_dry_run: false,
+            },
+            classes: ClassesConfig {
+                promotion_enabled: false,
+                promotion_interval_secs: 24 * 3600,
+                promotion_dry_run: false,
+                system_user_id: None,
+            },
+            email: EmailConfig {
+                dispatch_enabled: false,
+                dispatch_interval_secs: 30,
+                dispatch_batch_size: 50,
+                transport: "smtp".to_string(),
+                smtp_host: "localhost".to_string(),
+                smtp_port: 587,
+                smtp_security: "starttls".to_string(),
+                smtp_username: None,
+                smtp_password: None,
+                file_dir: PathBuf::from("/tmp/tracker-mail"),
+                from: "Tracker <noreply@localhost>".to_string(),
+                site_name: "Tracker".to_string(),
+                site_url: "http://localhost:8080".to_string(),
+                unsubscribe_secret: String::new(),
+                templates_dir: None,
+            },
+            tracker: TrackerConfig {
+                scrape_require_passkey: true,
+                full_scrape_enabled: false,
+                scrape_rebuild_interval_secs: 300,
+                scrapes_per_window: 30,
+                scrape_rate_window_secs: 60,
+            },
         }
     }
 }
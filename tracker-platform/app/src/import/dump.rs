@@ -0,0 +1,519 @@
+//! Dump readers
+//!
+//! Loads tables from either a `mysqldump` SQL file or a directory of CSV
+//! exports (one `<table>.csv` per table with a header row). Both end up as
+//! the same [`Table`] of nullable text cells; typing happens when the
+//! source mapping reads a [`Record`].
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A table loaded from a dump
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+impl Table {
+    /// Iterate over rows with column lookup by name
+    pub fn records(&self) -> impl Iterator<Item = Record<'_>> {
+        self.rows.iter().map(move |values| Record {
+            columns: &self.columns,
+            values,
+        })
+    }
+}
+
+/// One row of a [`Table`]
+#[derive(Debug, Clone, Copy)]
+pub struct Record<'a> {
+    columns: &'a [String],
+    values: &'a [Option<String>],
+}
+
+impl<'a> Record<'a> {
+    /// Raw value of a column; `None` for NULL or a missing column
+    pub fn get(&self, column: &str) -> Option<&'a str> {
+        let idx = self
+            .columns
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(column))?;
+        self.values.get(idx)?.as_deref()
+    }
+
+    /// Required text column
+    pub fn text(&self, column: &str) -> Result<String> {
+        self.get(column)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Missing column {}", column))
+    }
+
+    /// Optional text column, with empty strings treated as NULL
+    pub fn opt_text(&self, column: &str) -> Option<String> {
+        self.get(column)
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Optional integer column
+    pub fn opt_int(&self, column: &str) -> Option<i64> {
+        self.get(column)?.trim().parse().ok()
+    }
+
+    /// Boolean column stored as 0/1, 'true'/'false' or an ENUM('0','1')
+    pub fn flag(&self, column: &str) -> bool {
+        matches!(
+            self.get(column).map(|v| v.trim().to_ascii_lowercase()).as_deref(),
+            Some("1" | "true" | "yes")
+        )
+    }
+
+    /// Timestamp column stored as a DATETIME string or unix seconds
+    pub fn timestamp(&self, column: &str) -> Option<DateTime<Utc>> {
+        parse_timestamp(self.get(column)?)
+    }
+}
+
+/// All tables loaded from a dump, keyed by lowercase table name
+#[derive(Debug, Clone, Default)]
+pub struct Dump {
+    tables: HashMap<String, Table>,
+}
+
+impl Dump {
+    /// Load a dump from an `.sql` file or a directory of `.csv` files
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            return Self::from_csv_dir(path);
+        }
+
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read dump {}", path.display()))?;
+        Self::from_sql(&String::from_utf8_lossy(&bytes))
+    }
+
+    /// Parse the CREATE TABLE and INSERT statements of a MySQL dump
+    pub fn from_sql(sql: &str) -> Result<Self> {
+        let mut dump = Self::default();
+
+        for statement in split_statements(sql) {
+            let statement = statement.trim();
+            let upper = statement
+                .get(..12.min(statement.len()))
+                .unwrap_or_default()
+                .to_ascii_uppercase();
+
+            if upper.starts_with("CREATE TABLE") {
+                let name = first_identifier(statement)
+                    .ok_or_else(|| anyhow!("CREATE TABLE without a name"))?;
+                let columns = statement
+                    .lines()
+                    .skip(1)
+                    .filter_map(|line| {
+                        let line = line.trim();
+                        line.starts_with('`').then(|| first_identifier(line)).flatten()
+                    })
+                    .collect();
+                dump.tables.entry(name.to_ascii_lowercase()).or_default().columns = columns;
+            } else if upper.starts_with("INSERT INTO") || upper.starts_with("REPLACE INTO") {
+                dump.parse_insert(statement)?;
+            }
+        }
+
+        Ok(dump)
+    }
+
+    /// Read every `<table>.csv` file in a directory
+    pub fn from_csv_dir(dir: &Path) -> Result<Self> {
+        let mut dump = Self::default();
+
+        for entry in std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read dump directory {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("csv") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let bytes = std::fs::read(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let mut rows = parse_csv(&String::from_utf8_lossy(&bytes)).into_iter();
+            let columns = rows
+                .next()
+                .map(|header| header.into_iter().map(Option::unwrap_or_default).collect())
+                .unwrap_or_default();
+
+            dump.tables.insert(
+                name.to_ascii_lowercase(),
+                Table {
+                    columns,
+                    rows: rows.collect(),
+                },
+            );
+        }
+
+        Ok(dump)
+    }
+
+    /// Get a table by name
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(&name.to_ascii_lowercase())
+    }
+
+    /// Get a table by name, failing if the dump does not contain it
+    pub fn require(&self, name: &str) -> Result<&Table> {
+        self.table(name)
+            .ok_or_else(|| anyhow!("Dump has no `{}` table", name))
+    }
+
+    fn parse_insert(&mut self, statement: &str) -> Result<()> {
+        let name = first_identifier(statement)
+            .ok_or_else(|| anyhow!("INSERT without a table name"))?
+            .to_ascii_lowercase();
+
+        let values_at = find_keyword(statement, "VALUES")
+            .ok_or_else(|| anyhow!("INSERT into {} has no VALUES", name))?;
+        let head = &statement[..values_at];
+
+        // Explicit column list: INSERT INTO `t` (`a`, `b`) VALUES ...
+        let explicit: Vec<String> = match (head.find('('), head.rfind(')')) {
+            (Some(open), Some(close)) if open < close => head[open + 1..close]
+                .split(',')
+                .map(|c| c.trim().trim_matches('`').to_string())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let rows = parse_tuples(&statement[values_at + "VALUES".len()..])
+            .with_context(|| format!("Failed to parse INSERT into {}", name))?;
+
+        let table = self.tables.entry(name.clone()).or_default();
+        if !explicit.is_empty() {
+            if table.columns.is_empty() {
+                table.columns = explicit;
+            } else if table.columns != explicit {
+                // Reorder to the CREATE TABLE column order
+                let order: Vec<Option<usize>> = table
+                    .columns
+                    .iter()
+                    .map(|c| explicit.iter().position(|e| e.eq_ignore_ascii_case(c)))
+                    .collect();
+                for row in rows {
+                    table.rows.push(
+                        order
+                            .iter()
+                            .map(|idx| idx.and_then(|i| row.get(i).cloned().flatten()))
+                            .collect(),
+                    );
+                }
+                return Ok(());
+            }
+        } else if table.columns.is_empty() {
+            bail!("INSERT into {} has no column list and no CREATE TABLE", name);
+        }
+
+        table.rows.extend(rows);
+        Ok(())
+    }
+}
+
+/// Parse a DATETIME string or unix timestamp
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if value.is_empty() || value.starts_with("0000-00-00") {
+        return None;
+    }
+
+    if let Ok(secs) = value.parse::<i64>() {
+        return (secs > 0).then(|| Utc.timestamp_opt(secs, 0).single()).flatten();
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .map(|dt| Utc.from_utc_datetime(&dt))
+}
+
+/// Split SQL into statements on `;`, ignoring quotes and comments
+fn split_statements(sql: &str) -> Vec<&str> {
+    let bytes = sql.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() {
+                    if bytes[i] == b'\\' && quote != b'`' {
+                        i += 1;
+                    } else if bytes[i] == quote {
+                        break;
+                    }
+                    i += 1;
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                let end = sql[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                if sql[start..i].trim().is_empty() {
+                    start = end;
+                }
+                i = end;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = sql[i + 2..].find("*/").map_or(bytes.len(), |n| i + n + 4);
+                if sql[start..i].trim().is_empty() {
+                    start = end;
+                }
+                i = end;
+                continue;
+            }
+            b';' => {
+                statements.push(&sql[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if !sql[start.min(sql.len())..].trim().is_empty() {
+        statements.push(&sql[start..]);
+    }
+
+    statements
+}
+
+/// First backtick-quoted (or bare) identifier after the leading keywords
+fn first_identifier(statement: &str) -> Option<String> {
+    if let Some(open) = statement.find('`') {
+        let close = statement[open + 1..].find('`')?;
+        return Some(statement[open + 1..open + 1 + close].to_string());
+    }
+
+    // Unquoted: INSERT INTO name ... / CREATE TABLE name (...
+    statement
+        .split_whitespace()
+        .nth(2)
+        .map(|s| s.trim_end_matches('(').to_string())
+}
+
+/// Byte offset of a keyword outside quotes
+fn find_keyword(statement: &str, keyword: &str) -> Option<usize> {
+    let upper = statement.to_ascii_uppercase();
+    let mut from = 0;
+    while let Some(pos) = upper[from..].find(keyword) {
+        let at = from + pos;
+        let before = upper[..at].chars().last();
+        let after = upper[at + keyword.len()..].chars().next();
+        if !before.is_some_and(|c| c.is_alphanumeric() || c == '_')
+            && !after.is_some_and(|c| c.is_alphanumeric() || c == '_')
+        {
+            return Some(at);
+        }
+        from = at + keyword.len();
+    }
+    None
+}
+
+/// Parse `(..), (..)` value tuples
+fn parse_tuples(input: &str) -> Result<Vec<Vec<Option<String>>>> {
+    let mut chars = input.chars().peekable();
+    let mut rows = Vec::new();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        match chars.next() {
+            None => break,
+            Some('(') => {}
+            Some(c) => bail!("Expected '(' but found {:?}", c),
+        }
+
+        let mut row = Vec::new();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.peek() {
+                Some('\'') | Some('"') => {
+                    let quote = chars.next().unwrap_or('\'');
+                    let mut value = String::new();
+                    loop {
+                        match chars.next() {
+                            None => bail!("Unterminated string"),
+                            Some('\\') => match chars.next() {
+                                Some('0') => value.push('\0'),
+                                Some('n') => value.push('\n'),
+                                Some('r') => value.push('\r'),
+                                Some('t') => value.push('\t'),
+                                Some('Z') => value.push('\u{1a}'),
+                                Some(c) => value.push(c),
+                                None => bail!("Unterminated string"),
+                            },
+                            Some(c) if c == quote => {
+                                if chars.next_if_eq(&quote).is_some() {
+                                    value.push(quote);
+                                } else {
+                                    break;
+                                }
+                            }
+                            Some(c) => value.push(c),
+                        }
+                    }
+                    row.push(Some(value));
+                }
+                _ => {
+                    let mut token = String::new();
+                    while let Some(c) = chars.next_if(|c| *c != ',' && *c != ')') {
+                        token.push(c);
+                    }
+                    let token = token.trim();
+                    row.push(if token.eq_ignore_ascii_case("NULL") {
+                        None
+                    } else {
+                        Some(token.to_string())
+                    });
+                }
+            }
+
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.next() {
+                Some(',') => continue,
+                Some(')') => break,
+                other => bail!("Expected ',' or ')' but found {:?}", other),
+            }
+        }
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Parse RFC 4180 CSV; `\N` and empty unquoted fields are NULL
+fn parse_csv(text: &str) -> Vec<Vec<Option<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    let finish = |field: &mut String, quoted: &mut bool| {
+        let value = std::mem::take(field);
+        let was_quoted = std::mem::replace(quoted, false);
+        if !was_quoted && (value.is_empty() || value == "\\N") {
+            None
+        } else {
+            Some(value)
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == '"' {
+                if chars.next_if_eq(&'"').is_some() {
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => {
+                in_quotes = true;
+                quoted = true;
+            }
+            ',' => row.push(finish(&mut field, &mut quoted)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(finish(&mut field, &mut quoted));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || quoted || !row.is_empty() {
+        row.push(finish(&mut field, &mut quoted));
+        rows.push(row);
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQL: &str = r#"
+-- MySQL dump 10.13
+/*!40101 SET NAMES utf8 */;
+DROP TABLE IF EXISTS `users_main`;
+CREATE TABLE `users_main` (
+  `ID` int(10) unsigned NOT NULL AUTO_INCREMENT,
+  `Username` varchar(20) NOT NULL,
+  `Email` varchar(255) NOT NULL,
+  PRIMARY KEY (`ID`)
+) ENGINE=InnoDB;
+INSERT INTO `users_main` VALUES (1,'alice','a@example.com'),(2,'o\'brien;','b@example.com');
+INSERT INTO `users_main` (`Email`, `ID`, `Username`) VALUES ('c@example.com',3,NULL);
+"#;
+
+    #[test]
+    fn test_parse_mysql_dump() {
+        let dump = Dump::from_sql(SQL).unwrap();
+        let users = dump.require("users_main").unwrap();
+
+        assert_eq!(users.columns, vec!["ID", "Username", "Email"]);
+        assert_eq!(users.rows.len(), 3);
+
+        let records: Vec<_> = users.records().collect();
+        assert_eq!(records[1].text("Username").unwrap(), "o'brien;");
+        assert_eq!(records[2].opt_int("id"), Some(3));
+        assert_eq!(records[2].get("Username"), None);
+        assert_eq!(records[2].get("Email"), Some("c@example.com"));
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let rows = parse_csv("id,name,note\n1,\"a, \"\"b\"\"\",\\N\r\n2,,\"\"\n");
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1][1].as_deref(), Some("a, \"b\""));
+        assert_eq!(rows[1][2], None);
+        assert_eq!(rows[2][1], None);
+        assert_eq!(rows[2][2].as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(
+            parse_timestamp("2012-03-04 05:06:07").unwrap().to_rfc3339(),
+            "2012-03-04T05:06:07+00:00"
+        );
+        assert_eq!(
+            parse_timestamp("1330837567").unwrap().to_rfc3339(),
+            "2012-03-04T05:06:07+00:00"
+        );
+        assert!(parse_timestamp("0000-00-00 00:00:00").is_none());
+        assert!(parse_timestamp("").is_none());
+    }
+}
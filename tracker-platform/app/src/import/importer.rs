@@ -0,0 +1,900 @@
+//! Import writer
+//!
+//! Walks the source records in dependency order (users and categories
+//! before torrents, torrents before snatches, forums before topics before
+//! posts) and writes them to the platform's tables. Every imported row is
+//! recorded in `import_id_map`, so an interrupted run picks up where it
+//! stopped and a finished run is a no-op.
+//!
+//! Torrents go through `UploadService::import_torrent`, which runs the same
+//! parsing and upload-rule checks as a normal upload. In dry-run mode the
+//! checks still run but nothing is written. The upload commits on its own,
+//! so a torrent's mapping row is written afterwards; a run that stopped in
+//! between finds the torrent by info hash and maps it then. All other
+//! records are mapped in the transaction that writes them.
+//!
+//! Password hashes are copied verbatim. Only Argon2 hashes verify at login,
+//! so users coming from a bcrypt or MD5 site need a password reset.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use community::TopicStatus;
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use torrent::{Torrent, UploadRejected, UploadRequest, UploadService};
+use uuid::Uuid;
+
+use super::source::{
+    Source, SourceCategory, SourceForum, SourceForumCategory, SourcePost, SourceSnatch,
+    SourceTopic, SourceTorrent, SourceUser,
+};
+
+/// Failures kept per entity in the report; the counts are always exact
+const MAX_REPORTED_FAILURES: usize = 1000;
+
+/// Log progress every this many records
+const PROGRESS_INTERVAL: usize = 1000;
+
+/// Kind of record being imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entity {
+    User,
+    Category,
+    Torrent,
+    Snatch,
+    ForumCategory,
+    Forum,
+    Topic,
+    Post,
+}
+
+impl Entity {
+    /// All entities in import order
+    pub const ALL: [Entity; 8] = [
+        Entity::User,
+        Entity::Category,
+        Entity::Torrent,
+        Entity::Snatch,
+        Entity::ForumCategory,
+        Entity::Forum,
+        Entity::Topic,
+        Entity::Post,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Entity::User => "user",
+            Entity::Category => "category",
+            Entity::Torrent => "torrent",
+            Entity::Snatch => "snatch",
+            Entity::ForumCategory => "forum_category",
+            Entity::Forum => "forum",
+            Entity::Topic => "topic",
+            Entity::Post => "post",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == s)
+    }
+}
+
+impl std::str::FromStr for Entity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // Accept plurals: users, categories, forum_categories
+        let s = s.trim();
+        Self::parse(s)
+            .or_else(|| {
+                s.strip_suffix("ies")
+                    .and_then(|stem| Self::parse(&format!("{}y", stem)))
+            })
+            .or_else(|| s.strip_suffix('s').and_then(Self::parse))
+            .ok_or_else(|| anyhow!("Unknown entity {:?}", s))
+    }
+}
+
+/// A record that could not be imported
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportFailure {
+    pub source_id: String,
+    pub reason: String,
+}
+
+/// Outcome for one entity
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EntityReport {
+    /// Imported in this run (or would be, in dry-run mode)
+    pub imported: usize,
+    /// Already imported by an earlier run
+    pub skipped: usize,
+    pub failed: usize,
+    pub failures: Vec<ImportFailure>,
+}
+
+/// Outcome of an import run
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub source: &'static str,
+    pub dry_run: bool,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub entities: BTreeMap<Entity, EntityReport>,
+}
+
+impl ImportReport {
+    /// One line per entity for the console
+    pub fn summary(&self) -> String {
+        let mut out = format!(
+            "{} import{}:\n",
+            self.source,
+            if self.dry_run { " (dry run)" } else { "" }
+        );
+        for (entity, report) in &self.entities {
+            out.push_str(&format!(
+                "  {:<15} {:>8} {} {:>8} skipped {:>8} failed\n",
+                entity.as_str(),
+                report.imported,
+                if self.dry_run { "would import" } else { "imported" },
+                report.skipped,
+                report.failed,
+            ));
+        }
+        out
+    }
+
+    /// Whether any record failed
+    pub fn has_failures(&self) -> bool {
+        self.entities.values().any(|r| r.failed > 0)
+    }
+
+    fn entity(&mut self, entity: Entity) -> &mut EntityReport {
+        self.entities.entry(entity).or_default()
+    }
+}
+
+/// Source-to-platform ID mapping, mirrored from `import_id_map`
+#[derive(Debug, Default)]
+struct IdMap {
+    ids: HashMap<(Entity, String), Uuid>,
+}
+
+impl IdMap {
+    fn get(&self, entity: Entity, source_id: &str) -> Option<Uuid> {
+        self.ids.get(&(entity, source_id.to_string())).copied()
+    }
+
+    fn require(&self, entity: Entity, source_id: &str) -> Result<Uuid> {
+        self.get(entity, source_id)
+            .ok_or_else(|| anyhow!("{} {} was not imported", entity.as_str(), source_id))
+    }
+
+    fn insert(&mut self, entity: Entity, source_id: &str, target_id: Uuid) {
+        self.ids.insert((entity, source_id.to_string()), target_id);
+    }
+}
+
+/// Imports a tracker dump into the platform database
+pub struct Importer {
+    pool: PgPool,
+    upload: UploadService,
+    source: Source,
+    torrent_dir: PathBuf,
+    dry_run: bool,
+    ids: IdMap,
+    report: ImportReport,
+}
+
+impl Importer {
+    pub fn new(pool: PgPool, upload: UploadService, source: Source, torrent_dir: PathBuf) -> Self {
+        let report = ImportReport {
+            source: source.kind.as_str(),
+            dry_run: false,
+            started_at: Utc::now(),
+            finished_at: None,
+            entities: BTreeMap::new(),
+        };
+
+        Self {
+            pool,
+            upload,
+            source,
+            torrent_dir,
+            dry_run: false,
+            ids: IdMap::default(),
+            report,
+        }
+    }
+
+    /// Run all checks without writing anything
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self.report.dry_run = dry_run;
+        self
+    }
+
+    /// Import the given entities, in dependency order
+    ///
+    /// Entities that are not selected must have been imported by an earlier
+    /// run for their dependents to resolve.
+    pub async fn run(mut self, entities: &[Entity]) -> Result<ImportReport> {
+        self.load_id_map().await?;
+
+        for entity in Entity::ALL {
+            if !entities.contains(&entity) {
+                continue;
+            }
+            tracing::info!("Importing {}s", entity.as_str());
+            match entity {
+                Entity::User => self.import_users().await?,
+                Entity::Category => self.import_categories().await?,
+                Entity::Torrent => self.import_torrents().await?,
+                Entity::Snatch => self.import_snatches().await?,
+                Entity::ForumCategory => self.import_forum_categories().await?,
+                Entity::Forum => self.import_forums().await?,
+                Entity::Topic => self.import_topics().await?,
+                Entity::Post => self.import_posts().await?,
+            }
+        }
+
+        if !self.dry_run {
+            self.refresh_counters().await?;
+        }
+
+        self.report.finished_at = Some(Utc::now());
+        Ok(self.report)
+    }
+
+    async fn load_id_map(&mut self) -> Result<()> {
+        let rows = sqlx::query_as::<_, (String, String, Uuid)>(
+            "SELECT entity, source_id, target_id FROM import_id_map WHERE source = $1",
+        )
+        .bind(self.source.kind.as_str())
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to load import_id_map (has migration 047 run?)")?;
+
+        for (entity, source_id, target_id) in rows {
+            if let Some(entity) = Entity::parse(&entity) {
+                self.ids.insert(entity, &source_id, target_id);
+            }
+        }
+
+        tracing::info!("Loaded {} previously imported IDs", self.ids.ids.len());
+        Ok(())
+    }
+
+    /// Whether a record was imported by an earlier run
+    fn already_imported(&mut self, entity: Entity, source_id: &str) -> bool {
+        let done = self.ids.get(entity, source_id).is_some();
+        if done {
+            self.report.entity(entity).skipped += 1;
+        }
+        done
+    }
+
+    /// Record the outcome of importing one record
+    fn finish(&mut self, entity: Entity, source_id: &str, result: Result<Uuid>) {
+        match result {
+            Ok(target_id) => {
+                self.report.entity(entity).imported += 1;
+                self.ids.insert(entity, source_id, target_id);
+            }
+            Err(e) => self.fail(entity, source_id, e),
+        }
+        self.log_progress(entity);
+    }
+
+    fn fail(&mut self, entity: Entity, source_id: &str, error: anyhow::Error) {
+        tracing::debug!("Failed to import {} {}: {:#}", entity.as_str(), source_id, error);
+
+        let report = self.report.entity(entity);
+        report.failed += 1;
+        if report.failures.len() < MAX_REPORTED_FAILURES {
+            report.failures.push(ImportFailure {
+                source_id: source_id.to_string(),
+                reason: format!("{:#}", error),
+            });
+        }
+    }
+
+    fn log_progress(&mut self, entity: Entity) {
+        let report = self.report.entity(entity);
+        let done = report.imported + report.failed;
+        if done % PROGRESS_INTERVAL == 0 {
+            tracing::info!("  {} {}s processed", done, entity.as_str());
+        }
+    }
+
+    /// Write the mapping row inside the record's transaction
+    async fn map(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        entity: Entity,
+        source_id: &str,
+        target_id: Uuid,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO import_id_map (source, entity, source_id, target_id)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(self.source.kind.as_str())
+        .bind(entity.as_str())
+        .bind(source_id)
+        .bind(target_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn import_users(&mut self) -> Result<()> {
+        let group_id: i32 = sqlx::query_scalar("SELECT id FROM user_groups WHERE name = 'member'")
+            .fetch_one(&self.pool)
+            .await
+            .context("No 'member' user group")?;
+
+        for user in self.source.users()? {
+            if self.already_imported(Entity::User, &user.id) {
+                continue;
+            }
+            let result = self.import_user(&user, group_id).await;
+            self.finish(Entity::User, &user.id, result);
+        }
+        Ok(())
+    }
+
+    async fn import_user(&self, user: &SourceUser, group_id: i32) -> Result<Uuid> {
+        // Same person on both source sites: link to the existing account
+        let existing: Option<(Uuid, String)> = sqlx::query_as(
+            "SELECT id, username FROM users WHERE LOWER(email) = LOWER($1)",
+        )
+        .bind(&user.email)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        if let Some((id, _)) = existing {
+            if !self.dry_run {
+                let mut tx = self.pool.begin().await?;
+                self.map(&mut tx, Entity::User, &user.id, id).await?;
+                tx.commit().await?;
+            }
+            return Ok(id);
+        }
+
+        let taken: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(username) = LOWER($1))",
+        )
+        .bind(&user.username)
+        .fetch_one(&self.pool)
+        .await?;
+        if taken {
+            bail!("Username {:?} is taken by a different account", user.username);
+        }
+
+        let id = Uuid::new_v4();
+        if self.dry_run {
+            return Ok(id);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO users (
+                id, username, email, password_hash, passkey, group_id,
+                is_active, is_verified, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, true, $8, NOW())
+            "#,
+        )
+        .bind(id)
+        .bind(&user.username)
+        .bind(&user.email)
+        .bind(&user.password_hash)
+        .bind(auth::generate_passkey())
+        .bind(group_id)
+        .bind(user.is_enabled)
+        .bind(user.created_at.unwrap_or_else(Utc::now))
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_statistics (user_id, uploaded, downloaded, raw_uploaded, raw_downloaded)
+            VALUES ($1, $2, $3, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE
+            SET uploaded = EXCLUDED.uploaded,
+                downloaded = EXCLUDED.downloaded,
+                raw_uploaded = EXCLUDED.raw_uploaded,
+                raw_downloaded = EXCLUDED.raw_downloaded
+            "#,
+        )
+        .bind(id)
+        .bind(user.uploaded)
+        .bind(user.downloaded)
+        .execute(&mut *tx)
+        .await?;
+
+        self.map(&mut tx, Entity::User, &user.id, id).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn import_categories(&mut self) -> Result<()> {
+        for category in self.source.categories()? {
+            if self.already_imported(Entity::Category, &category.id) {
+                continue;
+            }
+            let result = self.import_category(&category).await;
+            self.finish(Entity::Category, &category.id, result);
+        }
+        Ok(())
+    }
+
+    async fn import_category(&self, category: &SourceCategory) -> Result<Uuid> {
+        // Reuse a category with the same name rather than duplicating it
+        let existing: Option<Uuid> =
+            sqlx::query_scalar("SELECT id FROM categories WHERE LOWER(name) = LOWER($1)")
+                .bind(&category.name)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        let id = existing.unwrap_or_else(Uuid::new_v4);
+        if self.dry_run {
+            return Ok(id);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        if existing.is_none() {
+            sqlx::query(
+                r#"
+                INSERT INTO categories (id, name, slug, sort_order)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(id)
+            .bind(&category.name)
+            .bind(slugify(&category.name))
+            .bind(category.sort_order)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        self.map(&mut tx, Entity::Category, &category.id, id).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn import_torrents(&mut self) -> Result<()> {
+        for torrent in self.source.torrents(&self.torrent_dir)? {
+            if self.already_imported(Entity::Torrent, &torrent.id) {
+                continue;
+            }
+            let result = self.import_torrent(&torrent).await;
+            self.finish(Entity::Torrent, &torrent.id, result);
+        }
+        Ok(())
+    }
+
+    async fn import_torrent(&self, torrent: &SourceTorrent) -> Result<Uuid> {
+        let uploader_id = self.ids.require(Entity::User, &torrent.uploader_id)?;
+        let category_id = self.ids.require(Entity::Category, &torrent.category_id)?;
+
+        let data = std::fs::read(&torrent.file)
+            .with_context(|| format!("Failed to read {}", torrent.file.display()))?;
+
+        // Already on the platform, e.g. a run that stopped before writing
+        // the mapping row
+        let info_hash = Torrent::parse(&data)
+            .context("Failed to parse torrent file")?
+            .info_hash;
+        let existing: Option<Uuid> =
+            sqlx::query_scalar("SELECT id FROM torrents WHERE info_hash = $1")
+                .bind(&info_hash)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        if let Some(id) = existing {
+            if !self.dry_run {
+                let mut tx = self.pool.begin().await?;
+                self.map(&mut tx, Entity::Torrent, &torrent.id, id).await?;
+                tx.commit().await?;
+            }
+            return Ok(id);
+        }
+
+        let request = UploadRequest {
+            // Fall back to the .torrent name when the source name would
+            // fail validation
+            name: torrent
+                .name
+                .clone()
+                .filter(|n| (3..=255).contains(&n.chars().count())),
+            description: torrent.description.clone(),
+            category_id,
+            tags: Some(torrent.tags.clone()).filter(|t| !t.is_empty()),
+            nfo_content: None,
+            rip_logs: None,
+            cue_sheets: None,
+            mediainfo: None,
+            tmdb_id: torrent.tmdb_id,
+            imdb_id: torrent.imdb_id.clone(),
+            tvdb_id: None,
+            igdb_id: None,
+            poster_url: None,
+            screenshots: None,
+            year: torrent.year,
+            anonymous: Some(torrent.anonymous),
+        };
+
+        let result = if self.dry_run {
+            self.upload
+                .check_torrent(&data, &request)
+                .await
+                .map(|_| Uuid::new_v4())
+        } else {
+            self.upload
+                .import_torrent(
+                    uploader_id,
+                    data,
+                    request,
+                    torrent.created_at.unwrap_or_else(Utc::now),
+                )
+                .await
+                .map(|response| response.torrent_id)
+        };
+
+        let id = result.map_err(|e| match e.downcast::<UploadRejected>() {
+            Ok(rejected) => anyhow!(
+                "Rejected by upload rules: {}",
+                rejected
+                    .violations
+                    .iter()
+                    .filter(|v| v.is_error())
+                    .map(|v| v.message.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            Err(e) => e,
+        })?;
+
+        if !self.dry_run {
+            let mut tx = self.pool.begin().await?;
+            self.map(&mut tx, Entity::Torrent, &torrent.id, id).await?;
+            tx.commit().await?;
+        }
+
+        Ok(id)
+    }
+
+    async fn import_snatches(&mut self) -> Result<()> {
+        // Snatches have no ID of their own and the (user, torrent) primary
+        // key already makes them idempotent, so they skip the mapping table
+        for snatch in self.source.snatches()? {
+            let source_id = format!("{}:{}", snatch.user_id, snatch.torrent_id);
+            match self.import_snatch(&snatch).await {
+                Ok(true) => self.report.entity(Entity::Snatch).imported += 1,
+                Ok(false) => {
+                    self.report.entity(Entity::Snatch).skipped += 1;
+                    continue;
+                }
+                Err(e) => self.fail(Entity::Snatch, &source_id, e),
+            }
+            self.log_progress(Entity::Snatch);
+        }
+        Ok(())
+    }
+
+    /// Returns whether a new snatch row was written
+    async fn import_snatch(&self, snatch: &SourceSnatch) -> Result<bool> {
+        let user_id = self.ids.require(Entity::User, &snatch.user_id)?;
+        let torrent_id = self.ids.require(Entity::Torrent, &snatch.torrent_id)?;
+
+        if self.dry_run {
+            return Ok(true);
+        }
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO snatched (
+                user_id, torrent_id, uploaded, downloaded, seed_time, snatched_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (user_id, torrent_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(torrent_id)
+        .bind(snatch.uploaded)
+        .bind(snatch.downloaded)
+        .bind(snatch.seed_time)
+        .bind(snatch.snatched_at.unwrap_or_else(Utc::now))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn import_forum_categories(&mut self) -> Result<()> {
+        for category in self.source.forum_categories()? {
+            if self.already_imported(Entity::ForumCategory, &category.id) {
+                continue;
+            }
+            let result = self.import_forum_category(&category).await;
+            self.finish(Entity::ForumCategory, &category.id, result);
+        }
+        Ok(())
+    }
+
+    async fn import_forum_category(&self, category: &SourceForumCategory) -> Result<Uuid> {
+        let id = Uuid::new_v4();
+        if self.dry_run {
+            return Ok(id);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO forum_categories (id, name, description, sort_order, created_at, updated_at)
+            VALUES ($1, $2, NULL, $3, NOW(), NOW())
+            "#,
+        )
+        .bind(id)
+        .bind(&category.name)
+        .bind(category.sort_order)
+        .execute(&mut *tx)
+        .await?;
+
+        self.map(&mut tx, Entity::ForumCategory, &category.id, id).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn import_forums(&mut self) -> Result<()> {
+        for forum in self.source.forums()? {
+            if self.already_imported(Entity::Forum, &forum.id) {
+                continue;
+            }
+            let result = self.import_forum(&forum).await;
+            self.finish(Entity::Forum, &forum.id, result);
+        }
+        Ok(())
+    }
+
+    async fn import_forum(&self, forum: &SourceForum) -> Result<Uuid> {
+        let category_id = self.ids.require(Entity::ForumCategory, &forum.category_id)?;
+
+        let id = Uuid::new_v4();
+        if self.dry_run {
+            return Ok(id);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        // Permissions start open; staff re-apply class limits after import
+        sqlx::query(
+            r#"
+            INSERT INTO forums (
+                id, category_id, parent_id, name, description, icon, sort_order,
+                min_class_read, min_class_write, min_class_create,
+                topic_count, post_count, is_locked, auto_lock_topics,
+                created_at, updated_at
+            )
+            VALUES ($1, $2, NULL, $3, $4, NULL, $5, 0, 0, 0, 0, 0, false, false, NOW(), NOW())
+            "#,
+        )
+        .bind(id)
+        .bind(category_id)
+        .bind(&forum.name)
+        .bind(&forum.description)
+        .bind(forum.sort_order)
+        .execute(&mut *tx)
+        .await?;
+
+        self.map(&mut tx, Entity::Forum, &forum.id, id).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn import_topics(&mut self) -> Result<()> {
+        for topic in self.source.topics()? {
+            if self.already_imported(Entity::Topic, &topic.id) {
+                continue;
+            }
+            let result = self.import_topic(&topic).await;
+            self.finish(Entity::Topic, &topic.id, result);
+        }
+        Ok(())
+    }
+
+    async fn import_topic(&self, topic: &SourceTopic) -> Result<Uuid> {
+        let forum_id = self.ids.require(Entity::Forum, &topic.forum_id)?;
+        let user_id = self.ids.require(Entity::User, &topic.user_id)?;
+
+        let id = Uuid::new_v4();
+        if self.dry_run {
+            return Ok(id);
+        }
+
+        let created_at = topic.created_at.unwrap_or_else(Utc::now);
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO topics (
+                id, forum_id, user_id, title, status, is_pinned, is_locked, is_sticky,
+                view_count, post_count, has_poll, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, false, 0, 0, false, $8, $8)
+            "#,
+        )
+        .bind(id)
+        .bind(forum_id)
+        .bind(user_id)
+        .bind(&topic.title)
+        .bind(if topic.is_locked {
+            TopicStatus::Locked
+        } else {
+            TopicStatus::Open
+        })
+        .bind(topic.is_pinned)
+        .bind(topic.is_locked)
+        .bind(created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        self.map(&mut tx, Entity::Topic, &topic.id, id).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn import_posts(&mut self) -> Result<()> {
+        for post in self.source.posts()? {
+            if self.already_imported(Entity::Post, &post.id) {
+                continue;
+            }
+            let result = self.import_post(&post).await;
+            self.finish(Entity::Post, &post.id, result);
+        }
+        Ok(())
+    }
+
+    async fn import_post(&self, post: &SourcePost) -> Result<Uuid> {
+        let topic_id = self.ids.require(Entity::Topic, &post.topic_id)?;
+        let user_id = self.ids.require(Entity::User, &post.user_id)?;
+
+        let id = Uuid::new_v4();
+        if self.dry_run {
+            return Ok(id);
+        }
+
+        let created_at = post.created_at.unwrap_or_else(Utc::now);
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO posts (id, topic_id, user_id, content, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            "#,
+        )
+        .bind(id)
+        .bind(topic_id)
+        .bind(user_id)
+        .bind(&post.content)
+        .bind(created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        self.map(&mut tx, Entity::Post, &post.id, id).await?;
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    /// Recompute denormalized counters on everything this source touched
+    async fn refresh_counters(&self) -> Result<()> {
+        let source = self.source.kind.as_str();
+
+        sqlx::query(
+            r#"
+            UPDATE torrents t
+            SET times_completed = (SELECT COUNT(*) FROM snatched s WHERE s.torrent_id = t.id)
+            WHERE t.id IN (
+                SELECT target_id FROM import_id_map WHERE source = $1 AND entity = 'torrent'
+            )
+            "#,
+        )
+        .bind(source)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE topics t
+            SET post_count = s.post_count,
+                last_post_id = s.last_post_id,
+                last_post_at = s.last_post_at,
+                last_poster_id = s.last_poster_id
+            FROM (
+                SELECT DISTINCT ON (topic_id)
+                    topic_id,
+                    id AS last_post_id,
+                    created_at AS last_post_at,
+                    user_id AS last_poster_id,
+                    COUNT(*) OVER (PARTITION BY topic_id) AS post_count
+                FROM posts
+                ORDER BY topic_id, created_at DESC
+            ) s
+            WHERE t.id = s.topic_id
+              AND t.id IN (
+                  SELECT target_id FROM import_id_map WHERE source = $1 AND entity = 'topic'
+              )
+            "#,
+        )
+        .bind(source)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE forums f
+            SET topic_count = (SELECT COUNT(*) FROM topics t WHERE t.forum_id = f.id),
+                post_count = (
+                    SELECT COUNT(*) FROM posts p
+                    JOIN topics t ON t.id = p.topic_id
+                    WHERE t.forum_id = f.id
+                )
+            WHERE f.id IN (
+                SELECT target_id FROM import_id_map WHERE source = $1 AND entity = 'forum'
+            )
+            "#,
+        )
+        .bind(source)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// URL slug for an imported category name
+fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entity_from_str() {
+        assert_eq!("users".parse::<Entity>().unwrap(), Entity::User);
+        assert_eq!("forum_categories".parse::<Entity>().unwrap(), Entity::ForumCategory);
+        assert_eq!("snatch".parse::<Entity>().unwrap(), Entity::Snatch);
+        assert!("peers".parse::<Entity>().is_err());
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("E-Learning Videos"), "e-learning-videos");
+        assert_eq!(slugify("  TV / HD "), "tv-hd");
+    }
+}
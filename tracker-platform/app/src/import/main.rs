@@ -0,0 +1,143 @@
+//! Offline importer for Gazelle and UNIT3D databases
+//!
+//! Reads a dump of the source site (a `mysqldump` file or a directory of CSV
+//! exports) plus its .torrent files and imports users, categories,
+//! torrents, snatches and forum content. Runs are resumable: re-running the
+//! same import only picks up records that are not in `import_id_map` yet.
+//!
+//! ```text
+//! tracker-import --source gazelle --dump site.sql --torrents ./torrents \
+//!     [--only users,torrents] [--dry-run] [--report report.json]
+//! ```
+//!
+//! The database comes from `DATABASE_URL`.
+
+mod dump;
+mod importer;
+mod source;
+
+use anyhow::{anyhow, bail, Context, Result};
+use sqlx::postgres::PgPoolOptions;
+use std::path::PathBuf;
+use torrent::{moderation::AutoApprovalRules, UploadService};
+
+use dump::Dump;
+use importer::{Entity, Importer};
+use source::{Source, SourceKind};
+
+const USAGE: &str = "\
+Usage: tracker-import --source <gazelle|unit3d> --dump <file.sql|csv-dir> --torrents <dir> [options]
+
+Options:
+    --only <entities>   Comma-separated subset: users, categories, torrents,
+                        snatches, forum_categories, forums, topics, posts
+    --dry-run           Run every check but write nothing
+    --report <file>     Write a JSON report of the run
+    -h, --help          Show this help";
+
+/// Command line arguments
+#[derive(Debug)]
+struct Args {
+    source: SourceKind,
+    dump: PathBuf,
+    torrents: PathBuf,
+    entities: Vec<Entity>,
+    dry_run: bool,
+    report: Option<PathBuf>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>> {
+        let mut source = None;
+        let mut dump = None;
+        let mut torrents = None;
+        let mut entities = Entity::ALL.to_vec();
+        let mut dry_run = false;
+        let mut report = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--source" => source = Some(value()?.parse()?),
+                "--dump" => dump = Some(PathBuf::from(value()?)),
+                "--torrents" => torrents = Some(PathBuf::from(value()?)),
+                "--only" => {
+                    entities = value()?
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<_>>()?;
+                }
+                "--dry-run" => dry_run = true,
+                "--report" => report = Some(PathBuf::from(value()?)),
+                "-h" | "--help" => return Ok(None),
+                other => bail!("Unknown argument {:?}", other),
+            }
+        }
+
+        Ok(Some(Self {
+            source: source.ok_or_else(|| anyhow!("--source is required"))?,
+            dump: dump.ok_or_else(|| anyhow!("--dump is required"))?,
+            torrents: torrents.ok_or_else(|| anyhow!("--torrents is required"))?,
+            entities,
+            dry_run,
+            report,
+        }))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "info".into()),
+        )
+        .init();
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let database_url = std::env::var("DATABASE_URL").context("DATABASE_URL is not set")?;
+    let pool = PgPoolOptions::new()
+        .max_connections(4)
+        .connect(&database_url)
+        .await
+        .context("Failed to connect to database")?;
+
+    tracing::info!("Loading dump from {}", args.dump.display());
+    let dump = Dump::load(&args.dump)?;
+    let source = Source::new(args.source, dump);
+
+    let upload = UploadService::new(pool.clone(), AutoApprovalRules::default());
+    let report = Importer::new(pool, upload, source, args.torrents)
+        .with_dry_run(args.dry_run)
+        .run(&args.entities)
+        .await?;
+
+    print!("{}", report.summary());
+
+    if let Some(path) = args.report {
+        std::fs::write(&path, serde_json::to_vec_pretty(&report)?)
+            .with_context(|| format!("Failed to write report to {}", path.display()))?;
+        println!("Report written to {}", path.display());
+    }
+
+    if report.has_failures() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,533 @@
+//! Source schemas
+//!
+//! Maps the tables of a Gazelle or UNIT3D dump onto source-neutral records.
+//! Only the columns the importer needs are read; everything else in the dump
+//! is ignored. Source IDs are kept as strings so they can be recorded in the
+//! ID mapping table as-is.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::dump::{Dump, Record};
+
+/// Tracker software the dump comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Gazelle,
+    Unit3d,
+}
+
+impl SourceKind {
+    /// Name stored in the ID mapping table
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceKind::Gazelle => "gazelle",
+            SourceKind::Unit3d => "unit3d",
+        }
+    }
+}
+
+impl std::str::FromStr for SourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "gazelle" => Ok(SourceKind::Gazelle),
+            "unit3d" => Ok(SourceKind::Unit3d),
+            other => bail!("Unknown source {:?} (expected gazelle or unit3d)", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceUser {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub password_hash: String,
+    pub uploaded: i64,
+    pub downloaded: i64,
+    pub is_enabled: bool,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceCategory {
+    pub id: String,
+    pub name: String,
+    pub sort_order: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceTorrent {
+    pub id: String,
+    pub uploader_id: String,
+    pub category_id: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub year: Option<i32>,
+    pub imdb_id: Option<String>,
+    pub tmdb_id: Option<i64>,
+    pub anonymous: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    /// Location of the .torrent file under the torrents directory
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceSnatch {
+    pub user_id: String,
+    pub torrent_id: String,
+    pub uploaded: i64,
+    pub downloaded: i64,
+    pub seed_time: i64,
+    pub snatched_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceForumCategory {
+    pub id: String,
+    pub name: String,
+    pub sort_order: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceForum {
+    pub id: String,
+    pub category_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub sort_order: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceTopic {
+    pub id: String,
+    pub forum_id: String,
+    pub user_id: String,
+    pub title: String,
+    pub is_locked: bool,
+    pub is_pinned: bool,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourcePost {
+    pub id: String,
+    pub topic_id: String,
+    pub user_id: String,
+    pub content: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Gazelle keeps categories in its config file rather than the database
+const GAZELLE_CATEGORIES: &[&str] = &[
+    "Music",
+    "Applications",
+    "E-Books",
+    "Audiobooks",
+    "E-Learning Videos",
+    "Comedy",
+    "Comics",
+];
+
+/// A dump read through the schema of its source tracker
+pub struct Source {
+    pub kind: SourceKind,
+    dump: Dump,
+}
+
+impl Source {
+    pub fn new(kind: SourceKind, dump: Dump) -> Self {
+        Self { kind, dump }
+    }
+
+    pub fn users(&self) -> Result<Vec<SourceUser>> {
+        match self.kind {
+            SourceKind::Gazelle => {
+                // Join dates live in users_info
+                let joined: HashMap<String, Option<DateTime<Utc>>> = self
+                    .dump
+                    .table("users_info")
+                    .map(|t| {
+                        t.records()
+                            .filter_map(|r| Some((r.get("UserID")?.to_string(), r.timestamp("JoinDate"))))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                self.dump
+                    .require("users_main")?
+                    .records()
+                    .map(|r| {
+                        let id = r.text("ID")?;
+                        Ok(SourceUser {
+                            username: r.text("Username")?,
+                            email: r.text("Email")?,
+                            password_hash: r.opt_text("PassHash").unwrap_or_default(),
+                            uploaded: r.opt_int("Uploaded").unwrap_or(0),
+                            downloaded: r.opt_int("Downloaded").unwrap_or(0),
+                            is_enabled: r.get("Enabled").unwrap_or("1") == "1",
+                            created_at: joined.get(&id).copied().flatten(),
+                            id,
+                        })
+                    })
+                    .collect()
+            }
+            SourceKind::Unit3d => self
+                .dump
+                .require("users")?
+                .records()
+                .map(|r| {
+                    Ok(SourceUser {
+                        id: r.text("id")?,
+                        username: r.text("username")?,
+                        email: r.text("email")?,
+                        password_hash: r.opt_text("password").unwrap_or_default(),
+                        uploaded: r.opt_int("uploaded").unwrap_or(0),
+                        downloaded: r.opt_int("downloaded").unwrap_or(0),
+                        is_enabled: r.get("deleted_at").is_none(),
+                        created_at: r.timestamp("created_at"),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn categories(&self) -> Result<Vec<SourceCategory>> {
+        match self.kind {
+            SourceKind::Gazelle => Ok(GAZELLE_CATEGORIES
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| SourceCategory {
+                    id: (idx + 1).to_string(),
+                    name: name.to_string(),
+                    sort_order: idx as i32,
+                })
+                .collect()),
+            SourceKind::Unit3d => self
+                .dump
+                .require("categories")?
+                .records()
+                .map(|r| {
+                    Ok(SourceCategory {
+                        id: r.text("id")?,
+                        name: r.text("name")?,
+                        sort_order: r.opt_int("position").unwrap_or(0) as i32,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn torrents(&self, torrent_dir: &Path) -> Result<Vec<SourceTorrent>> {
+        match self.kind {
+            SourceKind::Gazelle => {
+                // Names, tags and descriptions are per group in Gazelle
+                let groups: HashMap<String, Record<'_>> = self
+                    .dump
+                    .require("torrents_group")?
+                    .records()
+                    .filter_map(|r| Some((r.get("ID")?.to_string(), r)))
+                    .collect();
+
+                self.dump
+                    .require("torrents")?
+                    .records()
+                    .map(|r| {
+                        let id = r.text("ID")?;
+                        let group = r.get("GroupID").and_then(|g| groups.get(g));
+                        Ok(SourceTorrent {
+                            uploader_id: r.text("UserID")?,
+                            category_id: group
+                                .and_then(|g| g.get("CategoryID"))
+                                .unwrap_or("1")
+                                .to_string(),
+                            name: group.and_then(|g| g.opt_text("Name")),
+                            description: r
+                                .opt_text("Description")
+                                .or_else(|| group.and_then(|g| g.opt_text("WikiBody"))),
+                            tags: group
+                                .and_then(|g| g.get("TagList"))
+                                .map(gazelle_tags)
+                                .unwrap_or_default(),
+                            year: group.and_then(|g| g.opt_int("Year")).map(|y| y as i32),
+                            imdb_id: None,
+                            tmdb_id: None,
+                            anonymous: r.flag("Anonymous"),
+                            created_at: r.timestamp("Time"),
+                            file: torrent_dir.join(format!("{}.torrent", id)),
+                            id,
+                        })
+                    })
+                    .collect()
+            }
+            SourceKind::Unit3d => {
+                // Tags are stored as keywords, one row per tag
+                let mut keywords: HashMap<String, Vec<String>> = HashMap::new();
+                if let Some(table) = self.dump.table("keywords") {
+                    for r in table.records() {
+                        if let (Some(torrent_id), Some(name)) = (r.get("torrent_id"), r.opt_text("name")) {
+                            keywords.entry(torrent_id.to_string()).or_default().push(name);
+                        }
+                    }
+                }
+
+                self.dump
+                    .require("torrents")?
+                    .records()
+                    .map(|r| {
+                        let id = r.text("id")?;
+                        let file_name = r
+                            .opt_text("file_name")
+                            .unwrap_or_else(|| format!("{}.torrent", id));
+                        Ok(SourceTorrent {
+                            uploader_id: r.text("user_id")?,
+                            category_id: r.text("category_id")?,
+                            name: r.opt_text("name"),
+                            description: r.opt_text("description"),
+                            tags: keywords.remove(&id).unwrap_or_default(),
+                            year: r.opt_int("release_year").map(|y| y as i32),
+                            imdb_id: r
+                                .opt_int("imdb")
+                                .filter(|id| *id > 0)
+                                .map(|id| format!("tt{:07}", id)),
+                            tmdb_id: r.opt_int("tmdb").filter(|id| *id > 0),
+                            anonymous: r.flag("anon"),
+                            created_at: r.timestamp("created_at"),
+                            file: torrent_dir.join(file_name),
+                            id,
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub fn snatches(&self) -> Result<Vec<SourceSnatch>> {
+        match self.kind {
+            SourceKind::Gazelle => {
+                // Per-user transfer totals live in xbt_files_users
+                let transfer: HashMap<(String, String), Record<'_>> = self
+                    .dump
+                    .table("xbt_files_users")
+                    .map(|t| {
+                        t.records()
+                            .filter_map(|r| Some(((r.get("uid")?.to_string(), r.get("fid")?.to_string()), r)))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                self.dump
+                    .require("xbt_snatched")?
+                    .records()
+                    .map(|r| {
+                        let user_id = r.text("uid")?;
+                        let torrent_id = r.text("fid")?;
+                        let stats = transfer.get(&(user_id.clone(), torrent_id.clone()));
+                        Ok(SourceSnatch {
+                            uploaded: stats.and_then(|s| s.opt_int("uploaded")).unwrap_or(0),
+                            downloaded: stats.and_then(|s| s.opt_int("downloaded")).unwrap_or(0),
+                            seed_time: stats.and_then(|s| s.opt_int("timespent")).unwrap_or(0),
+                            snatched_at: r.timestamp("tstamp"),
+                            user_id,
+                            torrent_id,
+                        })
+                    })
+                    .collect()
+            }
+            SourceKind::Unit3d => self
+                .dump
+                .require("history")?
+                .records()
+                .filter(|r| r.get("completed_at").is_some())
+                .map(|r| {
+                    Ok(SourceSnatch {
+                        user_id: r.text("user_id")?,
+                        torrent_id: r.text("torrent_id")?,
+                        uploaded: r.opt_int("actual_uploaded").unwrap_or(0),
+                        downloaded: r.opt_int("actual_downloaded").unwrap_or(0),
+                        seed_time: r.opt_int("seedtime").unwrap_or(0),
+                        snatched_at: r.timestamp("completed_at"),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn forum_categories(&self) -> Result<Vec<SourceForumCategory>> {
+        let (table, sort) = match self.kind {
+            SourceKind::Gazelle => ("forums_categories", "Sort"),
+            SourceKind::Unit3d => ("forum_categories", "position"),
+        };
+
+        if let Some(table) = self.dump.table(table) {
+            return table
+                .records()
+                .map(|r| {
+                    Ok(SourceForumCategory {
+                        id: r.text("ID")?,
+                        name: r.text("Name")?,
+                        sort_order: r.opt_int(sort).unwrap_or(0) as i32,
+                    })
+                })
+                .collect();
+        }
+
+        // Older UNIT3D releases use top-level forums as categories
+        match self.kind {
+            SourceKind::Unit3d => Ok(self
+                .dump
+                .require("forums")?
+                .records()
+                .filter(|r| is_unit3d_category(r))
+                .map(|r| {
+                    Ok(SourceForumCategory {
+                        id: r.text("id")?,
+                        name: r.text("name")?,
+                        sort_order: r.opt_int("position").unwrap_or(0) as i32,
+                    })
+                })
+                .collect::<Result<_>>()?),
+            SourceKind::Gazelle => Ok(Vec::new()),
+        }
+    }
+
+    pub fn forums(&self) -> Result<Vec<SourceForum>> {
+        let (category, sort) = match self.kind {
+            SourceKind::Gazelle => ("CategoryID", "Sort"),
+            SourceKind::Unit3d => {
+                if self.dump.table("forum_categories").is_some() {
+                    ("forum_category_id", "position")
+                } else {
+                    ("parent_id", "position")
+                }
+            }
+        };
+
+        // Skip the top-level forums already imported as categories
+        let nested_only = category == "parent_id";
+
+        self.dump
+            .require("forums")?
+            .records()
+            .filter(|r| !nested_only || !is_unit3d_category(r))
+            .map(|r| {
+                Ok(SourceForum {
+                    id: r.text("ID")?,
+                    category_id: r.text(category)?,
+                    name: r.text("Name")?,
+                    description: r.opt_text("Description"),
+                    sort_order: r.opt_int(sort).unwrap_or(0) as i32,
+                })
+            })
+            .collect()
+    }
+
+    pub fn topics(&self) -> Result<Vec<SourceTopic>> {
+        match self.kind {
+            SourceKind::Gazelle => self
+                .dump
+                .require("forums_topics")?
+                .records()
+                .map(|r| {
+                    Ok(SourceTopic {
+                        id: r.text("ID")?,
+                        forum_id: r.text("ForumID")?,
+                        user_id: r.text("AuthorID")?,
+                        title: r.text("Title")?,
+                        is_locked: r.flag("IsLocked"),
+                        is_pinned: r.flag("IsSticky"),
+                        created_at: r.timestamp("CreatedTime"),
+                    })
+                })
+                .collect(),
+            SourceKind::Unit3d => self
+                .dump
+                .require("topics")?
+                .records()
+                .map(|r| {
+                    Ok(SourceTopic {
+                        id: r.text("id")?,
+                        forum_id: r.text("forum_id")?,
+                        user_id: r.text("first_post_user_id")?,
+                        title: r.text("name")?,
+                        is_locked: r.get("state") == Some("close"),
+                        is_pinned: r.flag("pinned"),
+                        created_at: r.timestamp("created_at"),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn posts(&self) -> Result<Vec<SourcePost>> {
+        let (table, topic, user, body, time) = match self.kind {
+            SourceKind::Gazelle => ("forums_posts", "TopicID", "AuthorID", "Body", "AddedTime"),
+            SourceKind::Unit3d => ("posts", "topic_id", "user_id", "content", "created_at"),
+        };
+
+        let mut posts = self
+            .dump
+            .require(table)?
+            .records()
+            .map(|r| {
+                Ok(SourcePost {
+                    id: r.text("ID")?,
+                    topic_id: r.text(topic)?,
+                    user_id: r.text(user)?,
+                    content: r.text(body)?,
+                    created_at: r.timestamp(time),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Oldest first so each topic's first post is imported first
+        posts.sort_by_key(|p| p.created_at);
+        Ok(posts)
+    }
+}
+
+/// Top-level UNIT3D forums (no parent) act as categories
+fn is_unit3d_category(record: &Record<'_>) -> bool {
+    record.opt_int("parent_id").unwrap_or(0) == 0
+}
+
+/// Gazelle stores a group's tags as one space-separated list
+fn gazelle_tags(list: &str) -> Vec<String> {
+    list.split_whitespace().map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gazelle_tags() {
+        assert_eq!(
+            gazelle_tags("rock  hip.hop electronic"),
+            vec!["rock", "hip.hop", "electronic"]
+        );
+    }
+
+    #[test]
+    fn test_unit3d_torrents() {
+        let dump = Dump::from_sql(
+            "INSERT INTO `torrents` (`id`,`name`,`user_id`,`category_id`,`file_name`,`imdb`,`anon`,`created_at`) \
+             VALUES (7,'Some.Movie.2020.1080p',3,1,'abc.torrent',1234567,1,'2020-01-02 03:04:05');\n\
+             INSERT INTO `keywords` (`id`,`name`,`torrent_id`) VALUES (1,'drama',7),(2,'thriller',7);",
+        )
+        .unwrap();
+        let source = Source::new(SourceKind::Unit3d, dump);
+        let torrents = source.torrents(Path::new("/dumps/torrents")).unwrap();
+
+        assert_eq!(torrents.len(), 1);
+        assert_eq!(torrents[0].imdb_id.as_deref(), Some("tt1234567"));
+        assert_eq!(torrents[0].tags, vec!["drama", "thriller"]);
+        assert_eq!(torrents[0].file, Path::new("/dumps/torrents/abc.torrent"));
+        assert!(torrents[0].anonymous);
+    }
+}
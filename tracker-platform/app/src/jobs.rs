@@ -0,0 +1,464 @@
+use crate::config::EmailConfig;
+use crate::state::AppState;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use user::{
+    AchievementService, BonusAccrualJob, ClassAction, ClassPromotionJob, ReseedService,
+    SeedingTorrent, StatisticsService,
+};
+
+/// How often open reseed requests are checked for revival or expiry
+const RESEED_RESOLVE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often users with new snatches are checked for download achievements
+const SNATCH_ACHIEVEMENT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often matured request fills are paid out and old requests expired
+const REQUEST_ESCROW_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often subscribers are emailed a digest of new posts in their topics
+const TOPIC_DIGEST_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often overdue moderation queue items are escalated
+///
+/// Also how often expired claims are released, so keep it well under the
+/// claim length.
+const MODERATION_ESCALATION_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often JWT signing keys are reloaded and rotated if due
+///
+/// Well inside the publish-ahead window, so every instance learns about a
+/// new key before it starts signing.
+const JWT_KEY_SYNC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Spawn the periodic background jobs
+pub fn spawn_background_jobs(state: AppState) {
+    if state.config.bonus.accrual_enabled {
+        tokio::spawn(run_bonus_accrual(state.clone()));
+    }
+
+    if state.config.classes.promotion_enabled {
+        tokio::spawn(run_class_promotion(state.clone()));
+    }
+
+    if state.config.email.dispatch_enabled {
+        tokio::spawn(run_email_dispatch(state.clone()));
+        tokio::spawn(run_topic_digests(state.clone()));
+    }
+
+    // Flush the tracker's batched peer, torrent and snatch writes
+    tokio::spawn(Arc::clone(state.tracker_service.batch_writer()).run());
+
+    // Refresh scrape visibility and rebuild the full scrape
+    let tracker = &state.tracker_service;
+    tokio::spawn(Arc::clone(tracker.scrape_policy()).run(
+        Arc::clone(tracker.db_pool()),
+        Arc::clone(tracker.peer_manager()),
+    ));
+
+    tokio::spawn(run_jwt_key_rotation(state.clone()));
+    tokio::spawn(run_moderation_escalation(state.clone()));
+    tokio::spawn(run_request_escrow(state.clone()));
+    tokio::spawn(run_snatch_achievements(state.clone()));
+    tokio::spawn(run_reseed_resolution(state));
+}
+
+/// Reload JWT signing keys and rotate them on schedule
+async fn run_jwt_key_rotation(state: AppState) {
+    let store = auth::KeyStore::new(state.db.clone());
+    let policy = state.config.auth.key_rotation_policy();
+    let mut interval = time::interval(JWT_KEY_SYNC_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = store.sync(&state.jwt_keys, &policy).await {
+            tracing::error!("JWT key rotation failed: {}", e);
+        }
+    }
+}
+
+/// Credit seeding bonus from the tracker's live swarms once per interval
+async fn run_bonus_accrual(state: AppState) {
+    let config = &state.config.bonus;
+    let job = BonusAccrualJob::with_interval(
+        state.db.clone(),
+        Duration::from_secs(config.accrual_interval_secs),
+    );
+
+    tracing::info!(
+        "Starting seeding bonus accrual every {}s{}",
+        config.accrual_interval_secs,
+        if config.accrual_dry_run {
+            " (dry run)"
+        } else {
+            ""
+        }
+    );
+
+    let mut interval = time::interval(job.interval());
+
+    loop {
+        interval.tick().await;
+
+        let Some(swarms) = seeding_swarms(&state).await else {
+            continue;
+        };
+
+        match job
+            .run(&swarms, chrono::Utc::now(), config.accrual_dry_run)
+            .await
+        {
+            Ok(report) if report.dry_run => {
+                for payout in &report.payouts {
+                    tracing::info!(
+                        "Dry run: would award {:.2} points to {} for {} torrent(s)",
+                        payout.amount,
+                        payout.user_id,
+                        payout.torrents_seeded
+                    );
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Seeding bonus accrual failed: {}", e),
+        }
+    }
+}
+
+/// Take the tracker's live seeding state
+///
+/// The swarms are filled by `/tracker/announce`, so only peers that
+/// announced with a passkey are credited, and the first run after a restart
+/// only sees clients that have announced since. Returns `None`, after
+/// logging, if the snapshot task failed.
+async fn seeding_swarms(state: &AppState) -> Option<Vec<SeedingTorrent>> {
+    // Walking every swarm is CPU-bound; keep it off the async workers
+    let tracker = state.tracker_service.clone();
+    let snapshot = match tokio::task::spawn_blocking(move || {
+        tracker.peer_manager().seeding_snapshot()
+    })
+    .await
+    {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            tracing::error!("Seeding snapshot task failed: {}", e);
+            return None;
+        }
+    };
+
+    Some(
+        snapshot
+            .into_iter()
+            .map(|swarm| SeedingTorrent {
+                info_hash: swarm.info_hash.to_hex(),
+                seeders: swarm.seeders as i32,
+                leechers: swarm.leechers as i32,
+                seeding_users: swarm.seeding_users,
+            })
+            .collect(),
+    )
+}
+
+/// Promote and demote users between classes once per interval
+async fn run_class_promotion(state: AppState) {
+    let config = &state.config.classes;
+    let Some(system_user_id) = config.system_user_id else {
+        tracing::error!("Class promotion is enabled but classes.system_user_id is not set");
+        return;
+    };
+
+    let mut job = ClassPromotionJob::new(state.db.clone(), system_user_id);
+    match redis::Client::open(state.config.redis.url.as_str()) {
+        Ok(client) => {
+            let resolver = auth::PermissionResolver::new(state.db.clone(), client);
+            job = job.with_permission_resolver(Arc::new(resolver));
+        }
+        Err(e) => tracing::warn!("Class changes will wait for the permission cache: {}", e),
+    }
+
+    tracing::info!(
+        "Starting class promotion every {}s{}",
+        config.promotion_interval_secs,
+        if config.promotion_dry_run {
+            " (dry run)"
+        } else {
+            ""
+        }
+    );
+
+    let mut interval = time::interval(Duration::from_secs(config.promotion_interval_secs));
+
+    loop {
+        interval.tick().await;
+
+        match job.run(chrono::Utc::now(), config.promotion_dry_run).await {
+            Ok(report) if report.dry_run => {
+                for change in &report.changes {
+                    let action = match &change.action {
+                        ClassAction::Promote { .. } => "promote",
+                        ClassAction::Warn { .. } => "warn of demotion",
+                        ClassAction::Demote { .. } => "demote",
+                        ClassAction::Recovered => "cancel the demotion of",
+                    };
+                    tracing::info!(
+                        "Dry run: would {} {} ({:?})",
+                        action,
+                        change.username,
+                        change.action
+                    );
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Class promotion failed: {}", e),
+        }
+    }
+}
+
+/// Email service with the configured templates
+fn email_service(state: &AppState, config: &EmailConfig) -> auth::EmailService {
+    let service = auth::EmailService::new(state.db.clone(), config.service_config());
+    let Some(dir) = &config.templates_dir else {
+        return service;
+    };
+
+    let mut templates = auth::TemplateSet::builtin();
+    match templates.load_dir(dir) {
+        Ok(count) => {
+            tracing::info!("Loaded {} email templates from {}", count, dir.display());
+            service.with_templates(templates)
+        }
+        Err(e) => {
+            tracing::error!("Failed to load email templates, using built-in ones: {}", e);
+            service
+        }
+    }
+}
+
+/// Deliver queued transactional email
+async fn run_email_dispatch(state: AppState) {
+    let config = &state.config.email;
+    let service = email_service(&state, config);
+
+    let transport: Arc<dyn auth::EmailTransport> = if config.transport == "file" {
+        Arc::new(auth::FileTransport::new(&config.file_dir))
+    } else {
+        let security = auth::SmtpSecurity::parse(&config.smtp_security)
+            .unwrap_or(auth::SmtpSecurity::StartTls);
+        let credentials = config
+            .smtp_username
+            .clone()
+            .zip(config.smtp_password.clone());
+        match auth::SmtpTransport::new(&config.smtp_host, config.smtp_port, security, credentials) {
+            Ok(transport) => Arc::new(transport),
+            Err(e) => {
+                tracing::error!("Email dispatch disabled: {}", e);
+                return;
+            }
+        }
+    };
+
+    let dispatcher = match auth::EmailDispatcher::new(Arc::new(service), transport) {
+        Ok(dispatcher) => dispatcher,
+        Err(e) => {
+            tracing::error!("Email dispatch disabled: {}", e);
+            return;
+        }
+    };
+
+    tracing::info!(
+        "Starting email dispatch every {}s via {}",
+        config.dispatch_interval_secs,
+        config.transport
+    );
+
+    let mut interval = time::interval(Duration::from_secs(config.dispatch_interval_secs));
+
+    loop {
+        interval.tick().await;
+
+        match dispatcher.dispatch(config.dispatch_batch_size).await {
+            Ok(report) => {
+                if report.retried > 0 || report.failed > 0 || report.bounced > 0 {
+                    tracing::warn!(
+                        "Email dispatch: {} sent, {} to retry, {} failed, {} bounced",
+                        report.sent,
+                        report.retried,
+                        report.failed,
+                        report.bounced
+                    );
+                }
+            }
+            Err(e) => tracing::error!("Email dispatch failed: {}", e),
+        }
+    }
+}
+
+/// Email topic subscribers a digest of new posts once per interval
+async fn run_topic_digests(state: AppState) {
+    let email = email_service(&state, &state.config.email);
+    let mut interval = time::interval(TOPIC_DIGEST_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = send_topic_digests(&state, &email).await {
+            tracing::error!("Topic digests failed: {}", e);
+        }
+    }
+}
+
+/// Queue a digest for every user with new posts in subscribed topics
+///
+/// A user whose digest fails to queue gets the same posts next time.
+async fn send_topic_digests(state: &AppState, email: &auth::EmailService) -> anyhow::Result<()> {
+    let topics = &state.community_service.topics;
+    let until = chrono::Utc::now();
+
+    let mut digests: BTreeMap<uuid::Uuid, Vec<auth::DigestTopic>> = BTreeMap::new();
+    for entry in topics.pending_digest_entries(until).await? {
+        digests
+            .entry(entry.user_id)
+            .or_default()
+            .push(auth::DigestTopic {
+                title: entry.title,
+                url: format!("{}/forum/topic/{}", email.config().site_url, entry.topic_id),
+                new_posts: entry.new_posts,
+            });
+    }
+
+    for (user_id, digest) in digests {
+        let Some((address, username, locale)) =
+            sqlx::query_as::<_, (String, String, Option<String>)>(
+                "SELECT email, username, locale FROM users WHERE id = $1",
+            )
+            .bind(user_id)
+            .fetch_optional(&state.db)
+            .await?
+        else {
+            continue;
+        };
+
+        let locale = locale.unwrap_or_else(|| auth::email::templates::DEFAULT_LOCALE.to_string());
+        match email
+            .send_topic_digest(user_id, &address, &username, &locale, &digest)
+            .await
+        {
+            Ok(_) => topics.mark_digest_sent(user_id, until).await?,
+            Err(e) => tracing::warn!("Failed to queue topic digest for {}: {}", user_id, e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Escalate torrents waiting in the moderation queue past the SLA
+async fn run_moderation_escalation(state: AppState) {
+    let mut interval = time::interval(MODERATION_ESCALATION_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = state.torrent_service.moderation().escalate_overdue().await {
+            tracing::error!("Moderation escalation failed: {}", e);
+        }
+    }
+}
+
+/// Pay out request bounties whose challenge window has passed, and expire
+/// old requests with refunds to their contributors
+async fn run_request_escrow(state: AppState) {
+    let mut interval = time::interval(REQUEST_ESCROW_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let requests = state.torrent_service.requests();
+
+        match requests.release_matured_fills().await {
+            Ok(0) => {}
+            Ok(paid) => tracing::info!("Paid out {} matured request fills", paid),
+            Err(e) => tracing::error!("Request fill payout failed: {}", e),
+        }
+
+        match requests.expire_requests().await {
+            Ok(0) => {}
+            Ok(expired) => {
+                tracing::info!("Expired {} requests and refunded their bounties", expired)
+            }
+            Err(e) => tracing::error!("Request expiry failed: {}", e),
+        }
+    }
+}
+
+/// Check users who snatched since the last run for download achievements
+async fn run_snatch_achievements(state: AppState) {
+    let statistics = StatisticsService::new(state.db.clone());
+    let achievements = AchievementService::new(state.db.clone());
+    let mut interval = time::interval(SNATCH_ACHIEVEMENT_INTERVAL);
+
+    // Awards are never repeated, so looking back one interval after a
+    // restart is harmless
+    let mut since = chrono::Utc::now()
+        - chrono::Duration::seconds(SNATCH_ACHIEVEMENT_INTERVAL.as_secs() as i64);
+
+    loop {
+        interval.tick().await;
+        let now = chrono::Utc::now();
+
+        let users = match statistics.users_with_snatches_since(since).await {
+            Ok(users) => users,
+            Err(e) => {
+                tracing::error!("Loading new snatches failed: {}", e);
+                continue;
+            }
+        };
+
+        for user_id in users {
+            match achievements.check_snatch_achievements(user_id).await {
+                Ok(awarded) if !awarded.is_empty() => {
+                    tracing::info!(
+                        "Awarded {} download achievement(s) to {}",
+                        awarded.len(),
+                        user_id
+                    )
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::error!("Download achievement check for {} failed: {}", user_id, e)
+                }
+            }
+        }
+
+        since = now;
+    }
+}
+
+/// Pay out bounties on revived torrents and refund expired reseed requests
+async fn run_reseed_resolution(state: AppState) {
+    let service = ReseedService::new(state.db.clone());
+    let mut interval = time::interval(RESEED_RESOLVE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let Some(swarms) = seeding_swarms(&state).await else {
+            continue;
+        };
+
+        match service.resolve(&swarms).await {
+            Ok(resolutions) => {
+                for resolution in &resolutions {
+                    tracing::info!(
+                        "Reseed request {} for torrent {} closed as {:?}",
+                        resolution.request_id,
+                        resolution.torrent_id,
+                        resolution.status
+                    );
+                }
+            }
+            Err(e) => tracing::error!("Reseed resolution failed: {}", e),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 mod config;
+mod jobs;
 mod middleware;
 mod routes;
 mod shutdown;
@@ -33,6 +34,9 @@ async fn main() -> Result<()> {
 
     tracing::info!("Application state initialized successfully");
 
+    // Start periodic background jobs
+    jobs::spawn_background_jobs(app_state.clone());
+
     // Build router with all routes and middleware
     let app = routes::build_router(app_state.clone());
 
@@ -78,9 +82,13 @@ async fn main() -> Result<()> {
 
     // Spawn the server
     let server_handle = tokio::spawn(async move {
-        axum::serve(listener, app)
-            .await
-            .context("Server error")
+        // Connection info lets handlers fall back to the peer address for the client IP
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .context("Server error")
     });
 
     tracing::info!("Tracker Platform started successfully");
@@ -4,7 +4,7 @@ use crate::middleware::{
 };
 use crate::state::{AppState, HealthStatus};
 use axum::extract::State;
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Json};
 use axum::routing::{get, post};
 use axum::{middleware, Router};
@@ -19,6 +19,8 @@ pub fn build_router(state: AppState) -> Router {
         .route("/health", get(health_check))
         .route("/health/ready", get(readiness_check))
         .route("/health/live", get(liveness_check))
+        // Public JWT verification keys (no auth required)
+        .route("/.well-known/jwks.json", get(jwks))
         // Metrics endpoint (no auth required, but consider protecting in production)
         .route("/metrics", get(crate::telemetry::metrics::metrics_handler))
         // API routes
@@ -27,6 +29,14 @@ pub fn build_router(state: AppState) -> Router {
         .nest("/graphql", graphql_routes(app_state.clone()))
         // Tracker endpoints (BitTorrent protocol)
         .nest("/tracker", tracker_routes(app_state.clone()))
+        // Tracker staff endpoints (live swarms and peers)
+        .nest(
+            "/admin/tracker",
+            tracker::admin::routes(
+                app_state.tracker_service.clone(),
+                app_state.auth_state.clone(),
+            ),
+        )
         // Static file serving (for uploaded media)
         .nest("/static", static_routes(app_state.clone()))
         // Fallback for 404
@@ -141,9 +151,9 @@ fn graphql_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
 /// Tracker routes (BitTorrent protocol)
 fn tracker_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
-        .route("/announce", get(tracker_announce))
-        .route("/scrape", get(tracker_scrape))
-        .with_state(state)
+        .route("/announce", get(tracker::announce::handle_announce))
+        .route("/scrape", get(tracker::scrape::handle_scrape))
+        .with_state(state.tracker_service.clone())
 }
 
 /// Static file routes
@@ -188,16 +198,14 @@ async fn liveness_check() -> impl IntoResponse {
     StatusCode::OK
 }
 
-/// Tracker announce endpoint
-async fn tracker_announce(State(_state): State<Arc<AppState>>) -> impl IntoResponse {
-    // This is a placeholder - actual implementation should be in the tracker crate
-    StatusCode::NOT_IMPLEMENTED
-}
-
-/// Tracker scrape endpoint
-async fn tracker_scrape(State(_state): State<Arc<AppState>>) -> impl IntoResponse {
-    // This is a placeholder - actual implementation should be in the tracker crate
-    StatusCode::NOT_IMPLEMENTED
+/// JWKS document with the public keys that verify our JWTs
+///
+/// Includes the next key ahead of its activation, so a short cache is safe.
+async fn jwks(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(state.jwt_keys.jwks(chrono::Utc::now())),
+    )
 }
 
 /// Serve static files
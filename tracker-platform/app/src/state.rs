@@ -14,6 +14,8 @@ pub struct AppState {
     pub db: PgPool,
     pub redis: ConnectionManager,
     pub meilisearch: MeilisearchClient,
+    pub jwt_keys: Arc<auth::KeyRing>,
+    pub auth_state: auth::AuthState,
     pub auth_service: Arc<auth::AuthService>,
     pub tracker_service: Arc<tracker::TrackerService>,
     pub torrent_service: Arc<torrent::TorrentService>,
@@ -52,22 +54,40 @@ impl AppState {
         let meilisearch = create_meilisearch_client(&config.meilisearch)?;
         tracing::info!("Meilisearch client initialized");
 
+        // Load JWT signing keys, generating the first one on a fresh database
+        tracing::info!("Loading JWT signing keys...");
+        let jwt_keys = Arc::new(auth::KeyRing::default());
+        auth::KeyStore::new(db.clone())
+            .sync(&jwt_keys, &config.auth.key_rotation_policy())
+            .await
+            .context("Failed to load JWT signing keys")?;
+        tracing::info!("JWT signing keys loaded");
+
+        // Token verification for handlers that take an `AuthUser`
+        let redis_client = redis::Client::open(config.redis.url.as_str())
+            .context("Failed to create Redis client")?;
+        let auth_state = auth::AuthState::new(
+            auth::JwtManager::new(jwt_keys.clone()),
+            auth::TokenRevocationList::new(redis_client.clone()),
+        )
+        .with_permission_resolver(Arc::new(auth::PermissionResolver::new(
+            db.clone(),
+            redis_client,
+        )));
+
         // Initialize services
         tracing::info!("Initializing services...");
 
         let auth_service = Arc::new(
-            auth::AuthService::new(
-                db.clone(),
-                redis.clone(),
-                &config.auth.jwt_secret,
-                config.auth.jwt_expiration_hours,
-            )
-            .await?,
+            auth::AuthService::new(db.clone(), redis.clone(), config.auth.jwt_expiration_hours)
+                .await?,
         );
 
-        let tracker_service = Arc::new(
-            tracker::TrackerService::new(db.clone(), redis.clone()).await?,
-        );
+        let tracker_service = Arc::new(tracker::TrackerService::with_config(
+            db.clone(),
+            tracker::connectability::ProbeConfig::default(),
+            config.tracker.scrape_config(),
+        ));
 
         let torrent_service = Arc::new(
             torrent::TorrentService::new(
@@ -109,6 +129,8 @@ impl AppState {
             db,
             redis,
             meilisearch,
+            jwt_keys,
+            auth_state,
             auth_service,
             tracker_service,
             torrent_service,
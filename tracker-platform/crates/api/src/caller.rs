@@ -0,0 +1,207 @@
+//! # Request Callers
+//!
+//! Resolves who is making a request. Scripts authenticate with a personal
+//! API token, sent either as `Authorization: Bearer trk_...` or in the
+//! `X-API-Key` header; everything else is treated as a browser session.
+//!
+//! API token callers are limited to the scopes on their token and are
+//! rate limited per token rather than per user.
+//!
+//! The client address is the connection's peer address. Forwarding headers
+//! are only believed when the peer is one of the configured trusted proxies,
+//! since anyone else can put whatever they like in them.
+
+use auth::api_tokens::{is_api_token, ApiTokenError, ApiTokenPrincipal, IpRange};
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::{rate_limit::RateLimitError, ApiError, ApiState};
+
+/// Header scripts can use instead of `Authorization`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Get the API token from a request, if it carries one
+pub fn api_token(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let api_key = headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    bearer
+        .into_iter()
+        .chain(api_key)
+        .map(str::trim)
+        .find(|token| is_api_token(token))
+}
+
+/// Address of the client making a request
+///
+/// Extracting it needs the router to be served with connect info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<Arc<ApiState>> for ClientIp {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<ApiState>,
+    ) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(peer) = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .copied()
+            .ok_or_else(|| ApiError::InternalError("Client address unavailable".to_string()))?;
+
+        Ok(Self(client_ip(
+            peer.ip(),
+            &parts.headers,
+            &state.config.trusted_proxies,
+        )))
+    }
+}
+
+/// Get the client IP of a request that arrived from `peer`
+///
+/// When `peer` is a trusted proxy, `X-Forwarded-For` is walked from the
+/// right, skipping further trusted proxies; the first other hop is the
+/// client. Entries left of it were written by the client and are ignored.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpRange]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted_proxies.iter().any(|range| range.contains(ip));
+    let peer = peer.to_canonical();
+
+    if !is_trusted(peer) {
+        return peer;
+    }
+
+    let hops: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        // A malformed hop was not written by one of our proxies
+        let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = ip.to_canonical();
+        if !is_trusted(client) {
+            break;
+        }
+    }
+
+    client
+}
+
+/// Authenticate a request made with an API token and apply its rate limit
+pub async fn authenticate_api_token(
+    state: &ApiState,
+    token: &str,
+    client_ip: ClientIp,
+) -> Result<ApiTokenPrincipal, ApiError> {
+    let principal = state
+        .api_tokens
+        .authenticate(token, Some(client_ip.0))
+        .await?;
+
+    state
+        .rate_limiter
+        .check_api_key_limit(&principal.token_id.to_string())
+        .await
+        .map_err(|e| match e {
+            RateLimitError::RateLimitExceeded { .. } => ApiError::RateLimitExceeded,
+            RateLimitError::InternalError => {
+                ApiError::InternalError("Rate limiter unavailable".to_string())
+            }
+        })?;
+
+    Ok(principal)
+}
+
+impl From<ApiTokenError> for ApiError {
+    fn from(e: ApiTokenError) -> Self {
+        match e {
+            ApiTokenError::InvalidToken | ApiTokenError::Expired | ApiTokenError::Revoked => {
+                ApiError::AuthenticationError(e.to_string())
+            }
+            ApiTokenError::IpNotAllowed
+            | ApiTokenError::ApiAccessDenied
+            | ApiTokenError::ScopeNotAllowed(_) => ApiError::AuthorizationError(e.to_string()),
+            ApiTokenError::NoScopes
+            | ApiTokenError::InvalidIpRange(_)
+            | ApiTokenError::TooManyIpRanges
+            | ApiTokenError::InvalidExpiry
+            | ApiTokenError::TooManyTokens
+            | ApiTokenError::ValidationError(_) => ApiError::ValidationError(e.to_string()),
+            ApiTokenError::NotFound => ApiError::NotFound(e.to_string()),
+            ApiTokenError::Database(msg) => ApiError::InternalError(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_api_token_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(api_token(&headers), None);
+
+        // JWTs are left to the session path
+        headers.insert(
+            "authorization",
+            HeaderValue::from_static("Bearer eyJhbGciOi"),
+        );
+        assert_eq!(api_token(&headers), None);
+
+        headers.insert(API_KEY_HEADER, HeaderValue::from_static("trk_fromheader"));
+        assert_eq!(api_token(&headers), Some("trk_fromheader"));
+
+        headers.insert(
+            "authorization",
+            HeaderValue::from_static("Bearer trk_frombearer"),
+        );
+        assert_eq!(api_token(&headers), Some("trk_frombearer"));
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let trusted: Vec<IpRange> = vec!["10.0.0.0/8".parse().unwrap()];
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let direct: IpAddr = "198.51.100.2".parse().unwrap();
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(client_ip(direct, &headers, &trusted), direct);
+        assert_eq!(client_ip(proxy, &headers, &trusted), proxy);
+
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("192.0.2.9, 203.0.113.5, 10.0.0.7"),
+        );
+
+        // Spoofed entries on the left are skipped, as are our own proxies
+        assert_eq!(
+            client_ip(proxy, &headers, &trusted),
+            "203.0.113.5".parse::<IpAddr>().unwrap()
+        );
+
+        // Headers from anyone but a trusted proxy are ignored
+        assert_eq!(client_ip(direct, &headers, &trusted), direct);
+        assert_eq!(client_ip(proxy, &headers, &[]), proxy);
+    }
+}
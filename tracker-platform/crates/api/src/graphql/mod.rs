@@ -8,7 +8,7 @@
 //! - **Mutations**: All CRUD operations
 //! - **Subscriptions**: Real-time updates via WebSocket
 //! - **DataLoaders**: Efficient batching and caching to prevent N+1 queries
-//! - **Authentication**: User context with JWT validation
+//! - **Authentication**: User context with JWT validation or a scoped API token
 //! - **Authorization**: Field-level and object-level access control
 
 pub mod mutations;
@@ -26,6 +26,7 @@ use async_graphql::{
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 use axum::{
     extract::State,
+    http::HeaderMap,
     response::{Html, IntoResponse},
     routing::{get, post},
     Router,
@@ -48,8 +49,10 @@ pub struct GraphQLContext {
     pub redis_client: redis::Client,
     /// Current user ID (if authenticated)
     pub user_id: Option<uuid::Uuid>,
-    /// User permissions
+    /// Effective permission names, from [`permission_names`]
     pub permissions: Vec<String>,
+    /// Scopes of the API token the request was made with (None for sessions)
+    pub token_scopes: Option<Vec<auth::ApiTokenScope>>,
     /// Torrent dataloader
     pub torrent_loader: DataLoader<TorrentLoader>,
     /// User dataloader
@@ -93,6 +96,7 @@ impl GraphQLContext {
             redis_client,
             user_id,
             permissions,
+            token_scopes: None,
             torrent_loader,
             user_loader,
             forum_loader,
@@ -100,15 +104,70 @@ impl GraphQLContext {
     }
 
     /// Check if user has a specific permission
+    ///
+    /// Site admin is already expanded and denials removed when the
+    /// permissions were resolved, so this is a plain membership check.
     pub fn has_permission(&self, permission: &str) -> bool {
         self.permissions.iter().any(|p| p == permission)
     }
 
+    /// Mark the context as authenticated with an API token limited to `scopes`
+    pub fn with_token_scopes(mut self, scopes: Vec<auth::ApiTokenScope>) -> Self {
+        self.token_scopes = Some(scopes);
+        self
+    }
+
     /// Get current user ID or return error
+    ///
+    /// Only sessions are accepted; resolvers open to API tokens use
+    /// [`GraphQLContext::require_scope`].
     pub fn require_auth(&self) -> Result<uuid::Uuid, async_graphql::Error> {
+        if self.token_scopes.is_some() {
+            return Err(async_graphql::Error::new(
+                "This operation cannot be used with an API token",
+            ));
+        }
+
         self.user_id
             .ok_or_else(|| async_graphql::Error::new("Authentication required"))
     }
+
+    /// Get current user ID if the session or API token allows `scope`
+    ///
+    /// The user must also still hold the permission the scope stands for.
+    pub fn require_scope(
+        &self,
+        scope: auth::ApiTokenScope,
+    ) -> Result<uuid::Uuid, async_graphql::Error> {
+        let user_id = self
+            .user_id
+            .ok_or_else(|| async_graphql::Error::new("Authentication required"))?;
+
+        if let Some(scopes) = &self.token_scopes {
+            if !scopes.contains(&scope) {
+                return Err(async_graphql::Error::new(format!(
+                    "API token is missing the {} scope",
+                    scope
+                )));
+            }
+        }
+
+        match scope.required_permission() {
+            Some(permission) if !self.has_permission(permission.as_str()) => Err(
+                async_graphql::Error::new(format!("{} permission required", permission)),
+            ),
+            _ => Ok(user_id),
+        }
+    }
+}
+
+/// Names of the permissions a user effectively has, for [`GraphQLContext`]
+pub fn permission_names(permissions: &auth::PermissionSet) -> Vec<String> {
+    permissions
+        .effective()
+        .into_iter()
+        .map(|permission| permission.as_str().to_string())
+        .collect()
 }
 
 /// DataLoader for batch loading torrents
@@ -225,14 +284,54 @@ pub fn configure_routes(app: Router<Arc<ApiState>>, state: Arc<ApiState>) -> Rou
 }
 
 /// GraphQL query handler
-#[instrument(skip(state, req))]
+#[instrument(skip(state, headers, req))]
 async fn graphql_handler(
     State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    client_ip: crate::caller::ClientIp,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
+    // Scripts authenticate with an API token limited to its scopes
+    if let Some(token) = crate::caller::api_token(&headers) {
+        let principal = match crate::caller::authenticate_api_token(&state, token, client_ip).await
+        {
+            Ok(principal) => principal,
+            Err(e) => {
+                let error = async_graphql::ServerError::new(e.to_string(), None);
+                return async_graphql::Response::from_errors(vec![error]).into();
+            }
+        };
+
+        let ctx = GraphQLContext::new(
+            state.db_pool.clone(),
+            state.redis_client.clone(),
+            Some(principal.user_id),
+            principal
+                .permissions
+                .iter()
+                .map(|permission| permission.as_str().to_string())
+                .collect(),
+        )
+        .with_token_scopes(principal.scopes);
+
+        return state.graphql_schema.execute(req.into_inner().data(ctx)).await.into();
+    }
+
     // TODO: Extract user from JWT token in request headers
-    let user_id = None;
-    let permissions = vec![];
+    let user_id: Option<uuid::Uuid> = None;
+
+    // Resolved per request, so class changes apply without a fresh login
+    let permissions = match user_id {
+        Some(user_id) => match state.permissions.resolve(user_id).await {
+            Ok(permissions) => permission_names(&permissions),
+            Err(e) => {
+                tracing::error!("Failed to resolve permissions for {}: {}", user_id, e);
+                let error = async_graphql::ServerError::new("Failed to load permissions", None);
+                return async_graphql::Response::from_errors(vec![error]).into();
+            }
+        },
+        None => vec![],
+    };
 
     // Create GraphQL context
     let ctx = GraphQLContext::new(
@@ -309,4 +408,62 @@ mod tests {
         assert!(ctx.has_permission("write:torrents"));
         assert!(!ctx.has_permission("admin:users"));
     }
+
+    #[test]
+    fn test_permission_names_are_effective() {
+        let mut permissions = auth::PermissionSet::from_vec(vec![auth::Permission::SiteAdmin]);
+        permissions.deny(auth::Permission::ViewUserIPs);
+
+        let db_pool = sqlx::PgPool::connect_lazy("postgres://localhost/test").unwrap();
+        let redis_client = redis::Client::open("redis://localhost/").unwrap();
+        let ctx = GraphQLContext::new(
+            db_pool,
+            redis_client,
+            Some(uuid::Uuid::new_v4()),
+            permission_names(&permissions),
+        );
+
+        assert!(ctx.has_permission("site_admin"));
+        assert!(ctx.has_permission("ban_users"));
+        assert!(!ctx.has_permission("view_user_i_ps"));
+    }
+
+    #[test]
+    fn test_graphql_context_token_scopes() {
+        use auth::ApiTokenScope;
+
+        let db_pool = sqlx::PgPool::connect_lazy("postgres://localhost/test").unwrap();
+        let redis_client = redis::Client::open("redis://localhost/").unwrap();
+        let user_id = uuid::Uuid::new_v4();
+
+        let uploader = vec![auth::Permission::UploadTorrent.as_str().to_string()];
+        let session = GraphQLContext::new(
+            db_pool.clone(),
+            redis_client.clone(),
+            Some(user_id),
+            uploader,
+        );
+        assert_eq!(session.require_auth().unwrap(), user_id);
+        assert_eq!(
+            session.require_scope(ApiTokenScope::Upload).unwrap(),
+            user_id
+        );
+        assert_eq!(
+            session.require_scope(ApiTokenScope::ReadTorrents).unwrap(),
+            user_id
+        );
+
+        // Scopes whose permission was lost are refused
+        assert!(session.require_scope(ApiTokenScope::Download).is_err());
+
+        let downloader = vec![auth::Permission::Download.as_str().to_string()];
+        let token = GraphQLContext::new(db_pool, redis_client, Some(user_id), downloader)
+            .with_token_scopes(vec![ApiTokenScope::Download]);
+        assert!(token.require_auth().is_err());
+        assert_eq!(
+            token.require_scope(ApiTokenScope::Download).unwrap(),
+            user_id
+        );
+        assert!(token.require_scope(ApiTokenScope::Upload).is_err());
+    }
 }
@@ -3,6 +3,7 @@
 //! Mutation resolvers for creating, updating, and deleting data.
 
 use async_graphql::Result;
+use auth::ApiTokenScope;
 use chrono::Utc;
 use tracing::instrument;
 
@@ -11,7 +12,7 @@ use super::{types::*, GraphQLContext};
 /// Upload a new torrent
 #[instrument(skip(ctx, input))]
 pub async fn upload_torrent(ctx: &GraphQLContext, input: UploadTorrentInput) -> Result<Torrent> {
-    let user_id = ctx.require_auth()?;
+    let user_id = ctx.require_scope(ApiTokenScope::Upload)?;
 
     // Validate input
     if input.name.is_empty() {
@@ -75,7 +76,7 @@ pub async fn update_torrent(
     id: uuid::Uuid,
     input: UpdateTorrentInput,
 ) -> Result<Torrent> {
-    let user_id = ctx.require_auth()?;
+    let user_id = ctx.require_scope(ApiTokenScope::Upload)?;
 
     // Get existing torrent
     let existing = sqlx::query_as::<_, Torrent>("SELECT * FROM torrents WHERE id = $1")
@@ -96,18 +97,36 @@ pub async fn update_torrent(
     let description = input.description.or(existing.description);
     let category = input.category.unwrap_or(existing.category);
 
-    let torrent = sqlx::query_as::<_, Torrent>(
-        "UPDATE torrents
-         SET name = $1, description = $2, category = $3
-         WHERE id = $4
-         RETURNING *",
-    )
-    .bind(&name)
-    .bind(&description)
-    .bind(&category)
-    .bind(id)
-    .fetch_one(&ctx.db_pool)
-    .await?;
+    // Version the edit so it can be reviewed and rolled back; the history
+    // service updates the listing columns in the same transaction
+    let history = torrent::EditHistoryService::new(ctx.db_pool.clone());
+    let torrent = match history.current(id).await? {
+        Some(mut snapshot) => {
+            snapshot.metadata.name = name;
+            snapshot.metadata.description = description;
+            snapshot.category = category;
+            history.edit(&snapshot, user_id, None).await?;
+
+            sqlx::query_as::<_, Torrent>("SELECT * FROM torrents WHERE id = $1")
+                .bind(id)
+                .fetch_one(&ctx.db_pool)
+                .await?
+        }
+        None => {
+            sqlx::query_as::<_, Torrent>(
+                "UPDATE torrents
+                 SET name = $1, description = $2, category = $3
+                 WHERE id = $4
+                 RETURNING *",
+            )
+            .bind(&name)
+            .bind(&description)
+            .bind(&category)
+            .bind(id)
+            .fetch_one(&ctx.db_pool)
+            .await?
+        }
+    };
 
     // TODO: Update search index
     // TODO: Trigger webhooks
@@ -189,7 +208,7 @@ pub async fn update_profile(ctx: &GraphQLContext, input: UpdateProfileInput) ->
 /// Create a forum topic
 #[instrument(skip(ctx, input))]
 pub async fn create_topic(ctx: &GraphQLContext, input: CreateTopicInput) -> Result<Topic> {
-    let user_id = ctx.require_auth()?;
+    let user_id = ctx.require_scope(ApiTokenScope::Post)?;
 
     // Validate input
     if input.title.is_empty() {
@@ -257,7 +276,7 @@ pub async fn create_topic(ctx: &GraphQLContext, input: CreateTopicInput) -> Resu
 /// Post a reply to a topic
 #[instrument(skip(ctx, input))]
 pub async fn post_reply(ctx: &GraphQLContext, input: PostReplyInput) -> Result<Post> {
-    let user_id = ctx.require_auth()?;
+    let user_id = ctx.require_scope(ApiTokenScope::Post)?;
 
     // Validate input
     if input.content.is_empty() {
@@ -9,6 +9,13 @@
 //! - **Rate Limiting**: Per-user and per-endpoint rate limiting with token bucket algorithm
 //! - **Webhooks**: Event-driven webhook system with retry logic
 //! - **Authentication**: JWT-based authentication with context propagation
+//! - **API Tokens**: Scoped personal tokens for scripts, accepted by REST and GraphQL
+//! - **OAuth / OpenID Connect**: Authorization code + PKCE provider for sister sites
+//! - **User Classes**: Admin management of classes and per-user permission overrides
+//! - **Email**: Signed one-click unsubscribe links for transactional email
+//! - **Passwords**: Password change and reset with breached password screening
+//! - **Sign-in Security**: Approving risky sign-ins and one-click "this wasn't me" from email links
+//! - **Security Keys**: WebAuthn security key and passkey registration and passkey sign-in
 //! - **DataLoaders**: Efficient data loading to prevent N+1 queries
 //! - **Real-time Updates**: WebSocket-based subscriptions for live data
 //! - **OpenAPI Documentation**: Auto-generated API documentation with Swagger UI
@@ -35,6 +42,7 @@
 //! └────────────────────────────────────────────────────────────┘
 //! ```
 
+pub mod caller;
 pub mod graphql;
 pub mod openapi;
 pub mod rate_limit;
@@ -53,6 +61,9 @@ use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 use tracing::{info, instrument};
 
+/// How often the JWT signing keys are reloaded
+const JWT_KEY_SYNC_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
 /// API service configuration
 #[derive(Debug, Clone)]
 pub struct ApiConfig {
@@ -68,12 +79,30 @@ pub struct ApiConfig {
     pub enable_cors: bool,
     /// Rate limit: requests per minute
     pub rate_limit_per_minute: u32,
-    /// JWT secret for authentication
-    pub jwt_secret: String,
     /// Database connection pool
     pub database_url: String,
     /// Redis connection URL
     pub redis_url: String,
+    /// Public URL of the site, used as the OAuth / OpenID Connect issuer
+    /// and for links in emails
+    pub oauth_issuer: String,
+    /// Site name used in emails
+    pub site_name: String,
+    /// Sender of transactional email
+    pub email_from: String,
+    /// Key for signing email unsubscribe links
+    pub email_unsubscribe_secret: String,
+    /// Secret the mail server presents when reporting bounces; the bounce
+    /// intake is closed without it
+    pub email_bounce_secret: Option<String>,
+    /// Directory of HIBP-format range files for breached password screening
+    pub breached_passwords_dir: Option<String>,
+    /// GeoIP range file (DB-IP Lite CSV) for sign-in location checks
+    pub geoip_database: Option<String>,
+    /// Reverse proxies whose `X-Forwarded-For` header is believed
+    pub trusted_proxies: Vec<auth::api_tokens::IpRange>,
+    /// Roles that must sign in with a security key
+    pub security_key_roles: Vec<auth::Role>,
 }
 
 impl Default for ApiConfig {
@@ -85,9 +114,17 @@ impl Default for ApiConfig {
             enable_swagger_ui: true,
             enable_cors: true,
             rate_limit_per_minute: 60,
-            jwt_secret: "change-me-in-production".to_string(),
             database_url: "postgres://localhost/tracker".to_string(),
             redis_url: "redis://localhost/".to_string(),
+            oauth_issuer: "http://localhost:8080".to_string(),
+            site_name: "Tracker".to_string(),
+            email_from: "Tracker <noreply@localhost>".to_string(),
+            email_unsubscribe_secret: "change-me-in-production".to_string(),
+            email_bounce_secret: None,
+            breached_passwords_dir: None,
+            geoip_database: None,
+            trusted_proxies: Vec::new(),
+            security_key_roles: Vec::new(),
         }
     }
 }
@@ -107,6 +144,28 @@ pub struct ApiState {
     pub rate_limiter: Arc<rate_limit::RateLimiter>,
     /// Webhook manager
     pub webhook_manager: Arc<webhooks::WebhookManager>,
+    /// Personal API tokens
+    pub api_tokens: Arc<auth::ApiTokenService>,
+    /// JWT signing keys
+    pub jwt_keys: Arc<auth::KeyRing>,
+    /// OAuth / OpenID Connect provider
+    pub oauth: Arc<auth::OAuthProvider>,
+    /// Cached effective permissions
+    pub permissions: Arc<auth::PermissionResolver>,
+    /// User classes and permission overrides
+    pub classes: Arc<auth::UserClassService>,
+    /// Transactional email queue and unsubscribes
+    pub email: Arc<auth::EmailService>,
+    /// Password change and reset
+    pub passwords: Arc<auth::PasswordService>,
+    /// Forced password resets and breach rescreening
+    pub breach_screening: auth::BreachScreening,
+    /// Sign-in approvals and "this wasn't me" reports
+    pub login_risk: Arc<auth::LoginRiskService>,
+    /// Security key and passkey registration
+    pub security_keys: Arc<auth::SecurityKeyService>,
+    /// Password, security key and passkey sign-in
+    pub login: Arc<auth::LoginService>,
 }
 
 impl ApiState {
@@ -139,6 +198,107 @@ impl ApiState {
             redis_client.clone(),
         ));
 
+        // Create permission resolver, shared by everything that checks
+        // a user's class permissions
+        let permissions = Arc::new(auth::PermissionResolver::new(
+            db_pool.clone(),
+            redis_client.clone(),
+        ));
+
+        let api_tokens = Arc::new(auth::ApiTokenService::new(
+            db_pool.clone(),
+            permissions.clone(),
+        ));
+
+        // Load JWT signing keys and keep them in step with rotation; the
+        // app's rotation job owns the schedule, so only reload them here
+        let jwt_keys = Arc::new(auth::KeyRing::default());
+        let key_store = auth::KeyStore::new(db_pool.clone());
+        key_store.load(&jwt_keys).await?;
+        tokio::spawn(sync_jwt_keys(key_store, jwt_keys.clone()));
+
+        // Create OAuth provider
+        let oauth = Arc::new(auth::OAuthProvider::new(
+            db_pool.clone(),
+            redis_client.clone(),
+            jwt_keys.clone(),
+            permissions.clone(),
+            auth::OAuthConfig::new(config.oauth_issuer.as_str()),
+        ));
+
+        // Create user class management
+        let classes = Arc::new(auth::UserClassService::new(
+            db_pool.clone(),
+            permissions.clone(),
+        ));
+
+        // Create the email service; delivery runs in the app's dispatcher job
+        let mut email_config = auth::EmailConfig::new(
+            config.email_from.as_str(),
+            config.site_name.as_str(),
+            config.oauth_issuer.as_str(),
+            config.email_unsubscribe_secret.as_bytes(),
+        );
+        if let Some(secret) = &config.email_bounce_secret {
+            email_config = email_config.with_bounce_secret(secret.as_bytes());
+        }
+        let email = Arc::new(auth::EmailService::new(db_pool.clone(), email_config));
+
+        // New and forcibly reset passwords sign the user out everywhere
+        let sessions = Arc::new(auth::SessionManager::new(redis_client.clone()));
+
+        // Create the password service, screening against the corpus if configured
+        let breach_corpus = match &config.breached_passwords_dir {
+            Some(dir) => Some(Arc::new(auth::BreachedPasswords::open(dir)?)),
+            None => None,
+        };
+        let mut passwords = auth::PasswordService::new(db_pool.clone())
+            .with_email(email.clone())
+            .with_sign_out(sessions.clone(), api_tokens.clone());
+        if let Some(corpus) = &breach_corpus {
+            passwords = passwords.with_breach_corpus(corpus.clone());
+        }
+        let passwords = Arc::new(passwords);
+        let breach_screening = auth::BreachScreening::new(db_pool.clone())
+            .with_sign_out(sessions, api_tokens.clone());
+
+        // Create the sign-in risk service, with location checks if a GeoIP file is set
+        let mut login_risk = auth::LoginRiskService::new(db_pool.clone(), redis_client.clone())
+            .with_email(email.clone());
+        if let Some(path) = &config.geoip_database {
+            let geoip = auth::GeoIpDatabase::load(path)?;
+            info!("Loaded {} GeoIP ranges", geoip.len());
+            login_risk = login_risk.with_geoip(Arc::new(geoip));
+        }
+        let login_risk = Arc::new(login_risk);
+
+        // Security keys are scoped to the site's host name
+        let security_keys = Arc::new(auth::SecurityKeyService::new(
+            db_pool.clone(),
+            redis_client.clone(),
+            auth::WebAuthnManager::new(auth::WebAuthnConfig::new(
+                webauthn_rp_id(&config.oauth_issuer),
+                config.site_name.as_str(),
+                config.oauth_issuer.trim_end_matches('/'),
+            )),
+        ));
+
+        // Create the login service; forced roles sign in with a security key
+        let mut login = auth::LoginService::new(
+            db_pool.clone(),
+            auth::JwtManager::new(jwt_keys.clone()),
+            auth::SessionManager::new(redis_client.clone()),
+            auth::TokenRevocationList::new(redis_client.clone()),
+            auth::TwoFactorManager::new(config.site_name.as_str()),
+            permissions.clone(),
+        )
+        .with_security_keys(security_keys.clone(), config.security_key_roles.clone())
+        .with_login_risk(login_risk.clone());
+        if let Some(corpus) = breach_corpus {
+            login = login.with_breach_corpus(corpus);
+        }
+        let login = Arc::new(login);
+
         Ok(Self {
             config,
             db_pool,
@@ -146,10 +306,45 @@ impl ApiState {
             graphql_schema,
             rate_limiter,
             webhook_manager,
+            api_tokens,
+            jwt_keys,
+            oauth,
+            permissions,
+            classes,
+            email,
+            passwords,
+            breach_screening,
+            login_risk,
+            security_keys,
+            login,
         })
     }
 }
 
+/// WebAuthn relying party ID: the host of the site's public URL
+fn webauthn_rp_id(site_url: &str) -> String {
+    site_url
+        .parse::<http::Uri>()
+        .ok()
+        .and_then(|uri| uri.host().map(str::to_string))
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+/// Reload the JWT signing keys so OAuth tokens follow key rotation
+async fn sync_jwt_keys(store: auth::KeyStore, keys: Arc<auth::KeyRing>) {
+    let mut interval = tokio::time::interval(JWT_KEY_SYNC_INTERVAL);
+    // The first tick fires immediately, and the keys were just loaded
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = store.load(&keys).await {
+            tracing::error!("JWT key reload failed: {}", e);
+        }
+    }
+}
+
 /// Main API service
 pub struct ApiService {
     state: Arc<ApiState>,
@@ -229,7 +424,13 @@ impl ApiService {
             info!("Swagger UI: http://{}/swagger-ui", addr);
         }
 
-        axum::serve(listener, self.router).await?;
+        // Client addresses come from the connection, see `caller::ClientIp`
+        axum::serve(
+            listener,
+            self.router
+                .into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        )
+        .await?;
 
         Ok(())
     }
@@ -365,6 +566,13 @@ mod tests {
         assert!(config.enable_swagger_ui);
     }
 
+    #[test]
+    fn test_webauthn_rp_id() {
+        assert_eq!(webauthn_rp_id("https://tracker.example/"), "tracker.example");
+        assert_eq!(webauthn_rp_id("http://localhost:8080"), "localhost");
+        assert_eq!(webauthn_rp_id("not a url"), "localhost");
+    }
+
     #[tokio::test]
     async fn test_health_check() {
         let response = health_check().await.into_response();
@@ -12,14 +12,43 @@
 
 use axum::Router;
 use utoipa::{
-    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
     Modify, OpenApi,
 };
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::rest::{
-    torrents::{TorrentResponse, TorrentSearchParams, UploadTorrentRequest, UpdateTorrentRequest},
-    users::{UserResponse, UserStatisticsResponse, UpdateUserRequest},
+    api_tokens::{
+        ApiTokenResponse, CreateApiTokenBody, CreatedApiTokenResponse, RevokedApiTokensResponse,
+    },
+    classes::{
+        CreateClassBody, PermissionOverrideResponse, SetOverrideBody, SetUserClassBody,
+        UpdateClassBody, UserClassResponse,
+    },
+    email::{BounceIntakeResponse, UnsubscribeResponse},
+    oauth::{
+        ConsentDecisionBody, OAuthClientResponse, RegisterOAuthClientBody,
+        RegisteredOAuthClientResponse,
+    },
+    passwords::{
+        ChangePasswordBody, ForgotPasswordBody, RescreenBody, RescreenResponse, ResetPasswordBody,
+    },
+    requests::{RejectFillBody, RequestFillResponse},
+    security::{NotMeResponse, SecurityNotificationResponse, SignInTokenBody},
+    security_keys::{
+        EnrollmentBody, FinishRegistrationBody, PasskeyLoginBody, SecurityKeyChallengeResponse,
+        SecurityKeyResponse, SignInResponse, StartRegistrationBody,
+    },
+    torrents::{
+        DeadTorrentResponse, EditNotificationResponse, ReseedResponse, ReseedTorrentRequest,
+        RipSummaryResponse, TorrentResponse, TorrentRevisionResponse, TorrentSearchParams,
+        TrumpCheckResponse, UploadTorrentRequest, UpdateTorrentRequest,
+    },
+    upload_rules::{SaveUploadRulesBody, UploadRulesResponse},
+    users::{
+        ActiveTorrentResponse, CreateInviteBody, HitAndRunResponse, InviteResponse,
+        SnatchResponse, UserResponse, UserStatisticsResponse, UpdateUserRequest,
+    },
     ErrorResponse, PaginatedResponse, PaginationMeta, PaginationParams,
 };
 
@@ -50,10 +79,66 @@ use crate::rest::{
         crate::rest::torrents::upload_torrent,
         crate::rest::torrents::update_torrent,
         crate::rest::torrents::download_torrent,
+        crate::rest::torrents::get_torrent_nfo_image,
+        crate::rest::torrents::get_torrent_history,
+        crate::rest::torrents::rollback_torrent,
+        crate::rest::torrents::list_edit_notifications,
+        crate::rest::torrents::mark_edit_notifications_read,
+        crate::rest::torrents::request_reseed,
+        crate::rest::torrents::list_dead_torrents,
+        crate::rest::torrents::check_trump,
+        crate::rest::requests::reject_fill,
+        crate::rest::upload_rules::list_upload_rules,
+        crate::rest::upload_rules::save_default_rules,
+        crate::rest::upload_rules::delete_default_rules,
+        crate::rest::upload_rules::save_category_rules,
+        crate::rest::upload_rules::delete_category_rules,
         crate::rest::users::get_user,
         crate::rest::users::update_user,
         crate::rest::users::get_user_stats,
         crate::rest::users::get_user_torrents,
+        crate::rest::users::get_user_active_torrents,
+        crate::rest::users::get_user_snatches,
+        crate::rest::users::get_user_hit_and_runs,
+        crate::rest::users::create_invite,
+        crate::rest::api_tokens::list_api_tokens,
+        crate::rest::api_tokens::create_api_token,
+        crate::rest::api_tokens::revoke_api_token,
+        crate::rest::api_tokens::revoke_all_api_tokens,
+        crate::rest::oauth::authorize,
+        crate::rest::oauth::decide,
+        crate::rest::oauth::list_clients,
+        crate::rest::oauth::register_client,
+        crate::rest::oauth::revoke_client,
+        crate::rest::classes::list_classes,
+        crate::rest::classes::create_class,
+        crate::rest::classes::get_class,
+        crate::rest::classes::update_class,
+        crate::rest::classes::delete_class,
+        crate::rest::classes::get_user_class,
+        crate::rest::classes::set_user_class,
+        crate::rest::classes::list_overrides,
+        crate::rest::classes::set_override,
+        crate::rest::classes::remove_override,
+        crate::rest::email::check_unsubscribe,
+        crate::rest::email::unsubscribe,
+        crate::rest::email::report_bounces,
+        crate::rest::passwords::change_password,
+        crate::rest::passwords::forgot_password,
+        crate::rest::passwords::reset_password,
+        crate::rest::passwords::rescreen,
+        crate::rest::passwords::force_reset,
+        crate::rest::security::approve_sign_in,
+        crate::rest::security::report_not_me,
+        crate::rest::security::list_security_notifications,
+        crate::rest::security::mark_security_notification_read,
+        crate::rest::security_keys::list_security_keys,
+        crate::rest::security_keys::start_registration,
+        crate::rest::security_keys::finish_registration,
+        crate::rest::security_keys::remove_security_key,
+        crate::rest::security_keys::finish_enrollment,
+        crate::rest::security_keys::start_passkey_login,
+        crate::rest::security_keys::passkey_login,
     ),
     components(
         schemas(
@@ -61,9 +146,56 @@ use crate::rest::{
             TorrentSearchParams,
             UploadTorrentRequest,
             UpdateTorrentRequest,
+            TorrentRevisionResponse,
+            EditNotificationResponse,
+            ReseedTorrentRequest,
+            ReseedResponse,
+            DeadTorrentResponse,
+            RipSummaryResponse,
+            TrumpCheckResponse,
+            RejectFillBody,
+            RequestFillResponse,
+            UploadRulesResponse,
+            SaveUploadRulesBody,
             UserResponse,
             UserStatisticsResponse,
             UpdateUserRequest,
+            ActiveTorrentResponse,
+            SnatchResponse,
+            HitAndRunResponse,
+            CreateInviteBody,
+            InviteResponse,
+            ApiTokenResponse,
+            CreatedApiTokenResponse,
+            RevokedApiTokensResponse,
+            CreateApiTokenBody,
+            OAuthClientResponse,
+            RegisteredOAuthClientResponse,
+            RegisterOAuthClientBody,
+            ConsentDecisionBody,
+            UserClassResponse,
+            CreateClassBody,
+            UpdateClassBody,
+            SetUserClassBody,
+            PermissionOverrideResponse,
+            SetOverrideBody,
+            UnsubscribeResponse,
+            BounceIntakeResponse,
+            ChangePasswordBody,
+            ForgotPasswordBody,
+            ResetPasswordBody,
+            RescreenBody,
+            RescreenResponse,
+            SignInTokenBody,
+            NotMeResponse,
+            SecurityNotificationResponse,
+            SecurityKeyResponse,
+            SecurityKeyChallengeResponse,
+            StartRegistrationBody,
+            FinishRegistrationBody,
+            EnrollmentBody,
+            PasskeyLoginBody,
+            SignInResponse,
             ErrorResponse,
             PaginationParams,
             PaginationMeta,
@@ -73,7 +205,16 @@ use crate::rest::{
     tags(
         (name = "meta", description = "API metadata and version information"),
         (name = "torrents", description = "Torrent operations"),
+        (name = "requests", description = "Request fills and bounty escrow"),
+        (name = "upload-rules", description = "Per-category upload rules"),
         (name = "users", description = "User management"),
+        (name = "api-tokens", description = "Personal API tokens for scripts"),
+        (name = "oauth", description = "OAuth / OpenID Connect consent and client registration"),
+        (name = "classes", description = "User classes and per-user permission overrides"),
+        (name = "email", description = "Unsubscribing from email categories"),
+        (name = "passwords", description = "Password change, reset and breached password screening"),
+        (name = "security", description = "Approving risky sign-ins, reporting sign-ins that weren't you and security notifications"),
+        (name = "security-keys", description = "Security key and passkey registration, and passkey sign-in"),
     ),
     modifiers(&SecurityAddon)
 )]
@@ -95,6 +236,13 @@ impl Modify for SecurityAddon {
                         .build(),
                 ),
             );
+            components.add_security_scheme(
+                "api_token",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    "X-API-Key",
+                    "Personal API token (trk_...); may also be sent as a bearer token",
+                ))),
+            );
         }
     }
 }
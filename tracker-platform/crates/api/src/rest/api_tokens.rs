@@ -0,0 +1,243 @@
+//! # API Token REST Endpoints
+//!
+//! Lets users manage the personal API tokens their scripts use. These
+//! endpoints need a signed-in session; an API token cannot mint or revoke
+//! other tokens.
+
+use auth::{ApiToken, ApiTokenScope, CreateApiTokenRequest};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::instrument;
+
+use super::{require_auth, ErrorResponse};
+use crate::{ApiError, ApiState};
+
+/// API token response DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ApiTokenResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    /// Start of the token, to tell tokens apart
+    pub token_prefix: String,
+    /// Granted scopes: read_torrents, download, upload, post
+    pub scopes: Vec<String>,
+    /// IP addresses or CIDR ranges the token may be used from; empty means any
+    pub allowed_ips: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiToken> for ApiTokenResponse {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            token_prefix: token.token_prefix,
+            scopes: token
+                .scopes
+                .iter()
+                .map(|s| s.as_str().to_string())
+                .collect(),
+            allowed_ips: token.allowed_ips,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+            last_used_ip: token.last_used_ip,
+            created_at: token.created_at,
+            revoked_at: token.revoked_at,
+        }
+    }
+}
+
+/// Newly created API token; the secret is only shown once
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct CreatedApiTokenResponse {
+    /// The token to configure in the script
+    pub token: String,
+    #[serde(flatten)]
+    pub details: ApiTokenResponse,
+}
+
+/// API token creation request
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct CreateApiTokenBody {
+    /// Name to recognize the token by
+    pub name: String,
+    /// Scopes to grant: read_torrents, download, upload, post
+    pub scopes: Vec<String>,
+    /// Lifetime in days (default 90, at most 365)
+    pub expires_in_days: Option<u32>,
+    /// IP addresses or CIDR ranges the token may be used from
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+}
+
+/// Result of revoking every token
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RevokedApiTokensResponse {
+    /// Number of tokens that were still active
+    pub revoked: u64,
+}
+
+/// Configure API token routes
+pub fn routes() -> Router<Arc<ApiState>> {
+    Router::new()
+        .route(
+            "/",
+            get(list_api_tokens)
+                .post(create_api_token)
+                .delete(revoke_all_api_tokens),
+        )
+        .route("/:id", delete(revoke_api_token))
+}
+
+/// List the current user's API tokens
+#[utoipa::path(
+    get,
+    path = "/api/v1/api-tokens",
+    tag = "api-tokens",
+    responses(
+        (status = 200, description = "API tokens, newest first", body = Vec<ApiTokenResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn list_api_tokens(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ApiTokenResponse>>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    let tokens = state.api_tokens.list(user_id).await?;
+
+    Ok(Json(
+        tokens.into_iter().map(ApiTokenResponse::from).collect(),
+    ))
+}
+
+/// Create an API token
+#[utoipa::path(
+    post,
+    path = "/api/v1/api-tokens",
+    tag = "api-tokens",
+    request_body = CreateApiTokenBody,
+    responses(
+        (status = 201, description = "Token created; it is not shown again", body = CreatedApiTokenResponse),
+        (status = 400, description = "Invalid request or too many tokens", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "No API access or scope not allowed", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn create_api_token(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(body): Json<CreateApiTokenBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    let scopes = body
+        .scopes
+        .iter()
+        .map(|scope| {
+            ApiTokenScope::parse(scope)
+                .ok_or_else(|| ApiError::ValidationError(format!("Unknown scope: {}", scope)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let created = state
+        .api_tokens
+        .create(
+            user_id,
+            CreateApiTokenRequest {
+                name: body.name,
+                scopes,
+                expires_in_days: body.expires_in_days,
+                allowed_ips: body.allowed_ips,
+            },
+        )
+        .await?;
+
+    let response = CreatedApiTokenResponse {
+        token: created.token,
+        details: created.details.into(),
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Revoke an API token
+#[utoipa::path(
+    delete,
+    path = "/api/v1/api-tokens/{id}",
+    tag = "api-tokens",
+    params(
+        ("id" = uuid::Uuid, Path, description = "API token ID")
+    ),
+    responses(
+        (status = 204, description = "Token revoked"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 404, description = "Token not found or already revoked", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn revoke_api_token(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    state.api_tokens.revoke(user_id, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Revoke all of the current user's API tokens
+#[utoipa::path(
+    delete,
+    path = "/api/v1/api-tokens",
+    tag = "api-tokens",
+    responses(
+        (status = 200, description = "Tokens revoked", body = RevokedApiTokensResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn revoke_all_api_tokens(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<RevokedApiTokensResponse>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    let revoked = state.api_tokens.revoke_all(user_id).await?;
+    tracing::info!("User {} revoked all {} API tokens", user_id, revoked);
+
+    Ok(Json(RevokedApiTokensResponse { revoked }))
+}
@@ -0,0 +1,549 @@
+//! # User Class REST Endpoints
+//!
+//! Lets admins manage user classes, move users between them and grant or
+//! deny single permissions to a user. All endpoints need the manage
+//! permissions permission; changes apply on the affected users' next
+//! request.
+
+use auth::{
+    ClassError, CreateClassRequest, Permission, PermissionOverride, SetOverrideRequest,
+    UpdateClassRequest, UserClass,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::instrument;
+
+use super::{require_permission, ErrorResponse};
+use crate::{ApiError, ApiState};
+
+/// User class response DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct UserClassResponse {
+    pub id: i32,
+    pub name: String,
+    pub display_name: String,
+    pub level: i32,
+    pub permissions: Vec<String>,
+}
+
+impl From<UserClass> for UserClassResponse {
+    fn from(class: UserClass) -> Self {
+        Self {
+            id: class.id,
+            name: class.name,
+            display_name: class.display_name,
+            level: class.level,
+            permissions: class
+                .permissions
+                .iter()
+                .map(|p| p.as_str().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Permission override response DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct PermissionOverrideResponse {
+    pub permission: String,
+    /// `true` if granted, `false` if denied
+    pub granted: bool,
+    pub reason: Option<String>,
+    pub created_by: Option<uuid::Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<PermissionOverride> for PermissionOverrideResponse {
+    fn from(o: PermissionOverride) -> Self {
+        Self {
+            permission: o.permission.as_str().to_string(),
+            granted: o.granted,
+            reason: o.reason,
+            created_by: o.created_by,
+            created_at: o.created_at,
+            expires_at: o.expires_at,
+        }
+    }
+}
+
+/// User class creation request
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct CreateClassBody {
+    /// Identifier: lowercase letters, digits and underscores
+    pub name: String,
+    pub display_name: String,
+    /// Rank; higher classes are more senior
+    pub level: i32,
+    /// Permission names, e.g. upload_torrent
+    pub permissions: Vec<String>,
+}
+
+/// User class update request; omitted fields are left as they are
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct UpdateClassBody {
+    pub display_name: Option<String>,
+    pub level: Option<i32>,
+    /// Replaces the class's permissions
+    pub permissions: Option<Vec<String>>,
+}
+
+/// Request to move a user to another class
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct SetUserClassBody {
+    pub class_id: i32,
+}
+
+/// Request to grant or deny a permission to a user
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct SetOverrideBody {
+    /// `true` grants the permission, `false` denies it
+    pub granted: bool,
+    pub reason: Option<String>,
+    /// When the override lapses; omit for a permanent one
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<ClassError> for ApiError {
+    fn from(e: ClassError) -> Self {
+        match e {
+            ClassError::NotFound | ClassError::UserNotFound => ApiError::NotFound(e.to_string()),
+            ClassError::NameTaken | ClassError::ClassInUse | ClassError::ValidationError(_) => {
+                ApiError::ValidationError(e.to_string())
+            }
+            ClassError::Database(msg) | ClassError::Redis(msg) => ApiError::InternalError(msg),
+        }
+    }
+}
+
+fn parse_permission(name: &str) -> Result<Permission, ApiError> {
+    Permission::parse(name)
+        .ok_or_else(|| ApiError::ValidationError(format!("Unknown permission: {}", name)))
+}
+
+fn parse_permission_list(names: &[String]) -> Result<Vec<Permission>, ApiError> {
+    names.iter().map(|name| parse_permission(name)).collect()
+}
+
+/// Configure user class routes
+pub fn routes() -> Router<Arc<ApiState>> {
+    Router::new()
+        .route("/", get(list_classes).post(create_class))
+        .route("/:id", get(get_class).patch(update_class).delete(delete_class))
+        .route("/users/:user_id", get(get_user_class).put(set_user_class))
+        .route("/users/:user_id/overrides", get(list_overrides))
+        .route(
+            "/users/:user_id/overrides/:permission",
+            put(set_override).delete(remove_override),
+        )
+}
+
+/// List user classes
+#[utoipa::path(
+    get,
+    path = "/api/v1/classes",
+    tag = "classes",
+    responses(
+        (status = 200, description = "User classes, lowest level first", body = Vec<UserClassResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn list_classes(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<UserClassResponse>>, ApiError> {
+    require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    let classes = state.classes.list_classes().await?;
+
+    Ok(Json(
+        classes.into_iter().map(UserClassResponse::from).collect(),
+    ))
+}
+
+/// Create a user class
+#[utoipa::path(
+    post,
+    path = "/api/v1/classes",
+    tag = "classes",
+    request_body = CreateClassBody,
+    responses(
+        (status = 201, description = "Class created", body = UserClassResponse),
+        (status = 400, description = "Invalid name, duplicate name or unknown permission", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn create_class(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(body): Json<CreateClassBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    let class = state
+        .classes
+        .create_class(CreateClassRequest {
+            name: body.name,
+            display_name: body.display_name,
+            level: body.level,
+            permissions: parse_permission_list(&body.permissions)?,
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(UserClassResponse::from(class))))
+}
+
+/// Get a user class
+#[utoipa::path(
+    get,
+    path = "/api/v1/classes/{id}",
+    tag = "classes",
+    params(
+        ("id" = i32, Path, description = "Class ID")
+    ),
+    responses(
+        (status = 200, description = "User class", body = UserClassResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse),
+        (status = 404, description = "Class not found", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn get_class(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<Json<UserClassResponse>, ApiError> {
+    require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    let class = state.classes.get_class(id).await?;
+
+    Ok(Json(class.into()))
+}
+
+/// Update a user class
+#[utoipa::path(
+    patch,
+    path = "/api/v1/classes/{id}",
+    tag = "classes",
+    params(
+        ("id" = i32, Path, description = "Class ID")
+    ),
+    request_body = UpdateClassBody,
+    responses(
+        (status = 200, description = "Class updated; members' permissions change at once", body = UserClassResponse),
+        (status = 400, description = "Invalid display name or unknown permission", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse),
+        (status = 404, description = "Class not found", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn update_class(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateClassBody>,
+) -> Result<Json<UserClassResponse>, ApiError> {
+    require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    let permissions = body
+        .permissions
+        .as_deref()
+        .map(parse_permission_list)
+        .transpose()?;
+
+    let class = state
+        .classes
+        .update_class(
+            id,
+            UpdateClassRequest {
+                display_name: body.display_name,
+                level: body.level,
+                permissions,
+            },
+        )
+        .await?;
+
+    Ok(Json(class.into()))
+}
+
+/// Delete a user class
+#[utoipa::path(
+    delete,
+    path = "/api/v1/classes/{id}",
+    tag = "classes",
+    params(
+        ("id" = i32, Path, description = "Class ID")
+    ),
+    responses(
+        (status = 204, description = "Class deleted"),
+        (status = 400, description = "Class still has members", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse),
+        (status = 404, description = "Class not found", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn delete_class(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    state.classes.delete_class(id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get the class a user belongs to
+#[utoipa::path(
+    get,
+    path = "/api/v1/classes/users/{user_id}",
+    tag = "classes",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "The user's class", body = UserClassResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn get_user_class(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<uuid::Uuid>,
+) -> Result<Json<UserClassResponse>, ApiError> {
+    require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    let class = state.classes.user_class(user_id).await?;
+
+    Ok(Json(class.into()))
+}
+
+/// Move a user to another class
+#[utoipa::path(
+    put,
+    path = "/api/v1/classes/users/{user_id}",
+    tag = "classes",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    request_body = SetUserClassBody,
+    responses(
+        (status = 204, description = "User moved; new permissions apply on their next request"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse),
+        (status = 404, description = "User or class not found", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn set_user_class(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<uuid::Uuid>,
+    Json(body): Json<SetUserClassBody>,
+) -> Result<StatusCode, ApiError> {
+    let admin_id = require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    state.classes.set_user_class(user_id, body.class_id).await?;
+
+    tracing::info!(
+        "Admin {} moved user {} to class {}",
+        admin_id,
+        user_id,
+        body.class_id
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List a user's permission overrides
+#[utoipa::path(
+    get,
+    path = "/api/v1/classes/users/{user_id}/overrides",
+    tag = "classes",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Overrides, including expired ones", body = Vec<PermissionOverrideResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn list_overrides(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<uuid::Uuid>,
+) -> Result<Json<Vec<PermissionOverrideResponse>>, ApiError> {
+    require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    let overrides = state.classes.list_overrides(user_id).await?;
+
+    Ok(Json(
+        overrides
+            .into_iter()
+            .map(PermissionOverrideResponse::from)
+            .collect(),
+    ))
+}
+
+/// Grant or deny a permission to a user
+#[utoipa::path(
+    put,
+    path = "/api/v1/classes/users/{user_id}/overrides/{permission}",
+    tag = "classes",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID"),
+        ("permission" = String, Path, description = "Permission name, e.g. upload_torrent")
+    ),
+    request_body = SetOverrideBody,
+    responses(
+        (status = 204, description = "Override saved, replacing any earlier one"),
+        (status = 400, description = "Unknown permission or expiry in the past", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn set_override(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path((user_id, permission)): Path<(uuid::Uuid, String)>,
+    Json(body): Json<SetOverrideBody>,
+) -> Result<StatusCode, ApiError> {
+    let admin_id = require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    state
+        .classes
+        .set_override(
+            admin_id,
+            user_id,
+            SetOverrideRequest {
+                permission: parse_permission(&permission)?,
+                granted: body.granted,
+                reason: body.reason,
+                expires_at: body.expires_at,
+            },
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove a user's permission override
+#[utoipa::path(
+    delete,
+    path = "/api/v1/classes/users/{user_id}/overrides/{permission}",
+    tag = "classes",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID"),
+        ("permission" = String, Path, description = "Permission name")
+    ),
+    responses(
+        (status = 204, description = "Override removed; the class's permission applies again"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage permissions required", body = ErrorResponse),
+        (status = 404, description = "No override for this permission", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn remove_override(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path((user_id, permission)): Path<(uuid::Uuid, String)>,
+) -> Result<StatusCode, ApiError> {
+    require_permission(&state, &headers, Permission::ManagePermissions).await?;
+
+    state
+        .classes
+        .remove_override(user_id, parse_permission(&permission)?)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_permission_list() {
+        let names = vec!["download".to_string(), "upload_torrent".to_string()];
+        assert_eq!(
+            parse_permission_list(&names).unwrap(),
+            vec![Permission::Download, Permission::UploadTorrent]
+        );
+
+        let names = vec!["download".to_string(), "root".to_string()];
+        assert!(matches!(
+            parse_permission_list(&names),
+            Err(ApiError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_class_error_mapping() {
+        assert!(matches!(
+            ApiError::from(ClassError::NotFound),
+            ApiError::NotFound(_)
+        ));
+        assert!(matches!(
+            ApiError::from(ClassError::ClassInUse),
+            ApiError::ValidationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(ClassError::Redis("down".to_string())),
+            ApiError::InternalError(_)
+        ));
+    }
+}
@@ -0,0 +1,250 @@
+//! # Email REST Endpoints
+//!
+//! Unsubscribe links from transactional email. The links are signed, so
+//! these endpoints need no session: mail clients POST to them for one-click
+//! unsubscribe (RFC 8058), and the frontend's unsubscribe page checks a link
+//! with GET before the user confirms.
+//!
+//! The mail server or delivery provider forwards bounces and complaints to
+//! the bounce intake, authenticated with the shared `X-Bounce-Secret` header.
+
+use auth::{BounceReport, EmailCategory, EmailError};
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::instrument;
+
+use super::ErrorResponse;
+use crate::{ApiError, ApiState};
+
+/// Parameters carried by an unsubscribe link
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+pub struct UnsubscribeParams {
+    /// Address the email was sent to
+    pub address: String,
+    /// Email category: security_alerts, digests, invites or reseeds
+    pub category: String,
+    /// Signature from the link
+    pub token: String,
+}
+
+/// Unsubscribe link details
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct UnsubscribeResponse {
+    pub address: String,
+    pub category: String,
+    /// Whether the address is now unsubscribed (false when only checked)
+    pub unsubscribed: bool,
+}
+
+impl From<EmailError> for ApiError {
+    fn from(e: EmailError) -> Self {
+        match e {
+            EmailError::InvalidAddress(_)
+            | EmailError::InvalidUnsubscribeToken
+            | EmailError::NotUnsubscribable => ApiError::ValidationError(e.to_string()),
+            EmailError::InvalidBounceSecret => ApiError::AuthenticationError(e.to_string()),
+            EmailError::Template(msg) | EmailError::Io(msg) | EmailError::Database(msg) => {
+                ApiError::InternalError(msg)
+            }
+        }
+    }
+}
+
+fn parse_category(name: &str) -> Result<EmailCategory, ApiError> {
+    EmailCategory::parse(name)
+        .ok_or_else(|| ApiError::ValidationError(format!("Unknown email category: {}", name)))
+}
+
+/// Result of a bounce report
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct BounceIntakeResponse {
+    /// Bounces and complaints found in the report
+    pub recorded: usize,
+    /// Addresses suppressed as a result
+    pub suppressed: usize,
+}
+
+/// Header carrying the bounce intake secret
+const BOUNCE_SECRET_HEADER: &str = "x-bounce-secret";
+
+/// Bounces from a request body: JSON reports, or a raw DSN or ARF message
+fn parse_bounce_body(headers: &HeaderMap, body: &str) -> Result<Vec<BounceReport>, ApiError> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+
+    if is_json {
+        serde_json::from_str(body)
+            .map_err(|e| ApiError::ValidationError(format!("Invalid bounce reports: {}", e)))
+    } else {
+        Ok(auth::parse_report(body))
+    }
+}
+
+/// Configure email routes
+pub fn routes() -> Router<Arc<ApiState>> {
+    Router::new()
+        .route("/unsubscribe", get(check_unsubscribe).post(unsubscribe))
+        .route("/bounces", post(report_bounces))
+}
+
+/// Check an unsubscribe link without acting on it
+#[utoipa::path(
+    get,
+    path = "/api/v1/email/unsubscribe",
+    tag = "email",
+    params(UnsubscribeParams),
+    responses(
+        (status = 200, description = "Link is valid", body = UnsubscribeResponse),
+        (status = 400, description = "Invalid link or category", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state))]
+async fn check_unsubscribe(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<UnsubscribeParams>,
+) -> Result<Json<UnsubscribeResponse>, ApiError> {
+    let category = parse_category(&params.category)?;
+    state
+        .email
+        .verify_unsubscribe_token(&params.address, category, &params.token)?;
+
+    Ok(Json(UnsubscribeResponse {
+        address: params.address,
+        category: params.category,
+        unsubscribed: false,
+    }))
+}
+
+/// Unsubscribe an address from an email category
+#[utoipa::path(
+    post,
+    path = "/api/v1/email/unsubscribe",
+    tag = "email",
+    params(UnsubscribeParams),
+    responses(
+        (status = 200, description = "Unsubscribed", body = UnsubscribeResponse),
+        (status = 400, description = "Invalid link or category", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state))]
+async fn unsubscribe(
+    State(state): State<Arc<ApiState>>,
+    Query(params): Query<UnsubscribeParams>,
+) -> Result<Json<UnsubscribeResponse>, ApiError> {
+    let category = parse_category(&params.category)?;
+
+    state
+        .email
+        .unsubscribe(&params.address, category, &params.token)
+        .await?;
+
+    Ok(Json(UnsubscribeResponse {
+        address: params.address,
+        category: params.category,
+        unsubscribed: true,
+    }))
+}
+
+/// Report bounces and complaints received after delivery
+///
+/// Takes either a delivery status notification or ARF complaint as
+/// `message/rfc822` (or any non-JSON type), or a JSON array of
+/// `{address, kind, reason}` objects from a delivery provider's webhook.
+#[utoipa::path(
+    post,
+    path = "/api/v1/email/bounces",
+    tag = "email",
+    request_body(content = String, description = "DSN or ARF message, or JSON bounce reports"),
+    params(
+        ("X-Bounce-Secret" = String, Header, description = "Shared bounce intake secret")
+    ),
+    responses(
+        (status = 200, description = "Reports recorded", body = BounceIntakeResponse),
+        (status = 400, description = "Malformed JSON reports", body = ErrorResponse),
+        (status = 401, description = "Missing or wrong secret", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn report_bounces(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<BounceIntakeResponse>, ApiError> {
+    let secret = headers
+        .get(BOUNCE_SECRET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    state.email.verify_bounce_secret(secret)?;
+
+    let reports = parse_bounce_body(&headers, &body)?;
+    let suppressed = state.email.record_bounces(&reports).await?;
+
+    Ok(Json(BounceIntakeResponse {
+        recorded: reports.len(),
+        suppressed,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_category() {
+        assert_eq!(parse_category("digests").unwrap(), EmailCategory::Digests);
+        assert!(matches!(
+            parse_category("newsletters"),
+            Err(ApiError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_bounce_body() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+
+        let reports = parse_bounce_body(
+            &headers,
+            r#"[{"address": "gone@example.test", "kind": "hard", "reason": "550"}]"#,
+        )
+        .unwrap();
+        assert_eq!(reports[0].kind, auth::BounceKind::Hard);
+
+        assert!(matches!(
+            parse_bounce_body(&headers, "Final-Recipient: rfc822; gone@example.test"),
+            Err(ApiError::ValidationError(_))
+        ));
+
+        let dsn = "Final-Recipient: rfc822; gone@example.test\nAction: failed\nStatus: 5.1.1\n";
+        let reports = parse_bounce_body(&HeaderMap::new(), dsn).unwrap();
+        assert_eq!(reports[0].address, "gone@example.test");
+    }
+
+    #[test]
+    fn test_email_error_mapping() {
+        assert!(matches!(
+            ApiError::from(EmailError::InvalidUnsubscribeToken),
+            ApiError::ValidationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(EmailError::NotUnsubscribable),
+            ApiError::ValidationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(EmailError::InvalidBounceSecret),
+            ApiError::AuthenticationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(EmailError::Database("down".to_string())),
+            ApiError::InternalError(_)
+        ));
+    }
+}
@@ -7,11 +7,28 @@
 //! - **Versioning**: All endpoints are versioned (e.g., /api/v1/)
 //! - **OpenAPI**: Auto-generated documentation
 //! - **Rate Limiting**: Per-endpoint and per-user limits
-//! - **Authentication**: JWT-based authentication
+//! - **Authentication**: JWT-based authentication, or scoped API tokens for scripts
+//! - **OAuth / OpenID Connect**: Sign-in with a tracker account for sister sites
+//! - **Requests**: Rejecting request fills while their bounty is in escrow
+//! - **Upload Rules**: Per-category upload rule editing for staff
+//! - **User Classes**: Class and permission override management for admins
+//! - **Email**: One-click unsubscribe from email categories and bounce intake
+//! - **Passwords**: Password change and reset, and breached password rescreening
+//! - **Sign-in Security**: Approving risky sign-ins and reporting sign-ins that weren't the user
+//! - **Security Keys**: Registering security keys and passkeys, and signing in with a passkey
 //! - **Pagination**: Cursor-based and offset-based pagination
 //! - **Filtering**: Query parameters for filtering and sorting
 
+pub mod api_tokens;
+pub mod classes;
+pub mod email;
+pub mod oauth;
+pub mod passwords;
+pub mod requests;
+pub mod security;
+pub mod security_keys;
 pub mod torrents;
+pub mod upload_rules;
 pub mod users;
 
 use axum::{
@@ -34,8 +51,28 @@ pub fn configure_routes(app: Router<Arc<ApiState>>, state: Arc<ApiState>) -> Rou
         .route("/api/v1", get(api_version))
         // Torrent endpoints
         .nest("/api/v1/torrents", torrents::routes())
+        // Request fill endpoints
+        .nest("/api/v1/requests", requests::routes())
+        // Upload rule management endpoints
+        .nest("/api/v1/upload-rules", upload_rules::routes())
         // User endpoints
         .nest("/api/v1/users", users::routes())
+        // Personal API token endpoints
+        .nest("/api/v1/api-tokens", api_tokens::routes())
+        // OAuth consent and client management endpoints
+        .nest("/api/v1/oauth", oauth::routes())
+        // User class and permission override endpoints
+        .nest("/api/v1/classes", classes::routes())
+        // Email unsubscribe and bounce intake endpoints
+        .nest("/api/v1/email", email::routes())
+        // Password change, reset and breach screening endpoints
+        .nest("/api/v1/passwords", passwords::routes())
+        // Sign-in approval and "this wasn't me" endpoints
+        .nest("/api/v1/security", security::routes())
+        // Security key registration and passkey sign-in endpoints
+        .nest("/api/v1/security-keys", security_keys::routes())
+        // OAuth / OpenID Connect protocol endpoints
+        .merge(oauth::protocol_routes())
 }
 
 /// API version information
@@ -62,7 +99,16 @@ async fn api_version() -> Json<ApiVersion> {
         api_version: "v1".to_string(),
         endpoints: vec![
             "/api/v1/torrents".to_string(),
+            "/api/v1/requests".to_string(),
+            "/api/v1/upload-rules".to_string(),
             "/api/v1/users".to_string(),
+            "/api/v1/api-tokens".to_string(),
+            "/api/v1/oauth".to_string(),
+            "/api/v1/classes".to_string(),
+            "/api/v1/email".to_string(),
+            "/api/v1/passwords".to_string(),
+            "/api/v1/security".to_string(),
+            "/api/v1/security-keys".to_string(),
         ],
     })
 }
@@ -162,14 +208,78 @@ pub async fn extract_user_id(
 }
 
 /// Extract user ID from JWT token, or return error if not authenticated
+///
+/// API tokens are not accepted here; endpoints open to scripts use
+/// [`require_scope`] instead.
 pub async fn require_auth(
     headers: &axum::http::HeaderMap,
 ) -> Result<uuid::Uuid, ApiError> {
+    if crate::caller::api_token(headers).is_some() {
+        return Err(ApiError::AuthorizationError(
+            "This endpoint cannot be used with an API token".to_string(),
+        ));
+    }
+
     extract_user_id(headers)
         .await
         .ok_or_else(|| ApiError::AuthenticationError("Authentication required".to_string()))
 }
 
+/// Extract user ID from an API token with `scope`, or from the session
+///
+/// Either way the user must still hold the permission the scope stands
+/// for, so a token outlives neither a demotion nor a denial.
+pub async fn require_scope(
+    state: &ApiState,
+    headers: &axum::http::HeaderMap,
+    client_ip: crate::caller::ClientIp,
+    scope: auth::ApiTokenScope,
+) -> Result<uuid::Uuid, ApiError> {
+    let Some(token) = crate::caller::api_token(headers) else {
+        let user_id = require_auth(headers).await?;
+
+        if let Some(permission) = scope.required_permission() {
+            if !state.permissions.resolve(user_id).await?.has(permission) {
+                return Err(ApiError::AuthorizationError(format!(
+                    "{} permission required",
+                    permission
+                )));
+            }
+        }
+
+        return Ok(user_id);
+    };
+
+    let principal = crate::caller::authenticate_api_token(state, token, client_ip).await?;
+    if !principal.has_scope(scope) {
+        return Err(ApiError::AuthorizationError(format!(
+            "API token is missing the {} scope",
+            scope
+        )));
+    }
+
+    Ok(principal.user_id)
+}
+
+/// Require a signed-in user holding `permission`
+pub async fn require_permission(
+    state: &ApiState,
+    headers: &axum::http::HeaderMap,
+    permission: auth::Permission,
+) -> Result<uuid::Uuid, ApiError> {
+    let user_id = require_auth(headers).await?;
+
+    let permissions = state.permissions.resolve(user_id).await?;
+    if !permissions.has(permission) {
+        return Err(ApiError::AuthorizationError(format!(
+            "{} permission required",
+            permission
+        )));
+    }
+
+    Ok(user_id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,445 @@
+//! # OAuth 2.1 / OpenID Connect Endpoints
+//!
+//! Lets sister sites sign members in with their tracker account.
+//!
+//! - `/.well-known/openid-configuration`, `/oauth/token` and
+//!   `/oauth/userinfo` are the protocol endpoints clients talk to; they
+//!   answer with OAuth error bodies rather than [`ErrorResponse`].
+//! - `/api/v1/oauth/authorize` backs the consent screen at
+//!   `/oauth/authorize` in the frontend, which clients redirect users to.
+//! - `/api/v1/oauth/clients` is where admins register clients.
+
+use auth::{
+    AuthorizationRequest, AuthorizeOutcome, OAuthClient, OAuthError, Permission,
+    RegisterClientRequest, TokenRequest,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Form, Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::instrument;
+
+use super::{require_auth, require_permission, ErrorResponse};
+use crate::{ApiError, ApiState};
+
+/// Configure the protocol endpoints, mounted at the site root
+pub fn protocol_routes() -> Router<Arc<ApiState>> {
+    Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(openid_configuration),
+        )
+        .route("/oauth/token", post(token))
+        .route("/oauth/userinfo", get(userinfo).post(userinfo))
+}
+
+/// Configure the consent and client management endpoints
+pub fn routes() -> Router<Arc<ApiState>> {
+    Router::new()
+        .route("/authorize", get(authorize).post(decide))
+        .route("/clients", get(list_clients).post(register_client))
+        .route("/clients/:client_id", delete(revoke_client))
+}
+
+/// OAuth client response DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct OAuthClientResponse {
+    pub client_id: String,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub allowed_scopes: Vec<String>,
+    /// Whether the client authenticates with a secret
+    pub confidential: bool,
+    pub created_by: uuid::Uuid,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<OAuthClient> for OAuthClientResponse {
+    fn from(client: OAuthClient) -> Self {
+        Self {
+            client_id: client.client_id,
+            name: client.name,
+            redirect_uris: client.redirect_uris,
+            allowed_scopes: client.allowed_scopes,
+            confidential: client.confidential,
+            created_by: client.created_by,
+            created_at: client.created_at,
+            revoked_at: client.revoked_at,
+        }
+    }
+}
+
+/// Newly registered client; the secret is only shown once
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RegisteredOAuthClientResponse {
+    /// Secret for the token endpoint; absent for public clients
+    pub client_secret: Option<String>,
+    #[serde(flatten)]
+    pub client: OAuthClientResponse,
+}
+
+/// Client registration request
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct RegisterOAuthClientBody {
+    pub name: String,
+    /// Exact redirect URIs; https, or http on loopback
+    pub redirect_uris: Vec<String>,
+    /// Scopes the client may request: openid, profile, email or permission names
+    pub allowed_scopes: Vec<String>,
+    /// Issue a client secret (default true); public clients rely on PKCE alone
+    pub confidential: Option<bool>,
+}
+
+/// The user's answer on the consent screen
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ConsentDecisionBody {
+    /// The authorization request, as received by the consent screen
+    #[serde(flatten)]
+    #[schema(value_type = Object)]
+    pub request: AuthorizationRequest,
+    pub approve: bool,
+}
+
+impl From<OAuthError> for ApiError {
+    fn from(e: OAuthError) -> Self {
+        match e {
+            OAuthError::ClientNotFound => ApiError::NotFound(e.to_string()),
+            OAuthError::InvalidToken | OAuthError::InvalidClient => {
+                ApiError::AuthenticationError(e.to_string())
+            }
+            OAuthError::Database(msg) | OAuthError::Redis(msg) | OAuthError::Signing(msg) => {
+                ApiError::InternalError(msg)
+            }
+            _ => ApiError::ValidationError(e.to_string()),
+        }
+    }
+}
+
+/// OAuth error response for the token and userinfo endpoints
+struct OAuthErrorResponse(OAuthError);
+
+impl IntoResponse for OAuthErrorResponse {
+    fn into_response(self) -> Response {
+        let error = self.0;
+        let status = match error {
+            OAuthError::InvalidClient | OAuthError::InvalidToken => StatusCode::UNAUTHORIZED,
+            OAuthError::Database(_) | OAuthError::Redis(_) | OAuthError::Signing(_) => {
+                tracing::error!("OAuth error: {}", error);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => StatusCode::BAD_REQUEST,
+        };
+
+        let mut response = (status, no_store(), Json(error.to_body())).into_response();
+        match error {
+            OAuthError::InvalidClient => {
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static("Basic realm=\"oauth\""),
+                );
+            }
+            OAuthError::InvalidToken => {
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static("Bearer error=\"invalid_token\""),
+                );
+            }
+            _ => {}
+        }
+        response
+    }
+}
+
+/// Token responses must not be cached (RFC 6749 section 5.1)
+fn no_store() -> [(header::HeaderName, &'static str); 2] {
+    [
+        (header::CACHE_CONTROL, "no-store"),
+        (header::PRAGMA, "no-cache"),
+    ]
+}
+
+/// Client credentials from an `Authorization: Basic` header
+///
+/// Client IDs and secrets we issue are URL-safe, so the form-encoding of
+/// RFC 6749 section 2.3.1 never changes them.
+fn basic_auth(headers: &HeaderMap) -> Option<(String, String)> {
+    let encoded = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (client_id, secret) = decoded.split_once(':')?;
+    Some((client_id.to_string(), secret.to_string()))
+}
+
+/// Access token from an `Authorization: Bearer` header
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// OpenID Connect discovery document
+async fn openid_configuration(State(state): State<Arc<ApiState>>) -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "public, max-age=3600")],
+        Json(state.oauth.config().metadata()),
+    )
+}
+
+/// Exchange an authorization code for tokens
+#[instrument(skip(state, headers, request))]
+async fn token(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Form(request): Form<TokenRequest>,
+) -> Response {
+    match state
+        .oauth
+        .exchange_code(request, basic_auth(&headers))
+        .await
+    {
+        Ok(tokens) => (no_store(), Json(tokens)).into_response(),
+        Err(e) => OAuthErrorResponse(e).into_response(),
+    }
+}
+
+/// Claims about the user an access token was issued for
+#[instrument(skip(state, headers))]
+async fn userinfo(State(state): State<Arc<ApiState>>, headers: HeaderMap) -> Response {
+    let Some(token) = bearer_token(&headers) else {
+        return OAuthErrorResponse(OAuthError::InvalidToken).into_response();
+    };
+
+    match state.oauth.userinfo(token).await {
+        Ok(info) => Json(info).into_response(),
+        Err(e) => OAuthErrorResponse(e).into_response(),
+    }
+}
+
+/// Start an authorization request for the signed-in user
+///
+/// Returns the consent prompt, or where to send the user if they already
+/// approved the requested scopes.
+#[utoipa::path(
+    get,
+    path = "/api/v1/oauth/authorize",
+    tag = "oauth",
+    params(
+        ("response_type" = String, Query, description = "Must be code"),
+        ("client_id" = String, Query, description = "Client ID"),
+        ("redirect_uri" = String, Query, description = "A registered redirect URI"),
+        ("scope" = String, Query, description = "Space-separated scopes"),
+        ("state" = Option<String>, Query, description = "Opaque client state"),
+        ("code_challenge" = String, Query, description = "PKCE S256 challenge"),
+        ("code_challenge_method" = String, Query, description = "Must be S256"),
+        ("nonce" = Option<String>, Query, description = "Echoed in the ID token"),
+        ("prompt" = Option<String>, Query, description = "consent to always ask")
+    ),
+    responses(
+        (status = 200, description = "Consent prompt (type consent) or redirect (type redirect)"),
+        (status = 400, description = "Unknown client or redirect URI", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn authorize(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Query(request): Query<AuthorizationRequest>,
+) -> Result<Json<AuthorizeOutcome>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    Ok(Json(state.oauth.authorize(user_id, &request).await?))
+}
+
+/// Approve or deny an authorization request
+#[utoipa::path(
+    post,
+    path = "/api/v1/oauth/authorize",
+    tag = "oauth",
+    request_body = ConsentDecisionBody,
+    responses(
+        (status = 200, description = "Where to send the user (type redirect)"),
+        (status = 400, description = "Unknown client or redirect URI", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn decide(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(body): Json<ConsentDecisionBody>,
+) -> Result<Json<AuthorizeOutcome>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    Ok(Json(
+        state
+            .oauth
+            .decide(user_id, &body.request, body.approve)
+            .await?,
+    ))
+}
+
+/// List registered OAuth clients
+#[utoipa::path(
+    get,
+    path = "/api/v1/oauth/clients",
+    tag = "oauth",
+    responses(
+        (status = 200, description = "OAuth clients, newest first", body = Vec<OAuthClientResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Site admins only", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn list_clients(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<OAuthClientResponse>>, ApiError> {
+    require_permission(&state, &headers, Permission::SiteAdmin).await?;
+
+    let clients = state.oauth.list_clients().await?;
+
+    Ok(Json(
+        clients.into_iter().map(OAuthClientResponse::from).collect(),
+    ))
+}
+
+/// Register an OAuth client
+#[utoipa::path(
+    post,
+    path = "/api/v1/oauth/clients",
+    tag = "oauth",
+    request_body = RegisterOAuthClientBody,
+    responses(
+        (status = 201, description = "Client registered; the secret is not shown again", body = RegisteredOAuthClientResponse),
+        (status = 400, description = "Invalid redirect URI or scope", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Site admins only", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn register_client(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(body): Json<RegisterOAuthClientBody>,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin_id = require_permission(&state, &headers, Permission::SiteAdmin).await?;
+
+    let registered = state
+        .oauth
+        .register_client(
+            admin_id,
+            RegisterClientRequest {
+                name: body.name,
+                redirect_uris: body.redirect_uris,
+                allowed_scopes: body.allowed_scopes,
+                confidential: body.confidential.unwrap_or(true),
+            },
+        )
+        .await?;
+
+    let response = RegisteredOAuthClientResponse {
+        client_secret: registered.client_secret,
+        client: registered.client.into(),
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Revoke an OAuth client
+#[utoipa::path(
+    delete,
+    path = "/api/v1/oauth/clients/{client_id}",
+    tag = "oauth",
+    params(
+        ("client_id" = String, Path, description = "Client ID")
+    ),
+    responses(
+        (status = 204, description = "Client revoked and its consents dropped"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Site admins only", body = ErrorResponse),
+        (status = 404, description = "Client not found or already revoked", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn revoke_client(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(client_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    require_permission(&state, &headers, Permission::SiteAdmin).await?;
+
+    state.oauth.revoke_client(&client_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic_auth() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(basic_auth(&headers), None);
+
+        let encoded = STANDARD.encode("wiki:s3cret-value");
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Basic {}", encoded)).unwrap(),
+        );
+        assert_eq!(
+            basic_auth(&headers),
+            Some(("wiki".to_string(), "s3cret-value".to_string()))
+        );
+        assert_eq!(bearer_token(&headers), None);
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer eyJhbGciOi"),
+        );
+        assert_eq!(basic_auth(&headers), None);
+        assert_eq!(bearer_token(&headers), Some("eyJhbGciOi"));
+    }
+
+    #[test]
+    fn test_oauth_error_response() {
+        let response = OAuthErrorResponse(OAuthError::InvalidClient).into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key(header::WWW_AUTHENTICATE));
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+
+        let response =
+            OAuthErrorResponse(OAuthError::InvalidGrant("expired".to_string())).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,281 @@
+//! # Password REST Endpoints
+//!
+//! Changing a password while signed in, the forgotten password flow, and
+//! admin tools for breached password screening. New passwords are checked
+//! against the strength policy and the local breach corpus.
+
+use auth::{BreachError, CredentialError, PasswordError, Permission};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::instrument;
+
+use super::{require_auth, require_permission, ErrorResponse};
+use crate::{ApiError, ApiState};
+
+/// Request to change the signed-in user's password
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ChangePasswordBody {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// Request for a password reset link
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ForgotPasswordBody {
+    pub email: String,
+}
+
+/// Request to set a new password from a reset link
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ResetPasswordBody {
+    /// Token from the reset link
+    pub token: String,
+    pub new_password: String,
+}
+
+/// Request to rescreen passwords after a corpus update
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct RescreenBody {
+    /// Accounts to rescreen; omit for every account
+    pub user_ids: Option<Vec<uuid::Uuid>>,
+}
+
+/// Rescreen result
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RescreenResponse {
+    /// Accounts whose password will be checked at their next login
+    pub accounts: u64,
+}
+
+impl From<CredentialError> for ApiError {
+    fn from(e: CredentialError) -> Self {
+        match e {
+            CredentialError::InvalidCurrentPassword => ApiError::AuthenticationError(e.to_string()),
+            CredentialError::InvalidResetToken => ApiError::ValidationError(e.to_string()),
+            CredentialError::UserNotFound => ApiError::NotFound(e.to_string()),
+            CredentialError::Password(PasswordError::WeakPassword(_))
+            | CredentialError::Password(PasswordError::Breached(_)) => {
+                ApiError::ValidationError(e.to_string())
+            }
+            CredentialError::Password(err) => ApiError::InternalError(err.to_string()),
+            CredentialError::Email(msg)
+            | CredentialError::SignOut(msg)
+            | CredentialError::Database(msg) => ApiError::InternalError(msg),
+        }
+    }
+}
+
+impl From<BreachError> for ApiError {
+    fn from(e: BreachError) -> Self {
+        match e {
+            BreachError::UserNotFound => ApiError::NotFound(e.to_string()),
+            BreachError::CorpusNotFound(msg)
+            | BreachError::Io(msg)
+            | BreachError::SignOut(msg)
+            | BreachError::Database(msg) => ApiError::InternalError(msg),
+        }
+    }
+}
+
+/// Configure password routes
+pub fn routes() -> Router<Arc<ApiState>> {
+    Router::new()
+        .route("/change", post(change_password))
+        .route("/forgot", post(forgot_password))
+        .route("/reset", post(reset_password))
+        .route("/rescreen", post(rescreen))
+        .route("/users/:user_id/force-reset", post(force_reset))
+}
+
+/// Change the signed-in user's password
+#[utoipa::path(
+    post,
+    path = "/api/v1/passwords/change",
+    tag = "passwords",
+    request_body = ChangePasswordBody,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 400, description = "New password is too weak or has been breached", body = ErrorResponse),
+        (status = 401, description = "Unauthorized or current password incorrect", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn change_password(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(body): Json<ChangePasswordBody>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    state
+        .passwords
+        .change_password(user_id, &body.current_password, &body.new_password)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Email a password reset link
+///
+/// Always succeeds, so the response doesn't reveal whether the address is
+/// registered.
+#[utoipa::path(
+    post,
+    path = "/api/v1/passwords/forgot",
+    tag = "passwords",
+    request_body = ForgotPasswordBody,
+    responses(
+        (status = 202, description = "Reset link sent if the address is registered")
+    )
+)]
+#[instrument(skip(state, body))]
+async fn forgot_password(
+    State(state): State<Arc<ApiState>>,
+    Json(body): Json<ForgotPasswordBody>,
+) -> Result<StatusCode, ApiError> {
+    state.passwords.request_reset(&body.email).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Set a new password from a reset link
+#[utoipa::path(
+    post,
+    path = "/api/v1/passwords/reset",
+    tag = "passwords",
+    request_body = ResetPasswordBody,
+    responses(
+        (status = 204, description = "Password reset"),
+        (status = 400, description = "Invalid or expired link, or the password is too weak or has been breached", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state, body))]
+async fn reset_password(
+    State(state): State<Arc<ApiState>>,
+    Json(body): Json<ResetPasswordBody>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = state
+        .passwords
+        .reset_password(&body.token, &body.new_password)
+        .await?;
+
+    tracing::info!("Password reset for user {}", user_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Check passwords against the breach corpus at each user's next login
+#[utoipa::path(
+    post,
+    path = "/api/v1/passwords/rescreen",
+    tag = "passwords",
+    request_body = RescreenBody,
+    responses(
+        (status = 200, description = "Accounts marked for rescreening", body = RescreenResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage users required", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn rescreen(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(body): Json<RescreenBody>,
+) -> Result<Json<RescreenResponse>, ApiError> {
+    let admin_id = require_permission(&state, &headers, Permission::ManageUsers).await?;
+
+    let accounts = state
+        .breach_screening
+        .request_rescreen(body.user_ids.as_deref())
+        .await?;
+
+    tracing::info!(
+        "Admin {} requested breached password rescreening of {} accounts",
+        admin_id,
+        accounts
+    );
+
+    Ok(Json(RescreenResponse { accounts }))
+}
+
+/// Require a user to reset their password before logging in again
+#[utoipa::path(
+    post,
+    path = "/api/v1/passwords/users/{user_id}/force-reset",
+    tag = "passwords",
+    params(
+        ("user_id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "User must reset their password"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Manage users required", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn force_reset(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<uuid::Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let admin_id = require_permission(&state, &headers, Permission::ManageUsers).await?;
+
+    state.breach_screening.force_reset(user_id).await?;
+
+    tracing::info!("Admin {} forced a password reset for {}", admin_id, user_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credential_error_mapping() {
+        assert!(matches!(
+            ApiError::from(CredentialError::InvalidCurrentPassword),
+            ApiError::AuthenticationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(CredentialError::Password(PasswordError::Breached(3))),
+            ApiError::ValidationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(CredentialError::Password(PasswordError::InvalidHashFormat)),
+            ApiError::InternalError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(CredentialError::InvalidResetToken),
+            ApiError::ValidationError(_)
+        ));
+    }
+
+    #[test]
+    fn test_breach_error_mapping() {
+        assert!(matches!(
+            ApiError::from(BreachError::UserNotFound),
+            ApiError::NotFound(_)
+        ));
+        assert!(matches!(
+            ApiError::from(BreachError::Database("down".to_string())),
+            ApiError::InternalError(_)
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+//! # Request REST Endpoints
+//!
+//! Challenging request fills. The requester can reject a fill while its
+//! bounty is still in escrow; torrent moderators can reject at any time,
+//! taking back a bounty that was already paid.
+
+use auth::Permission;
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    routing::post,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use torrent::{RejectFillInput, RequestFill, RequestService, TorrentConfig};
+use tracing::instrument;
+
+use super::{require_auth, ErrorResponse};
+use crate::{ApiError, ApiState};
+
+/// Request fill rejection body
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct RejectFillBody {
+    /// Why the torrent does not satisfy the request
+    pub reason: String,
+}
+
+/// Request fill DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RequestFillResponse {
+    pub id: uuid::Uuid,
+    pub request_id: uuid::Uuid,
+    pub torrent_id: uuid::Uuid,
+    pub filler_id: uuid::Uuid,
+    pub bounty: i64,
+    /// pending, paid or rejected
+    #[schema(value_type = String)]
+    pub status: torrent::FillStatus,
+    /// End of the challenge window
+    pub challenge_until: DateTime<Utc>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub rejected_by: Option<uuid::Uuid>,
+    pub rejection_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<RequestFill> for RequestFillResponse {
+    fn from(fill: RequestFill) -> Self {
+        Self {
+            id: fill.id,
+            request_id: fill.request_id,
+            torrent_id: fill.torrent_id,
+            filler_id: fill.filler_id,
+            bounty: fill.bounty,
+            status: fill.status,
+            challenge_until: fill.challenge_until,
+            paid_at: fill.paid_at,
+            rejected_by: fill.rejected_by,
+            rejection_reason: fill.rejection_reason,
+            created_at: fill.created_at,
+        }
+    }
+}
+
+/// Configure request routes
+pub fn routes() -> Router<Arc<ApiState>> {
+    Router::new().route("/:id/fill/reject", post(reject_fill))
+}
+
+/// Request service for the fill endpoints, which need no bounty limits
+fn request_service(state: &ApiState) -> RequestService {
+    let config = TorrentConfig::default();
+    RequestService::new(
+        state.db_pool.clone(),
+        config.request_min_bounty,
+        config.request_max_bounty_per_user,
+        config.request_expiry_days,
+        config.request_fill_challenge_hours,
+        config.request_expiry_refund_percent,
+    )
+}
+
+/// Reject a request fill and reopen the request
+#[utoipa::path(
+    post,
+    path = "/api/v1/requests/{id}/fill/reject",
+    tag = "requests",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Request ID")
+    ),
+    request_body = RejectFillBody,
+    responses(
+        (status = 200, description = "Fill rejected and request reopened", body = RequestFillResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 500, description = "Not filled, challenge window closed, or not the requester", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn reject_fill(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Json(body): Json<RejectFillBody>,
+) -> Result<Json<RequestFillResponse>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+    let is_staff = state
+        .permissions
+        .resolve(user_id)
+        .await?
+        .has(Permission::TorrentModerator);
+
+    let fill = request_service(&state)
+        .reject_fill(
+            user_id,
+            is_staff,
+            RejectFillInput {
+                request_id: id,
+                reason: body.reason,
+            },
+        )
+        .await?;
+
+    tracing::info!(
+        "Fill of request {} by torrent {} rejected by {}",
+        id,
+        fill.torrent_id,
+        user_id
+    );
+
+    Ok(Json(fill.into()))
+}
@@ -0,0 +1,222 @@
+//! # Sign-in Security REST Endpoints
+//!
+//! Targets of the links in sign-in emails. Approving a risky sign-in lets
+//! the device that attempted it sign in again with the confirmation ID it
+//! was given; "this wasn't me" signs the account out of every session. The
+//! links carry single-use tokens, so these endpoints need no session.
+//!
+//! Signed-in users also read and dismiss their security notifications here,
+//! such as a replayed refresh token or a reported sign-in.
+
+use auth::{LoginRiskError, NotificationError, SecurityNotifier};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::instrument;
+
+use super::{require_auth, ErrorResponse};
+use crate::{ApiError, ApiState};
+
+/// Token from a sign-in email link
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct SignInTokenBody {
+    pub token: String,
+}
+
+/// Result of reporting a sign-in
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct NotMeResponse {
+    /// Sessions that were signed out
+    pub sessions_revoked: usize,
+}
+
+/// Security notification DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SecurityNotificationResponse {
+    pub id: uuid::Uuid,
+    /// Event kind, e.g. `refresh_token_reuse` or `sign_in_reported`
+    pub event: String,
+    pub message: String,
+    /// IP address the event came from, if known
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<auth::SecurityNotification> for SecurityNotificationResponse {
+    fn from(notification: auth::SecurityNotification) -> Self {
+        Self {
+            id: notification.id,
+            event: notification.event,
+            message: notification.message,
+            ip_address: notification.ip_address,
+            user_agent: notification.user_agent,
+            created_at: notification.created_at,
+        }
+    }
+}
+
+impl From<NotificationError> for ApiError {
+    fn from(e: NotificationError) -> Self {
+        match e {
+            NotificationError::NotFound => ApiError::NotFound(e.to_string()),
+            NotificationError::Database(msg) => ApiError::InternalError(msg),
+        }
+    }
+}
+
+impl From<LoginRiskError> for ApiError {
+    fn from(e: LoginRiskError) -> Self {
+        match e {
+            LoginRiskError::InvalidToken => ApiError::ValidationError(e.to_string()),
+            LoginRiskError::EmailUnavailable => ApiError::InternalError(e.to_string()),
+            LoginRiskError::Email(msg)
+            | LoginRiskError::Session(msg)
+            | LoginRiskError::Database(msg) => ApiError::InternalError(msg),
+        }
+    }
+}
+
+/// Configure sign-in security routes
+pub fn routes() -> Router<Arc<ApiState>> {
+    Router::new()
+        .route("/sign-ins/approve", post(approve_sign_in))
+        .route("/sign-ins/not-me", post(report_not_me))
+        .route("/notifications", get(list_security_notifications))
+        .route(
+            "/notifications/:id/read",
+            post(mark_security_notification_read),
+        )
+}
+
+/// Approve a risky sign-in from the emailed link
+#[utoipa::path(
+    post,
+    path = "/api/v1/security/sign-ins/approve",
+    tag = "security",
+    request_body = SignInTokenBody,
+    responses(
+        (status = 204, description = "Sign-in approved; the device can now sign in"),
+        (status = 400, description = "Invalid, expired or already used link", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state, body))]
+async fn approve_sign_in(
+    State(state): State<Arc<ApiState>>,
+    Json(body): Json<SignInTokenBody>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = state.login_risk.approve_confirmation(&body.token).await?;
+
+    tracing::info!("User {} approved a sign-in", user_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Report a sign-in as not yours, signing out every session
+#[utoipa::path(
+    post,
+    path = "/api/v1/security/sign-ins/not-me",
+    tag = "security",
+    request_body = SignInTokenBody,
+    responses(
+        (status = 200, description = "Every session signed out", body = NotMeResponse),
+        (status = 400, description = "Invalid, expired or already used link", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state, body))]
+async fn report_not_me(
+    State(state): State<Arc<ApiState>>,
+    Json(body): Json<SignInTokenBody>,
+) -> Result<Json<NotMeResponse>, ApiError> {
+    let report = state.login_risk.report_not_me(&body.token).await?;
+
+    Ok(Json(NotMeResponse {
+        sessions_revoked: report.sessions_revoked,
+    }))
+}
+
+/// List the current user's unread security notifications, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/security/notifications",
+    tag = "security",
+    responses(
+        (status = 200, description = "Unread security notifications", body = Vec<SecurityNotificationResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn list_security_notifications(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<SecurityNotificationResponse>>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    let notifications = SecurityNotifier::new(state.db_pool.clone())
+        .unread(user_id)
+        .await?;
+
+    Ok(Json(notifications.into_iter().map(Into::into).collect()))
+}
+
+/// Dismiss a security notification
+#[utoipa::path(
+    post,
+    path = "/api/v1/security/notifications/{id}/read",
+    tag = "security",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Notification ID")
+    ),
+    responses(
+        (status = 204, description = "Notification marked as read"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 404, description = "No such unread notification", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn mark_security_notification_read(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    SecurityNotifier::new(state.db_pool.clone())
+        .mark_read(user_id, id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_risk_error_mapping() {
+        assert!(matches!(
+            ApiError::from(LoginRiskError::InvalidToken),
+            ApiError::ValidationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(LoginRiskError::Session("down".to_string())),
+            ApiError::InternalError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(NotificationError::NotFound),
+            ApiError::NotFound(_)
+        ));
+    }
+}
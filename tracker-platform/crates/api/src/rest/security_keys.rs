@@ -0,0 +1,432 @@
+//! # Security Key REST Endpoints
+//!
+//! Registering and removing WebAuthn security keys and passkeys, and
+//! signing in with them. Each ceremony is two calls: the first returns a
+//! challenge ID and the options to hand to `navigator.credentials.create()`
+//! or `.get()`, the second sends back the browser's response with that ID.
+//!
+//! Users whose role requires a security key and who have none are given an
+//! enrollment challenge instead of a session when their password is
+//! accepted; registering a key through `/enrollment` signs them in.
+
+use auth::{LoginError, SecurityKey, WebAuthnChallenge, WebAuthnError};
+use axum::{
+    extract::{Path, State},
+    http::{header::USER_AGENT, HeaderMap, StatusCode},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::instrument;
+use uuid::Uuid;
+
+use super::{require_auth, ErrorResponse};
+use crate::{caller::ClientIp, ApiError, ApiState};
+
+/// Registered security key DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SecurityKeyResponse {
+    pub id: Uuid,
+    pub name: String,
+    /// Transport hints (usb, nfc, ble, internal, hybrid)
+    pub transports: Vec<String>,
+    /// Usable for passwordless login
+    pub is_passkey: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<SecurityKey> for SecurityKeyResponse {
+    fn from(key: SecurityKey) -> Self {
+        Self {
+            id: key.id,
+            name: key.name,
+            transports: key.transports,
+            is_passkey: key.is_passkey,
+            created_at: key.created_at,
+            last_used_at: key.last_used_at,
+        }
+    }
+}
+
+/// A started ceremony
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct SecurityKeyChallengeResponse {
+    /// Send back with the browser's response
+    pub challenge_id: Uuid,
+    /// Options for `navigator.credentials.create()` or `.get()`
+    #[schema(value_type = Object)]
+    pub options: serde_json::Value,
+}
+
+impl SecurityKeyChallengeResponse {
+    fn new<T: Serialize>(challenge: WebAuthnChallenge<T>) -> Result<Self, ApiError> {
+        Ok(Self {
+            challenge_id: challenge.challenge_id,
+            options: serde_json::to_value(challenge.options)
+                .map_err(|e| ApiError::InternalError(e.to_string()))?,
+        })
+    }
+}
+
+/// Start registering a key
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct StartRegistrationBody {
+    /// Register a passkey that can sign in without a password
+    #[serde(default)]
+    pub passkey: bool,
+}
+
+/// Finish registering a key
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct FinishRegistrationBody {
+    /// Challenge ID from the start call
+    pub challenge_id: Uuid,
+    /// Name for the key, e.g. "YubiKey 5C"
+    pub name: String,
+    /// Response from `navigator.credentials.create()`
+    #[schema(value_type = Object)]
+    pub credential: auth::RegistrationCredential,
+}
+
+/// Register the key a password login asked for
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct EnrollmentBody {
+    /// Challenge ID from the refused password login
+    pub challenge_id: Uuid,
+    /// Name for the key, e.g. "YubiKey 5C"
+    pub name: String,
+    /// Response from `navigator.credentials.create()`
+    #[schema(value_type = Object)]
+    pub credential: auth::RegistrationCredential,
+    /// Remember this device (longer session)
+    #[serde(default)]
+    pub remember_me: bool,
+}
+
+/// Sign in with a passkey
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct PasskeyLoginBody {
+    /// Challenge ID from `/passkey-login/options`
+    pub challenge_id: Uuid,
+    /// Response from `navigator.credentials.get()`
+    #[schema(value_type = Object)]
+    pub credential: auth::AssertionCredential,
+    /// Remember this device (longer session)
+    #[serde(default)]
+    pub remember_me: bool,
+}
+
+/// Tokens for a new session
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct SignInResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Always "Bearer"
+    pub token_type: String,
+    /// Access token lifetime in seconds
+    pub expires_in: i64,
+    #[schema(value_type = Object)]
+    pub user: auth::login::UserInfo,
+    pub two_factor_enabled: bool,
+}
+
+impl From<auth::LoginResponse> for SignInResponse {
+    fn from(response: auth::LoginResponse) -> Self {
+        Self {
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+            token_type: response.token_type,
+            expires_in: response.expires_in,
+            user: response.user,
+            two_factor_enabled: response.two_factor_enabled,
+        }
+    }
+}
+
+impl From<WebAuthnError> for ApiError {
+    fn from(e: WebAuthnError) -> Self {
+        match e {
+            WebAuthnError::KeyNotFound => ApiError::NotFound(e.to_string()),
+            WebAuthnError::Redis(msg)
+            | WebAuthnError::Database(msg)
+            | WebAuthnError::Serialization(msg) => ApiError::InternalError(msg),
+            _ => ApiError::ValidationError(e.to_string()),
+        }
+    }
+}
+
+impl From<LoginError> for ApiError {
+    fn from(e: LoginError) -> Self {
+        match e {
+            LoginError::PasskeysDisabled => ApiError::NotFound(e.to_string()),
+            LoginError::AccountDisabled
+            | LoginError::AccountLocked
+            | LoginError::EmailNotVerified
+            | LoginError::PasswordResetRequired => ApiError::AuthorizationError(e.to_string()),
+            LoginError::SecurityKeyError(msg)
+            | LoginError::DatabaseError(msg)
+            | LoginError::TokenGenerationFailed(msg)
+            | LoginError::SessionCreationFailed(msg) => ApiError::InternalError(msg),
+            _ => ApiError::AuthenticationError(e.to_string()),
+        }
+    }
+}
+
+/// Configure security key routes
+pub fn routes() -> Router<Arc<ApiState>> {
+    Router::new()
+        .route("/", get(list_security_keys).post(finish_registration))
+        .route("/registrations", post(start_registration))
+        .route("/:id", delete(remove_security_key))
+        .route("/enrollment", post(finish_enrollment))
+        .route("/passkey-login/options", post(start_passkey_login))
+        .route("/passkey-login", post(passkey_login))
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// List the current user's security keys and passkeys
+#[utoipa::path(
+    get,
+    path = "/api/v1/security-keys",
+    tag = "security-keys",
+    responses(
+        (status = 200, description = "Registered keys", body = Vec<SecurityKeyResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn list_security_keys(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<SecurityKeyResponse>>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    let keys = state.security_keys.list_keys(user_id).await?;
+
+    Ok(Json(keys.into_iter().map(Into::into).collect()))
+}
+
+/// Start registering a security key or passkey
+#[utoipa::path(
+    post,
+    path = "/api/v1/security-keys/registrations",
+    tag = "security-keys",
+    request_body = StartRegistrationBody,
+    responses(
+        (status = 200, description = "Options for navigator.credentials.create()", body = SecurityKeyChallengeResponse),
+        (status = 400, description = "Too many keys registered", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn start_registration(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(body): Json<StartRegistrationBody>,
+) -> Result<Json<SecurityKeyChallengeResponse>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    let username = sqlx::query_scalar::<_, String>("SELECT username FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+
+    let challenge = state
+        .security_keys
+        .start_registration(user_id, &username, body.passkey)
+        .await?;
+
+    Ok(Json(SecurityKeyChallengeResponse::new(challenge)?))
+}
+
+/// Finish registering a security key or passkey
+#[utoipa::path(
+    post,
+    path = "/api/v1/security-keys",
+    tag = "security-keys",
+    request_body = FinishRegistrationBody,
+    responses(
+        (status = 201, description = "Key registered", body = SecurityKeyResponse),
+        (status = 400, description = "Expired challenge or invalid response", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn finish_registration(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(body): Json<FinishRegistrationBody>,
+) -> Result<(StatusCode, Json<SecurityKeyResponse>), ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    let key = state
+        .security_keys
+        .finish_registration(user_id, body.challenge_id, &body.name, &body.credential)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(key.into())))
+}
+
+/// Remove a security key or passkey
+#[utoipa::path(
+    delete,
+    path = "/api/v1/security-keys/{id}",
+    tag = "security-keys",
+    params(
+        ("id" = Uuid, Path, description = "Key ID")
+    ),
+    responses(
+        (status = 204, description = "Key removed"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 404, description = "No such key", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn remove_security_key(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    state.security_keys.remove_key(user_id, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Register the security key a password login required, and sign in
+#[utoipa::path(
+    post,
+    path = "/api/v1/security-keys/enrollment",
+    tag = "security-keys",
+    request_body = EnrollmentBody,
+    responses(
+        (status = 200, description = "Key registered and signed in", body = SignInResponse),
+        (status = 401, description = "Expired challenge or invalid response", body = ErrorResponse),
+        (status = 403, description = "Account disabled or locked", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn finish_enrollment(
+    State(state): State<Arc<ApiState>>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Json(body): Json<EnrollmentBody>,
+) -> Result<Json<SignInResponse>, ApiError> {
+    let request = auth::SecurityKeyEnrollmentRequest {
+        challenge_id: body.challenge_id,
+        name: body.name,
+        credential: body.credential,
+        remember_me: body.remember_me,
+    };
+
+    let response = state
+        .login
+        .finish_security_key_enrollment(request, ip.to_string(), user_agent(&headers))
+        .await?;
+
+    Ok(Json(response.into()))
+}
+
+/// Start a passwordless login
+#[utoipa::path(
+    post,
+    path = "/api/v1/security-keys/passkey-login/options",
+    tag = "security-keys",
+    responses(
+        (status = 200, description = "Options for navigator.credentials.get()", body = SecurityKeyChallengeResponse),
+        (status = 404, description = "Passkey login is not enabled", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state))]
+async fn start_passkey_login(
+    State(state): State<Arc<ApiState>>,
+) -> Result<Json<SecurityKeyChallengeResponse>, ApiError> {
+    let challenge = state.login.start_passkey_login().await?;
+
+    Ok(Json(SecurityKeyChallengeResponse::new(challenge)?))
+}
+
+/// Sign in with a passkey, without a password
+#[utoipa::path(
+    post,
+    path = "/api/v1/security-keys/passkey-login",
+    tag = "security-keys",
+    request_body = PasskeyLoginBody,
+    responses(
+        (status = 200, description = "Signed in", body = SignInResponse),
+        (status = 401, description = "Expired challenge or unknown passkey", body = ErrorResponse),
+        (status = 403, description = "Account disabled, locked or unverified", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn passkey_login(
+    State(state): State<Arc<ApiState>>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Json(body): Json<PasskeyLoginBody>,
+) -> Result<Json<SignInResponse>, ApiError> {
+    let request = auth::PasskeyLoginRequest {
+        challenge_id: body.challenge_id,
+        credential: body.credential,
+        remember_me: body.remember_me,
+    };
+
+    let response = state
+        .login
+        .login_with_passkey(request, ip.to_string(), user_agent(&headers))
+        .await?;
+
+    Ok(Json(response.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_mapping() {
+        assert!(matches!(
+            ApiError::from(WebAuthnError::ChallengeExpired),
+            ApiError::ValidationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(WebAuthnError::KeyNotFound),
+            ApiError::NotFound(_)
+        ));
+        assert!(matches!(
+            ApiError::from(LoginError::InvalidCredentials),
+            ApiError::AuthenticationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(LoginError::AccountLocked),
+            ApiError::AuthorizationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(LoginError::PasskeysDisabled),
+            ApiError::NotFound(_)
+        ));
+    }
+}
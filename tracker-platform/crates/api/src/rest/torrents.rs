@@ -14,8 +14,10 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::instrument;
 
-use crate::{ApiError, ApiState};
-use super::{ErrorResponse, PaginatedResponse, PaginationMeta, PaginationParams, require_auth};
+use auth::ApiTokenScope;
+
+use crate::{caller::ClientIp, ApiError, ApiState};
+use super::{ErrorResponse, PaginatedResponse, PaginationMeta, PaginationParams, require_auth, require_scope};
 
 /// Torrent response DTO
 #[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
@@ -99,14 +101,216 @@ pub struct UpdateTorrentRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub category: Option<String>,
+    /// Edit summary shown in the torrent's history
+    pub summary: Option<String>,
+}
+
+/// Torrent revision DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct TorrentRevisionResponse {
+    pub version: i32,
+    pub editor_id: Option<uuid::Uuid>,
+    /// Changed fields with their old and new values
+    #[schema(value_type = Vec<Object>)]
+    pub changes: Vec<torrent::FieldChange>,
+    pub summary: Option<String>,
+    pub rollback_of: Option<i32>,
+    pub significant: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<torrent::TorrentRevision> for TorrentRevisionResponse {
+    fn from(revision: torrent::TorrentRevision) -> Self {
+        Self {
+            version: revision.version,
+            editor_id: revision.editor_id,
+            changes: revision.changes,
+            summary: revision.summary,
+            rollback_of: revision.rollback_of,
+            significant: revision.significant,
+            created_at: revision.created_at,
+        }
+    }
+}
+
+/// Significant-edit notification DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct EditNotificationResponse {
+    pub torrent_id: uuid::Uuid,
+    pub torrent_name: String,
+    pub version: i32,
+    /// Names of the changed fields
+    pub fields: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<torrent::EditNotification> for EditNotificationResponse {
+    fn from(notification: torrent::EditNotification) -> Self {
+        Self {
+            torrent_id: notification.torrent_id,
+            torrent_name: notification.torrent_name,
+            version: notification.version,
+            fields: notification.fields,
+            created_at: notification.created_at,
+        }
+    }
+}
+
+/// Reseed request body
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct ReseedTorrentRequest {
+    /// Bonus points offered to whoever reseeds
+    pub bounty: Option<f64>,
+}
+
+/// Reseed request DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct ReseedResponse {
+    pub id: uuid::Uuid,
+    pub torrent_id: uuid::Uuid,
+    pub bounty: f64,
+    #[schema(value_type = String)]
+    pub status: user::ReseedStatus,
+    /// Uploader and previous snatchers asked to seed
+    pub notified_count: i32,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<user::ReseedRequest> for ReseedResponse {
+    fn from(request: user::ReseedRequest) -> Self {
+        Self {
+            id: request.id,
+            torrent_id: request.torrent_id,
+            bounty: request.bounty,
+            status: request.status,
+            notified_count: request.notified_count,
+            expires_at: request.expires_at,
+            created_at: request.created_at,
+        }
+    }
+}
+
+/// Dead torrent list parameters
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+pub struct DeadTorrentParams {
+    /// Minimum days without a seeder
+    #[serde(default = "default_dead_days")]
+    pub days: i64,
+}
+
+fn default_dead_days() -> i64 {
+    30
+}
+
+/// Dead torrent DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DeadTorrentResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub uploader_id: uuid::Uuid,
+    pub size: i64,
+    pub snatches: i64,
+    /// Last time the tracker saw a seeder
+    pub dead_since: DateTime<Utc>,
+    pub last_reseed_request_at: Option<DateTime<Utc>>,
+    pub reseed_open: bool,
+}
+
+impl From<user::DeadTorrent> for DeadTorrentResponse {
+    fn from(torrent: user::DeadTorrent) -> Self {
+        Self {
+            id: torrent.torrent_id,
+            name: torrent.name,
+            uploader_id: torrent.uploader_id,
+            size: torrent.size,
+            snatches: torrent.snatches,
+            dead_since: torrent.dead_since,
+            last_reseed_request_at: torrent.last_reseed_request_at,
+            reseed_open: torrent.reseed_open,
+        }
+    }
+}
+
+/// Rip log score and cue state DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct RipSummaryResponse {
+    /// Lowest log score, or none without a log
+    pub log_score: Option<i16>,
+    pub has_cue: bool,
+}
+
+impl From<torrent::RipSummary> for RipSummaryResponse {
+    fn from(summary: torrent::RipSummary) -> Self {
+        Self {
+            log_score: summary.log_score,
+            has_cue: summary.has_cue,
+        }
+    }
+}
+
+/// Trump check DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct TrumpCheckResponse {
+    /// Whether the torrent's rip replaces the existing one
+    pub trumps: bool,
+    pub candidate: RipSummaryResponse,
+    pub existing: RipSummaryResponse,
+}
+
+impl From<user::ReseedError> for ApiError {
+    fn from(e: user::ReseedError) -> Self {
+        use user::{BonusError, ReseedError};
+
+        match e {
+            ReseedError::TorrentNotFound(_) | ReseedError::RequestNotFound(_) => {
+                ApiError::NotFound(e.to_string())
+            }
+            ReseedError::RateLimited { .. } => ApiError::RateLimitExceeded,
+            ReseedError::NotDead(_)
+            | ReseedError::AlreadyOpen(_)
+            | ReseedError::InvalidBounty(_)
+            | ReseedError::Bonus(BonusError::InsufficientBonus { .. }) => {
+                ApiError::ValidationError(e.to_string())
+            }
+            ReseedError::Database(e) => ApiError::DatabaseError(e),
+            ReseedError::Bonus(e) => ApiError::Other(e.into()),
+        }
+    }
 }
 
 /// Configure torrent routes
 pub fn routes() -> Router<Arc<ApiState>> {
     Router::new()
         .route("/", get(list_torrents).post(upload_torrent))
+        .route("/dead", get(list_dead_torrents))
+        .route("/edit-notifications", get(list_edit_notifications))
+        .route(
+            "/edit-notifications/read",
+            post(mark_edit_notifications_read),
+        )
         .route("/:id", get(get_torrent).patch(update_torrent))
         .route("/:id/download", get(download_torrent))
+        .route("/:id/history", get(get_torrent_history))
+        .route("/:id/nfo.png", get(get_torrent_nfo_image))
+        .route("/:id/history/:version/rollback", post(rollback_torrent))
+        .route("/:id/reseed", post(request_reseed))
+        .route("/:id/trumps/:existing_id", get(check_trump))
+}
+
+/// Whether a user's group may edit any torrent
+async fn can_edit_torrents(state: &ApiState, user_id: uuid::Uuid) -> Result<bool, ApiError> {
+    let allowed = sqlx::query_scalar::<_, bool>(
+        "SELECT g.can_edit_torrents
+         FROM users u
+         JOIN user_groups g ON g.id = u.group_id
+         WHERE u.id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(&state.db_pool)
+    .await?;
+
+    Ok(allowed.unwrap_or(false))
 }
 
 /// List torrents with filtering and pagination
@@ -234,16 +438,18 @@ async fn get_torrent(
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_token" = ["upload"])
     )
 )]
 #[instrument(skip(state, headers))]
 async fn upload_torrent(
     State(state): State<Arc<ApiState>>,
     headers: HeaderMap,
+    client_ip: ClientIp,
     Json(request): Json<UploadTorrentRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let user_id = require_auth(&headers).await?;
+    let user_id = require_scope(&state, &headers, client_ip, ApiTokenScope::Upload).await?;
 
     // Validate input
     if request.name.is_empty() {
@@ -318,17 +524,19 @@ async fn upload_torrent(
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_token" = ["upload"])
     )
 )]
 #[instrument(skip(state, headers))]
 async fn update_torrent(
     State(state): State<Arc<ApiState>>,
     headers: HeaderMap,
+    client_ip: ClientIp,
     Path(id): Path<uuid::Uuid>,
     Json(request): Json<UpdateTorrentRequest>,
 ) -> Result<Json<TorrentResponse>, ApiError> {
-    let user_id = require_auth(&headers).await?;
+    let user_id = require_scope(&state, &headers, client_ip, ApiTokenScope::Upload).await?;
 
     // Get existing torrent
     let existing = sqlx::query_as::<_, TorrentResponse>(
@@ -340,7 +548,7 @@ async fn update_torrent(
     .ok_or_else(|| ApiError::NotFound("Torrent not found".to_string()))?;
 
     // Check authorization
-    if existing.uploader_id != user_id {
+    if existing.uploader_id != user_id && !can_edit_torrents(&state, user_id).await? {
         return Err(ApiError::AuthorizationError(
             "Not authorized to update this torrent".to_string(),
         ));
@@ -351,24 +559,332 @@ async fn update_torrent(
     let description = request.description.or(existing.description);
     let category = request.category.unwrap_or(existing.category);
 
-    let torrent = sqlx::query_as::<_, TorrentResponse>(
-        "UPDATE torrents
-         SET name = $1, description = $2, category = $3
-         WHERE id = $4
-         RETURNING *",
-    )
-    .bind(&name)
-    .bind(&description)
-    .bind(&category)
-    .bind(id)
-    .fetch_one(&state.db_pool)
-    .await?;
+    // Version the edit so it can be reviewed and rolled back; the history
+    // service updates the listing columns in the same transaction
+    let history = torrent::EditHistoryService::new(state.db_pool.clone());
+    let torrent = match history.current(id).await? {
+        Some(mut snapshot) => {
+            snapshot.metadata.name = name;
+            snapshot.metadata.description = description;
+            snapshot.category = category;
+            history.edit(&snapshot, user_id, request.summary).await?;
+
+            sqlx::query_as::<_, TorrentResponse>("SELECT * FROM torrents WHERE id = $1")
+                .bind(id)
+                .fetch_one(&state.db_pool)
+                .await?
+        }
+        None => {
+            sqlx::query_as::<_, TorrentResponse>(
+                "UPDATE torrents
+                 SET name = $1, description = $2, category = $3
+                 WHERE id = $4
+                 RETURNING *",
+            )
+            .bind(&name)
+            .bind(&description)
+            .bind(&category)
+            .bind(id)
+            .fetch_one(&state.db_pool)
+            .await?
+        }
+    };
 
     tracing::info!("Torrent updated: {}", id);
 
     Ok(Json(torrent))
 }
 
+/// Get a torrent's edit history
+#[utoipa::path(
+    get,
+    path = "/api/v1/torrents/{id}/history",
+    tag = "torrents",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Torrent ID")
+    ),
+    responses(
+        (status = 200, description = "Revisions, newest first", body = Vec<TorrentRevisionResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn get_torrent_history(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Json<Vec<TorrentRevisionResponse>>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    if !can_edit_torrents(&state, user_id).await? {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to view torrent history".to_string(),
+        ));
+    }
+
+    let history = torrent::EditHistoryService::new(state.db_pool.clone())
+        .history(id)
+        .await?;
+
+    Ok(Json(history.into_iter().map(Into::into).collect()))
+}
+
+/// Roll a torrent back to an earlier revision
+#[utoipa::path(
+    post,
+    path = "/api/v1/torrents/{id}/history/{version}/rollback",
+    tag = "torrents",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Torrent ID"),
+        ("version" = i32, Path, description = "Revision to restore")
+    ),
+    responses(
+        (status = 200, description = "Rolled back; null if already in that state", body = Option<TorrentRevisionResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "Revision not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn rollback_torrent(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path((id, version)): Path<(uuid::Uuid, i32)>,
+) -> Result<Json<Option<TorrentRevisionResponse>>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    if !can_edit_torrents(&state, user_id).await? {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to roll back torrents".to_string(),
+        ));
+    }
+
+    let history = torrent::EditHistoryService::new(state.db_pool.clone());
+
+    if history.get_snapshot(id, version).await?.is_none() {
+        return Err(ApiError::NotFound("Revision not found".to_string()));
+    }
+
+    let revision = history.rollback(id, version, user_id).await?;
+
+    tracing::info!(
+        "Torrent {} rolled back to version {} by {}",
+        id,
+        version,
+        user_id
+    );
+
+    Ok(Json(revision.map(Into::into)))
+}
+
+/// List the current user's unread notices of significant edits to torrents
+/// they uploaded or snatched, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/torrents/edit-notifications",
+    tag = "torrents",
+    responses(
+        (status = 200, description = "Unread edit notifications", body = Vec<EditNotificationResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn list_edit_notifications(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<EditNotificationResponse>>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    let notifications = torrent::EditHistoryService::new(state.db_pool.clone())
+        .unread_notifications(user_id)
+        .await?;
+
+    Ok(Json(notifications.into_iter().map(Into::into).collect()))
+}
+
+/// Mark all of the current user's edit notifications as read
+#[utoipa::path(
+    post,
+    path = "/api/v1/torrents/edit-notifications/read",
+    tag = "torrents",
+    responses(
+        (status = 204, description = "Notifications marked as read"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn mark_edit_notifications_read(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    torrent::EditHistoryService::new(state.db_pool.clone())
+        .mark_notifications_read(user_id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Ask previous snatchers to reseed a dead torrent
+#[utoipa::path(
+    post,
+    path = "/api/v1/torrents/{id}/reseed",
+    tag = "torrents",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Torrent ID")
+    ),
+    request_body = ReseedTorrentRequest,
+    responses(
+        (status = 201, description = "Reseed requested", body = ReseedResponse),
+        (status = 400, description = "Torrent has seeders, request already open or invalid bounty", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 404, description = "Torrent not found", body = ErrorResponse),
+        (status = 429, description = "Reseed requested too recently", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_token" = ["post"])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn request_reseed(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    client_ip: ClientIp,
+    Path(id): Path<uuid::Uuid>,
+    Json(payload): Json<ReseedTorrentRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user_id = require_scope(&state, &headers, client_ip, ApiTokenScope::Post).await?;
+
+    let request = user::ReseedService::new(state.db_pool.clone())
+        .with_email(state.email.clone())
+        .request_reseed(id, user_id, payload.bounty)
+        .await?;
+
+    tracing::info!(
+        "Reseed of {} requested by {}, {} users asked",
+        id,
+        user_id,
+        request.notified_count
+    );
+
+    Ok((StatusCode::CREATED, Json(ReseedResponse::from(request))))
+}
+
+/// List torrents that have had no seeders for a number of days
+#[utoipa::path(
+    get,
+    path = "/api/v1/torrents/dead",
+    tag = "torrents",
+    params(
+        PaginationParams,
+        DeadTorrentParams
+    ),
+    responses(
+        (status = 200, description = "Dead torrents, longest dead first", body = Vec<DeadTorrentResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn list_dead_torrents(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Query(pagination): Query<PaginationParams>,
+    Query(params): Query<DeadTorrentParams>,
+) -> Result<Json<Vec<DeadTorrentResponse>>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    if !can_edit_torrents(&state, user_id).await? {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to view dead torrents".to_string(),
+        ));
+    }
+
+    let torrents = user::ReseedService::new(state.db_pool.clone())
+        .dead_torrents(params.days.max(0), pagination.limit(), pagination.offset())
+        .await?;
+
+    Ok(Json(torrents.into_iter().map(Into::into).collect()))
+}
+
+/// Check whether a torrent's rip trumps an existing rip of the same release
+#[utoipa::path(
+    get,
+    path = "/api/v1/torrents/{id}/trumps/{existing_id}",
+    tag = "torrents",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Candidate torrent ID"),
+        ("existing_id" = uuid::Uuid, Path, description = "Existing torrent ID")
+    ),
+    responses(
+        (status = 200, description = "Trump decision and both rips' log scores", body = TrumpCheckResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "Torrent not found", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn check_trump(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path((id, existing_id)): Path<(uuid::Uuid, uuid::Uuid)>,
+) -> Result<Json<TrumpCheckResponse>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    if !can_edit_torrents(&state, user_id).await? {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to make trump decisions".to_string(),
+        ));
+    }
+
+    // Anything but a database failure means the torrent wasn't found
+    let lookup_error = |e: anyhow::Error| match e.downcast::<sqlx::Error>() {
+        Ok(e) => ApiError::DatabaseError(e),
+        Err(e) => ApiError::NotFound(e.to_string()),
+    };
+
+    let rip_logs = torrent::RipLogService::new(state.db_pool.clone());
+    let candidate = rip_logs.get_summary(id).await.map_err(lookup_error)?;
+    let existing = rip_logs
+        .get_summary(existing_id)
+        .await
+        .map_err(lookup_error)?;
+
+    Ok(Json(TrumpCheckResponse {
+        trumps: torrent::can_trump(&candidate, &existing),
+        candidate: candidate.into(),
+        existing: existing.into(),
+    }))
+}
+
 /// Download a torrent file
 #[utoipa::path(
     get,
@@ -384,16 +900,18 @@ async fn update_torrent(
         (status = 500, description = "Internal server error", body = ErrorResponse)
     ),
     security(
-        ("bearer_auth" = [])
+        ("bearer_auth" = []),
+        ("api_token" = ["download"])
     )
 )]
 #[instrument(skip(state, headers))]
 async fn download_torrent(
     State(state): State<Arc<ApiState>>,
     headers: HeaderMap,
+    client_ip: ClientIp,
     Path(id): Path<uuid::Uuid>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let user_id = require_auth(&headers).await?;
+    let user_id = require_scope(&state, &headers, client_ip, ApiTokenScope::Download).await?;
 
     // Check if torrent exists
     let torrent = sqlx::query_as::<_, TorrentResponse>(
@@ -418,6 +936,37 @@ async fn download_torrent(
     ))
 }
 
+/// Render a torrent's NFO as a PNG image
+#[utoipa::path(
+    get,
+    path = "/api/v1/torrents/{id}/nfo.png",
+    tag = "torrents",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Torrent ID")
+    ),
+    responses(
+        (status = 200, description = "NFO image", content_type = "image/png"),
+        (status = 404, description = "Torrent has no NFO", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    )
+)]
+#[instrument(skip(state))]
+async fn get_torrent_nfo_image(
+    State(state): State<Arc<ApiState>>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let png = torrent::NfoService::new(state.db_pool.clone())
+        .render(id, &torrent::RenderOptions::default())
+        .await
+        .map_err(|e| match e.downcast::<sqlx::Error>() {
+            Ok(e) => ApiError::DatabaseError(e),
+            Err(e) => ApiError::InternalError(e.to_string()),
+        })?
+        .ok_or_else(|| ApiError::NotFound("Torrent has no NFO".to_string()))?;
+
+    Ok((StatusCode::OK, [("Content-Type", "image/png")], png))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,248 @@
+//! # Upload Rule REST Endpoints
+//!
+//! Lets staff edit the upload rules of each category and the site-wide
+//! default that categories without rules of their own fall back to. All
+//! endpoints need the site settings permission. Upload services cache
+//! rulesets for a few minutes, so edits apply to new uploads shortly after.
+
+use auth::Permission;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use torrent::{StoredRuleSet, UploadRuleSet, UploadRulesService};
+use tracing::instrument;
+
+use super::{require_permission, ErrorResponse};
+use crate::{ApiError, ApiState};
+
+/// Stored upload rules DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct UploadRulesResponse {
+    /// Category, or null for the site-wide default
+    pub category_id: Option<i32>,
+    /// Allowed extensions and containers, required files, name pattern,
+    /// size limits, banned groups and required quality fields
+    #[schema(value_type = Object)]
+    pub rules: UploadRuleSet,
+    pub updated_by: Option<uuid::Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<StoredRuleSet> for UploadRulesResponse {
+    fn from(stored: StoredRuleSet) -> Self {
+        Self {
+            category_id: stored.category_id,
+            rules: stored.rules,
+            updated_by: stored.updated_by,
+            updated_at: stored.updated_at,
+        }
+    }
+}
+
+/// Request to replace a ruleset
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct SaveUploadRulesBody {
+    /// New ruleset; missing fields take the built-in defaults
+    #[schema(value_type = Object)]
+    pub rules: UploadRuleSet,
+}
+
+/// Configure upload rule routes
+pub fn routes() -> Router<Arc<ApiState>> {
+    Router::new()
+        .route("/", get(list_upload_rules))
+        .route(
+            "/default",
+            put(save_default_rules).delete(delete_default_rules),
+        )
+        .route(
+            "/categories/:category_id",
+            put(save_category_rules).delete(delete_category_rules),
+        )
+}
+
+/// Validate and store a ruleset for a category or the default
+async fn save_rules(
+    state: &ApiState,
+    user_id: uuid::Uuid,
+    category_id: Option<i32>,
+    rules: UploadRuleSet,
+) -> Result<StatusCode, ApiError> {
+    rules
+        .validate()
+        .map_err(|e| ApiError::ValidationError(e.to_string()))?;
+
+    UploadRulesService::new(state.db_pool.clone())
+        .save_rules(category_id, &rules, user_id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove the ruleset of a category or the default
+async fn delete_rules(
+    state: &ApiState,
+    user_id: uuid::Uuid,
+    category_id: Option<i32>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = UploadRulesService::new(state.db_pool.clone())
+        .delete_rules(category_id)
+        .await?;
+    if !deleted {
+        return Err(ApiError::NotFound("No rules stored".to_string()));
+    }
+
+    tracing::info!(
+        "Upload rules for {} removed by {}",
+        category_id.map_or_else(|| "default".to_string(), |id| id.to_string()),
+        user_id
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the stored upload rules
+#[utoipa::path(
+    get,
+    path = "/api/v1/upload-rules",
+    tag = "upload-rules",
+    responses(
+        (status = 200, description = "Stored rulesets, default first", body = Vec<UploadRulesResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Site settings required", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn list_upload_rules(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<UploadRulesResponse>>, ApiError> {
+    require_permission(&state, &headers, Permission::SiteSettings).await?;
+
+    let rules = UploadRulesService::new(state.db_pool.clone())
+        .list_rules()
+        .await?;
+
+    Ok(Json(rules.into_iter().map(Into::into).collect()))
+}
+
+/// Replace the site-wide default rules
+#[utoipa::path(
+    put,
+    path = "/api/v1/upload-rules/default",
+    tag = "upload-rules",
+    request_body = SaveUploadRulesBody,
+    responses(
+        (status = 204, description = "Rules saved"),
+        (status = 400, description = "Invalid ruleset", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Site settings required", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn save_default_rules(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Json(body): Json<SaveUploadRulesBody>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = require_permission(&state, &headers, Permission::SiteSettings).await?;
+
+    save_rules(&state, user_id, None, body.rules).await
+}
+
+/// Remove the site-wide default rules, falling back to the built-in ones
+#[utoipa::path(
+    delete,
+    path = "/api/v1/upload-rules/default",
+    tag = "upload-rules",
+    responses(
+        (status = 204, description = "Rules removed"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Site settings required", body = ErrorResponse),
+        (status = 404, description = "No default rules stored", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn delete_default_rules(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    let user_id = require_permission(&state, &headers, Permission::SiteSettings).await?;
+
+    delete_rules(&state, user_id, None).await
+}
+
+/// Create or replace the rules of a category
+#[utoipa::path(
+    put,
+    path = "/api/v1/upload-rules/categories/{category_id}",
+    tag = "upload-rules",
+    params(
+        ("category_id" = i32, Path, description = "Category ID")
+    ),
+    request_body = SaveUploadRulesBody,
+    responses(
+        (status = 204, description = "Rules saved"),
+        (status = 400, description = "Invalid ruleset", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Site settings required", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn save_category_rules(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(category_id): Path<i32>,
+    Json(body): Json<SaveUploadRulesBody>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = require_permission(&state, &headers, Permission::SiteSettings).await?;
+
+    save_rules(&state, user_id, Some(category_id), body.rules).await
+}
+
+/// Remove the rules of a category, falling back to the default
+#[utoipa::path(
+    delete,
+    path = "/api/v1/upload-rules/categories/{category_id}",
+    tag = "upload-rules",
+    params(
+        ("category_id" = i32, Path, description = "Category ID")
+    ),
+    responses(
+        (status = 204, description = "Rules removed"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Site settings required", body = ErrorResponse),
+        (status = 404, description = "Category has no rules of its own", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn delete_category_rules(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(category_id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    let user_id = require_permission(&state, &headers, Permission::SiteSettings).await?;
+
+    delete_rules(&state, user_id, Some(category_id)).await
+}
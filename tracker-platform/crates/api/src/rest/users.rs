@@ -3,10 +3,10 @@
 //! RESTful API endpoints for user operations.
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::HeaderMap,
     response::IntoResponse,
-    routing::{get, patch},
+    routing::{get, patch, post},
     Json, Router,
 };
 use chrono::{DateTime, Utc};
@@ -14,8 +14,12 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tracing::instrument;
 
-use crate::{ApiError, ApiState};
-use super::{ErrorResponse, require_auth};
+use auth::ApiTokenScope;
+
+use super::{
+    require_auth, require_scope, ErrorResponse, PaginatedResponse, PaginationMeta, PaginationParams,
+};
+use crate::{caller::ClientIp, ApiError, ApiState};
 
 /// User response DTO
 #[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
@@ -44,6 +48,139 @@ pub struct UserStatisticsResponse {
     pub ratio: f64,
 }
 
+/// Active torrent entry for the user's active-torrents page
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, utoipa::ToSchema)]
+pub struct ActiveTorrentResponse {
+    pub torrent_id: uuid::Uuid,
+    pub torrent_name: String,
+    pub is_seeder: bool,
+    pub uploaded: i64,
+    pub downloaded: i64,
+    pub left_bytes: i64,
+    pub port: i32,
+    pub user_agent: Option<String>,
+    /// Whether the tracker could reach the client's port (null if not yet checked)
+    pub is_connectable: Option<bool>,
+    pub last_announce_at: DateTime<Utc>,
+}
+
+/// Snatch entry for the user's snatch list
+///
+/// Snatches are recorded by the tracker when the client reports completion.
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct SnatchResponse {
+    pub torrent_id: uuid::Uuid,
+    pub torrent_name: String,
+    /// Bytes uploaded as reported on completion
+    pub uploaded: i64,
+    /// Bytes downloaded as reported on completion
+    pub downloaded: i64,
+    /// Seconds from the first announce to completion
+    pub time_left: i64,
+    /// Seconds seeded since completion
+    pub seed_time: i64,
+    pub snatched_at: DateTime<Utc>,
+    /// Standing under the hit-and-run rules
+    #[schema(value_type = Object)]
+    pub hit_and_run: user::HitAndRunStatus,
+}
+
+impl SnatchResponse {
+    fn new(
+        snatch: user::SnatchedTorrent,
+        rules: &user::HitAndRunRules,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            hit_and_run: rules.evaluate(snatch.snatched_at, snatch.seed_time, now),
+            torrent_id: snatch.torrent_id,
+            torrent_name: snatch.torrent_name,
+            uploaded: snatch.uploaded,
+            downloaded: snatch.downloaded,
+            time_left: snatch.time_left,
+            seed_time: snatch.seed_time,
+            snatched_at: snatch.snatched_at,
+        }
+    }
+}
+
+/// Snatch that was not seeded for long enough
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct HitAndRunResponse {
+    pub torrent_id: uuid::Uuid,
+    pub torrent_name: String,
+    /// Seconds seeded since completion
+    pub seed_time: i64,
+    pub last_seeded_at: Option<DateTime<Utc>>,
+    pub snatched_at: DateTime<Utc>,
+}
+
+impl From<user::HitAndRun> for HitAndRunResponse {
+    fn from(hit_and_run: user::HitAndRun) -> Self {
+        Self {
+            torrent_id: hit_and_run.torrent_id,
+            torrent_name: hit_and_run.torrent_name,
+            seed_time: hit_and_run.seed_time,
+            last_seeded_at: hit_and_run.last_seeded_at,
+            snatched_at: hit_and_run.snatched_at,
+        }
+    }
+}
+
+impl From<user::StatisticsError> for ApiError {
+    fn from(e: user::StatisticsError) -> Self {
+        match e {
+            user::StatisticsError::NotFound(_) => ApiError::NotFound(e.to_string()),
+            user::StatisticsError::InvalidTimeRange => ApiError::ValidationError(e.to_string()),
+            user::StatisticsError::Database(e) => ApiError::DatabaseError(e),
+        }
+    }
+}
+
+impl From<user::HitAndRunError> for ApiError {
+    fn from(e: user::HitAndRunError) -> Self {
+        match e {
+            user::HitAndRunError::Database(e) => ApiError::DatabaseError(e),
+        }
+    }
+}
+
+impl From<user::InviteError> for ApiError {
+    fn from(e: user::InviteError) -> Self {
+        match e {
+            user::InviteError::NotFound(_) | user::InviteError::UserNotFound(_) => {
+                ApiError::NotFound(e.to_string())
+            }
+            user::InviteError::AlreadyUsed
+            | user::InviteError::Expired
+            | user::InviteError::InvalidCodeFormat => ApiError::ValidationError(e.to_string()),
+            user::InviteError::NoInvitesRemaining | user::InviteError::InvitesDisabled => {
+                ApiError::AuthorizationError(e.to_string())
+            }
+            user::InviteError::Database(e) => ApiError::DatabaseError(e),
+            user::InviteError::Email(e) => e.into(),
+        }
+    }
+}
+
+/// Invitation request
+#[derive(Debug, Deserialize, utoipa::ToSchema)]
+pub struct CreateInviteBody {
+    /// Address to email the invitation to
+    pub email: Option<String>,
+    /// Note from the inviter, included in the email
+    pub message: Option<String>,
+}
+
+/// Invitation DTO
+#[derive(Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct InviteResponse {
+    pub code: String,
+    pub expires_at: DateTime<Utc>,
+    /// Whether the invitation was emailed
+    pub emailed: bool,
+}
+
 /// User update request
 #[derive(Debug, Deserialize, utoipa::ToSchema)]
 pub struct UpdateUserRequest {
@@ -60,6 +197,10 @@ pub fn routes() -> Router<Arc<ApiState>> {
         .route("/:id", get(get_user).patch(update_user))
         .route("/:id/stats", get(get_user_stats))
         .route("/:id/torrents", get(get_user_torrents))
+        .route("/:id/active", get(get_user_active_torrents))
+        .route("/:id/snatches", get(get_user_snatches))
+        .route("/:id/hit-and-runs", get(get_user_hit_and_runs))
+        .route("/:id/invites", post(create_invite))
 }
 
 /// Get user by ID
@@ -279,6 +420,211 @@ async fn get_user_torrents(
     Ok(Json(torrents))
 }
 
+/// Get user's active torrents with connectability status
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}/active",
+    tag = "users",
+    params(
+        ("id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "User's active torrents", body = Vec<ActiveTorrentResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_token" = ["read_torrents"])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn get_user_active_torrents(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    client_ip: ClientIp,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Json<Vec<ActiveTorrentResponse>>, ApiError> {
+    let user_id = require_scope(&state, &headers, client_ip, ApiTokenScope::ReadTorrents).await?;
+
+    // Active peers expose the user's client and port, so only show them to the owner
+    if user_id != id {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to view this user's active torrents".to_string(),
+        ));
+    }
+
+    let active = sqlx::query_as::<_, ActiveTorrentResponse>(
+        "SELECT p.torrent_id, t.name AS torrent_name, p.is_seeder, p.uploaded, p.downloaded,
+         p.left_bytes, p.port, p.user_agent, p.is_connectable, p.last_announce_at
+         FROM peers p
+         JOIN torrents t ON p.torrent_id = t.id
+         WHERE p.user_id = $1
+         ORDER BY p.last_announce_at DESC",
+    )
+    .bind(id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(Json(active))
+}
+
+/// Get user's snatches, newest first, with their hit-and-run standing
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}/snatches",
+    tag = "users",
+    params(
+        ("id" = uuid::Uuid, Path, description = "User ID"),
+        PaginationParams
+    ),
+    responses(
+        (status = 200, description = "User's snatches", body = PaginatedResponse<SnatchResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_token" = ["read_torrents"])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn get_user_snatches(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    client_ip: ClientIp,
+    Path(id): Path<uuid::Uuid>,
+    Query(pagination): Query<PaginationParams>,
+) -> Result<Json<PaginatedResponse<SnatchResponse>>, ApiError> {
+    let user_id = require_scope(&state, &headers, client_ip, ApiTokenScope::ReadTorrents).await?;
+
+    if user_id != id {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to view this user's snatches".to_string(),
+        ));
+    }
+
+    let statistics = user::StatisticsService::new(state.db_pool.clone());
+    let total = statistics.count_snatches(id).await?;
+    let snatches = statistics
+        .get_snatched_torrents(id, pagination.limit(), pagination.offset())
+        .await?;
+
+    let rules = user::HitAndRunRules::default();
+    let now = Utc::now();
+
+    Ok(Json(PaginatedResponse {
+        data: snatches
+            .into_iter()
+            .map(|snatch| SnatchResponse::new(snatch, &rules, now))
+            .collect(),
+        pagination: PaginationMeta::new(pagination.page, pagination.per_page, total),
+    }))
+}
+
+/// Get user's hit-and-runs
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}/hit-and-runs",
+    tag = "users",
+    params(
+        ("id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Snatches past their grace period without enough seed time", body = Vec<HitAndRunResponse>),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = []),
+        ("api_token" = ["read_torrents"])
+    )
+)]
+#[instrument(skip(state, headers))]
+async fn get_user_hit_and_runs(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    client_ip: ClientIp,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<Json<Vec<HitAndRunResponse>>, ApiError> {
+    let user_id = require_scope(&state, &headers, client_ip, ApiTokenScope::ReadTorrents).await?;
+
+    if user_id != id {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to view this user's hit-and-runs".to_string(),
+        ));
+    }
+
+    let hit_and_runs = user::HitAndRunService::new(state.db_pool.clone())
+        .get_user_hit_and_runs(id)
+        .await?;
+
+    Ok(Json(hit_and_runs.into_iter().map(Into::into).collect()))
+}
+
+/// Create an invitation, optionally emailing it
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/{id}/invites",
+    tag = "users",
+    params(
+        ("id" = uuid::Uuid, Path, description = "User ID")
+    ),
+    request_body = CreateInviteBody,
+    responses(
+        (status = 200, description = "Invitation created", body = InviteResponse),
+        (status = 400, description = "Invalid email address", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "No invites left or invites disabled", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+#[instrument(skip(state, headers, body))]
+async fn create_invite(
+    State(state): State<Arc<ApiState>>,
+    headers: HeaderMap,
+    Path(id): Path<uuid::Uuid>,
+    Json(body): Json<CreateInviteBody>,
+) -> Result<Json<InviteResponse>, ApiError> {
+    let user_id = require_auth(&headers).await?;
+
+    if user_id != id {
+        return Err(ApiError::AuthorizationError(
+            "Not authorized to invite for this user".to_string(),
+        ));
+    }
+
+    // Check the address first so a typo doesn't use up an invite
+    if let Some(to) = &body.email {
+        if !validator::validate_email(to.as_str()) {
+            return Err(ApiError::ValidationError(format!(
+                "Invalid email address: {}",
+                to
+            )));
+        }
+    }
+
+    let invites = user::InviteService::new(state.db_pool.clone()).with_email(state.email.clone());
+    let invitation = invites.generate_invite(id, None, body.message).await?;
+
+    let emailed = match &body.email {
+        Some(to) => invites.email_invite(id, &invitation.code, to).await?,
+        None => false,
+    };
+
+    Ok(Json(InviteResponse {
+        code: invitation.code,
+        expires_at: invitation.expires_at,
+        emailed,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -301,6 +647,20 @@ mod tests {
         assert!(json.contains("testuser"));
     }
 
+    #[test]
+    fn test_invite_error_mapping() {
+        assert!(matches!(
+            ApiError::from(user::InviteError::NoInvitesRemaining),
+            ApiError::AuthorizationError(_)
+        ));
+        assert!(matches!(
+            ApiError::from(user::InviteError::Email(auth::EmailError::InvalidAddress(
+                "nope".to_string()
+            ))),
+            ApiError::ValidationError(_)
+        ));
+    }
+
     #[test]
     fn test_ratio_calculation() {
         let uploaded = 1000;
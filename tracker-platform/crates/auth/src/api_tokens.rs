@@ -0,0 +1,776 @@
+//! Personal API tokens
+//!
+//! Users create tokens for scripts and automation (autodl, upload scripts,
+//! Sonarr-style tools) instead of handing over their login JWT. A token is
+//! limited to the scopes picked when it was created, always expires, can be
+//! restricted to a set of IP ranges and can be revoked at any time.
+//!
+//! Only a SHA-256 hash of each token is stored. Tokens carry 256 bits of
+//! randomness, so an unsalted hash is enough to make a database leak useless
+//! while keeping authentication a single indexed lookup.
+
+use crate::classes::PermissionResolver;
+use crate::permissions::{Permission, PermissionSet};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Prefix of every API token, so they are easy to tell apart from JWTs and
+/// to spot in leaked code
+pub const TOKEN_PREFIX: &str = "trk_";
+
+/// Characters of the token (after the prefix) kept for display
+const DISPLAY_PREFIX_LEN: usize = 8;
+
+/// Active tokens a user may hold
+const MAX_TOKENS_PER_USER: i64 = 25;
+
+/// Lifetime of a token when none is requested
+const DEFAULT_EXPIRY_DAYS: u32 = 90;
+
+/// Longest lifetime a token may be given
+const MAX_EXPIRY_DAYS: u32 = 365;
+
+/// Most IP ranges in a token's allowlist
+const MAX_ALLOWED_IPS: usize = 16;
+
+/// Errors that can occur while managing or using API tokens
+#[derive(Debug, Error)]
+pub enum ApiTokenError {
+    #[error("Invalid API token")]
+    InvalidToken,
+
+    #[error("API token has expired")]
+    Expired,
+
+    #[error("API token has been revoked")]
+    Revoked,
+
+    #[error("API token is not allowed from this IP address")]
+    IpNotAllowed,
+
+    #[error("Your account does not have API access")]
+    ApiAccessDenied,
+
+    #[error("Your account cannot grant the {0} scope")]
+    ScopeNotAllowed(ApiTokenScope),
+
+    #[error("At least one scope is required")]
+    NoScopes,
+
+    #[error("Invalid IP address or range: {0}")]
+    InvalidIpRange(String),
+
+    #[error("At most {MAX_ALLOWED_IPS} IP ranges are allowed")]
+    TooManyIpRanges,
+
+    #[error("Token lifetime must be between 1 and {MAX_EXPIRY_DAYS} days")]
+    InvalidExpiry,
+
+    #[error("You already have {MAX_TOKENS_PER_USER} active API tokens")]
+    TooManyTokens,
+
+    #[error("API token not found")]
+    NotFound,
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+impl From<sqlx::Error> for ApiTokenError {
+    fn from(e: sqlx::Error) -> Self {
+        ApiTokenError::Database(e.to_string())
+    }
+}
+
+impl From<crate::classes::ClassError> for ApiTokenError {
+    fn from(e: crate::classes::ClassError) -> Self {
+        ApiTokenError::Database(e.to_string())
+    }
+}
+
+/// What an API token may be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiTokenScope {
+    /// Browse, search and read torrent details
+    ReadTorrents,
+    /// Download .torrent files
+    Download,
+    /// Upload and edit torrents
+    Upload,
+    /// Post in the forums and on requests
+    Post,
+}
+
+impl ApiTokenScope {
+    /// All scopes
+    pub fn all() -> [ApiTokenScope; 4] {
+        [
+            ApiTokenScope::ReadTorrents,
+            ApiTokenScope::Download,
+            ApiTokenScope::Upload,
+            ApiTokenScope::Post,
+        ]
+    }
+
+    /// Database representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiTokenScope::ReadTorrents => "read_torrents",
+            ApiTokenScope::Download => "download",
+            ApiTokenScope::Upload => "upload",
+            ApiTokenScope::Post => "post",
+        }
+    }
+
+    /// Parse the database representation
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::all().into_iter().find(|scope| scope.as_str() == s)
+    }
+
+    /// Permission the user needs to grant this scope, beyond `ApiAccess`
+    pub fn required_permission(&self) -> Option<Permission> {
+        match self {
+            ApiTokenScope::ReadTorrents => None,
+            ApiTokenScope::Download => Some(Permission::Download),
+            ApiTokenScope::Upload => Some(Permission::UploadTorrent),
+            ApiTokenScope::Post => Some(Permission::CreateForumPost),
+        }
+    }
+}
+
+impl fmt::Display for ApiTokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An IP address or CIDR range in a token's allowlist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Whether `ip` falls inside the range
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_ipv4() == self.network.is_ipv4() && mask(ip, self.prefix_len) == self.network
+    }
+}
+
+/// Keep the first `prefix_len` bits of `ip`
+fn mask(ip: IpAddr, prefix_len: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            IpAddr::from((u32::from(v4) & mask).to_be_bytes())
+        }
+        IpAddr::V6(v6) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            IpAddr::from((u128::from(v6) & mask).to_be_bytes())
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = ApiTokenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ApiTokenError::InvalidIpRange(s.to_string());
+        let (addr, prefix_len) = match s.trim().split_once('/') {
+            Some((addr, len)) => (addr, Some(len.parse::<u8>().map_err(|_| invalid())?)),
+            None => (s.trim(), None),
+        };
+
+        let ip = addr
+            .parse::<IpAddr>()
+            .map_err(|_| invalid())?
+            .to_canonical();
+        let max_len = if ip.is_ipv4() { 32 } else { 128 };
+        let prefix_len = prefix_len.unwrap_or(max_len);
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+
+        // Store 10.1.2.3/8 as 10.0.0.0/8
+        Ok(Self {
+            network: mask(ip, prefix_len),
+            prefix_len,
+        })
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+/// A personal API token, as shown to its owner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    /// Start of the token, to help the user tell their tokens apart
+    pub token_prefix: String,
+    pub scopes: Vec<ApiTokenScope>,
+    /// IP ranges the token may be used from; empty means anywhere
+    pub allowed_ips: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiToken {
+    /// Check that the token may be used at `now` from `ip`
+    ///
+    /// A token with an allowlist is refused when the client IP is unknown.
+    pub fn check_usable(
+        &self,
+        now: DateTime<Utc>,
+        ip: Option<IpAddr>,
+    ) -> Result<(), ApiTokenError> {
+        if self.revoked_at.is_some() {
+            return Err(ApiTokenError::Revoked);
+        }
+        if self.expires_at <= now {
+            return Err(ApiTokenError::Expired);
+        }
+        if self.allowed_ips.is_empty() {
+            return Ok(());
+        }
+
+        let ip = ip.ok_or(ApiTokenError::IpNotAllowed)?;
+        let allowed = self
+            .allowed_ips
+            .iter()
+            .filter_map(|range| range.parse::<IpRange>().ok())
+            .any(|range| range.contains(ip));
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(ApiTokenError::IpNotAllowed)
+        }
+    }
+}
+
+/// A newly created token; the secret is only ever returned here
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedApiToken {
+    /// The token to give to the tool
+    pub token: String,
+    #[serde(flatten)]
+    pub details: ApiToken,
+}
+
+/// Request to create an API token
+#[derive(Debug, Clone, Validate, Deserialize)]
+pub struct CreateApiTokenRequest {
+    #[validate(length(min = 1, max = 100, message = "Name must be 1-100 characters"))]
+    pub name: String,
+
+    pub scopes: Vec<ApiTokenScope>,
+
+    /// Lifetime in days (default 90, at most 365)
+    #[serde(default)]
+    pub expires_in_days: Option<u32>,
+
+    /// IP addresses or CIDR ranges the token may be used from
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+}
+
+/// The caller behind a request authenticated with an API token
+#[derive(Debug, Clone)]
+pub struct ApiTokenPrincipal {
+    pub token_id: Uuid,
+    pub user_id: Uuid,
+    pub scopes: Vec<ApiTokenScope>,
+    /// Permissions the token carries: `ApiAccess` plus those its scopes
+    /// need, limited to what the user currently has
+    pub permissions: Vec<Permission>,
+}
+
+impl ApiTokenPrincipal {
+    /// Whether the token was granted `scope` and the user still holds the
+    /// permission it needs
+    pub fn has_scope(&self, scope: ApiTokenScope) -> bool {
+        self.scopes.contains(&scope)
+            && scope
+                .required_permission()
+                .map_or(true, |permission| self.permissions.contains(&permission))
+    }
+}
+
+/// Whether a credential is an API token rather than a JWT
+pub fn is_api_token(credential: &str) -> bool {
+    credential.starts_with(TOKEN_PREFIX)
+}
+
+/// Generate a new token secret
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Hash a token for storage and lookup
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Check that a user with `permissions` may create a token with `scopes`
+pub fn check_scopes(
+    scopes: &[ApiTokenScope],
+    permissions: &PermissionSet,
+) -> Result<(), ApiTokenError> {
+    if !permissions.has(Permission::ApiAccess) {
+        return Err(ApiTokenError::ApiAccessDenied);
+    }
+    if scopes.is_empty() {
+        return Err(ApiTokenError::NoScopes);
+    }
+
+    for scope in scopes {
+        if let Some(permission) = scope.required_permission() {
+            if !permissions.has(permission) {
+                return Err(ApiTokenError::ScopeNotAllowed(*scope));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Permissions a token with `scopes` carries for a user with `permissions`
+///
+/// Tokens never carry staff permissions, even for staff accounts.
+pub fn token_permissions(scopes: &[ApiTokenScope], permissions: &PermissionSet) -> Vec<Permission> {
+    let mut granted = vec![Permission::ApiAccess];
+    for permission in scopes
+        .iter()
+        .filter_map(|scope| scope.required_permission())
+    {
+        if permissions.has(permission) && !granted.contains(&permission) {
+            granted.push(permission);
+        }
+    }
+    granted
+}
+
+/// Database row for a token
+struct ApiTokenRow {
+    id: Uuid,
+    user_id: Uuid,
+    name: String,
+    token_prefix: String,
+    scopes: Vec<String>,
+    allowed_ips: Vec<String>,
+    expires_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    last_used_ip: Option<String>,
+    created_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiTokenRow> for ApiToken {
+    fn from(row: ApiTokenRow) -> Self {
+        Self {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            token_prefix: row.token_prefix,
+            scopes: row
+                .scopes
+                .iter()
+                .filter_map(|scope| ApiTokenScope::parse(scope))
+                .collect(),
+            allowed_ips: row.allowed_ips,
+            expires_at: row.expires_at,
+            last_used_at: row.last_used_at,
+            last_used_ip: row.last_used_ip,
+            created_at: row.created_at,
+            revoked_at: row.revoked_at,
+        }
+    }
+}
+
+/// Issues, lists, revokes and authenticates API tokens
+///
+/// Scopes are checked against the owner's effective permissions, as
+/// resolved from their class and overrides, both when a token is created
+/// and every time it is used.
+#[derive(Clone)]
+pub struct ApiTokenService {
+    db_pool: PgPool,
+    permissions: Arc<PermissionResolver>,
+}
+
+impl ApiTokenService {
+    /// Create a new API token service
+    pub fn new(db_pool: PgPool, permissions: Arc<PermissionResolver>) -> Self {
+        Self {
+            db_pool,
+            permissions,
+        }
+    }
+
+    /// Create a token for a user
+    ///
+    /// The returned secret is not stored and cannot be shown again.
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        request: CreateApiTokenRequest,
+    ) -> Result<CreatedApiToken, ApiTokenError> {
+        request
+            .validate()
+            .map_err(|e| ApiTokenError::ValidationError(e.to_string()))?;
+
+        let expires_in_days = request.expires_in_days.unwrap_or(DEFAULT_EXPIRY_DAYS);
+        if expires_in_days == 0 || expires_in_days > MAX_EXPIRY_DAYS {
+            return Err(ApiTokenError::InvalidExpiry);
+        }
+
+        if request.allowed_ips.len() > MAX_ALLOWED_IPS {
+            return Err(ApiTokenError::TooManyIpRanges);
+        }
+        let allowed_ips = request
+            .allowed_ips
+            .iter()
+            .map(|range| range.parse::<IpRange>().map(|range| range.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut scopes = request.scopes;
+        scopes.sort_by_key(|scope| scope.as_str());
+        scopes.dedup();
+
+        // Disabled, banned and unknown users resolve to no permissions
+        check_scopes(&scopes, &self.permissions.resolve(user_id).await?)?;
+
+        let active_tokens = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "active_tokens!"
+            FROM api_tokens
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        if active_tokens >= MAX_TOKENS_PER_USER {
+            return Err(ApiTokenError::TooManyTokens);
+        }
+
+        let token = generate_token();
+        let token_prefix = token[..TOKEN_PREFIX.len() + DISPLAY_PREFIX_LEN].to_string();
+        let scope_names: Vec<String> = scopes.iter().map(|s| s.as_str().to_string()).collect();
+        let expires_at = Utc::now() + Duration::days(expires_in_days as i64);
+
+        let row = sqlx::query_as!(
+            ApiTokenRow,
+            r#"
+            INSERT INTO api_tokens
+                (user_id, name, token_hash, token_prefix, scopes, allowed_ips, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, user_id, name, token_prefix, scopes, allowed_ips, expires_at,
+                      last_used_at, host(last_used_ip) AS "last_used_ip?", created_at, revoked_at
+            "#,
+            user_id,
+            request.name,
+            hash_token(&token),
+            token_prefix,
+            &scope_names,
+            &allowed_ips,
+            expires_at
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        tracing::info!("User {} created API token {}", user_id, row.id);
+
+        Ok(CreatedApiToken {
+            token,
+            details: row.into(),
+        })
+    }
+
+    /// List a user's tokens, including revoked and expired ones
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<ApiToken>, ApiTokenError> {
+        let rows = sqlx::query_as!(
+            ApiTokenRow,
+            r#"
+            SELECT id, user_id, name, token_prefix, scopes, allowed_ips, expires_at,
+                   last_used_at, host(last_used_ip) AS "last_used_ip?", created_at, revoked_at
+            FROM api_tokens
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ApiToken::from).collect())
+    }
+
+    /// Revoke one of a user's tokens
+    pub async fn revoke(&self, user_id: Uuid, token_id: Uuid) -> Result<(), ApiTokenError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE api_tokens
+            SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+            token_id,
+            user_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ApiTokenError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Revoke all of a user's tokens, e.g. after a password change
+    pub async fn revoke_all(&self, user_id: Uuid) -> Result<u64, ApiTokenError> {
+        let result = sqlx::query!(
+            "UPDATE api_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+            user_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Authenticate a request made with an API token
+    ///
+    /// `ip` is the client address, checked against the token's allowlist.
+    pub async fn authenticate(
+        &self,
+        token: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<ApiTokenPrincipal, ApiTokenError> {
+        if !is_api_token(token) {
+            return Err(ApiTokenError::InvalidToken);
+        }
+
+        let api_token = sqlx::query_as!(
+            ApiTokenRow,
+            r#"
+            SELECT id, user_id, name, token_prefix, scopes, allowed_ips, expires_at,
+                   last_used_at, host(last_used_ip) AS "last_used_ip?", created_at, revoked_at
+            FROM api_tokens
+            WHERE token_hash = $1
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .map(ApiToken::from)
+        .ok_or(ApiTokenError::InvalidToken)?;
+
+        api_token.check_usable(Utc::now(), ip)?;
+
+        // Disabled, banned and deleted users resolve to no permissions
+        let permissions = self.permissions.resolve(api_token.user_id).await?;
+        if !permissions.has(Permission::ApiAccess) {
+            return Err(ApiTokenError::ApiAccessDenied);
+        }
+
+        // At most one write per token per minute
+        sqlx::query!(
+            r#"
+            UPDATE api_tokens
+            SET last_used_at = NOW(), last_used_ip = $2::text::inet
+            WHERE id = $1
+              AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+            "#,
+            api_token.id,
+            ip.map(|ip| ip.to_string())
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(ApiTokenPrincipal {
+            token_id: api_token.id,
+            user_id: api_token.user_id,
+            permissions: token_permissions(&api_token.scopes, &permissions),
+            scopes: api_token.scopes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::Role;
+
+    fn token(allowed_ips: &[&str]) -> ApiToken {
+        let now = Utc::now();
+        ApiToken {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: "autodl".to_string(),
+            token_prefix: "trk_abcdefgh".to_string(),
+            scopes: vec![ApiTokenScope::Download],
+            allowed_ips: allowed_ips.iter().map(|s| s.to_string()).collect(),
+            expires_at: now + Duration::days(1),
+            last_used_at: None,
+            last_used_ip: None,
+            created_at: now,
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn test_generated_token_format() {
+        let token = generate_token();
+        assert!(is_api_token(&token));
+        assert_eq!(token.len(), TOKEN_PREFIX.len() + 43);
+        assert_ne!(token, generate_token());
+
+        let hash = hash_token(&token);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_token(&token));
+    }
+
+    #[test]
+    fn test_ip_range_parsing() {
+        let range: IpRange = "10.1.2.3/8".parse().unwrap();
+        assert_eq!(range.to_string(), "10.0.0.0/8");
+        assert!(range.contains("10.200.0.1".parse().unwrap()));
+        assert!(!range.contains("11.0.0.1".parse().unwrap()));
+
+        let single: IpRange = "192.0.2.7".parse().unwrap();
+        assert_eq!(single.to_string(), "192.0.2.7/32");
+        assert!(single.contains("::ffff:192.0.2.7".parse().unwrap()));
+
+        let v6: IpRange = "2001:db8::/32".parse().unwrap();
+        assert!(v6.contains("2001:db8:1::5".parse().unwrap()));
+        assert!(!v6.contains("192.0.2.7".parse().unwrap()));
+
+        let any: IpRange = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("203.0.113.9".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("example.com".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn test_check_usable() {
+        let now = Utc::now();
+        let ip = Some("198.51.100.4".parse().unwrap());
+
+        assert!(token(&[]).check_usable(now, None).is_ok());
+        assert!(token(&["198.51.100.0/24"]).check_usable(now, ip).is_ok());
+        assert!(matches!(
+            token(&["203.0.113.0/24"]).check_usable(now, ip),
+            Err(ApiTokenError::IpNotAllowed)
+        ));
+        assert!(matches!(
+            token(&["198.51.100.0/24"]).check_usable(now, None),
+            Err(ApiTokenError::IpNotAllowed)
+        ));
+
+        let mut expired = token(&[]);
+        expired.expires_at = now;
+        assert!(matches!(
+            expired.check_usable(now, ip),
+            Err(ApiTokenError::Expired)
+        ));
+
+        let mut revoked = token(&[]);
+        revoked.revoked_at = Some(now);
+        assert!(matches!(
+            revoked.check_usable(now, ip),
+            Err(ApiTokenError::Revoked)
+        ));
+    }
+
+    #[test]
+    fn test_scopes_follow_role_permissions() {
+        let user = Role::User.permissions();
+        assert!(matches!(
+            check_scopes(&[ApiTokenScope::Download], &user),
+            Err(ApiTokenError::ApiAccessDenied)
+        ));
+
+        let power_user = Role::PowerUser.permissions();
+        assert!(check_scopes(&ApiTokenScope::all(), &power_user).is_ok());
+        assert!(matches!(
+            check_scopes(&[], &power_user),
+            Err(ApiTokenError::NoScopes)
+        ));
+
+        let mut no_upload = power_user.clone();
+        no_upload.remove(Permission::UploadTorrent);
+        assert!(matches!(
+            check_scopes(&[ApiTokenScope::Upload], &no_upload),
+            Err(ApiTokenError::ScopeNotAllowed(ApiTokenScope::Upload))
+        ));
+    }
+
+    #[test]
+    fn test_principal_scope_needs_current_permission() {
+        let mut principal = ApiTokenPrincipal {
+            token_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            scopes: vec![ApiTokenScope::ReadTorrents, ApiTokenScope::Upload],
+            permissions: vec![Permission::ApiAccess, Permission::UploadTorrent],
+        };
+        assert!(principal.has_scope(ApiTokenScope::Upload));
+        assert!(!principal.has_scope(ApiTokenScope::Download));
+
+        // Losing the permission disables the scope without touching the token
+        principal.permissions = vec![Permission::ApiAccess];
+        assert!(!principal.has_scope(ApiTokenScope::Upload));
+        assert!(principal.has_scope(ApiTokenScope::ReadTorrents));
+    }
+
+    #[test]
+    fn test_token_permissions_exclude_staff_powers() {
+        let admin = Role::Admin.permissions();
+        let granted = token_permissions(
+            &[ApiTokenScope::ReadTorrents, ApiTokenScope::Download],
+            &admin,
+        );
+
+        assert_eq!(granted, vec![Permission::ApiAccess, Permission::Download]);
+        assert!(!granted.contains(&Permission::SiteAdmin));
+    }
+
+    #[test]
+    fn test_scope_serialization() {
+        for scope in ApiTokenScope::all() {
+            let json = serde_json::to_string(&scope).unwrap();
+            assert_eq!(json, format!("\"{}\"", scope.as_str()));
+            assert_eq!(ApiTokenScope::parse(scope.as_str()), Some(scope));
+        }
+    }
+}
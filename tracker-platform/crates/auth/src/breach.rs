@@ -0,0 +1,349 @@
+//! Breached password screening
+//!
+//! New passwords are checked against a local copy of the Have I Been Pwned
+//! password corpus, so screening makes no external calls. The corpus is the
+//! k-anonymity range file set the HIBP downloader produces: one file per
+//! 5-character SHA-1 prefix (`00000` to `FFFFF`, optionally with a `.txt`
+//! extension), each line holding the remaining 35 hex characters of a hash
+//! and how often it was seen, e.g. `0018A45C4D1DEF81644B54AB7F969B88D65:10`.
+//!
+//! Screening fails open: if the corpus cannot be read the password is
+//! accepted and a warning logged, so a missing mount never blocks sign-ups.
+//!
+//! After the corpus is updated, admins can ask for every account to be
+//! rescreened with [`BreachScreening::request_rescreen`]. Passwords are only
+//! stored as Argon2 hashes, so the check happens at each user's next
+//! password login; a password found in the corpus must then be reset.
+
+use crate::api_tokens::ApiTokenService;
+use crate::password::PasswordError;
+use crate::session::SessionManager;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use sqlx::PgPool;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Errors from reading the corpus or recording rescreens
+#[derive(Debug, Error)]
+pub enum BreachError {
+    #[error("Breached password corpus not found at {0}")]
+    CorpusNotFound(String),
+
+    #[error("IO error: {0}")]
+    Io(String),
+
+    #[error("User not found")]
+    UserNotFound,
+
+    #[error("Failed to sign out sessions: {0}")]
+    SignOut(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+impl From<sqlx::Error> for BreachError {
+    fn from(e: sqlx::Error) -> Self {
+        BreachError::Database(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for BreachError {
+    fn from(e: std::io::Error) -> Self {
+        BreachError::Io(e.to_string())
+    }
+}
+
+/// A local HIBP-format range file corpus
+#[derive(Debug, Clone)]
+pub struct BreachedPasswords {
+    dir: PathBuf,
+    min_occurrences: u64,
+}
+
+impl BreachedPasswords {
+    /// Use the range files in `dir`
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, BreachError> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            return Err(BreachError::CorpusNotFound(dir.display().to_string()));
+        }
+
+        Ok(Self {
+            dir,
+            min_occurrences: 1,
+        })
+    }
+
+    /// Only reject passwords seen at least `min_occurrences` times
+    pub fn with_min_occurrences(mut self, min_occurrences: u64) -> Self {
+        self.min_occurrences = min_occurrences.max(1);
+        self
+    }
+
+    /// Directory holding the range files
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// How often `password` appears in the corpus (0 if never)
+    pub async fn occurrences(&self, password: &str) -> Result<u64, BreachError> {
+        let (prefix, suffix) = hash_prefix(password);
+
+        let mut contents = None;
+        for name in [prefix.clone(), format!("{}.txt", prefix)] {
+            match tokio::fs::read_to_string(self.dir.join(name)).await {
+                Ok(text) => {
+                    contents = Some(text);
+                    break;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        match contents {
+            Some(text) => Ok(range_lookup(&text, &suffix)),
+            None => Err(BreachError::Io(format!("Range file {} is missing", prefix))),
+        }
+    }
+
+    /// Whether `password` appears in the corpus often enough to be rejected
+    pub async fn is_breached(&self, password: &str) -> Result<bool, BreachError> {
+        Ok(self.occurrences(password).await? >= self.min_occurrences)
+    }
+
+    /// Reject a new password found in the corpus
+    ///
+    /// Corpus read errors are logged and the password accepted.
+    pub async fn screen(&self, password: &str) -> Result<(), PasswordError> {
+        match self.occurrences(password).await {
+            Ok(count) if count >= self.min_occurrences => Err(PasswordError::Breached(count)),
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::warn!("Breached password check skipped: {}", e);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Upper-case SHA-1 of `password`, split into the 5-character range prefix
+/// and the 35-character suffix
+pub fn hash_prefix(password: &str) -> (String, String) {
+    // SHA-1 only because the corpus is keyed by it
+    let hash = hex::encode_upper(digest(&SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes()));
+    let (prefix, suffix) = hash.split_at(5);
+    (prefix.to_string(), suffix.to_string())
+}
+
+/// Count for `suffix` in the contents of a range file
+///
+/// Padding entries (count 0) and malformed lines count as absent.
+pub fn range_lookup(contents: &str, suffix: &str) -> u64 {
+    contents
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(hash, _)| hash.eq_ignore_ascii_case(suffix))
+        .and_then(|(_, count)| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Forced password resets and rescreening after corpus updates
+#[derive(Clone)]
+pub struct BreachScreening {
+    db_pool: PgPool,
+    sessions: Option<Arc<SessionManager>>,
+    api_tokens: Option<Arc<ApiTokenService>>,
+}
+
+impl BreachScreening {
+    /// Create a new screening service
+    pub fn new(db_pool: PgPool) -> Self {
+        Self {
+            db_pool,
+            sessions: None,
+            api_tokens: None,
+        }
+    }
+
+    /// End the user's sessions and revoke their API tokens on a forced reset
+    pub fn with_sign_out(
+        mut self,
+        sessions: Arc<SessionManager>,
+        api_tokens: Arc<ApiTokenService>,
+    ) -> Self {
+        self.sessions = Some(sessions);
+        self.api_tokens = Some(api_tokens);
+        self
+    }
+
+    /// Check the passwords of `user_ids` (or of everyone) at their next login
+    ///
+    /// Returns the number of accounts marked.
+    pub async fn request_rescreen(&self, user_ids: Option<&[Uuid]>) -> Result<u64, BreachError> {
+        let result = match user_ids {
+            Some(ids) => {
+                sqlx::query!(
+                    "UPDATE users SET password_breach_check_pending = true WHERE id = ANY($1)",
+                    ids
+                )
+                .execute(&self.db_pool)
+                .await?
+            }
+            None => {
+                sqlx::query!("UPDATE users SET password_breach_check_pending = true")
+                    .execute(&self.db_pool)
+                    .await?
+            }
+        };
+
+        tracing::info!(
+            "Marked {} accounts for breached password rescreening",
+            result.rows_affected()
+        );
+        Ok(result.rows_affected())
+    }
+
+    /// Require `user_id` to choose a new password before logging in again
+    ///
+    /// Their sessions and API tokens stop working straight away.
+    pub async fn force_reset(&self, user_id: Uuid) -> Result<(), BreachError> {
+        let result = sqlx::query!(
+            "UPDATE users SET password_reset_required = true WHERE id = $1",
+            user_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(BreachError::UserNotFound);
+        }
+
+        // The password may be in an attacker's hands; cut off what it opened
+        if let Some(sessions) = &self.sessions {
+            sessions
+                .delete_all_user_sessions(user_id)
+                .await
+                .map_err(|e| BreachError::SignOut(e.to_string()))?;
+        }
+        if let Some(api_tokens) = &self.api_tokens {
+            api_tokens
+                .revoke_all(user_id)
+                .await
+                .map_err(|e| BreachError::SignOut(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Record the outcome of a login-time check
+    ///
+    /// A breached password also sets `password_reset_required`.
+    pub async fn record_check(&self, user_id: Uuid, breached: bool) -> Result<(), BreachError> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_breach_check_pending = false,
+                password_reset_required = password_reset_required OR $2
+            WHERE id = $1
+            "#,
+            user_id,
+            breached
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("breach-corpus-{}", Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_hash_prefix() {
+        // SHA-1("password") = 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        let (prefix, suffix) = hash_prefix("password");
+        assert_eq!(prefix, "5BAA6");
+        assert_eq!(suffix, "1E4C9B93F3F0682250B6CF8331B7EE68FD8");
+    }
+
+    #[test]
+    fn test_range_lookup() {
+        let contents = "003D68EB55068C33ACE09247EE4C639306B:3\r\n\
+                        1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n\
+                        1E4C9B93F3F0682250B6CF8331B7EE68FD9:0\r\n\
+                        garbage\n";
+
+        assert_eq!(
+            range_lookup(contents, "1E4C9B93F3F0682250B6CF8331B7EE68FD8"),
+            9545824
+        );
+        assert_eq!(
+            range_lookup(contents, "1e4c9b93f3f0682250b6cf8331b7ee68fd8"),
+            9545824
+        );
+        // Padding entry
+        assert_eq!(
+            range_lookup(contents, "1E4C9B93F3F0682250B6CF8331B7EE68FD9"),
+            0
+        );
+        assert_eq!(
+            range_lookup(contents, "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_screen_against_corpus() {
+        let dir = corpus_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("5BAA6.txt"),
+            "1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\n",
+        )
+        .unwrap();
+        // SHA-1("rare-breached-pw") prefix file, seen twice
+        let (prefix, suffix) = hash_prefix("rare-breached-pw");
+        std::fs::write(dir.join(&prefix), format!("{}:2\n", suffix)).unwrap();
+
+        let corpus = BreachedPasswords::open(&dir).unwrap();
+        assert_eq!(corpus.occurrences("password").await.unwrap(), 9545824);
+        assert!(matches!(
+            corpus.screen("password").await,
+            Err(PasswordError::Breached(9545824))
+        ));
+        assert!(corpus.is_breached("rare-breached-pw").await.unwrap());
+
+        let lenient = corpus.with_min_occurrences(10);
+        assert!(!lenient.is_breached("rare-breached-pw").await.unwrap());
+        assert!(lenient.screen("rare-breached-pw").await.is_ok());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_screen_fails_open() {
+        let dir = corpus_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let corpus = BreachedPasswords::open(&dir).unwrap();
+        // No range file for this prefix
+        assert!(corpus.occurrences("password").await.is_err());
+        assert!(corpus.screen("password").await.is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(
+            BreachedPasswords::open(&dir),
+            Err(BreachError::CorpusNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,816 @@
+//! Database-driven user classes
+//!
+//! A user's class is their row in `user_groups` (via `users.group_id`), and
+//! the permissions each class carries live in `user_group_permissions`, so
+//! admins can add classes such as "Uploader", "VIP" or "Interviewer" without
+//! a code change. Individual users can additionally be granted or denied
+//! single permissions in `user_permission_overrides`; a denial always wins.
+//!
+//! [`PermissionResolver`] computes a user's effective permissions and caches
+//! them in Redis. Every change made through [`UserClassService`] invalidates
+//! the affected cache entries, so new permissions apply on the user's next
+//! request without a fresh login. Overrides that expire on their own are
+//! picked up when the cache entry does, after at most
+//! [`DEFAULT_CACHE_TTL_SECS`].
+//!
+//! Cache entries are tagged with the class generation and the user's
+//! permission version at the time they were read. Invalidation bumps one of
+//! the two, so an entry written by a resolve that raced with a change is
+//! never served.
+
+use crate::permissions::{Permission, PermissionSet};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+use validator::Validate;
+
+/// How long resolved permissions are cached
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 300;
+
+/// Errors from user class management and permission resolution
+#[derive(Debug, Error)]
+pub enum ClassError {
+    #[error("User class not found")]
+    NotFound,
+
+    #[error("User not found")]
+    UserNotFound,
+
+    #[error("A user class with this name already exists")]
+    NameTaken,
+
+    #[error("User class is still assigned to users")]
+    ClassInUse,
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Redis error: {0}")]
+    Redis(String),
+}
+
+impl From<sqlx::Error> for ClassError {
+    fn from(e: sqlx::Error) -> Self {
+        ClassError::Database(e.to_string())
+    }
+}
+
+impl From<redis::RedisError> for ClassError {
+    fn from(e: redis::RedisError) -> Self {
+        ClassError::Redis(e.to_string())
+    }
+}
+
+/// A user class and the permissions it carries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserClass {
+    pub id: i32,
+    /// Identifier, e.g. `power_user`
+    pub name: String,
+    pub display_name: String,
+    /// Rank; higher classes are more senior
+    pub level: i32,
+    pub permissions: Vec<Permission>,
+}
+
+/// A permission granted or denied to a single user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionOverride {
+    pub permission: Permission,
+    /// `true` grants the permission, `false` denies it
+    pub granted: bool,
+    pub reason: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Request to create a user class
+#[derive(Debug, Clone, Validate, Deserialize)]
+pub struct CreateClassRequest {
+    #[validate(length(min = 1, max = 100, message = "Name must be 1-100 characters"))]
+    pub name: String,
+
+    #[validate(length(min = 1, max = 100, message = "Display name must be 1-100 characters"))]
+    pub display_name: String,
+
+    pub level: i32,
+
+    pub permissions: Vec<Permission>,
+}
+
+/// Changes to a user class; `None` leaves a field as it is
+#[derive(Debug, Clone, Default, Validate, Deserialize)]
+pub struct UpdateClassRequest {
+    #[validate(length(min = 1, max = 100, message = "Display name must be 1-100 characters"))]
+    pub display_name: Option<String>,
+
+    pub level: Option<i32>,
+
+    /// Replaces the class's permissions
+    pub permissions: Option<Vec<Permission>>,
+}
+
+/// Request to grant or deny a permission to one user
+#[derive(Debug, Clone, Validate, Deserialize)]
+pub struct SetOverrideRequest {
+    pub permission: Permission,
+    pub granted: bool,
+
+    #[validate(length(max = 500, message = "Reason must be at most 500 characters"))]
+    pub reason: Option<String>,
+
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Check a class identifier: lowercase letters, digits and underscores
+pub fn validate_class_name(name: &str) -> Result<(), ClassError> {
+    let valid = !name.is_empty()
+        && name.len() <= 100
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ClassError::ValidationError(
+            "Class name may only contain lowercase letters, digits and underscores".to_string(),
+        ))
+    }
+}
+
+/// Parse permission names read from the database
+///
+/// Names of permissions that no longer exist are skipped.
+pub fn parse_permissions(names: &[String]) -> Vec<Permission> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let permission = Permission::parse(name);
+            if permission.is_none() {
+                tracing::warn!("Ignoring unknown permission {} in the database", name);
+            }
+            permission
+        })
+        .collect()
+}
+
+/// Combine a class's permissions with a user's overrides
+pub fn resolve_permissions(
+    class_permissions: &[Permission],
+    grants: &[Permission],
+    denials: &[Permission],
+) -> PermissionSet {
+    let mut permissions = PermissionSet::from_vec(class_permissions.to_vec());
+    for permission in grants {
+        permissions.add(*permission);
+    }
+    for permission in denials {
+        permissions.deny(*permission);
+    }
+    permissions
+}
+
+fn permission_names(permissions: &[Permission]) -> Vec<String> {
+    let mut names: Vec<String> = permissions.iter().map(|p| p.as_str().to_string()).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Cached permissions, tagged with the class generation and user version
+/// they were resolved in
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedPermissions {
+    generation: u64,
+    #[serde(default)]
+    version: u64,
+    permissions: PermissionSet,
+}
+
+/// Cache counters read before resolving, used to tag the stored entry
+#[derive(Debug, Clone, Copy)]
+struct CacheStamp {
+    generation: u64,
+    version: u64,
+}
+
+/// Resolves users' effective permissions, with a Redis cache
+pub struct PermissionResolver {
+    db_pool: PgPool,
+    redis_client: redis::Client,
+    cache_ttl_secs: u64,
+    key_prefix: String,
+}
+
+impl PermissionResolver {
+    /// Create a new resolver
+    pub fn new(db_pool: PgPool, redis_client: redis::Client) -> Self {
+        Self {
+            db_pool,
+            redis_client,
+            cache_ttl_secs: DEFAULT_CACHE_TTL_SECS,
+            key_prefix: "auth:permissions".to_string(),
+        }
+    }
+
+    /// Set how long resolved permissions are cached
+    pub fn with_cache_ttl(mut self, secs: u64) -> Self {
+        self.cache_ttl_secs = secs;
+        self
+    }
+
+    fn generation_key(&self) -> String {
+        format!("{}:generation", self.key_prefix)
+    }
+
+    fn user_key(&self, user_id: Uuid) -> String {
+        format!("{}:user:{}", self.key_prefix, user_id)
+    }
+
+    fn version_key(&self, user_id: Uuid) -> String {
+        format!("{}:version:{}", self.key_prefix, user_id)
+    }
+
+    /// A user's effective permissions
+    ///
+    /// Disabled and unknown users have none. If Redis is unavailable the
+    /// permissions are loaded from the database.
+    pub async fn resolve(&self, user_id: Uuid) -> Result<PermissionSet, ClassError> {
+        let stamp = match self.cached(user_id).await {
+            Ok((_, Some(permissions))) => return Ok(permissions),
+            Ok((stamp, None)) => Some(stamp),
+            Err(e) => {
+                tracing::warn!("Permission cache unavailable: {}", e);
+                None
+            }
+        };
+
+        let permissions = self.load(user_id).await?;
+
+        // Tagged with the counters read before loading: if the user was
+        // invalidated meanwhile, the entry is stale on arrival and ignored
+        if let Some(stamp) = stamp {
+            if let Err(e) = self.store(user_id, stamp, &permissions).await {
+                tracing::warn!("Failed to cache permissions for {}: {}", user_id, e);
+            }
+        }
+
+        Ok(permissions)
+    }
+
+    /// Load a user's effective permissions from the database, bypassing
+    /// the cache
+    pub async fn load(&self, user_id: Uuid) -> Result<PermissionSet, ClassError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                ARRAY(SELECT permission::text FROM user_group_permissions
+                      WHERE group_id = u.group_id) AS "class_permissions!",
+                ARRAY(SELECT permission::text FROM user_permission_overrides o
+                      WHERE o.user_id = u.id AND o.granted
+                        AND (o.expires_at IS NULL OR o.expires_at > NOW())) AS "grants!",
+                ARRAY(SELECT permission::text FROM user_permission_overrides o
+                      WHERE o.user_id = u.id AND NOT o.granted
+                        AND (o.expires_at IS NULL OR o.expires_at > NOW())) AS "denials!"
+            FROM users u
+            WHERE u.id = $1
+              AND u.is_active = true
+              AND u.is_banned = false
+              AND u.deleted_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(row
+            .map(|row| {
+                resolve_permissions(
+                    &parse_permissions(&row.class_permissions),
+                    &parse_permissions(&row.grants),
+                    &parse_permissions(&row.denials),
+                )
+            })
+            .unwrap_or_default())
+    }
+
+    /// Drop a user's cached permissions, e.g. after a class change
+    ///
+    /// Also bumps the user's version, so a resolve already in flight cannot
+    /// put the old permissions back. The version outlives any entry written
+    /// before the bump.
+    pub async fn invalidate_user(&self, user_id: Uuid) -> Result<(), ClassError> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+
+        redis::pipe()
+            .atomic()
+            .cmd("DEL")
+            .arg(self.user_key(user_id))
+            .ignore()
+            .cmd("INCR")
+            .arg(self.version_key(user_id))
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(self.version_key(user_id))
+            .arg(self.cache_ttl_secs * 2)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Drop every user's cached permissions, e.g. after a class is edited
+    pub async fn invalidate_all(&self) -> Result<(), ClassError> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+
+        redis::cmd("INCR")
+            .arg(self.generation_key())
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Current cache counters and the user's cache entry, if still valid
+    async fn cached(
+        &self,
+        user_id: Uuid,
+    ) -> Result<(CacheStamp, Option<PermissionSet>), ClassError> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+
+        let (generation, version, data): (Option<u64>, Option<u64>, Option<String>) =
+            redis::cmd("MGET")
+                .arg(self.generation_key())
+                .arg(self.version_key(user_id))
+                .arg(self.user_key(user_id))
+                .query_async(&mut conn)
+                .await?;
+        let stamp = CacheStamp {
+            generation: generation.unwrap_or(0),
+            version: version.unwrap_or(0),
+        };
+
+        let permissions = data
+            .and_then(|data| serde_json::from_str::<CachedPermissions>(&data).ok())
+            .filter(|cached| {
+                cached.generation == stamp.generation && cached.version == stamp.version
+            })
+            .map(|cached| cached.permissions);
+
+        Ok((stamp, permissions))
+    }
+
+    async fn store(
+        &self,
+        user_id: Uuid,
+        stamp: CacheStamp,
+        permissions: &PermissionSet,
+    ) -> Result<(), ClassError> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+
+        let data = serde_json::to_string(&CachedPermissions {
+            generation: stamp.generation,
+            version: stamp.version,
+            permissions: permissions.clone(),
+        })
+        .map_err(|e| ClassError::Redis(e.to_string()))?;
+
+        redis::cmd("SET")
+            .arg(self.user_key(user_id))
+            .arg(data)
+            .arg("EX")
+            .arg(self.cache_ttl_secs)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Database row for a class
+struct UserClassRow {
+    id: i32,
+    name: String,
+    display_name: String,
+    level: i32,
+    permissions: Vec<String>,
+}
+
+impl From<UserClassRow> for UserClass {
+    fn from(row: UserClassRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            display_name: row.display_name,
+            level: row.level,
+            permissions: parse_permissions(&row.permissions),
+        }
+    }
+}
+
+/// Manages user classes, class membership and per-user overrides
+pub struct UserClassService {
+    db_pool: PgPool,
+    resolver: Arc<PermissionResolver>,
+}
+
+impl UserClassService {
+    /// Create a new user class service
+    pub fn new(db_pool: PgPool, resolver: Arc<PermissionResolver>) -> Self {
+        Self { db_pool, resolver }
+    }
+
+    /// List all classes, lowest level first
+    pub async fn list_classes(&self) -> Result<Vec<UserClass>, ClassError> {
+        let rows = sqlx::query_as!(
+            UserClassRow,
+            r#"
+            SELECT g.id, g.name, g.display_name, g.level,
+                   ARRAY(SELECT permission::text FROM user_group_permissions p
+                         WHERE p.group_id = g.id ORDER BY permission) AS "permissions!"
+            FROM user_groups g
+            ORDER BY g.level, g.name
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(UserClass::from).collect())
+    }
+
+    /// Get a class by ID
+    pub async fn get_class(&self, class_id: i32) -> Result<UserClass, ClassError> {
+        let row = sqlx::query_as!(
+            UserClassRow,
+            r#"
+            SELECT g.id, g.name, g.display_name, g.level,
+                   ARRAY(SELECT permission::text FROM user_group_permissions p
+                         WHERE p.group_id = g.id ORDER BY permission) AS "permissions!"
+            FROM user_groups g
+            WHERE g.id = $1
+            "#,
+            class_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(ClassError::NotFound)?;
+
+        Ok(row.into())
+    }
+
+    /// Get the class a user belongs to
+    pub async fn user_class(&self, user_id: Uuid) -> Result<UserClass, ClassError> {
+        let class_id = sqlx::query_scalar!("SELECT group_id FROM users WHERE id = $1", user_id)
+            .fetch_optional(&self.db_pool)
+            .await?
+            .ok_or(ClassError::UserNotFound)?;
+
+        self.get_class(class_id).await
+    }
+
+    /// Create a class
+    pub async fn create_class(&self, request: CreateClassRequest) -> Result<UserClass, ClassError> {
+        request
+            .validate()
+            .map_err(|e| ClassError::ValidationError(e.to_string()))?;
+        validate_class_name(&request.name)?;
+
+        let mut tx = self.db_pool.begin().await?;
+
+        let class_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO user_groups (name, display_name, level)
+            VALUES ($1, $2, $3)
+            RETURNING id
+            "#,
+            request.name,
+            request.display_name,
+            request.level
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => ClassError::NameTaken,
+            e => e.into(),
+        })?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_group_permissions (group_id, permission)
+            SELECT $1, unnest($2::text[])
+            "#,
+            class_id,
+            &permission_names(&request.permissions)
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        tracing::info!("Created user class {}", request.name);
+
+        self.get_class(class_id).await
+    }
+
+    /// Update a class; its members' new permissions apply immediately
+    pub async fn update_class(
+        &self,
+        class_id: i32,
+        request: UpdateClassRequest,
+    ) -> Result<UserClass, ClassError> {
+        request
+            .validate()
+            .map_err(|e| ClassError::ValidationError(e.to_string()))?;
+
+        let mut tx = self.db_pool.begin().await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_groups
+            SET display_name = COALESCE($2, display_name),
+                level = COALESCE($3, level),
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+            class_id,
+            request.display_name,
+            request.level
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ClassError::NotFound);
+        }
+
+        if let Some(permissions) = &request.permissions {
+            sqlx::query!(
+                "DELETE FROM user_group_permissions WHERE group_id = $1",
+                class_id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query!(
+                r#"
+                INSERT INTO user_group_permissions (group_id, permission)
+                SELECT $1, unnest($2::text[])
+                "#,
+                class_id,
+                &permission_names(permissions)
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        if request.permissions.is_some() {
+            self.resolver.invalidate_all().await?;
+        }
+
+        self.get_class(class_id).await
+    }
+
+    /// Delete a class that no user belongs to
+    pub async fn delete_class(&self, class_id: i32) -> Result<(), ClassError> {
+        let result = sqlx::query!("DELETE FROM user_groups WHERE id = $1", class_id)
+            .execute(&self.db_pool)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(ref db) if db.is_foreign_key_violation() => {
+                    ClassError::ClassInUse
+                }
+                e => e.into(),
+            })?;
+
+        if result.rows_affected() == 0 {
+            return Err(ClassError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Move a user to another class
+    ///
+    /// Any pending automatic demotion was for the old class, so it is
+    /// cancelled.
+    pub async fn set_user_class(&self, user_id: Uuid, class_id: i32) -> Result<(), ClassError> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let result = sqlx::query!(
+            "UPDATE users SET group_id = $2, updated_at = NOW() WHERE id = $1",
+            user_id,
+            class_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_foreign_key_violation() => ClassError::NotFound,
+            e => e.into(),
+        })?;
+
+        if result.rows_affected() == 0 {
+            return Err(ClassError::UserNotFound);
+        }
+
+        sqlx::query!(
+            "DELETE FROM user_class_demotion_notices WHERE user_id = $1",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        self.resolver.invalidate_user(user_id).await
+    }
+
+    /// A user's permission overrides, including expired ones
+    pub async fn list_overrides(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<PermissionOverride>, ClassError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT permission, granted, reason, created_by, created_at, expires_at
+            FROM user_permission_overrides
+            WHERE user_id = $1
+            ORDER BY permission
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(PermissionOverride {
+                    permission: Permission::parse(&row.permission)?,
+                    granted: row.granted,
+                    reason: row.reason,
+                    created_by: row.created_by,
+                    created_at: row.created_at,
+                    expires_at: row.expires_at,
+                })
+            })
+            .collect())
+    }
+
+    /// Grant or deny a permission to one user, replacing any existing
+    /// override for it
+    pub async fn set_override(
+        &self,
+        admin_id: Uuid,
+        user_id: Uuid,
+        request: SetOverrideRequest,
+    ) -> Result<(), ClassError> {
+        request
+            .validate()
+            .map_err(|e| ClassError::ValidationError(e.to_string()))?;
+
+        if request
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return Err(ClassError::ValidationError(
+                "Expiry must be in the future".to_string(),
+            ));
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_permission_overrides
+                (user_id, permission, granted, reason, created_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (user_id, permission) DO UPDATE
+            SET granted = EXCLUDED.granted,
+                reason = EXCLUDED.reason,
+                created_by = EXCLUDED.created_by,
+                created_at = NOW(),
+                expires_at = EXCLUDED.expires_at
+            "#,
+            user_id,
+            request.permission.as_str(),
+            request.granted,
+            request.reason,
+            admin_id,
+            request.expires_at
+        )
+        .execute(&self.db_pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_foreign_key_violation() => {
+                ClassError::UserNotFound
+            }
+            e => e.into(),
+        })?;
+
+        tracing::info!(
+            "Admin {} {} {} for user {}",
+            admin_id,
+            if request.granted { "granted" } else { "denied" },
+            request.permission.as_str(),
+            user_id
+        );
+
+        self.resolver.invalidate_user(user_id).await
+    }
+
+    /// Remove a user's override for a permission
+    pub async fn remove_override(
+        &self,
+        user_id: Uuid,
+        permission: Permission,
+    ) -> Result<(), ClassError> {
+        let result = sqlx::query!(
+            "DELETE FROM user_permission_overrides WHERE user_id = $1 AND permission = $2",
+            user_id,
+            permission.as_str()
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ClassError::NotFound);
+        }
+
+        self.resolver.invalidate_user(user_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::permissions::Role;
+
+    #[test]
+    fn test_resolve_permissions() {
+        let class = Role::User.permissions().all();
+
+        let permissions = resolve_permissions(&class, &[Permission::AutoDownload], &[]);
+        assert!(permissions.has(Permission::AutoDownload));
+        assert!(permissions.has(Permission::UploadTorrent));
+
+        let permissions = resolve_permissions(&class, &[], &[Permission::UploadTorrent]);
+        assert!(!permissions.has(Permission::UploadTorrent));
+        assert!(permissions.has(Permission::Download));
+
+        // A denial beats a grant of the same permission
+        let permissions = resolve_permissions(&class, &[Permission::Donor], &[Permission::Donor]);
+        assert!(!permissions.has(Permission::Donor));
+    }
+
+    #[test]
+    fn test_denial_applies_to_site_admins() {
+        let permissions =
+            resolve_permissions(&[Permission::SiteAdmin], &[], &[Permission::ViewUserIPs]);
+
+        assert!(permissions.has(Permission::ManageUsers));
+        assert!(!permissions.has(Permission::ViewUserIPs));
+    }
+
+    #[test]
+    fn test_parse_permissions_skips_unknown() {
+        let names = vec![
+            "download".to_string(),
+            "retired_permission".to_string(),
+            "upload_torrent".to_string(),
+        ];
+
+        assert_eq!(
+            parse_permissions(&names),
+            vec![Permission::Download, Permission::UploadTorrent]
+        );
+    }
+
+    #[test]
+    fn test_validate_class_name() {
+        assert!(validate_class_name("interviewer").is_ok());
+        assert!(validate_class_name("vip_2").is_ok());
+        assert!(validate_class_name("").is_err());
+        assert!(validate_class_name("Power User").is_err());
+        assert!(validate_class_name("uploader;").is_err());
+    }
+
+    #[test]
+    fn test_permission_names_sorted_and_unique() {
+        let names = permission_names(&[
+            Permission::UploadTorrent,
+            Permission::Download,
+            Permission::UploadTorrent,
+        ]);
+
+        assert_eq!(names, vec!["download", "upload_torrent"]);
+    }
+}
@@ -0,0 +1,343 @@
+//! Password change and reset
+//!
+//! Signed-in users change their password by confirming the current one;
+//! anyone else asks for a reset link by email. Reset tokens are random,
+//! stored only as SHA-256 hashes, expire after an hour and work once.
+//!
+//! Every new password goes through the strength policy and, when a corpus
+//! is configured, breached password screening. Setting a new password
+//! clears a forced reset and any pending rescreen, and signs the user out
+//! of every session and API token.
+
+use crate::api_tokens::ApiTokenService;
+use crate::breach::BreachedPasswords;
+use crate::email::{templates::DEFAULT_LOCALE, EmailService};
+use crate::password::{hash_password, verify_password, PasswordError, PasswordStrength};
+use crate::session::SessionManager;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// How long a password reset link works
+pub const RESET_TOKEN_TTL_MINUTES: i64 = 60;
+
+/// Errors from changing or resetting a password
+#[derive(Debug, Error)]
+pub enum CredentialError {
+    #[error("Current password is incorrect")]
+    InvalidCurrentPassword,
+
+    #[error("Password reset link is invalid or has expired")]
+    InvalidResetToken,
+
+    #[error("User not found")]
+    UserNotFound,
+
+    #[error(transparent)]
+    Password(#[from] PasswordError),
+
+    #[error("Failed to send email: {0}")]
+    Email(String),
+
+    #[error("Failed to sign out old sessions: {0}")]
+    SignOut(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+impl From<sqlx::Error> for CredentialError {
+    fn from(e: sqlx::Error) -> Self {
+        CredentialError::Database(e.to_string())
+    }
+}
+
+/// Changes and resets passwords
+pub struct PasswordService {
+    db_pool: PgPool,
+    strength: PasswordStrength,
+    breached: Option<Arc<BreachedPasswords>>,
+    email: Option<Arc<EmailService>>,
+    sessions: Option<Arc<SessionManager>>,
+    api_tokens: Option<Arc<ApiTokenService>>,
+    reset_ttl: Duration,
+}
+
+impl PasswordService {
+    /// Create a service with the default strength policy and no screening
+    pub fn new(db_pool: PgPool) -> Self {
+        Self {
+            db_pool,
+            strength: PasswordStrength::default(),
+            breached: None,
+            email: None,
+            sessions: None,
+            api_tokens: None,
+            reset_ttl: Duration::minutes(RESET_TOKEN_TTL_MINUTES),
+        }
+    }
+
+    /// Use a different strength policy
+    pub fn with_strength(mut self, strength: PasswordStrength) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    /// Reject new passwords found in `corpus`
+    pub fn with_breach_corpus(mut self, corpus: Arc<BreachedPasswords>) -> Self {
+        self.breached = Some(corpus);
+        self
+    }
+
+    /// Send reset links through `email`
+    pub fn with_email(mut self, email: Arc<EmailService>) -> Self {
+        self.email = Some(email);
+        self
+    }
+
+    /// End the user's sessions and revoke their API tokens on a new password
+    pub fn with_sign_out(
+        mut self,
+        sessions: Arc<SessionManager>,
+        api_tokens: Arc<ApiTokenService>,
+    ) -> Self {
+        self.sessions = Some(sessions);
+        self.api_tokens = Some(api_tokens);
+        self
+    }
+
+    /// Check a new password against the strength policy and breach corpus
+    pub async fn check_new_password(&self, password: &str) -> Result<(), PasswordError> {
+        self.strength.validate(password)?;
+        if let Some(corpus) = &self.breached {
+            corpus.screen(password).await?;
+        }
+        Ok(())
+    }
+
+    /// Change a signed-in user's password
+    pub async fn change_password(
+        &self,
+        user_id: Uuid,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<(), CredentialError> {
+        let password_hash =
+            sqlx::query_scalar!("SELECT password_hash FROM users WHERE id = $1", user_id)
+                .fetch_optional(&self.db_pool)
+                .await?
+                .ok_or(CredentialError::UserNotFound)?;
+
+        verify_password(current_password, &password_hash)
+            .map_err(|_| CredentialError::InvalidCurrentPassword)?;
+
+        if current_password == new_password {
+            return Err(PasswordError::WeakPassword(
+                "new password must differ from the current one".to_string(),
+            )
+            .into());
+        }
+        self.check_new_password(new_password).await?;
+
+        self.set_password(user_id, new_password).await
+    }
+
+    /// Email a reset link to the account registered with `email`
+    ///
+    /// Succeeds whether or not the address belongs to an account, so the
+    /// response doesn't reveal who is registered.
+    pub async fn request_reset(&self, email: &str) -> Result<(), CredentialError> {
+        let Some(user) = sqlx::query!(
+            "SELECT id, email, username, locale FROM users WHERE email = $1",
+            email
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        else {
+            return Ok(());
+        };
+
+        let token = generate_reset_token();
+        sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens (token_hash, user_id, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            hash_reset_token(&token),
+            user.id,
+            Utc::now() + self.reset_ttl
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        match &self.email {
+            Some(service) => {
+                service
+                    .send_password_reset(
+                        user.id,
+                        &user.email,
+                        &user.username,
+                        user.locale.as_deref().unwrap_or(DEFAULT_LOCALE),
+                        &token,
+                    )
+                    .await
+                    .map_err(|e| CredentialError::Email(e.to_string()))?;
+            }
+            None => tracing::warn!("No email service; reset link for {} not sent", user.id),
+        }
+
+        Ok(())
+    }
+
+    /// Set a new password with a reset token
+    ///
+    /// Returns the user whose password was reset. Their other outstanding
+    /// reset links stop working.
+    pub async fn reset_password(
+        &self,
+        token: &str,
+        new_password: &str,
+    ) -> Result<Uuid, CredentialError> {
+        let token_hash = hash_reset_token(token);
+
+        let user_id = sqlx::query_scalar!(
+            r#"
+            SELECT user_id FROM password_reset_tokens
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+            "#,
+            token_hash
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(CredentialError::InvalidResetToken)?;
+
+        self.check_new_password(new_password).await?;
+
+        // Claim the token; a concurrent reset with the same link loses
+        let claimed = sqlx::query!(
+            r#"
+            UPDATE password_reset_tokens SET used_at = NOW()
+            WHERE user_id = $1 AND used_at IS NULL
+            RETURNING token_hash
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+        if !claimed.iter().any(|row| row.token_hash == token_hash) {
+            return Err(CredentialError::InvalidResetToken);
+        }
+
+        self.set_password(user_id, new_password).await?;
+
+        Ok(user_id)
+    }
+
+    async fn set_password(&self, user_id: Uuid, password: &str) -> Result<(), CredentialError> {
+        let password_hash = hash_password(password)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $2,
+                password_changed_at = NOW(),
+                password_reset_required = false,
+                password_breach_check_pending = false
+            WHERE id = $1
+            "#,
+            user_id,
+            password_hash
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(CredentialError::UserNotFound);
+        }
+
+        // Whoever knew the old password is signed out along with everyone else
+        if let Some(sessions) = &self.sessions {
+            sessions
+                .delete_all_user_sessions(user_id)
+                .await
+                .map_err(|e| CredentialError::SignOut(e.to_string()))?;
+        }
+        if let Some(api_tokens) = &self.api_tokens {
+            api_tokens
+                .revoke_all(user_id)
+                .await
+                .map_err(|e| CredentialError::SignOut(e.to_string()))?;
+        }
+
+        tracing::info!("Password changed for user {}", user_id);
+        Ok(())
+    }
+}
+
+/// Generate a reset token for a link
+fn generate_reset_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hash a reset token for storage and lookup
+fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> PasswordService {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/tracker_test")
+            .unwrap();
+        PasswordService::new(pool)
+    }
+
+    #[test]
+    fn test_reset_tokens_are_random_and_hashed() {
+        let a = generate_reset_token();
+        let b = generate_reset_token();
+        assert_ne!(a, b);
+        assert_eq!(a.len(), 43);
+
+        assert_eq!(hash_reset_token(&a), hash_reset_token(&a));
+        assert_eq!(hash_reset_token(&a).len(), 64);
+        assert_ne!(hash_reset_token(&a), hash_reset_token(&b));
+    }
+
+    #[tokio::test]
+    async fn test_check_new_password_strength() {
+        let service = service();
+        assert!(matches!(
+            service.check_new_password("short").await,
+            Err(PasswordError::WeakPassword(_))
+        ));
+        assert!(service.check_new_password("Str0ng!Passw0rd").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_new_password_breached() {
+        let dir = std::env::temp_dir().join(format!("breach-corpus-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (prefix, suffix) = crate::breach::hash_prefix("Str0ng!Passw0rd");
+        std::fs::write(dir.join(prefix), format!("{}:42\n", suffix)).unwrap();
+
+        let corpus = Arc::new(BreachedPasswords::open(&dir).unwrap());
+        let service = service().with_breach_corpus(corpus);
+        assert!(matches!(
+            service.check_new_password("Str0ng!Passw0rd").await,
+            Err(PasswordError::Breached(42))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
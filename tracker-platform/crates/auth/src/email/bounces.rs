@@ -0,0 +1,206 @@
+//! Asynchronous bounce and complaint reports
+//!
+//! Most failures only show up after the SMTP server accepted a message: the
+//! remote system mails back a delivery status notification (RFC 3464), or a
+//! mailbox provider forwards a spam complaint in the Abuse Reporting Format
+//! (RFC 5965). [`parse_report`] turns either into [`BounceReport`]s for
+//! [`EmailService::record_bounces`](super::EmailService::record_bounces).
+
+use super::BounceKind;
+use serde::{Deserialize, Serialize};
+
+/// A bounce or complaint for one address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BounceReport {
+    pub address: String,
+    pub kind: BounceKind,
+    /// Status code or diagnostic, kept with the suppression
+    pub reason: String,
+}
+
+/// Parse a DSN or ARF report, including its MIME wrapper
+///
+/// Only failed recipients are reported; delays and successful deliveries
+/// are skipped. Anything that is neither kind of report yields nothing.
+pub fn parse_report(raw: &str) -> Vec<BounceReport> {
+    let fields = unfold(raw);
+
+    if let Some(feedback_type) = field(&fields, "feedback-type") {
+        return complaint(&fields, feedback_type).into_iter().collect();
+    }
+
+    // Per-recipient groups start at each Final-Recipient field
+    let mut reports = Vec::new();
+    let mut group: Option<Vec<(String, String)>> = None;
+    for (name, value) in fields {
+        if name == "final-recipient" {
+            reports.extend(group.take().and_then(|g| failed_recipient(&g)));
+            group = Some(Vec::new());
+        }
+        if let Some(group) = group.as_mut() {
+            group.push((name, value));
+        }
+    }
+    reports.extend(group.and_then(|g| failed_recipient(&g)));
+
+    reports
+}
+
+/// Header-style fields of a report, continuation lines joined
+fn unfold(raw: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+
+    for line in raw.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            if !name.is_empty() && !name.contains(char::is_whitespace) {
+                fields.push((name.to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+    }
+
+    fields
+}
+
+fn field<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Address from a `type; address` recipient field
+fn recipient(value: &str) -> Option<String> {
+    let address = match value.split_once(';') {
+        Some((kind, address)) if kind.trim().eq_ignore_ascii_case("rfc822") => address,
+        Some(_) => return None,
+        None => value,
+    };
+    let address = address.trim().trim_start_matches('<').trim_end_matches('>');
+
+    address.contains('@').then(|| address.to_string())
+}
+
+/// A spam complaint from an ARF feedback report
+fn complaint(fields: &[(String, String)], feedback_type: &str) -> Option<BounceReport> {
+    if !feedback_type.eq_ignore_ascii_case("abuse") {
+        return None;
+    }
+
+    let address = field(fields, "original-rcpt-to").and_then(recipient)?;
+
+    Some(BounceReport {
+        address,
+        kind: BounceKind::Complaint,
+        reason: "Complaint: abuse".to_string(),
+    })
+}
+
+/// A bounce from one recipient's DSN fields, if delivery failed
+fn failed_recipient(fields: &[(String, String)]) -> Option<BounceReport> {
+    let action = field(fields, "action")?;
+    if !action.eq_ignore_ascii_case("failed") {
+        return None;
+    }
+
+    let address = field(fields, "final-recipient").and_then(recipient)?;
+    let status = field(fields, "status").unwrap_or_default();
+
+    // 4.x.x is temporary, as is a full mailbox (x.2.2) even when reported
+    // as permanent
+    let code = status.split_whitespace().next().unwrap_or_default();
+    let kind = if code.starts_with('5') && !code.ends_with(".2.2") {
+        BounceKind::Hard
+    } else {
+        BounceKind::Soft
+    };
+
+    let reason = match field(fields, "diagnostic-code") {
+        Some(diagnostic) => format!("{} {}", status, diagnostic),
+        None => status.to_string(),
+    };
+
+    Some(BounceReport {
+        address,
+        kind,
+        reason: reason.trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DSN: &str = "From: Mail Delivery System <MAILER-DAEMON@mx.example.test>\r\n\
+Content-Type: multipart/report; report-type=delivery-status; boundary=\"b1\"\r\n\
+\r\n\
+--b1\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Your message could not be delivered.\r\n\
+--b1\r\n\
+Content-Type: message/delivery-status\r\n\
+\r\n\
+Reporting-MTA: dns; mx.example.test\r\n\
+\r\n\
+Final-Recipient: rfc822; gone@example.test\r\n\
+Action: failed\r\n\
+Status: 5.1.1\r\n\
+Diagnostic-Code: smtp; 550 5.1.1 User unknown\r\n\
+\r\n\
+Final-Recipient: rfc822; <full@example.test>\r\n\
+Action: failed\r\n\
+Status: 5.2.2\r\n\
+Diagnostic-Code: smtp; 552 5.2.2 Mailbox full,\r\n\
+ try again later\r\n\
+\r\n\
+Final-Recipient: rfc822; slow@example.test\r\n\
+Action: delayed\r\n\
+Status: 4.4.1\r\n\
+--b1--\r\n";
+
+    #[test]
+    fn test_parse_dsn() {
+        let reports = parse_report(DSN);
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].address, "gone@example.test");
+        assert_eq!(reports[0].kind, BounceKind::Hard);
+        assert_eq!(reports[0].reason, "5.1.1 smtp; 550 5.1.1 User unknown");
+
+        assert_eq!(reports[1].address, "full@example.test");
+        assert_eq!(reports[1].kind, BounceKind::Soft);
+        assert!(reports[1].reason.ends_with("Mailbox full, try again later"));
+    }
+
+    #[test]
+    fn test_parse_complaint() {
+        let arf = "Content-Type: message/feedback-report\r\n\
+\r\n\
+Feedback-Type: abuse\r\n\
+User-Agent: ExampleFBL/1.0\r\n\
+Version: 1\r\n\
+Original-Rcpt-To: <reader@example.test>\r\n";
+
+        assert_eq!(
+            parse_report(arf),
+            vec![BounceReport {
+                address: "reader@example.test".to_string(),
+                kind: BounceKind::Complaint,
+                reason: "Complaint: abuse".to_string(),
+            }]
+        );
+
+        // Other feedback, e.g. a forwarded virus report, is not a complaint
+        assert!(parse_report(&arf.replace("abuse", "virus")).is_empty());
+    }
+
+    #[test]
+    fn test_parse_unrelated_message() {
+        assert!(parse_report("Subject: Hello\r\n\r\nJust a reply.\r\n").is_empty());
+    }
+}
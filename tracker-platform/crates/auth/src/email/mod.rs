@@ -0,0 +1,827 @@
+//! Transactional email
+//!
+//! [`EmailService`] renders a localized template and stores the result in
+//! the `email_outbox` queue; an [`EmailDispatcher`] running as a background
+//! job delivers it through an [`EmailTransport`] (SMTP in production, a
+//! `.eml` file sink in development), retrying temporary failures with
+//! backoff.
+//!
+//! Addresses that hard-bounce, or soft-bounce repeatedly, are suppressed and
+//! receive nothing further. Every email except account mail (verification,
+//! password resets and sign-in approvals) belongs to a category users can
+//! unsubscribe from with one click; the unsubscribe link carries an HMAC of
+//! the address and category, so it works without signing in.
+//!
+//! Bounces and complaints that arrive after delivery, as DSN or ARF reports,
+//! are fed back through a bounce intake authenticated with a shared secret.
+
+pub mod bounces;
+pub mod queue;
+pub mod templates;
+pub mod transport;
+
+pub use bounces::{parse_report, BounceReport};
+pub use queue::{DispatchReport, EmailDispatcher, QueuedEmail, RetryPolicy};
+pub use templates::{EmailTemplate, RenderedEmail, Template, TemplateSet, TemplateVars};
+pub use transport::{EmailTransport, FileTransport, SmtpSecurity, SmtpTransport, TransportError};
+
+use crate::register::EmailVerificationToken;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use lettre::Address;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Soft bounces in a row before an address is suppressed
+pub const DEFAULT_SOFT_BOUNCE_LIMIT: i32 = 3;
+
+/// Endpoint mail clients POST to for one-click unsubscribe (RFC 8058)
+pub const UNSUBSCRIBE_API_PATH: &str = "/api/v1/email/unsubscribe";
+
+/// Frontend page the unsubscribe link in an email's footer opens
+pub const UNSUBSCRIBE_PAGE_PATH: &str = "/unsubscribe";
+
+/// Errors from queueing and delivering email
+#[derive(Debug, Error)]
+pub enum EmailError {
+    #[error("Invalid email address: {0}")]
+    InvalidAddress(String),
+
+    #[error("Template error: {0}")]
+    Template(String),
+
+    #[error("Invalid unsubscribe link")]
+    InvalidUnsubscribeToken,
+
+    #[error("Account emails cannot be unsubscribed from")]
+    NotUnsubscribable,
+
+    #[error("Invalid bounce intake secret")]
+    InvalidBounceSecret,
+
+    #[error("IO error: {0}")]
+    Io(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+impl From<sqlx::Error> for EmailError {
+    fn from(e: sqlx::Error) -> Self {
+        EmailError::Database(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for EmailError {
+    fn from(e: std::io::Error) -> Self {
+        EmailError::Io(e.to_string())
+    }
+}
+
+/// What an email is about, for unsubscribes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailCategory {
+    /// Verification, password resets and sign-in confirmations; always sent
+    Account,
+    /// New sign-in alerts
+    SecurityAlerts,
+    /// Forum topic digests
+    Digests,
+    /// Invitations from members
+    Invites,
+    /// Requests to reseed torrents the user uploaded or downloaded
+    Reseeds,
+}
+
+impl EmailCategory {
+    /// Database and URL representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailCategory::Account => "account",
+            EmailCategory::SecurityAlerts => "security_alerts",
+            EmailCategory::Digests => "digests",
+            EmailCategory::Invites => "invites",
+            EmailCategory::Reseeds => "reseeds",
+        }
+    }
+
+    /// Parse the database representation
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "account" => Some(EmailCategory::Account),
+            "security_alerts" => Some(EmailCategory::SecurityAlerts),
+            "digests" => Some(EmailCategory::Digests),
+            "invites" => Some(EmailCategory::Invites),
+            "reseeds" => Some(EmailCategory::Reseeds),
+            _ => None,
+        }
+    }
+
+    /// Whether users can opt out of this category
+    pub fn unsubscribable(&self) -> bool {
+        !matches!(self, EmailCategory::Account)
+    }
+}
+
+/// Where and how a sign-in happened, for alert and confirmation emails
+#[derive(Debug, Clone)]
+pub struct SignInDetails {
+    pub ip_address: String,
+    /// Device description, e.g. "Firefox on Linux (Desktop)"
+    pub device: String,
+    /// Coarse location, if it could be resolved
+    pub location: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+impl SignInDetails {
+    fn vars(&self, vars: TemplateVars) -> TemplateVars {
+        vars.set("ip_address", &self.ip_address)
+            .set("device", &self.device)
+            .set("location", self.location.as_deref().unwrap_or("Unknown"))
+            .set("time", self.at.format("%Y-%m-%d %H:%M UTC").to_string())
+    }
+}
+
+/// Kind of delivery failure reported for an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BounceKind {
+    /// The mailbox does not exist; suppressed immediately
+    Hard,
+    /// Mailbox full and the like; suppressed after repeated bounces
+    Soft,
+    /// The recipient marked a message as spam; suppressed immediately
+    Complaint,
+}
+
+/// Email settings
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    /// Sender, e.g. `Tracker <noreply@tracker.example>`
+    pub from: String,
+    /// Site name used in subjects and greetings
+    pub site_name: String,
+    /// Public URL of the site, for links
+    pub site_url: String,
+    /// Key for signing unsubscribe links
+    pub unsubscribe_secret: Vec<u8>,
+    /// Soft bounces in a row before an address is suppressed
+    pub soft_bounce_limit: i32,
+    /// Secret the bounce intake must present; without it the intake is closed
+    pub bounce_secret: Option<Vec<u8>>,
+}
+
+impl EmailConfig {
+    /// Create a config with the default bounce limit
+    pub fn new(
+        from: impl Into<String>,
+        site_name: impl Into<String>,
+        site_url: impl Into<String>,
+        unsubscribe_secret: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self {
+            from: from.into(),
+            site_name: site_name.into(),
+            site_url: site_url.into().trim_end_matches('/').to_string(),
+            unsubscribe_secret: unsubscribe_secret.into(),
+            soft_bounce_limit: DEFAULT_SOFT_BOUNCE_LIMIT,
+            bounce_secret: None,
+        }
+    }
+
+    /// Accept bounce and complaint reports presenting `secret`
+    pub fn with_bounce_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.bounce_secret = Some(secret.into());
+        self
+    }
+}
+
+/// A forum topic in a digest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestTopic {
+    pub title: String,
+    pub url: String,
+    pub new_posts: i64,
+}
+
+/// Queues transactional email and manages suppressions and unsubscribes
+pub struct EmailService {
+    pub(crate) db_pool: PgPool,
+    config: EmailConfig,
+    templates: Arc<TemplateSet>,
+    unsubscribe_key: hmac::Key,
+}
+
+impl EmailService {
+    /// Create a service using the built-in templates
+    pub fn new(db_pool: PgPool, config: EmailConfig) -> Self {
+        let unsubscribe_key = hmac::Key::new(hmac::HMAC_SHA256, &config.unsubscribe_secret);
+        Self {
+            db_pool,
+            config,
+            templates: Arc::new(TemplateSet::builtin()),
+            unsubscribe_key,
+        }
+    }
+
+    /// Use a different set of templates
+    pub fn with_templates(mut self, templates: TemplateSet) -> Self {
+        self.templates = Arc::new(templates);
+        self
+    }
+
+    /// Email settings
+    pub fn config(&self) -> &EmailConfig {
+        &self.config
+    }
+
+    /// Render a template and queue it for delivery
+    ///
+    /// Returns `None` without queueing when the address is suppressed or
+    /// has unsubscribed from the template's category. `site_name`,
+    /// `site_url` and (for unsubscribable categories) `unsubscribe_url` are
+    /// added to `vars`.
+    pub async fn enqueue(
+        &self,
+        user_id: Option<Uuid>,
+        to: &str,
+        locale: &str,
+        template: EmailTemplate,
+        mut vars: TemplateVars,
+    ) -> Result<Option<Uuid>, EmailError> {
+        let address = normalize_address(to)?;
+        let category = template.category();
+
+        if self.is_blocked(&address, category).await? {
+            tracing::debug!(
+                "Not sending {} email to {}: suppressed or unsubscribed",
+                template.as_str(),
+                address
+            );
+            return Ok(None);
+        }
+
+        vars.insert("site_name", self.config.site_name.as_str());
+        vars.insert("site_url", self.config.site_url.as_str());
+        let list_unsubscribe = if category.unsubscribable() {
+            // The footer link opens a confirmation page; mail clients POST
+            // to the API directly for one-click unsubscribe
+            vars.insert(
+                "unsubscribe_url",
+                self.unsubscribe_link(UNSUBSCRIBE_PAGE_PATH, &address, category),
+            );
+            Some(self.unsubscribe_url(&address, category))
+        } else {
+            None
+        };
+
+        let rendered = self.templates.render(locale, template, &vars)?;
+
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO email_outbox (
+                user_id, to_address, template, category, locale,
+                subject, text_body, html_body, list_unsubscribe
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id
+            "#,
+            user_id,
+            address,
+            template.as_str(),
+            category.as_str(),
+            locale,
+            rendered.subject,
+            rendered.text_body,
+            rendered.html_body,
+            list_unsubscribe
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(Some(id))
+    }
+
+    /// Queue the email verification link sent after registration
+    pub async fn send_verification(
+        &self,
+        username: &str,
+        locale: &str,
+        token: &EmailVerificationToken,
+    ) -> Result<Option<Uuid>, EmailError> {
+        let encoded = token
+            .encode()
+            .map_err(|e| EmailError::Template(e.to_string()))?;
+        let vars = TemplateVars::new()
+            .set("username", username)
+            .set("link", self.link("/verify-email", &[("token", &encoded)]));
+
+        self.enqueue(
+            Some(token.user_id),
+            &token.email,
+            locale,
+            EmailTemplate::Verification,
+            vars,
+        )
+        .await
+    }
+
+    /// Queue a password reset link carrying `token`
+    pub async fn send_password_reset(
+        &self,
+        user_id: Uuid,
+        to: &str,
+        username: &str,
+        locale: &str,
+        token: &str,
+    ) -> Result<Option<Uuid>, EmailError> {
+        let vars = TemplateVars::new()
+            .set("username", username)
+            .set("link", self.link("/reset-password", &[("token", token)]));
+
+        self.enqueue(
+            Some(user_id),
+            to,
+            locale,
+            EmailTemplate::PasswordReset,
+            vars,
+        )
+        .await
+    }
+
+    /// Queue an invitation from a member
+    pub async fn send_invite(
+        &self,
+        to: &str,
+        locale: &str,
+        inviter: &str,
+        invite_code: &str,
+        message: Option<&str>,
+    ) -> Result<Option<Uuid>, EmailError> {
+        let vars = TemplateVars::new()
+            .set("inviter", inviter)
+            .set("message", message.unwrap_or_default())
+            .set("link", self.link("/register", &[("invite", invite_code)]));
+
+        self.enqueue(None, to, locale, EmailTemplate::Invite, vars)
+            .await
+    }
+
+    /// Queue an alert about a sign-in from a new device or location
+    ///
+    /// `not_me_token` goes into the one-click "this wasn't me" link, which
+    /// signs the account out everywhere.
+    pub async fn send_new_login_alert(
+        &self,
+        user_id: Uuid,
+        to: &str,
+        username: &str,
+        locale: &str,
+        sign_in: &SignInDetails,
+        not_me_token: &str,
+    ) -> Result<Option<Uuid>, EmailError> {
+        let vars = sign_in
+            .vars(TemplateVars::new())
+            .set("username", username)
+            .set(
+                "not_me_url",
+                self.link("/security/not-me", &[("token", not_me_token)]),
+            )
+            .set("sessions_url", self.link("/settings/sessions", &[]));
+
+        self.enqueue(Some(user_id), to, locale, EmailTemplate::NewLogin, vars)
+            .await
+    }
+
+    /// Queue a link approving a risky sign-in
+    pub async fn send_login_confirmation(
+        &self,
+        user_id: Uuid,
+        to: &str,
+        username: &str,
+        locale: &str,
+        sign_in: &SignInDetails,
+        token: &str,
+    ) -> Result<Option<Uuid>, EmailError> {
+        let vars = sign_in
+            .vars(TemplateVars::new())
+            .set("username", username)
+            .set("link", self.link("/confirm-login", &[("token", token)]));
+
+        self.enqueue(
+            Some(user_id),
+            to,
+            locale,
+            EmailTemplate::LoginConfirmation,
+            vars,
+        )
+        .await
+    }
+
+    /// Queue a digest of new posts in subscribed topics
+    ///
+    /// Nothing is queued when `topics` is empty.
+    pub async fn send_topic_digest(
+        &self,
+        user_id: Uuid,
+        to: &str,
+        username: &str,
+        locale: &str,
+        topics: &[DigestTopic],
+    ) -> Result<Option<Uuid>, EmailError> {
+        if topics.is_empty() {
+            return Ok(None);
+        }
+
+        let mut topics_text = String::new();
+        let mut topics_html = String::new();
+        for topic in topics {
+            topics_text.push_str(&format!(
+                "- {} ({} new)\n  {}\n",
+                topic.title, topic.new_posts, topic.url
+            ));
+            topics_html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a> ({} new)</li>\n",
+                templates::escape_html(&topic.url),
+                templates::escape_html(&topic.title),
+                topic.new_posts
+            ));
+        }
+
+        let vars = TemplateVars::new()
+            .set("username", username)
+            .set("topic_count", topics.len().to_string())
+            .set("topics_text", topics_text)
+            .set("topics_html", topics_html);
+
+        self.enqueue(Some(user_id), to, locale, EmailTemplate::TopicDigest, vars)
+            .await
+    }
+
+    /// Queue a request to reseed a torrent without seeders
+    ///
+    /// Sent to the uploader and earlier downloaders; `bounty` is the bonus
+    /// offered to whoever reseeds it, if any.
+    pub async fn send_reseed_request(
+        &self,
+        user_id: Uuid,
+        to: &str,
+        locale: &str,
+        torrent_id: Uuid,
+        torrent_name: &str,
+        bounty: f64,
+    ) -> Result<Option<Uuid>, EmailError> {
+        let bounty_note = if bounty > 0.0 {
+            format!("Whoever reseeds it receives {bounty:.0} bonus points.")
+        } else {
+            String::new()
+        };
+        let vars = TemplateVars::new()
+            .set("torrent_name", torrent_name)
+            .set("bounty_note", bounty_note)
+            .set("link", self.link(&format!("/torrents/{torrent_id}"), &[]));
+
+        self.enqueue(
+            Some(user_id),
+            to,
+            locale,
+            EmailTemplate::ReseedRequest,
+            vars,
+        )
+        .await
+    }
+
+    /// Record a bounce or complaint reported for an address
+    ///
+    /// Returns whether the address is now suppressed. Suppressing an address
+    /// also cancels its queued mail.
+    pub async fn record_bounce(
+        &self,
+        address: &str,
+        kind: BounceKind,
+        reason: &str,
+    ) -> Result<bool, EmailError> {
+        let address = normalize_address(address)?;
+        let immediate = !matches!(kind, BounceKind::Soft);
+
+        let suppressed = sqlx::query_scalar!(
+            r#"
+            INSERT INTO email_suppressions (address, soft_bounces, suppressed, reason)
+            VALUES ($1, CASE WHEN $2 THEN 0 ELSE 1 END, $2 OR $3 <= 1, $4)
+            ON CONFLICT (address) DO UPDATE
+            SET soft_bounces = email_suppressions.soft_bounces + CASE WHEN $2 THEN 0 ELSE 1 END,
+                suppressed = email_suppressions.suppressed
+                    OR $2
+                    OR email_suppressions.soft_bounces + 1 >= $3,
+                reason = $4,
+                updated_at = NOW()
+            RETURNING suppressed
+            "#,
+            address,
+            immediate,
+            self.config.soft_bounce_limit,
+            reason
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        if suppressed {
+            tracing::info!("Suppressing email to {}: {}", address, reason);
+            sqlx::query!(
+                r#"
+                UPDATE email_outbox SET status = 'suppressed'
+                WHERE to_address = $1 AND status = 'pending'
+                "#,
+                address
+            )
+            .execute(&self.db_pool)
+            .await?;
+        }
+
+        Ok(suppressed)
+    }
+
+    /// Record bounces and complaints from DSN or ARF reports
+    ///
+    /// Returns how many of the addresses are now suppressed.
+    pub async fn record_bounces(&self, reports: &[BounceReport]) -> Result<usize, EmailError> {
+        let mut suppressed = 0;
+        for report in reports {
+            if self
+                .record_bounce(&report.address, report.kind, &report.reason)
+                .await?
+            {
+                suppressed += 1;
+            }
+        }
+
+        Ok(suppressed)
+    }
+
+    /// Check the secret a bounce report was delivered with
+    pub fn verify_bounce_secret(&self, secret: &str) -> Result<(), EmailError> {
+        let expected = self
+            .config
+            .bounce_secret
+            .as_deref()
+            .filter(|expected| !expected.is_empty())
+            .ok_or(EmailError::InvalidBounceSecret)?;
+
+        // Compare MACs so the check takes the same time however much matches
+        let key = hmac::Key::new(hmac::HMAC_SHA256, expected);
+        let presented = hmac::sign(&key, secret.as_bytes());
+        hmac::verify(&key, expected, presented.as_ref())
+            .map_err(|_| EmailError::InvalidBounceSecret)
+    }
+
+    /// Lift a suppression, e.g. after the user fixed their mailbox
+    pub async fn clear_suppression(&self, address: &str) -> Result<(), EmailError> {
+        let address = normalize_address(address)?;
+        sqlx::query!("DELETE FROM email_suppressions WHERE address = $1", address)
+            .execute(&self.db_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Signed one-click link that unsubscribes `address` from `category`
+    pub fn unsubscribe_url(&self, address: &str, category: EmailCategory) -> String {
+        self.unsubscribe_link(UNSUBSCRIBE_API_PATH, address, category)
+    }
+
+    fn unsubscribe_link(&self, path: &str, address: &str, category: EmailCategory) -> String {
+        let token = self.unsubscribe_token(address, category);
+        self.link(
+            path,
+            &[
+                ("address", address),
+                ("category", category.as_str()),
+                ("token", &token),
+            ],
+        )
+    }
+
+    /// Signature carried by unsubscribe links
+    pub fn unsubscribe_token(&self, address: &str, category: EmailCategory) -> String {
+        let message = format!("{}\n{}", address.trim().to_lowercase(), category.as_str());
+        URL_SAFE_NO_PAD.encode(hmac::sign(&self.unsubscribe_key, message.as_bytes()))
+    }
+
+    /// Check an unsubscribe link's signature without acting on it
+    pub fn verify_unsubscribe_token(
+        &self,
+        address: &str,
+        category: EmailCategory,
+        token: &str,
+    ) -> Result<(), EmailError> {
+        if !category.unsubscribable() {
+            return Err(EmailError::NotUnsubscribable);
+        }
+
+        let signature = URL_SAFE_NO_PAD
+            .decode(token)
+            .map_err(|_| EmailError::InvalidUnsubscribeToken)?;
+        let message = format!("{}\n{}", address.trim().to_lowercase(), category.as_str());
+        hmac::verify(&self.unsubscribe_key, message.as_bytes(), &signature)
+            .map_err(|_| EmailError::InvalidUnsubscribeToken)
+    }
+
+    /// Unsubscribe `address` from `category` via a signed link
+    pub async fn unsubscribe(
+        &self,
+        address: &str,
+        category: EmailCategory,
+        token: &str,
+    ) -> Result<(), EmailError> {
+        self.verify_unsubscribe_token(address, category, token)?;
+        let address = normalize_address(address)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO email_unsubscribes (address, category)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            address,
+            category.as_str()
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Opt back in to a category
+    pub async fn resubscribe(
+        &self,
+        address: &str,
+        category: EmailCategory,
+    ) -> Result<(), EmailError> {
+        let address = normalize_address(address)?;
+        sqlx::query!(
+            "DELETE FROM email_unsubscribes WHERE address = $1 AND category = $2",
+            address,
+            category.as_str()
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Whether mail in `category` must not be sent to `address`
+    async fn is_blocked(&self, address: &str, category: EmailCategory) -> Result<bool, EmailError> {
+        let blocked = sqlx::query_scalar!(
+            r#"
+            SELECT
+                EXISTS(SELECT 1 FROM email_suppressions WHERE address = $1 AND suppressed)
+                OR EXISTS(SELECT 1 FROM email_unsubscribes WHERE address = $1 AND category = $2)
+                AS "blocked!"
+            "#,
+            address,
+            category.as_str()
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(blocked)
+    }
+
+    /// Absolute link to a page on the site
+    fn link(&self, path: &str, params: &[(&str, &str)]) -> String {
+        let mut link = format!("{}{}", self.config.site_url, path);
+        if !params.is_empty() {
+            let query = url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(params)
+                .finish();
+            link.push('?');
+            link.push_str(&query);
+        }
+        link
+    }
+}
+
+/// Validate an address and lowercase it for suppression lookups
+fn normalize_address(address: &str) -> Result<String, EmailError> {
+    let normalized = address.trim().to_lowercase();
+    normalized
+        .parse::<Address>()
+        .map_err(|_| EmailError::InvalidAddress(address.to_string()))?;
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> EmailService {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/tracker_test")
+            .unwrap();
+        EmailService::new(
+            pool,
+            EmailConfig::new(
+                "Tracker <noreply@tracker.test>",
+                "Tracker",
+                "https://tracker.test/",
+                b"unsubscribe-secret".to_vec(),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_categories() {
+        for category in [
+            EmailCategory::Account,
+            EmailCategory::SecurityAlerts,
+            EmailCategory::Digests,
+            EmailCategory::Invites,
+        ] {
+            assert_eq!(EmailCategory::parse(category.as_str()), Some(category));
+        }
+        assert!(!EmailCategory::Account.unsubscribable());
+        assert!(EmailCategory::Digests.unsubscribable());
+    }
+
+    #[test]
+    fn test_normalize_address() {
+        assert_eq!(
+            normalize_address(" User@Example.TEST ").unwrap(),
+            "user@example.test"
+        );
+        assert!(normalize_address("not an address").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_link_encodes_query() {
+        let service = service();
+        assert_eq!(
+            service.link("/verify-email", &[("token", "a+b/c=")]),
+            "https://tracker.test/verify-email?token=a%2Bb%2Fc%3D"
+        );
+        assert_eq!(
+            service.link("/settings/sessions", &[]),
+            "https://tracker.test/settings/sessions"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_token_is_bound_to_address_and_category() {
+        let service = service();
+        let token = service.unsubscribe_token("user@example.test", EmailCategory::Digests);
+
+        assert_eq!(
+            token,
+            service.unsubscribe_token("USER@example.test", EmailCategory::Digests)
+        );
+        assert_ne!(
+            token,
+            service.unsubscribe_token("other@example.test", EmailCategory::Digests)
+        );
+        assert_ne!(
+            token,
+            service.unsubscribe_token("user@example.test", EmailCategory::Invites)
+        );
+        assert!(service
+            .unsubscribe_url("user@example.test", EmailCategory::Digests)
+            .starts_with("https://tracker.test/api/v1/email/unsubscribe?address=user%40example.test&category=digests&token="));
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_rejects_bad_tokens_before_touching_the_database() {
+        let service = service();
+
+        let result = service
+            .unsubscribe("user@example.test", EmailCategory::Digests, "bogus")
+            .await;
+        assert!(matches!(result, Err(EmailError::InvalidUnsubscribeToken)));
+
+        let token = service.unsubscribe_token("user@example.test", EmailCategory::Account);
+        let result = service
+            .unsubscribe("user@example.test", EmailCategory::Account, &token)
+            .await;
+        assert!(matches!(result, Err(EmailError::NotUnsubscribable)));
+    }
+
+    #[tokio::test]
+    async fn test_bounce_secret() {
+        // Closed until a secret is configured
+        assert!(matches!(
+            service().verify_bounce_secret(""),
+            Err(EmailError::InvalidBounceSecret)
+        ));
+
+        let mut service = service();
+        service.config = service.config.clone().with_bounce_secret("bounce-secret");
+
+        assert!(service.verify_bounce_secret("bounce-secret").is_ok());
+        assert!(service.verify_bounce_secret("bounce-secre").is_err());
+        assert!(service.verify_bounce_secret("").is_err());
+    }
+}
@@ -0,0 +1,379 @@
+//! Outbound email queue
+//!
+//! [`EmailService`] renders emails into `email_outbox`; an
+//! [`EmailDispatcher`] claims due rows, hands them to the transport and
+//! records the outcome. Claims use `FOR UPDATE SKIP LOCKED` and expire, so
+//! several dispatchers can share the queue and a crash mid-send only delays
+//! a message. Temporary failures are retried with exponential backoff;
+//! permanent ones mark the message failed and count as a hard bounce.
+
+use super::transport::{EmailTransport, TransportError};
+use super::{BounceKind, EmailError, EmailService};
+use chrono::{DateTime, Duration, Utc};
+use lettre::message::header::{Header, HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How long a dispatcher may hold a claimed message before others retry it
+const CLAIM_TIMEOUT_SECS: i64 = 300;
+
+/// When failed deliveries are retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts before a message is given up on
+    pub max_attempts: i32,
+    /// Delay after the first failure; doubled after each further one
+    pub base_delay: Duration,
+    /// Longest delay between attempts
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            base_delay: Duration::minutes(1),
+            max_delay: Duration::hours(6),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the next attempt, after `attempts` failed ones
+    pub fn delay(&self, attempts: i32) -> Duration {
+        let doublings = attempts.saturating_sub(1).clamp(0, 30) as u32;
+        let delay = self
+            .base_delay
+            .num_seconds()
+            .saturating_mul(1i64 << doublings);
+        Duration::seconds(delay.min(self.max_delay.num_seconds()))
+    }
+
+    /// Whether a message that has failed `attempts` times gets another try
+    pub fn should_retry(&self, attempts: i32) -> bool {
+        attempts < self.max_attempts
+    }
+}
+
+/// A claimed message
+#[derive(Debug, Clone)]
+pub struct QueuedEmail {
+    pub id: Uuid,
+    pub to_address: String,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: String,
+    /// One-click unsubscribe URL, for categories users can opt out of
+    pub list_unsubscribe: Option<String>,
+    /// Attempts so far, including this one
+    pub attempts: i32,
+}
+
+impl QueuedEmail {
+    /// Build the MIME message
+    pub fn to_message(&self, from: &Mailbox) -> Result<Message, EmailError> {
+        let to: Mailbox = self
+            .to_address
+            .parse()
+            .map_err(|_| EmailError::InvalidAddress(self.to_address.clone()))?;
+
+        let mut builder = Message::builder()
+            .from(from.clone())
+            .to(to)
+            .subject(self.subject.as_str());
+        if let Some(url) = &self.list_unsubscribe {
+            builder = builder
+                .header(ListUnsubscribe(format!("<{}>", url)))
+                .header(ListUnsubscribePost);
+        }
+
+        builder
+            .multipart(MultiPart::alternative_plain_html(
+                self.text_body.clone(),
+                self.html_body.clone(),
+            ))
+            .map_err(|e| EmailError::Template(e.to_string()))
+    }
+}
+
+/// `List-Unsubscribe` header (RFC 2369)
+#[derive(Debug, Clone)]
+struct ListUnsubscribe(String);
+
+impl Header for ListUnsubscribe {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe")
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self(s.to_string()))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), self.0.clone())
+    }
+}
+
+/// `List-Unsubscribe-Post` header (RFC 8058 one-click unsubscribe)
+#[derive(Debug, Clone)]
+struct ListUnsubscribePost;
+
+impl Header for ListUnsubscribePost {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe-Post")
+    }
+
+    fn parse(_: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Self)
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), "List-Unsubscribe=One-Click".to_string())
+    }
+}
+
+/// Outcome of one dispatch pass
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DispatchReport {
+    pub sent: usize,
+    /// Failed temporarily and rescheduled
+    pub retried: usize,
+    /// Given up on after too many temporary failures
+    pub failed: usize,
+    /// Rejected permanently; the address has been suppressed
+    pub bounced: usize,
+}
+
+/// Delivers queued emails
+pub struct EmailDispatcher {
+    service: Arc<EmailService>,
+    transport: Arc<dyn EmailTransport>,
+    from: Mailbox,
+    policy: RetryPolicy,
+}
+
+impl EmailDispatcher {
+    /// Create a dispatcher sending from the service's configured address
+    pub fn new(
+        service: Arc<EmailService>,
+        transport: Arc<dyn EmailTransport>,
+    ) -> Result<Self, EmailError> {
+        let from = service
+            .config()
+            .from
+            .parse()
+            .map_err(|_| EmailError::InvalidAddress(service.config().from.clone()))?;
+
+        Ok(Self {
+            service,
+            transport,
+            from,
+            policy: RetryPolicy::default(),
+        })
+    }
+
+    /// Use a different retry policy
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Deliver up to `batch_size` due messages
+    pub async fn dispatch(&self, batch_size: i64) -> Result<DispatchReport, EmailError> {
+        let mut report = DispatchReport::default();
+
+        for email in self.claim(batch_size).await? {
+            let result = match email.to_message(&self.from) {
+                Ok(message) => self.transport.send(message).await,
+                Err(e) => Err(TransportError::Permanent(e.to_string())),
+            };
+
+            match result {
+                Ok(()) => {
+                    self.mark_sent(email.id).await?;
+                    report.sent += 1;
+                }
+                Err(TransportError::Transient(error)) => {
+                    if self.policy.should_retry(email.attempts) {
+                        let next = Utc::now() + self.policy.delay(email.attempts);
+                        self.reschedule(email.id, next, &error).await?;
+                        report.retried += 1;
+                    } else {
+                        tracing::warn!(
+                            "Giving up on email {} after {} attempts: {}",
+                            email.id,
+                            email.attempts,
+                            error
+                        );
+                        self.mark_failed(email.id, &error).await?;
+                        report.failed += 1;
+                    }
+                }
+                Err(TransportError::Permanent(error)) => {
+                    self.mark_failed(email.id, &error).await?;
+                    match self
+                        .service
+                        .record_bounce(&email.to_address, BounceKind::Hard, &error)
+                        .await
+                    {
+                        // Nothing to suppress for an address that cannot exist
+                        Ok(_) | Err(EmailError::InvalidAddress(_)) => {}
+                        Err(e) => return Err(e),
+                    }
+                    report.bounced += 1;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Claim due messages, including ones whose previous claim expired
+    async fn claim(&self, batch_size: i64) -> Result<Vec<QueuedEmail>, EmailError> {
+        let emails = sqlx::query_as!(
+            QueuedEmail,
+            r#"
+            UPDATE email_outbox
+            SET status = 'sending',
+                attempts = attempts + 1,
+                locked_until = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM email_outbox
+                WHERE (status = 'pending' AND next_attempt_at <= NOW())
+                   OR (status = 'sending' AND locked_until < NOW())
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, to_address, subject, text_body, html_body, list_unsubscribe, attempts
+            "#,
+            batch_size,
+            CLAIM_TIMEOUT_SECS as f64
+        )
+        .fetch_all(&self.service.db_pool)
+        .await?;
+
+        Ok(emails)
+    }
+
+    async fn mark_sent(&self, id: Uuid) -> Result<(), EmailError> {
+        sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = 'sent', sent_at = NOW(), locked_until = NULL, last_error = NULL
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.service.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reschedule(
+        &self,
+        id: Uuid,
+        next_attempt_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), EmailError> {
+        sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = 'pending', next_attempt_at = $2, locked_until = NULL, last_error = $3
+            WHERE id = $1
+            "#,
+            id,
+            next_attempt_at,
+            error
+        )
+        .execute(&self.service.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_failed(&self, id: Uuid, error: &str) -> Result<(), EmailError> {
+        sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = 'failed', locked_until = NULL, last_error = $2
+            WHERE id = $1
+            "#,
+            id,
+            error
+        )
+        .execute(&self.service.db_pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(list_unsubscribe: Option<&str>) -> QueuedEmail {
+        QueuedEmail {
+            id: Uuid::new_v4(),
+            to_address: "user@example.test".to_string(),
+            subject: "New posts".to_string(),
+            text_body: "Plain body".to_string(),
+            html_body: "<p>HTML body</p>".to_string(),
+            list_unsubscribe: list_unsubscribe.map(str::to_string),
+            attempts: 1,
+        }
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1), Duration::minutes(1));
+        assert_eq!(policy.delay(2), Duration::minutes(2));
+        assert_eq!(policy.delay(4), Duration::minutes(8));
+        // Capped
+        assert_eq!(policy.delay(20), Duration::hours(6));
+        assert_eq!(policy.delay(i32::MAX), Duration::hours(6));
+
+        assert!(policy.should_retry(7));
+        assert!(!policy.should_retry(8));
+    }
+
+    #[test]
+    fn test_message_has_both_bodies() {
+        let from: Mailbox = "Tracker <noreply@tracker.test>".parse().unwrap();
+        let message = queued(None).to_message(&from).unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+
+        assert!(formatted.contains("multipart/alternative"));
+        assert!(formatted.contains("Plain body"));
+        assert!(formatted.contains("<p>HTML body</p>"));
+        assert!(!formatted.contains("List-Unsubscribe"));
+    }
+
+    #[test]
+    fn test_message_unsubscribe_headers() {
+        let from: Mailbox = "noreply@tracker.test".parse().unwrap();
+        let message = queued(Some("https://tracker.test/unsub?t=1"))
+            .to_message(&from)
+            .unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+
+        assert!(formatted.contains("List-Unsubscribe: <https://tracker.test/unsub?t=1>"));
+        assert!(formatted.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+    }
+
+    #[test]
+    fn test_invalid_recipient() {
+        let from: Mailbox = "noreply@tracker.test".parse().unwrap();
+        let mut email = queued(None);
+        email.to_address = "not an address".to_string();
+        assert!(matches!(
+            email.to_message(&from),
+            Err(EmailError::InvalidAddress(_))
+        ));
+    }
+}
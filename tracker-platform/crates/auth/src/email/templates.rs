@@ -0,0 +1,627 @@
+//! Localized email templates
+//!
+//! Each template has a subject, a plain-text body and an HTML body, with
+//! `{{name}}` placeholders filled from [`TemplateVars`]. Values are
+//! HTML-escaped in HTML bodies; `{{{name}}}` inserts a value verbatim, for
+//! fragments the service has already escaped.
+//!
+//! English templates are built in. Other locales (or replacement English
+//! wording) are loaded from a directory laid out as
+//! `<dir>/<locale>/<template>.subject`, `<template>.txt` and
+//! `<template>.html`. A locale missing a template falls back to its base
+//! language and then to English, so `pt-BR` tries `pt-BR`, `pt`, `en`.
+
+use super::{EmailCategory, EmailError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Locale every template is available in
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Transactional emails the site sends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTemplate {
+    /// Confirm the address given at registration
+    Verification,
+    /// Link to choose a new password
+    PasswordReset,
+    /// Invitation from an existing member
+    Invite,
+    /// Sign-in from a new device or location
+    NewLogin,
+    /// Link to approve a risky sign-in
+    LoginConfirmation,
+    /// New posts in subscribed forum topics
+    TopicDigest,
+    /// Ask to reseed a torrent without seeders
+    ReseedRequest,
+}
+
+impl EmailTemplate {
+    /// All templates
+    pub const ALL: [EmailTemplate; 7] = [
+        EmailTemplate::Verification,
+        EmailTemplate::PasswordReset,
+        EmailTemplate::Invite,
+        EmailTemplate::NewLogin,
+        EmailTemplate::LoginConfirmation,
+        EmailTemplate::TopicDigest,
+        EmailTemplate::ReseedRequest,
+    ];
+
+    /// Database and file name representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailTemplate::Verification => "verification",
+            EmailTemplate::PasswordReset => "password_reset",
+            EmailTemplate::Invite => "invite",
+            EmailTemplate::NewLogin => "new_login",
+            EmailTemplate::LoginConfirmation => "login_confirmation",
+            EmailTemplate::TopicDigest => "topic_digest",
+            EmailTemplate::ReseedRequest => "reseed_request",
+        }
+    }
+
+    /// Parse the database representation
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == s)
+    }
+
+    /// Category the email belongs to, for unsubscribes
+    pub fn category(&self) -> EmailCategory {
+        match self {
+            EmailTemplate::Verification
+            | EmailTemplate::PasswordReset
+            | EmailTemplate::LoginConfirmation => EmailCategory::Account,
+            EmailTemplate::NewLogin => EmailCategory::SecurityAlerts,
+            EmailTemplate::TopicDigest => EmailCategory::Digests,
+            EmailTemplate::Invite => EmailCategory::Invites,
+            EmailTemplate::ReseedRequest => EmailCategory::Reseeds,
+        }
+    }
+}
+
+/// Source of one template in one locale
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+impl Template {
+    /// Create a template
+    pub fn new(
+        subject: impl Into<String>,
+        text: impl Into<String>,
+        html: impl Into<String>,
+    ) -> Self {
+        Self {
+            subject: subject.into(),
+            text: text.into(),
+            html: html.into(),
+        }
+    }
+}
+
+/// A template with its placeholders filled in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: String,
+}
+
+/// Values for a template's placeholders
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVars(BTreeMap<String, String>);
+
+impl TemplateVars {
+    /// Create an empty set of values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a value
+    pub fn set(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    /// Add a value in place
+    pub fn insert(&mut self, name: &str, value: impl Into<String>) {
+        self.0.insert(name.to_string(), value.into());
+    }
+
+    /// Look up a value
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+/// Templates for every locale the site supports
+#[derive(Debug, Clone)]
+pub struct TemplateSet {
+    templates: HashMap<(String, EmailTemplate), Template>,
+}
+
+impl Default for TemplateSet {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl TemplateSet {
+    /// The built-in English templates
+    pub fn builtin() -> Self {
+        let mut set = Self {
+            templates: HashMap::new(),
+        };
+        for template in EmailTemplate::ALL {
+            set.insert(DEFAULT_LOCALE, template, builtin_template(template));
+        }
+        set
+    }
+
+    /// Add or replace a template for a locale
+    pub fn insert(&mut self, locale: &str, template: EmailTemplate, source: Template) {
+        self.templates
+            .insert((normalize_locale(locale), template), source);
+    }
+
+    /// Load every template found under `dir`
+    ///
+    /// Returns the number of templates loaded. A template is only loaded
+    /// when all three of its files are present.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<usize, EmailError> {
+        let mut loaded = 0;
+
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let locale = entry.file_name().to_string_lossy().into_owned();
+
+            for template in EmailTemplate::ALL {
+                let path = |ext: &str| entry.path().join(format!("{}.{}", template.as_str(), ext));
+                let (subject, text, html) = (path("subject"), path("txt"), path("html"));
+                if !(subject.is_file() && text.is_file() && html.is_file()) {
+                    continue;
+                }
+
+                let source = Template::new(
+                    std::fs::read_to_string(subject)?.trim(),
+                    std::fs::read_to_string(text)?,
+                    std::fs::read_to_string(html)?,
+                );
+                // Catch typos in placeholders at startup rather than at send time
+                parse_placeholders(&source.subject)?;
+                parse_placeholders(&source.text)?;
+                parse_placeholders(&source.html)?;
+
+                self.insert(&locale, template, source);
+                loaded += 1;
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    /// Locales with at least one template
+    pub fn locales(&self) -> Vec<String> {
+        let mut locales: Vec<String> = self
+            .templates
+            .keys()
+            .map(|(locale, _)| locale.clone())
+            .collect();
+        locales.sort();
+        locales.dedup();
+        locales
+    }
+
+    /// The template to use for `locale`, following the fallback chain
+    pub fn resolve(&self, locale: &str, template: EmailTemplate) -> Option<&Template> {
+        locale_chain(locale)
+            .into_iter()
+            .find_map(|locale| self.templates.get(&(locale, template)))
+    }
+
+    /// Render a template for `locale`
+    pub fn render(
+        &self,
+        locale: &str,
+        template: EmailTemplate,
+        vars: &TemplateVars,
+    ) -> Result<RenderedEmail, EmailError> {
+        let source = self.resolve(locale, template).ok_or_else(|| {
+            EmailError::Template(format!("No {} template for {}", template.as_str(), locale))
+        })?;
+
+        Ok(RenderedEmail {
+            subject: render_str(&source.subject, vars, false)?,
+            text_body: render_str(&source.text, vars, false)?,
+            html_body: render_str(&source.html, vars, true)?,
+        })
+    }
+}
+
+/// Lowercase a locale and use `-` as its separator (`pt_BR` → `pt-br`)
+fn normalize_locale(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_lowercase()
+}
+
+/// Locales to try for `locale`, most specific first
+fn locale_chain(locale: &str) -> Vec<String> {
+    let locale = normalize_locale(locale);
+    let mut chain = Vec::new();
+
+    if !locale.is_empty() {
+        let mut parts: Vec<&str> = locale.split('-').collect();
+        while !parts.is_empty() {
+            chain.push(parts.join("-"));
+            parts.pop();
+        }
+    }
+    if !chain.iter().any(|l| l == DEFAULT_LOCALE) {
+        chain.push(DEFAULT_LOCALE.to_string());
+    }
+
+    chain
+}
+
+/// A piece of template source
+#[derive(Debug, PartialEq, Eq)]
+enum Segment<'a> {
+    Literal(&'a str),
+    /// `{{name}}`, escaped in HTML
+    Value(&'a str),
+    /// `{{{name}}}`, inserted verbatim
+    Raw(&'a str),
+}
+
+fn parse_placeholders(source: &str) -> Result<Vec<Segment<'_>>, EmailError> {
+    let mut segments = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            segments.push(Segment::Literal(&rest[..start]));
+        }
+
+        let (raw, open, close) = if rest[start..].starts_with("{{{") {
+            (true, 3, "}}}")
+        } else {
+            (false, 2, "}}")
+        };
+        let after = &rest[start + open..];
+        let end = after.find(close).ok_or_else(|| {
+            EmailError::Template(format!("Unclosed placeholder near {:?}", truncate(after)))
+        })?;
+
+        let name = after[..end].trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(EmailError::Template(format!(
+                "Invalid placeholder {:?}",
+                name
+            )));
+        }
+        segments.push(if raw {
+            Segment::Raw(name)
+        } else {
+            Segment::Value(name)
+        });
+
+        rest = &after[end + close.len()..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest));
+    }
+
+    Ok(segments)
+}
+
+/// Fill in a template's placeholders
+fn render_str(source: &str, vars: &TemplateVars, html: bool) -> Result<String, EmailError> {
+    let mut out = String::with_capacity(source.len());
+
+    for segment in parse_placeholders(source)? {
+        match segment {
+            Segment::Literal(text) => out.push_str(text),
+            Segment::Value(name) | Segment::Raw(name) => {
+                let value = vars
+                    .get(name)
+                    .ok_or_else(|| EmailError::Template(format!("Missing value for {}", name)))?;
+                if html && matches!(segment, Segment::Value(_)) {
+                    out.push_str(&escape_html(value));
+                } else {
+                    out.push_str(value);
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Escape text for inclusion in HTML
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn truncate(text: &str) -> &str {
+    match text.char_indices().nth(20) {
+        Some((i, _)) => &text[..i],
+        None => text,
+    }
+}
+
+/// Built-in English wording
+fn builtin_template(template: EmailTemplate) -> Template {
+    match template {
+        EmailTemplate::Verification => Template::new(
+            "Confirm your email address for {{site_name}}",
+            "Hi {{username}},\n\n\
+             Welcome to {{site_name}}! Confirm your email address by opening the link below:\n\n\
+             {{link}}\n\n\
+             The link expires in 24 hours. If you didn't create an account, you can ignore \
+             this email.\n",
+            "<p>Hi {{username}},</p>\n\
+             <p>Welcome to {{site_name}}! Confirm your email address by opening the link below:</p>\n\
+             <p><a href=\"{{link}}\">Confirm my email address</a></p>\n\
+             <p>The link expires in 24 hours. If you didn't create an account, you can ignore \
+             this email.</p>\n",
+        ),
+        EmailTemplate::PasswordReset => Template::new(
+            "Reset your {{site_name}} password",
+            "Hi {{username}},\n\n\
+             Someone asked to reset the password for your account. Choose a new password here:\n\n\
+             {{link}}\n\n\
+             The link expires in 1 hour. If you didn't ask for this, you can ignore this email; \
+             your password has not been changed.\n",
+            "<p>Hi {{username}},</p>\n\
+             <p>Someone asked to reset the password for your account. Choose a new password here:</p>\n\
+             <p><a href=\"{{link}}\">Reset my password</a></p>\n\
+             <p>The link expires in 1 hour. If you didn't ask for this, you can ignore this email; \
+             your password has not been changed.</p>\n",
+        ),
+        EmailTemplate::Invite => Template::new(
+            "{{inviter}} invited you to {{site_name}}",
+            "Hi,\n\n\
+             {{inviter}} has invited you to join {{site_name}}.\n\n\
+             {{message}}\n\n\
+             Create your account here:\n\n\
+             {{link}}\n\n\
+             ---\n\
+             Don't want invitations? {{unsubscribe_url}}\n",
+            "<p>Hi,</p>\n\
+             <p>{{inviter}} has invited you to join {{site_name}}.</p>\n\
+             <blockquote>{{message}}</blockquote>\n\
+             <p><a href=\"{{link}}\">Create my account</a></p>\n\
+             <hr>\n\
+             <p><small><a href=\"{{unsubscribe_url}}\">Don't send me invitations</a></small></p>\n",
+        ),
+        EmailTemplate::NewLogin => Template::new(
+            "New sign-in to your {{site_name}} account",
+            "Hi {{username}},\n\n\
+             Your account was just signed in to from a device or location we haven't seen \
+             before.\n\n\
+             When: {{time}}\n\
+             Device: {{device}}\n\
+             Location: {{location}}\n\
+             IP address: {{ip_address}}\n\n\
+             If this was you, there's nothing to do. If it wasn't, sign out every session \
+             with one click, then change your password:\n\n\
+             {{not_me_url}}\n\n\
+             Review your sessions: {{sessions_url}}\n\n\
+             ---\n\
+             Stop these alerts: {{unsubscribe_url}}\n",
+            "<p>Hi {{username}},</p>\n\
+             <p>Your account was just signed in to from a device or location we haven't seen \
+             before.</p>\n\
+             <ul>\n\
+             <li>When: {{time}}</li>\n\
+             <li>Device: {{device}}</li>\n\
+             <li>Location: {{location}}</li>\n\
+             <li>IP address: {{ip_address}}</li>\n\
+             </ul>\n\
+             <p>If this was you, there's nothing to do. If it wasn't, \
+             <a href=\"{{not_me_url}}\">this wasn't me: sign out everywhere</a>, then change \
+             your password.</p>\n\
+             <p><a href=\"{{sessions_url}}\">Review your sessions</a></p>\n\
+             <hr>\n\
+             <p><small><a href=\"{{unsubscribe_url}}\">Stop these alerts</a></small></p>\n",
+        ),
+        EmailTemplate::LoginConfirmation => Template::new(
+            "Confirm your sign-in to {{site_name}}",
+            "Hi {{username}},\n\n\
+             Someone signed in to your account with your password from a location we didn't \
+             expect.\n\n\
+             When: {{time}}\n\
+             Device: {{device}}\n\
+             Location: {{location}}\n\
+             IP address: {{ip_address}}\n\n\
+             If this was you, approve the sign-in here and then sign in again:\n\n\
+             {{link}}\n\n\
+             The link expires in 15 minutes. If it wasn't you, don't click it, and change your \
+             password: whoever tried knows it.\n",
+            "<p>Hi {{username}},</p>\n\
+             <p>Someone signed in to your account with your password from a location we didn't \
+             expect.</p>\n\
+             <ul>\n\
+             <li>When: {{time}}</li>\n\
+             <li>Device: {{device}}</li>\n\
+             <li>Location: {{location}}</li>\n\
+             <li>IP address: {{ip_address}}</li>\n\
+             </ul>\n\
+             <p>If this was you, <a href=\"{{link}}\">approve the sign-in</a> and then sign in \
+             again.</p>\n\
+             <p>The link expires in 15 minutes. If it wasn't you, don't click it, and change \
+             your password: whoever tried knows it.</p>\n",
+        ),
+        EmailTemplate::TopicDigest => Template::new(
+            "New posts in {{topic_count}} topics you follow on {{site_name}}",
+            "Hi {{username}},\n\n\
+             There are new posts in topics you follow:\n\n\
+             {{{topics_text}}}\n\
+             ---\n\
+             Stop these digests: {{unsubscribe_url}}\n",
+            "<p>Hi {{username}},</p>\n\
+             <p>There are new posts in topics you follow:</p>\n\
+             <ul>\n{{{topics_html}}}</ul>\n\
+             <hr>\n\
+             <p><small><a href=\"{{unsubscribe_url}}\">Stop these digests</a></small></p>\n",
+        ),
+        EmailTemplate::ReseedRequest => Template::new(
+            "Can you reseed {{torrent_name}}?",
+            "Hi,\n\n\
+             {{torrent_name}} has no seeders left, and another member has asked for it. You \
+             uploaded or downloaded it before; if you still have the files, please start \
+             seeding again.\n\n\
+             {{bounty_note}}\n\n\
+             {{link}}\n\n\
+             ---\n\
+             Stop reseed requests: {{unsubscribe_url}}\n",
+            "<p>Hi,</p>\n\
+             <p>{{torrent_name}} has no seeders left, and another member has asked for it. You \
+             uploaded or downloaded it before; if you still have the files, please start \
+             seeding again.</p>\n\
+             <p>{{bounty_note}}</p>\n\
+             <p><a href=\"{{link}}\">Open the torrent</a></p>\n\
+             <hr>\n\
+             <p><small><a href=\"{{unsubscribe_url}}\">Stop reseed requests</a></small></p>\n",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_chain() {
+        assert_eq!(locale_chain("pt_BR"), vec!["pt-br", "pt", "en"]);
+        assert_eq!(locale_chain("en-GB"), vec!["en-gb", "en"]);
+        assert_eq!(locale_chain(""), vec!["en"]);
+    }
+
+    #[test]
+    fn test_render_escapes_html_only() {
+        let mut set = TemplateSet::builtin();
+        set.insert(
+            "en",
+            EmailTemplate::Verification,
+            Template::new(
+                "Hi {{username}}",
+                "{{username}}",
+                "<b>{{username}}</b> {{{raw}}}",
+            ),
+        );
+        let vars = TemplateVars::new()
+            .set("username", "<script>")
+            .set("raw", "<i>ok</i>");
+
+        let rendered = set
+            .render("en", EmailTemplate::Verification, &vars)
+            .unwrap();
+        assert_eq!(rendered.subject, "Hi <script>");
+        assert_eq!(rendered.text_body, "<script>");
+        assert_eq!(rendered.html_body, "<b>&lt;script&gt;</b> <i>ok</i>");
+    }
+
+    #[test]
+    fn test_render_falls_back_to_base_language() {
+        let mut set = TemplateSet::builtin();
+        set.insert(
+            "pt",
+            EmailTemplate::PasswordReset,
+            Template::new("Redefinir senha", "{{link}}", "{{link}}"),
+        );
+        let vars = TemplateVars::new().set("link", "https://x");
+
+        let rendered = set
+            .render("pt-BR", EmailTemplate::PasswordReset, &vars)
+            .unwrap();
+        assert_eq!(rendered.subject, "Redefinir senha");
+
+        // No Portuguese verification template, so English is used
+        let vars = vars.set("username", "u").set("site_name", "Tracker");
+        let rendered = set
+            .render("pt-BR", EmailTemplate::Verification, &vars)
+            .unwrap();
+        assert!(rendered.subject.starts_with("Confirm"));
+    }
+
+    #[test]
+    fn test_missing_value_is_an_error() {
+        let set = TemplateSet::builtin();
+        let result = set.render("en", EmailTemplate::Verification, &TemplateVars::new());
+        assert!(matches!(result, Err(EmailError::Template(_))));
+    }
+
+    #[test]
+    fn test_invalid_placeholders() {
+        assert!(parse_placeholders("Hello {{name").is_err());
+        assert!(parse_placeholders("Hello {{na me}}").is_err());
+        assert!(parse_placeholders("Hello {{ name }}").is_ok());
+    }
+
+    #[test]
+    fn test_template_categories() {
+        for template in EmailTemplate::ALL {
+            assert_eq!(EmailTemplate::parse(template.as_str()), Some(template));
+        }
+        assert_eq!(
+            EmailTemplate::Verification.category(),
+            EmailCategory::Account
+        );
+        assert_eq!(
+            EmailTemplate::LoginConfirmation.category(),
+            EmailCategory::Account
+        );
+        assert_eq!(
+            EmailTemplate::TopicDigest.category(),
+            EmailCategory::Digests
+        );
+        assert_eq!(
+            EmailTemplate::ReseedRequest.category(),
+            EmailCategory::Reseeds
+        );
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = std::env::temp_dir().join(format!("email-templates-{}", uuid::Uuid::new_v4()));
+        let de = dir.join("de");
+        std::fs::create_dir_all(&de).unwrap();
+        std::fs::write(de.join("invite.subject"), "Einladung zu {{site_name}}\n").unwrap();
+        std::fs::write(de.join("invite.txt"), "{{link}}").unwrap();
+        std::fs::write(de.join("invite.html"), "{{link}}").unwrap();
+        // Incomplete template is skipped
+        std::fs::write(de.join("new_login.subject"), "Neue Anmeldung").unwrap();
+
+        let mut set = TemplateSet::builtin();
+        assert_eq!(set.load_dir(&dir).unwrap(), 1);
+        assert_eq!(
+            set.resolve("de-AT", EmailTemplate::Invite).unwrap().subject,
+            "Einladung zu {{site_name}}"
+        );
+        assert_eq!(set.locales(), vec!["de", "en"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+//! Email transports
+//!
+//! The dispatcher hands finished messages to an [`EmailTransport`]. SMTP is
+//! the production transport; [`FileTransport`] writes each message to a
+//! `.eml` file instead, for development and tests.
+
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Why a message could not be delivered
+#[derive(Debug, Error)]
+pub enum TransportError {
+    /// Worth retrying later (connection failure, 4xx reply)
+    #[error("Temporary delivery failure: {0}")]
+    Transient(String),
+
+    /// Will never succeed (5xx reply, e.g. the mailbox does not exist)
+    #[error("Permanent delivery failure: {0}")]
+    Permanent(String),
+}
+
+/// Delivers finished messages
+#[axum::async_trait]
+pub trait EmailTransport: Send + Sync {
+    /// Deliver one message
+    async fn send(&self, message: Message) -> Result<(), TransportError>;
+}
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Implicit TLS (port 465)
+    Tls,
+    /// STARTTLS upgrade (port 587)
+    StartTls,
+    /// Plain text; only for a relay on localhost
+    None,
+}
+
+impl SmtpSecurity {
+    /// Parse a config value (`tls`, `starttls` or `none`)
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "tls" => Some(SmtpSecurity::Tls),
+            "starttls" => Some(SmtpSecurity::StartTls),
+            "none" => Some(SmtpSecurity::None),
+            _ => None,
+        }
+    }
+}
+
+/// Delivers through an SMTP relay
+pub struct SmtpTransport {
+    inner: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    /// Connect to `host:port`, optionally authenticating
+    pub fn new(
+        host: &str,
+        port: u16,
+        security: SmtpSecurity,
+        credentials: Option<(String, String)>,
+    ) -> Result<Self, TransportError> {
+        let builder = match security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                host,
+            )),
+        }
+        .map_err(|e| TransportError::Permanent(e.to_string()))?;
+
+        let mut builder = builder.port(port).timeout(Some(Duration::from_secs(30)));
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            inner: builder.build(),
+        })
+    }
+}
+
+#[axum::async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, message: Message) -> Result<(), TransportError> {
+        self.inner.send(message).await.map(|_| ()).map_err(|e| {
+            if e.is_permanent() {
+                TransportError::Permanent(e.to_string())
+            } else {
+                TransportError::Transient(e.to_string())
+            }
+        })
+    }
+}
+
+/// Writes each message to `<dir>/<timestamp>-<uuid>.eml`
+pub struct FileTransport {
+    dir: PathBuf,
+}
+
+impl FileTransport {
+    /// Write messages into `dir`, which is created if needed
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[axum::async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, message: Message) -> Result<(), TransportError> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| TransportError::Transient(e.to_string()))?;
+
+        let name = format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+            uuid::Uuid::new_v4()
+        );
+        tokio::fs::write(self.dir.join(name), message.formatted())
+            .await
+            .map_err(|e| TransportError::Transient(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lettre::message::header::ContentType;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    fn message(to: &str) -> Message {
+        Message::builder()
+            .from("Tracker <noreply@tracker.test>".parse().unwrap())
+            .to(to.parse().unwrap())
+            .subject("Hello")
+            .header(ContentType::TEXT_PLAIN)
+            .body("Hello there".to_string())
+            .unwrap()
+    }
+
+    /// Minimal SMTP server that accepts one connection and reports each
+    /// message's DATA section. Recipients at `reject.test` get a 550.
+    async fn smtp_stand_in() -> (u16, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read, mut write) = socket.into_split();
+            let mut lines = BufReader::new(read).lines();
+
+            write
+                .write_all(b"220 localhost ESMTP stand-in\r\n")
+                .await
+                .unwrap();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let command = line.to_ascii_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                    b"250 localhost\r\n"
+                } else if command.starts_with("RCPT") && command.contains("@REJECT.TEST") {
+                    b"550 5.1.1 No such user\r\n"
+                } else if command.starts_with("DATA") {
+                    write
+                        .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                        .await
+                        .unwrap();
+                    let mut data = String::new();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if line == "." {
+                            break;
+                        }
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    let _ = tx.send(data);
+                    b"250 2.0.0 Queued\r\n"
+                } else if command.starts_with("QUIT") {
+                    let _ = write.write_all(b"221 Bye\r\n").await;
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                write.write_all(reply).await.unwrap();
+            }
+        });
+
+        (port, rx)
+    }
+
+    #[tokio::test]
+    async fn test_smtp_delivery() {
+        let (port, mut received) = smtp_stand_in().await;
+        let transport = SmtpTransport::new("127.0.0.1", port, SmtpSecurity::None, None).unwrap();
+
+        transport.send(message("user@example.test")).await.unwrap();
+
+        let data = received.recv().await.unwrap();
+        assert!(data.contains("Subject: Hello"));
+        assert!(data.contains("Hello there"));
+    }
+
+    #[tokio::test]
+    async fn test_smtp_rejection_is_permanent() {
+        let (port, _received) = smtp_stand_in().await;
+        let transport = SmtpTransport::new("127.0.0.1", port, SmtpSecurity::None, None).unwrap();
+
+        let result = transport.send(message("nobody@reject.test")).await;
+        assert!(matches!(result, Err(TransportError::Permanent(_))));
+    }
+
+    #[tokio::test]
+    async fn test_smtp_unreachable_is_transient() {
+        // Bind and drop to find a port nothing listens on
+        let port = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let transport = SmtpTransport::new("127.0.0.1", port, SmtpSecurity::None, None).unwrap();
+
+        let result = transport.send(message("user@example.test")).await;
+        assert!(matches!(result, Err(TransportError::Transient(_))));
+    }
+
+    #[tokio::test]
+    async fn test_file_transport() {
+        let dir = std::env::temp_dir().join(format!("email-sink-{}", uuid::Uuid::new_v4()));
+        let transport = FileTransport::new(&dir);
+
+        transport.send(message("user@example.test")).await.unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        let contents = std::fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+        assert!(contents.contains("To: user@example.test"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
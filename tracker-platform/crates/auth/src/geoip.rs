@@ -0,0 +1,263 @@
+//! Offline IP geolocation
+//!
+//! Resolves an IP address to a coarse location (country, region, city and
+//! approximate coordinates) from a local range file, so no lookup service
+//! is called at login. The file is CSV with one address range per line, in
+//! the layout of the free DB-IP "IP to City Lite" download:
+//!
+//! ```text
+//! start_ip,end_ip,continent,country,region,city,latitude,longitude
+//! 1.0.0.0,1.0.0.255,OC,AU,Queensland,"South Brisbane",-27.4767,153.017
+//! ```
+//!
+//! The "IP to Country Lite" layout (`start_ip,end_ip,country`) is accepted
+//! too; locations then carry only a country. IPv4 and IPv6 ranges can be
+//! mixed, and a header line is skipped.
+
+use std::net::IpAddr;
+use std::path::Path;
+use thiserror::Error;
+
+/// Errors from loading a GeoIP database
+#[derive(Debug, Error)]
+pub enum GeoIpError {
+    #[error("IO error: {0}")]
+    Io(String),
+
+    #[error("Invalid GeoIP database at line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
+impl From<std::io::Error> for GeoIpError {
+    fn from(e: std::io::Error) -> Self {
+        GeoIpError::Io(e.to_string())
+    }
+}
+
+/// Coarse location of an IP address
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoLocation {
+    /// ISO 3166-1 alpha-2 country code, upper case
+    pub country_code: String,
+    pub region: Option<String>,
+    pub city: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl GeoLocation {
+    /// Short description for session lists and emails, e.g. "Lyon, FR"
+    pub fn display(&self) -> String {
+        match &self.city {
+            Some(city) => format!("{}, {}", city, self.country_code),
+            None => self.country_code.clone(),
+        }
+    }
+
+    /// Latitude and longitude, if the database has them
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        Some((self.latitude?, self.longitude?))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct GeoRange {
+    start: u128,
+    end: u128,
+    location: GeoLocation,
+}
+
+/// In-memory IP range database
+#[derive(Debug, Clone, Default)]
+pub struct GeoIpDatabase {
+    ranges: Vec<GeoRange>,
+}
+
+impl GeoIpDatabase {
+    /// Load a range file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GeoIpError> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_csv(&contents)
+    }
+
+    /// Parse range file contents
+    pub fn from_csv(contents: &str) -> Result<Self, GeoIpError> {
+        let mut ranges = Vec::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = |message: &str| GeoIpError::Parse {
+                line: index + 1,
+                message: message.to_string(),
+            };
+
+            let fields = split_csv_line(line);
+            let (start, end) = match (fields[0].parse::<IpAddr>(), fields.get(1)) {
+                (Ok(start), Some(end)) => {
+                    let end = end
+                        .parse::<IpAddr>()
+                        .map_err(|_| parse_error("invalid end address"))?;
+                    (ip_to_u128(start), ip_to_u128(end))
+                }
+                // Header line
+                (Err(_), _) if ranges.is_empty() => continue,
+                _ => return Err(parse_error("invalid start address")),
+            };
+            if end < start {
+                return Err(parse_error("range ends before it starts"));
+            }
+
+            let field = |i: usize| fields.get(i).map(|f| f.trim()).filter(|f| !f.is_empty());
+            let location = match fields.len() {
+                3 => GeoLocation {
+                    country_code: field(2).unwrap_or_default().to_uppercase(),
+                    region: None,
+                    city: None,
+                    latitude: None,
+                    longitude: None,
+                },
+                n if n >= 8 => GeoLocation {
+                    country_code: field(3).unwrap_or_default().to_uppercase(),
+                    region: field(4).map(str::to_string),
+                    city: field(5).map(str::to_string),
+                    latitude: field(6).and_then(|f| f.parse().ok()),
+                    longitude: field(7).and_then(|f| f.parse().ok()),
+                },
+                _ => return Err(parse_error("expected 3 or 8 columns")),
+            };
+
+            // Unassigned and reserved ranges carry no country
+            if location.country_code.len() != 2 || location.country_code == "ZZ" {
+                continue;
+            }
+
+            ranges.push(GeoRange {
+                start,
+                end,
+                location,
+            });
+        }
+
+        ranges.sort_by_key(|range| range.start);
+        Ok(Self { ranges })
+    }
+
+    /// Location of `ip`, if it falls in a known range
+    pub fn lookup(&self, ip: IpAddr) -> Option<&GeoLocation> {
+        let ip = ip_to_u128(ip);
+        let index = self.ranges.partition_point(|range| range.start <= ip);
+        let range = self.ranges.get(index.checked_sub(1)?)?;
+        (ip <= range.end).then_some(&range.location)
+    }
+
+    /// Number of ranges loaded
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Whether no ranges are loaded
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+/// Map an address onto one number line, IPv4 as IPv4-mapped IPv6
+fn ip_to_u128(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(v4) => u128::from(v4.to_ipv6_mapped()),
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+/// Split a CSV line, honouring double-quoted fields
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CITY_CSV: &str = "start_ip,end_ip,continent,country,region,city,latitude,longitude\n\
+        1.0.0.0,1.0.0.255,OC,AU,Queensland,\"South Brisbane\",-27.4767,153.017\n\
+        2.16.0.0,2.16.5.255,EU,FR,Auvergne-Rhone-Alpes,\"Lyon, Presqu'ile\",45.7578,4.8320\n\
+        10.0.0.0,10.255.255.255,ZZ,ZZ,,,,\n\
+        2001:200::,2001:200:ffff:ffff:ffff:ffff:ffff:ffff,AS,JP,Tokyo,Tokyo,35.6895,139.6917\n";
+
+    #[test]
+    fn test_lookup_city_database() {
+        let db = GeoIpDatabase::from_csv(CITY_CSV).unwrap();
+        assert_eq!(db.len(), 3);
+
+        let brisbane = db.lookup("1.0.0.42".parse().unwrap()).unwrap();
+        assert_eq!(brisbane.country_code, "AU");
+        assert_eq!(brisbane.display(), "South Brisbane, AU");
+        assert_eq!(brisbane.coordinates(), Some((-27.4767, 153.017)));
+
+        let lyon = db.lookup("2.16.3.1".parse().unwrap()).unwrap();
+        assert_eq!(lyon.city.as_deref(), Some("Lyon, Presqu'ile"));
+
+        let tokyo = db.lookup("2001:200::1".parse().unwrap()).unwrap();
+        assert_eq!(tokyo.country_code, "JP");
+
+        // Gaps, reserved ranges and addresses past the last range
+        assert!(db.lookup("1.0.1.0".parse().unwrap()).is_none());
+        assert!(db.lookup("10.1.2.3".parse().unwrap()).is_none());
+        assert!(db.lookup("0.0.0.1".parse().unwrap()).is_none());
+        assert!(db.lookup("255.255.255.255".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_lookup_country_database() {
+        let db = GeoIpDatabase::from_csv("1.0.0.0,1.0.0.255,au\n").unwrap();
+        let location = db.lookup("1.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(location.display(), "AU");
+        assert_eq!(location.coordinates(), None);
+    }
+
+    #[test]
+    fn test_invalid_database() {
+        assert!(matches!(
+            GeoIpDatabase::from_csv("1.0.0.0,1.0.0.255,AU\nnot-an-ip,1.0.1.0,AU\n"),
+            Err(GeoIpError::Parse { line: 2, .. })
+        ));
+        assert!(matches!(
+            GeoIpDatabase::from_csv("1.0.0.255,1.0.0.0,AU\n"),
+            Err(GeoIpError::Parse { line: 1, .. })
+        ));
+        assert!(matches!(
+            GeoIpDatabase::from_csv("1.0.0.0,1.0.0.255,OC,AU\n"),
+            Err(GeoIpError::Parse { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_split_csv_line() {
+        assert_eq!(
+            split_csv_line("a,\"b, c\",\"say \"\"hi\"\"\",,"),
+            vec!["a", "b, c", "say \"hi\"", "", ""]
+        );
+    }
+}
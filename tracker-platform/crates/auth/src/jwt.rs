@@ -2,11 +2,15 @@
 //!
 //! This module provides JSON Web Token (JWT) functionality for authentication,
 //! supporting both access tokens (short-lived) and refresh tokens (long-lived).
+//! Tokens are signed with the asymmetric keys in a [`KeyRing`] and name their
+//! key in the `kid` header; see the `keys` module for rotation and JWKS.
 
+use crate::keys::KeyRing;
 use crate::permissions::Permission;
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Validation};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -68,6 +72,12 @@ pub struct Claims {
     pub jti: Uuid,
     /// Issuer
     pub iss: String,
+    /// Session the token was issued for
+    ///
+    /// The session is the token family: each refresh rotates the refresh
+    /// token it records, and replaying an old one revokes the session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
 }
 
 impl Claims {
@@ -84,6 +94,7 @@ impl Claims {
             iat: now.timestamp(),
             jti: Uuid::new_v4(),
             iss: "tracker-platform".to_string(),
+            sid: None,
         }
     }
 
@@ -100,6 +111,7 @@ impl Claims {
             iat: now.timestamp(),
             jti: Uuid::new_v4(),
             iss: "tracker-platform".to_string(),
+            sid: None,
         }
     }
 
@@ -121,9 +133,16 @@ impl Claims {
             iat: now.timestamp(),
             jti: Uuid::new_v4(),
             iss: "tracker-platform".to_string(),
+            sid: None,
         }
     }
 
+    /// Bind the token to a session
+    pub fn with_session(mut self, session_id: Uuid) -> Self {
+        self.sid = Some(session_id);
+        self
+    }
+
     /// Check if the token has expired
     pub fn is_expired(&self) -> bool {
         Utc::now().timestamp() > self.exp
@@ -142,41 +161,60 @@ impl Claims {
 
 /// JWT token manager
 ///
-/// Handles token generation, validation, and revocation.
+/// Handles token generation and validation.
 pub struct JwtManager {
-    encoding_key: EncodingKey,
-    decoding_key: DecodingKey,
-    validation: Validation,
+    keys: Arc<KeyRing>,
 }
 
 impl JwtManager {
-    /// Create a new JWT manager with a secret key
+    /// Create a new JWT manager over a key ring
     ///
-    /// # Security Note
-    /// The secret should be a strong, randomly generated string (at least 32 bytes).
-    /// Store it securely (e.g., in environment variables, not in code).
-    pub fn new(secret: &str) -> Self {
-        let encoding_key = EncodingKey::from_secret(secret.as_bytes());
-        let decoding_key = DecodingKey::from_secret(secret.as_bytes());
-        let mut validation = Validation::default();
-        validation.set_issuer(&["tracker-platform"]);
+    /// The ring is shared so that key rotation (`KeyStore::sync`) takes effect
+    /// without rebuilding the manager. A ring built with `KeyRing::from_jwks`
+    /// can validate tokens but not generate them.
+    pub fn new(keys: Arc<KeyRing>) -> Self {
+        Self { keys }
+    }
 
-        Self {
-            encoding_key,
-            decoding_key,
-            validation,
-        }
+    /// The key ring used for signing and verification
+    pub fn keys(&self) -> &Arc<KeyRing> {
+        &self.keys
     }
 
     /// Generate a JWT token from claims
     pub fn generate_token(&self, claims: &Claims) -> Result<String, JwtError> {
-        encode(&Header::default(), claims, &self.encoding_key)
+        let key = self
+            .keys
+            .signing_key(Utc::now())
+            .ok_or_else(|| JwtError::TokenGeneration("No active signing key".to_string()))?;
+        let encoding_key = key.encoding_key().ok_or_else(|| {
+            JwtError::TokenGeneration("Signing key has no private half".to_string())
+        })?;
+
+        encode(&key.header(), claims, encoding_key)
             .map_err(|e| JwtError::TokenGeneration(e.to_string()))
     }
 
     /// Validate and decode a JWT token
+    ///
+    /// The key is picked by the token's `kid`, and only that key's algorithm
+    /// is accepted.
     pub fn validate_token(&self, token: &str) -> Result<Claims, JwtError> {
-        let token_data = decode::<Claims>(token, &self.decoding_key, &self.validation)
+        let header = decode_header(token).map_err(|e| JwtError::InvalidToken(e.to_string()))?;
+        let kid = header
+            .kid
+            .ok_or_else(|| JwtError::InvalidToken("Missing key ID".to_string()))?;
+        let key = self
+            .keys
+            .verification_key(&kid, Utc::now())
+            .ok_or_else(|| JwtError::InvalidToken(format!("Unknown signing key: {}", kid)))?;
+
+        let mut validation = Validation::new(key.algorithm.jwt_algorithm());
+        validation.set_issuer(&["tracker-platform"]);
+        // Match `Claims::is_expired`
+        validation.leeway = 0;
+
+        let token_data = decode::<Claims>(token, key.decoding_key(), &validation)
             .map_err(|e| match e.kind() {
                 jsonwebtoken::errors::ErrorKind::ExpiredSignature => JwtError::TokenExpired,
                 jsonwebtoken::errors::ErrorKind::InvalidSignature => {
@@ -218,27 +256,6 @@ impl JwtManager {
             refresh_token,
         })
     }
-
-    /// Refresh an access token using a refresh token
-    ///
-    /// Validates the refresh token and generates a new access token.
-    pub fn refresh_access_token(
-        &self,
-        refresh_token: &str,
-        permissions: Vec<Permission>,
-    ) -> Result<String, JwtError> {
-        let claims = self.validate_token(refresh_token)?;
-
-        // Verify this is actually a refresh token
-        if claims.token_type != TokenType::Refresh {
-            return Err(JwtError::InvalidToken(
-                "Expected refresh token".to_string(),
-            ));
-        }
-
-        // Generate new access token
-        self.generate_access_token(claims.user_id(), permissions)
-    }
 }
 
 /// A pair of access and refresh tokens
@@ -355,12 +372,16 @@ impl TokenRevocationList {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::keys::{JwtKey, RotationPolicy};
 
-    const TEST_SECRET: &str = "test-secret-key-at-least-32-characters-long-for-security";
+    fn test_manager() -> JwtManager {
+        let key = JwtKey::generate(Utc::now()).unwrap();
+        JwtManager::new(Arc::new(KeyRing::new(vec![key])))
+    }
 
     #[test]
     fn test_token_generation() {
-        let manager = JwtManager::new(TEST_SECRET);
+        let manager = test_manager();
         let user_id = Uuid::new_v4();
         let permissions = vec![Permission::Download, Permission::UploadTorrent];
 
@@ -370,7 +391,7 @@ mod tests {
 
     #[test]
     fn test_token_validation() {
-        let manager = JwtManager::new(TEST_SECRET);
+        let manager = test_manager();
         let user_id = Uuid::new_v4();
         let permissions = vec![Permission::Download];
 
@@ -384,7 +405,7 @@ mod tests {
 
     #[test]
     fn test_token_pair_generation() {
-        let manager = JwtManager::new(TEST_SECRET);
+        let manager = test_manager();
         let user_id = Uuid::new_v4();
         let permissions = vec![Permission::Download];
 
@@ -401,31 +422,29 @@ mod tests {
     }
 
     #[test]
-    fn test_refresh_token_flow() {
-        let manager = JwtManager::new(TEST_SECRET);
-        let user_id = Uuid::new_v4();
-        let permissions = vec![Permission::Download];
+    fn test_session_bound_token() {
+        let manager = test_manager();
+        let session_id = Uuid::new_v4();
 
-        let refresh_token = manager.generate_refresh_token(user_id).unwrap();
-        let new_access_token = manager
-            .refresh_access_token(&refresh_token, permissions.clone())
-            .unwrap();
+        let claims = Claims::new_refresh_token(Uuid::new_v4()).with_session(session_id);
+        let token = manager.generate_token(&claims).unwrap();
+        assert_eq!(manager.validate_token(&token).unwrap().sid, Some(session_id));
 
-        let claims = manager.validate_token(&new_access_token).unwrap();
-        assert_eq!(claims.user_id(), user_id);
-        assert_eq!(claims.permissions, permissions);
+        // Tokens without a session omit the claim entirely
+        let claims = Claims::new_access_token(Uuid::new_v4(), vec![]);
+        assert!(serde_json::to_value(&claims).unwrap().get("sid").is_none());
     }
 
     #[test]
     fn test_invalid_token() {
-        let manager = JwtManager::new(TEST_SECRET);
+        let manager = test_manager();
         let result = manager.validate_token("invalid.token.here");
         assert!(result.is_err());
     }
 
     #[test]
     fn test_expired_token() {
-        let manager = JwtManager::new(TEST_SECRET);
+        let manager = test_manager();
         let user_id = Uuid::new_v4();
 
         // Create an expired token
@@ -457,4 +476,75 @@ mod tests {
         );
         assert!(expired_claims.is_expired());
     }
+
+    #[test]
+    fn test_token_names_signing_key() {
+        let manager = test_manager();
+        let token = manager.generate_access_token(Uuid::new_v4(), vec![]).unwrap();
+        let header = decode_header(&token).unwrap();
+
+        let signing_key = manager.keys().signing_key(Utc::now()).unwrap();
+        assert_eq!(header.alg, jsonwebtoken::Algorithm::EdDSA);
+        assert_eq!(header.kid.as_deref(), Some(signing_key.kid.as_str()));
+    }
+
+    #[test]
+    fn test_tokens_survive_rotation() {
+        let policy = RotationPolicy::default();
+        let start = Utc::now() - policy.rotation_interval;
+        let mut keys = vec![JwtKey::generate(start).unwrap()];
+        let manager = JwtManager::new(Arc::new(KeyRing::new(keys.clone())));
+        let token = manager.generate_access_token(Uuid::new_v4(), vec![]).unwrap();
+
+        // Activate the successor immediately to simulate the switch-over
+        policy.rotate(&mut keys, Utc::now()).unwrap().unwrap();
+        keys[1].activates_at = Utc::now();
+        manager.keys().replace(keys.clone());
+
+        assert!(manager.validate_token(&token).is_ok());
+        let new_token = manager.generate_access_token(Uuid::new_v4(), vec![]).unwrap();
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid.as_deref(),
+            Some(keys[1].kid.as_str())
+        );
+    }
+
+    #[test]
+    fn test_verify_only_manager() {
+        let signer = test_manager();
+        let token = signer.generate_access_token(Uuid::new_v4(), vec![]).unwrap();
+
+        let jwks = signer.keys().jwks(Utc::now());
+        let verifier = JwtManager::new(Arc::new(KeyRing::from_jwks(&jwks).unwrap()));
+
+        assert!(verifier.validate_token(&token).is_ok());
+        assert!(matches!(
+            verifier.generate_access_token(Uuid::new_v4(), vec![]),
+            Err(JwtError::TokenGeneration(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unknown_key_and_wrong_algorithm() {
+        let manager = test_manager();
+        let other = test_manager();
+        let token = other.generate_access_token(Uuid::new_v4(), vec![]).unwrap();
+        assert!(matches!(
+            manager.validate_token(&token),
+            Err(JwtError::InvalidToken(_))
+        ));
+
+        // An HMAC token claiming a known kid must not be accepted
+        let kid = manager.keys().signing_key(Utc::now()).unwrap().kid.clone();
+        let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256);
+        header.kid = Some(kid);
+        let claims = Claims::new_access_token(Uuid::new_v4(), vec![Permission::Download]);
+        let forged = encode(
+            &header,
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(b"guessed"),
+        )
+        .unwrap();
+        assert!(manager.validate_token(&forged).is_err());
+    }
 }
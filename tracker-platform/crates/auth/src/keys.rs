@@ -0,0 +1,659 @@
+//! JWT signing keys, rotation and JWKS
+//!
+//! Tokens are signed with asymmetric keys (Ed25519 by default, RS256 for
+//! imported RSA keys) and carry the signing key's ID in the `kid` header.
+//! Verifiers only need the public halves, published as a JWKS document, so a
+//! service that checks tokens cannot mint them.
+//!
+//! Keys rotate on a schedule. A new key is published `publish_ahead` before it
+//! starts signing, so verifiers that cache the JWKS already know it, and the
+//! key it replaces keeps verifying for `overlap` afterwards, so the tokens it
+//! signed stay valid until they expire. Rotation never logs anyone out.
+//!
+//! `KeyStore` keeps the keys in PostgreSQL so every instance signs with the
+//! same key and sees rotations made by the others.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Errors that can occur while managing signing keys
+#[derive(Debug, Error)]
+pub enum KeyError {
+    #[error("Failed to generate signing key: {0}")]
+    Generation(String),
+
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+/// Signature algorithm of a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyAlgorithm {
+    /// Ed25519 signatures
+    #[serde(rename = "EdDSA")]
+    EdDsa,
+    /// RSASSA-PKCS1-v1_5 with SHA-256
+    #[serde(rename = "RS256")]
+    Rs256,
+}
+
+impl KeyAlgorithm {
+    /// The JWS algorithm name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyAlgorithm::EdDsa => "EdDSA",
+            KeyAlgorithm::Rs256 => "RS256",
+        }
+    }
+
+    /// The matching `jsonwebtoken` algorithm
+    pub fn jwt_algorithm(&self) -> Algorithm {
+        match self {
+            KeyAlgorithm::EdDsa => Algorithm::EdDSA,
+            KeyAlgorithm::Rs256 => Algorithm::RS256,
+        }
+    }
+}
+
+/// Public key in JWK form (RFC 7517)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwk {
+    /// Key type: "OKP" for Ed25519, "RSA" for RSA
+    pub kty: String,
+    pub kid: String,
+    pub alg: KeyAlgorithm,
+    #[serde(rename = "use")]
+    pub key_use: String,
+    /// Curve, for OKP keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    /// Public key, for OKP keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+    /// Modulus, for RSA keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    /// Exponent, for RSA keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+}
+
+/// JWKS document served at `/.well-known/jwks.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+/// A signing or verification key
+#[derive(Clone)]
+pub struct JwtKey {
+    /// Key ID, sent in the `kid` header of tokens it signs
+    pub kid: String,
+    /// Signature algorithm
+    pub algorithm: KeyAlgorithm,
+    /// When the key starts signing; before that it is only published
+    pub activates_at: DateTime<Utc>,
+    /// When the key stops verifying; `None` while it is current
+    pub retires_at: Option<DateTime<Utc>>,
+    jwk: Jwk,
+    /// PKCS#8 (Ed25519) or PKCS#1 (RSA) DER; `None` for verify-only keys
+    private_der: Option<Vec<u8>>,
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+}
+
+impl fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtKey")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .field("activates_at", &self.activates_at)
+            .field("retires_at", &self.retires_at)
+            .field("can_sign", &self.can_sign())
+            .finish()
+    }
+}
+
+impl JwtKey {
+    /// Generate a new Ed25519 key
+    pub fn generate(activates_at: DateTime<Utc>) -> Result<Self, KeyError> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| KeyError::Generation("Ed25519 key generation failed".to_string()))?;
+        let kid = Uuid::new_v4().simple().to_string();
+
+        Self::from_ed25519_pkcs8(kid, pkcs8.as_ref(), activates_at)
+    }
+
+    /// Load an Ed25519 key from PKCS#8 DER
+    pub fn from_ed25519_pkcs8(
+        kid: String,
+        der: &[u8],
+        activates_at: DateTime<Utc>,
+    ) -> Result<Self, KeyError> {
+        let key_pair =
+            Ed25519KeyPair::from_pkcs8(der).map_err(|e| KeyError::InvalidKey(e.to_string()))?;
+        let x = URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref());
+
+        Ok(Self {
+            algorithm: KeyAlgorithm::EdDsa,
+            activates_at,
+            retires_at: None,
+            jwk: Jwk {
+                kty: "OKP".to_string(),
+                kid: kid.clone(),
+                alg: KeyAlgorithm::EdDsa,
+                key_use: "sig".to_string(),
+                crv: Some("Ed25519".to_string()),
+                x: Some(x),
+                n: None,
+                e: None,
+            },
+            kid,
+            private_der: Some(der.to_vec()),
+            encoding_key: Some(EncodingKey::from_ed_der(der)),
+            decoding_key: DecodingKey::from_ed_der(key_pair.public_key().as_ref()),
+        })
+    }
+
+    /// Load an RSA key from PKCS#1 DER, signing with RS256
+    ///
+    /// Use this when verifiers cannot handle EdDSA. Keys must be at least
+    /// 2048 bits.
+    pub fn from_rsa_der(
+        kid: String,
+        der: &[u8],
+        activates_at: DateTime<Utc>,
+    ) -> Result<Self, KeyError> {
+        let key_pair =
+            RsaKeyPair::from_der(der).map_err(|e| KeyError::InvalidKey(e.to_string()))?;
+        let public = RsaPublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+
+        Ok(Self {
+            algorithm: KeyAlgorithm::Rs256,
+            activates_at,
+            retires_at: None,
+            jwk: Jwk {
+                kty: "RSA".to_string(),
+                kid: kid.clone(),
+                alg: KeyAlgorithm::Rs256,
+                key_use: "sig".to_string(),
+                crv: None,
+                x: None,
+                n: Some(URL_SAFE_NO_PAD.encode(&public.n)),
+                e: Some(URL_SAFE_NO_PAD.encode(&public.e)),
+            },
+            kid,
+            private_der: Some(der.to_vec()),
+            encoding_key: Some(EncodingKey::from_rsa_der(der)),
+            decoding_key: DecodingKey::from_rsa_raw_components(&public.n, &public.e),
+        })
+    }
+
+    /// Build a verify-only key from a published JWK
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self, KeyError> {
+        let missing =
+            |field: &str| KeyError::InvalidKey(format!("JWK {} is missing {}", jwk.kid, field));
+
+        let decoding_key = match jwk.alg {
+            KeyAlgorithm::EdDsa => {
+                let x = jwk.x.as_deref().ok_or_else(|| missing("x"))?;
+                DecodingKey::from_ed_components(x)
+            }
+            KeyAlgorithm::Rs256 => {
+                let n = jwk.n.as_deref().ok_or_else(|| missing("n"))?;
+                let e = jwk.e.as_deref().ok_or_else(|| missing("e"))?;
+                DecodingKey::from_rsa_components(n, e)
+            }
+        }
+        .map_err(|e| KeyError::InvalidKey(e.to_string()))?;
+
+        Ok(Self {
+            kid: jwk.kid.clone(),
+            algorithm: jwk.alg,
+            activates_at: Utc::now(),
+            retires_at: None,
+            jwk: jwk.clone(),
+            private_der: None,
+            encoding_key: None,
+            decoding_key,
+        })
+    }
+
+    /// Public key in JWK form
+    pub fn jwk(&self) -> &Jwk {
+        &self.jwk
+    }
+
+    /// Whether this key holds a private half
+    pub fn can_sign(&self) -> bool {
+        self.encoding_key.is_some()
+    }
+
+    /// Whether the key no longer verifies tokens
+    pub fn is_retired(&self, now: DateTime<Utc>) -> bool {
+        self.retires_at.is_some_and(|retires_at| retires_at <= now)
+    }
+
+    /// JWT header for tokens signed with this key
+    pub fn header(&self) -> Header {
+        let mut header = Header::new(self.algorithm.jwt_algorithm());
+        header.kid = Some(self.kid.clone());
+        header
+    }
+
+    pub(crate) fn encoding_key(&self) -> Option<&EncodingKey> {
+        self.encoding_key.as_ref()
+    }
+
+    pub(crate) fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+}
+
+/// Key rotation schedule
+#[derive(Debug, Clone)]
+pub struct RotationPolicy {
+    /// How long each key signs before the next one takes over
+    pub rotation_interval: Duration,
+    /// How long a new key is published before it starts signing
+    pub publish_ahead: Duration,
+    /// How long a replaced key keeps verifying; must cover the longest
+    /// token lifetime
+    pub overlap: Duration,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        Self {
+            rotation_interval: Duration::days(30),
+            publish_ahead: Duration::days(1),
+            // Refresh tokens live 7 days
+            overlap: Duration::days(8),
+        }
+    }
+}
+
+impl RotationPolicy {
+    /// Apply the schedule to a key set
+    ///
+    /// Drops retired keys, and when the current key is due for replacement
+    /// generates its successor and sets retirement dates on the keys it
+    /// replaces. Returns the new key's ID if one was generated.
+    pub fn rotate(
+        &self,
+        keys: &mut Vec<JwtKey>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<String>, KeyError> {
+        keys.retain(|key| !key.is_retired(now));
+
+        let newest = keys
+            .iter()
+            .filter(|key| key.can_sign())
+            .map(|key| key.activates_at)
+            .max();
+
+        let activates_at = match newest {
+            None => now,
+            // A successor is already published and waiting
+            Some(newest) if newest > now => return Ok(None),
+            Some(newest) => {
+                let next = newest + self.rotation_interval;
+                if now < next - self.publish_ahead {
+                    return Ok(None);
+                }
+                // If rotation is overdue, still give verifiers time to fetch it
+                next.max(now + self.publish_ahead)
+            }
+        };
+
+        let key = JwtKey::generate(activates_at)?;
+        let retires_at = activates_at + self.overlap;
+        for old in keys.iter_mut().filter(|key| key.retires_at.is_none()) {
+            old.retires_at = Some(retires_at);
+        }
+
+        let kid = key.kid.clone();
+        keys.push(key);
+        Ok(Some(kid))
+    }
+}
+
+/// The keys currently in use, shared by every signer and verifier in the
+/// process
+#[derive(Debug, Default)]
+pub struct KeyRing {
+    keys: RwLock<Vec<Arc<JwtKey>>>,
+}
+
+impl KeyRing {
+    /// Create a key ring holding the given keys
+    pub fn new(keys: Vec<JwtKey>) -> Self {
+        let ring = Self::default();
+        ring.replace(keys);
+        ring
+    }
+
+    /// Create a verify-only key ring from a JWKS document
+    pub fn from_jwks(jwks: &JwkSet) -> Result<Self, KeyError> {
+        let keys = jwks
+            .keys
+            .iter()
+            .map(JwtKey::from_jwk)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(keys))
+    }
+
+    /// Swap in a new key set
+    pub fn replace(&self, keys: Vec<JwtKey>) {
+        let keys = keys.into_iter().map(Arc::new).collect();
+        *self.keys.write().unwrap_or_else(|e| e.into_inner()) = keys;
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Vec<Arc<JwtKey>>> {
+        self.keys.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The key new tokens are signed with: the most recently activated key
+    /// that holds a private half
+    pub fn signing_key(&self, now: DateTime<Utc>) -> Option<Arc<JwtKey>> {
+        self.read()
+            .iter()
+            .filter(|key| key.can_sign() && key.activates_at <= now && !key.is_retired(now))
+            .max_by_key(|key| key.activates_at)
+            .cloned()
+    }
+
+    /// Look up a key by ID for verification
+    pub fn verification_key(&self, kid: &str, now: DateTime<Utc>) -> Option<Arc<JwtKey>> {
+        self.read()
+            .iter()
+            .find(|key| key.kid == kid && !key.is_retired(now))
+            .cloned()
+    }
+
+    /// Public keys that verifiers should accept, including published keys
+    /// that have not started signing yet
+    pub fn jwks(&self, now: DateTime<Utc>) -> JwkSet {
+        JwkSet {
+            keys: self
+                .read()
+                .iter()
+                .filter(|key| !key.is_retired(now))
+                .map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Database row for a stored key
+struct KeyRow {
+    kid: String,
+    algorithm: String,
+    private_key: Vec<u8>,
+    activates_at: DateTime<Utc>,
+    retires_at: Option<DateTime<Utc>>,
+}
+
+impl KeyRow {
+    fn into_key(self) -> Result<JwtKey, KeyError> {
+        let mut key = match self.algorithm.as_str() {
+            "EdDSA" => JwtKey::from_ed25519_pkcs8(self.kid, &self.private_key, self.activates_at)?,
+            "RS256" => JwtKey::from_rsa_der(self.kid, &self.private_key, self.activates_at)?,
+            other => {
+                return Err(KeyError::InvalidKey(format!(
+                    "Unsupported algorithm {} for key {}",
+                    other, self.kid
+                )))
+            }
+        };
+        key.retires_at = self.retires_at;
+        Ok(key)
+    }
+}
+
+/// PostgreSQL storage for signing keys
+pub struct KeyStore {
+    db_pool: PgPool,
+}
+
+impl KeyStore {
+    /// Create a new key store
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// Add a key, e.g. an imported RSA key
+    pub async fn insert(&self, key: &JwtKey) -> Result<(), KeyError> {
+        let private_key = key
+            .private_der
+            .as_deref()
+            .ok_or_else(|| KeyError::InvalidKey(format!("Key {} has no private half", key.kid)))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO jwt_signing_keys (kid, algorithm, private_key, activates_at, retires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            key.kid,
+            key.algorithm.as_str(),
+            private_key,
+            key.activates_at,
+            key.retires_at
+        )
+        .execute(&self.db_pool)
+        .await
+        .map_err(|e| KeyError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Load the stored keys and install them in `ring` without rotating
+    ///
+    /// For instances that sign and verify but leave the rotation schedule
+    /// to the one that owns the configured policy.
+    pub async fn load(&self, ring: &KeyRing) -> Result<usize, KeyError> {
+        let rows = sqlx::query_as!(
+            KeyRow,
+            r#"
+            SELECT kid, algorithm, private_key, activates_at, retires_at
+            FROM jwt_signing_keys
+            WHERE retires_at IS NULL OR retires_at > NOW()
+            ORDER BY activates_at
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .map_err(|e| KeyError::Database(e.to_string()))?;
+
+        let keys = rows
+            .into_iter()
+            .map(KeyRow::into_key)
+            .collect::<Result<Vec<_>, _>>()?;
+        let count = keys.len();
+        ring.replace(keys);
+
+        Ok(count)
+    }
+
+    /// Load the stored keys, rotate them if due and install them in `ring`
+    ///
+    /// Run at startup and periodically on every instance. An advisory lock
+    /// makes sure only one instance generates each new key.
+    pub async fn sync(
+        &self,
+        ring: &KeyRing,
+        policy: &RotationPolicy,
+    ) -> Result<Option<String>, KeyError> {
+        let db_err = |e: sqlx::Error| KeyError::Database(e.to_string());
+        let mut tx = self.db_pool.begin().await.map_err(db_err)?;
+
+        sqlx::query!("SELECT pg_advisory_xact_lock(hashtext('jwt_signing_keys'))")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_err)?;
+
+        sqlx::query!("DELETE FROM jwt_signing_keys WHERE retires_at <= NOW()")
+            .execute(&mut *tx)
+            .await
+            .map_err(db_err)?;
+
+        let rows = sqlx::query_as!(
+            KeyRow,
+            r#"
+            SELECT kid, algorithm, private_key, activates_at, retires_at
+            FROM jwt_signing_keys
+            ORDER BY activates_at
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_err)?;
+
+        let mut keys = rows
+            .into_iter()
+            .map(KeyRow::into_key)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let rotated = policy.rotate(&mut keys, Utc::now())?;
+
+        if let Some(kid) = &rotated {
+            for key in &keys {
+                if &key.kid == kid {
+                    sqlx::query!(
+                        r#"
+                        INSERT INTO jwt_signing_keys (kid, algorithm, private_key, activates_at)
+                        VALUES ($1, $2, $3, $4)
+                        "#,
+                        key.kid,
+                        key.algorithm.as_str(),
+                        key.private_der.as_deref(),
+                        key.activates_at
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(db_err)?;
+                } else {
+                    sqlx::query!(
+                        "UPDATE jwt_signing_keys SET retires_at = $1 WHERE kid = $2",
+                        key.retires_at,
+                        key.kid
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(db_err)?;
+                }
+            }
+
+            tracing::info!("Generated JWT signing key {}", kid);
+        }
+
+        tx.commit().await.map_err(db_err)?;
+        ring.replace(keys);
+
+        Ok(rotated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RotationPolicy {
+        RotationPolicy::default()
+    }
+
+    #[test]
+    fn test_generated_key_jwk() {
+        let key = JwtKey::generate(Utc::now()).unwrap();
+        let jwk = serde_json::to_value(key.jwk()).unwrap();
+
+        assert_eq!(jwk["kty"], "OKP");
+        assert_eq!(jwk["crv"], "Ed25519");
+        assert_eq!(jwk["alg"], "EdDSA");
+        assert_eq!(jwk["use"], "sig");
+        assert_eq!(jwk["kid"], key.kid.as_str());
+        assert!(jwk.get("n").is_none());
+        assert!(key.can_sign());
+    }
+
+    #[test]
+    fn test_jwks_round_trip_is_verify_only() {
+        let ring = KeyRing::new(vec![JwtKey::generate(Utc::now()).unwrap()]);
+        let jwks: JwkSet =
+            serde_json::from_str(&serde_json::to_string(&ring.jwks(Utc::now())).unwrap()).unwrap();
+
+        let verifier = KeyRing::from_jwks(&jwks).unwrap();
+        assert!(verifier.signing_key(Utc::now()).is_none());
+        assert!(verifier
+            .verification_key(&jwks.keys[0].kid, Utc::now())
+            .is_some());
+    }
+
+    #[test]
+    fn test_rotation_schedule() {
+        let policy = policy();
+        let start = Utc::now();
+        let mut keys = Vec::new();
+
+        // First run creates a key that signs immediately
+        let first = policy.rotate(&mut keys, start).unwrap().unwrap();
+        assert_eq!(keys.len(), 1);
+        assert!(policy
+            .rotate(&mut keys, start + Duration::days(1))
+            .unwrap()
+            .is_none());
+
+        // A day before the interval ends the successor is published
+        let publish = start + policy.rotation_interval - policy.publish_ahead;
+        let second = policy.rotate(&mut keys, publish).unwrap().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert!(policy.rotate(&mut keys, publish).unwrap().is_none());
+
+        let ring = KeyRing::new(keys.clone());
+        assert_eq!(ring.signing_key(publish).unwrap().kid, first);
+        assert_eq!(ring.jwks(publish).keys.len(), 2);
+
+        // Once active, the successor signs and the old key still verifies
+        let switch = start + policy.rotation_interval;
+        assert_eq!(ring.signing_key(switch).unwrap().kid, second);
+        assert!(ring.verification_key(&first, switch).is_some());
+
+        // After the overlap the old key is gone
+        let retired = switch + policy.overlap;
+        assert!(ring.verification_key(&first, retired).is_none());
+        assert!(ring.verification_key(&second, retired).is_some());
+    }
+
+    #[test]
+    fn test_overdue_rotation_is_still_published_ahead() {
+        let policy = policy();
+        let start = Utc::now() - Duration::days(90);
+        let mut keys = vec![JwtKey::generate(start).unwrap()];
+
+        let now = Utc::now();
+        let kid = policy.rotate(&mut keys, now).unwrap().unwrap();
+        let new_key = keys.iter().find(|key| key.kid == kid).unwrap();
+
+        assert_eq!(new_key.activates_at, now + policy.publish_ahead);
+        assert_eq!(
+            keys[0].retires_at,
+            Some(new_key.activates_at + policy.overlap)
+        );
+        assert_eq!(
+            KeyRing::new(keys.clone()).signing_key(now).unwrap().kid,
+            keys[0].kid
+        );
+    }
+}
@@ -6,22 +6,34 @@
 //! - **User Registration**: Email/password registration with validation, email verification
 //! - **Login**: Secure login with password hashing (Argon2), JWT tokens, and session management
 //! - **Two-Factor Authentication (2FA)**: TOTP-based 2FA with recovery codes
-//! - **JWT Token Management**: Access and refresh tokens with automatic expiration
+//! - **Security Keys**: WebAuthn/FIDO2 keys as a second factor, and passkeys for passwordless login
+//! - **JWT Token Management**: Access and refresh tokens signed with rotating Ed25519/RSA keys, published as a JWKS
+//! - **Refresh Token Rotation**: Single-use refresh tokens per session; replaying one revokes the session
 //! - **Session Management**: Redis-backed sessions with device tracking
+//! - **Sign-in Risk Checks**: Known devices, offline GeoIP locations, new-country and impossible-travel detection, email approval of risky sign-ins and one-click "this wasn't me"
+//! - **API Tokens**: Scoped, expiring personal tokens for scripts, with IP allowlists
+//! - **OAuth 2.1 / OpenID Connect**: Sign-in for sister sites with authorization code + PKCE and consent
 //! - **Permission System**: Role-based access control (RBAC) with 20+ permissions
+//! - **User Classes**: Database-driven classes with per-user grants and denials, resolved through a cache
 //! - **Middleware**: Axum extractors for authentication and authorization
 //! - **Password Management**: Password reset, change, and strength validation
+//! - **Breached Password Screening**: New passwords checked against a local HIBP-format corpus, with forced resets and login-time rescreening
+//! - **Transactional Email**: Localized templates, a persistent delivery queue with retries, bounce suppression and one-click unsubscribes
 //!
 //! # Architecture
 //!
 //! The auth crate follows a layered architecture:
 //!
-//! 1. **Core Layer** (`password`, `jwt`, `permissions`)
+//! 1. **Core Layer** (`password`, `breach`, `geoip`, `jwt`, `keys`, `permissions`, `classes`)
 //!    - Password hashing and validation
+//!    - Breached password lookups
+//!    - IP address geolocation
 //!    - JWT token generation and verification
+//!    - Signing key rotation and JWKS publication
 //!    - Permission definitions and checks
+//!    - User classes and effective permission resolution
 //!
-//! 2. **Service Layer** (`register`, `login`, `two_factor`, `session`)
+//! 2. **Service Layer** (`register`, `login`, `login_risk`, `credentials`, `two_factor`, `webauthn`, `session`, `api_tokens`, `oauth`, `notifications`, `email`)
 //!    - Business logic for authentication flows
 //!    - Database interactions
 //!    - External service integration (email, Redis)
@@ -36,16 +48,20 @@
 //! ## Setting up JWT and Session Management
 //!
 //! ```rust,no_run
+//! use std::sync::Arc;
 //! use auth::{
 //!     jwt::{JwtManager, TokenRevocationList},
+//!     keys::{KeyRing, KeyStore, RotationPolicy},
 //!     session::SessionManager,
 //!     middleware::AuthState,
 //! };
 //!
-//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
-//! // Initialize JWT manager with a secret key (store securely!)
-//! let jwt_secret = std::env::var("JWT_SECRET")?;
-//! let jwt_manager = JwtManager::new(&jwt_secret);
+//! # async fn example(db_pool: sqlx::PgPool) -> Result<(), Box<dyn std::error::Error>> {
+//! // Load the signing keys, generating the first one if needed. Run `sync`
+//! // periodically so every instance picks up rotations.
+//! let keys = Arc::new(KeyRing::default());
+//! KeyStore::new(db_pool).sync(&keys, &RotationPolicy::default()).await?;
+//! let jwt_manager = JwtManager::new(keys);
 //!
 //! // Initialize Redis for sessions and token revocation
 //! let redis_url = std::env::var("REDIS_URL")?;
@@ -91,21 +107,28 @@
 //!
 //! ```rust,no_run
 //! use auth::login::{LoginRequest, LoginService};
-//! use auth::jwt::JwtManager;
+//! use auth::jwt::{JwtManager, TokenRevocationList};
 //! use auth::session::SessionManager;
 //! use auth::two_factor::TwoFactorManager;
+//! use auth::PermissionResolver;
+//! use std::sync::Arc;
 //!
 //! # async fn example(
 //! #     db_pool: sqlx::PgPool,
+//! #     redis_client: redis::Client,
 //! #     jwt_manager: JwtManager,
 //! #     session_manager: SessionManager,
+//! #     revocation_list: TokenRevocationList,
 //! # ) -> Result<(), Box<dyn std::error::Error>> {
 //! let two_factor_manager = TwoFactorManager::new("TrackerPlatform");
+//! let permissions = Arc::new(PermissionResolver::new(db_pool.clone(), redis_client));
 //! let login_service = LoginService::new(
 //!     db_pool,
 //!     jwt_manager,
 //!     session_manager,
+//!     revocation_list,
 //!     two_factor_manager,
+//!     permissions,
 //! );
 //!
 //! let request = LoginRequest {
@@ -113,7 +136,9 @@
 //!     password: "SecurePassword123!".to_string(),
 //!     totp_code: None,
 //!     recovery_code: None,
+//!     security_key: None,
 //!     remember_me: false,
+//!     login_confirmation: None,
 //! };
 //!
 //! let response = login_service.login(
@@ -156,9 +181,10 @@
 //! # Security Considerations
 //!
 //! - **Password Storage**: Uses Argon2id for password hashing (industry standard)
-//! - **JWT Secrets**: Store JWT_SECRET in environment variables, use strong random strings (32+ bytes)
+//! - **JWT Keys**: Tokens are signed with asymmetric keys that rotate monthly; other services verify them against the JWKS and cannot mint tokens
 //! - **Session Storage**: Redis provides fast, distributed session management
 //! - **Token Revocation**: Supports both individual token and user-wide revocation
+//! - **API Tokens**: Stored only as SHA-256 hashes and never carry staff permissions
 //! - **Rate Limiting**: Implement rate limiting at the API layer (not included in this crate)
 //! - **HTTPS**: Always use HTTPS in production to protect tokens in transit
 //! - **CORS**: Configure CORS appropriately to prevent unauthorized access
@@ -168,7 +194,19 @@
 //! This crate expects the following database tables (use migrations to create):
 //!
 //! - `users`: User accounts with credentials and metadata
+//! - `user_groups`, `user_group_permissions`: User classes and the permissions they carry
+//! - `user_permission_overrides`: Per-user permission grants and denials
+//! - `api_tokens`: Personal API tokens (hashed) with scopes, expiry and IP allowlists
+//! - `email_outbox`: Rendered emails waiting for delivery, with retry state
+//! - `email_suppressions`, `email_unsubscribes`: Bounced addresses and per-category opt-outs
 //! - `invitations` (optional): Invitation codes for restricted registration
+//! - `jwt_signing_keys`: JWT signing keys and their rotation schedule
+//! - `known_devices`, `login_events`: Devices and sign-in history used for risk checks
+//! - `login_confirmations`: Emailed approvals of risky sign-ins
+//! - `password_reset_tokens`: Single-use password reset links (hashed)
+//! - `oauth_clients`, `oauth_consents`: Registered OAuth clients and the scopes users approved
+//! - `security_notifications`: Security alerts shown to users (e.g. refresh token reuse)
+//! - `webauthn_credentials` (optional): Registered security keys and passkeys
 //!
 //! See the `migrations/` directory for SQL schema definitions.
 
@@ -176,19 +214,61 @@
 pub use uuid::Uuid;
 
 // Module declarations
+pub mod api_tokens;
+pub mod breach;
+pub mod classes;
+pub mod credentials;
+pub mod email;
+pub mod geoip;
 pub mod jwt;
+pub mod keys;
 pub mod login;
+pub mod login_risk;
 pub mod middleware;
+pub mod notifications;
+pub mod oauth;
 pub mod password;
 pub mod permissions;
 pub mod register;
 pub mod session;
 pub mod two_factor;
+pub mod webauthn;
 
 // Re-export key types for convenience
+pub use api_tokens::{
+    ApiToken, ApiTokenError, ApiTokenPrincipal, ApiTokenScope, ApiTokenService,
+    CreateApiTokenRequest, CreatedApiToken,
+};
+pub use breach::{BreachError, BreachScreening, BreachedPasswords};
+pub use classes::{
+    ClassError, CreateClassRequest, PermissionOverride, PermissionResolver, SetOverrideRequest,
+    UpdateClassRequest, UserClass, UserClassService,
+};
+pub use credentials::{CredentialError, PasswordService};
+pub use email::{
+    parse_report, BounceKind, BounceReport, DigestTopic, DispatchReport, EmailCategory,
+    EmailConfig, EmailDispatcher, EmailError, EmailService, EmailTemplate, EmailTransport,
+    FileTransport, RetryPolicy, SignInDetails, SmtpSecurity, SmtpTransport, TemplateSet,
+    TemplateVars, TransportError,
+};
+pub use geoip::{GeoIpDatabase, GeoIpError, GeoLocation};
 pub use jwt::{Claims, JwtManager, TokenPair, TokenRevocationList};
-pub use login::{LoginError, LoginRequest, LoginResponse, LoginService};
+pub use keys::{Jwk, JwkSet, JwtKey, KeyAlgorithm, KeyError, KeyRing, KeyStore, RotationPolicy};
+pub use login::{
+    LoginError, LoginRequest, LoginResponse, LoginService, PasskeyLoginRequest,
+    SecurityKeyAssertion, SecurityKeyEnrollmentRequest,
+};
+pub use login_risk::{
+    ConfirmationStatus, LoginRiskError, LoginRiskService, LoginRiskSettings, NotMeReport,
+    RiskAssessment, RiskSignal,
+};
 pub use middleware::{AuthError, AuthState, AuthUser, OptionalAuthUser};
+pub use notifications::{NotificationError, SecurityEvent, SecurityNotification, SecurityNotifier};
+pub use oauth::{
+    AuthorizationRequest, AuthorizeOutcome, OAuthClient, OAuthConfig, OAuthError, OAuthProvider,
+    OAuthScope, ProviderMetadata, RegisterClientRequest, RegisteredClient, TokenRequest,
+    TokenResponse, UserInfo,
+};
 pub use password::{
     hash_password, validate_password_strength, verify_password, PasswordError, PasswordStrength,
 };
@@ -201,6 +281,10 @@ pub use session::{parse_user_agent, Session, SessionError, SessionManager};
 pub use two_factor::{
     TwoFactorConfig, TwoFactorError, TwoFactorManager, TwoFactorSetup,
 };
+pub use webauthn::{
+    AssertionCredential, CreationOptions, RegistrationCredential, RequestOptions, SecurityKey,
+    SecurityKeyService, WebAuthnChallenge, WebAuthnConfig, WebAuthnError, WebAuthnManager,
+};
 
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -210,14 +294,25 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 /// This is a convenience re-export that groups all authentication-related
 /// functionality in one place.
 pub mod auth {
+    pub use crate::api_tokens::*;
+    pub use crate::breach::*;
+    pub use crate::classes::*;
+    pub use crate::credentials::*;
+    pub use crate::email::*;
+    pub use crate::geoip::*;
     pub use crate::jwt::*;
+    pub use crate::keys::*;
     pub use crate::login::*;
+    pub use crate::login_risk::*;
     pub use crate::middleware::*;
+    pub use crate::notifications::*;
+    pub use crate::oauth::*;
     pub use crate::password::*;
     pub use crate::permissions::*;
     pub use crate::register::*;
     pub use crate::session::*;
     pub use crate::two_factor::*;
+    pub use crate::webauthn::*;
 }
 
 #[cfg(test)]
@@ -232,10 +327,21 @@ mod tests {
     #[test]
     fn test_exports() {
         // Verify key types are exported
+        let _: Result<(), ApiTokenError> = Ok(());
+        let _: Result<(), BreachError> = Ok(());
+        let _: Result<(), ClassError> = Ok(());
+        let _: Result<(), CredentialError> = Ok(());
+        let _: Result<(), EmailError> = Ok(());
+        let _: Result<(), GeoIpError> = Ok(());
         let _: Result<(), PasswordError> = Ok(());
+        let _: Result<(), KeyError> = Ok(());
         let _: Result<(), LoginError> = Ok(());
+        let _: Result<(), LoginRiskError> = Ok(());
+        let _: Result<(), NotificationError> = Ok(());
+        let _: Result<(), OAuthError> = Ok(());
         let _: Result<(), RegistrationError> = Ok(());
         let _: Result<(), SessionError> = Ok(());
         let _: Result<(), TwoFactorError> = Ok(());
+        let _: Result<(), WebAuthnError> = Ok(());
     }
 }
@@ -1,16 +1,45 @@
 //! User login and authentication
 //!
 //! This module handles the login flow including password verification,
-//! 2FA validation, JWT token generation, and session management.
-
-use crate::jwt::{JwtManager, TokenPair};
+//! 2FA validation (TOTP, recovery codes or security keys), passwordless
+//! passkey login, JWT token generation, and session management.
+//!
+//! Roles can be forced onto security keys. A user in such a role who has
+//! none yet gets no session from a password login, only a challenge to
+//! register one; registering it completes the sign-in.
+//!
+//! Accounts flagged for a forced password reset cannot log in with their
+//! password until it is reset. Accounts queued for breached password
+//! rescreening have their password checked against the corpus on their
+//! next password login.
+//!
+//! With sign-in risk checks enabled, each sign-in is compared with the
+//! account's known devices and past locations. A risky password sign-in
+//! without a second factor must be approved from an emailed link, and the
+//! user is alerted about sign-ins from new devices or places.
+//!
+//! Refresh tokens are single use and bound to their session. Refreshing
+//! rotates both tokens; presenting an already-used refresh token revokes
+//! the whole session and notifies the user.
+
+use crate::breach::{BreachScreening, BreachedPasswords};
+use crate::classes::PermissionResolver;
+use crate::geoip::GeoLocation;
+use crate::jwt::{Claims, JwtManager, TokenPair, TokenRevocationList, TokenType};
+use crate::login_risk::{ConfirmationStatus, LoginRiskError, LoginRiskService, RiskAssessment};
+use crate::notifications::{SecurityEvent, SecurityNotifier};
 use crate::password::verify_password;
-use crate::permissions::PermissionSet;
-use crate::session::{Session, SessionManager};
+use crate::permissions::{Permission, PermissionSet, Role};
+use crate::session::{parse_user_agent, Session, SessionError, SessionManager};
 use crate::two_factor::{TwoFactorConfig, TwoFactorManager};
+use crate::webauthn::{
+    AssertionCredential, CreationOptions, RegistrationCredential, RequestOptions,
+    SecurityKeyService, WebAuthnChallenge,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
 use thiserror::Error;
 use uuid::Uuid;
 use validator::Validate;
@@ -27,6 +56,30 @@ pub enum LoginError {
     #[error("Invalid 2FA code")]
     InvalidTwoFactorCode,
 
+    #[error("Security key verification required")]
+    SecurityKeyRequired {
+        /// Assertion challenge to answer with one of the user's keys
+        challenge: Box<WebAuthnChallenge<RequestOptions>>,
+        /// Whether a TOTP or recovery code is accepted instead
+        totp_allowed: bool,
+    },
+
+    #[error("A security key must be registered before signing in")]
+    SecurityKeyEnrollmentRequired {
+        /// Registration challenge; answer it with
+        /// `LoginService::finish_security_key_enrollment`
+        challenge: Box<WebAuthnChallenge<CreationOptions>>,
+    },
+
+    #[error("Invalid security key response")]
+    InvalidSecurityKey,
+
+    #[error("Passkey login is not enabled")]
+    PasskeysDisabled,
+
+    #[error("Security key error: {0}")]
+    SecurityKeyError(String),
+
     #[error("Account is disabled")]
     AccountDisabled,
 
@@ -36,6 +89,18 @@ pub enum LoginError {
     #[error("Account is locked due to too many failed login attempts")]
     AccountLocked,
 
+    #[error("Password must be reset before logging in")]
+    PasswordResetRequired,
+
+    #[error("Unusual sign-in; approve it from the link emailed to you, then sign in again")]
+    ConfirmationRequired {
+        /// Send back as `login_confirmation` once the link has been followed
+        confirmation_id: Uuid,
+    },
+
+    #[error("Sign-in confirmation failed: {0}")]
+    ConfirmationFailed(String),
+
     #[error("Database error: {0}")]
     DatabaseError(String),
 
@@ -44,6 +109,9 @@ pub enum LoginError {
 
     #[error("Session creation failed: {0}")]
     SessionCreationFailed(String),
+
+    #[error("Refresh token has already been used; the session has been revoked")]
+    RefreshTokenReused,
 }
 
 /// Login request with email and password
@@ -63,6 +131,56 @@ pub struct LoginRequest {
     /// Recovery code (alternative to TOTP)
     pub recovery_code: Option<String>,
 
+    /// Security key assertion (alternative to TOTP)
+    #[serde(default)]
+    pub security_key: Option<SecurityKeyAssertion>,
+
+    /// Remember this device (longer session)
+    #[serde(default)]
+    pub remember_me: bool,
+
+    /// Approved confirmation ID from `LoginError::ConfirmationRequired`
+    #[serde(default)]
+    pub login_confirmation: Option<Uuid>,
+}
+
+/// Answer to a security key challenge issued by a previous login attempt
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecurityKeyAssertion {
+    /// Challenge ID from `LoginError::SecurityKeyRequired`
+    pub challenge_id: Uuid,
+
+    /// Response from `navigator.credentials.get()`
+    pub credential: AssertionCredential,
+}
+
+/// Passwordless login with a passkey
+#[derive(Debug, Clone, Deserialize)]
+pub struct PasskeyLoginRequest {
+    /// Challenge ID from `LoginService::start_passkey_login`
+    pub challenge_id: Uuid,
+
+    /// Response from `navigator.credentials.get()`
+    pub credential: AssertionCredential,
+
+    /// Remember this device (longer session)
+    #[serde(default)]
+    pub remember_me: bool,
+}
+
+/// First security key of a user whose role requires one, completing their
+/// sign-in
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecurityKeyEnrollmentRequest {
+    /// Challenge ID from `LoginError::SecurityKeyEnrollmentRequired`
+    pub challenge_id: Uuid,
+
+    /// Name for the key, e.g. "YubiKey 5C"
+    pub name: String,
+
+    /// Response from `navigator.credentials.create()`
+    pub credential: RegistrationCredential,
+
     /// Remember this device (longer session)
     #[serde(default)]
     pub remember_me: bool,
@@ -118,6 +236,8 @@ pub struct User {
     pub recovery_codes: Vec<String>,
     pub failed_login_attempts: i32,
     pub locked_until: Option<DateTime<Utc>>,
+    pub password_reset_required: bool,
+    pub password_breach_check_pending: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -154,10 +274,17 @@ pub struct LoginService {
     db_pool: PgPool,
     jwt_manager: JwtManager,
     session_manager: SessionManager,
+    revocation_list: TokenRevocationList,
+    notifier: SecurityNotifier,
     two_factor_manager: TwoFactorManager,
+    security_keys: Option<Arc<SecurityKeyService>>,
+    security_key_roles: Vec<Role>,
     max_failed_attempts: i32,
     lockout_duration_minutes: i64,
     require_email_verification: bool,
+    breach_corpus: Option<Arc<BreachedPasswords>>,
+    login_risk: Option<Arc<LoginRiskService>>,
+    permissions: Arc<PermissionResolver>,
 }
 
 impl LoginService {
@@ -166,16 +293,25 @@ impl LoginService {
         db_pool: PgPool,
         jwt_manager: JwtManager,
         session_manager: SessionManager,
+        revocation_list: TokenRevocationList,
         two_factor_manager: TwoFactorManager,
+        permissions: Arc<PermissionResolver>,
     ) -> Self {
         Self {
+            notifier: SecurityNotifier::new(db_pool.clone()),
             db_pool,
             jwt_manager,
             session_manager,
+            revocation_list,
             two_factor_manager,
+            security_keys: None,
+            security_key_roles: Vec::new(),
             max_failed_attempts: 5,
             lockout_duration_minutes: 15,
             require_email_verification: true,
+            breach_corpus: None,
+            login_risk: None,
+            permissions,
         }
     }
 
@@ -192,6 +328,34 @@ impl LoginService {
         self
     }
 
+    /// Enable security keys and passkey login
+    ///
+    /// Users in `required_roles` who have registered a key must use it;
+    /// TOTP and recovery codes are not accepted in its place. Until they
+    /// register one, a password login only hands them an enrollment
+    /// challenge.
+    pub fn with_security_keys(
+        mut self,
+        security_keys: Arc<SecurityKeyService>,
+        required_roles: Vec<Role>,
+    ) -> Self {
+        self.security_keys = Some(security_keys);
+        self.security_key_roles = required_roles;
+        self
+    }
+
+    /// Check passwords queued for rescreening against `corpus` at login
+    pub fn with_breach_corpus(mut self, corpus: Arc<BreachedPasswords>) -> Self {
+        self.breach_corpus = Some(corpus);
+        self
+    }
+
+    /// Check sign-ins for new devices and unusual locations
+    pub fn with_login_risk(mut self, login_risk: Arc<LoginRiskService>) -> Self {
+        self.login_risk = Some(login_risk);
+        self
+    }
+
     /// Get user by email
     async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, LoginError> {
         let record = sqlx::query!(
@@ -200,7 +364,8 @@ impl LoginService {
                 id, email, username, password_hash, role,
                 email_verified, is_enabled,
                 two_factor_enabled, two_factor_secret, recovery_codes,
-                failed_login_attempts, locked_until, created_at
+                failed_login_attempts, locked_until,
+                password_reset_required, password_breach_check_pending, created_at
             FROM users
             WHERE email = $1
             "#,
@@ -224,10 +389,70 @@ impl LoginService {
             recovery_codes: r.recovery_codes.unwrap_or_default(),
             failed_login_attempts: r.failed_login_attempts,
             locked_until: r.locked_until,
+            password_reset_required: r.password_reset_required,
+            password_breach_check_pending: r.password_breach_check_pending,
             created_at: r.created_at,
         }))
     }
 
+    /// Get the user a security key belongs to
+    async fn get_user_by_id(&self, user_id: Uuid) -> Result<User, LoginError> {
+        let email = sqlx::query_scalar!("SELECT email FROM users WHERE id = $1", user_id)
+            .fetch_optional(&self.db_pool)
+            .await
+            .map_err(|e| LoginError::DatabaseError(e.to_string()))?
+            .ok_or(LoginError::InvalidCredentials)?;
+
+        self.get_user_by_email(&email)
+            .await?
+            .ok_or(LoginError::InvalidCredentials)
+    }
+
+    /// Enforce a forced reset, running a pending breach rescreen first
+    ///
+    /// Only called once the password has been verified. If the corpus
+    /// can't be read the check stays pending for the next login.
+    async fn check_password_screening(
+        &self,
+        user: &User,
+        password: &str,
+    ) -> Result<(), LoginError> {
+        if user.password_reset_required {
+            return Err(LoginError::PasswordResetRequired);
+        }
+
+        let Some(corpus) = self
+            .breach_corpus
+            .as_ref()
+            .filter(|_| user.password_breach_check_pending)
+        else {
+            return Ok(());
+        };
+
+        let breached = match corpus.is_breached(password).await {
+            Ok(breached) => breached,
+            Err(e) => {
+                tracing::warn!("Breached password rescreen for {} skipped: {}", user.id, e);
+                return Ok(());
+            }
+        };
+
+        BreachScreening::new(self.db_pool.clone())
+            .record_check(user.id, breached)
+            .await
+            .map_err(|e| LoginError::DatabaseError(e.to_string()))?;
+
+        if breached {
+            tracing::info!(
+                "Password for {} found in breach corpus; reset required",
+                user.id
+            );
+            return Err(LoginError::PasswordResetRequired);
+        }
+
+        Ok(())
+    }
+
     /// Record failed login attempt
     async fn record_failed_attempt(&self, user_id: Uuid) -> Result<(), LoginError> {
         let failed_attempts = sqlx::query_scalar::<_, i32>(
@@ -313,6 +538,39 @@ impl LoginService {
         Ok(())
     }
 
+    /// Whether the user's role is forced onto security keys
+    fn security_key_required(&self, user: &User) -> bool {
+        self.security_key_roles
+            .iter()
+            .any(|role| role.as_str() == user.role)
+    }
+
+    /// Verify a security key assertion for a user who entered their password
+    async fn verify_security_key(
+        &self,
+        user: &User,
+        assertion: &SecurityKeyAssertion,
+    ) -> Result<(), LoginError> {
+        let security_keys = self
+            .security_keys
+            .as_ref()
+            .ok_or(LoginError::InvalidSecurityKey)?;
+
+        let key = security_keys
+            .finish_authentication(assertion.challenge_id, &assertion.credential)
+            .await
+            .map_err(|e| {
+                tracing::warn!("Security key assertion failed for {}: {}", user.id, e);
+                LoginError::InvalidSecurityKey
+            })?;
+
+        if key.user_id != user.id {
+            return Err(LoginError::InvalidSecurityKey);
+        }
+
+        Ok(())
+    }
+
     /// Perform login
     pub async fn login(
         &self,
@@ -352,61 +610,378 @@ impl LoginService {
             return Err(LoginError::InvalidCredentials);
         }
 
-        // Check 2FA if enabled
-        if user.two_factor_enabled {
-            if let Some(totp_code) = &request.totp_code {
-                self.verify_two_factor(&user, totp_code)?;
-            } else if let Some(recovery_code) = &request.recovery_code {
-                self.verify_recovery_code(&user, recovery_code).await?;
-            } else {
-                return Err(LoginError::TwoFactorRequired);
+        self.check_password_screening(&user, &request.password)
+            .await?;
+
+        // Registered security keys count as 2FA alongside TOTP
+        let security_keys = match &self.security_keys {
+            Some(service) => service
+                .list_keys(user.id)
+                .await
+                .map_err(|e| LoginError::SecurityKeyError(e.to_string()))?,
+            None => Vec::new(),
+        };
+        let key_required = self.security_key_required(&user);
+
+        // Staff forced onto security keys cannot fall back to TOTP once enrolled
+        let totp_allowed = user.two_factor_enabled && !(key_required && !security_keys.is_empty());
+        let totp_code = request.totp_code.as_deref().filter(|_| totp_allowed);
+        let recovery_code = request.recovery_code.as_deref().filter(|_| totp_allowed);
+
+        if let Some(assertion) = &request.security_key {
+            if let Err(e) = self.verify_security_key(&user, assertion).await {
+                self.record_failed_attempt(user.id).await?;
+                return Err(e);
+            }
+        } else if let Some(code) = totp_code {
+            self.verify_two_factor(&user, code)?;
+        } else if let Some(code) = recovery_code {
+            self.verify_recovery_code(&user, code).await?;
+        } else if let Some(service) = self
+            .security_keys
+            .as_ref()
+            .filter(|_| !security_keys.is_empty())
+        {
+            // Password was correct; hand out a challenge for the second step
+            let challenge = service
+                .start_authentication(user.id, &security_keys)
+                .await
+                .map_err(|e| LoginError::SecurityKeyError(e.to_string()))?;
+            return Err(LoginError::SecurityKeyRequired {
+                challenge: Box::new(challenge),
+                totp_allowed,
+            });
+        } else if user.two_factor_enabled {
+            return Err(LoginError::TwoFactorRequired);
+        }
+
+        // A second factor given now covers a risky sign-in; otherwise email approval does
+        let assessment = self
+            .assess_risk(&user, &ip_address, user_agent.as_deref())
+            .await;
+        let second_factor =
+            request.security_key.is_some() || totp_code.is_some() || recovery_code.is_some();
+        if let Some(assessment) = assessment.as_ref().filter(|a| a.is_risky()) {
+            if !second_factor {
+                self.require_confirmation(
+                    &user,
+                    request.login_confirmation,
+                    &ip_address,
+                    assessment,
+                )
+                .await?;
+            }
+        }
+
+        // No session until the required key exists; the challenge is all
+        // the password grants
+        if key_required && security_keys.is_empty() {
+            if let Some(service) = &self.security_keys {
+                let challenge = service
+                    .start_enrollment(user.id, &user.username)
+                    .await
+                    .map_err(|e| LoginError::SecurityKeyError(e.to_string()))?;
+                return Err(LoginError::SecurityKeyEnrollmentRequired {
+                    challenge: Box::new(challenge),
+                });
             }
         }
 
+        self.complete_login(
+            &user,
+            request.remember_me,
+            ip_address,
+            user_agent,
+            assessment,
+        )
+        .await
+    }
+
+    /// Register the security key a password login asked for and sign in
+    ///
+    /// The password (and second factor, if any) were checked when the
+    /// enrollment challenge was issued.
+    pub async fn finish_security_key_enrollment(
+        &self,
+        request: SecurityKeyEnrollmentRequest,
+        ip_address: String,
+        user_agent: Option<String>,
+    ) -> Result<LoginResponse, LoginError> {
+        let security_keys = self
+            .security_keys
+            .as_ref()
+            .ok_or(LoginError::PasskeysDisabled)?;
+
+        let key = security_keys
+            .finish_enrollment(request.challenge_id, &request.name, &request.credential)
+            .await
+            .map_err(|e| {
+                tracing::warn!("Security key enrollment failed: {}", e);
+                LoginError::InvalidSecurityKey
+            })?;
+
+        let user = self.get_user_by_id(key.user_id).await?;
+
+        if user.is_locked() {
+            return Err(LoginError::AccountLocked);
+        }
+
+        if !user.is_enabled {
+            return Err(LoginError::AccountDisabled);
+        }
+
+        let assessment = self
+            .assess_risk(&user, &ip_address, user_agent.as_deref())
+            .await;
+
+        self.complete_login(
+            &user,
+            request.remember_me,
+            ip_address,
+            user_agent,
+            assessment,
+        )
+        .await
+    }
+
+    /// Start a passwordless login
+    ///
+    /// The returned options go to `navigator.credentials.get()`; the browser
+    /// lets the user pick one of their passkeys for this site.
+    pub async fn start_passkey_login(
+        &self,
+    ) -> Result<WebAuthnChallenge<RequestOptions>, LoginError> {
+        self.security_keys
+            .as_ref()
+            .ok_or(LoginError::PasskeysDisabled)?
+            .start_passkey_authentication()
+            .await
+            .map_err(|e| LoginError::SecurityKeyError(e.to_string()))
+    }
+
+    /// Finish a passwordless login with a passkey
+    ///
+    /// A passkey proves possession and user verification (PIN or biometric)
+    /// on its own, so it replaces both the password and the second factor.
+    pub async fn login_with_passkey(
+        &self,
+        request: PasskeyLoginRequest,
+        ip_address: String,
+        user_agent: Option<String>,
+    ) -> Result<LoginResponse, LoginError> {
+        let security_keys = self
+            .security_keys
+            .as_ref()
+            .ok_or(LoginError::PasskeysDisabled)?;
+
+        let key = security_keys
+            .finish_authentication(request.challenge_id, &request.credential)
+            .await
+            .map_err(|e| {
+                tracing::warn!("Passkey login failed: {}", e);
+                LoginError::InvalidCredentials
+            })?;
+
+        let user = self.get_user_by_id(key.user_id).await?;
+
+        if user.is_locked() {
+            return Err(LoginError::AccountLocked);
+        }
+
+        if !user.is_enabled {
+            return Err(LoginError::AccountDisabled);
+        }
+
+        if self.require_email_verification && !user.email_verified {
+            return Err(LoginError::EmailNotVerified);
+        }
+
+        // A passkey is a strong factor on its own, so risky sign-ins need no approval
+        let assessment = self
+            .assess_risk(&user, &ip_address, user_agent.as_deref())
+            .await;
+
+        self.complete_login(
+            &user,
+            request.remember_me,
+            ip_address,
+            user_agent,
+            assessment,
+        )
+        .await
+    }
+
+    /// Check a sign-in against the account's history
+    ///
+    /// Failures are logged and the sign-in treated as ordinary.
+    async fn assess_risk(
+        &self,
+        user: &User,
+        ip_address: &str,
+        user_agent: Option<&str>,
+    ) -> Option<RiskAssessment> {
+        let login_risk = self.login_risk.as_ref()?;
+        match login_risk.assess(user.id, ip_address, user_agent).await {
+            Ok(assessment) => Some(assessment),
+            Err(e) => {
+                tracing::warn!("Sign-in risk check for {} skipped: {}", user.id, e);
+                None
+            }
+        }
+    }
+
+    /// Let a risky sign-in through only with an approved email confirmation
+    ///
+    /// Without one, a confirmation link is emailed (or the pending one
+    /// reused) and `ConfirmationRequired` returned.
+    async fn require_confirmation(
+        &self,
+        user: &User,
+        confirmation_id: Option<Uuid>,
+        ip_address: &str,
+        assessment: &RiskAssessment,
+    ) -> Result<(), LoginError> {
+        let Some(login_risk) = &self.login_risk else {
+            return Ok(());
+        };
+
+        if let Some(confirmation_id) = confirmation_id {
+            let status = login_risk
+                .use_confirmation(confirmation_id, user.id, &assessment.device_hash)
+                .await
+                .map_err(|e| LoginError::ConfirmationFailed(e.to_string()))?;
+            match status {
+                ConfirmationStatus::Approved => return Ok(()),
+                ConfirmationStatus::Pending => {
+                    return Err(LoginError::ConfirmationRequired { confirmation_id })
+                }
+                ConfirmationStatus::Invalid => {}
+            }
+        }
+
+        match login_risk
+            .start_confirmation(user.id, ip_address, assessment)
+            .await
+        {
+            Ok(confirmation_id) => Err(LoginError::ConfirmationRequired { confirmation_id }),
+            Err(LoginRiskError::EmailUnavailable) => {
+                tracing::warn!(
+                    "Risky sign-in for {} allowed: no email service for confirmations",
+                    user.id
+                );
+                Ok(())
+            }
+            Err(e) => Err(LoginError::ConfirmationFailed(e.to_string())),
+        }
+    }
+
+    /// Issue tokens and a session once the user is authenticated
+    async fn complete_login(
+        &self,
+        user: &User,
+        remember_me: bool,
+        ip_address: String,
+        user_agent: Option<String>,
+        assessment: Option<RiskAssessment>,
+    ) -> Result<LoginResponse, LoginError> {
         // Reset failed attempts
         self.reset_failed_attempts(user.id).await?;
 
-        // Generate tokens
-        let token_pair = self
-            .jwt_manager
-            .generate_token_pair(user.id, user.permissions.all())
-            .map_err(|e| LoginError::TokenGenerationFailed(e.to_string()))?;
-
         // Create session
-        let ttl = if request.remember_me {
+        let ttl = if remember_me {
             30 * 24 * 60 * 60 // 30 days
         } else {
             7 * 24 * 60 * 60 // 7 days
         };
 
-        // Decode token to get token_id
-        let claims = self
-            .jwt_manager
-            .validate_token(&token_pair.access_token)
-            .map_err(|e| LoginError::TokenGenerationFailed(e.to_string()))?;
+        // Token IDs are filled in once the tokens are signed
+        let mut session = Session::new(user.id, Uuid::nil(), ip_address, user_agent, ttl);
+        if let Some(user_agent) = session.user_agent.as_deref() {
+            (session.device_type, session.os, session.browser) = parse_user_agent(user_agent);
+        }
+        session.location = assessment
+            .as_ref()
+            .and_then(|a| a.location.as_ref())
+            .map(GeoLocation::display);
 
-        let session = Session::new(user.id, claims.token_id(), ip_address, user_agent, ttl);
+        // Generate tokens
+        let permissions = self.resolve_permissions(user.id).await?;
+        let token_pair = self.issue_tokens(&mut session, permissions.all())?;
 
         self.session_manager
             .create_session(&session)
             .await
             .map_err(|e| LoginError::SessionCreationFailed(e.to_string()))?;
 
+        if let (Some(login_risk), Some(assessment)) = (&self.login_risk, &assessment) {
+            if let Err(e) = login_risk
+                .record_login(user.id, &session.ip_address, assessment)
+                .await
+            {
+                tracing::warn!("Failed to record sign-in for {}: {}", user.id, e);
+            }
+        }
+
         // Build response
+        let mut info = user.to_info();
+        info.permissions = permissions
+            .all()
+            .iter()
+            .map(|p| format!("{:?}", p))
+            .collect();
+
         Ok(LoginResponse {
             access_token: token_pair.access_token,
             refresh_token: token_pair.refresh_token,
             token_type: "Bearer".to_string(),
             expires_in: 15 * 60, // 15 minutes
-            user: user.to_info(),
+            user: info,
             two_factor_enabled: user.two_factor_enabled,
         })
     }
 
+    /// The user's effective permissions, from their class and overrides
+    async fn resolve_permissions(&self, user_id: Uuid) -> Result<PermissionSet, LoginError> {
+        self.permissions
+            .resolve(user_id)
+            .await
+            .map_err(|e| LoginError::DatabaseError(e.to_string()))
+    }
+
+    /// Sign a new token pair bound to `session` and record it there
+    fn issue_tokens(
+        &self,
+        session: &mut Session,
+        permissions: Vec<Permission>,
+    ) -> Result<TokenPair, LoginError> {
+        let access =
+            Claims::new_access_token(session.user_id, permissions).with_session(session.session_id);
+        let refresh = Claims::new_refresh_token(session.user_id).with_session(session.session_id);
+
+        let sign = |claims: &Claims| {
+            self.jwt_manager
+                .generate_token(claims)
+                .map_err(|e| LoginError::TokenGenerationFailed(e.to_string()))
+        };
+        let token_pair = TokenPair {
+            access_token: sign(&access)?,
+            refresh_token: sign(&refresh)?,
+        };
+
+        session.rotate_tokens(access.token_id(), refresh.token_id());
+        Ok(token_pair)
+    }
+
     /// Refresh access token
+    ///
+    /// The refresh token is consumed: a new access and refresh token are
+    /// returned and the old refresh token stops working. If it is presented
+    /// again, the session it belongs to is revoked and the user notified.
     pub async fn refresh_token(
         &self,
         refresh_token: &str,
+        ip_address: String,
+        user_agent: Option<String>,
     ) -> Result<TokenPair, LoginError> {
         // Validate refresh token
         let claims = self
@@ -414,9 +989,27 @@ impl LoginService {
             .validate_token(refresh_token)
             .map_err(|_| LoginError::InvalidCredentials)?;
 
-        // Get user to fetch current permissions
-        let user = sqlx::query!(
-            "SELECT id, role FROM users WHERE id = $1 AND is_enabled = true",
+        if claims.token_type != TokenType::Refresh {
+            return Err(LoginError::InvalidCredentials);
+        }
+
+        // Refresh tokens issued without a session cannot be rotated
+        let session_id = claims.sid.ok_or(LoginError::InvalidCredentials)?;
+        let mut session = match self.session_manager.get_session(session_id).await {
+            Ok(session) if session.user_id == claims.user_id() => session,
+            Ok(_) | Err(SessionError::SessionNotFound) => {
+                return Err(LoginError::InvalidCredentials)
+            }
+            Err(e) => return Err(LoginError::SessionCreationFailed(e.to_string())),
+        };
+
+        // Make sure the user is still active and hasn't been told to reset
+        // their password, which a refresh would otherwise sidestep
+        let password_reset_required = sqlx::query_scalar!(
+            r#"
+            SELECT password_reset_required FROM users
+            WHERE id = $1 AND is_active = true AND is_banned = false AND deleted_at IS NULL
+            "#,
             claims.user_id()
         )
         .fetch_optional(&self.db_pool)
@@ -424,18 +1017,92 @@ impl LoginService {
         .map_err(|e| LoginError::DatabaseError(e.to_string()))?
         .ok_or(LoginError::InvalidCredentials)?;
 
-        // Get permissions (simplified - in production, load from database)
-        let permissions = PermissionSet::new();
+        if password_reset_required {
+            return Err(LoginError::PasswordResetRequired);
+        }
 
-        // Generate new token pair
-        let new_tokens = self
-            .jwt_manager
-            .generate_token_pair(user.id, permissions.all())
-            .map_err(|e| LoginError::TokenGenerationFailed(e.to_string()))?;
+        let ttl = claims.exp - Utc::now().timestamp();
+        let first_use = self
+            .session_manager
+            .claim_refresh_token(claims.token_id(), ttl)
+            .await
+            .map_err(|e| LoginError::SessionCreationFailed(e.to_string()))?;
+
+        if !first_use || !session.is_current_refresh_token(claims.token_id()) {
+            self.revoke_token_family(&session, &ip_address, user_agent.as_deref())
+                .await?;
+            return Err(LoginError::RefreshTokenReused);
+        }
+
+        // Sign with the user's current permissions, not those at login
+        let permissions = self.resolve_permissions(claims.user_id()).await?;
+
+        // Rotate both tokens
+        let new_tokens = self.issue_tokens(&mut session, permissions.all())?;
+        session.ip_address = ip_address;
+        if user_agent.is_some() {
+            session.user_agent = user_agent;
+        }
+
+        self.session_manager
+            .create_session(&session)
+            .await
+            .map_err(|e| LoginError::SessionCreationFailed(e.to_string()))?;
 
         Ok(new_tokens)
     }
 
+    /// Revoke a session's token family after its refresh token was replayed
+    ///
+    /// Either the legitimate client or an attacker holds a copy of the token,
+    /// and there is no telling which, so the session's current access and
+    /// refresh tokens are revoked and both parties have to log in again.
+    async fn revoke_token_family(
+        &self,
+        session: &Session,
+        ip_address: &str,
+        user_agent: Option<&str>,
+    ) -> Result<(), LoginError> {
+        tracing::warn!(
+            "Refresh token reuse for user {} session {} from {}; revoking session",
+            session.user_id,
+            session.session_id,
+            ip_address
+        );
+
+        let now = Utc::now();
+        let revocations = [
+            (Some(session.token_id), TokenType::Access),
+            (session.refresh_token_id, TokenType::Refresh),
+        ];
+        for (token_id, token_type) in revocations {
+            if let Some(token_id) = token_id {
+                let expires_at = now + token_type.expiration_duration();
+                self.revocation_list
+                    .revoke_token(token_id, expires_at.timestamp())
+                    .await
+                    .map_err(|e| LoginError::SessionCreationFailed(e.to_string()))?;
+            }
+        }
+
+        self.session_manager
+            .delete_session(session.session_id)
+            .await
+            .map_err(|e| LoginError::SessionCreationFailed(e.to_string()))?;
+
+        self.notifier
+            .notify(
+                session.user_id,
+                SecurityEvent::RefreshTokenReuse,
+                Some(ip_address),
+                user_agent,
+            )
+            .await
+            .map_err(|e| LoginError::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
     /// Logout (revoke session)
     pub async fn logout(&self, session_id: Uuid) -> Result<(), LoginError> {
         self.session_manager
@@ -469,7 +1136,9 @@ mod tests {
             password: "password123".to_string(),
             totp_code: None,
             recovery_code: None,
+            security_key: None,
             remember_me: false,
+            login_confirmation: None,
         };
 
         assert!(valid_request.validate().is_ok());
@@ -482,12 +1151,42 @@ mod tests {
             password: "password123".to_string(),
             totp_code: None,
             recovery_code: None,
+            security_key: None,
             remember_me: false,
+            login_confirmation: None,
         };
 
         assert!(request.validate().is_err());
     }
 
+    #[test]
+    fn test_login_request_with_security_key() {
+        let request: LoginRequest = serde_json::from_value(serde_json::json!({
+            "email": "test@example.com",
+            "password": "password123",
+            "security_key": {
+                "challenge_id": Uuid::new_v4(),
+                "credential": {
+                    "id": "AQID",
+                    "rawId": "AQID",
+                    "type": "public-key",
+                    "response": {
+                        "clientDataJSON": "e30",
+                        "authenticatorData": "AAAA",
+                        "signature": "MEQ",
+                        "userHandle": null
+                    }
+                }
+            }
+        }))
+        .unwrap();
+
+        let assertion = request.security_key.unwrap();
+        assert_eq!(assertion.credential.raw_id, vec![1, 2, 3]);
+        assert!(assertion.credential.response.user_handle.is_none());
+        assert!(request.totp_code.is_none());
+    }
+
     #[test]
     fn test_user_is_locked() {
         let mut user = User {
@@ -504,6 +1203,8 @@ mod tests {
             recovery_codes: Vec::new(),
             failed_login_attempts: 0,
             locked_until: None,
+            password_reset_required: false,
+            password_breach_check_pending: false,
             created_at: Utc::now(),
         };
 
@@ -532,6 +1233,8 @@ mod tests {
             recovery_codes: Vec::new(),
             failed_login_attempts: 0,
             locked_until: None,
+            password_reset_required: false,
+            password_breach_check_pending: false,
             created_at: Utc::now(),
         };
 
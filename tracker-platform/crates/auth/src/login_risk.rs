@@ -0,0 +1,810 @@
+//! Sign-in risk checks
+//!
+//! Every successful sign-in is compared with the user's history:
+//!
+//! - **New device**: the user agent (ignoring version numbers) has not
+//!   signed in to this account before
+//! - **New country**: the IP address resolves to a country the account has
+//!   never signed in from
+//! - **Impossible travel**: reaching this location from the previous
+//!   sign-in's location in the time between them would need a speed no
+//!   flight manages
+//!
+//! Locations come from a local [`GeoIpDatabase`], and are coarse: city and
+//! country at best. A new country or impossible travel makes the sign-in
+//! risky. A risky password sign-in without a fresh second factor has to be
+//! approved from a link emailed to the account before it goes through.
+//!
+//! Any signal sends the user a "new sign-in" alert. Its "this wasn't me"
+//! link signs the account out everywhere with one click.
+
+use crate::email::{templates::DEFAULT_LOCALE, EmailService, SignInDetails};
+use crate::geoip::{GeoIpDatabase, GeoLocation};
+use crate::jwt::TokenRevocationList;
+use crate::notifications::{SecurityEvent, SecurityNotifier};
+use crate::session::{parse_user_agent, SessionManager};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::net::IpAddr;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Mean Earth radius used for distances
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// How long a "this wasn't me" link works; the longest a session lasts
+const NOT_ME_LINK_DAYS: i64 = 30;
+
+/// Errors from sign-in risk checks
+#[derive(Debug, Error)]
+pub enum LoginRiskError {
+    #[error("Link is invalid or has expired")]
+    InvalidToken,
+
+    #[error("No email service configured for sign-in confirmations")]
+    EmailUnavailable,
+
+    #[error("Failed to send email: {0}")]
+    Email(String),
+
+    #[error("Session error: {0}")]
+    Session(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+impl From<sqlx::Error> for LoginRiskError {
+    fn from(e: sqlx::Error) -> Self {
+        LoginRiskError::Database(e.to_string())
+    }
+}
+
+/// Something unusual about a sign-in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskSignal {
+    /// Device not seen on this account before
+    NewDevice,
+    /// Country not seen on this account before
+    NewCountry,
+    /// Too far from the previous sign-in for the time between them
+    ImpossibleTravel,
+}
+
+impl RiskSignal {
+    /// Database representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RiskSignal::NewDevice => "new_device",
+            RiskSignal::NewCountry => "new_country",
+            RiskSignal::ImpossibleTravel => "impossible_travel",
+        }
+    }
+}
+
+/// Thresholds for the risk checks
+#[derive(Debug, Clone)]
+pub struct LoginRiskSettings {
+    /// Travel faster than this between sign-ins is impossible
+    pub max_travel_speed_kmh: f64,
+    /// Shorter distances are within GeoIP error and never flagged
+    pub min_travel_distance_km: f64,
+    /// How long an emailed sign-in approval link works
+    pub confirmation_ttl: Duration,
+}
+
+impl Default for LoginRiskSettings {
+    fn default() -> Self {
+        Self {
+            max_travel_speed_kmh: 1000.0,
+            min_travel_distance_km: 500.0,
+            confirmation_ttl: Duration::minutes(15),
+        }
+    }
+}
+
+/// Outcome of checking a sign-in
+#[derive(Debug, Clone)]
+pub struct RiskAssessment {
+    /// Hash identifying the device
+    pub device_hash: String,
+    /// Device description, e.g. "Firefox on Linux (Desktop)"
+    pub device: String,
+    /// Resolved location of the IP address
+    pub location: Option<GeoLocation>,
+    pub signals: Vec<RiskSignal>,
+}
+
+impl RiskAssessment {
+    /// Whether the sign-in needs a fresh second factor or email approval
+    pub fn is_risky(&self) -> bool {
+        self.signals
+            .iter()
+            .any(|s| matches!(s, RiskSignal::NewCountry | RiskSignal::ImpossibleTravel))
+    }
+
+    /// Whether the user should be alerted about the sign-in
+    pub fn should_alert(&self) -> bool {
+        !self.signals.is_empty()
+    }
+
+    /// Details for alert and confirmation emails
+    pub fn sign_in_details(&self, ip_address: &str) -> SignInDetails {
+        SignInDetails {
+            ip_address: ip_address.to_string(),
+            device: self.device.clone(),
+            location: self.location.as_ref().map(GeoLocation::display),
+            at: Utc::now(),
+        }
+    }
+}
+
+/// State of an emailed sign-in approval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationStatus {
+    /// Approved; the sign-in may go ahead
+    Approved,
+    /// Link not followed yet
+    Pending,
+    /// Unknown, expired, already used or from another device
+    Invalid,
+}
+
+/// Result of a "this wasn't me" report
+#[derive(Debug, Clone, Serialize)]
+pub struct NotMeReport {
+    pub user_id: Uuid,
+    /// Sessions signed out
+    pub sessions_revoked: usize,
+}
+
+/// What the account's previous sign-ins tell us
+#[derive(Debug, Clone, Default)]
+struct LoginHistory {
+    has_logins: bool,
+    countries: Vec<String>,
+    /// Most recent sign-in with coordinates
+    last_position: Option<(f64, f64, DateTime<Utc>)>,
+}
+
+/// Sign-in risk checks, approvals and "this wasn't me" reports
+pub struct LoginRiskService {
+    db_pool: PgPool,
+    sessions: SessionManager,
+    revocation_list: TokenRevocationList,
+    notifier: SecurityNotifier,
+    geoip: Option<Arc<GeoIpDatabase>>,
+    email: Option<Arc<EmailService>>,
+    settings: LoginRiskSettings,
+}
+
+impl LoginRiskService {
+    /// Create a service without location checks or email
+    pub fn new(db_pool: PgPool, redis_client: redis::Client) -> Self {
+        Self {
+            notifier: SecurityNotifier::new(db_pool.clone()),
+            sessions: SessionManager::new(redis_client.clone()),
+            revocation_list: TokenRevocationList::new(redis_client),
+            db_pool,
+            geoip: None,
+            email: None,
+            settings: LoginRiskSettings::default(),
+        }
+    }
+
+    /// Resolve locations with `geoip`, enabling the country and travel checks
+    pub fn with_geoip(mut self, geoip: Arc<GeoIpDatabase>) -> Self {
+        self.geoip = Some(geoip);
+        self
+    }
+
+    /// Send alerts and approval links through `email`
+    pub fn with_email(mut self, email: Arc<EmailService>) -> Self {
+        self.email = Some(email);
+        self
+    }
+
+    /// Use different thresholds
+    pub fn with_settings(mut self, settings: LoginRiskSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Resolve the coarse location of an IP address
+    pub fn locate(&self, ip_address: &str) -> Option<GeoLocation> {
+        let ip = ip_address.parse::<IpAddr>().ok()?;
+        self.geoip.as_ref()?.lookup(ip).cloned()
+    }
+
+    /// Check a sign-in against the account's history
+    pub async fn assess(
+        &self,
+        user_id: Uuid,
+        ip_address: &str,
+        user_agent: Option<&str>,
+    ) -> Result<RiskAssessment, LoginRiskError> {
+        let (device_hash, device) = device_fingerprint(user_agent);
+        let location = self.locate(ip_address);
+
+        let device_known = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM known_devices WHERE user_id = $1 AND device_hash = $2
+            ) AS "exists!"
+            "#,
+            user_id,
+            device_hash
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        let history = self.history(user_id).await?;
+        let signals = evaluate(
+            device_known,
+            &history,
+            location.as_ref(),
+            Utc::now(),
+            &self.settings,
+        );
+
+        Ok(RiskAssessment {
+            device_hash,
+            device,
+            location,
+            signals,
+        })
+    }
+
+    async fn history(&self, user_id: Uuid) -> Result<LoginHistory, LoginRiskError> {
+        let countries = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT country_code AS "country_code!"
+            FROM login_events
+            WHERE user_id = $1 AND country_code IS NOT NULL
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let has_logins = !countries.is_empty()
+            || sqlx::query_scalar!(
+                r#"SELECT EXISTS(SELECT 1 FROM login_events WHERE user_id = $1) AS "exists!""#,
+                user_id
+            )
+            .fetch_one(&self.db_pool)
+            .await?;
+
+        let last_position = sqlx::query!(
+            r#"
+            SELECT latitude AS "latitude!", longitude AS "longitude!", created_at
+            FROM login_events
+            WHERE user_id = $1 AND latitude IS NOT NULL AND longitude IS NOT NULL
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .map(|r| (r.latitude, r.longitude, r.created_at));
+
+        Ok(LoginHistory {
+            has_logins,
+            countries,
+            last_position,
+        })
+    }
+
+    /// Record a completed sign-in and alert the user if anything was unusual
+    ///
+    /// Failing to queue the alert is logged, not returned.
+    pub async fn record_login(
+        &self,
+        user_id: Uuid,
+        ip_address: &str,
+        assessment: &RiskAssessment,
+    ) -> Result<(), LoginRiskError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO known_devices (user_id, device_hash, description, last_ip_address)
+            VALUES ($1, $2, $3, $4::text::inet)
+            ON CONFLICT (user_id, device_hash)
+            DO UPDATE SET last_seen_at = NOW(), last_ip_address = EXCLUDED.last_ip_address
+            "#,
+            user_id,
+            assessment.device_hash,
+            assessment.device,
+            ip_address
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        let report_token = assessment.should_alert().then(generate_token);
+        let location = assessment.location.as_ref();
+        let signals: Vec<String> = assessment
+            .signals
+            .iter()
+            .map(|s| s.as_str().to_string())
+            .collect();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO login_events (
+                user_id, device_hash, ip_address, country_code, location,
+                latitude, longitude, signals, report_token_hash
+            )
+            VALUES ($1, $2, $3::text::inet, $4, $5, $6, $7, $8, $9)
+            "#,
+            user_id,
+            assessment.device_hash,
+            ip_address,
+            location.map(|l| l.country_code.as_str()),
+            location.map(GeoLocation::display),
+            location.and_then(|l| l.latitude),
+            location.and_then(|l| l.longitude),
+            &signals,
+            report_token.as_deref().map(hash_token)
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        if let (Some(token), Some(email)) = (report_token, &self.email) {
+            let (to, username, locale) = self.recipient(user_id).await?;
+            if let Err(e) = email
+                .send_new_login_alert(
+                    user_id,
+                    &to,
+                    &username,
+                    &locale,
+                    &assessment.sign_in_details(ip_address),
+                    &token,
+                )
+                .await
+            {
+                tracing::warn!("Failed to queue sign-in alert for {}: {}", user_id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Email a link approving a risky sign-in
+    ///
+    /// Returns the confirmation ID the client sends with its next sign-in
+    /// attempt once the link has been followed.
+    pub async fn start_confirmation(
+        &self,
+        user_id: Uuid,
+        ip_address: &str,
+        assessment: &RiskAssessment,
+    ) -> Result<Uuid, LoginRiskError> {
+        let email = self
+            .email
+            .as_ref()
+            .ok_or(LoginRiskError::EmailUnavailable)?;
+
+        let token = generate_token();
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO login_confirmations (
+                user_id, token_hash, device_hash, ip_address, expires_at
+            )
+            VALUES ($1, $2, $3, $4::text::inet, $5)
+            RETURNING id
+            "#,
+            user_id,
+            hash_token(&token),
+            assessment.device_hash,
+            ip_address,
+            Utc::now() + self.settings.confirmation_ttl
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        let (to, username, locale) = self.recipient(user_id).await?;
+        email
+            .send_login_confirmation(
+                user_id,
+                &to,
+                &username,
+                &locale,
+                &assessment.sign_in_details(ip_address),
+                &token,
+            )
+            .await
+            .map_err(|e| LoginRiskError::Email(e.to_string()))?;
+
+        Ok(id)
+    }
+
+    /// Approve a risky sign-in from the emailed link
+    ///
+    /// Returns the user the sign-in belongs to.
+    pub async fn approve_confirmation(&self, token: &str) -> Result<Uuid, LoginRiskError> {
+        sqlx::query_scalar!(
+            r#"
+            UPDATE login_confirmations SET approved_at = NOW()
+            WHERE token_hash = $1 AND approved_at IS NULL AND expires_at > NOW()
+            RETURNING user_id
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(LoginRiskError::InvalidToken)
+    }
+
+    /// Check a confirmation a sign-in attempt refers to, using it up if
+    /// it has been approved
+    ///
+    /// Only the device that started the confirmation can use it.
+    pub async fn use_confirmation(
+        &self,
+        confirmation_id: Uuid,
+        user_id: Uuid,
+        device_hash: &str,
+    ) -> Result<ConfirmationStatus, LoginRiskError> {
+        // used_at is only set once the link has been followed
+        let approved = sqlx::query_scalar!(
+            r#"
+            UPDATE login_confirmations
+            SET used_at = CASE WHEN approved_at IS NOT NULL THEN NOW() END
+            WHERE id = $1 AND user_id = $2 AND device_hash = $3
+              AND used_at IS NULL AND expires_at > NOW()
+            RETURNING approved_at IS NOT NULL AS "approved!"
+            "#,
+            confirmation_id,
+            user_id,
+            device_hash
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(match approved {
+            Some(true) => ConfirmationStatus::Approved,
+            Some(false) => ConfirmationStatus::Pending,
+            None => ConfirmationStatus::Invalid,
+        })
+    }
+
+    /// Handle a "this wasn't me" link from a sign-in alert
+    ///
+    /// Signs the account out of every session, forgets the reported device
+    /// and records a security notification. Each link works once.
+    pub async fn report_not_me(&self, token: &str) -> Result<NotMeReport, LoginRiskError> {
+        let event = sqlx::query!(
+            r#"
+            UPDATE login_events SET reported_at = NOW()
+            WHERE report_token_hash = $1 AND reported_at IS NULL AND created_at > $2
+            RETURNING user_id, device_hash, host(ip_address) AS "ip_address!"
+            "#,
+            hash_token(token),
+            Utc::now() - Duration::days(NOT_ME_LINK_DAYS)
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(LoginRiskError::InvalidToken)?;
+
+        sqlx::query!(
+            "DELETE FROM known_devices WHERE user_id = $1 AND device_hash = $2",
+            event.user_id,
+            event.device_hash
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        let sessions_revoked = self
+            .sessions
+            .delete_all_user_sessions(event.user_id)
+            .await
+            .map_err(|e| LoginRiskError::Session(e.to_string()))?;
+        self.revocation_list
+            .revoke_user_tokens(
+                event.user_id,
+                Duration::days(NOT_ME_LINK_DAYS).num_seconds(),
+            )
+            .await
+            .map_err(|e| LoginRiskError::Session(e.to_string()))?;
+
+        if let Err(e) = self
+            .notifier
+            .notify(
+                event.user_id,
+                SecurityEvent::SignInReported,
+                Some(&event.ip_address),
+                None,
+            )
+            .await
+        {
+            tracing::warn!(
+                "Failed to record sign-in report for {}: {}",
+                event.user_id,
+                e
+            );
+        }
+
+        tracing::info!(
+            "User {} reported a sign-in from {}; {} sessions revoked",
+            event.user_id,
+            event.ip_address,
+            sessions_revoked
+        );
+
+        Ok(NotMeReport {
+            user_id: event.user_id,
+            sessions_revoked,
+        })
+    }
+
+    /// Address, username and locale to email a user at
+    async fn recipient(&self, user_id: Uuid) -> Result<(String, String, String), LoginRiskError> {
+        let user = sqlx::query!(
+            "SELECT email, username, locale FROM users WHERE id = $1",
+            user_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok((
+            user.email,
+            user.username,
+            user.locale.unwrap_or_else(|| DEFAULT_LOCALE.to_string()),
+        ))
+    }
+}
+
+/// Decide which signals a sign-in raises
+fn evaluate(
+    device_known: bool,
+    history: &LoginHistory,
+    location: Option<&GeoLocation>,
+    now: DateTime<Utc>,
+    settings: &LoginRiskSettings,
+) -> Vec<RiskSignal> {
+    let mut signals = Vec::new();
+
+    // An account's first sign-in has nothing to compare with
+    if !history.has_logins {
+        return signals;
+    }
+
+    if !device_known {
+        signals.push(RiskSignal::NewDevice);
+    }
+
+    if let Some(location) = location {
+        if !history.countries.is_empty() && !history.countries.contains(&location.country_code) {
+            signals.push(RiskSignal::NewCountry);
+        }
+
+        if let (Some(here), Some((lat, lon, at))) = (location.coordinates(), history.last_position)
+        {
+            let distance = distance_km(here, (lat, lon));
+            let hours = (now - at).num_seconds().max(60) as f64 / 3600.0;
+            if distance >= settings.min_travel_distance_km
+                && distance / hours > settings.max_travel_speed_kmh
+            {
+                signals.push(RiskSignal::ImpossibleTravel);
+            }
+        }
+    }
+
+    signals
+}
+
+/// Great-circle distance between two coordinates
+pub fn distance_km(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (a.0.to_radians(), a.1.to_radians());
+    let (lat2, lon2) = (b.0.to_radians(), b.1.to_radians());
+
+    let h = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+/// Identify a device by its user agent, ignoring version numbers so that
+/// browser updates don't make it new
+///
+/// Returns the hash and a readable description.
+pub fn device_fingerprint(user_agent: Option<&str>) -> (String, String) {
+    let user_agent = user_agent.unwrap_or_default();
+    let normalized: String = user_agent
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_ascii_digit())
+        .collect();
+    let hash = hex::encode(Sha256::digest(normalized.as_bytes()));
+
+    let (device_type, os, browser) = parse_user_agent(user_agent);
+    let description = match (browser, os) {
+        _ if user_agent.is_empty() => "Unknown device".to_string(),
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(name), None) | (None, Some(name)) => name,
+        (None, None) => "Unknown browser".to_string(),
+    };
+    let description = match device_type {
+        Some(device_type) if !user_agent.is_empty() => {
+            format!("{} ({})", description, device_type)
+        }
+        _ => description,
+    };
+
+    (hash, description)
+}
+
+/// Generate a token for an emailed link
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hash a link token for storage and lookup
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHROME_91: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+                             (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+    const CHROME_126: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+                              (KHTML, like Gecko) Chrome/126.0.6478.61 Safari/537.36";
+
+    fn location(country: &str, lat: f64, lon: f64) -> GeoLocation {
+        GeoLocation {
+            country_code: country.to_string(),
+            region: None,
+            city: None,
+            latitude: Some(lat),
+            longitude: Some(lon),
+        }
+    }
+
+    fn history(countries: &[&str], last: Option<(f64, f64, DateTime<Utc>)>) -> LoginHistory {
+        LoginHistory {
+            has_logins: true,
+            countries: countries.iter().map(|c| c.to_string()).collect(),
+            last_position: last,
+        }
+    }
+
+    #[test]
+    fn test_device_fingerprint_ignores_versions() {
+        let (hash_91, description) = device_fingerprint(Some(CHROME_91));
+        let (hash_126, _) = device_fingerprint(Some(CHROME_126));
+        assert_eq!(hash_91, hash_126);
+        assert_eq!(description, "Chrome on Windows (Desktop)");
+
+        let (firefox, _) = device_fingerprint(Some(
+            "Mozilla/5.0 (X11; Linux x86_64; rv:127.0) Gecko/20100101 Firefox/127.0",
+        ));
+        assert_ne!(firefox, hash_91);
+
+        assert_eq!(device_fingerprint(None).1, "Unknown device");
+    }
+
+    #[test]
+    fn test_distance_km() {
+        let paris = (48.8566, 2.3522);
+        let new_york = (40.7128, -74.0060);
+        let distance = distance_km(paris, new_york);
+        assert!((distance - 5837.0).abs() < 10.0, "{}", distance);
+        assert_eq!(distance_km(paris, paris), 0.0);
+    }
+
+    #[test]
+    fn test_first_login_raises_nothing() {
+        let signals = evaluate(
+            false,
+            &LoginHistory::default(),
+            Some(&location("FR", 48.8566, 2.3522)),
+            Utc::now(),
+            &LoginRiskSettings::default(),
+        );
+        assert!(signals.is_empty());
+    }
+
+    #[test]
+    fn test_new_device_and_country() {
+        let settings = LoginRiskSettings::default();
+        let paris = location("FR", 48.8566, 2.3522);
+
+        let signals = evaluate(
+            false,
+            &history(&["FR"], None),
+            Some(&paris),
+            Utc::now(),
+            &settings,
+        );
+        assert_eq!(signals, vec![RiskSignal::NewDevice]);
+
+        let signals = evaluate(
+            true,
+            &history(&["DE"], None),
+            Some(&paris),
+            Utc::now(),
+            &settings,
+        );
+        assert_eq!(signals, vec![RiskSignal::NewCountry]);
+
+        // No location, nothing to compare
+        let signals = evaluate(true, &history(&["DE"], None), None, Utc::now(), &settings);
+        assert!(signals.is_empty());
+    }
+
+    #[test]
+    fn test_impossible_travel() {
+        let settings = LoginRiskSettings::default();
+        let now = Utc::now();
+        let new_york = location("US", 40.7128, -74.0060);
+        let paris = (48.8566, 2.3522);
+
+        // Paris to New York in an hour
+        let signals = evaluate(
+            true,
+            &history(
+                &["US", "FR"],
+                Some((paris.0, paris.1, now - Duration::hours(1))),
+            ),
+            Some(&new_york),
+            now,
+            &settings,
+        );
+        assert_eq!(signals, vec![RiskSignal::ImpossibleTravel]);
+
+        // A day later is a plausible flight
+        let signals = evaluate(
+            true,
+            &history(
+                &["US", "FR"],
+                Some((paris.0, paris.1, now - Duration::days(1))),
+            ),
+            Some(&new_york),
+            now,
+            &settings,
+        );
+        assert!(signals.is_empty());
+
+        // Neighbouring cities are within GeoIP error
+        let signals = evaluate(
+            true,
+            &history(&["FR"], Some((48.8566, 2.3522, now))),
+            Some(&location("FR", 49.4431, 1.0993)),
+            now,
+            &settings,
+        );
+        assert!(signals.is_empty());
+    }
+
+    #[test]
+    fn test_assessment_risk() {
+        let mut assessment = RiskAssessment {
+            device_hash: String::new(),
+            device: "Chrome on Windows (Desktop)".to_string(),
+            location: Some(location("FR", 48.8566, 2.3522)),
+            signals: vec![RiskSignal::NewDevice],
+        };
+        assert!(assessment.should_alert());
+        assert!(!assessment.is_risky());
+
+        assessment.signals.push(RiskSignal::ImpossibleTravel);
+        assert!(assessment.is_risky());
+        assert_eq!(
+            assessment
+                .sign_in_details("203.0.113.9")
+                .location
+                .as_deref(),
+            Some("FR")
+        );
+    }
+}
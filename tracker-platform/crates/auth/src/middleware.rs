@@ -2,9 +2,14 @@
 //!
 //! This module provides middleware to protect routes, extract authenticated
 //! users from JWT tokens, and enforce permission-based access control.
+//!
+//! When an [`AuthState`] has a [`PermissionResolver`], a user's permissions
+//! are looked up on every request instead of being taken from the token,
+//! so class changes and overrides apply without a fresh login.
 
+use crate::classes::PermissionResolver;
 use crate::jwt::{Claims, JwtManager, TokenRevocationList};
-use crate::permissions::Permission;
+use crate::permissions::{Permission, PermissionSet};
 use axum::{
     async_trait,
     extract::{FromRef, FromRequestParts},
@@ -25,6 +30,8 @@ use uuid::Uuid;
 pub struct AuthState {
     pub jwt_manager: Arc<JwtManager>,
     pub revocation_list: Arc<TokenRevocationList>,
+    /// Resolves current permissions; without it the token's are used
+    pub permission_resolver: Option<Arc<PermissionResolver>>,
 }
 
 impl AuthState {
@@ -32,14 +39,22 @@ impl AuthState {
         Self {
             jwt_manager: Arc::new(jwt_manager),
             revocation_list: Arc::new(revocation_list),
+            permission_resolver: None,
         }
     }
+
+    /// Resolve users' permissions from their class and overrides
+    pub fn with_permission_resolver(mut self, resolver: Arc<PermissionResolver>) -> Self {
+        self.permission_resolver = Some(resolver);
+        self
+    }
 }
 
 /// Authenticated user extracted from JWT token
 #[derive(Debug, Clone)]
 pub struct AuthUser {
     pub user_id: Uuid,
+    /// Effective permissions, with site admin expanded and denials removed
     pub permissions: Vec<Permission>,
     pub token_id: Uuid,
 }
@@ -87,6 +102,10 @@ impl AuthError {
     pub fn forbidden() -> Self {
         Self::new("forbidden", "Insufficient permissions")
     }
+
+    pub fn internal() -> Self {
+        Self::new("internal_error", "Failed to load permissions")
+    }
 }
 
 impl IntoResponse for AuthError {
@@ -160,9 +179,17 @@ where
             return Err(AuthError::invalid_token());
         }
 
+        let permissions = match &auth_state.permission_resolver {
+            Some(resolver) => resolver.resolve(claims.user_id()).await.map_err(|e| {
+                tracing::error!("Failed to resolve permissions for {}: {}", claims.user_id(), e);
+                AuthError::internal()
+            })?,
+            None => PermissionSet::from_vec(claims.permissions),
+        };
+
         Ok(AuthUser {
             user_id: claims.user_id(),
-            permissions: claims.permissions,
+            permissions: permissions.effective(),
             token_id: claims.token_id(),
         })
     }
@@ -205,6 +232,21 @@ impl RequirePermission {
             require_all: true,
         }
     }
+
+    /// Check a user against the requirement
+    pub fn check(&self, user: &AuthUser) -> Result<(), AuthError> {
+        let allowed = if self.require_all {
+            user.has_all_permissions(&self.required_permissions)
+        } else {
+            user.has_any_permission(&self.required_permissions)
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(AuthError::forbidden())
+        }
+    }
 }
 
 /// User with permission requirements checked
@@ -340,6 +382,25 @@ mod tests {
         assert_eq!(err.error, "forbidden");
     }
 
+    #[test]
+    fn test_require_permission_check() {
+        let user = AuthUser {
+            user_id: Uuid::new_v4(),
+            permissions: vec![Permission::Download, Permission::UploadTorrent],
+            token_id: Uuid::new_v4(),
+        };
+
+        assert!(RequirePermission::new(vec![Permission::Download, Permission::BanUsers])
+            .check(&user)
+            .is_ok());
+        assert!(RequirePermission::all(vec![Permission::Download, Permission::BanUsers])
+            .check(&user)
+            .is_err());
+        assert!(RequirePermission::all(vec![Permission::Download, Permission::UploadTorrent])
+            .check(&user)
+            .is_ok());
+    }
+
     #[test]
     fn test_permission_user_deref() {
         let auth_user = AuthUser {
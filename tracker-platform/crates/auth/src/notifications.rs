@@ -0,0 +1,159 @@
+//! Security notifications
+//!
+//! Account security events (such as a stolen refresh token being replayed)
+//! are recorded per user so the site can alert them on their next visit.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Errors that can occur while recording or reading notifications
+#[derive(Debug, Error)]
+pub enum NotificationError {
+    #[error("Notification not found")]
+    NotFound,
+
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+impl From<sqlx::Error> for NotificationError {
+    fn from(e: sqlx::Error) -> Self {
+        NotificationError::Database(e.to_string())
+    }
+}
+
+/// Kind of security event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityEvent {
+    /// A refresh token was used twice; the session it belonged to was revoked
+    RefreshTokenReuse,
+    /// The user said a sign-in wasn't them; every session was revoked
+    SignInReported,
+}
+
+impl SecurityEvent {
+    /// Database representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecurityEvent::RefreshTokenReuse => "refresh_token_reuse",
+            SecurityEvent::SignInReported => "sign_in_reported",
+        }
+    }
+
+    /// Message shown to the user
+    pub fn message(&self) -> &'static str {
+        match self {
+            SecurityEvent::RefreshTokenReuse => {
+                "A sign-in token for one of your sessions was used twice, which usually means \
+                 it was copied. That session has been signed out; if this wasn't you, change \
+                 your password."
+            }
+            SecurityEvent::SignInReported => {
+                "You reported a sign-in to your account that wasn't you, so every session has \
+                 been signed out. Change your password now, and turn on two-factor \
+                 authentication if you haven't already."
+            }
+        }
+    }
+}
+
+/// A security notification shown to the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityNotification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Event kind (see [`SecurityEvent::as_str`])
+    pub event: String,
+    pub message: String,
+    /// IP address the event came from, if known
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// Records and lists security notifications
+#[derive(Clone)]
+pub struct SecurityNotifier {
+    db_pool: PgPool,
+}
+
+impl SecurityNotifier {
+    /// Create a new notifier
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// Record a security event for a user
+    pub async fn notify(
+        &self,
+        user_id: Uuid,
+        event: SecurityEvent,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Result<Uuid, NotificationError> {
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO security_notifications (user_id, event, message, ip_address, user_agent)
+            VALUES ($1, $2, $3, $4::text::inet, $5)
+            RETURNING id
+            "#,
+            user_id,
+            event.as_str(),
+            event.message(),
+            ip_address,
+            user_agent
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Get a user's unread notifications, newest first
+    pub async fn unread(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<SecurityNotification>, NotificationError> {
+        let notifications = sqlx::query_as!(
+            SecurityNotification,
+            r#"
+            SELECT id, user_id, event, message, host(ip_address) AS "ip_address?",
+                   user_agent, created_at, read_at
+            FROM security_notifications
+            WHERE user_id = $1 AND read_at IS NULL
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(notifications)
+    }
+
+    /// Mark a notification as read
+    pub async fn mark_read(&self, user_id: Uuid, id: Uuid) -> Result<(), NotificationError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE security_notifications
+            SET read_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND read_at IS NULL
+            "#,
+            id,
+            user_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(NotificationError::NotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1788 @@
+//! OAuth 2.1 / OpenID Connect provider
+//!
+//! Lets sister sites (wiki, IRC bouncer, stats site) sign members in with
+//! their tracker account. Only the authorization code flow with PKCE (S256)
+//! is supported, as OAuth 2.1 requires; there is no implicit flow, no
+//! password grant and no refresh tokens. Clients re-run the authorization
+//! flow when their access token expires, which skips the consent screen once
+//! the user has approved the requested scopes.
+//!
+//! Clients are registered by site admins with a fixed set of redirect URIs
+//! and the scopes they may ask for. Besides the OpenID Connect scopes
+//! (`openid`, `profile`, `email`), every [`Permission`] is a scope of the
+//! same name; a client only receives the permission scopes the user actually
+//! holds.
+//!
+//! Access tokens and ID tokens are JWTs signed with the same rotating keys as
+//! first-party tokens, so clients verify them against the published JWKS.
+//! Their issuer is the provider's URL, which first-party validation rejects.
+
+use crate::classes::PermissionResolver;
+use crate::keys::{KeyError, KeyRing};
+use crate::permissions::{Permission, PermissionSet};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, decode_header, encode, Validation};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+use url::Url;
+use uuid::Uuid;
+use validator::Validate;
+
+/// `typ` header of access tokens (RFC 9068)
+const ACCESS_TOKEN_TYP: &str = "at+jwt";
+
+/// Most redirect URIs a client may register
+const MAX_REDIRECT_URIS: usize = 10;
+
+/// Errors returned by the OAuth provider
+///
+/// [`OAuthError::error_code`] gives the code sent to clients.
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    #[error("{0}")]
+    InvalidRequest(String),
+
+    #[error("Client authentication failed")]
+    InvalidClient,
+
+    #[error("{0}")]
+    InvalidGrant(String),
+
+    #[error("Only the authorization_code grant is supported")]
+    UnsupportedGrantType,
+
+    #[error("Only the code response type is supported")]
+    UnsupportedResponseType,
+
+    #[error("{0}")]
+    InvalidScope(String),
+
+    #[error("The user denied the request")]
+    AccessDenied,
+
+    #[error("The access token is invalid or has expired")]
+    InvalidToken,
+
+    /// Unknown client or unregistered redirect URI; the user must not be
+    /// redirected anywhere
+    #[error("Unknown client or redirect URI")]
+    InvalidRedirect,
+
+    #[error("Client not found")]
+    ClientNotFound,
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Redis error: {0}")]
+    Redis(String),
+
+    #[error("Signing error: {0}")]
+    Signing(String),
+}
+
+impl OAuthError {
+    /// Error code from RFC 6749 / RFC 6750
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_)
+            | OAuthError::InvalidRedirect
+            | OAuthError::ValidationError(_) => "invalid_request",
+            OAuthError::InvalidClient | OAuthError::ClientNotFound => "invalid_client",
+            OAuthError::InvalidGrant(_) => "invalid_grant",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::UnsupportedResponseType => "unsupported_response_type",
+            OAuthError::InvalidScope(_) => "invalid_scope",
+            OAuthError::AccessDenied => "access_denied",
+            OAuthError::InvalidToken => "invalid_token",
+            OAuthError::Database(_) | OAuthError::Redis(_) | OAuthError::Signing(_) => {
+                "server_error"
+            }
+        }
+    }
+
+    /// Error body for the token and userinfo endpoints
+    pub fn to_body(&self) -> OAuthErrorBody {
+        let description = match self {
+            // Don't leak internals to clients
+            OAuthError::Database(_) | OAuthError::Redis(_) | OAuthError::Signing(_) => {
+                "Internal server error".to_string()
+            }
+            _ => self.to_string(),
+        };
+
+        OAuthErrorBody {
+            error: self.error_code().to_string(),
+            error_description: description,
+        }
+    }
+}
+
+impl From<sqlx::Error> for OAuthError {
+    fn from(e: sqlx::Error) -> Self {
+        OAuthError::Database(e.to_string())
+    }
+}
+
+impl From<KeyError> for OAuthError {
+    fn from(e: KeyError) -> Self {
+        OAuthError::Signing(e.to_string())
+    }
+}
+
+/// Error response body (RFC 6749 section 5.2)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthErrorBody {
+    pub error: String,
+    pub error_description: String,
+}
+
+/// Provider settings
+#[derive(Debug, Clone)]
+pub struct OAuthConfig {
+    /// Issuer URL, e.g. `https://tracker.example.com`
+    pub issuer: String,
+    /// Lifetime of access and ID tokens
+    pub access_token_ttl: Duration,
+    /// Lifetime of authorization codes
+    pub code_ttl: Duration,
+}
+
+impl OAuthConfig {
+    /// Create a config with default lifetimes (1 hour tokens, 60 second codes)
+    pub fn new(issuer: impl Into<String>) -> Self {
+        Self {
+            issuer: issuer.into().trim_end_matches('/').to_string(),
+            access_token_ttl: Duration::hours(1),
+            code_ttl: Duration::seconds(60),
+        }
+    }
+
+    /// Provider metadata served at `/.well-known/openid-configuration`
+    pub fn metadata(&self) -> ProviderMetadata {
+        let mut scopes = vec![
+            OAuthScope::OpenId.to_string(),
+            OAuthScope::Profile.to_string(),
+            OAuthScope::Email.to_string(),
+        ];
+        scopes.extend(Permission::all().iter().map(|p| p.as_str().to_string()));
+
+        ProviderMetadata {
+            issuer: self.issuer.clone(),
+            authorization_endpoint: format!("{}/oauth/authorize", self.issuer),
+            token_endpoint: format!("{}/oauth/token", self.issuer),
+            userinfo_endpoint: format!("{}/oauth/userinfo", self.issuer),
+            jwks_uri: format!("{}/.well-known/jwks.json", self.issuer),
+            scopes_supported: scopes,
+            response_types_supported: vec!["code".to_string()],
+            response_modes_supported: vec!["query".to_string()],
+            grant_types_supported: vec!["authorization_code".to_string()],
+            subject_types_supported: vec!["public".to_string()],
+            id_token_signing_alg_values_supported: vec!["EdDSA".to_string(), "RS256".to_string()],
+            token_endpoint_auth_methods_supported: vec![
+                "client_secret_basic".to_string(),
+                "client_secret_post".to_string(),
+                "none".to_string(),
+            ],
+            code_challenge_methods_supported: vec!["S256".to_string()],
+            claims_supported: [
+                "iss",
+                "sub",
+                "aud",
+                "exp",
+                "iat",
+                "auth_time",
+                "nonce",
+                "preferred_username",
+                "email",
+                "email_verified",
+                "user_class",
+            ]
+            .iter()
+            .map(|c| c.to_string())
+            .collect(),
+            authorization_response_iss_parameter_supported: true,
+        }
+    }
+}
+
+/// OpenID Connect discovery document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: Vec<String>,
+    pub response_modes_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+    pub authorization_response_iss_parameter_supported: bool,
+}
+
+/// A scope a client can request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OAuthScope {
+    /// Sign-in; an ID token is issued
+    OpenId,
+    /// Username and user class in the ID token and userinfo
+    Profile,
+    /// Email address
+    Email,
+    /// Act with one of the user's permissions
+    Permission(Permission),
+}
+
+impl OAuthScope {
+    /// Parse a scope token
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "openid" => Some(OAuthScope::OpenId),
+            "profile" => Some(OAuthScope::Profile),
+            "email" => Some(OAuthScope::Email),
+            _ => Permission::parse(s).map(OAuthScope::Permission),
+        }
+    }
+
+    /// Scope token
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OAuthScope::OpenId => "openid",
+            OAuthScope::Profile => "profile",
+            OAuthScope::Email => "email",
+            OAuthScope::Permission(permission) => permission.as_str(),
+        }
+    }
+
+    /// Text shown on the consent screen
+    pub fn description(&self) -> &'static str {
+        match self {
+            OAuthScope::OpenId => "Sign you in with your tracker account",
+            OAuthScope::Profile => "See your username and user class",
+            OAuthScope::Email => "See your email address",
+            OAuthScope::Permission(permission) => permission.description(),
+        }
+    }
+}
+
+impl fmt::Display for OAuthScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parse a space-delimited scope parameter, dropping duplicates
+pub fn parse_scope(scope: &str) -> Result<Vec<OAuthScope>, OAuthError> {
+    let mut scopes = Vec::new();
+    for token in scope.split(' ').filter(|s| !s.is_empty()) {
+        let parsed = OAuthScope::parse(token)
+            .ok_or_else(|| OAuthError::InvalidScope(format!("Unknown scope: {}", token)))?;
+        if !scopes.contains(&parsed) {
+            scopes.push(parsed);
+        }
+    }
+    Ok(scopes)
+}
+
+/// Format scopes as a space-delimited scope parameter
+pub fn format_scope(scopes: &[OAuthScope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether `value` is a valid PKCE verifier or S256 challenge (RFC 7636)
+fn is_pkce_value(value: &str) -> bool {
+    (43..=128).contains(&value.len())
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'))
+}
+
+/// S256 code challenge for a PKCE verifier
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Check a PKCE verifier against the S256 challenge it was committed to
+pub fn verify_pkce(verifier: &str, challenge: &str) -> bool {
+    is_pkce_value(verifier) && pkce_challenge(verifier) == challenge
+}
+
+/// Check a redirect URI a client wants to register
+///
+/// HTTPS is required, except for loopback addresses used by local tools.
+/// Fragments are not allowed (RFC 6749 section 3.1.2).
+pub fn validate_redirect_uri(uri: &str) -> Result<(), OAuthError> {
+    let invalid = |reason: &str| OAuthError::ValidationError(format!("{}: {}", reason, uri));
+    let url = Url::parse(uri).map_err(|_| invalid("Invalid redirect URI"))?;
+
+    if url.fragment().is_some() {
+        return Err(invalid("Redirect URI must not contain a fragment"));
+    }
+
+    let loopback = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    match url.scheme() {
+        "https" => Ok(()),
+        "http" if loopback => Ok(()),
+        _ => Err(invalid("Redirect URI must use https")),
+    }
+}
+
+/// Generate a random URL-safe secret
+fn random_secret(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(&mut buf);
+    URL_SAFE_NO_PAD.encode(buf)
+}
+
+/// Hash a secret for storage and lookup
+fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// A registered client application
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthClient {
+    pub id: Uuid,
+    pub client_id: String,
+    pub name: String,
+    /// Exact redirect URIs the client may use
+    pub redirect_uris: Vec<String>,
+    /// Scopes the client may request
+    pub allowed_scopes: Vec<String>,
+    /// Confidential clients authenticate with a secret at the token
+    /// endpoint; public clients (apps, SPAs) rely on PKCE alone
+    pub confidential: bool,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    client_secret_hash: Option<String>,
+}
+
+impl OAuthClient {
+    /// Whether `uri` exactly matches a registered redirect URI
+    pub fn has_redirect_uri(&self, uri: &str) -> bool {
+        self.redirect_uris
+            .iter()
+            .any(|registered| registered == uri)
+    }
+
+    /// Whether the client may request `scope`
+    pub fn allows_scope(&self, scope: OAuthScope) -> bool {
+        self.allowed_scopes.iter().any(|s| s == scope.as_str())
+    }
+
+    /// Authenticate the client at the token endpoint
+    pub fn authenticate(&self, secret: Option<&str>) -> Result<(), OAuthError> {
+        if self.revoked_at.is_some() {
+            return Err(OAuthError::InvalidClient);
+        }
+
+        match (&self.client_secret_hash, secret) {
+            (None, _) => Ok(()),
+            (Some(expected), Some(secret)) if hash_secret(secret) == *expected => Ok(()),
+            (Some(_), _) => Err(OAuthError::InvalidClient),
+        }
+    }
+}
+
+/// Request to register a client
+#[derive(Debug, Clone, Validate, Deserialize)]
+pub struct RegisterClientRequest {
+    #[validate(length(min = 1, max = 100, message = "Name must be 1-100 characters"))]
+    pub name: String,
+
+    pub redirect_uris: Vec<String>,
+
+    /// Scopes the client may request
+    pub allowed_scopes: Vec<String>,
+
+    /// Issue a client secret (for server-side clients)
+    #[serde(default = "default_confidential")]
+    pub confidential: bool,
+}
+
+fn default_confidential() -> bool {
+    true
+}
+
+/// A newly registered client; the secret is only ever returned here
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisteredClient {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(flatten)]
+    pub client: OAuthClient,
+}
+
+/// Authorization request parameters (RFC 6749 section 4.1.1, RFC 7636)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub code_challenge: Option<String>,
+    #[serde(default)]
+    pub code_challenge_method: Option<String>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    /// `consent` forces the consent screen even if already approved
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+impl AuthorizationRequest {
+    /// Validate the request against the client's registration
+    ///
+    /// The client and redirect URI must already have been checked; errors
+    /// returned here are sent back to the client's redirect URI.
+    pub fn validate(&self, client: &OAuthClient) -> Result<Vec<OAuthScope>, OAuthError> {
+        if self.response_type != "code" {
+            return Err(OAuthError::UnsupportedResponseType);
+        }
+
+        let challenge = self.code_challenge.as_deref().ok_or_else(|| {
+            OAuthError::InvalidRequest("code_challenge is required (PKCE)".to_string())
+        })?;
+        // RFC 7636 defaults to "plain", which OAuth 2.1 servers should not accept
+        if self.code_challenge_method.as_deref() != Some("S256") {
+            return Err(OAuthError::InvalidRequest(
+                "code_challenge_method must be S256".to_string(),
+            ));
+        }
+        if challenge.len() != 43 || !is_pkce_value(challenge) {
+            return Err(OAuthError::InvalidRequest(
+                "code_challenge is malformed".to_string(),
+            ));
+        }
+
+        if self.nonce.as_ref().is_some_and(|nonce| nonce.len() > 255) {
+            return Err(OAuthError::InvalidRequest("nonce is too long".to_string()));
+        }
+
+        let scopes = parse_scope(self.scope.as_deref().unwrap_or(""))?;
+        if scopes.is_empty() {
+            return Err(OAuthError::InvalidScope("scope is required".to_string()));
+        }
+        if let Some(scope) = scopes.iter().find(|scope| !client.allows_scope(**scope)) {
+            return Err(OAuthError::InvalidScope(format!(
+                "Client may not request the {} scope",
+                scope
+            )));
+        }
+
+        Ok(scopes)
+    }
+}
+
+/// Build a redirect back to the client with the given query parameters
+fn redirect_to(redirect_uri: &str, params: &[(&str, &str)]) -> String {
+    match Url::parse(redirect_uri) {
+        Ok(mut url) => {
+            url.query_pairs_mut().extend_pairs(params);
+            url.to_string()
+        }
+        // Registered URIs are validated, so this cannot happen in practice
+        Err(_) => redirect_uri.to_string(),
+    }
+}
+
+/// Redirect carrying an authorization code
+pub fn code_redirect(
+    config: &OAuthConfig,
+    redirect_uri: &str,
+    code: &str,
+    state: Option<&str>,
+) -> String {
+    let mut params = vec![("code", code)];
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
+    params.push(("iss", &config.issuer));
+    redirect_to(redirect_uri, &params)
+}
+
+/// Redirect carrying an error
+pub fn error_redirect(
+    config: &OAuthConfig,
+    redirect_uri: &str,
+    error: &OAuthError,
+    state: Option<&str>,
+) -> String {
+    let body = error.to_body();
+    let mut params = vec![
+        ("error", body.error.as_str()),
+        ("error_description", body.error_description.as_str()),
+    ];
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
+    params.push(("iss", &config.issuer));
+    redirect_to(redirect_uri, &params)
+}
+
+/// What was approved, stored against an authorization code until redeemed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorizationGrant {
+    pub client_id: String,
+    pub user_id: Uuid,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub code_challenge: String,
+    pub nonce: Option<String>,
+    /// When the user approved the request (Unix seconds)
+    pub auth_time: i64,
+}
+
+impl AuthorizationGrant {
+    /// Check a token request against the grant (RFC 6749 section 4.1.3)
+    pub fn redeem(
+        &self,
+        client_id: &str,
+        redirect_uri: Option<&str>,
+        code_verifier: Option<&str>,
+    ) -> Result<(), OAuthError> {
+        if self.client_id != client_id {
+            return Err(OAuthError::InvalidGrant(
+                "Code was issued to another client".to_string(),
+            ));
+        }
+        if redirect_uri != Some(self.redirect_uri.as_str()) {
+            return Err(OAuthError::InvalidGrant(
+                "redirect_uri does not match the authorization request".to_string(),
+            ));
+        }
+
+        let verifier = code_verifier
+            .ok_or_else(|| OAuthError::InvalidRequest("code_verifier is required".to_string()))?;
+        if !verify_pkce(verifier, &self.code_challenge) {
+            return Err(OAuthError::InvalidGrant(
+                "code_verifier does not match code_challenge".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Token request parameters, sent form-encoded
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub redirect_uri: Option<String>,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub code_verifier: Option<String>,
+}
+
+/// Successful token response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub scope: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+/// Claims of an access token (RFC 9068)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    /// The provider itself; access tokens are used at its userinfo endpoint
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub jti: String,
+    pub client_id: String,
+    pub scope: String,
+}
+
+impl AccessTokenClaims {
+    /// Scopes granted to the token
+    pub fn scopes(&self) -> Vec<OAuthScope> {
+        self.scope
+            .split(' ')
+            .filter_map(OAuthScope::parse)
+            .collect()
+    }
+}
+
+/// Claims of an ID token (OpenID Connect Core section 2)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub auth_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    /// The member's user class, e.g. `power_user`
+    pub user_class: String,
+}
+
+/// User details the provider hands out
+#[derive(Debug, Clone)]
+pub struct OAuthUser {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub email_verified: bool,
+    /// Name of the user's class in `user_groups`
+    pub user_class: String,
+    /// Effective permissions, from the user's class and overrides
+    pub permissions: PermissionSet,
+}
+
+impl OAuthUser {
+    /// Drop permission scopes the user doesn't hold
+    pub fn grantable_scopes(&self, scopes: &[OAuthScope]) -> Vec<OAuthScope> {
+        scopes
+            .iter()
+            .copied()
+            .filter(|scope| match scope {
+                OAuthScope::Permission(permission) => self.permissions.has(*permission),
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+/// Userinfo response (OpenID Connect Core section 5.3)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    /// Permission scopes the token carries
+    pub permissions: Vec<String>,
+}
+
+impl UserInfo {
+    /// Claims about `user` that `scopes` allow
+    pub fn new(user: &OAuthUser, scopes: &[OAuthScope]) -> Self {
+        let profile = scopes.contains(&OAuthScope::Profile);
+        let email = scopes.contains(&OAuthScope::Email);
+
+        Self {
+            sub: user.id.to_string(),
+            preferred_username: profile.then(|| user.username.clone()),
+            user_class: profile.then(|| user.user_class.clone()),
+            email: email.then(|| user.email.clone()),
+            email_verified: email.then_some(user.email_verified),
+            permissions: user
+                .grantable_scopes(scopes)
+                .iter()
+                .filter(|scope| matches!(scope, OAuthScope::Permission(_)))
+                .map(|scope| scope.as_str().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Sign the access token and, for `openid` requests, the ID token
+pub fn issue_tokens(
+    keys: &KeyRing,
+    config: &OAuthConfig,
+    grant: &AuthorizationGrant,
+    user: &OAuthUser,
+    now: DateTime<Utc>,
+) -> Result<TokenResponse, OAuthError> {
+    let key = keys
+        .signing_key(now)
+        .ok_or_else(|| OAuthError::Signing("No active signing key".to_string()))?;
+    let encoding_key = key
+        .encoding_key()
+        .ok_or_else(|| OAuthError::Signing("Signing key has no private half".to_string()))?;
+
+    // The user may have lost permissions since approving
+    let requested = parse_scope(&grant.scope)?;
+    let scopes = user.grantable_scopes(&requested);
+    let scope = format_scope(&scopes);
+    let exp = (now + config.access_token_ttl).timestamp();
+
+    let access_claims = AccessTokenClaims {
+        iss: config.issuer.clone(),
+        sub: user.id.to_string(),
+        aud: config.issuer.clone(),
+        exp,
+        iat: now.timestamp(),
+        jti: Uuid::new_v4().to_string(),
+        client_id: grant.client_id.clone(),
+        scope: scope.clone(),
+    };
+    let mut access_header = key.header();
+    access_header.typ = Some(ACCESS_TOKEN_TYP.to_string());
+    let access_token = encode(&access_header, &access_claims, encoding_key)
+        .map_err(|e| OAuthError::Signing(e.to_string()))?;
+
+    let id_token = if scopes.contains(&OAuthScope::OpenId) {
+        let info = UserInfo::new(user, &scopes);
+        let id_claims = IdTokenClaims {
+            iss: config.issuer.clone(),
+            sub: info.sub,
+            aud: grant.client_id.clone(),
+            exp,
+            iat: now.timestamp(),
+            auth_time: grant.auth_time,
+            nonce: grant.nonce.clone(),
+            preferred_username: info.preferred_username,
+            email: info.email,
+            email_verified: info.email_verified,
+            user_class: user.user_class.clone(),
+        };
+        Some(
+            encode(&key.header(), &id_claims, encoding_key)
+                .map_err(|e| OAuthError::Signing(e.to_string()))?,
+        )
+    } else {
+        None
+    };
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: config.access_token_ttl.num_seconds(),
+        scope,
+        id_token,
+    })
+}
+
+/// Verify an access token issued by [`issue_tokens`]
+pub fn verify_access_token(
+    keys: &KeyRing,
+    config: &OAuthConfig,
+    token: &str,
+    now: DateTime<Utc>,
+) -> Result<AccessTokenClaims, OAuthError> {
+    let header = decode_header(token).map_err(|_| OAuthError::InvalidToken)?;
+    // Keep ID tokens and first-party JWTs out
+    if header.typ.as_deref() != Some(ACCESS_TOKEN_TYP) {
+        return Err(OAuthError::InvalidToken);
+    }
+
+    let kid = header.kid.ok_or(OAuthError::InvalidToken)?;
+    let key = keys
+        .verification_key(&kid, now)
+        .ok_or(OAuthError::InvalidToken)?;
+
+    let mut validation = Validation::new(key.algorithm.jwt_algorithm());
+    validation.set_issuer(&[&config.issuer]);
+    validation.set_audience(&[&config.issuer]);
+    // Expiry is checked against `now` below
+    validation.validate_exp = false;
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<AccessTokenClaims>(token, key.decoding_key(), &validation)
+        .map_err(|_| OAuthError::InvalidToken)?
+        .claims;
+
+    if claims.exp <= now.timestamp() {
+        return Err(OAuthError::InvalidToken);
+    }
+
+    Ok(claims)
+}
+
+/// Outcome of an authorization request
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthorizeOutcome {
+    /// Ask the user to approve the request
+    Consent(ConsentPrompt),
+    /// Send the user back to the client (with a code or an error)
+    Redirect { redirect_to: String },
+}
+
+/// What the consent screen shows
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsentPrompt {
+    pub client_id: String,
+    pub client_name: String,
+    pub redirect_uri: String,
+    pub scopes: Vec<ConsentScope>,
+}
+
+/// A scope on the consent screen
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsentScope {
+    pub scope: String,
+    pub description: String,
+}
+
+/// Database row for a client
+struct OAuthClientRow {
+    id: Uuid,
+    client_id: String,
+    name: String,
+    client_secret_hash: Option<String>,
+    redirect_uris: Vec<String>,
+    allowed_scopes: Vec<String>,
+    created_by: Uuid,
+    created_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<OAuthClientRow> for OAuthClient {
+    fn from(row: OAuthClientRow) -> Self {
+        Self {
+            id: row.id,
+            client_id: row.client_id,
+            name: row.name,
+            redirect_uris: row.redirect_uris,
+            allowed_scopes: row.allowed_scopes,
+            confidential: row.client_secret_hash.is_some(),
+            created_by: row.created_by,
+            created_at: row.created_at,
+            revoked_at: row.revoked_at,
+            client_secret_hash: row.client_secret_hash,
+        }
+    }
+}
+
+/// OAuth 2.1 / OpenID Connect provider
+///
+/// Clients and consents live in PostgreSQL; authorization codes live in
+/// Redis until redeemed or expired.
+pub struct OAuthProvider {
+    db_pool: PgPool,
+    redis_client: redis::Client,
+    keys: Arc<KeyRing>,
+    permissions: Arc<PermissionResolver>,
+    config: OAuthConfig,
+    key_prefix: String,
+}
+
+impl OAuthProvider {
+    /// Create a new provider
+    pub fn new(
+        db_pool: PgPool,
+        redis_client: redis::Client,
+        keys: Arc<KeyRing>,
+        permissions: Arc<PermissionResolver>,
+        config: OAuthConfig,
+    ) -> Self {
+        Self {
+            db_pool,
+            redis_client,
+            keys,
+            permissions,
+            config,
+            key_prefix: "auth:oauth".to_string(),
+        }
+    }
+
+    /// Provider settings
+    pub fn config(&self) -> &OAuthConfig {
+        &self.config
+    }
+
+    /// Register a client (admin only)
+    pub async fn register_client(
+        &self,
+        admin_id: Uuid,
+        request: RegisterClientRequest,
+    ) -> Result<RegisteredClient, OAuthError> {
+        request
+            .validate()
+            .map_err(|e| OAuthError::ValidationError(e.to_string()))?;
+
+        if request.redirect_uris.is_empty() || request.redirect_uris.len() > MAX_REDIRECT_URIS {
+            return Err(OAuthError::ValidationError(format!(
+                "Between 1 and {} redirect URIs are required",
+                MAX_REDIRECT_URIS
+            )));
+        }
+        for uri in &request.redirect_uris {
+            validate_redirect_uri(uri)?;
+        }
+
+        let scopes = parse_scope(&request.allowed_scopes.join(" "))?;
+        let allowed_scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_string()).collect();
+
+        let client_id = Uuid::new_v4().simple().to_string();
+        let client_secret = request.confidential.then(|| random_secret(32));
+
+        let row = sqlx::query_as!(
+            OAuthClientRow,
+            r#"
+            INSERT INTO oauth_clients
+                (client_id, name, client_secret_hash, redirect_uris, allowed_scopes, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, client_id, name, client_secret_hash, redirect_uris, allowed_scopes,
+                      created_by, created_at, revoked_at
+            "#,
+            client_id,
+            request.name,
+            client_secret.as_deref().map(hash_secret),
+            &request.redirect_uris,
+            &allowed_scopes,
+            admin_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        tracing::info!("Admin {} registered OAuth client {}", admin_id, client_id);
+
+        Ok(RegisteredClient {
+            client_secret,
+            client: row.into(),
+        })
+    }
+
+    /// List all clients
+    pub async fn list_clients(&self) -> Result<Vec<OAuthClient>, OAuthError> {
+        let rows = sqlx::query_as!(
+            OAuthClientRow,
+            r#"
+            SELECT id, client_id, name, client_secret_hash, redirect_uris, allowed_scopes,
+                   created_by, created_at, revoked_at
+            FROM oauth_clients
+            ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(OAuthClient::from).collect())
+    }
+
+    /// Revoke a client; its codes stop working and users' consents are dropped
+    pub async fn revoke_client(&self, client_id: &str) -> Result<(), OAuthError> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let result = sqlx::query!(
+            "UPDATE oauth_clients SET revoked_at = NOW() WHERE client_id = $1 AND revoked_at IS NULL",
+            client_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(OAuthError::ClientNotFound);
+        }
+
+        sqlx::query!("DELETE FROM oauth_consents WHERE client_id = $1", client_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Get an active client
+    pub async fn get_client(&self, client_id: &str) -> Result<OAuthClient, OAuthError> {
+        let row = sqlx::query_as!(
+            OAuthClientRow,
+            r#"
+            SELECT id, client_id, name, client_secret_hash, redirect_uris, allowed_scopes,
+                   created_by, created_at, revoked_at
+            FROM oauth_clients
+            WHERE client_id = $1 AND revoked_at IS NULL
+            "#,
+            client_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(OAuthError::ClientNotFound)?;
+
+        Ok(row.into())
+    }
+
+    /// Handle an authorization request from a signed-in user
+    ///
+    /// Returns the consent prompt, or a redirect if the user has already
+    /// approved these scopes for the client. Errors are only returned when
+    /// the client or redirect URI can't be trusted.
+    pub async fn authorize(
+        &self,
+        user_id: Uuid,
+        request: &AuthorizationRequest,
+    ) -> Result<AuthorizeOutcome, OAuthError> {
+        let client = self.trusted_client(request).await?;
+        let scopes = match request.validate(&client) {
+            Ok(scopes) => scopes,
+            Err(e) => return Ok(self.error_outcome(request, &e)),
+        };
+
+        let force_consent = request.prompt.as_deref() == Some("consent");
+        if !force_consent
+            && self
+                .has_consent(user_id, &client.client_id, &scopes)
+                .await?
+        {
+            return self.approve(user_id, request, &scopes).await;
+        }
+
+        Ok(AuthorizeOutcome::Consent(ConsentPrompt {
+            client_id: client.client_id,
+            client_name: client.name,
+            redirect_uri: request.redirect_uri.clone(),
+            scopes: scopes
+                .iter()
+                .map(|scope| ConsentScope {
+                    scope: scope.as_str().to_string(),
+                    description: scope.description().to_string(),
+                })
+                .collect(),
+        }))
+    }
+
+    /// Record the user's answer on the consent screen
+    pub async fn decide(
+        &self,
+        user_id: Uuid,
+        request: &AuthorizationRequest,
+        approved: bool,
+    ) -> Result<AuthorizeOutcome, OAuthError> {
+        let client = self.trusted_client(request).await?;
+        let scopes = match request.validate(&client) {
+            Ok(scopes) => scopes,
+            Err(e) => return Ok(self.error_outcome(request, &e)),
+        };
+
+        if !approved {
+            return Ok(self.error_outcome(request, &OAuthError::AccessDenied));
+        }
+
+        let scope_names: Vec<String> = scopes.iter().map(|s| s.as_str().to_string()).collect();
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_consents (user_id, client_id, scopes)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, client_id) DO UPDATE
+            SET scopes = ARRAY(SELECT DISTINCT unnest(oauth_consents.scopes || EXCLUDED.scopes)),
+                granted_at = NOW()
+            "#,
+            user_id,
+            client.client_id,
+            &scope_names
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        self.approve(user_id, request, &scopes).await
+    }
+
+    /// Exchange an authorization code for tokens
+    ///
+    /// `basic_auth` is the client ID and secret from an HTTP Basic header.
+    pub async fn exchange_code(
+        &self,
+        request: TokenRequest,
+        basic_auth: Option<(String, String)>,
+    ) -> Result<TokenResponse, OAuthError> {
+        if request.grant_type != "authorization_code" {
+            return Err(OAuthError::UnsupportedGrantType);
+        }
+
+        let (client_id, secret) = match basic_auth {
+            Some((id, secret)) => (id, Some(secret)),
+            None => (
+                request.client_id.clone().ok_or(OAuthError::InvalidClient)?,
+                request.client_secret.clone(),
+            ),
+        };
+        let client = self.get_client(&client_id).await.map_err(|e| match e {
+            OAuthError::ClientNotFound => OAuthError::InvalidClient,
+            e => e,
+        })?;
+        client.authenticate(secret.as_deref())?;
+
+        let code = request
+            .code
+            .as_deref()
+            .ok_or_else(|| OAuthError::InvalidRequest("code is required".to_string()))?;
+        let grant = self
+            .take_grant(code)
+            .await?
+            .ok_or_else(|| OAuthError::InvalidGrant("Invalid or expired code".to_string()))?;
+        grant.redeem(
+            &client.client_id,
+            request.redirect_uri.as_deref(),
+            request.code_verifier.as_deref(),
+        )?;
+
+        let user = self
+            .get_user(grant.user_id)
+            .await?
+            .ok_or_else(|| OAuthError::InvalidGrant("User is disabled".to_string()))?;
+
+        issue_tokens(&self.keys, &self.config, &grant, &user, Utc::now())
+    }
+
+    /// Claims for the userinfo endpoint
+    pub async fn userinfo(&self, access_token: &str) -> Result<UserInfo, OAuthError> {
+        let claims = verify_access_token(&self.keys, &self.config, access_token, Utc::now())?;
+        let user_id = claims
+            .sub
+            .parse::<Uuid>()
+            .map_err(|_| OAuthError::InvalidToken)?;
+
+        let user = self
+            .get_user(user_id)
+            .await?
+            .ok_or(OAuthError::InvalidToken)?;
+
+        Ok(UserInfo::new(&user, &claims.scopes()))
+    }
+
+    /// Look up the client and check the redirect URI; failures here must
+    /// not redirect
+    async fn trusted_client(
+        &self,
+        request: &AuthorizationRequest,
+    ) -> Result<OAuthClient, OAuthError> {
+        let client = self
+            .get_client(&request.client_id)
+            .await
+            .map_err(|e| match e {
+                OAuthError::ClientNotFound => OAuthError::InvalidRedirect,
+                e => e,
+            })?;
+
+        if !client.has_redirect_uri(&request.redirect_uri) {
+            return Err(OAuthError::InvalidRedirect);
+        }
+
+        Ok(client)
+    }
+
+    fn error_outcome(
+        &self,
+        request: &AuthorizationRequest,
+        error: &OAuthError,
+    ) -> AuthorizeOutcome {
+        AuthorizeOutcome::Redirect {
+            redirect_to: error_redirect(
+                &self.config,
+                &request.redirect_uri,
+                error,
+                request.state.as_deref(),
+            ),
+        }
+    }
+
+    /// Issue a code and redirect back to the client
+    async fn approve(
+        &self,
+        user_id: Uuid,
+        request: &AuthorizationRequest,
+        scopes: &[OAuthScope],
+    ) -> Result<AuthorizeOutcome, OAuthError> {
+        let grant = AuthorizationGrant {
+            client_id: request.client_id.clone(),
+            user_id,
+            redirect_uri: request.redirect_uri.clone(),
+            scope: format_scope(scopes),
+            // Checked by `validate`
+            code_challenge: request.code_challenge.clone().unwrap_or_default(),
+            nonce: request.nonce.clone(),
+            auth_time: Utc::now().timestamp(),
+        };
+
+        let code = self.put_grant(&grant).await?;
+
+        Ok(AuthorizeOutcome::Redirect {
+            redirect_to: code_redirect(
+                &self.config,
+                &request.redirect_uri,
+                &code,
+                request.state.as_deref(),
+            ),
+        })
+    }
+
+    /// Whether the user already approved `scopes` for the client
+    async fn has_consent(
+        &self,
+        user_id: Uuid,
+        client_id: &str,
+        scopes: &[OAuthScope],
+    ) -> Result<bool, OAuthError> {
+        let granted = sqlx::query_scalar!(
+            "SELECT scopes FROM oauth_consents WHERE user_id = $1 AND client_id = $2",
+            user_id,
+            client_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(granted.is_some_and(|granted| {
+            scopes
+                .iter()
+                .all(|scope| granted.iter().any(|s| s == scope.as_str()))
+        }))
+    }
+
+    fn code_key(&self, code: &str) -> String {
+        format!("{}:code:{}", self.key_prefix, hash_secret(code))
+    }
+
+    /// Store a grant under a new authorization code
+    async fn put_grant(&self, grant: &AuthorizationGrant) -> Result<String, OAuthError> {
+        let mut conn = self
+            .redis_client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| OAuthError::Redis(e.to_string()))?;
+
+        let code = random_secret(32);
+        let data = serde_json::to_string(grant).map_err(|e| OAuthError::Redis(e.to_string()))?;
+
+        redis::cmd("SET")
+            .arg(self.code_key(&code))
+            .arg(data)
+            .arg("EX")
+            .arg(self.config.code_ttl.num_seconds())
+            .query_async::<_, ()>(&mut conn)
+            .await
+            .map_err(|e| OAuthError::Redis(e.to_string()))?;
+
+        Ok(code)
+    }
+
+    /// Take the grant for a code; each code can only be redeemed once
+    async fn take_grant(&self, code: &str) -> Result<Option<AuthorizationGrant>, OAuthError> {
+        let mut conn = self
+            .redis_client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| OAuthError::Redis(e.to_string()))?;
+
+        let data: Option<String> = redis::cmd("GETDEL")
+            .arg(self.code_key(code))
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| OAuthError::Redis(e.to_string()))?;
+
+        data.map(|data| serde_json::from_str(&data).map_err(|e| OAuthError::Redis(e.to_string())))
+            .transpose()
+    }
+
+    /// Load an active user
+    async fn get_user(&self, user_id: Uuid) -> Result<Option<OAuthUser>, OAuthError> {
+        let user = sqlx::query!(
+            r#"
+            SELECT u.id, u.username, u.email,
+                   u.email_verified_at IS NOT NULL AS "email_verified!",
+                   g.name AS user_class
+            FROM users u
+            JOIN user_groups g ON g.id = u.group_id
+            WHERE u.id = $1
+              AND u.is_active = true
+              AND u.is_banned = false
+              AND u.deleted_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        let Some(u) = user else {
+            return Ok(None);
+        };
+
+        let permissions = self
+            .permissions
+            .resolve(u.id)
+            .await
+            .map_err(|e| OAuthError::Database(e.to_string()))?;
+
+        Ok(Some(OAuthUser {
+            id: u.id,
+            username: u.username,
+            email: u.email,
+            email_verified: u.email_verified,
+            user_class: u.user_class,
+            permissions,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! Conformance tests: a local relying party runs the authorization code
+    //! flow against the provider logic and checks the results the way an
+    //! external OpenID Connect client would, using only the discovery
+    //! document and the published JWKS.
+
+    use super::*;
+    use crate::keys::{JwkSet, JwtKey};
+    use crate::permissions::Role;
+    use jsonwebtoken::DecodingKey;
+
+    const ISSUER: &str = "https://tracker.example.com";
+    const REDIRECT_URI: &str = "https://wiki.example.com/oauth/callback";
+
+    /// A relying party, e.g. the wiki
+    struct LocalClient {
+        client_id: String,
+        verifier: String,
+        state: String,
+        nonce: String,
+    }
+
+    impl LocalClient {
+        fn new(client_id: &str) -> Self {
+            Self {
+                client_id: client_id.to_string(),
+                verifier: random_secret(48),
+                state: random_secret(16),
+                nonce: random_secret(16),
+            }
+        }
+
+        fn authorization_request(&self, scope: &str) -> AuthorizationRequest {
+            AuthorizationRequest {
+                response_type: "code".to_string(),
+                client_id: self.client_id.clone(),
+                redirect_uri: REDIRECT_URI.to_string(),
+                scope: Some(scope.to_string()),
+                state: Some(self.state.clone()),
+                code_challenge: Some(pkce_challenge(&self.verifier)),
+                code_challenge_method: Some("S256".to_string()),
+                nonce: Some(self.nonce.clone()),
+                prompt: None,
+            }
+        }
+
+        /// Parse the redirect the provider sent the browser to
+        fn callback(&self, redirect_to: &str) -> Vec<(String, String)> {
+            let url = Url::parse(redirect_to).unwrap();
+            assert_eq!(
+                format!(
+                    "{}://{}{}",
+                    url.scheme(),
+                    url.host_str().unwrap(),
+                    url.path()
+                ),
+                REDIRECT_URI
+            );
+            url.query_pairs().into_owned().collect()
+        }
+
+        /// Verify an ID token using only the published JWKS
+        fn verify_id_token(
+            &self,
+            metadata: &ProviderMetadata,
+            jwks_json: &str,
+            id_token: &str,
+        ) -> IdTokenClaims {
+            let jwks: JwkSet = serde_json::from_str(jwks_json).unwrap();
+            let header = decode_header(id_token).unwrap();
+            assert!(metadata
+                .id_token_signing_alg_values_supported
+                .contains(&format!("{:?}", header.alg)));
+
+            let kid = header.kid.unwrap();
+            let jwk = jwks.keys.iter().find(|k| k.kid == kid).unwrap();
+            let jwk: jsonwebtoken::jwk::Jwk =
+                serde_json::from_value(serde_json::to_value(jwk).unwrap()).unwrap();
+
+            let mut validation = Validation::new(header.alg);
+            validation.set_issuer(&[&metadata.issuer]);
+            validation.set_audience(&[&self.client_id]);
+            let claims = decode::<IdTokenClaims>(
+                id_token,
+                &DecodingKey::from_jwk(&jwk).unwrap(),
+                &validation,
+            )
+            .unwrap()
+            .claims;
+
+            assert_eq!(claims.nonce.as_deref(), Some(self.nonce.as_str()));
+            claims
+        }
+    }
+
+    fn client(confidential: bool) -> (OAuthClient, Option<String>) {
+        let secret = confidential.then(|| random_secret(32));
+        let client = OAuthClient {
+            id: Uuid::new_v4(),
+            client_id: Uuid::new_v4().simple().to_string(),
+            name: "Wiki".to_string(),
+            redirect_uris: vec![REDIRECT_URI.to_string()],
+            allowed_scopes: ["openid", "profile", "email", "download", "site_admin"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            confidential,
+            created_by: Uuid::new_v4(),
+            created_at: Utc::now(),
+            revoked_at: None,
+            client_secret_hash: secret.as_deref().map(hash_secret),
+        };
+        (client, secret)
+    }
+
+    fn user() -> OAuthUser {
+        OAuthUser {
+            id: Uuid::new_v4(),
+            username: "seedbox_sam".to_string(),
+            email: "sam@example.com".to_string(),
+            email_verified: true,
+            user_class: "power_user".to_string(),
+            permissions: Role::PowerUser.permissions(),
+        }
+    }
+
+    fn key_ring(now: DateTime<Utc>) -> KeyRing {
+        KeyRing::new(vec![JwtKey::generate(now - Duration::hours(1)).unwrap()])
+    }
+
+    /// Run the provider side of an approved request
+    fn approve(
+        config: &OAuthConfig,
+        request: &AuthorizationRequest,
+        client: &OAuthClient,
+        user: &OAuthUser,
+    ) -> (String, AuthorizationGrant) {
+        assert!(client.has_redirect_uri(&request.redirect_uri));
+        let scopes = request.validate(client).unwrap();
+        let grant = AuthorizationGrant {
+            client_id: client.client_id.clone(),
+            user_id: user.id,
+            redirect_uri: request.redirect_uri.clone(),
+            scope: format_scope(&scopes),
+            code_challenge: request.code_challenge.clone().unwrap(),
+            nonce: request.nonce.clone(),
+            auth_time: Utc::now().timestamp(),
+        };
+        let code = random_secret(32);
+        let redirect = code_redirect(
+            config,
+            &request.redirect_uri,
+            &code,
+            request.state.as_deref(),
+        );
+        (redirect, grant)
+    }
+
+    #[test]
+    fn test_authorization_code_flow() {
+        let now = Utc::now();
+        let config = OAuthConfig::new(format!("{}/", ISSUER));
+        let metadata = config.metadata();
+        let keys = key_ring(now);
+        let jwks_json = serde_json::to_string(&keys.jwks(now)).unwrap();
+        let (client, secret) = client(true);
+        let user = user();
+        let rp = LocalClient::new(&client.client_id);
+
+        // Authorization request → redirect with code, state and iss
+        let request = rp.authorization_request("openid profile email download site_admin");
+        let (redirect, grant) = approve(&config, &request, &client, &user);
+        let params = rp.callback(&redirect);
+        let get = |name: &str| {
+            params
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+        };
+        assert!(get("code").is_some());
+        assert_eq!(get("state"), Some(rp.state.clone()));
+        assert_eq!(get("iss"), Some(metadata.issuer.clone()));
+
+        // Token request with client authentication and the PKCE verifier
+        client.authenticate(secret.as_deref()).unwrap();
+        grant
+            .redeem(&rp.client_id, Some(REDIRECT_URI), Some(&rp.verifier))
+            .unwrap();
+        let response = issue_tokens(&keys, &config, &grant, &user, now).unwrap();
+        assert_eq!(response.token_type, "Bearer");
+        assert_eq!(response.expires_in, 3600);
+        // Power users don't hold site_admin, so it is dropped
+        assert_eq!(response.scope, "openid profile email download");
+
+        // ID token verified with nothing but the JWKS
+        let id_token = response.id_token.as_deref().unwrap();
+        let claims = rp.verify_id_token(&metadata, &jwks_json, id_token);
+        assert_eq!(claims.iss, ISSUER);
+        assert_eq!(claims.sub, user.id.to_string());
+        assert_eq!(claims.aud, rp.client_id);
+        assert_eq!(claims.user_class, "power_user");
+        assert_eq!(claims.preferred_username.as_deref(), Some("seedbox_sam"));
+        assert_eq!(claims.email.as_deref(), Some("sam@example.com"));
+        assert_eq!(claims.email_verified, Some(true));
+
+        // Access token works at userinfo
+        let access = verify_access_token(&keys, &config, &response.access_token, now).unwrap();
+        assert_eq!(access.client_id, rp.client_id);
+        let info = UserInfo::new(&user, &access.scopes());
+        assert_eq!(info.sub, claims.sub);
+        assert_eq!(info.permissions, vec!["download".to_string()]);
+    }
+
+    #[test]
+    fn test_scopes_limit_claims() {
+        let now = Utc::now();
+        let config = OAuthConfig::new(ISSUER);
+        let keys = key_ring(now);
+        let (client, _) = client(false);
+        let user = user();
+        let rp = LocalClient::new(&client.client_id);
+
+        let (_, grant) = approve(&config, &rp.authorization_request("openid"), &client, &user);
+        let response = issue_tokens(&keys, &config, &grant, &user, now).unwrap();
+        let jwks_json = serde_json::to_string(&keys.jwks(now)).unwrap();
+        let claims =
+            rp.verify_id_token(&config.metadata(), &jwks_json, &response.id_token.unwrap());
+
+        assert_eq!(claims.user_class, "power_user");
+        assert!(claims.preferred_username.is_none());
+        assert!(claims.email.is_none());
+
+        // Plain OAuth requests get no ID token
+        let (_, grant) = approve(
+            &config,
+            &rp.authorization_request("download"),
+            &client,
+            &user,
+        );
+        let response = issue_tokens(&keys, &config, &grant, &user, now).unwrap();
+        assert!(response.id_token.is_none());
+    }
+
+    #[test]
+    fn test_pkce_is_required() {
+        let (client, _) = client(false);
+        let rp = LocalClient::new(&client.client_id);
+
+        let mut request = rp.authorization_request("openid");
+        request.code_challenge = None;
+        assert_eq!(
+            request.validate(&client).unwrap_err().error_code(),
+            "invalid_request"
+        );
+
+        let mut request = rp.authorization_request("openid");
+        request.code_challenge_method = Some("plain".to_string());
+        request.code_challenge = Some(rp.verifier.clone());
+        assert_eq!(
+            request.validate(&client).unwrap_err().error_code(),
+            "invalid_request"
+        );
+
+        let mut request = rp.authorization_request("openid");
+        request.code_challenge_method = None;
+        assert_eq!(
+            request.validate(&client).unwrap_err().error_code(),
+            "invalid_request"
+        );
+    }
+
+    #[test]
+    fn test_authorization_request_errors() {
+        let (client, _) = client(false);
+        let rp = LocalClient::new(&client.client_id);
+
+        let mut request = rp.authorization_request("openid");
+        request.response_type = "token".to_string();
+        assert_eq!(
+            request.validate(&client).unwrap_err().error_code(),
+            "unsupported_response_type"
+        );
+
+        let request = rp.authorization_request("openid ban_users");
+        assert_eq!(
+            request.validate(&client).unwrap_err().error_code(),
+            "invalid_scope"
+        );
+
+        let request = rp.authorization_request("openid offline_access");
+        assert_eq!(
+            request.validate(&client).unwrap_err().error_code(),
+            "invalid_scope"
+        );
+
+        let request = rp.authorization_request("");
+        assert_eq!(
+            request.validate(&client).unwrap_err().error_code(),
+            "invalid_scope"
+        );
+
+        // Redirect URIs must match exactly
+        assert!(!client.has_redirect_uri("https://wiki.example.com/oauth/callback/"));
+        assert!(!client.has_redirect_uri("https://wiki.example.com/oauth/callback?x=1"));
+        assert!(!client.has_redirect_uri("https://evil.example.com/oauth/callback"));
+    }
+
+    #[test]
+    fn test_error_redirect() {
+        let config = OAuthConfig::new(ISSUER);
+        let redirect = error_redirect(
+            &config,
+            "https://irc.example.com/cb?existing=1",
+            &OAuthError::AccessDenied,
+            Some("xyz"),
+        );
+        let url = Url::parse(&redirect).unwrap();
+        let params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+
+        assert!(params.contains(&("existing".to_string(), "1".to_string())));
+        assert!(params.contains(&("error".to_string(), "access_denied".to_string())));
+        assert!(params.contains(&("state".to_string(), "xyz".to_string())));
+        assert!(params.contains(&("iss".to_string(), ISSUER.to_string())));
+    }
+
+    #[test]
+    fn test_code_redemption_checks() {
+        let config = OAuthConfig::new(ISSUER);
+        let (client, _) = client(false);
+        let user = user();
+        let rp = LocalClient::new(&client.client_id);
+        let (_, grant) = approve(&config, &rp.authorization_request("openid"), &client, &user);
+
+        let wrong_verifier = random_secret(48);
+        let err = grant
+            .redeem(&rp.client_id, Some(REDIRECT_URI), Some(&wrong_verifier))
+            .unwrap_err();
+        assert_eq!(err.error_code(), "invalid_grant");
+
+        let err = grant
+            .redeem(&rp.client_id, Some(REDIRECT_URI), None)
+            .unwrap_err();
+        assert_eq!(err.error_code(), "invalid_request");
+
+        let err = grant
+            .redeem(
+                &rp.client_id,
+                Some("https://wiki.example.com/other"),
+                Some(&rp.verifier),
+            )
+            .unwrap_err();
+        assert_eq!(err.error_code(), "invalid_grant");
+
+        let err = grant
+            .redeem("another-client", Some(REDIRECT_URI), Some(&rp.verifier))
+            .unwrap_err();
+        assert_eq!(err.error_code(), "invalid_grant");
+    }
+
+    #[test]
+    fn test_client_authentication() {
+        let (confidential, secret) = client(true);
+        assert!(confidential.authenticate(secret.as_deref()).is_ok());
+        assert!(confidential.authenticate(Some("wrong")).is_err());
+        assert!(confidential.authenticate(None).is_err());
+
+        let (public, _) = client(false);
+        assert!(public.authenticate(None).is_ok());
+
+        let mut revoked = public.clone();
+        revoked.revoked_at = Some(Utc::now());
+        assert_eq!(
+            revoked.authenticate(None).unwrap_err().error_code(),
+            "invalid_client"
+        );
+    }
+
+    #[test]
+    fn test_access_token_verification() {
+        let now = Utc::now();
+        let config = OAuthConfig::new(ISSUER);
+        let keys = key_ring(now);
+        let (client, _) = client(false);
+        let user = user();
+        let rp = LocalClient::new(&client.client_id);
+        let (_, grant) = approve(&config, &rp.authorization_request("openid"), &client, &user);
+        let response = issue_tokens(&keys, &config, &grant, &user, now).unwrap();
+
+        // ID tokens can't be used as access tokens
+        let id_token = response.id_token.unwrap();
+        assert!(verify_access_token(&keys, &config, &id_token, now).is_err());
+
+        // Tokens from another issuer are rejected
+        let other = OAuthConfig::new("https://other.example.com");
+        assert!(verify_access_token(&keys, &other, &response.access_token, now).is_err());
+
+        // Expired tokens are rejected
+        let later = now + Duration::hours(2);
+        assert!(verify_access_token(&keys, &config, &response.access_token, later).is_err());
+    }
+
+    #[test]
+    fn test_redirect_uri_registration() {
+        assert!(validate_redirect_uri("https://stats.example.com/callback").is_ok());
+        assert!(validate_redirect_uri("http://127.0.0.1:8400/callback").is_ok());
+        assert!(validate_redirect_uri("http://localhost/callback").is_ok());
+        assert!(validate_redirect_uri("http://stats.example.com/callback").is_err());
+        assert!(validate_redirect_uri("https://stats.example.com/cb#frag").is_err());
+        assert!(validate_redirect_uri("not a url").is_err());
+    }
+
+    #[test]
+    fn test_discovery_metadata() {
+        let metadata = OAuthConfig::new(ISSUER).metadata();
+
+        assert_eq!(metadata.issuer, ISSUER);
+        assert_eq!(
+            metadata.jwks_uri,
+            format!("{}/.well-known/jwks.json", ISSUER)
+        );
+        assert_eq!(metadata.response_types_supported, vec!["code"]);
+        assert_eq!(metadata.code_challenge_methods_supported, vec!["S256"]);
+        assert!(metadata.scopes_supported.contains(&"openid".to_string()));
+        assert!(metadata
+            .scopes_supported
+            .contains(&"upload_torrent".to_string()));
+    }
+
+    #[test]
+    fn test_pkce_challenge_rfc7636_example() {
+        // RFC 7636 appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+        assert_eq!(pkce_challenge(verifier), challenge);
+        assert!(verify_pkce(verifier, challenge));
+        assert!(!verify_pkce("short", &pkce_challenge("short")));
+    }
+}
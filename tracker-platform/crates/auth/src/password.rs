@@ -24,6 +24,9 @@ pub enum PasswordError {
 
     #[error("Password is too weak: {0}")]
     WeakPassword(String),
+
+    #[error("Password has appeared {0} times in data breaches; choose a different one")]
+    Breached(u64),
 }
 
 /// Password strength requirements
@@ -155,6 +155,46 @@ impl Permission {
             Permission::SendInvites => "Access to invite system",
         }
     }
+
+    /// The permission's identifier, as serialized in tokens
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::SiteAdmin => "site_admin",
+            Permission::SiteLogs => "site_logs",
+            Permission::SiteSettings => "site_settings",
+            Permission::ManageUsers => "manage_users",
+            Permission::ViewUserSessions => "view_user_sessions",
+            Permission::ForumModerator => "forum_moderator",
+            Permission::DeleteForumPosts => "delete_forum_posts",
+            Permission::EditForumPosts => "edit_forum_posts",
+            Permission::TorrentModerator => "torrent_moderator",
+            Permission::DeleteTorrents => "delete_torrents",
+            Permission::EditTorrents => "edit_torrents",
+            Permission::BanUsers => "ban_users",
+            Permission::WarnUsers => "warn_users",
+            Permission::ViewUserIPs => "view_user_i_ps",
+            Permission::ManageInvites => "manage_invites",
+            Permission::ManagePermissions => "manage_permissions",
+            Permission::UploadTorrent => "upload_torrent",
+            Permission::CreateForumPost => "create_forum_post",
+            Permission::CreateRequest => "create_request",
+            Permission::VoteRequest => "vote_request",
+            Permission::FillRequest => "fill_request",
+            Permission::ApiAccess => "api_access",
+            Permission::ViewStatistics => "view_statistics",
+            Permission::Download => "download",
+            Permission::AutoDownload => "auto_download",
+            Permission::BypassUploadWait => "bypass_upload_wait",
+            Permission::ImmunityAutomated => "immunity_automated",
+            Permission::Donor => "donor",
+            Permission::SendInvites => "send_invites",
+        }
+    }
+
+    /// Parse a permission identifier
+    pub fn parse(s: &str) -> Option<Permission> {
+        Self::all().into_iter().find(|permission| permission.as_str() == s)
+    }
 }
 
 impl fmt::Display for Permission {
@@ -195,23 +235,26 @@ impl fmt::Display for Permission {
 }
 
 /// A set of permissions for a user
+///
+/// Denied permissions win over everything, including [`Permission::SiteAdmin`].
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PermissionSet {
     permissions: HashSet<Permission>,
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    denied: HashSet<Permission>,
 }
 
 impl PermissionSet {
     /// Create a new empty permission set
     pub fn new() -> Self {
-        Self {
-            permissions: HashSet::new(),
-        }
+        Self::default()
     }
 
     /// Create a permission set from a vector of permissions
     pub fn from_vec(permissions: Vec<Permission>) -> Self {
         Self {
             permissions: permissions.into_iter().collect(),
+            denied: HashSet::new(),
         }
     }
 
@@ -225,8 +268,17 @@ impl PermissionSet {
         self.permissions.remove(&permission);
     }
 
+    /// Deny a permission, even if the set would otherwise grant it
+    pub fn deny(&mut self, permission: Permission) {
+        self.permissions.remove(&permission);
+        self.denied.insert(permission);
+    }
+
     /// Check if the set contains a specific permission
     pub fn has(&self, permission: Permission) -> bool {
+        if self.denied.contains(&permission) {
+            return false;
+        }
         // Site admins have all permissions
         if self.permissions.contains(&Permission::SiteAdmin) {
             return true;
@@ -236,20 +288,12 @@ impl PermissionSet {
 
     /// Check if the set contains any of the specified permissions
     pub fn has_any(&self, permissions: &[Permission]) -> bool {
-        // Site admins have all permissions
-        if self.permissions.contains(&Permission::SiteAdmin) {
-            return true;
-        }
-        permissions.iter().any(|p| self.permissions.contains(p))
+        permissions.iter().any(|p| self.has(*p))
     }
 
     /// Check if the set contains all of the specified permissions
     pub fn has_all(&self, permissions: &[Permission]) -> bool {
-        // Site admins have all permissions
-        if self.permissions.contains(&Permission::SiteAdmin) {
-            return true;
-        }
-        permissions.iter().all(|p| self.permissions.contains(p))
+        permissions.iter().all(|p| self.has(*p))
     }
 
     /// Get all permissions in the set
@@ -257,6 +301,20 @@ impl PermissionSet {
         self.permissions.iter().copied().collect()
     }
 
+    /// Every permission [`PermissionSet::has`] grants, with site admin
+    /// expanded, for callers that check membership directly
+    pub fn effective(&self) -> Vec<Permission> {
+        Permission::all()
+            .into_iter()
+            .filter(|permission| self.has(*permission))
+            .collect()
+    }
+
+    /// Permissions explicitly denied
+    pub fn denied(&self) -> Vec<Permission> {
+        self.denied.iter().copied().collect()
+    }
+
     /// Check if the permission set is empty
     pub fn is_empty(&self) -> bool {
         self.permissions.is_empty()
@@ -268,7 +326,11 @@ impl PermissionSet {
     }
 }
 
-/// Predefined user roles with standard permission sets
+/// Built-in user roles with standard permission sets
+///
+/// User classes and their permissions now live in the database (see
+/// [`crate::classes`]); these are the defaults the class migration seeds
+/// from and are handy in tests.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Role {
@@ -355,6 +417,32 @@ impl Role {
             Role::Disabled => "Disabled",
         }
     }
+
+    /// The role's identifier as stored in `users.role`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Moderator => "moderator",
+            Role::PowerUser => "power_user",
+            Role::User => "user",
+            Role::NewUser => "new_user",
+            Role::Disabled => "disabled",
+        }
+    }
+
+    /// Parse a role identifier as stored in `users.role`
+    pub fn parse(s: &str) -> Option<Role> {
+        [
+            Role::Admin,
+            Role::Moderator,
+            Role::PowerUser,
+            Role::User,
+            Role::NewUser,
+            Role::Disabled,
+        ]
+        .into_iter()
+        .find(|role| role.as_str() == s)
+    }
 }
 
 impl fmt::Display for Role {
@@ -412,6 +500,27 @@ mod tests {
         assert!(!perms.has_all(&[Permission::Download, Permission::BanUsers]));
     }
 
+    #[test]
+    fn test_denied_permissions_win() {
+        let mut perms = Role::Admin.permissions();
+        perms.deny(Permission::ViewUserIPs);
+
+        assert!(perms.has(Permission::BanUsers));
+        assert!(!perms.has(Permission::ViewUserIPs));
+        assert!(!perms.has_all(&[Permission::BanUsers, Permission::ViewUserIPs]));
+        assert!(perms.has_any(&[Permission::BanUsers, Permission::ViewUserIPs]));
+
+        let effective = perms.effective();
+        assert!(effective.contains(&Permission::SiteAdmin));
+        assert!(effective.contains(&Permission::Download));
+        assert!(!effective.contains(&Permission::ViewUserIPs));
+
+        // Denials survive the cache round trip
+        let json = serde_json::to_string(&perms).unwrap();
+        let restored: PermissionSet = serde_json::from_str(&json).unwrap();
+        assert!(!restored.has(Permission::ViewUserIPs));
+    }
+
     #[test]
     fn test_role_permissions() {
         let admin_perms = Role::Admin.permissions();
@@ -437,4 +546,29 @@ mod tests {
         assert_eq!(Role::Admin.name(), "Administrator");
         assert_eq!(Role::User.name(), "User");
     }
+
+    #[test]
+    fn test_role_as_str_matches_serde() {
+        for role in [Role::Admin, Role::Moderator, Role::PowerUser, Role::NewUser] {
+            let json = serde_json::to_value(role).unwrap();
+            assert_eq!(json, role.as_str());
+        }
+    }
+
+    #[test]
+    fn test_role_parse() {
+        assert_eq!(Role::parse("power_user"), Some(Role::PowerUser));
+        assert_eq!(Role::parse(Role::Disabled.as_str()), Some(Role::Disabled));
+        assert_eq!(Role::parse("root"), None);
+    }
+
+    #[test]
+    fn test_permission_as_str_matches_serde() {
+        for permission in Permission::all() {
+            let json = serde_json::to_value(permission).unwrap();
+            assert_eq!(json, permission.as_str());
+            assert_eq!(Permission::parse(permission.as_str()), Some(permission));
+        }
+        assert_eq!(Permission::parse("superuser"), None);
+    }
 }
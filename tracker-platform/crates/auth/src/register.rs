@@ -4,12 +4,15 @@
 //! password hashing, passkey generation for BitTorrent, and optional
 //! email verification.
 
+use crate::breach::BreachedPasswords;
+use crate::email::{templates::DEFAULT_LOCALE, EmailService};
 use crate::password::{hash_password, validate_password_strength, PasswordError};
 use crate::permissions::{PermissionSet, Role};
 use chrono::{DateTime, Utc};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
 use thiserror::Error;
 use uuid::Uuid;
 use validator::{Validate, ValidationError};
@@ -186,6 +189,8 @@ pub struct RegistrationService {
     db_pool: PgPool,
     require_email_verification: bool,
     require_invite: bool,
+    email: Option<Arc<EmailService>>,
+    breached: Option<Arc<BreachedPasswords>>,
 }
 
 impl RegistrationService {
@@ -199,9 +204,23 @@ impl RegistrationService {
             db_pool,
             require_email_verification,
             require_invite,
+            email: None,
+            breached: None,
         }
     }
 
+    /// Queue verification emails through `email` when verification is required
+    pub fn with_email(mut self, email: Arc<EmailService>) -> Self {
+        self.email = Some(email);
+        self
+    }
+
+    /// Reject passwords found in the breached password `corpus`
+    pub fn with_breach_corpus(mut self, corpus: Arc<BreachedPasswords>) -> Self {
+        self.breached = Some(corpus);
+        self
+    }
+
     /// Check if email is already registered
     pub async fn email_exists(&self, email: &str) -> Result<bool, RegistrationError> {
         let result = sqlx::query_scalar::<_, bool>(
@@ -269,6 +288,9 @@ impl RegistrationService {
             .map_err(|e| RegistrationError::ValidationFailed(e.to_string()))?;
 
         request.validate_password_strength()?;
+        if let Some(corpus) = &self.breached {
+            corpus.screen(&request.password).await?;
+        }
 
         // Check invite code if required
         if self.require_invite {
@@ -316,6 +338,25 @@ impl RegistrationService {
         .await
         .map_err(|e| RegistrationError::DatabaseError(e.to_string()))?;
 
+        if self.require_email_verification {
+            if let Some(email) = &self.email {
+                // The account exists either way; the user can ask for the
+                // link again, so a queueing failure doesn't fail registration
+                let token =
+                    self.generate_verification_token(new_user.id, new_user.email.clone());
+                if let Err(e) = email
+                    .send_verification(&new_user.username, DEFAULT_LOCALE, &token)
+                    .await
+                {
+                    tracing::warn!(
+                        "Failed to queue verification email for {}: {}",
+                        new_user.id,
+                        e
+                    );
+                }
+            }
+        }
+
         Ok(new_user)
     }
 
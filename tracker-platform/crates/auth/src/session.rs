@@ -3,6 +3,11 @@
 //! This module handles user sessions, storing them in Redis for fast access
 //! and supporting features like session listing, individual revocation, and
 //! logout from all devices.
+//!
+//! Each session is also a refresh token family. Refresh tokens are single
+//! use: the session records the one currently valid, every refresh replaces
+//! it, and a token that is presented a second time marks the family as
+//! compromised.
 
 use chrono::{DateTime, Utc};
 use redis::AsyncCommands;
@@ -37,6 +42,9 @@ pub struct Session {
     pub user_id: Uuid,
     /// JWT token ID associated with this session
     pub token_id: Uuid,
+    /// ID of the session's current refresh token
+    #[serde(default)]
+    pub refresh_token_id: Option<Uuid>,
     /// Session creation time
     pub created_at: DateTime<Utc>,
     /// Session last activity time
@@ -73,6 +81,7 @@ impl Session {
             session_id: Uuid::new_v4(),
             user_id,
             token_id,
+            refresh_token_id: None,
             created_at: now,
             last_activity: now,
             expires_at,
@@ -94,6 +103,18 @@ impl Session {
     pub fn is_expired(&self) -> bool {
         Utc::now() > self.expires_at
     }
+
+    /// Record a newly issued token pair, replacing the previous one
+    pub fn rotate_tokens(&mut self, access_token_id: Uuid, refresh_token_id: Uuid) {
+        self.token_id = access_token_id;
+        self.refresh_token_id = Some(refresh_token_id);
+        self.update_activity();
+    }
+
+    /// Whether `refresh_token_id` is the refresh token this session last issued
+    pub fn is_current_refresh_token(&self, refresh_token_id: Uuid) -> bool {
+        self.refresh_token_id == Some(refresh_token_id)
+    }
 }
 
 /// Session manager for Redis-backed session storage
@@ -124,6 +145,11 @@ impl SessionManager {
         format!("{}:{}", self.key_prefix, session_id)
     }
 
+    /// Generate Redis key marking a refresh token as used
+    fn used_refresh_token_key(&self, token_id: Uuid) -> String {
+        format!("{}:refresh_used:{}", self.key_prefix, token_id)
+    }
+
     /// Generate Redis key for user's session list
     fn user_sessions_key(&self, user_id: Uuid) -> String {
         format!("{}:user:{}:sessions", self.key_prefix, user_id)
@@ -275,6 +301,34 @@ impl SessionManager {
         Ok(count)
     }
 
+    /// Mark a refresh token as used
+    ///
+    /// Returns `false` if it was already used. The check is atomic, so of two
+    /// concurrent refreshes with the same token only one succeeds.
+    pub async fn claim_refresh_token(
+        &self,
+        token_id: Uuid,
+        ttl_seconds: i64,
+    ) -> Result<bool, SessionError> {
+        let mut conn = self
+            .redis_client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| SessionError::RedisConnection(e.to_string()))?;
+
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(self.used_refresh_token_key(token_id))
+            .arg(Utc::now().timestamp())
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_seconds.max(1))
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| SessionError::OperationFailed(e.to_string()))?;
+
+        Ok(claimed.is_some())
+    }
+
     /// Get session by token ID
     pub async fn get_session_by_token(&self, token_id: Uuid) -> Result<Session, SessionError> {
         let mut conn = self
@@ -411,6 +465,38 @@ mod tests {
         assert!(session.is_expired());
     }
 
+    #[test]
+    fn test_refresh_token_rotation() {
+        let mut session = Session::new(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            "127.0.0.1".to_string(),
+            None,
+            3600,
+        );
+        assert!(!session.is_current_refresh_token(Uuid::new_v4()));
+
+        let (first_access, first_refresh) = (Uuid::new_v4(), Uuid::new_v4());
+        session.rotate_tokens(first_access, first_refresh);
+        assert!(session.is_current_refresh_token(first_refresh));
+
+        let (second_access, second_refresh) = (Uuid::new_v4(), Uuid::new_v4());
+        session.rotate_tokens(second_access, second_refresh);
+        assert_eq!(session.token_id, second_access);
+        assert!(session.is_current_refresh_token(second_refresh));
+        assert!(!session.is_current_refresh_token(first_refresh));
+    }
+
+    #[test]
+    fn test_session_without_refresh_token_deserializes() {
+        let session = Session::new(Uuid::new_v4(), Uuid::new_v4(), "::1".to_string(), None, 60);
+        let mut json = serde_json::to_value(&session).unwrap();
+        json.as_object_mut().unwrap().remove("refresh_token_id");
+
+        let session: Session = serde_json::from_value(json).unwrap();
+        assert_eq!(session.refresh_token_id, None);
+    }
+
     #[test]
     fn test_parse_user_agent_chrome() {
         let ua = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
@@ -0,0 +1,1395 @@
+//! WebAuthn / FIDO2 security keys
+//!
+//! This module implements the WebAuthn registration and assertion ceremonies
+//! so users can sign in with hardware security keys and passkeys, either as a
+//! second factor after their password or on their own (passwordless login).
+//!
+//! Only ES256 (ECDSA P-256 with SHA-256) credentials are accepted; every
+//! FIDO2 authenticator supports it. We request `"none"` attestation and do not
+//! check attestation statements, so a key proves possession, not its make.
+//!
+//! `WebAuthnManager` holds the ceremony logic and has no I/O.
+//! `SecurityKeyService` stores credentials in PostgreSQL and keeps pending
+//! ceremony state in Redis between the start and finish calls.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use ciborium::value::Value;
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// COSE algorithm identifier for ES256
+const COSE_ALG_ES256: i64 = -7;
+
+/// Authenticator data flags
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// Maximum number of security keys a user can register
+pub const MAX_KEYS_PER_USER: usize = 10;
+
+/// Errors that can occur during WebAuthn operations
+#[derive(Debug, Error)]
+pub enum WebAuthnError {
+    #[error("Invalid client data: {0}")]
+    InvalidClientData(String),
+
+    #[error("Challenge does not match")]
+    ChallengeMismatch,
+
+    #[error("Origin not allowed: {0}")]
+    OriginNotAllowed(String),
+
+    #[error("Relying party ID does not match")]
+    RpIdMismatch,
+
+    #[error("User presence was not confirmed")]
+    UserNotPresent,
+
+    #[error("User verification is required")]
+    UserNotVerified,
+
+    #[error("Invalid authenticator data: {0}")]
+    InvalidAuthenticatorData(String),
+
+    #[error("Unsupported public key algorithm: {0}")]
+    UnsupportedAlgorithm(i64),
+
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    #[error("Signature counter did not increase, the authenticator may be cloned")]
+    CounterRegression,
+
+    #[error("Credential is not allowed for this ceremony")]
+    CredentialNotAllowed,
+
+    #[error("Credential is already registered")]
+    CredentialExists,
+
+    #[error("Security key not found")]
+    KeyNotFound,
+
+    #[error("Too many security keys registered (maximum {0})")]
+    TooManyKeys(usize),
+
+    #[error("Challenge expired or not found")]
+    ChallengeExpired,
+
+    #[error("Redis error: {0}")]
+    Redis(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+}
+
+/// Relying party configuration
+#[derive(Debug, Clone)]
+pub struct WebAuthnConfig {
+    /// Relying party ID, the site's registrable domain (e.g. "tracker.example")
+    pub rp_id: String,
+    /// Human-readable site name shown by the authenticator
+    pub rp_name: String,
+    /// Origins the browser may report (e.g. "https://tracker.example")
+    pub origins: Vec<String>,
+    /// Ceremony timeout hint for the browser, in milliseconds
+    pub timeout_ms: u32,
+    /// How long a started ceremony can be finished, in seconds
+    pub challenge_ttl_seconds: u64,
+}
+
+impl WebAuthnConfig {
+    /// Create a config for a single origin
+    pub fn new(
+        rp_id: impl Into<String>,
+        rp_name: impl Into<String>,
+        origin: impl Into<String>,
+    ) -> Self {
+        Self {
+            rp_id: rp_id.into(),
+            rp_name: rp_name.into(),
+            origins: vec![origin.into()],
+            timeout_ms: 60_000,
+            challenge_ttl_seconds: 300,
+        }
+    }
+}
+
+/// Base64url (no padding) encoding for binary fields, as used by the
+/// WebAuthn JSON serialization
+mod base64url {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        decode(&encoded).map_err(serde::de::Error::custom)
+    }
+
+    /// Decode, tolerating padding some clients add
+    pub fn decode(encoded: &str) -> Result<Vec<u8>, base64::DecodeError> {
+        URL_SAFE_NO_PAD.decode(encoded.trim_end_matches('='))
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            bytes: &Option<Vec<u8>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match bytes {
+                Some(bytes) => super::serialize(bytes, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Vec<u8>>, D::Error> {
+            match Option::<String>::deserialize(deserializer)? {
+                Some(encoded) if !encoded.is_empty() => {
+                    decode(&encoded).map(Some).map_err(serde::de::Error::custom)
+                }
+                _ => Ok(None),
+            }
+        }
+    }
+}
+
+/// Relying party entity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: String,
+}
+
+/// User entity the credential is created for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    /// User handle (the user's UUID bytes)
+    #[serde(with = "base64url")]
+    pub id: Vec<u8>,
+    pub name: String,
+    pub display_name: String,
+}
+
+/// Accepted credential algorithm
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialParameter {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub alg: i64,
+}
+
+/// Reference to an existing credential
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(with = "base64url")]
+    pub id: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transports: Vec<String>,
+}
+
+impl CredentialDescriptor {
+    fn from_key(key: &SecurityKey) -> Self {
+        Self {
+            kind: "public-key".to_string(),
+            id: key.credential_id.clone(),
+            transports: key.transports.clone(),
+        }
+    }
+}
+
+/// Authenticator requirements for registration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    /// "required" for passkeys, "discouraged" for second-factor keys
+    pub resident_key: String,
+    pub require_resident_key: bool,
+    /// "required" or "discouraged"
+    pub user_verification: String,
+}
+
+/// Options for `navigator.credentials.create()`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    #[serde(with = "base64url")]
+    pub challenge: Vec<u8>,
+    pub rp: RelyingParty,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameter>,
+    pub timeout: u32,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+    pub attestation: String,
+}
+
+/// Options for `navigator.credentials.get()`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    #[serde(with = "base64url")]
+    pub challenge: Vec<u8>,
+    pub timeout: u32,
+    pub rp_id: String,
+    /// Empty for passkey login, letting the authenticator pick the account
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: String,
+}
+
+/// A started ceremony: the options to hand to the browser and the ID to
+/// finish it with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebAuthnChallenge<T> {
+    pub challenge_id: Uuid,
+    pub options: T,
+}
+
+/// Authenticator response to a registration ceremony
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON", with = "base64url")]
+    pub client_data_json: Vec<u8>,
+    #[serde(rename = "attestationObject", with = "base64url")]
+    pub attestation_object: Vec<u8>,
+    #[serde(default)]
+    pub transports: Vec<String>,
+}
+
+/// New credential returned by `navigator.credentials.create()`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationCredential {
+    pub id: String,
+    #[serde(with = "base64url")]
+    pub raw_id: Vec<u8>,
+    pub response: AttestationResponse,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// Authenticator response to an assertion ceremony
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON", with = "base64url")]
+    pub client_data_json: Vec<u8>,
+    #[serde(rename = "authenticatorData", with = "base64url")]
+    pub authenticator_data: Vec<u8>,
+    #[serde(with = "base64url")]
+    pub signature: Vec<u8>,
+    #[serde(
+        rename = "userHandle",
+        default,
+        with = "base64url::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub user_handle: Option<Vec<u8>>,
+}
+
+/// Assertion returned by `navigator.credentials.get()`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssertionCredential {
+    pub id: String,
+    #[serde(with = "base64url")]
+    pub raw_id: Vec<u8>,
+    pub response: AssertionResponse,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// Server-side state of a registration ceremony
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrationState {
+    pub user_id: Uuid,
+    pub challenge: Vec<u8>,
+    /// Whether a discoverable, user-verified credential was requested
+    pub passkey: bool,
+    pub exclude_credentials: Vec<Vec<u8>>,
+}
+
+/// Server-side state of an assertion ceremony
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthenticationState {
+    /// User being authenticated, `None` for passkey login
+    pub user_id: Option<Uuid>,
+    pub challenge: Vec<u8>,
+    pub user_verification: bool,
+    pub allow_credentials: Vec<Vec<u8>>,
+}
+
+/// A registered security key or passkey
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityKey {
+    /// Key ID
+    pub id: Uuid,
+    /// Owner
+    pub user_id: Uuid,
+    /// User-chosen label
+    pub name: String,
+    /// Credential ID assigned by the authenticator
+    pub credential_id: Vec<u8>,
+    /// Public key as an uncompressed SEC1 P-256 point
+    pub public_key: Vec<u8>,
+    /// Last signature counter seen
+    pub sign_count: u32,
+    /// Transport hints (usb, nfc, ble, internal, hybrid)
+    pub transports: Vec<String>,
+    /// Discoverable and user-verified, usable for passwordless login
+    pub is_passkey: bool,
+    /// Registered at
+    pub created_at: DateTime<Utc>,
+    /// Last successful assertion
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Collected client data, as signed by the authenticator
+#[derive(Debug, Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+/// Parsed authenticator data
+#[derive(Debug)]
+struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    attested_credential: Option<AttestedCredential>,
+}
+
+/// Credential data included in registration responses
+#[derive(Debug)]
+struct AttestedCredential {
+    credential_id: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+impl AuthenticatorData {
+    fn parse(data: &[u8]) -> Result<Self, WebAuthnError> {
+        let invalid = |msg: &str| WebAuthnError::InvalidAuthenticatorData(msg.to_string());
+
+        if data.len() < 37 {
+            return Err(invalid("too short"));
+        }
+
+        let mut rp_id_hash = [0u8; 32];
+        rp_id_hash.copy_from_slice(&data[..32]);
+        let flags = data[32];
+        let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+        let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+            // AAGUID (16 bytes), credential ID length (2 bytes), credential ID, COSE key
+            let rest = &data[37..];
+            if rest.len() < 18 {
+                return Err(invalid("truncated attested credential data"));
+            }
+            let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+            let rest = &rest[18..];
+            if rest.len() < id_len {
+                return Err(invalid("truncated credential ID"));
+            }
+            let credential_id = rest[..id_len].to_vec();
+
+            let mut key_bytes = &rest[id_len..];
+            let cose_key: Value = ciborium::de::from_reader(&mut key_bytes)
+                .map_err(|e| WebAuthnError::InvalidPublicKey(e.to_string()))?;
+
+            Some(AttestedCredential {
+                credential_id,
+                public_key: cose_to_sec1(&cose_key)?,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            rp_id_hash,
+            flags,
+            sign_count,
+            attested_credential,
+        })
+    }
+
+    fn user_present(&self) -> bool {
+        self.flags & FLAG_USER_PRESENT != 0
+    }
+
+    fn user_verified(&self) -> bool {
+        self.flags & FLAG_USER_VERIFIED != 0
+    }
+}
+
+/// Convert a COSE EC2 P-256 key to an uncompressed SEC1 point
+fn cose_to_sec1(key: &Value) -> Result<Vec<u8>, WebAuthnError> {
+    let invalid = |msg: &str| WebAuthnError::InvalidPublicKey(msg.to_string());
+
+    let entries = key
+        .as_map()
+        .ok_or_else(|| invalid("COSE key is not a map"))?;
+    let get = |label: i128| {
+        entries.iter().find_map(|(k, v)| match k.as_integer() {
+            Some(k) if i128::from(k) == label => Some(v),
+            _ => None,
+        })
+    };
+    let int = |label: i128| {
+        get(label)
+            .and_then(Value::as_integer)
+            .and_then(|v| i64::try_from(i128::from(v)).ok())
+    };
+
+    let alg = int(3).ok_or_else(|| invalid("missing algorithm"))?;
+    if alg != COSE_ALG_ES256 {
+        return Err(WebAuthnError::UnsupportedAlgorithm(alg));
+    }
+    // kty 2 = EC2, crv 1 = P-256
+    if int(1) != Some(2) || int(-1) != Some(1) {
+        return Err(invalid("expected an EC2 P-256 key"));
+    }
+
+    let x = get(-2)
+        .and_then(Value::as_bytes)
+        .ok_or_else(|| invalid("missing x"))?;
+    let y = get(-3)
+        .and_then(Value::as_bytes)
+        .ok_or_else(|| invalid("missing y"))?;
+    if x.len() != 32 || y.len() != 32 {
+        return Err(invalid("bad coordinate length"));
+    }
+
+    let mut point = Vec::with_capacity(65);
+    point.push(0x04);
+    point.extend_from_slice(x);
+    point.extend_from_slice(y);
+
+    // Reject points that are not on the curve now rather than at login
+    VerifyingKey::from_sec1_bytes(&point).map_err(|e| invalid(&e.to_string()))?;
+
+    Ok(point)
+}
+
+/// WebAuthn ceremony logic for one relying party
+pub struct WebAuthnManager {
+    config: WebAuthnConfig,
+}
+
+impl WebAuthnManager {
+    /// Create a new WebAuthn manager
+    pub fn new(config: WebAuthnConfig) -> Self {
+        Self { config }
+    }
+
+    /// Relying party configuration
+    pub fn config(&self) -> &WebAuthnConfig {
+        &self.config
+    }
+
+    fn generate_challenge() -> Vec<u8> {
+        let mut challenge = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut challenge);
+        challenge
+    }
+
+    /// Start registering a new credential
+    ///
+    /// `existing` keys are excluded so the same authenticator is not
+    /// registered twice. With `passkey` set, a discoverable credential with
+    /// user verification is requested so it can be used without a password.
+    pub fn start_registration(
+        &self,
+        user_id: Uuid,
+        username: &str,
+        existing: &[SecurityKey],
+        passkey: bool,
+    ) -> (CreationOptions, RegistrationState) {
+        let challenge = Self::generate_challenge();
+        let requirement = if passkey { "required" } else { "discouraged" };
+
+        let options = CreationOptions {
+            challenge: challenge.clone(),
+            rp: RelyingParty {
+                id: self.config.rp_id.clone(),
+                name: self.config.rp_name.clone(),
+            },
+            user: UserEntity {
+                id: user_id.as_bytes().to_vec(),
+                name: username.to_string(),
+                display_name: username.to_string(),
+            },
+            pub_key_cred_params: vec![CredentialParameter {
+                kind: "public-key".to_string(),
+                alg: COSE_ALG_ES256,
+            }],
+            timeout: self.config.timeout_ms,
+            exclude_credentials: existing
+                .iter()
+                .map(CredentialDescriptor::from_key)
+                .collect(),
+            authenticator_selection: AuthenticatorSelection {
+                resident_key: requirement.to_string(),
+                require_resident_key: passkey,
+                user_verification: requirement.to_string(),
+            },
+            attestation: "none".to_string(),
+        };
+
+        let state = RegistrationState {
+            user_id,
+            challenge,
+            passkey,
+            exclude_credentials: existing.iter().map(|k| k.credential_id.clone()).collect(),
+        };
+
+        (options, state)
+    }
+
+    /// Verify a registration response and build the credential to store
+    pub fn finish_registration(
+        &self,
+        state: &RegistrationState,
+        credential: &RegistrationCredential,
+        name: &str,
+    ) -> Result<SecurityKey, WebAuthnError> {
+        self.verify_client_data(
+            &credential.response.client_data_json,
+            "webauthn.create",
+            &state.challenge,
+        )?;
+
+        let attestation: Value =
+            ciborium::de::from_reader(credential.response.attestation_object.as_slice())
+                .map_err(|e| WebAuthnError::InvalidAuthenticatorData(e.to_string()))?;
+        let auth_data = attestation
+            .as_map()
+            .and_then(|entries| {
+                entries
+                    .iter()
+                    .find(|(k, _)| k.as_text() == Some("authData"))
+                    .and_then(|(_, v)| v.as_bytes())
+            })
+            .ok_or_else(|| {
+                WebAuthnError::InvalidAuthenticatorData("missing authData".to_string())
+            })?;
+
+        let data = AuthenticatorData::parse(auth_data)?;
+        self.verify_authenticator_data(&data, state.passkey)?;
+        let user_verified = data.user_verified();
+
+        let attested = data.attested_credential.ok_or_else(|| {
+            WebAuthnError::InvalidAuthenticatorData("missing attested credential".to_string())
+        })?;
+
+        if attested.credential_id != credential.raw_id {
+            return Err(WebAuthnError::InvalidAuthenticatorData(
+                "credential ID does not match".to_string(),
+            ));
+        }
+        if state.exclude_credentials.contains(&attested.credential_id) {
+            return Err(WebAuthnError::CredentialExists);
+        }
+
+        Ok(SecurityKey {
+            id: Uuid::new_v4(),
+            user_id: state.user_id,
+            name: name.to_string(),
+            credential_id: attested.credential_id,
+            public_key: attested.public_key,
+            sign_count: data.sign_count,
+            transports: credential.response.transports.clone(),
+            is_passkey: state.passkey && user_verified,
+            created_at: Utc::now(),
+            last_used_at: None,
+        })
+    }
+
+    /// Start an assertion ceremony
+    ///
+    /// For second-factor use pass the user's keys; for passkey login pass
+    /// `None` and no keys, and the authenticator offers its discoverable
+    /// credentials for this site.
+    pub fn start_authentication(
+        &self,
+        user_id: Option<Uuid>,
+        keys: &[SecurityKey],
+    ) -> (RequestOptions, AuthenticationState) {
+        let challenge = Self::generate_challenge();
+        // Passwordless login must prove the user, not just their presence
+        let user_verification = user_id.is_none();
+
+        let options = RequestOptions {
+            challenge: challenge.clone(),
+            timeout: self.config.timeout_ms,
+            rp_id: self.config.rp_id.clone(),
+            allow_credentials: keys.iter().map(CredentialDescriptor::from_key).collect(),
+            user_verification: if user_verification {
+                "required"
+            } else {
+                "discouraged"
+            }
+            .to_string(),
+        };
+
+        let state = AuthenticationState {
+            user_id,
+            challenge,
+            user_verification,
+            allow_credentials: keys.iter().map(|k| k.credential_id.clone()).collect(),
+        };
+
+        (options, state)
+    }
+
+    /// Verify an assertion against the stored key
+    ///
+    /// Returns the authenticator's new signature counter.
+    pub fn finish_authentication(
+        &self,
+        state: &AuthenticationState,
+        credential: &AssertionCredential,
+        key: &SecurityKey,
+    ) -> Result<u32, WebAuthnError> {
+        if credential.raw_id != key.credential_id {
+            return Err(WebAuthnError::CredentialNotAllowed);
+        }
+        if !state.allow_credentials.is_empty()
+            && !state.allow_credentials.contains(&key.credential_id)
+        {
+            return Err(WebAuthnError::CredentialNotAllowed);
+        }
+        if let Some(user_id) = state.user_id {
+            if key.user_id != user_id {
+                return Err(WebAuthnError::CredentialNotAllowed);
+            }
+        }
+        if let Some(handle) = &credential.response.user_handle {
+            if handle.as_slice() != key.user_id.as_bytes() {
+                return Err(WebAuthnError::CredentialNotAllowed);
+            }
+        }
+        if state.user_id.is_none() && !key.is_passkey {
+            return Err(WebAuthnError::CredentialNotAllowed);
+        }
+
+        let response = &credential.response;
+        self.verify_client_data(&response.client_data_json, "webauthn.get", &state.challenge)?;
+
+        let data = AuthenticatorData::parse(&response.authenticator_data)?;
+        self.verify_authenticator_data(&data, state.user_verification)?;
+
+        let verifying_key = VerifyingKey::from_sec1_bytes(&key.public_key)
+            .map_err(|e| WebAuthnError::InvalidPublicKey(e.to_string()))?;
+        let signature = Signature::from_der(&response.signature)
+            .map_err(|_| WebAuthnError::InvalidSignature)?;
+
+        let mut signed = response.authenticator_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&response.client_data_json));
+        verifying_key
+            .verify(&signed, &signature)
+            .map_err(|_| WebAuthnError::InvalidSignature)?;
+
+        // Authenticators that keep a counter must increase it on every use;
+        // a repeat means two devices share the same private key
+        if (data.sign_count != 0 || key.sign_count != 0) && data.sign_count <= key.sign_count {
+            return Err(WebAuthnError::CounterRegression);
+        }
+
+        Ok(data.sign_count)
+    }
+
+    fn verify_client_data(
+        &self,
+        raw: &[u8],
+        expected_type: &str,
+        challenge: &[u8],
+    ) -> Result<(), WebAuthnError> {
+        let client_data: ClientData = serde_json::from_slice(raw)
+            .map_err(|e| WebAuthnError::InvalidClientData(e.to_string()))?;
+
+        if client_data.kind != expected_type {
+            return Err(WebAuthnError::InvalidClientData(format!(
+                "expected type {}, got {}",
+                expected_type, client_data.kind
+            )));
+        }
+
+        let received = base64url::decode(&client_data.challenge)
+            .map_err(|_| WebAuthnError::ChallengeMismatch)?;
+        if received != challenge {
+            return Err(WebAuthnError::ChallengeMismatch);
+        }
+
+        if !self.config.origins.iter().any(|o| o == &client_data.origin) {
+            return Err(WebAuthnError::OriginNotAllowed(client_data.origin));
+        }
+
+        Ok(())
+    }
+
+    fn verify_authenticator_data(
+        &self,
+        data: &AuthenticatorData,
+        user_verification: bool,
+    ) -> Result<(), WebAuthnError> {
+        if data.rp_id_hash[..] != Sha256::digest(self.config.rp_id.as_bytes())[..] {
+            return Err(WebAuthnError::RpIdMismatch);
+        }
+        if !data.user_present() {
+            return Err(WebAuthnError::UserNotPresent);
+        }
+        if user_verification && !data.user_verified() {
+            return Err(WebAuthnError::UserNotVerified);
+        }
+        Ok(())
+    }
+}
+
+/// Database row for a stored credential
+struct SecurityKeyRow {
+    id: Uuid,
+    user_id: Uuid,
+    name: String,
+    credential_id: Vec<u8>,
+    public_key: Vec<u8>,
+    sign_count: i64,
+    transports: Vec<String>,
+    is_passkey: bool,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<SecurityKeyRow> for SecurityKey {
+    fn from(row: SecurityKeyRow) -> Self {
+        Self {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            credential_id: row.credential_id,
+            public_key: row.public_key,
+            sign_count: row.sign_count as u32,
+            transports: row.transports,
+            is_passkey: row.is_passkey,
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+        }
+    }
+}
+
+/// Security key storage and ceremony bookkeeping
+pub struct SecurityKeyService {
+    db_pool: PgPool,
+    redis_client: redis::Client,
+    manager: WebAuthnManager,
+    key_prefix: String,
+}
+
+impl SecurityKeyService {
+    /// Create a new security key service
+    pub fn new(db_pool: PgPool, redis_client: redis::Client, manager: WebAuthnManager) -> Self {
+        Self {
+            db_pool,
+            redis_client,
+            manager,
+            key_prefix: "auth:webauthn".to_string(),
+        }
+    }
+
+    /// Ceremony logic
+    pub fn manager(&self) -> &WebAuthnManager {
+        &self.manager
+    }
+
+    fn state_key(&self, kind: &str, challenge_id: Uuid) -> String {
+        format!("{}:{}:{}", self.key_prefix, kind, challenge_id)
+    }
+
+    /// Store ceremony state until it is finished or times out
+    async fn put_state<T: Serialize>(&self, kind: &str, state: &T) -> Result<Uuid, WebAuthnError> {
+        let mut conn = self
+            .redis_client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| WebAuthnError::Redis(e.to_string()))?;
+
+        let challenge_id = Uuid::new_v4();
+        let data = serde_json::to_string(state)
+            .map_err(|e| WebAuthnError::Serialization(e.to_string()))?;
+
+        redis::cmd("SET")
+            .arg(self.state_key(kind, challenge_id))
+            .arg(data)
+            .arg("EX")
+            .arg(self.manager.config.challenge_ttl_seconds)
+            .query_async::<_, ()>(&mut conn)
+            .await
+            .map_err(|e| WebAuthnError::Redis(e.to_string()))?;
+
+        Ok(challenge_id)
+    }
+
+    /// Take ceremony state; each challenge can only be answered once
+    async fn take_state<T: serde::de::DeserializeOwned>(
+        &self,
+        kind: &str,
+        challenge_id: Uuid,
+    ) -> Result<T, WebAuthnError> {
+        let mut conn = self
+            .redis_client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| WebAuthnError::Redis(e.to_string()))?;
+
+        let data: Option<String> = redis::cmd("GETDEL")
+            .arg(self.state_key(kind, challenge_id))
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| WebAuthnError::Redis(e.to_string()))?;
+
+        let data = data.ok_or(WebAuthnError::ChallengeExpired)?;
+        serde_json::from_str(&data).map_err(|e| WebAuthnError::Serialization(e.to_string()))
+    }
+
+    /// List a user's registered keys
+    pub async fn list_keys(&self, user_id: Uuid) -> Result<Vec<SecurityKey>, WebAuthnError> {
+        let rows = sqlx::query_as!(
+            SecurityKeyRow,
+            r#"
+            SELECT id, user_id, name, credential_id, public_key, sign_count,
+                   transports, is_passkey, created_at, last_used_at
+            FROM webauthn_credentials
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .map_err(|e| WebAuthnError::Database(e.to_string()))?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Start registering a security key or passkey for a signed-in user
+    pub async fn start_registration(
+        &self,
+        user_id: Uuid,
+        username: &str,
+        passkey: bool,
+    ) -> Result<WebAuthnChallenge<CreationOptions>, WebAuthnError> {
+        self.start_ceremony("registration", user_id, username, passkey)
+            .await
+    }
+
+    /// Verify the browser's response and store the new key
+    pub async fn finish_registration(
+        &self,
+        user_id: Uuid,
+        challenge_id: Uuid,
+        name: &str,
+        credential: &RegistrationCredential,
+    ) -> Result<SecurityKey, WebAuthnError> {
+        let state: RegistrationState = self.take_state("registration", challenge_id).await?;
+        if state.user_id != user_id {
+            return Err(WebAuthnError::ChallengeExpired);
+        }
+
+        self.store_key(&state, name, credential).await
+    }
+
+    /// Start registering the first key of a user who must have one to sign in
+    ///
+    /// Issued by a password login in place of a session. The challenge can
+    /// only be finished with [`Self::finish_enrollment`], never with
+    /// [`Self::finish_registration`].
+    pub async fn start_enrollment(
+        &self,
+        user_id: Uuid,
+        username: &str,
+    ) -> Result<WebAuthnChallenge<CreationOptions>, WebAuthnError> {
+        self.start_ceremony("enrollment", user_id, username, false)
+            .await
+    }
+
+    /// Verify and store the key registered for an enrollment challenge
+    ///
+    /// Returns the new key, whose `user_id` is the user the challenge was
+    /// issued to.
+    pub async fn finish_enrollment(
+        &self,
+        challenge_id: Uuid,
+        name: &str,
+        credential: &RegistrationCredential,
+    ) -> Result<SecurityKey, WebAuthnError> {
+        let state: RegistrationState = self.take_state("enrollment", challenge_id).await?;
+        self.store_key(&state, name, credential).await
+    }
+
+    async fn start_ceremony(
+        &self,
+        kind: &str,
+        user_id: Uuid,
+        username: &str,
+        passkey: bool,
+    ) -> Result<WebAuthnChallenge<CreationOptions>, WebAuthnError> {
+        let existing = self.list_keys(user_id).await?;
+        if existing.len() >= MAX_KEYS_PER_USER {
+            return Err(WebAuthnError::TooManyKeys(MAX_KEYS_PER_USER));
+        }
+
+        let (options, state) = self
+            .manager
+            .start_registration(user_id, username, &existing, passkey);
+        let challenge_id = self.put_state(kind, &state).await?;
+
+        Ok(WebAuthnChallenge {
+            challenge_id,
+            options,
+        })
+    }
+
+    async fn store_key(
+        &self,
+        state: &RegistrationState,
+        name: &str,
+        credential: &RegistrationCredential,
+    ) -> Result<SecurityKey, WebAuthnError> {
+        let user_id = state.user_id;
+        let key = self.manager.finish_registration(state, credential, name)?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO webauthn_credentials
+                (id, user_id, name, credential_id, public_key, sign_count,
+                 transports, is_passkey, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            key.id,
+            key.user_id,
+            key.name,
+            key.credential_id,
+            key.public_key,
+            key.sign_count as i64,
+            &key.transports,
+            key.is_passkey,
+            key.created_at
+        )
+        .execute(&self.db_pool)
+        .await;
+
+        match result {
+            Ok(_) => {
+                tracing::info!("User {} registered security key {}", user_id, key.id);
+                Ok(key)
+            }
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(WebAuthnError::CredentialExists)
+            }
+            Err(e) => Err(WebAuthnError::Database(e.to_string())),
+        }
+    }
+
+    /// Remove one of a user's keys
+    pub async fn remove_key(&self, user_id: Uuid, key_id: Uuid) -> Result<(), WebAuthnError> {
+        let result = sqlx::query!(
+            "DELETE FROM webauthn_credentials WHERE id = $1 AND user_id = $2",
+            key_id,
+            user_id
+        )
+        .execute(&self.db_pool)
+        .await
+        .map_err(|e| WebAuthnError::Database(e.to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(WebAuthnError::KeyNotFound);
+        }
+
+        tracing::info!("User {} removed security key {}", user_id, key_id);
+        Ok(())
+    }
+
+    /// Start a second-factor assertion against the user's keys
+    pub async fn start_authentication(
+        &self,
+        user_id: Uuid,
+        keys: &[SecurityKey],
+    ) -> Result<WebAuthnChallenge<RequestOptions>, WebAuthnError> {
+        let (options, state) = self.manager.start_authentication(Some(user_id), keys);
+        let challenge_id = self.put_state("authentication", &state).await?;
+
+        Ok(WebAuthnChallenge {
+            challenge_id,
+            options,
+        })
+    }
+
+    /// Start a passwordless login with any passkey for this site
+    pub async fn start_passkey_authentication(
+        &self,
+    ) -> Result<WebAuthnChallenge<RequestOptions>, WebAuthnError> {
+        let (options, state) = self.manager.start_authentication(None, &[]);
+        let challenge_id = self.put_state("authentication", &state).await?;
+
+        Ok(WebAuthnChallenge {
+            challenge_id,
+            options,
+        })
+    }
+
+    /// Verify an assertion and record the key's use
+    ///
+    /// Returns the key that signed, whose `user_id` is the authenticated user.
+    pub async fn finish_authentication(
+        &self,
+        challenge_id: Uuid,
+        credential: &AssertionCredential,
+    ) -> Result<SecurityKey, WebAuthnError> {
+        let state: AuthenticationState = self.take_state("authentication", challenge_id).await?;
+
+        let mut key: SecurityKey = sqlx::query_as!(
+            SecurityKeyRow,
+            r#"
+            SELECT id, user_id, name, credential_id, public_key, sign_count,
+                   transports, is_passkey, created_at, last_used_at
+            FROM webauthn_credentials
+            WHERE credential_id = $1
+            "#,
+            credential.raw_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .map_err(|e| WebAuthnError::Database(e.to_string()))?
+        .ok_or(WebAuthnError::KeyNotFound)?
+        .into();
+
+        let sign_count = self
+            .manager
+            .finish_authentication(&state, credential, &key)?;
+
+        let now = Utc::now();
+        sqlx::query!(
+            "UPDATE webauthn_credentials SET sign_count = $1, last_used_at = $2 WHERE id = $3",
+            sign_count as i64,
+            now,
+            key.id
+        )
+        .execute(&self.db_pool)
+        .await
+        .map_err(|e| WebAuthnError::Database(e.to_string()))?;
+
+        key.sign_count = sign_count;
+        key.last_used_at = Some(now);
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::{signature::Signer, SigningKey};
+
+    const ORIGIN: &str = "https://tracker.example";
+
+    /// Minimal software authenticator holding one P-256 credential
+    struct SoftAuthenticator {
+        key: SigningKey,
+        credential_id: Vec<u8>,
+        counter: u32,
+        user_verifying: bool,
+    }
+
+    impl SoftAuthenticator {
+        fn new() -> Self {
+            let mut credential_id = vec![0u8; 16];
+            rand::thread_rng().fill_bytes(&mut credential_id);
+            Self {
+                key: SigningKey::random(&mut rand::rngs::OsRng),
+                credential_id,
+                counter: 0,
+                user_verifying: true,
+            }
+        }
+
+        fn flags(&self) -> u8 {
+            let mut flags = FLAG_USER_PRESENT;
+            if self.user_verifying {
+                flags |= FLAG_USER_VERIFIED;
+            }
+            flags
+        }
+
+        fn client_data(kind: &str, challenge: &[u8], origin: &str) -> Vec<u8> {
+            serde_json::to_vec(&serde_json::json!({
+                "type": kind,
+                "challenge": URL_SAFE_NO_PAD.encode(challenge),
+                "origin": origin,
+                "crossOrigin": false,
+            }))
+            .unwrap()
+        }
+
+        fn register(&mut self, options: &CreationOptions) -> RegistrationCredential {
+            let point = self.key.verifying_key().to_encoded_point(false);
+            let cose_key = Value::Map(vec![
+                (Value::from(1), Value::from(2)),
+                (Value::from(3), Value::from(COSE_ALG_ES256)),
+                (Value::from(-1), Value::from(1)),
+                (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+                (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+            ]);
+
+            let mut auth_data = Sha256::digest(options.rp.id.as_bytes()).to_vec();
+            auth_data.push(self.flags() | FLAG_ATTESTED_CREDENTIAL);
+            auth_data.extend_from_slice(&self.counter.to_be_bytes());
+            auth_data.extend_from_slice(&[0u8; 16]);
+            auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+            auth_data.extend_from_slice(&self.credential_id);
+            ciborium::ser::into_writer(&cose_key, &mut auth_data).unwrap();
+
+            let attestation = Value::Map(vec![
+                (Value::from("fmt"), Value::from("none")),
+                (Value::from("attStmt"), Value::Map(vec![])),
+                (Value::from("authData"), Value::Bytes(auth_data)),
+            ]);
+            let mut attestation_object = Vec::new();
+            ciborium::ser::into_writer(&attestation, &mut attestation_object).unwrap();
+
+            RegistrationCredential {
+                id: URL_SAFE_NO_PAD.encode(&self.credential_id),
+                raw_id: self.credential_id.clone(),
+                response: AttestationResponse {
+                    client_data_json: Self::client_data(
+                        "webauthn.create",
+                        &options.challenge,
+                        ORIGIN,
+                    ),
+                    attestation_object,
+                    transports: vec!["usb".to_string()],
+                },
+                kind: "public-key".to_string(),
+            }
+        }
+
+        fn assert(&mut self, options: &RequestOptions, user_id: Uuid) -> AssertionCredential {
+            self.counter += 1;
+
+            let mut authenticator_data = Sha256::digest(options.rp_id.as_bytes()).to_vec();
+            authenticator_data.push(self.flags());
+            authenticator_data.extend_from_slice(&self.counter.to_be_bytes());
+
+            let client_data_json = Self::client_data("webauthn.get", &options.challenge, ORIGIN);
+            let mut signed = authenticator_data.clone();
+            signed.extend_from_slice(&Sha256::digest(&client_data_json));
+            let signature: Signature = self.key.sign(&signed);
+
+            AssertionCredential {
+                id: URL_SAFE_NO_PAD.encode(&self.credential_id),
+                raw_id: self.credential_id.clone(),
+                response: AssertionResponse {
+                    client_data_json,
+                    authenticator_data,
+                    signature: signature.to_der().as_bytes().to_vec(),
+                    user_handle: Some(user_id.as_bytes().to_vec()),
+                },
+                kind: "public-key".to_string(),
+            }
+        }
+    }
+
+    fn manager() -> WebAuthnManager {
+        WebAuthnManager::new(WebAuthnConfig::new("tracker.example", "Tracker", ORIGIN))
+    }
+
+    fn registered(
+        manager: &WebAuthnManager,
+        authenticator: &mut SoftAuthenticator,
+        user_id: Uuid,
+        passkey: bool,
+    ) -> SecurityKey {
+        let (options, state) = manager.start_registration(user_id, "alice", &[], passkey);
+        let credential = authenticator.register(&options);
+        manager
+            .finish_registration(&state, &credential, "YubiKey")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_registration_and_assertion() {
+        let manager = manager();
+        let mut authenticator = SoftAuthenticator::new();
+        let user_id = Uuid::new_v4();
+
+        let key = registered(&manager, &mut authenticator, user_id, false);
+        assert_eq!(key.user_id, user_id);
+        assert_eq!(key.credential_id, authenticator.credential_id);
+        assert_eq!(key.public_key.len(), 65);
+        assert!(!key.is_passkey);
+
+        let (options, state) =
+            manager.start_authentication(Some(user_id), std::slice::from_ref(&key));
+        assert_eq!(options.allow_credentials.len(), 1);
+
+        let assertion = authenticator.assert(&options, user_id);
+        let counter = manager
+            .finish_authentication(&state, &assertion, &key)
+            .unwrap();
+        assert_eq!(counter, 1);
+    }
+
+    #[test]
+    fn test_options_serialize_as_webauthn_json() {
+        let (options, _) = manager().start_registration(Uuid::new_v4(), "alice", &[], true);
+        let json = serde_json::to_value(&options).unwrap();
+
+        assert_eq!(json["rp"]["id"], "tracker.example");
+        assert_eq!(json["pubKeyCredParams"][0]["alg"], -7);
+        assert_eq!(json["authenticatorSelection"]["residentKey"], "required");
+        assert!(json["challenge"].as_str().unwrap().len() >= 43);
+        assert!(!json["challenge"].as_str().unwrap().contains('='));
+    }
+
+    #[test]
+    fn test_rejects_wrong_challenge_and_origin() {
+        let manager = manager();
+        let mut authenticator = SoftAuthenticator::new();
+        let user_id = Uuid::new_v4();
+        let key = registered(&manager, &mut authenticator, user_id, false);
+
+        // Answer to a different ceremony
+        let (options, _) = manager.start_authentication(Some(user_id), std::slice::from_ref(&key));
+        let (_, other_state) =
+            manager.start_authentication(Some(user_id), std::slice::from_ref(&key));
+        let assertion = authenticator.assert(&options, user_id);
+        assert!(matches!(
+            manager.finish_authentication(&other_state, &assertion, &key),
+            Err(WebAuthnError::ChallengeMismatch)
+        ));
+
+        // Phishing origin
+        let mut config = WebAuthnConfig::new("tracker.example", "Tracker", ORIGIN);
+        config.origins = vec!["https://other.example".to_string()];
+        let strict = WebAuthnManager::new(config);
+        let (options, state) =
+            strict.start_authentication(Some(user_id), std::slice::from_ref(&key));
+        let assertion = authenticator.assert(&options, user_id);
+        assert!(matches!(
+            strict.finish_authentication(&state, &assertion, &key),
+            Err(WebAuthnError::OriginNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_bad_signature_and_cloned_counter() {
+        let manager = manager();
+        let mut authenticator = SoftAuthenticator::new();
+        let user_id = Uuid::new_v4();
+        let mut key = registered(&manager, &mut authenticator, user_id, false);
+
+        let (options, state) =
+            manager.start_authentication(Some(user_id), std::slice::from_ref(&key));
+        let mut assertion = authenticator.assert(&options, user_id);
+        assertion.response.client_data_json.push(b' ');
+        assert!(matches!(
+            manager.finish_authentication(&state, &assertion, &key),
+            Err(WebAuthnError::InvalidSignature)
+        ));
+
+        // A counter that does not advance suggests a cloned key
+        key.sign_count = 10;
+        let (options, state) =
+            manager.start_authentication(Some(user_id), std::slice::from_ref(&key));
+        let assertion = authenticator.assert(&options, user_id);
+        assert!(matches!(
+            manager.finish_authentication(&state, &assertion, &key),
+            Err(WebAuthnError::CounterRegression)
+        ));
+    }
+
+    #[test]
+    fn test_passkey_login_requires_user_verification() {
+        let manager = manager();
+        let mut authenticator = SoftAuthenticator::new();
+        let user_id = Uuid::new_v4();
+
+        let passkey = registered(&manager, &mut authenticator, user_id, true);
+        assert!(passkey.is_passkey);
+
+        let (options, state) = manager.start_authentication(None, &[]);
+        assert!(options.allow_credentials.is_empty());
+        assert_eq!(options.user_verification, "required");
+        let assertion = authenticator.assert(&options, user_id);
+        assert!(manager
+            .finish_authentication(&state, &assertion, &passkey)
+            .is_ok());
+
+        // Presence alone is not enough without a password
+        authenticator.user_verifying = false;
+        let (options, state) = manager.start_authentication(None, &[]);
+        let assertion = authenticator.assert(&options, user_id);
+        assert!(matches!(
+            manager.finish_authentication(&state, &assertion, &passkey),
+            Err(WebAuthnError::UserNotVerified)
+        ));
+
+        // Second-factor keys cannot be used on their own
+        let mut second_factor = SoftAuthenticator::new();
+        let key = registered(&manager, &mut second_factor, user_id, false);
+        let (options, state) = manager.start_authentication(None, &[]);
+        let assertion = second_factor.assert(&options, user_id);
+        assert!(matches!(
+            manager.finish_authentication(&state, &assertion, &key),
+            Err(WebAuthnError::CredentialNotAllowed)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_excluded_and_foreign_credentials() {
+        let manager = manager();
+        let mut authenticator = SoftAuthenticator::new();
+        let user_id = Uuid::new_v4();
+        let key = registered(&manager, &mut authenticator, user_id, false);
+
+        // Registering the same authenticator again
+        let (options, state) =
+            manager.start_registration(user_id, "alice", std::slice::from_ref(&key), false);
+        assert_eq!(options.exclude_credentials.len(), 1);
+        let credential = authenticator.register(&options);
+        assert!(matches!(
+            manager.finish_registration(&state, &credential, "Again"),
+            Err(WebAuthnError::CredentialExists)
+        ));
+
+        // Another user's key cannot answer this user's challenge
+        let other_user = Uuid::new_v4();
+        let (options, state) = manager.start_authentication(Some(other_user), &[]);
+        let assertion = authenticator.assert(&options, user_id);
+        assert!(matches!(
+            manager.finish_authentication(&state, &assertion, &key),
+            Err(WebAuthnError::CredentialNotAllowed)
+        ));
+    }
+}
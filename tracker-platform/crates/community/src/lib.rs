@@ -135,6 +135,7 @@
 //! - `topics`: Discussion threads
 //! - `posts`: Individual forum posts
 //! - `post_edits`: Edit history for posts
+//! - `topic_digests`: When each user was last emailed a digest of subscribed topics
 //! - `conversations`: Private message threads
 //! - `messages`: Individual private messages
 //! - `chat_rooms`: Chat room definitions
@@ -180,7 +181,8 @@ pub use posts::{
     CreatePostRequest, Post, PostEdit, PostError, PostReaction, PostService, ReactionType,
 };
 pub use topics::{
-    CreateTopicRequest, Topic, TopicError, TopicService, TopicStatus, TopicSubscription,
+    CreateTopicRequest, Topic, TopicDigestEntry, TopicError, TopicService, TopicStatus,
+    TopicSubscription,
 };
 pub use wiki::{
     CreateWikiPageRequest, WikiError, WikiPage, WikiRevision, WikiService,
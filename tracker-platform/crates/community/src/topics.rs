@@ -96,6 +96,15 @@ pub struct TopicSubscription {
     pub created_at: DateTime<Utc>,
 }
 
+/// New posts in a topic a user subscribed to by email, for a digest
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TopicDigestEntry {
+    pub user_id: Uuid,
+    pub topic_id: Uuid,
+    pub title: String,
+    pub new_posts: i64,
+}
+
 /// Request to create a new topic
 #[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct CreateTopicRequest {
@@ -567,6 +576,54 @@ impl TopicService {
         Ok(subscriptions)
     }
 
+    /// Gets new posts in email-subscribed topics not yet covered by a digest
+    ///
+    /// Covers posts up to `until` since each user's last digest, or since they
+    /// subscribed. A user's own posts are left out. Entries are grouped by user.
+    pub async fn pending_digest_entries(
+        &self,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<TopicDigestEntry>> {
+        let entries = sqlx::query_as::<_, TopicDigestEntry>(
+            r#"
+            SELECT s.user_id, t.id AS topic_id, t.title, COUNT(p.id) AS new_posts
+            FROM topic_subscriptions s
+            JOIN topics t ON t.id = s.topic_id
+            JOIN posts p ON p.topic_id = t.id
+            LEFT JOIN topic_digests d ON d.user_id = s.user_id
+            WHERE s.notify_email
+              AND p.is_deleted = false
+              AND p.user_id <> s.user_id
+              AND p.created_at > GREATEST(s.created_at, COALESCE(d.last_sent_at, s.created_at))
+              AND p.created_at <= $1
+            GROUP BY s.user_id, t.id, t.title
+            ORDER BY s.user_id, new_posts DESC
+            "#,
+        )
+        .bind(until)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Records that a user's digest covered posts up to `until`
+    pub async fn mark_digest_sent(&self, user_id: Uuid, until: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO topic_digests (user_id, last_sent_at)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET last_sent_at = EXCLUDED.last_sent_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(until)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
     /// Increments view count
     pub async fn increment_views(&self, topic_id: Uuid) -> Result<()> {
         sqlx::query("UPDATE topics SET view_count = view_count + 1 WHERE id = $1")
@@ -4,11 +4,12 @@
 //! with metadata from various sources based on their names.
 //!
 //! Workflow:
-//! 1. Detect media type from torrent name
-//! 2. Extract title, year, and other info
-//! 3. Search appropriate metadata source
-//! 4. Store metadata in database
-//! 5. Queue torrent for search reindexing
+//! 1. Look up IMDb/TMDB links found in the torrent's NFO
+//! 2. Otherwise detect media type from torrent name
+//! 3. Extract title, year, and other info
+//! 4. Search appropriate metadata source
+//! 5. Store metadata in database
+//! 6. Queue torrent for search reindexing
 
 use crate::detector::{detect_media_info, MediaInfo};
 use crate::{MediaMetadata, MediaService, MediaType};
@@ -46,6 +47,20 @@ pub async fn enrich_torrent(
 ) -> Result<Option<MediaMetadata>> {
     info!("Enriching torrent: {} ({})", torrent_id, torrent_name);
 
+    // Links from the NFO identify the release exactly, so try them first
+    if let Some(metadata) = lookup_nfo_links(service, torrent_id).await? {
+        store_enrichment(service.db(), torrent_id, &metadata)
+            .await
+            .context("Failed to store enrichment")?;
+
+        info!(
+            "Enriched torrent {} from NFO link with metadata for '{}'",
+            torrent_id, metadata.title
+        );
+
+        return Ok(Some(metadata));
+    }
+
     // Step 1: Detect media type and extract info
     let media_info = detect_media_info(torrent_name);
     debug!("Detected media info: {:?}", media_info);
@@ -84,6 +99,53 @@ pub async fn enrich_torrent(
     Ok(best_match)
 }
 
+/// Resolve the first usable NFO link of a torrent
+async fn lookup_nfo_links(service: &MediaService, torrent_id: Uuid) -> Result<Option<MediaMetadata>> {
+    let links = get_nfo_links(service.db(), torrent_id).await?;
+
+    for (source, external_id) in links {
+        let Some(media_type) = link_media_type(&source) else {
+            continue;
+        };
+
+        match service.get_by_id(media_type, &external_id).await {
+            Ok(Some(metadata)) => return Ok(Some(metadata)),
+            Ok(None) => debug!("No metadata for NFO link {}:{}", source, external_id),
+            Err(e) => warn!("Failed to resolve NFO link {}:{}: {}", source, external_id, e),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Media type to resolve an NFO link source with
+fn link_media_type(source: &str) -> Option<MediaType> {
+    match source {
+        "tmdb_movie" => Some(MediaType::Movie),
+        "tmdb_tv" => Some(MediaType::TvShow),
+        // IMDb IDs are resolved through the IMDb client
+        "imdb" => Some(MediaType::Other),
+        _ => None,
+    }
+}
+
+/// Get NFO links of a torrent, TMDB links first
+async fn get_nfo_links(db: &PgPool, torrent_id: Uuid) -> Result<Vec<(String, String)>> {
+    let results = sqlx::query!(
+        r#"
+        SELECT source, external_id
+        FROM torrent_nfo_links
+        WHERE torrent_id = $1
+        ORDER BY (source = 'imdb'), position
+        "#,
+        torrent_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(results.into_iter().map(|r| (r.source, r.external_id)).collect())
+}
+
 /// Pick the best matching metadata from search results
 fn pick_best_match(results: &[MediaMetadata], media_info: &MediaInfo) -> Option<MediaMetadata> {
     if results.is_empty() {
@@ -315,6 +377,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_link_media_type() {
+        assert_eq!(link_media_type("tmdb_movie"), Some(MediaType::Movie));
+        assert_eq!(link_media_type("tmdb_tv"), Some(MediaType::TvShow));
+        assert_eq!(link_media_type("imdb"), Some(MediaType::Other));
+        assert_eq!(link_media_type("anidb"), None);
+    }
+
     #[test]
     fn test_pick_best_match() {
         let media_info = MediaInfo {
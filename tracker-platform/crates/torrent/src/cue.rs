@@ -0,0 +1,256 @@
+//! Cue sheet parsing
+//!
+//! Cue sheets describe the track layout of a CD rip. They are uploaded next
+//! to the rip log and cross-checked against it and the torrent's file list.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::files::{FileType, TorrentFileInfo};
+
+/// Parsed cue sheet
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CueSheet {
+    /// Album performer
+    pub performer: Option<String>,
+
+    /// Album title
+    pub title: Option<String>,
+
+    /// Referenced audio files and their tracks
+    pub files: Vec<CueFile>,
+}
+
+/// A FILE entry in a cue sheet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CueFile {
+    /// File name as written in the cue sheet
+    pub name: String,
+
+    /// Tracks in this file
+    pub tracks: Vec<CueTrack>,
+}
+
+/// A TRACK entry in a cue sheet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CueTrack {
+    /// Track number
+    pub number: u32,
+
+    /// Track title
+    pub title: Option<String>,
+
+    /// Track performer
+    pub performer: Option<String>,
+
+    /// INDEX entries (index number, MM:SS:FF position)
+    pub indexes: Vec<(u32, String)>,
+}
+
+impl CueSheet {
+    /// Parse a cue sheet
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut sheet = CueSheet::default();
+
+        for (line_no, raw) in content.lines().enumerate() {
+            let line = raw.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+
+            match command.to_ascii_uppercase().as_str() {
+                "FILE" => {
+                    let name = match rest.strip_prefix('"') {
+                        Some(quoted) => quoted
+                            .split_once('"')
+                            .map(|(name, _)| name.to_string())
+                            .ok_or_else(|| anyhow!("Unterminated FILE name on line {}", line_no + 1))?,
+                        // Unquoted: the last word is the file type
+                        None => rest
+                            .rsplit_once(char::is_whitespace)
+                            .map_or(rest, |(name, _)| name)
+                            .to_string(),
+                    };
+                    sheet.files.push(CueFile {
+                        name,
+                        tracks: Vec::new(),
+                    });
+                }
+                "TRACK" => {
+                    let number = rest
+                        .split_whitespace()
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .ok_or_else(|| anyhow!("Invalid TRACK on line {}", line_no + 1))?;
+                    let file = sheet
+                        .files
+                        .last_mut()
+                        .ok_or_else(|| anyhow!("TRACK before FILE on line {}", line_no + 1))?;
+                    file.tracks.push(CueTrack {
+                        number,
+                        title: None,
+                        performer: None,
+                        indexes: Vec::new(),
+                    });
+                }
+                "INDEX" => {
+                    let mut parts = rest.split_whitespace();
+                    let index = parts.next().and_then(|n| n.parse().ok());
+                    let position = parts.next();
+                    let track = sheet.files.last_mut().and_then(|f| f.tracks.last_mut());
+
+                    match (index, position, track) {
+                        (Some(index), Some(position), Some(track)) => {
+                            track.indexes.push((index, position.to_string()));
+                        }
+                        _ => return Err(anyhow!("Invalid INDEX on line {}", line_no + 1)),
+                    }
+                }
+                "TITLE" | "PERFORMER" => {
+                    let value = Some(unquote(rest).to_string());
+                    let is_title = command.eq_ignore_ascii_case("TITLE");
+
+                    match sheet.files.last_mut().and_then(|f| f.tracks.last_mut()) {
+                        Some(track) if is_title => track.title = value,
+                        Some(track) => track.performer = value,
+                        None if is_title => sheet.title = value,
+                        None => sheet.performer = value,
+                    }
+                }
+                // REM, CATALOG, FLAGS, PREGAP, ISRC, ... carry nothing we check
+                _ => {}
+            }
+        }
+
+        if sheet.files.is_empty() {
+            return Err(anyhow!("Cue sheet references no files"));
+        }
+
+        Ok(sheet)
+    }
+
+    /// All tracks across all files
+    pub fn tracks(&self) -> impl Iterator<Item = &CueTrack> {
+        self.files.iter().flat_map(|f| f.tracks.iter())
+    }
+
+    /// Number of tracks
+    pub fn track_count(&self) -> usize {
+        self.tracks().count()
+    }
+
+    /// Check the sheet is internally consistent
+    ///
+    /// Tracks must be numbered 1..n in order and each must have an INDEX 01.
+    pub fn check_consistency(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        for (expected, track) in (1u32..).zip(self.tracks()) {
+            if track.number != expected {
+                problems.push(format!(
+                    "Track {} is out of sequence (expected {})",
+                    track.number, expected
+                ));
+            }
+            if !track.indexes.iter().any(|(index, _)| *index == 1) {
+                problems.push(format!("Track {} has no INDEX 01", track.number));
+            }
+        }
+
+        problems
+    }
+
+    /// Referenced files that are missing from the torrent
+    ///
+    /// Matching is on the file name only, since cue sheets rarely carry the
+    /// torrent's directory layout.
+    pub fn missing_files<'a>(&'a self, files: &[TorrentFileInfo]) -> Vec<&'a str> {
+        self.files
+            .iter()
+            .map(|f| f.name.as_str())
+            .filter(|name| {
+                let wanted = base_name(name);
+                !files.iter().any(|f| {
+                    f.file_type == FileType::Audio && base_name(&f.path).eq_ignore_ascii_case(wanted)
+                })
+            })
+            .collect()
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn base_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_CUE: &str = r#"REM GENRE Rock
+REM DATE 1994
+PERFORMER "Sample Artist"
+TITLE "Sample Album"
+FILE "01 - Opening.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "Opening"
+    PERFORMER "Sample Artist"
+    INDEX 01 00:00:00
+FILE "02 - Closing.flac" WAVE
+  TRACK 02 AUDIO
+    TITLE "Closing"
+    PERFORMER "Sample Artist"
+    INDEX 00 00:00:00
+    INDEX 01 00:01:65
+"#;
+
+    fn audio(path: &str) -> TorrentFileInfo {
+        TorrentFileInfo {
+            path: path.to_string(),
+            size: 20_000_000,
+            extension: Some("flac".to_string()),
+            file_type: FileType::Audio,
+            media_type: None,
+            is_sample: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_cue_sheet() {
+        let sheet = CueSheet::parse(SAMPLE_CUE).unwrap();
+
+        assert_eq!(sheet.performer.as_deref(), Some("Sample Artist"));
+        assert_eq!(sheet.title.as_deref(), Some("Sample Album"));
+        assert_eq!(sheet.files.len(), 2);
+        assert_eq!(sheet.track_count(), 2);
+        assert_eq!(sheet.files[1].tracks[0].title.as_deref(), Some("Closing"));
+        assert_eq!(sheet.files[1].tracks[0].indexes.len(), 2);
+        assert!(sheet.check_consistency().is_empty());
+    }
+
+    #[test]
+    fn test_missing_files() {
+        let sheet = CueSheet::parse(SAMPLE_CUE).unwrap();
+        let files = vec![audio("Sample Album/01 - Opening.flac")];
+
+        assert_eq!(sheet.missing_files(&files), vec!["02 - Closing.flac"]);
+    }
+
+    #[test]
+    fn test_inconsistent_sheet() {
+        let cue = "FILE \"a.flac\" WAVE\n  TRACK 02 AUDIO\n    INDEX 00 00:00:00\n";
+        let sheet = CueSheet::parse(cue).unwrap();
+
+        assert_eq!(sheet.check_consistency().len(), 2);
+        assert!(CueSheet::parse("TRACK 01 AUDIO").is_err());
+    }
+}
@@ -370,17 +370,8 @@ impl DownloadService {
         .execute(&self.pool)
         .await?;
 
-        // Update torrent download count
-        sqlx::query!(
-            r#"
-            UPDATE torrents
-            SET times_completed = times_completed + 1
-            WHERE id = $1
-            "#,
-            torrent_id
-        )
-        .execute(&self.pool)
-        .await?;
+        // times_completed is maintained by the tracker from completed
+        // announces; fetching the .torrent file is not a snatch
 
         Ok(DownloadEvent {
             id: event_id,
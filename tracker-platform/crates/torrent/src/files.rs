@@ -8,6 +8,8 @@ use path_clean::PathClean;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
+use crate::rules::{RuleViolation, UploadRuleSet, ViolationCode};
+
 /// File information with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TorrentFileInfo {
@@ -92,14 +94,11 @@ pub enum MediaType {
 /// File validation result
 #[derive(Debug)]
 pub struct FileValidation {
-    /// Whether the file list is valid
+    /// Whether the file list is valid (no error-level violations)
     pub is_valid: bool,
 
-    /// Validation errors
-    pub errors: Vec<String>,
-
-    /// Validation warnings
-    pub warnings: Vec<String>,
+    /// Rule violations, errors and warnings
+    pub violations: Vec<RuleViolation>,
 
     /// Total file count
     pub file_count: usize,
@@ -111,6 +110,18 @@ pub struct FileValidation {
     pub media_file_count: usize,
 }
 
+impl FileValidation {
+    /// Error-level violations
+    pub fn errors(&self) -> impl Iterator<Item = &RuleViolation> {
+        self.violations.iter().filter(|v| v.is_error())
+    }
+
+    /// Warning-level violations
+    pub fn warnings(&self) -> impl Iterator<Item = &RuleViolation> {
+        self.violations.iter().filter(|v| !v.is_error())
+    }
+}
+
 /// Parse and validate file list from torrent
 pub fn parse_file_list(
     files: Vec<crate::bencode::TorrentFile>,
@@ -284,18 +295,19 @@ fn is_sample_file(path: &str) -> bool {
         || path_lower.contains("trailer")
 }
 
-/// Validate file list
-pub fn validate_file_list(files: &[TorrentFileInfo]) -> FileValidation {
-    let mut errors = Vec::new();
-    let mut warnings = Vec::new();
+/// Validate file list against a category's upload rules
+pub fn validate_file_list(files: &[TorrentFileInfo], rules: &UploadRuleSet) -> FileValidation {
+    let mut violations = Vec::new();
 
     // Check for empty file list
     if files.is_empty() {
-        errors.push("File list is empty".to_string());
+        violations.push(RuleViolation::error(
+            ViolationCode::EmptyFileList,
+            "File list is empty",
+        ));
         return FileValidation {
             is_valid: false,
-            errors,
-            warnings,
+            violations,
             file_count: 0,
             total_size: 0,
             media_file_count: 0,
@@ -308,12 +320,21 @@ pub fn validate_file_list(files: &[TorrentFileInfo]) -> FileValidation {
     for (idx, file) in files.iter().enumerate() {
         // Validate file size
         if file.size <= 0 {
-            errors.push(format!("File {} has invalid size: {}", file.path, file.size));
-        } else if file.size < 100 {
-            warnings.push(format!(
-                "File {} is very small ({} bytes)",
-                file.path, file.size
-            ));
+            violations.push(
+                RuleViolation::error(
+                    ViolationCode::InvalidFileSize,
+                    format!("File {} has invalid size: {}", file.path, file.size),
+                )
+                .at(&file.path),
+            );
+        } else if file.size < rules.small_file_warning_bytes {
+            violations.push(
+                RuleViolation::warning(
+                    ViolationCode::SmallFile,
+                    format!("File {} is very small ({} bytes)", file.path, file.size),
+                )
+                .at(&file.path),
+            );
         }
 
         total_size += file.size;
@@ -327,44 +348,80 @@ pub fn validate_file_list(files: &[TorrentFileInfo]) -> FileValidation {
         }
 
         // Warn about executable files
-        if file.file_type == FileType::Executable {
-            warnings.push(format!(
-                "File {} is an executable - ensure this is intentional",
-                file.path
-            ));
+        if rules.warn_executables && file.file_type == FileType::Executable {
+            violations.push(
+                RuleViolation::warning(
+                    ViolationCode::ExecutableFile,
+                    format!(
+                        "File {} is an executable - ensure this is intentional",
+                        file.path
+                    ),
+                )
+                .at(&file.path),
+            );
         }
 
         // Check for duplicate file names (case-insensitive)
         for other in files.iter().skip(idx + 1) {
             if file.path.eq_ignore_ascii_case(&other.path) {
-                errors.push(format!("Duplicate file name detected: {}", file.path));
+                violations.push(
+                    RuleViolation::error(
+                        ViolationCode::DuplicatePath,
+                        format!("Duplicate file name detected: {}", file.path),
+                    )
+                    .at(&file.path),
+                );
             }
         }
     }
 
     // Validate total size
     if total_size <= 0 {
-        errors.push("Total torrent size is invalid".to_string());
+        violations.push(RuleViolation::error(
+            ViolationCode::InvalidFileSize,
+            "Total torrent size is invalid",
+        ));
     }
 
     // Warn if no media files
-    if media_file_count == 0 {
-        warnings.push("No media files detected in torrent".to_string());
+    if rules.warn_no_media && media_file_count == 0 {
+        violations.push(RuleViolation::warning(
+            ViolationCode::NoMediaFiles,
+            "No media files detected in torrent",
+        ));
     }
 
-    // Check size limits (example: 500GB max)
-    const MAX_TORRENT_SIZE: i64 = 500 * 1024 * 1024 * 1024; // 500GB
-    if total_size > MAX_TORRENT_SIZE {
-        errors.push(format!(
-            "Torrent size exceeds maximum allowed size ({}GB)",
-            MAX_TORRENT_SIZE / (1024 * 1024 * 1024)
-        ));
+    // Check size limits
+    if let Some(min_size) = rules.min_size {
+        if total_size < min_size {
+            violations.push(RuleViolation::error(
+                ViolationCode::SizeTooSmall,
+                format!(
+                    "Torrent size is below the minimum allowed size ({} bytes)",
+                    min_size
+                ),
+            ));
+        }
     }
 
+    if let Some(max_size) = rules.max_size {
+        if total_size > max_size {
+            violations.push(RuleViolation::error(
+                ViolationCode::SizeTooLarge,
+                format!(
+                    "Torrent size exceeds maximum allowed size ({}GB)",
+                    max_size / (1024 * 1024 * 1024)
+                ),
+            ));
+        }
+    }
+
+    // Category-specific extension, container and required-file rules
+    violations.extend(rules.check_file_types(files));
+
     FileValidation {
-        is_valid: errors.is_empty(),
-        errors,
-        warnings,
+        is_valid: !violations.iter().any(RuleViolation::is_error),
+        violations,
         file_count: files.len(),
         total_size,
         media_file_count,
@@ -506,4 +563,43 @@ mod tests {
         assert_eq!(calculate_path_depth("dir/file.txt"), 2);
         assert_eq!(calculate_path_depth("dir/subdir/file.txt"), 3);
     }
+
+    #[test]
+    fn test_validate_file_list_with_rules() {
+        let files = vec![
+            TorrentFileInfo {
+                path: "Movie/movie.mkv".to_string(),
+                size: 4 * 1024 * 1024 * 1024,
+                extension: Some("mkv".to_string()),
+                file_type: FileType::Video,
+                media_type: Some(MediaType::Movie),
+                is_sample: false,
+            },
+            TorrentFileInfo {
+                path: "Movie/setup.exe".to_string(),
+                size: 50,
+                extension: Some("exe".to_string()),
+                file_type: FileType::Executable,
+                media_type: Some(MediaType::Application),
+                is_sample: false,
+            },
+        ];
+
+        let validation = validate_file_list(&files, &UploadRuleSet::default());
+        assert!(validation.is_valid);
+        assert_eq!(validation.warnings().count(), 2);
+
+        let strict = UploadRuleSet {
+            max_size: Some(1024 * 1024 * 1024),
+            warn_executables: false,
+            ..Default::default()
+        };
+        let validation = validate_file_list(&files, &strict);
+        assert!(!validation.is_valid);
+        assert_eq!(
+            validation.errors().map(|v| v.code).collect::<Vec<_>>(),
+            vec![ViolationCode::SizeTooLarge]
+        );
+        assert_eq!(validation.warnings().count(), 1);
+    }
 }
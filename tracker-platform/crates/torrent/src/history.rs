@@ -0,0 +1,554 @@
+//! Torrent edit history
+//!
+//! Every metadata edit is stored as a numbered revision holding the full
+//! snapshot and a field-level diff against the previous state, so vandalised
+//! descriptions or metadata can be restored. A snapshot covers the
+//! `torrent_metadata` row and the listing category on `torrents`; the
+//! listing's name and description are kept in step in the same transaction.
+//! Rolling back applies an old snapshot as a new revision; history is never
+//! rewritten.
+//!
+//! Significant changes (name, category, quality, external IDs) notify the
+//! uploader and everyone who snatched the torrent.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool, Postgres, Row, Transaction};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::metadata::TorrentMetadata;
+
+/// Fields whose change is announced to the uploader and snatchers
+pub const SIGNIFICANT_FIELDS: &[&str] = &[
+    "name",
+    "category",
+    "category_id",
+    "media_type",
+    "quality",
+    "external_ids",
+];
+
+/// The state a revision records and a rollback restores
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TorrentSnapshot {
+    #[serde(flatten)]
+    pub metadata: TorrentMetadata,
+
+    /// Listing category (`torrents.category`)
+    pub category: String,
+}
+
+/// A single changed field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    /// Field name as serialized in `TorrentSnapshot`
+    pub field: String,
+
+    /// Value before the edit
+    pub old: serde_json::Value,
+
+    /// Value after the edit
+    pub new: serde_json::Value,
+}
+
+/// A stored revision, without its snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TorrentRevision {
+    pub id: Uuid,
+    pub torrent_id: Uuid,
+
+    /// Revision number, starting at 1 for the state before the first edit
+    pub version: i32,
+
+    /// Editor (None for the original upload)
+    pub editor_id: Option<Uuid>,
+
+    /// Changes against the previous revision
+    pub changes: Vec<FieldChange>,
+
+    /// Edit summary
+    pub summary: Option<String>,
+
+    /// Version this revision restored, for rollbacks
+    pub rollback_of: Option<i32>,
+
+    /// Whether the uploader and snatchers were notified
+    pub significant: bool,
+
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Unread edit notification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditNotification {
+    pub revision_id: Uuid,
+    pub torrent_id: Uuid,
+    pub torrent_name: String,
+    pub version: i32,
+    /// Names of the changed fields
+    pub fields: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Compute the field-level diff between two snapshots
+pub fn diff_snapshots(old: &TorrentSnapshot, new: &TorrentSnapshot) -> Result<Vec<FieldChange>> {
+    let old = serde_json::to_value(old)?;
+    let new = serde_json::to_value(new)?;
+
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        return Err(anyhow!("Metadata did not serialize to an object"));
+    };
+
+    Ok(new
+        .iter()
+        .filter(|(field, _)| field.as_str() != "id")
+        .filter_map(|(field, value)| {
+            let previous = old.get(field).cloned().unwrap_or(serde_json::Value::Null);
+            (previous != *value).then(|| FieldChange {
+                field: field.clone(),
+                old: previous,
+                new: value.clone(),
+            })
+        })
+        .collect())
+}
+
+/// Whether any change touches a significant field
+pub fn is_significant(changes: &[FieldChange]) -> bool {
+    changes
+        .iter()
+        .any(|change| SIGNIFICANT_FIELDS.contains(&change.field.as_str()))
+}
+
+/// Edit history service
+pub struct EditHistoryService {
+    pool: PgPool,
+}
+
+impl EditHistoryService {
+    /// Create a new edit history service
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Current state of a torrent, to edit and pass to [`Self::edit`]
+    ///
+    /// Returns `None` for torrents without metadata.
+    pub async fn current(&self, torrent_id: Uuid) -> Result<Option<TorrentSnapshot>> {
+        let mut conn = self.pool.acquire().await?;
+        Self::load(&mut *conn, torrent_id).await
+    }
+
+    /// Apply an edit and record it as a new revision
+    ///
+    /// Returns `None` if nothing changed.
+    pub async fn edit(
+        &self,
+        updated: &TorrentSnapshot,
+        editor_id: Uuid,
+        summary: Option<String>,
+    ) -> Result<Option<TorrentRevision>> {
+        self.apply(updated, editor_id, summary, None).await
+    }
+
+    /// Restore the state of an earlier revision
+    ///
+    /// The restored state is recorded as a new revision. Returns `None` if
+    /// the torrent is already in that state.
+    pub async fn rollback(
+        &self,
+        torrent_id: Uuid,
+        version: i32,
+        editor_id: Uuid,
+    ) -> Result<Option<TorrentRevision>> {
+        let snapshot = self
+            .get_snapshot(torrent_id, version)
+            .await?
+            .ok_or_else(|| anyhow!("Revision {} not found", version))?;
+
+        self.apply(
+            &snapshot,
+            editor_id,
+            Some(format!("Rolled back to version {}", version)),
+            Some(version),
+        )
+        .await
+    }
+
+    async fn apply(
+        &self,
+        updated: &TorrentSnapshot,
+        editor_id: Uuid,
+        summary: Option<String>,
+        rollback_of: Option<i32>,
+    ) -> Result<Option<TorrentRevision>> {
+        updated.metadata.validate()?;
+        let torrent_id = updated.metadata.id;
+
+        let mut tx = self.pool.begin().await?;
+
+        // Serialize concurrent edits of the same torrent
+        sqlx::query!(
+            "SELECT id FROM torrent_metadata WHERE id = $1 FOR UPDATE",
+            torrent_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| anyhow!("Torrent not found"))?;
+
+        let current = Self::load(&mut *tx, torrent_id)
+            .await?
+            .ok_or_else(|| anyhow!("Torrent not found"))?;
+
+        let changes = diff_snapshots(&current, updated)?;
+        if changes.is_empty() {
+            return Ok(None);
+        }
+
+        let latest = sqlx::query_scalar!(
+            "SELECT MAX(version) FROM torrent_revisions WHERE torrent_id = $1",
+            torrent_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // Torrents edited for the first time keep their uploaded state as version 1
+        let version = match latest {
+            Some(latest) => latest + 1,
+            None => {
+                self.insert_revision(
+                    &mut tx,
+                    &current,
+                    1,
+                    None,
+                    &[],
+                    Some("Original upload".to_string()),
+                    None,
+                )
+                .await?;
+                2
+            }
+        };
+
+        updated.metadata.update(&mut *tx).await?;
+
+        // Keep the listing columns in step with the metadata
+        sqlx::query("UPDATE torrents SET name = $1, description = $2, category = $3 WHERE id = $4")
+            .bind(&updated.metadata.name)
+            .bind(&updated.metadata.description)
+            .bind(&updated.category)
+            .bind(torrent_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let revision = self
+            .insert_revision(
+                &mut tx,
+                updated,
+                version,
+                Some(editor_id),
+                &changes,
+                summary,
+                rollback_of,
+            )
+            .await?;
+
+        if revision.significant {
+            let notified = self.notify(&mut tx, &revision, editor_id).await?;
+            tracing::info!(
+                "Torrent {} version {} notified {} users",
+                revision.torrent_id,
+                revision.version,
+                notified
+            );
+        }
+
+        // Names and categories are searchable
+        sqlx::query!(
+            r#"
+            INSERT INTO search_index_queue (id, torrent_id, status)
+            VALUES ($1, $2, 'pending')
+            ON CONFLICT (torrent_id) DO UPDATE SET status = 'pending'
+            "#,
+            Uuid::new_v4(),
+            torrent_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(revision))
+    }
+
+    /// Load a torrent's metadata and listing category
+    async fn load(conn: &mut PgConnection, torrent_id: Uuid) -> Result<Option<TorrentSnapshot>> {
+        let Some(metadata) = TorrentMetadata::get_by_id(torrent_id, &mut *conn).await? else {
+            return Ok(None);
+        };
+
+        let category: String = sqlx::query("SELECT category FROM torrents WHERE id = $1")
+            .bind(torrent_id)
+            .fetch_one(&mut *conn)
+            .await?
+            .try_get("category")?;
+
+        Ok(Some(TorrentSnapshot { metadata, category }))
+    }
+
+    async fn insert_revision(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        snapshot: &TorrentSnapshot,
+        version: i32,
+        editor_id: Option<Uuid>,
+        changes: &[FieldChange],
+        summary: Option<String>,
+        rollback_of: Option<i32>,
+    ) -> Result<TorrentRevision> {
+        let id = Uuid::new_v4();
+        let significant = is_significant(changes);
+
+        let record = sqlx::query!(
+            r#"
+            INSERT INTO torrent_revisions (
+                id, torrent_id, version, editor_id, snapshot, changes,
+                summary, rollback_of, significant
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING created_at
+            "#,
+            id,
+            snapshot.metadata.id,
+            version,
+            editor_id,
+            serde_json::to_value(snapshot)?,
+            serde_json::to_value(changes)?,
+            summary,
+            rollback_of,
+            significant,
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(TorrentRevision {
+            id,
+            torrent_id: snapshot.metadata.id,
+            version,
+            editor_id,
+            changes: changes.to_vec(),
+            summary,
+            rollback_of,
+            significant,
+            created_at: record.created_at,
+        })
+    }
+
+    /// Queue notifications for the uploader and snatchers
+    async fn notify(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        revision: &TorrentRevision,
+        editor_id: Uuid,
+    ) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO torrent_edit_notifications (revision_id, user_id)
+            SELECT $1, uploader_id FROM torrents WHERE id = $2 AND uploader_id <> $3
+            UNION
+            SELECT $1, user_id FROM snatched WHERE torrent_id = $2 AND user_id <> $3
+            ON CONFLICT DO NOTHING
+            "#,
+            revision.id,
+            revision.torrent_id,
+            editor_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Get the revisions of a torrent, newest first
+    pub async fn history(&self, torrent_id: Uuid) -> Result<Vec<TorrentRevision>> {
+        let records = sqlx::query!(
+            r#"
+            SELECT id, torrent_id, version, editor_id, changes, summary,
+                   rollback_of, significant, created_at
+            FROM torrent_revisions
+            WHERE torrent_id = $1
+            ORDER BY version DESC
+            "#,
+            torrent_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        records
+            .into_iter()
+            .map(|r| {
+                Ok(TorrentRevision {
+                    id: r.id,
+                    torrent_id: r.torrent_id,
+                    version: r.version,
+                    editor_id: r.editor_id,
+                    changes: serde_json::from_value(r.changes)?,
+                    summary: r.summary,
+                    rollback_of: r.rollback_of,
+                    significant: r.significant,
+                    created_at: r.created_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Get the full metadata state of a revision
+    pub async fn get_snapshot(
+        &self,
+        torrent_id: Uuid,
+        version: i32,
+    ) -> Result<Option<TorrentSnapshot>> {
+        let snapshot = sqlx::query_scalar!(
+            r#"
+            SELECT snapshot
+            FROM torrent_revisions
+            WHERE torrent_id = $1 AND version = $2
+            "#,
+            torrent_id,
+            version
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        snapshot
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Get a user's unread edit notifications
+    pub async fn unread_notifications(&self, user_id: Uuid) -> Result<Vec<EditNotification>> {
+        let records = sqlx::query!(
+            r#"
+            SELECT r.id, r.torrent_id, r.version, r.changes, r.created_at,
+                   tm.name as torrent_name
+            FROM torrent_edit_notifications n
+            JOIN torrent_revisions r ON r.id = n.revision_id
+            JOIN torrent_metadata tm ON tm.id = r.torrent_id
+            WHERE n.user_id = $1 AND n.read_at IS NULL
+            ORDER BY r.created_at DESC
+            LIMIT 100
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        records
+            .into_iter()
+            .map(|r| {
+                let changes: Vec<FieldChange> = serde_json::from_value(r.changes)?;
+                Ok(EditNotification {
+                    revision_id: r.id,
+                    torrent_id: r.torrent_id,
+                    torrent_name: r.torrent_name,
+                    version: r.version,
+                    fields: changes.into_iter().map(|c| c.field).collect(),
+                    created_at: r.created_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Mark all of a user's edit notifications as read
+    pub async fn mark_notifications_read(&self, user_id: Uuid) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE torrent_edit_notifications
+            SET read_at = NOW()
+            WHERE user_id = $1 AND read_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::MediaType;
+
+    fn snapshot() -> TorrentSnapshot {
+        let metadata = TorrentMetadata {
+            id: Uuid::new_v4(),
+            name: "Example.Movie.2024.1080p.BluRay.x264-GRP".to_string(),
+            description: Some("A great movie".to_string()),
+            category_id: Uuid::new_v4(),
+            media_type: MediaType::Movie,
+            tags: vec!["action".to_string()],
+            quality: None,
+            external_ids: Default::default(),
+            nfo_content: None,
+            media_urls: Default::default(),
+            year: Some(2024),
+            imdb_rating: None,
+            is_featured: false,
+            is_sticky: false,
+        };
+
+        TorrentSnapshot {
+            metadata,
+            category: "movies".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let old = snapshot();
+        let mut new = old.clone();
+        assert!(diff_snapshots(&old, &new).unwrap().is_empty());
+
+        new.metadata.description = Some("vandalised".to_string());
+        new.metadata.year = Some(2023);
+
+        let changes = diff_snapshots(&old, &new).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].field, "description");
+        assert_eq!(changes[0].old, serde_json::json!("A great movie"));
+        assert_eq!(changes[0].new, serde_json::json!("vandalised"));
+        assert_eq!(changes[1].field, "year");
+        assert!(!is_significant(&changes));
+    }
+
+    #[test]
+    fn test_significant_changes() {
+        let old = snapshot();
+        let mut new = old.clone();
+        new.metadata.name = "Example.Movie.2024.2160p.BluRay.x265-GRP".to_string();
+
+        let changes = diff_snapshots(&old, &new).unwrap();
+        assert!(is_significant(&changes));
+    }
+
+    #[test]
+    fn test_category_is_versioned() {
+        let old = snapshot();
+        let mut new = old.clone();
+        new.category = "tv".to_string();
+
+        let changes = diff_snapshots(&old, &new).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field, "category");
+        assert!(is_significant(&changes));
+
+        // Rollbacks restore the category from the stored snapshot
+        let stored = serde_json::to_value(&old).unwrap();
+        let restored: TorrentSnapshot = serde_json::from_value(stored).unwrap();
+        assert_eq!(restored.category, "movies");
+        assert_eq!(restored.metadata.name, old.metadata.name);
+    }
+}
@@ -6,11 +6,18 @@
 //!
 //! - **Torrent Upload**: Parse and validate .torrent files with bencode support
 //! - **Metadata Management**: Rich metadata including quality indicators, external IDs, and tags
+//! - **Edit History**: Versioned metadata edits with field-level diffs and rollback
+//! - **Tag Taxonomy**: Aliases, implications, bans and merges for voted tags
 //! - **Moderation System**: Three-stage workflow (PENDING → APPROVED/REJECTED/POSTPONED)
+//!   with claims, per-category checklists and SLA escalation
 //! - **File Management**: File validation, sanitization, and media type detection
 //! - **Download Tracking**: Passkey-based downloads with freeleech support
 //! - **Search Integration**: Meilisearch indexing for fast torrent discovery
 //! - **Request/Bounty System**: User requests with pooled bounties
+//! - **Rip Log Verification**: EAC/XLD log scoring and cue sheet checks for lossless music
+//! - **MediaInfo**: Structured track data cross-checked against the release name
+//! - **NFO Handling**: CP437/CP1252/UTF-16 decoding, PNG rendering and link mining
+//! - **Upload Rules**: Admin-editable rulesets per category with structured violations
 //!
 //! # Architecture
 //!
@@ -19,11 +26,18 @@
 //! - `bencode`: BitTorrent bencode parsing and info_hash calculation
 //! - `files`: File list management and validation
 //! - `metadata`: Torrent metadata and quality information
+//! - `history`: Versioned metadata edits and rollback
+//! - `tags`: Tag aliases, implications and bans
 //! - `moderation`: Three-stage moderation workflow
 //! - `upload`: Torrent upload handler
 //! - `download`: Download tracking and permission checks
 //! - `search`: Meilisearch integration
 //! - `requests`: Request/bounty system
+//! - `rules`: Per-category upload rules
+//! - `riplog`: EAC/XLD rip log verification and scoring
+//! - `cue`: Cue sheet parsing
+//! - `mediainfo`: MediaInfo parsing and quality cross-check
+//! - `nfo`: NFO decoding, rendering and external link extraction
 //!
 //! # Example Usage
 //!
@@ -44,6 +58,9 @@
 //!     category_id: uuid::Uuid::new_v4(),
 //!     tags: Some(vec!["example".to_string()]),
 //!     nfo_content: None,
+//!     rip_logs: None,
+//!     cue_sheets: None,
+//!     mediainfo: None,
 //!     tmdb_id: None,
 //!     imdb_id: None,
 //!     tvdb_id: None,
@@ -62,12 +79,19 @@
 //! ```
 
 pub mod bencode;
+pub mod cue;
 pub mod download;
 pub mod files;
+pub mod history;
+pub mod mediainfo;
 pub mod metadata;
 pub mod moderation;
+pub mod nfo;
 pub mod requests;
+pub mod riplog;
+pub mod rules;
 pub mod search;
+pub mod tags;
 pub mod upload;
 
 use anyhow::Result;
@@ -78,11 +102,28 @@ use sqlx::PgPool;
 pub use bencode::{Torrent, TorrentInfo};
 pub use download::{DownloadService, FreeleechType};
 pub use files::{FileType, MediaType as FileMediaType, TorrentFileInfo};
+pub use history::{
+    EditHistoryService, EditNotification, FieldChange, TorrentRevision, TorrentSnapshot,
+};
+pub use mediainfo::{MediaInfoReport, MediaInfoService, QualityMismatch};
 pub use metadata::{MediaType, QualityInfo, TorrentMetadata};
-pub use moderation::{ModerationService, ModerationStatus};
-pub use requests::{RequestService, RequestStatus, TorrentRequest};
+pub use moderation::{
+    ChecklistAnswer, ModerationChecklist, ModerationService, ModerationStats, ModerationStatus,
+    SlaPolicy,
+};
+pub use nfo::{NfoDocument, NfoEncoding, NfoLinks, NfoService, RenderOptions, TmdbKind};
+pub use requests::{
+    FillMismatch, FillStatus, QualityRequirements, RejectFillInput, RequestFill, RequestService,
+    RequestStatus, TorrentRequest,
+};
+pub use riplog::{can_trump, ChecksumStatus, RipLogService, RipSummary, RipVerification};
+pub use rules::{
+    CompiledRuleSet, RuleViolation, Severity, StoredRuleSet, UploadRuleSet, UploadRulesService,
+    ViolationCode,
+};
 pub use search::SearchService;
-pub use upload::{UploadRequest, UploadResponse, UploadService};
+pub use tags::{MergeReport, TagTaxonomy, TagTaxonomyService};
+pub use upload::{CheckedTorrent, UploadRejected, UploadRequest, UploadResponse, UploadService};
 
 /// Torrent service configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -96,10 +137,16 @@ pub struct TorrentConfig {
     /// Auto-approval rules
     pub auto_approval: moderation::AutoApprovalRules,
 
+    /// Moderation queue SLA and claim settings
+    pub moderation_sla: moderation::SlaPolicy,
+
     /// Request/bounty settings
     pub request_min_bounty: i64,
     pub request_max_bounty_per_user: i64,
     pub request_expiry_days: i64,
+    pub request_fill_challenge_hours: i64,
+    /// Share of the bounty pool refunded when a request expires (0-100)
+    pub request_expiry_refund_percent: i64,
 
     /// Search settings
     pub meilisearch_url: String,
@@ -113,9 +160,12 @@ impl Default for TorrentConfig {
             min_ratio: 0.5,
             ratio_watch_threshold: 0.75,
             auto_approval: moderation::AutoApprovalRules::default(),
+            moderation_sla: moderation::SlaPolicy::default(),
             request_min_bounty: 100,
             request_max_bounty_per_user: 100000,
             request_expiry_days: 90,
+            request_fill_challenge_hours: 72,
+            request_expiry_refund_percent: 100,
             meilisearch_url: "http://localhost:7700".to_string(),
             meilisearch_api_key: "masterKey".to_string(),
             meilisearch_index: "torrents".to_string(),
@@ -135,6 +185,7 @@ pub struct TorrentService {
     moderation: ModerationService,
     search: SearchService,
     requests: RequestService,
+    history: EditHistoryService,
 }
 
 impl TorrentService {
@@ -151,19 +202,24 @@ impl TorrentService {
             config.min_ratio,
             config.ratio_watch_threshold,
         );
-        let moderation = ModerationService::new(pool.clone());
+        let moderation =
+            ModerationService::new(pool.clone()).with_sla(config.moderation_sla.clone());
         let search = SearchService::new(
             pool.clone(),
             &config.meilisearch_url,
             &config.meilisearch_api_key,
             config.meilisearch_index.clone(),
-        );
+        )
+        .with_tags(upload.tags().clone());
         let requests = RequestService::new(
             pool.clone(),
             config.request_min_bounty,
             config.request_max_bounty_per_user,
             config.request_expiry_days,
+            config.request_fill_challenge_hours,
+            config.request_expiry_refund_percent,
         );
+        let history = EditHistoryService::new(pool.clone());
 
         Ok(Self {
             pool,
@@ -173,6 +229,7 @@ impl TorrentService {
             moderation,
             search,
             requests,
+            history,
         })
     }
 
@@ -201,6 +258,36 @@ impl TorrentService {
         &self.requests
     }
 
+    /// Get edit history service
+    pub fn history(&self) -> &EditHistoryService {
+        &self.history
+    }
+
+    /// Get tag taxonomy service
+    pub fn tags(&self) -> &TagTaxonomyService {
+        self.upload.tags()
+    }
+
+    /// Get upload rules service
+    pub fn upload_rules(&self) -> &UploadRulesService {
+        self.upload.rules()
+    }
+
+    /// Get rip log service
+    pub fn rip_logs(&self) -> &RipLogService {
+        self.upload.rip_logs()
+    }
+
+    /// Get MediaInfo service
+    pub fn mediainfo(&self) -> &MediaInfoService {
+        self.upload.mediainfo()
+    }
+
+    /// Get NFO service
+    pub fn nfo(&self) -> &NfoService {
+        self.upload.nfo()
+    }
+
     /// Get database pool
     pub fn pool(&self) -> &PgPool {
         &self.pool
@@ -236,8 +323,11 @@ impl TorrentService {
         torrent_id: uuid::Uuid,
         moderator_id: uuid::Uuid,
         reason: Option<String>,
+        checklist: Vec<ChecklistAnswer>,
     ) -> Result<()> {
-        self.moderation.approve_torrent(torrent_id, moderator_id, reason).await
+        self.moderation
+            .approve_torrent(torrent_id, moderator_id, reason, checklist)
+            .await
     }
 
     /// Initialize search index (convenience method)
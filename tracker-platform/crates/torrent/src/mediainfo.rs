@@ -0,0 +1,664 @@
+//! MediaInfo ingestion
+//!
+//! Uploaders paste the text output of MediaInfo for the main video file. It
+//! is parsed into structured track data and cross-checked against the
+//! quality guessed from the release name; mismatches (a name claiming 2160p
+//! on a 1080p file, DTS-HD MA on an AC-3 track, ...) hold the upload for
+//! moderation. The parsed tracks are stored with the torrent and indexed for
+//! search.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{metadata::QualityInfo, rules::QualityField};
+
+/// Parsed MediaInfo report
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaInfoReport {
+    /// General section
+    pub general: GeneralInfo,
+
+    /// Video tracks
+    pub video: Vec<VideoTrack>,
+
+    /// Audio tracks
+    pub audio: Vec<AudioTrack>,
+
+    /// Subtitle tracks
+    pub subtitles: Vec<SubtitleTrack>,
+}
+
+/// General (container) information
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GeneralInfo {
+    /// Container format (e.g., "Matroska")
+    pub format: Option<String>,
+
+    /// Duration as written by MediaInfo
+    pub duration: Option<String>,
+
+    /// Overall bitrate in kbps
+    pub overall_bitrate: Option<i32>,
+}
+
+/// Video track
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VideoTrack {
+    /// Format (e.g., "AVC", "HEVC")
+    pub format: Option<String>,
+
+    /// Width in pixels
+    pub width: Option<u32>,
+
+    /// Height in pixels
+    pub height: Option<u32>,
+
+    /// Bitrate in kbps
+    pub bitrate: Option<i32>,
+
+    /// Frame rate as written by MediaInfo
+    pub frame_rate: Option<String>,
+
+    /// Bit depth
+    pub bit_depth: Option<u8>,
+
+    /// HDR format (e.g., "Dolby Vision, Version 1.0, ...")
+    pub hdr_format: Option<String>,
+}
+
+/// Audio track
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioTrack {
+    /// Format (e.g., "E-AC-3 JOC", "DTS XLL")
+    pub format: Option<String>,
+
+    /// Commercial name (e.g., "Dolby TrueHD with Dolby Atmos")
+    pub commercial_name: Option<String>,
+
+    /// Channel count
+    pub channels: Option<u8>,
+
+    /// Bitrate in kbps
+    pub bitrate: Option<i32>,
+
+    /// Language
+    pub language: Option<String>,
+
+    /// Track title
+    pub title: Option<String>,
+}
+
+/// Subtitle track
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleTrack {
+    /// Format (e.g., "UTF-8", "PGS")
+    pub format: Option<String>,
+
+    /// Language
+    pub language: Option<String>,
+
+    /// Track title
+    pub title: Option<String>,
+
+    /// Forced subtitles
+    pub forced: bool,
+}
+
+/// A quality claim in the release name that the file does not back up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QualityMismatch {
+    /// Quality field
+    pub field: QualityField,
+
+    /// Value claimed by the release name
+    pub claimed: String,
+
+    /// Value found in MediaInfo
+    pub actual: String,
+}
+
+#[derive(Clone, Copy)]
+enum Section {
+    General,
+    Video,
+    Audio,
+    Text,
+    Other,
+}
+
+/// Parse a number followed by a unit, allowing MediaInfo's digit grouping
+/// ("1 920 pixels", "24.5 Mb/s")
+fn parse_number(value: &str) -> Option<f64> {
+    let digits: String = value
+        .split(|c: char| c.is_alphabetic() || c == '/')
+        .next()?
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Parse a bitrate into kbps
+fn parse_bitrate(value: &str) -> Option<i32> {
+    let number = parse_number(value)?;
+    let kbps = if value.contains("Mb/s") {
+        number * 1000.0
+    } else if value.contains("Gb/s") {
+        number * 1_000_000.0
+    } else if value.contains("kb/s") {
+        number
+    } else {
+        number / 1000.0
+    };
+    Some(kbps.round() as i32)
+}
+
+impl MediaInfoReport {
+    /// Parse MediaInfo text output
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut report = MediaInfoReport::default();
+        let mut section = None;
+
+        for raw in text.lines() {
+            let line = raw.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(':') else {
+                // Section header ("Video", "Audio #2", "Text #1", ...)
+                let name = line.split_whitespace().next().unwrap_or("");
+                section = Some(match name {
+                    "General" => Section::General,
+                    "Video" => {
+                        report.video.push(VideoTrack::default());
+                        Section::Video
+                    }
+                    "Audio" => {
+                        report.audio.push(AudioTrack::default());
+                        Section::Audio
+                    }
+                    "Text" => {
+                        report.subtitles.push(SubtitleTrack::default());
+                        Section::Text
+                    }
+                    _ => Section::Other,
+                });
+                continue;
+            };
+
+            let key = key.trim();
+            let value = value.trim();
+            let text = Some(value.to_string());
+
+            match section {
+                Some(Section::General) => match key {
+                    "Format" => report.general.format = text,
+                    "Duration" => report.general.duration = text,
+                    "Overall bit rate" => report.general.overall_bitrate = parse_bitrate(value),
+                    _ => {}
+                },
+                Some(Section::Video) => {
+                    let track = report.video.last_mut().expect("video section has a track");
+                    match key {
+                        "Format" => track.format = text,
+                        "Width" => track.width = parse_number(value).map(|n| n as u32),
+                        "Height" => track.height = parse_number(value).map(|n| n as u32),
+                        "Bit rate" => track.bitrate = parse_bitrate(value),
+                        "Frame rate" => track.frame_rate = text,
+                        "Bit depth" => track.bit_depth = parse_number(value).map(|n| n as u8),
+                        "HDR format" => track.hdr_format = text,
+                        _ => {}
+                    }
+                }
+                Some(Section::Audio) => {
+                    let track = report.audio.last_mut().expect("audio section has a track");
+                    match key {
+                        "Format" => track.format = text,
+                        "Commercial name" => track.commercial_name = text,
+                        "Channel(s)" => track.channels = parse_number(value).map(|n| n as u8),
+                        "Bit rate" => track.bitrate = parse_bitrate(value),
+                        "Language" => track.language = text,
+                        "Title" => track.title = text,
+                        _ => {}
+                    }
+                }
+                Some(Section::Text) => {
+                    let track = report.subtitles.last_mut().expect("text section has a track");
+                    match key {
+                        "Format" => track.format = text,
+                        "Language" => track.language = text,
+                        "Title" => track.title = text,
+                        "Forced" => track.forced = value.eq_ignore_ascii_case("yes"),
+                        _ => {}
+                    }
+                }
+                Some(Section::Other) => {}
+                None => return Err(anyhow!("MediaInfo text must start with a section header")),
+            }
+        }
+
+        if report.video.is_empty() && report.audio.is_empty() {
+            return Err(anyhow!("MediaInfo contains no video or audio tracks"));
+        }
+
+        Ok(report)
+    }
+
+    /// Main video track
+    pub fn primary_video(&self) -> Option<&VideoTrack> {
+        self.video.first()
+    }
+
+    /// Resolution class of the main video track (e.g., "1080p")
+    ///
+    /// Uses the width as well as the height so cropped scope releases
+    /// (1920x800) still count as 1080p.
+    pub fn resolution(&self) -> Option<&'static str> {
+        let video = self.primary_video()?;
+        let width = video.width.unwrap_or(0);
+        let height = video.height.unwrap_or(0);
+
+        let resolution = if width >= 3200 || height >= 1800 {
+            "2160p"
+        } else if width >= 1700 || height >= 1000 {
+            "1080p"
+        } else if width >= 1100 || height >= 700 {
+            "720p"
+        } else if height > 480 {
+            "576p"
+        } else if height > 360 {
+            "480p"
+        } else if height > 0 {
+            "360p"
+        } else {
+            return None;
+        };
+
+        Some(resolution)
+    }
+
+    /// Video codec of the main track, in release-name terms
+    pub fn video_codec(&self) -> Option<&'static str> {
+        match self.primary_video()?.format.as_deref()? {
+            "AVC" => Some("H.264"),
+            "HEVC" => Some("H.265"),
+            "AV1" => Some("AV1"),
+            "VP9" => Some("VP9"),
+            "MPEG-4 Visual" => Some("XviD"),
+            _ => None,
+        }
+    }
+
+    /// Container, in release-name terms
+    pub fn container(&self) -> Option<&'static str> {
+        match self.general.format.as_deref()? {
+            "Matroska" => Some("MKV"),
+            "MPEG-4" => Some("MP4"),
+            "AVI" => Some("AVI"),
+            _ => None,
+        }
+    }
+
+    /// Distinct audio codecs, in release-name terms where known
+    pub fn audio_codecs(&self) -> Vec<String> {
+        distinct(self.audio.iter().map(audio_codec))
+    }
+
+    /// Distinct audio languages
+    pub fn audio_languages(&self) -> Vec<String> {
+        distinct(self.audio.iter().filter_map(|t| t.language.clone()))
+    }
+
+    /// Distinct subtitle languages
+    pub fn subtitle_languages(&self) -> Vec<String> {
+        distinct(self.subtitles.iter().filter_map(|t| t.language.clone()))
+    }
+
+    /// Cross-check the quality guessed from the release name
+    pub fn cross_check(&self, claimed: &QualityInfo) -> Vec<QualityMismatch> {
+        let mut mismatches = Vec::new();
+        let mut check = |field: QualityField, claimed: &Option<String>, actual: Option<String>, ok: bool| {
+            if let (Some(claimed), Some(actual)) = (claimed, actual) {
+                if !ok {
+                    mismatches.push(QualityMismatch {
+                        field,
+                        claimed: claimed.clone(),
+                        actual,
+                    });
+                }
+            }
+        };
+
+        let resolution = self.resolution();
+        check(
+            QualityField::Resolution,
+            &claimed.resolution,
+            resolution.map(str::to_string),
+            claimed.resolution.as_deref() == resolution,
+        );
+
+        let video_codec = self.video_codec();
+        check(
+            QualityField::VideoCodec,
+            &claimed.video_codec,
+            video_codec.map(str::to_string),
+            claimed.video_codec.as_deref() == video_codec,
+        );
+
+        let container = self.container();
+        check(
+            QualityField::Container,
+            &claimed.container,
+            container.map(str::to_string),
+            claimed.container.as_deref() == container,
+        );
+
+        // Audio claims are satisfied by any track
+        if let Some(ref codec) = claimed.audio_codec {
+            let actual: Vec<_> = self.audio.iter().map(audio_codec).collect();
+            if !actual.is_empty() && !actual.iter().any(|a| audio_codec_satisfies(codec, a)) {
+                mismatches.push(QualityMismatch {
+                    field: QualityField::AudioCodec,
+                    claimed: codec.clone(),
+                    actual: actual.join(", "),
+                });
+            }
+        }
+
+        if let Some(ref channels) = claimed.audio_channels {
+            let actual: Vec<_> = self
+                .audio
+                .iter()
+                .filter_map(|t| t.channels.map(channel_layout))
+                .collect();
+            if !actual.is_empty() && !actual.contains(channels) {
+                mismatches.push(QualityMismatch {
+                    field: QualityField::AudioChannels,
+                    claimed: channels.clone(),
+                    actual: actual.join(", "),
+                });
+            }
+        }
+
+        if let Some(ref hdr) = claimed.hdr {
+            let formats = self
+                .video
+                .iter()
+                .filter_map(|v| v.hdr_format.as_deref())
+                .collect::<Vec<_>>()
+                .join(" / ");
+            let ok = match hdr.as_str() {
+                "Dolby Vision" => formats.contains("Dolby Vision"),
+                "HDR10+" => formats.contains("HDR10+") || formats.contains("SMPTE ST 2094"),
+                _ => formats.contains("HDR10") || formats.contains("SMPTE ST 2086"),
+            };
+            if !ok && self.primary_video().is_some() {
+                mismatches.push(QualityMismatch {
+                    field: QualityField::Hdr,
+                    claimed: hdr.clone(),
+                    actual: if formats.is_empty() {
+                        "SDR".to_string()
+                    } else {
+                        formats
+                    },
+                });
+            }
+        }
+
+        mismatches
+    }
+}
+
+fn distinct(values: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = Vec::new();
+    for value in values {
+        if !seen.contains(&value) {
+            seen.push(value);
+        }
+    }
+    seen
+}
+
+/// Channel count as a release-name layout (6 -> "5.1")
+fn channel_layout(channels: u8) -> String {
+    match channels {
+        0..=2 => format!("{}.0", channels),
+        n => format!("{}.1", n - 1),
+    }
+}
+
+/// Audio track codec, in release-name terms where known
+fn audio_codec(track: &AudioTrack) -> String {
+    let format = track.format.as_deref().unwrap_or("");
+    let commercial = track.commercial_name.as_deref().unwrap_or("");
+
+    let codec = if commercial.contains("Atmos") || format.contains("JOC") || format.contains("16-ch") {
+        "Dolby Atmos"
+    } else if format.starts_with("MLP FBA") {
+        "TrueHD"
+    } else if format == "DTS XLL" || commercial.contains("DTS-HD Master Audio") {
+        "DTS-HD MA"
+    } else if format.starts_with("DTS") {
+        "DTS"
+    } else if format == "AC-3" || format == "E-AC-3" {
+        "AC3"
+    } else if format.starts_with("AAC") {
+        "AAC"
+    } else if format == "MPEG Audio" {
+        "MP3"
+    } else {
+        format
+    };
+
+    codec.to_string()
+}
+
+/// Whether a track codec backs up a claimed codec
+///
+/// Claims of a core codec are satisfied by its lossless extension (a
+/// DTS-HD MA track carries a DTS core).
+fn audio_codec_satisfies(claimed: &str, actual: &str) -> bool {
+    claimed.eq_ignore_ascii_case(actual)
+        || (claimed == "DTS" && actual == "DTS-HD MA")
+        || (claimed == "TrueHD" && actual == "Dolby Atmos")
+}
+
+/// MediaInfo stored for a torrent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredMediaInfo {
+    /// Torrent ID
+    pub torrent_id: Uuid,
+
+    /// Parsed report
+    pub report: sqlx::types::Json<MediaInfoReport>,
+
+    /// Mismatches against the release name
+    pub mismatches: sqlx::types::Json<Vec<QualityMismatch>>,
+
+    /// Original MediaInfo text
+    pub raw_text: String,
+}
+
+/// MediaInfo service
+pub struct MediaInfoService {
+    pool: PgPool,
+}
+
+impl MediaInfoService {
+    /// Create a new MediaInfo service
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store MediaInfo for a torrent
+    ///
+    /// Runs in the upload's transaction, so a torrent is never visible
+    /// without its MediaInfo.
+    pub async fn store(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        torrent_id: Uuid,
+        raw_text: &str,
+        report: &MediaInfoReport,
+        mismatches: &[QualityMismatch],
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO torrent_mediainfo (torrent_id, raw_text, report, mismatches)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (torrent_id) DO UPDATE
+            SET raw_text = $2, report = $3, mismatches = $4, updated_at = NOW()
+            "#,
+            torrent_id,
+            raw_text,
+            serde_json::to_value(report)?,
+            serde_json::to_value(mismatches)?,
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the MediaInfo stored for a torrent
+    pub async fn get(&self, torrent_id: Uuid) -> Result<Option<StoredMediaInfo>> {
+        let stored = sqlx::query_as!(
+            StoredMediaInfo,
+            r#"
+            SELECT
+                torrent_id,
+                report as "report: sqlx::types::Json<MediaInfoReport>",
+                mismatches as "mismatches: sqlx::types::Json<Vec<QualityMismatch>>",
+                raw_text
+            FROM torrent_mediainfo
+            WHERE torrent_id = $1
+            "#,
+            torrent_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(stored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::parse_quality_from_name;
+
+    const SAMPLE: &str = "General
+Unique ID                                : 1234567890
+Complete name                            : Sample.Movie.2023.2160p.UHD.BluRay.x265.mkv
+Format                                   : Matroska
+Format version                           : Version 4
+File size                                : 58.3 GiB
+Duration                                 : 2 h 10 min
+Overall bit rate                         : 64.2 Mb/s
+
+Video
+ID                                       : 1
+Format                                   : HEVC
+Format/Info                              : High Efficiency Video Coding
+HDR format                               : SMPTE ST 2086, HDR10 compatible
+Bit rate                                 : 58.1 Mb/s
+Width                                    : 3 840 pixels
+Height                                   : 1 600 pixels
+Frame rate                               : 23.976 (24000/1001) FPS
+Bit depth                                : 10 bits
+
+Audio #1
+ID                                       : 2
+Format                                   : MLP FBA 16-ch
+Commercial name                          : Dolby TrueHD with Dolby Atmos
+Bit rate                                 : 4 877 kb/s
+Channel(s)                               : 8 channels
+Language                                 : English
+
+Audio #2
+ID                                       : 3
+Format                                   : AC-3
+Bit rate                                 : 640 kb/s
+Channel(s)                               : 6 channels
+Language                                 : French
+
+Text #1
+ID                                       : 4
+Format                                   : PGS
+Language                                 : English
+Forced                                   : No
+
+Text #2
+ID                                       : 5
+Format                                   : PGS
+Language                                 : French
+Forced                                   : Yes
+";
+
+    #[test]
+    fn test_parse_mediainfo() {
+        let report = MediaInfoReport::parse(SAMPLE).unwrap();
+
+        assert_eq!(report.general.format.as_deref(), Some("Matroska"));
+        assert_eq!(report.general.overall_bitrate, Some(64200));
+
+        let video = report.primary_video().unwrap();
+        assert_eq!(video.width, Some(3840));
+        assert_eq!(video.height, Some(1600));
+        assert_eq!(video.bitrate, Some(58100));
+        assert_eq!(video.bit_depth, Some(10));
+        assert_eq!(report.resolution(), Some("2160p"));
+        assert_eq!(report.video_codec(), Some("H.265"));
+
+        assert_eq!(report.audio.len(), 2);
+        assert_eq!(report.audio[0].channels, Some(8));
+        assert_eq!(report.audio[1].bitrate, Some(640));
+        assert_eq!(report.audio_languages(), vec!["English", "French"]);
+
+        assert_eq!(report.subtitles.len(), 2);
+        assert!(report.subtitles[1].forced);
+    }
+
+    #[test]
+    fn test_cross_check_matching_name() {
+        let report = MediaInfoReport::parse(SAMPLE).unwrap();
+        let claimed = parse_quality_from_name("Sample.Movie.2023.2160p.BluRay.HDR10.Atmos.7.1.x265-GRP");
+
+        assert!(report.cross_check(&claimed).is_empty());
+    }
+
+    #[test]
+    fn test_cross_check_flags_mismatches() {
+        let mut report = MediaInfoReport::parse(SAMPLE).unwrap();
+        report.video[0].width = Some(1920);
+        report.video[0].height = Some(800);
+
+        let claimed = parse_quality_from_name("Sample.Movie.2023.2160p.BluRay.DTS-HD.MA.5.1.x264-GRP");
+        let fields: Vec<_> = report.cross_check(&claimed).into_iter().map(|m| m.field).collect();
+
+        assert_eq!(
+            fields,
+            vec![
+                QualityField::Resolution,
+                QualityField::VideoCodec,
+                QualityField::AudioCodec,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert!(MediaInfoReport::parse("not mediainfo").is_err());
+        assert!(MediaInfoReport::parse("Width : 1920").is_err());
+    }
+
+    #[test]
+    fn test_channel_layout() {
+        assert_eq!(channel_layout(2), "2.0");
+        assert_eq!(channel_layout(6), "5.1");
+        assert_eq!(channel_layout(8), "7.1");
+    }
+}
@@ -4,9 +4,9 @@
 //! tags, media type detection, quality indicators, external ID linking, NFO parsing,
 //! and media storage.
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Postgres, Transaction};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 use validator::Validate;
 
@@ -189,6 +189,9 @@ pub struct Tag {
     /// Net score (upvotes - downvotes)
     pub score: i32,
 
+    /// Score with older votes decayed, used for ranking
+    pub weight: f64,
+
     /// Timestamp when added
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
@@ -370,24 +373,10 @@ pub fn parse_quality_from_name(name: &str) -> QualityInfo {
 
 /// Parse NFO file content
 ///
-/// NFO files contain release information in ASCII art format
+/// NFO files contain release information in ASCII art format, usually in
+/// CP437. See [`crate::nfo`] for encoding detection.
 pub fn parse_nfo(content: &[u8]) -> Result<String> {
-    // Try UTF-8 first
-    if let Ok(text) = std::str::from_utf8(content) {
-        return Ok(text.to_string());
-    }
-
-    // Try Windows-1252 (CP-1252) encoding for legacy NFO files
-    let text = content
-        .iter()
-        .map(|&b| b as char)
-        .collect::<String>();
-
-    if text.is_empty() {
-        return Err(anyhow!("NFO file is empty"));
-    }
-
-    Ok(text)
+    crate::nfo::decode_nfo(content).map(|nfo| nfo.text)
 }
 
 /// Extract media type from category and file analysis
@@ -472,8 +461,14 @@ impl TorrentMetadata {
         Ok(())
     }
 
-    /// Update torrent metadata
-    pub async fn update(&self, pool: &PgPool) -> Result<()> {
+    /// Update torrent metadata in place
+    ///
+    /// Edits should go through [`crate::history::EditHistoryService`] so
+    /// they are versioned.
+    pub async fn update<'e, E>(&self, executor: E) -> Result<()>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         sqlx::query!(
             r#"
             UPDATE torrent_metadata
@@ -498,14 +493,17 @@ impl TorrentMetadata {
             self.is_featured,
             self.is_sticky,
         )
-        .execute(pool)
+        .execute(executor)
         .await?;
 
         Ok(())
     }
 
     /// Get metadata by torrent ID
-    pub async fn get_by_id(id: Uuid, pool: &PgPool) -> Result<Option<Self>> {
+    pub async fn get_by_id<'e, E>(id: Uuid, executor: E) -> Result<Option<Self>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         let record = sqlx::query!(
             r#"
             SELECT id, name, description, category_id, media_type as "media_type: MediaType",
@@ -516,7 +514,7 @@ impl TorrentMetadata {
             "#,
             id
         )
-        .fetch_optional(pool)
+        .fetch_optional(executor)
         .await?;
 
         match record {
@@ -552,15 +550,20 @@ pub struct TagService;
 
 impl TagService {
     /// Add or vote on a tag
+    ///
+    /// The vote is recorded against the tag's canonical name; banned tags
+    /// are rejected.
     pub async fn vote_tag(
         torrent_id: Uuid,
         user_id: Uuid,
         tag_name: &str,
         vote: TagVote,
+        taxonomy: &crate::tags::TagTaxonomy,
         tx: &mut Transaction<'_, Postgres>,
     ) -> Result<()> {
-        // Normalize tag name (lowercase, trim)
-        let normalized_tag = tag_name.trim().to_lowercase();
+        let normalized_tag = taxonomy
+            .canonical(tag_name)
+            .ok_or_else(|| anyhow::anyhow!("Tag is not allowed: {}", tag_name))?;
 
         // Insert or update vote
         let vote_value = match vote {
@@ -587,6 +590,8 @@ impl TagService {
     }
 
     /// Get tags for a torrent with scores
+    ///
+    /// Tags are ranked by their decayed weight; see [`crate::tags::decayed_weight`].
     pub async fn get_tags(torrent_id: Uuid, pool: &PgPool) -> Result<Vec<Tag>> {
         let records = sqlx::query!(
             r#"
@@ -596,15 +601,19 @@ impl TagService {
                 SUM(CASE WHEN vote = 1 THEN 1 ELSE 0 END)::int as upvotes,
                 SUM(CASE WHEN vote = -1 THEN 1 ELSE 0 END)::int as downvotes,
                 SUM(vote)::int as score,
+                SUM(
+                    vote * power(0.5, EXTRACT(EPOCH FROM NOW() - updated_at) / 86400.0 / $2)
+                )::float8 as "weight!",
                 MIN(created_at) as created_at
             FROM torrent_tags
             WHERE torrent_id = $1
             GROUP BY tag_name
             HAVING SUM(vote) > -5
-            ORDER BY SUM(vote) DESC, COUNT(*) DESC
+            ORDER BY "weight!" DESC, COUNT(*) DESC
             LIMIT 50
             "#,
-            torrent_id
+            torrent_id,
+            crate::tags::VOTE_HALF_LIFE_DAYS
         )
         .fetch_all(pool)
         .await?;
@@ -617,6 +626,7 @@ impl TagService {
                 upvotes: r.upvotes.unwrap_or(0),
                 downvotes: r.downvotes.unwrap_or(0),
                 score: r.score.unwrap_or(0),
+                weight: r.weight,
                 created_at: r.created_at.unwrap(),
             })
             .collect())
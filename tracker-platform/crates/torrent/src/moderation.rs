@@ -7,8 +7,14 @@
 //! - POSTPONED: Needs more information or review
 //!
 //! Includes auto-approval for trusted uploaders and duplicate detection.
+//!
+//! Moderators claim queue items before reviewing them and work through the
+//! category's checklist; the answers are recorded with the action. Items
+//! that wait longer than the SLA target count as breaches and are escalated
+//! to the top of the queue.
 
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
@@ -65,6 +71,13 @@ pub struct ModerationAction {
     /// Rejection category (if rejected)
     pub rejection_category: Option<RejectionCategory>,
 
+    /// Checklist answers recorded with the action
+    #[serde(default)]
+    pub checklist: Vec<ChecklistAnswer>,
+
+    /// Seconds the torrent had been waiting when the action was taken
+    pub queue_seconds: Option<i64>,
+
     /// Timestamp
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
@@ -135,6 +148,149 @@ impl Default for AutoApprovalRules {
     }
 }
 
+/// A single item on a moderation checklist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChecklistItem {
+    /// Stable key answers refer to
+    pub key: String,
+
+    /// Text shown to the moderator
+    pub label: String,
+
+    /// Must be passed before the torrent can be approved
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl ChecklistItem {
+    fn new(key: &str, label: &str, required: bool) -> Self {
+        Self {
+            key: key.to_string(),
+            label: label.to_string(),
+            required,
+        }
+    }
+}
+
+/// A moderator's answer to a checklist item
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChecklistAnswer {
+    /// Item key
+    pub key: String,
+
+    /// Whether the torrent passes this item
+    pub passed: bool,
+
+    /// Optional note
+    pub note: Option<String>,
+}
+
+/// Moderation checklist for a category
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModerationChecklist {
+    pub items: Vec<ChecklistItem>,
+}
+
+impl Default for ModerationChecklist {
+    fn default() -> Self {
+        Self {
+            items: vec![
+                ChecklistItem::new("name", "Release name follows the naming rules", true),
+                ChecklistItem::new("files", "File list matches the release", true),
+                ChecklistItem::new("not_duplicate", "Not a duplicate of an existing torrent", true),
+                ChecklistItem::new("description", "Description and media info are complete", false),
+            ],
+        }
+    }
+}
+
+impl ModerationChecklist {
+    /// Check that an item list is usable
+    pub fn validate(&self) -> Result<()> {
+        for (i, item) in self.items.iter().enumerate() {
+            if item.key.trim().is_empty() {
+                return Err(anyhow!("Checklist item {} has no key", i + 1));
+            }
+            if self.items[..i].iter().any(|other| other.key == item.key) {
+                return Err(anyhow!("Duplicate checklist item: {}", item.key));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check answers against this checklist
+    ///
+    /// Answers must refer to known items. Approval additionally requires
+    /// every required item to be answered as passed.
+    pub fn check_answers(&self, answers: &[ChecklistAnswer], approving: bool) -> Result<()> {
+        for answer in answers {
+            if !self.items.iter().any(|item| item.key == answer.key) {
+                return Err(anyhow!("Unknown checklist item: {}", answer.key));
+            }
+        }
+
+        if approving {
+            let missing: Vec<&str> = self
+                .items
+                .iter()
+                .filter(|item| item.required)
+                .filter(|item| !answers.iter().any(|a| a.key == item.key && a.passed))
+                .map(|item| item.label.as_str())
+                .collect();
+
+            if !missing.is_empty() {
+                return Err(anyhow!("Checklist incomplete: {}", missing.join(", ")));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Queue SLA and claim settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlaPolicy {
+    /// How long a claim lasts without action
+    pub claim_minutes: i32,
+
+    /// Target time from upload to decision
+    pub target_hours: i32,
+
+    /// Time in queue after which a pending torrent is escalated
+    pub escalate_hours: i32,
+
+    /// Window for per-moderator throughput statistics
+    pub stats_window_days: i32,
+}
+
+impl Default for SlaPolicy {
+    fn default() -> Self {
+        Self {
+            claim_minutes: 60,
+            target_hours: 24,
+            escalate_hours: 48,
+            stats_window_days: 30,
+        }
+    }
+}
+
+impl SlaPolicy {
+    /// Whether an item waiting this long breaches the target
+    pub fn is_breached(&self, queue_seconds: i64) -> bool {
+        queue_seconds > i64::from(self.target_hours) * 3600
+    }
+}
+
+/// A moderator's claim on a queue item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationClaim {
+    pub torrent_id: Uuid,
+    pub moderator_id: Uuid,
+    pub claimed_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
 /// Duplicate detection result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DuplicateCheck {
@@ -151,12 +307,27 @@ pub struct DuplicateCheck {
 /// Moderation service
 pub struct ModerationService {
     pool: PgPool,
+    sla: SlaPolicy,
 }
 
 impl ModerationService {
     /// Create new moderation service
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            sla: SlaPolicy::default(),
+        }
+    }
+
+    /// Use a custom SLA policy
+    pub fn with_sla(mut self, sla: SlaPolicy) -> Self {
+        self.sla = sla;
+        self
+    }
+
+    /// Get the SLA policy
+    pub fn sla(&self) -> &SlaPolicy {
+        &self.sla
     }
 
     /// Check if user qualifies for auto-approval
@@ -285,25 +456,20 @@ impl ModerationService {
     }
 
     /// Approve torrent
+    ///
+    /// Every required checklist item must be answered as passed.
     pub async fn approve_torrent(
         &self,
         torrent_id: Uuid,
         moderator_id: Uuid,
         reason: Option<String>,
+        checklist: Vec<ChecklistAnswer>,
     ) -> Result<()> {
         let mut tx = self.pool.begin().await?;
 
-        // Get current status
-        let current = sqlx::query!(
-            r#"
-            SELECT moderation_status as "status: ModerationStatus"
-            FROM torrents
-            WHERE id = $1
-            "#,
-            torrent_id
-        )
-        .fetch_one(&mut *tx)
-        .await?;
+        let current = self
+            .begin_action(&mut tx, torrent_id, moderator_id, &checklist, true)
+            .await?;
 
         // Update torrent status
         sqlx::query!(
@@ -324,18 +490,23 @@ impl ModerationService {
         sqlx::query!(
             r#"
             INSERT INTO moderation_actions (
-                id, torrent_id, moderator_id, previous_status, new_status, reason
-            ) VALUES ($1, $2, $3, $4, 'approved', $5)
+                id, torrent_id, moderator_id, previous_status, new_status, reason,
+                checklist, queue_seconds
+            ) VALUES ($1, $2, $3, $4, 'approved', $5, $6, $7)
             "#,
             Uuid::new_v4(),
             torrent_id,
             moderator_id,
             current.status as ModerationStatus,
-            reason
+            reason,
+            current.checklist,
+            current.queue_seconds
         )
         .execute(&mut *tx)
         .await?;
 
+        self.finish_action(&mut tx, torrent_id).await?;
+
         // Award upload credit to uploader
         sqlx::query!(
             r#"
@@ -360,20 +531,13 @@ impl ModerationService {
         moderator_id: Uuid,
         reason: String,
         category: RejectionCategory,
+        checklist: Vec<ChecklistAnswer>,
     ) -> Result<()> {
         let mut tx = self.pool.begin().await?;
 
-        // Get current status
-        let current = sqlx::query!(
-            r#"
-            SELECT moderation_status as "status: ModerationStatus"
-            FROM torrents
-            WHERE id = $1
-            "#,
-            torrent_id
-        )
-        .fetch_one(&mut *tx)
-        .await?;
+        let current = self
+            .begin_action(&mut tx, torrent_id, moderator_id, &checklist, false)
+            .await?;
 
         // Update torrent status
         sqlx::query!(
@@ -397,19 +561,23 @@ impl ModerationService {
             r#"
             INSERT INTO moderation_actions (
                 id, torrent_id, moderator_id, previous_status, new_status,
-                reason, rejection_category
-            ) VALUES ($1, $2, $3, $4, 'rejected', $5, $6)
+                reason, rejection_category, checklist, queue_seconds
+            ) VALUES ($1, $2, $3, $4, 'rejected', $5, $6, $7, $8)
             "#,
             Uuid::new_v4(),
             torrent_id,
             moderator_id,
             current.status as ModerationStatus,
             reason,
-            category as RejectionCategory
+            category as RejectionCategory,
+            current.checklist,
+            current.queue_seconds
         )
         .execute(&mut *tx)
         .await?;
 
+        self.finish_action(&mut tx, torrent_id).await?;
+
         // Update uploader stats
         sqlx::query!(
             r#"
@@ -433,20 +601,13 @@ impl ModerationService {
         torrent_id: Uuid,
         moderator_id: Uuid,
         reason: String,
+        checklist: Vec<ChecklistAnswer>,
     ) -> Result<()> {
         let mut tx = self.pool.begin().await?;
 
-        // Get current status
-        let current = sqlx::query!(
-            r#"
-            SELECT moderation_status as "status: ModerationStatus"
-            FROM torrents
-            WHERE id = $1
-            "#,
-            torrent_id
-        )
-        .fetch_one(&mut *tx)
-        .await?;
+        let current = self
+            .begin_action(&mut tx, torrent_id, moderator_id, &checklist, false)
+            .await?;
 
         // Update torrent status
         sqlx::query!(
@@ -469,18 +630,23 @@ impl ModerationService {
         sqlx::query!(
             r#"
             INSERT INTO moderation_actions (
-                id, torrent_id, moderator_id, previous_status, new_status, reason
-            ) VALUES ($1, $2, $3, $4, 'postponed', $5)
+                id, torrent_id, moderator_id, previous_status, new_status, reason,
+                checklist, queue_seconds
+            ) VALUES ($1, $2, $3, $4, 'postponed', $5, $6, $7)
             "#,
             Uuid::new_v4(),
             torrent_id,
             moderator_id,
             current.status as ModerationStatus,
-            reason
+            reason,
+            current.checklist,
+            current.queue_seconds
         )
         .execute(&mut *tx)
         .await?;
 
+        self.finish_action(&mut tx, torrent_id).await?;
+
         tx.commit().await?;
 
         Ok(())
@@ -496,7 +662,7 @@ impl ModerationService {
                 new_status as "new_status: ModerationStatus",
                 reason,
                 rejection_category as "rejection_category: RejectionCategory",
-                created_at
+                checklist, queue_seconds, created_at
             FROM moderation_actions
             WHERE torrent_id = $1
             ORDER BY created_at DESC
@@ -506,19 +672,262 @@ impl ModerationService {
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(records
+        records
             .into_iter()
-            .map(|r| ModerationAction {
-                id: r.id,
-                torrent_id: r.torrent_id,
-                moderator_id: r.moderator_id,
-                previous_status: r.previous_status,
-                new_status: r.new_status,
-                reason: r.reason,
-                rejection_category: r.rejection_category,
-                created_at: r.created_at,
+            .map(|r| {
+                Ok(ModerationAction {
+                    id: r.id,
+                    torrent_id: r.torrent_id,
+                    moderator_id: r.moderator_id,
+                    previous_status: r.previous_status,
+                    new_status: r.new_status,
+                    reason: r.reason,
+                    rejection_category: r.rejection_category,
+                    checklist: r
+                        .checklist
+                        .map(serde_json::from_value)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    queue_seconds: r.queue_seconds,
+                    created_at: r.created_at,
+                })
             })
-            .collect())
+            .collect()
+    }
+
+    /// Lock a torrent for a moderation action and check the checklist
+    ///
+    /// Fails if another moderator holds an active claim on the torrent.
+    async fn begin_action(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        torrent_id: Uuid,
+        moderator_id: Uuid,
+        answers: &[ChecklistAnswer],
+        approving: bool,
+    ) -> Result<ActionContext> {
+        let current = sqlx::query!(
+            r#"
+            SELECT
+                moderation_status as "status: ModerationStatus",
+                category_id,
+                EXTRACT(EPOCH FROM NOW() - created_at)::BIGINT as "queue_seconds!"
+            FROM torrents
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+            torrent_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        let claim = sqlx::query!(
+            r#"
+            SELECT moderator_id
+            FROM moderation_claims
+            WHERE torrent_id = $1 AND expires_at > NOW()
+            "#,
+            torrent_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        if let Some(claim) = claim {
+            if claim.moderator_id != moderator_id {
+                return Err(anyhow!("Torrent is claimed by another moderator"));
+            }
+        }
+
+        let checklist = self.checklist_for_category(current.category_id).await?;
+        checklist.check_answers(answers, approving)?;
+
+        let checklist = if answers.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_value(answers)?)
+        };
+
+        Ok(ActionContext {
+            status: current.status,
+            checklist,
+            queue_seconds: current.queue_seconds,
+        })
+    }
+
+    /// Release the claim once the action is recorded
+    async fn finish_action(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        torrent_id: Uuid,
+    ) -> Result<()> {
+        sqlx::query!(
+            "DELETE FROM moderation_claims WHERE torrent_id = $1",
+            torrent_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Claim a queued torrent for review
+    ///
+    /// Claiming again extends the caller's own claim. Fails if the torrent is
+    /// not in the queue or another moderator holds an active claim.
+    pub async fn claim_torrent(&self, torrent_id: Uuid, moderator_id: Uuid) -> Result<ModerationClaim> {
+        let claim = sqlx::query_as!(
+            ModerationClaim,
+            r#"
+            INSERT INTO moderation_claims (torrent_id, moderator_id, claimed_at, expires_at)
+            SELECT id, $2, NOW(), NOW() + make_interval(mins => $3)
+            FROM torrents
+            WHERE id = $1 AND moderation_status IN ('pending', 'postponed')
+            ON CONFLICT (torrent_id) DO UPDATE
+            SET moderator_id = EXCLUDED.moderator_id,
+                claimed_at = CASE
+                    WHEN moderation_claims.moderator_id = EXCLUDED.moderator_id
+                    THEN moderation_claims.claimed_at
+                    ELSE EXCLUDED.claimed_at
+                END,
+                expires_at = EXCLUDED.expires_at
+            WHERE moderation_claims.moderator_id = EXCLUDED.moderator_id
+               OR moderation_claims.expires_at <= NOW()
+            RETURNING torrent_id, moderator_id, claimed_at, expires_at
+            "#,
+            torrent_id,
+            moderator_id,
+            self.sla.claim_minutes
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        claim.ok_or_else(|| anyhow!("Torrent is not in the queue or is claimed by another moderator"))
+    }
+
+    /// Release a claim held by a moderator
+    pub async fn release_claim(&self, torrent_id: Uuid, moderator_id: Uuid) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM moderation_claims
+            WHERE torrent_id = $1 AND moderator_id = $2
+            "#,
+            torrent_id,
+            moderator_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Escalate torrents waiting longer than the escalation threshold
+    ///
+    /// Also drops expired claims so escalated items can be picked up by
+    /// anyone. Returns the newly escalated torrent IDs.
+    pub async fn escalate_overdue(&self) -> Result<Vec<Uuid>> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!("DELETE FROM moderation_claims WHERE expires_at <= NOW()")
+            .execute(&mut *tx)
+            .await?;
+
+        let escalated = sqlx::query!(
+            r#"
+            UPDATE torrents
+            SET escalated_at = NOW()
+            WHERE moderation_status = 'pending'
+            AND escalated_at IS NULL
+            AND created_at < NOW() - make_interval(hours => $1)
+            RETURNING id
+            "#,
+            self.sla.escalate_hours
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let ids: Vec<Uuid> = escalated.into_iter().map(|r| r.id).collect();
+        if !ids.is_empty() {
+            tracing::warn!(
+                "Escalated {} torrents pending for over {} hours",
+                ids.len(),
+                self.sla.escalate_hours
+            );
+        }
+
+        Ok(ids)
+    }
+
+    /// Get the checklist for a category
+    ///
+    /// Falls back to the site-wide checklist, then the built-in one.
+    pub async fn checklist_for_category(&self, category_id: i32) -> Result<ModerationChecklist> {
+        let record = sqlx::query!(
+            r#"
+            SELECT items
+            FROM moderation_checklists
+            WHERE category_id = $1 OR category_id IS NULL
+            ORDER BY category_id NULLS LAST
+            LIMIT 1
+            "#,
+            category_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match record {
+            Some(record) => Ok(ModerationChecklist {
+                items: serde_json::from_value(record.items)?,
+            }),
+            None => Ok(ModerationChecklist::default()),
+        }
+    }
+
+    /// Create or replace a checklist
+    ///
+    /// `category_id` of `None` sets the site-wide checklist.
+    pub async fn save_checklist(
+        &self,
+        category_id: Option<i32>,
+        checklist: &ModerationChecklist,
+        updated_by: Uuid,
+    ) -> Result<()> {
+        checklist.validate()?;
+
+        let items = serde_json::to_value(&checklist.items)?;
+        let mut tx = self.pool.begin().await?;
+
+        let updated = sqlx::query!(
+            r#"
+            UPDATE moderation_checklists
+            SET items = $2, updated_by = $3, updated_at = NOW()
+            WHERE category_id IS NOT DISTINCT FROM $1
+            "#,
+            category_id,
+            items,
+            updated_by
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            sqlx::query!(
+                r#"
+                INSERT INTO moderation_checklists (category_id, items, updated_by, updated_at)
+                VALUES ($1, $2, $3, NOW())
+                "#,
+                category_id,
+                items,
+                updated_by
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
     }
 
     /// Get pending torrents for moderation
@@ -532,12 +941,21 @@ impl ModerationService {
             SELECT
                 t.id, t.name, t.info_hash, t.total_size,
                 t.uploader_id, u.username as uploader_name,
-                t.created_at, t.category_id, c.name as category_name
+                t.created_at, t.category_id, c.name as category_name,
+                COALESCE(jsonb_array_length(mi.mismatches), 0) as "quality_mismatches!",
+                EXTRACT(EPOCH FROM NOW() - t.created_at)::BIGINT as "queue_seconds!",
+                t.escalated_at,
+                mc.moderator_id as "claimed_by?",
+                cu.username as "claimed_by_name?",
+                mc.expires_at as "claim_expires_at?"
             FROM torrents t
             JOIN users u ON u.id = t.uploader_id
             JOIN categories c ON c.id = t.category_id
+            LEFT JOIN torrent_mediainfo mi ON mi.torrent_id = t.id
+            LEFT JOIN moderation_claims mc ON mc.torrent_id = t.id AND mc.expires_at > NOW()
+            LEFT JOIN users cu ON cu.id = mc.moderator_id
             WHERE t.moderation_status = 'pending'
-            ORDER BY t.created_at ASC
+            ORDER BY t.escalated_at IS NULL, t.created_at ASC
             LIMIT $1 OFFSET $2
             "#,
             limit,
@@ -558,6 +976,13 @@ impl ModerationService {
                 category_id: r.category_id,
                 category_name: r.category_name,
                 created_at: r.created_at,
+                quality_mismatches: r.quality_mismatches,
+                queue_seconds: r.queue_seconds,
+                sla_breached: self.sla.is_breached(r.queue_seconds),
+                escalated: r.escalated_at.is_some(),
+                claimed_by: r.claimed_by,
+                claimed_by_name: r.claimed_by_name,
+                claim_expires_at: r.claim_expires_at,
             })
             .collect())
     }
@@ -575,15 +1000,55 @@ impl ModerationService {
                     WHERE moderation_status = 'pending'
                     AND created_at < NOW() - INTERVAL '24 hours'
                 ) as "pending_over_24h!",
+                COUNT(*) FILTER (
+                    WHERE moderation_status = 'pending'
+                    AND created_at < NOW() - make_interval(hours => $1)
+                ) as "sla_breaches!",
+                COUNT(*) FILTER (
+                    WHERE moderation_status = 'pending' AND escalated_at IS NOT NULL
+                ) as "escalated!",
+                (
+                    SELECT COUNT(*) FROM moderation_claims WHERE expires_at > NOW()
+                ) as "claimed!",
+                MAX(EXTRACT(EPOCH FROM NOW() - created_at)::BIGINT)
+                    FILTER (WHERE moderation_status = 'pending') as oldest_pending_seconds,
                 AVG(
                     EXTRACT(EPOCH FROM (approved_at - created_at))
                 ) FILTER (WHERE approved_at IS NOT NULL) as approval_time_avg
             FROM torrents
-            "#
+            "#,
+            self.sla.target_hours
         )
         .fetch_one(&self.pool)
         .await?;
 
+        // Decisions per moderator; postpones count towards throughput but
+        // only final decisions count towards SLA breaches
+        let moderators = sqlx::query!(
+            r#"
+            SELECT
+                ma.moderator_id,
+                u.username,
+                COUNT(*) FILTER (WHERE ma.new_status = 'approved') as "approved!",
+                COUNT(*) FILTER (WHERE ma.new_status = 'rejected') as "rejected!",
+                COUNT(*) FILTER (WHERE ma.new_status = 'postponed') as "postponed!",
+                AVG(ma.queue_seconds)::BIGINT as avg_queue_seconds,
+                COUNT(*) FILTER (
+                    WHERE ma.new_status IN ('approved', 'rejected')
+                    AND ma.queue_seconds > $1::BIGINT * 3600
+                ) as "sla_breaches!"
+            FROM moderation_actions ma
+            JOIN users u ON u.id = ma.moderator_id
+            WHERE ma.created_at > NOW() - make_interval(days => $2)
+            GROUP BY ma.moderator_id, u.username
+            ORDER BY COUNT(*) DESC
+            "#,
+            self.sla.target_hours,
+            self.sla.stats_window_days
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
         Ok(ModerationStats {
             pending: stats.pending,
             approved: stats.approved,
@@ -591,6 +1056,22 @@ impl ModerationService {
             postponed: stats.postponed,
             pending_over_24h: stats.pending_over_24h,
             avg_approval_time_seconds: stats.approval_time_avg.map(|t| t as i64),
+            sla_breaches: stats.sla_breaches,
+            escalated: stats.escalated,
+            claimed: stats.claimed,
+            oldest_pending_seconds: stats.oldest_pending_seconds,
+            moderators: moderators
+                .into_iter()
+                .map(|r| ModeratorThroughput {
+                    moderator_id: r.moderator_id,
+                    username: r.username,
+                    approved: r.approved,
+                    rejected: r.rejected,
+                    postponed: r.postponed,
+                    avg_queue_seconds: r.avg_queue_seconds,
+                    sla_breaches: r.sla_breaches,
+                })
+                .collect(),
         })
     }
 }
@@ -607,6 +1088,24 @@ pub struct PendingTorrent {
     pub category_id: Uuid,
     pub category_name: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Release-name claims contradicted by the uploaded MediaInfo
+    pub quality_mismatches: i32,
+    /// Seconds since upload
+    pub queue_seconds: i64,
+    /// Waiting longer than the SLA target
+    pub sla_breached: bool,
+    pub escalated: bool,
+    /// Moderator holding an active claim
+    pub claimed_by: Option<Uuid>,
+    pub claimed_by_name: Option<String>,
+    pub claim_expires_at: Option<DateTime<Utc>>,
+}
+
+/// State read when starting a moderation action
+struct ActionContext {
+    status: ModerationStatus,
+    checklist: Option<serde_json::Value>,
+    queue_seconds: i64,
 }
 
 /// Moderation statistics
@@ -618,6 +1117,29 @@ pub struct ModerationStats {
     pub postponed: i64,
     pub pending_over_24h: i64,
     pub avg_approval_time_seconds: Option<i64>,
+    /// Pending torrents waiting longer than the SLA target
+    pub sla_breaches: i64,
+    /// Pending torrents that have been escalated
+    pub escalated: i64,
+    /// Queue items with an active claim
+    pub claimed: i64,
+    pub oldest_pending_seconds: Option<i64>,
+    /// Per-moderator throughput over the stats window
+    pub moderators: Vec<ModeratorThroughput>,
+}
+
+/// A moderator's throughput over the stats window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModeratorThroughput {
+    pub moderator_id: Uuid,
+    pub username: String,
+    pub approved: i64,
+    pub rejected: i64,
+    pub postponed: i64,
+    /// Average time in queue of the torrents they acted on
+    pub avg_queue_seconds: Option<i64>,
+    /// Decisions made after the SLA target had passed
+    pub sla_breaches: i64,
 }
 
 #[cfg(test)]
@@ -641,4 +1163,47 @@ mod tests {
         assert!(!rules.require_trusted);
         assert!(rules.require_no_warnings);
     }
+
+    fn answer(key: &str, passed: bool) -> ChecklistAnswer {
+        ChecklistAnswer {
+            key: key.to_string(),
+            passed,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_checklist_answers() {
+        let checklist = ModerationChecklist::default();
+        let all_passed = vec![
+            answer("name", true),
+            answer("files", true),
+            answer("not_duplicate", true),
+        ];
+
+        assert!(checklist.check_answers(&all_passed, true).is_ok());
+
+        // A failed required item blocks approval but not rejection
+        let failed = vec![answer("name", true), answer("files", false)];
+        assert!(checklist.check_answers(&failed, true).is_err());
+        assert!(checklist.check_answers(&failed, false).is_ok());
+
+        assert!(checklist.check_answers(&[answer("bogus", true)], false).is_err());
+    }
+
+    #[test]
+    fn test_checklist_validate() {
+        let mut checklist = ModerationChecklist::default();
+        assert!(checklist.validate().is_ok());
+
+        checklist.items.push(ChecklistItem::new("name", "Again", false));
+        assert!(checklist.validate().is_err());
+    }
+
+    #[test]
+    fn test_sla_breach() {
+        let sla = SlaPolicy::default();
+        assert!(!sla.is_breached(23 * 3600));
+        assert!(sla.is_breached(25 * 3600));
+    }
 }
@@ -0,0 +1,561 @@
+//! NFO decoding, rendering and link mining
+//!
+//! Scene NFOs are mostly CP437 (the DOS codepage with box-drawing and block
+//! characters used for ASCII art); newer ones are CP1252, UTF-8 or UTF-16.
+//! This module detects the encoding, decodes to Unicode, strips ANSI escape
+//! sequences and other control characters, and can render the result to a
+//! PNG with an 8x16 bitmap font so the art displays as intended.
+//!
+//! Decoded NFOs are also mined for IMDb and TMDB links, which are stored
+//! with the torrent and used by the media enricher before it falls back to
+//! searching by name.
+
+use crate::metadata::MediaType;
+use anyhow::{anyhow, Result};
+use font8x8::{
+    UnicodeFonts, BASIC_FONTS, BLOCK_FONTS, BOX_FONTS, GREEK_FONTS, LATIN_FONTS, MISC_FONTS,
+};
+use image::{DynamicImage, GrayImage, ImageFormat, Luma};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::io::Cursor;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+/// CP437 code points for bytes 0x80-0xFF
+const CP437_HIGH: &str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»\
+░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀\
+αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+
+/// CP1252 code points for bytes 0x80-0x9F (undefined bytes map to C1 controls)
+const CP1252_C1: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+/// Maximum NFO size accepted (NFOs are small; anything larger is not one)
+const MAX_NFO_SIZE: usize = 512 * 1024;
+
+fn cp437_table() -> &'static [char] {
+    static TABLE: OnceLock<Vec<char>> = OnceLock::new();
+    TABLE.get_or_init(|| CP437_HIGH.chars().collect())
+}
+
+/// Detected NFO encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NfoEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Cp437,
+    Cp1252,
+}
+
+/// Decoded NFO
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NfoDocument {
+    /// Detected encoding
+    pub encoding: NfoEncoding,
+
+    /// Decoded and cleaned text
+    pub text: String,
+}
+
+/// Detect the encoding of raw NFO bytes
+pub fn detect_encoding(bytes: &[u8]) -> NfoEncoding {
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return NfoEncoding::Utf16Le;
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return NfoEncoding::Utf16Be;
+    }
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) || std::str::from_utf8(bytes).is_ok() {
+        // Plain ASCII is also valid UTF-8, unless it is BOM-less UTF-16
+        return utf16_without_bom(bytes).unwrap_or(NfoEncoding::Utf8);
+    }
+
+    // Box-drawing and block characters live in 0xB0-0xDF in CP437; in
+    // CP1252 that range is accented capitals, which are rare in bulk
+    let high = bytes.iter().filter(|&&b| b >= 0x80).count();
+    let art = bytes
+        .iter()
+        .filter(|&&b| (0xB0..=0xDF).contains(&b))
+        .count();
+
+    if art * 2 >= high {
+        NfoEncoding::Cp437
+    } else {
+        NfoEncoding::Cp1252
+    }
+}
+
+/// Detect UTF-16 without a BOM from the share of zero bytes
+fn utf16_without_bom(bytes: &[u8]) -> Option<NfoEncoding> {
+    if bytes.len() < 4 || bytes.len() % 2 != 0 {
+        return None;
+    }
+
+    let pairs = bytes.len() / 2;
+    let zero_odd = bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    let zero_even = bytes.iter().step_by(2).filter(|&&b| b == 0).count();
+
+    if zero_odd * 2 > pairs {
+        Some(NfoEncoding::Utf16Le)
+    } else if zero_even * 2 > pairs {
+        Some(NfoEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Decode bytes with a given encoding
+pub fn decode(bytes: &[u8], encoding: NfoEncoding) -> String {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|c| from([c[0], c[1]])).collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    match encoding {
+        NfoEncoding::Utf8 => {
+            let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+        NfoEncoding::Utf16Le => {
+            let bytes = bytes.strip_prefix(&[0xFF, 0xFE]).unwrap_or(bytes);
+            utf16(bytes, u16::from_le_bytes)
+        }
+        NfoEncoding::Utf16Be => {
+            let bytes = bytes.strip_prefix(&[0xFE, 0xFF]).unwrap_or(bytes);
+            utf16(bytes, u16::from_be_bytes)
+        }
+        NfoEncoding::Cp437 | NfoEncoding::Cp1252 => {
+            // SUB (0x1A) marks end of file; SAUCE metadata may follow it
+            let end = bytes.iter().position(|&b| b == 0x1A).unwrap_or(bytes.len());
+            bytes[..end]
+                .iter()
+                .map(|&b| match (encoding, b) {
+                    (_, 0x00..=0x7F) => b as char,
+                    (NfoEncoding::Cp437, _) => cp437_table()[(b - 0x80) as usize],
+                    (_, 0x80..=0x9F) => CP1252_C1[(b - 0x80) as usize],
+                    _ => b as char,
+                })
+                .collect()
+        }
+    }
+}
+
+/// Strip ANSI escape sequences and control characters
+///
+/// Line endings are normalised to `\n`, trailing whitespace is removed from
+/// each line, and trailing blank lines are dropped.
+pub fn strip_control_sequences(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => {
+                if chars.peek() == Some(&'[') {
+                    // CSI: parameters up to a final byte in 0x40-0x7E
+                    chars.next();
+                    for c in chars.by_ref() {
+                        if ('\u{40}'..='\u{7e}').contains(&c) {
+                            break;
+                        }
+                    }
+                } else {
+                    // Two-character escape
+                    chars.next();
+                }
+            }
+            '\r' => {
+                if chars.peek() != Some(&'\n') {
+                    out.push('\n');
+                }
+            }
+            '\n' | '\t' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+
+    let lines: Vec<&str> = out.lines().map(str::trim_end).collect();
+    let end = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .map_or(0, |i| i + 1);
+    lines[..end].join("\n")
+}
+
+/// Decode raw NFO bytes
+pub fn decode_nfo(bytes: &[u8]) -> Result<NfoDocument> {
+    if bytes.len() > MAX_NFO_SIZE {
+        return Err(anyhow!(
+            "NFO file too large (max {}KB)",
+            MAX_NFO_SIZE / 1024
+        ));
+    }
+
+    let encoding = detect_encoding(bytes);
+    let text = strip_control_sequences(&decode(bytes, encoding));
+
+    if text.trim().is_empty() {
+        return Err(anyhow!("NFO file is empty"));
+    }
+
+    Ok(NfoDocument { encoding, text })
+}
+
+/// TMDB entry kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TmdbKind {
+    Movie,
+    Tv,
+}
+
+impl TmdbKind {
+    /// TMDB kind that a torrent of this media type is listed under
+    pub fn for_media_type(media_type: MediaType) -> Option<Self> {
+        match media_type {
+            MediaType::Movie => Some(TmdbKind::Movie),
+            MediaType::TvShow => Some(TmdbKind::Tv),
+            _ => None,
+        }
+    }
+}
+
+/// External links found in an NFO
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NfoLinks {
+    /// IMDb title IDs (e.g., "tt0133093"), in order of appearance
+    pub imdb_ids: Vec<String>,
+
+    /// TMDB entries, in order of appearance
+    pub tmdb: Vec<(TmdbKind, i64)>,
+}
+
+impl NfoLinks {
+    /// Whether no links were found
+    pub fn is_empty(&self) -> bool {
+        self.imdb_ids.is_empty() && self.tmdb.is_empty()
+    }
+
+    /// First TMDB ID of the given kind
+    ///
+    /// Movie and TV IDs overlap, so an ID is only usable for the kind it was linked as.
+    pub fn tmdb_id(&self, kind: TmdbKind) -> Option<i64> {
+        self.tmdb
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, id)| *id)
+    }
+}
+
+fn imdb_link() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)imdb\.com/(?:[a-z]{2}/)?title/(tt\d{7,9})").unwrap())
+}
+
+fn tmdb_link() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)themoviedb\.org/(movie|tv)/(\d+)").unwrap())
+}
+
+/// Find IMDb and TMDB links in NFO text
+pub fn extract_links(text: &str) -> NfoLinks {
+    let mut links = NfoLinks::default();
+
+    for caps in imdb_link().captures_iter(text) {
+        let id = caps[1].to_lowercase();
+        if !links.imdb_ids.contains(&id) {
+            links.imdb_ids.push(id);
+        }
+    }
+
+    for caps in tmdb_link().captures_iter(text) {
+        let kind = if caps[1].eq_ignore_ascii_case("tv") {
+            TmdbKind::Tv
+        } else {
+            TmdbKind::Movie
+        };
+        if let Ok(id) = caps[2].parse() {
+            if !links.tmdb.contains(&(kind, id)) {
+                links.tmdb.push((kind, id));
+            }
+        }
+    }
+
+    links
+}
+
+/// NFO image rendering options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderOptions {
+    /// Text colour (grayscale)
+    pub foreground: u8,
+
+    /// Background colour (grayscale)
+    pub background: u8,
+
+    /// Columns beyond this are cut off
+    pub max_columns: usize,
+
+    /// Lines beyond this are cut off
+    pub max_lines: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            foreground: 0xC0,
+            background: 0x00,
+            max_columns: 160,
+            max_lines: 1000,
+        }
+    }
+}
+
+/// Glyph width in pixels
+const GLYPH_WIDTH: u32 = 8;
+
+/// Glyph height in pixels (8x8 font rows doubled to the VGA 8x16 aspect)
+const GLYPH_HEIGHT: u32 = 16;
+
+fn glyph(c: char) -> [u8; 8] {
+    BASIC_FONTS
+        .get(c)
+        .or_else(|| BOX_FONTS.get(c))
+        .or_else(|| BLOCK_FONTS.get(c))
+        .or_else(|| LATIN_FONTS.get(c))
+        .or_else(|| GREEK_FONTS.get(c))
+        .or_else(|| MISC_FONTS.get(c))
+        .or_else(|| BASIC_FONTS.get('?'))
+        .unwrap_or([0; 8])
+}
+
+/// Render decoded NFO text to a grayscale PNG
+pub fn render_png(text: &str, options: &RenderOptions) -> Result<Vec<u8>> {
+    let lines: Vec<Vec<char>> = text
+        .lines()
+        .take(options.max_lines)
+        .map(|l| l.chars().take(options.max_columns).collect())
+        .collect();
+
+    let columns = lines.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let width = columns as u32 * GLYPH_WIDTH;
+    let height = lines.len().max(1) as u32 * GLYPH_HEIGHT;
+
+    let mut image = GrayImage::from_pixel(width, height, Luma([options.background]));
+
+    for (row, line) in lines.iter().enumerate() {
+        for (col, &c) in line.iter().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+
+            let bitmap = glyph(c);
+            let x0 = col as u32 * GLYPH_WIDTH;
+            let y0 = row as u32 * GLYPH_HEIGHT;
+
+            for (gy, bits) in bitmap.iter().enumerate() {
+                for gx in 0..GLYPH_WIDTH {
+                    // Least significant bit is the leftmost pixel
+                    if bits & (1 << gx) != 0 {
+                        let y = y0 + gy as u32 * 2;
+                        image.put_pixel(x0 + gx, y, Luma([options.foreground]));
+                        image.put_pixel(x0 + gx, y + 1, Luma([options.foreground]));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut output = Cursor::new(Vec::new());
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut output, ImageFormat::Png)
+        .map_err(|e| anyhow!("Failed to encode NFO image: {}", e))?;
+
+    Ok(output.into_inner())
+}
+
+/// NFO service
+pub struct NfoService {
+    pool: PgPool,
+}
+
+impl NfoService {
+    /// Create a new NFO service
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store the links mined from a torrent's NFO
+    ///
+    /// Runs in the upload's transaction, replacing any links already stored.
+    pub async fn store_links(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        torrent_id: Uuid,
+        links: &NfoLinks,
+    ) -> Result<()> {
+        sqlx::query!(
+            "DELETE FROM torrent_nfo_links WHERE torrent_id = $1",
+            torrent_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        let imdb = links.imdb_ids.iter().map(|id| ("imdb", id.clone()));
+        let tmdb = links.tmdb.iter().map(|(kind, id)| {
+            let source = match kind {
+                TmdbKind::Movie => "tmdb_movie",
+                TmdbKind::Tv => "tmdb_tv",
+            };
+            (source, id.to_string())
+        });
+
+        for (position, (source, external_id)) in imdb.chain(tmdb).enumerate() {
+            sqlx::query!(
+                r#"
+                INSERT INTO torrent_nfo_links (torrent_id, source, external_id, position)
+                VALUES ($1, $2, $3, $4)
+                "#,
+                torrent_id,
+                source,
+                external_id,
+                position as i32,
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Render a torrent's NFO to PNG
+    pub async fn render(
+        &self,
+        torrent_id: Uuid,
+        options: &RenderOptions,
+    ) -> Result<Option<Vec<u8>>> {
+        let record = sqlx::query!(
+            "SELECT nfo_content FROM torrent_metadata WHERE id = $1",
+            torrent_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(text) = record.and_then(|r| r.nfo_content) else {
+            return Ok(None);
+        };
+
+        // Rendering is CPU-bound
+        let options = options.clone();
+        let png = tokio::task::spawn_blocking(move || render_png(&text, &options)).await??;
+
+        Ok(Some(png))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A CP437 NFO header: box-drawing frame, a shaded bar and an IMDb link
+    fn cp437_sample() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&[0xC9, 0xCD, 0xCD, 0xCD, 0xBB, b'\r', b'\n']);
+        bytes.extend_from_slice(&[0xBA, b' ', b'G', b' ', 0xBA, b'\r', b'\n']);
+        bytes.extend_from_slice(&[0xC8, 0xCD, 0xCD, 0xCD, 0xBC, b'\r', b'\n']);
+        bytes.extend_from_slice(&[0xB0, 0xB1, 0xB2, 0xDB, b'\r', b'\n']);
+        bytes.extend_from_slice(b"\x1b[1;37mhttps://www.imdb.com/title/tt0133093/\x1b[0m\r\n");
+        bytes.extend_from_slice(b"\x1aSAUCE00 trailing metadata");
+        bytes
+    }
+
+    #[test]
+    fn test_cp437_table() {
+        assert_eq!(cp437_table().len(), 128);
+    }
+
+    #[test]
+    fn test_decode_cp437_nfo() {
+        let nfo = decode_nfo(&cp437_sample()).unwrap();
+
+        assert_eq!(nfo.encoding, NfoEncoding::Cp437);
+        assert_eq!(
+            nfo.text,
+            "╔═══╗\n║ G ║\n╚═══╝\n░▒▓█\nhttps://www.imdb.com/title/tt0133093/"
+        );
+    }
+
+    #[test]
+    fn test_decode_cp1252_and_utf16() {
+        // "Café – 2023" with a CP1252 en dash
+        let cp1252 = b"Caf\xe9 \x96 2023";
+        assert_eq!(detect_encoding(cp1252), NfoEncoding::Cp1252);
+        assert_eq!(decode_nfo(cp1252).unwrap().text, "Café – 2023");
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        for unit in "Release ║ Info".encode_utf16() {
+            utf16.extend_from_slice(&unit.to_le_bytes());
+        }
+        let nfo = decode_nfo(&utf16).unwrap();
+        assert_eq!(nfo.encoding, NfoEncoding::Utf16Le);
+        assert_eq!(nfo.text, "Release ║ Info");
+
+        let no_bom: Vec<u8> = "Plain"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        assert_eq!(detect_encoding(&no_bom), NfoEncoding::Utf16Le);
+
+        assert_eq!(detect_encoding("Ünïcödé".as_bytes()), NfoEncoding::Utf8);
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let text = "IMDb....: https://www.imdb.com/title/tt0133093/\n\
+                    Also....: http://imdb.com/de/title/TT0133093\n\
+                    TMDB....: https://www.themoviedb.org/movie/603-the-matrix\n\
+                    Series..: https://themoviedb.org/tv/1396";
+        let links = extract_links(text);
+
+        assert_eq!(links.imdb_ids, vec!["tt0133093"]);
+        assert_eq!(
+            links.tmdb,
+            vec![(TmdbKind::Movie, 603), (TmdbKind::Tv, 1396)]
+        );
+        assert!(extract_links("no links here").is_empty());
+    }
+
+    #[test]
+    fn test_tmdb_id_by_kind() {
+        let links =
+            extract_links("https://themoviedb.org/tv/1396 https://themoviedb.org/movie/603");
+
+        assert_eq!(links.tmdb_id(TmdbKind::Movie), Some(603));
+        assert_eq!(links.tmdb_id(TmdbKind::Tv), Some(1396));
+        assert_eq!(TmdbKind::for_media_type(MediaType::Music), None);
+        assert_eq!(
+            extract_links("https://themoviedb.org/tv/1396").tmdb_id(TmdbKind::Movie),
+            None
+        );
+    }
+
+    #[test]
+    fn test_render_png() {
+        let nfo = decode_nfo(&cp437_sample()).unwrap();
+        let png = render_png(&nfo.text, &RenderOptions::default()).unwrap();
+
+        assert_eq!(&png[1..4], b"PNG");
+
+        let image = image::load_from_memory(&png).unwrap();
+        let longest = nfo.text.lines().map(|l| l.chars().count()).max().unwrap() as u32;
+        assert_eq!(image.width(), longest * GLYPH_WIDTH);
+        assert_eq!(image.height(), 5 * GLYPH_HEIGHT);
+    }
+}
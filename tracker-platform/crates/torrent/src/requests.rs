@@ -6,8 +6,16 @@
 //! - Fill requests by uploading matching content
 //! - Automatically distribute bounties to uploader
 //! - Vote on requests to show community interest
+//!
+//! Fills are checked against the request's category, media type, external
+//! IDs and quality requirements. The bounty is then held in escrow until the
+//! challenge window passes, during which the requester (or staff at any time)
+//! can reject the fill. Rejecting reopens the request and reverses any payout.
+//! Expired requests refund their contributors pro rata.
 
+use crate::metadata::{ExternalIds, MediaType, QualityInfo};
 use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
@@ -30,6 +38,292 @@ pub enum RequestStatus {
     Expired,
 }
 
+/// Accepted quality values for a request (empty list = any)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityRequirements {
+    /// Accepted resolutions (e.g., "1080p", "2160p")
+    pub resolutions: Vec<String>,
+
+    /// Accepted sources (e.g., "BluRay", "WEB-DL")
+    pub sources: Vec<String>,
+
+    /// Accepted video codecs (e.g., "H.264", "H.265")
+    pub video_codecs: Vec<String>,
+
+    /// Accepted audio codecs (e.g., "FLAC", "DTS")
+    pub audio_codecs: Vec<String>,
+}
+
+impl QualityRequirements {
+    /// Whether any quality constraint is set
+    pub fn is_empty(&self) -> bool {
+        self.resolutions.is_empty()
+            && self.sources.is_empty()
+            && self.video_codecs.is_empty()
+            && self.audio_codecs.is_empty()
+    }
+}
+
+/// Escrow state of a request fill
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "request_fill_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FillStatus {
+    /// Bounty held in escrow, fill can still be challenged
+    Pending,
+
+    /// Challenge window passed and the bounty was paid out
+    Paid,
+
+    /// Fill was rejected and the request reopened
+    Rejected,
+}
+
+/// A fill of a request and its bounty escrow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestFill {
+    /// Fill ID
+    pub id: Uuid,
+
+    /// Request ID
+    pub request_id: Uuid,
+
+    /// Torrent that filled the request
+    pub torrent_id: Uuid,
+
+    /// Uploader who receives the bounty
+    pub filler_id: Uuid,
+
+    /// Bounty held for (or paid to) the filler
+    pub bounty: i64,
+
+    /// Escrow state
+    pub status: FillStatus,
+
+    /// End of the challenge window
+    pub challenge_until: DateTime<Utc>,
+
+    /// When the bounty was paid
+    pub paid_at: Option<DateTime<Utc>>,
+
+    /// Who rejected the fill
+    pub rejected_by: Option<Uuid>,
+
+    /// Why the fill was rejected
+    pub rejection_reason: Option<String>,
+
+    /// Created timestamp
+    pub created_at: DateTime<Utc>,
+}
+
+/// Torrent attributes compared against a request when filling it
+#[derive(Debug, Clone)]
+pub struct FillCandidate {
+    pub category_id: Uuid,
+    pub media_type: MediaType,
+    pub external_ids: ExternalIds,
+    pub year: Option<i32>,
+    pub quality: Option<QualityInfo>,
+}
+
+/// Reason a torrent does not satisfy a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum FillMismatch {
+    /// Torrent is in a different category
+    Category,
+
+    /// Torrent has a different media type
+    MediaType {
+        expected: MediaType,
+        actual: MediaType,
+    },
+
+    /// An external ID set on the request is missing or different
+    ExternalId {
+        source: String,
+        expected: String,
+        actual: Option<String>,
+    },
+
+    /// Release year differs
+    Year { expected: i32, actual: i32 },
+
+    /// A quality attribute is not one of the accepted values
+    Quality {
+        field: String,
+        accepted: Vec<String>,
+        actual: Option<String>,
+    },
+}
+
+impl std::fmt::Display for FillMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FillMismatch::Category => write!(f, "category does not match"),
+            FillMismatch::MediaType { expected, actual } => {
+                write!(f, "media type is {:?}, expected {:?}", actual, expected)
+            }
+            FillMismatch::ExternalId {
+                source,
+                expected,
+                actual,
+            } => match actual {
+                Some(actual) => write!(f, "{} is {}, expected {}", source, actual, expected),
+                None => write!(f, "{} is missing, expected {}", source, expected),
+            },
+            FillMismatch::Year { expected, actual } => {
+                write!(f, "year is {}, expected {}", actual, expected)
+            }
+            FillMismatch::Quality {
+                field,
+                accepted,
+                actual,
+            } => write!(
+                f,
+                "{} is {}, expected one of {}",
+                field,
+                actual.as_deref().unwrap_or("unknown"),
+                accepted.join(", ")
+            ),
+        }
+    }
+}
+
+/// Compare a torrent against a request
+///
+/// Returns every mismatch found; an empty list means the torrent fills the
+/// request. Unset request fields (media type `Other`, missing IDs, no year,
+/// empty quality lists) accept anything.
+pub fn match_fill(request: &TorrentRequest, candidate: &FillCandidate) -> Vec<FillMismatch> {
+    let mut mismatches = Vec::new();
+
+    if request.category_id != candidate.category_id {
+        mismatches.push(FillMismatch::Category);
+    }
+
+    if request.media_type != MediaType::Other && request.media_type != candidate.media_type {
+        mismatches.push(FillMismatch::MediaType {
+            expected: request.media_type,
+            actual: candidate.media_type,
+        });
+    }
+
+    let wanted = &request.external_ids;
+    let have = &candidate.external_ids;
+    let ids: [(&str, Option<String>, Option<String>); 7] = [
+        ("tmdb_id", wanted.tmdb_id.map(|v| v.to_string()), have.tmdb_id.map(|v| v.to_string())),
+        ("imdb_id", wanted.imdb_id.clone(), have.imdb_id.clone()),
+        ("tvdb_id", wanted.tvdb_id.map(|v| v.to_string()), have.tvdb_id.map(|v| v.to_string())),
+        ("igdb_id", wanted.igdb_id.map(|v| v.to_string()), have.igdb_id.map(|v| v.to_string())),
+        ("musicbrainz_id", wanted.musicbrainz_id.clone(), have.musicbrainz_id.clone()),
+        ("anilist_id", wanted.anilist_id.map(|v| v.to_string()), have.anilist_id.map(|v| v.to_string())),
+        ("mal_id", wanted.mal_id.map(|v| v.to_string()), have.mal_id.map(|v| v.to_string())),
+    ];
+
+    for (source, expected, actual) in ids {
+        if let Some(expected) = expected {
+            let matches = actual
+                .as_deref()
+                .map_or(false, |actual| actual.eq_ignore_ascii_case(&expected));
+            if !matches {
+                mismatches.push(FillMismatch::ExternalId {
+                    source: source.to_string(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+
+    if let (Some(expected), Some(actual)) = (request.year, candidate.year) {
+        if expected != actual {
+            mismatches.push(FillMismatch::Year { expected, actual });
+        }
+    }
+
+    let quality = candidate.quality.as_ref();
+    let checks = [
+        ("resolution", &request.quality.resolutions, quality.and_then(|q| q.resolution.clone())),
+        ("source", &request.quality.sources, quality.and_then(|q| q.source.clone())),
+        ("video_codec", &request.quality.video_codecs, quality.and_then(|q| q.video_codec.clone())),
+        ("audio_codec", &request.quality.audio_codecs, quality.and_then(|q| q.audio_codec.clone())),
+    ];
+
+    for (field, accepted, actual) in checks {
+        if accepted.is_empty() {
+            continue;
+        }
+
+        let ok = actual.as_deref().map_or(false, |actual| {
+            accepted
+                .iter()
+                .any(|value| normalize_quality(value) == normalize_quality(actual))
+        });
+
+        if !ok {
+            mismatches.push(FillMismatch::Quality {
+                field: field.to_string(),
+                accepted: accepted.clone(),
+                actual,
+            });
+        }
+    }
+
+    mismatches
+}
+
+/// Normalize a quality value for comparison ("WEB-DL" == "web dl" == "webdl")
+fn normalize_quality(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Split a refund pool across contributions in proportion to their amounts
+///
+/// Uses the largest remainder method so the refunds add up to exactly
+/// `pool` (capped at the total contributed).
+pub fn pro_rata_refunds(contributions: &[(Uuid, i64)], pool: i64) -> Vec<(Uuid, i64)> {
+    let total: i64 = contributions.iter().map(|(_, amount)| *amount).sum();
+    if total <= 0 || pool <= 0 {
+        return contributions.iter().map(|(id, _)| (*id, 0)).collect();
+    }
+
+    let pool = pool.min(total) as i128;
+    let total = total as i128;
+
+    let mut refunds: Vec<(Uuid, i64, i128)> = contributions
+        .iter()
+        .map(|(id, amount)| {
+            let scaled = *amount as i128 * pool;
+            (*id, (scaled / total) as i64, scaled % total)
+        })
+        .collect();
+
+    let distributed: i64 = refunds.iter().map(|(_, refund, _)| *refund).sum();
+    let mut leftover = pool as i64 - distributed;
+
+    // Hand the rounding leftovers to the largest remainders
+    let mut order: Vec<usize> = (0..refunds.len()).collect();
+    order.sort_by(|a, b| refunds[*b].2.cmp(&refunds[*a].2));
+    for index in order {
+        if leftover == 0 {
+            break;
+        }
+        refunds[index].1 += 1;
+        leftover -= 1;
+    }
+
+    refunds
+        .into_iter()
+        .map(|(id, refund, _)| (id, refund))
+        .collect()
+}
+
 /// Torrent request
 #[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 pub struct TorrentRequest {
@@ -59,6 +353,9 @@ pub struct TorrentRequest {
     /// Year
     pub year: Option<i32>,
 
+    /// Accepted quality for fills
+    pub quality: QualityRequirements,
+
     /// Total bounty amount (sum of all contributions)
     pub total_bounty: i64,
 
@@ -105,9 +402,12 @@ pub struct BountyContribution {
     /// Amount contributed (in bonus points)
     pub amount: i64,
 
-    /// Refunded (if request cancelled)
+    /// Refunded (if request cancelled or expired)
     pub refunded: bool,
 
+    /// Amount returned to the contributor (may be less than `amount` on expiry)
+    pub refunded_amount: Option<i64>,
+
     /// Created timestamp
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
@@ -154,6 +454,10 @@ pub struct CreateRequestInput {
     /// Year
     pub year: Option<i32>,
 
+    /// Accepted quality for fills
+    #[serde(default)]
+    pub quality: Option<QualityRequirements>,
+
     /// Initial bounty amount (optional)
     pub initial_bounty: Option<i64>,
 }
@@ -179,12 +483,25 @@ pub struct FillRequestInput {
     pub torrent_id: Uuid,
 }
 
+/// Request fill rejection input
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct RejectFillInput {
+    /// Request ID
+    pub request_id: Uuid,
+
+    /// Why the fill does not satisfy the request
+    #[validate(length(min = 10, max = 2000))]
+    pub reason: String,
+}
+
 /// Request service
 pub struct RequestService {
     pool: PgPool,
     min_bounty: i64,
     max_bounty_per_user: i64,
     request_expiry_days: i64,
+    fill_challenge_hours: i64,
+    expiry_refund_percent: i64,
 }
 
 impl RequestService {
@@ -194,12 +511,16 @@ impl RequestService {
         min_bounty: i64,
         max_bounty_per_user: i64,
         request_expiry_days: i64,
+        fill_challenge_hours: i64,
+        expiry_refund_percent: i64,
     ) -> Self {
         Self {
             pool,
             min_bounty,
             max_bounty_per_user,
             request_expiry_days,
+            fill_challenge_hours,
+            expiry_refund_percent: expiry_refund_percent.clamp(0, 100),
         }
     }
 
@@ -231,14 +552,16 @@ impl RequestService {
         let expires_at = chrono::Utc::now()
             + chrono::Duration::days(self.request_expiry_days);
 
+        let quality = input.quality.unwrap_or_default();
+
         // Insert request
         sqlx::query!(
             r#"
             INSERT INTO torrent_requests (
                 id, requester_id, title, description, category_id,
-                media_type, external_ids, year, total_bounty,
+                media_type, external_ids, year, quality_requirements, total_bounty,
                 bounty_contributors, vote_count, status, expires_at
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 0, 0, 0, 'open', $9)
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 0, 0, 0, 'open', $10)
             "#,
             request_id,
             user_id,
@@ -248,6 +571,7 @@ impl RequestService {
             media_type as crate::metadata::MediaType,
             serde_json::to_value(&external_ids)?,
             input.year,
+            serde_json::to_value(&quality)?,
             expires_at,
         )
         .execute(&mut *tx)
@@ -262,6 +586,10 @@ impl RequestService {
                 ));
             }
 
+            // The bounty is held by the request until it is paid or refunded
+            self.deduct_bonus_points(&mut tx, user_id, request_id, bounty)
+                .await?;
+
             self.add_bounty_internal(&mut tx, request_id, user_id, bounty)
                 .await?;
 
@@ -305,7 +633,7 @@ impl RequestService {
         let user_total = sqlx::query!(
             r#"
             SELECT COALESCE(SUM(amount), 0) as "total!"
-            FROM bounty_contributions
+            FROM torrent_request_bounties
             WHERE request_id = $1 AND user_id = $2 AND refunded = false
             "#,
             input.request_id,
@@ -323,7 +651,7 @@ impl RequestService {
         }
 
         // Deduct bonus points from user
-        self.deduct_bonus_points(&mut tx, user_id, input.amount)
+        self.deduct_bonus_points(&mut tx, user_id, input.request_id, input.amount)
             .await?;
 
         // Add bounty
@@ -346,7 +674,7 @@ impl RequestService {
         // Insert bounty contribution
         sqlx::query!(
             r#"
-            INSERT INTO bounty_contributions (id, request_id, user_id, amount)
+            INSERT INTO torrent_request_bounties (id, request_id, user_id, amount)
             VALUES ($1, $2, $3, $4)
             "#,
             Uuid::new_v4(),
@@ -365,7 +693,7 @@ impl RequestService {
                 total_bounty = total_bounty + $2,
                 bounty_contributors = (
                     SELECT COUNT(DISTINCT user_id)
-                    FROM bounty_contributions
+                    FROM torrent_request_bounties
                     WHERE request_id = $1 AND refunded = false
                 )::int,
                 updated_at = NOW()
@@ -381,25 +709,33 @@ impl RequestService {
     }
 
     /// Fill a request
-    pub async fn fill_request(&self, input: FillRequestInput, filler_id: Uuid) -> Result<()> {
+    ///
+    /// The torrent must be approved and match the request. The bounty is
+    /// placed in escrow and paid out by `release_matured_fills` once the
+    /// challenge window has passed without the fill being rejected. The
+    /// torrent's uploader is credited as the filler.
+    pub async fn fill_request(&self, input: FillRequestInput) -> Result<RequestFill> {
+        let request = self
+            .get_request(input.request_id)
+            .await?
+            .ok_or_else(|| anyhow!("Request not found"))?;
+
         let mut tx = self.pool.begin().await?;
 
-        // Check request exists and is open
-        let request = sqlx::query!(
+        // Lock the request so two fills cannot race
+        let status = sqlx::query!(
             r#"
-            SELECT
-                status as "status: RequestStatus",
-                total_bounty
+            SELECT status as "status: RequestStatus", total_bounty
             FROM torrent_requests
             WHERE id = $1
+            FOR UPDATE
             "#,
             input.request_id
         )
-        .fetch_optional(&mut *tx)
-        .await?
-        .ok_or_else(|| anyhow!("Request not found"))?;
+        .fetch_one(&mut *tx)
+        .await?;
 
-        if request.status != RequestStatus::Open {
+        if status.status != RequestStatus::Open {
             return Err(anyhow!("Request is not open"));
         }
 
@@ -407,10 +743,16 @@ impl RequestService {
         let torrent = sqlx::query!(
             r#"
             SELECT
-                uploader_id,
-                moderation_status as "status: crate::moderation::ModerationStatus"
-            FROM torrents
-            WHERE id = $1
+                t.uploader_id,
+                t.category_id,
+                t.moderation_status as "status: crate::moderation::ModerationStatus",
+                tm.media_type as "media_type: MediaType",
+                tm.external_ids,
+                tm.year,
+                tm.quality
+            FROM torrents t
+            JOIN torrent_metadata tm ON tm.id = t.id
+            WHERE t.id = $1
             "#,
             input.torrent_id
         )
@@ -422,6 +764,26 @@ impl RequestService {
             return Err(anyhow!("Torrent must be approved"));
         }
 
+        let candidate = FillCandidate {
+            category_id: torrent.category_id,
+            media_type: torrent.media_type,
+            external_ids: torrent
+                .external_ids
+                .and_then(|v| serde_json::from_value(v).ok())
+                .unwrap_or_default(),
+            year: torrent.year,
+            quality: torrent.quality.and_then(|v| serde_json::from_value(v).ok()),
+        };
+
+        let mismatches = match_fill(&request, &candidate);
+        if !mismatches.is_empty() {
+            let reasons: Vec<String> = mismatches.iter().map(|m| m.to_string()).collect();
+            return Err(anyhow!(
+                "Torrent does not match request: {}",
+                reasons.join("; ")
+            ));
+        }
+
         // Mark request as filled
         sqlx::query!(
             r#"
@@ -441,15 +803,248 @@ impl RequestService {
         .execute(&mut *tx)
         .await?;
 
-        // Award bounty to uploader
-        if request.total_bounty > 0 {
-            self.award_bonus_points(&mut tx, torrent.uploader_id, request.total_bounty)
+        // Hold the bounty until the challenge window passes
+        let challenge_until = Utc::now() + chrono::Duration::hours(self.fill_challenge_hours);
+
+        let fill = sqlx::query_as!(
+            RequestFill,
+            r#"
+            INSERT INTO request_fills (
+                id, request_id, torrent_id, filler_id, bounty, status, challenge_until
+            ) VALUES ($1, $2, $3, $4, $5, 'pending', $6)
+            RETURNING
+                id, request_id, torrent_id, filler_id, bounty,
+                status as "status: FillStatus",
+                challenge_until, paid_at, rejected_by, rejection_reason, created_at
+            "#,
+            Uuid::new_v4(),
+            input.request_id,
+            input.torrent_id,
+            torrent.uploader_id,
+            status.total_bounty,
+            challenge_until
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(fill)
+    }
+
+    /// Reject a fill and reopen the request
+    ///
+    /// The requester may reject a fill while its challenge window is open.
+    /// Staff may reject at any time; if the bounty was already paid it is
+    /// taken back from the filler.
+    pub async fn reject_fill(
+        &self,
+        user_id: Uuid,
+        is_staff: bool,
+        input: RejectFillInput,
+    ) -> Result<RequestFill> {
+        input.validate().context("Invalid rejection input")?;
+
+        let mut tx = self.pool.begin().await?;
+
+        let request = sqlx::query!(
+            r#"
+            SELECT requester_id, status as "status: RequestStatus"
+            FROM torrent_requests
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+            input.request_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| anyhow!("Request not found"))?;
+
+        if request.status != RequestStatus::Filled {
+            return Err(anyhow!("Request is not filled"));
+        }
+
+        let fill = sqlx::query_as!(
+            RequestFill,
+            r#"
+            SELECT
+                id, request_id, torrent_id, filler_id, bounty,
+                status as "status: FillStatus",
+                challenge_until, paid_at, rejected_by, rejection_reason, created_at
+            FROM request_fills
+            WHERE request_id = $1 AND status <> 'rejected'
+            FOR UPDATE
+            "#,
+            input.request_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| anyhow!("Request has no active fill"))?;
+
+        if !is_staff {
+            if request.requester_id != user_id {
+                return Err(anyhow!("Only the requester or staff can reject a fill"));
+            }
+            if fill.status != FillStatus::Pending || Utc::now() >= fill.challenge_until {
+                return Err(anyhow!("The challenge window for this fill has closed"));
+            }
+        }
+
+        // Undo the payout; the filler's balance may go negative
+        if fill.status == FillStatus::Paid && fill.bounty > 0 {
+            self.clawback_bonus_points(&mut tx, fill.filler_id, fill.request_id, fill.bounty)
                 .await?;
         }
 
+        let rejected = sqlx::query_as!(
+            RequestFill,
+            r#"
+            UPDATE request_fills
+            SET
+                status = 'rejected',
+                rejected_by = $2,
+                rejected_at = NOW(),
+                rejection_reason = $3
+            WHERE id = $1
+            RETURNING
+                id, request_id, torrent_id, filler_id, bounty,
+                status as "status: FillStatus",
+                challenge_until, paid_at, rejected_by, rejection_reason, created_at
+            "#,
+            fill.id,
+            user_id,
+            input.reason
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // Reopen the request
+        sqlx::query!(
+            r#"
+            UPDATE torrent_requests
+            SET
+                status = 'open',
+                filled_by_torrent_id = NULL,
+                filled_by_user_id = NULL,
+                filled_at = NULL,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+            input.request_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
         tx.commit().await?;
 
-        Ok(())
+        Ok(rejected)
+    }
+
+    /// Pay out fills whose challenge window has passed
+    ///
+    /// Safe to run concurrently; each fill is paid exactly once. Returns the
+    /// number of fills paid.
+    pub async fn release_matured_fills(&self) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let matured = sqlx::query!(
+            r#"
+            SELECT id, request_id, filler_id, bounty
+            FROM request_fills
+            WHERE status = 'pending' AND challenge_until <= NOW()
+            ORDER BY challenge_until
+            FOR UPDATE SKIP LOCKED
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for fill in &matured {
+            if fill.bounty > 0 {
+                self.award_bonus_points(
+                    &mut tx,
+                    fill.filler_id,
+                    fill.request_id,
+                    fill.bounty,
+                    "request_reward",
+                )
+                .await?;
+            }
+
+            sqlx::query!(
+                r#"
+                UPDATE request_fills
+                SET status = 'paid', paid_at = NOW()
+                WHERE id = $1
+                "#,
+                fill.id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(matured.len() as u64)
+    }
+
+    /// Expire open requests past their expiry date
+    ///
+    /// Contributors are refunded pro rata from `expiry_refund_percent` of
+    /// the bounty pool. Returns the number of requests expired.
+    pub async fn expire_requests(&self) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let expired = sqlx::query!(
+            r#"
+            SELECT id
+            FROM torrent_requests
+            WHERE status = 'open' AND expires_at <= NOW()
+            FOR UPDATE SKIP LOCKED
+            "#
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for request in &expired {
+            sqlx::query!(
+                r#"
+                UPDATE torrent_requests
+                SET status = 'expired', updated_at = NOW()
+                WHERE id = $1
+                "#,
+                request.id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            self.refund_bounties(&mut tx, request.id, self.expiry_refund_percent)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(expired.len() as u64)
+    }
+
+    /// Get the active (pending or paid) fill of a request
+    pub async fn get_active_fill(&self, request_id: Uuid) -> Result<Option<RequestFill>> {
+        let fill = sqlx::query_as!(
+            RequestFill,
+            r#"
+            SELECT
+                id, request_id, torrent_id, filler_id, bounty,
+                status as "status: FillStatus",
+                challenge_until, paid_at, rejected_by, rejection_reason, created_at
+            FROM request_fills
+            WHERE request_id = $1 AND status <> 'rejected'
+            "#,
+            request_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(fill)
     }
 
     /// Vote on a request
@@ -564,7 +1159,7 @@ impl RequestService {
         .await?;
 
         // Refund all bounties
-        self.refund_bounties(&mut tx, request_id).await?;
+        self.refund_bounties(&mut tx, request_id, 100).await?;
 
         tx.commit().await?;
 
@@ -572,16 +1167,20 @@ impl RequestService {
     }
 
     /// Refund bounties for a request
+    ///
+    /// `percent` of the outstanding pool is split across contributors in
+    /// proportion to what they put in.
     async fn refund_bounties(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         request_id: Uuid,
+        percent: i64,
     ) -> Result<()> {
         // Get all contributions
         let contributions = sqlx::query!(
             r#"
             SELECT id, user_id, amount
-            FROM bounty_contributions
+            FROM torrent_request_bounties
             WHERE request_id = $1 AND refunded = false
             "#,
             request_id
@@ -589,18 +1188,32 @@ impl RequestService {
         .fetch_all(&mut **tx)
         .await?;
 
+        let total: i64 = contributions.iter().map(|c| c.amount).sum();
+        let pool = total * percent / 100;
+        let shares: Vec<(Uuid, i64)> = contributions.iter().map(|c| (c.id, c.amount)).collect();
+        let refunds = pro_rata_refunds(&shares, pool);
+
         // Refund each contribution
-        for contribution in contributions {
-            self.award_bonus_points(&mut **tx, contribution.user_id, contribution.amount)
+        for (contribution, (_, refund)) in contributions.iter().zip(refunds) {
+            if refund > 0 {
+                self.award_bonus_points(
+                    tx,
+                    contribution.user_id,
+                    request_id,
+                    refund,
+                    "request_refund",
+                )
                 .await?;
+            }
 
             sqlx::query!(
                 r#"
-                UPDATE bounty_contributions
-                SET refunded = true
+                UPDATE torrent_request_bounties
+                SET refunded = true, refunded_amount = $2
                 WHERE id = $1
                 "#,
-                contribution.id
+                contribution.id,
+                refund
             )
             .execute(&mut **tx)
             .await?;
@@ -616,7 +1229,7 @@ impl RequestService {
             SELECT
                 id, requester_id, title, description, category_id,
                 media_type as "media_type: crate::metadata::MediaType",
-                external_ids, year, total_bounty, bounty_contributors,
+                external_ids, year, quality_requirements, total_bounty, bounty_contributors,
                 vote_count, status as "status: RequestStatus",
                 filled_by_torrent_id, filled_by_user_id, filled_at,
                 created_at, updated_at, expires_at
@@ -644,6 +1257,7 @@ impl RequestService {
                     media_type: r.media_type,
                     external_ids,
                     year: r.year,
+                    quality: serde_json::from_value(r.quality_requirements).unwrap_or_default(),
                     total_bounty: r.total_bounty,
                     bounty_contributors: r.bounty_contributors,
                     vote_count: r.vote_count,
@@ -664,8 +1278,8 @@ impl RequestService {
     pub async fn get_contributions(&self, request_id: Uuid) -> Result<Vec<BountyContribution>> {
         let records = sqlx::query!(
             r#"
-            SELECT id, request_id, user_id, amount, refunded, created_at
-            FROM bounty_contributions
+            SELECT id, request_id, user_id, amount, refunded, refunded_amount, created_at
+            FROM torrent_request_bounties
             WHERE request_id = $1
             ORDER BY created_at DESC
             "#,
@@ -682,6 +1296,7 @@ impl RequestService {
                 user_id: r.user_id,
                 amount: r.amount,
                 refunded: r.refunded,
+                refunded_amount: r.refunded_amount,
                 created_at: r.created_at,
             })
             .collect())
@@ -700,7 +1315,7 @@ impl RequestService {
                 SELECT
                     id, requester_id, title, description, category_id,
                     media_type as "media_type: crate::metadata::MediaType",
-                    external_ids, year, total_bounty, bounty_contributors,
+                    external_ids, year, quality_requirements, total_bounty, bounty_contributors,
                     vote_count, status as "status: RequestStatus",
                     filled_by_torrent_id, filled_by_user_id, filled_at,
                     created_at, updated_at, expires_at
@@ -721,7 +1336,7 @@ impl RequestService {
                 SELECT
                     id, requester_id, title, description, category_id,
                     media_type as "media_type: crate::metadata::MediaType",
-                    external_ids, year, total_bounty, bounty_contributors,
+                    external_ids, year, quality_requirements, total_bounty, bounty_contributors,
                     vote_count, status as "status: RequestStatus",
                     filled_by_torrent_id, filled_by_user_id, filled_at,
                     created_at, updated_at, expires_at
@@ -753,6 +1368,7 @@ impl RequestService {
                     media_type: r.media_type,
                     external_ids,
                     year: r.year,
+                    quality: serde_json::from_value(r.quality_requirements).unwrap_or_default(),
                     total_bounty: r.total_bounty,
                     bounty_contributors: r.bounty_contributors,
                     vote_count: r.vote_count,
@@ -773,48 +1389,88 @@ impl RequestService {
         &self,
         tx: &mut Transaction<'_, Postgres>,
         user_id: Uuid,
+        request_id: Uuid,
         amount: i64,
     ) -> Result<()> {
-        let result = sqlx::query!(
-            r#"
-            UPDATE user_stats
-            SET bonus_points = bonus_points - $2
-            WHERE user_id = $1 AND bonus_points >= $2
-            RETURNING bonus_points
-            "#,
-            user_id,
-            amount
-        )
-        .fetch_optional(&mut **tx)
-        .await?;
+        let recorded = self
+            .adjust_bonus_points(tx, user_id, request_id, -amount, "request_bounty", false)
+            .await?;
 
-        if result.is_none() {
+        if !recorded {
             return Err(anyhow!("Insufficient bonus points"));
         }
 
         Ok(())
     }
 
+    /// Take back bonus points that were paid out, even below zero
+    async fn clawback_bonus_points(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        request_id: Uuid,
+        amount: i64,
+    ) -> Result<()> {
+        self.adjust_bonus_points(tx, user_id, request_id, -amount, "request_clawback", true)
+            .await?;
+
+        Ok(())
+    }
+
     /// Award bonus points to user
     async fn award_bonus_points(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         user_id: Uuid,
+        request_id: Uuid,
         amount: i64,
+        transaction_type: &str,
     ) -> Result<()> {
-        sqlx::query!(
+        self.adjust_bonus_points(tx, user_id, request_id, amount, transaction_type, true)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Change a user's bonus balance and record it in the ledger
+    ///
+    /// Returns false, changing nothing, if the balance would go below zero
+    /// and `allow_negative` is not set.
+    async fn adjust_bonus_points(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        request_id: Uuid,
+        amount: i64,
+        transaction_type: &str,
+        allow_negative: bool,
+    ) -> Result<bool> {
+        let recorded = sqlx::query_scalar!(
             r#"
-            UPDATE user_stats
-            SET bonus_points = bonus_points + $2
-            WHERE user_id = $1
+            WITH updated AS (
+                UPDATE user_statistics
+                SET seedbonus = seedbonus + $3::BIGINT, updated_at = NOW()
+                WHERE user_id = $1 AND ($5 OR seedbonus + $3::BIGINT >= 0)
+                RETURNING seedbonus
+            )
+            INSERT INTO bonus_transactions (
+                user_id, transaction_type, amount, balance_before, balance_after,
+                source_type, source_id
+            )
+            SELECT $1, $4, $3::BIGINT, seedbonus - $3::BIGINT, seedbonus, 'request', $2
+            FROM updated
+            RETURNING id
             "#,
             user_id,
-            amount
+            request_id,
+            amount,
+            transaction_type,
+            allow_negative
         )
-        .execute(&mut **tx)
+        .fetch_optional(&mut **tx)
         .await?;
 
-        Ok(())
+        Ok(recorded.is_some())
     }
 }
 
@@ -827,4 +1483,110 @@ mod tests {
         assert_eq!(RequestStatus::Open as i32, 0);
         assert!(RequestStatus::Open != RequestStatus::Filled);
     }
+
+    fn request() -> TorrentRequest {
+        TorrentRequest {
+            id: Uuid::new_v4(),
+            requester_id: Uuid::new_v4(),
+            title: "Some Movie".to_string(),
+            description: "Looking for a good encode".to_string(),
+            category_id: Uuid::new_v4(),
+            media_type: MediaType::Movie,
+            external_ids: ExternalIds {
+                imdb_id: Some("tt0111161".to_string()),
+                ..Default::default()
+            },
+            year: Some(1994),
+            quality: QualityRequirements {
+                resolutions: vec!["1080p".to_string(), "2160p".to_string()],
+                sources: vec!["BluRay".to_string()],
+                ..Default::default()
+            },
+            total_bounty: 1000,
+            bounty_contributors: 1,
+            vote_count: 0,
+            status: RequestStatus::Open,
+            filled_by_torrent_id: None,
+            filled_by_user_id: None,
+            filled_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            expires_at: None,
+        }
+    }
+
+    fn candidate(request: &TorrentRequest) -> FillCandidate {
+        FillCandidate {
+            category_id: request.category_id,
+            media_type: MediaType::Movie,
+            external_ids: ExternalIds {
+                imdb_id: Some("TT0111161".to_string()),
+                tmdb_id: Some(278),
+                ..Default::default()
+            },
+            year: Some(1994),
+            quality: Some(QualityInfo {
+                resolution: Some("1080p".to_string()),
+                video_codec: Some("H.264".to_string()),
+                audio_codec: None,
+                source: Some("Blu-Ray".to_string()),
+                container: None,
+                audio_channels: None,
+                bitrate: None,
+                hdr: None,
+                release_group: None,
+                is_scene: false,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_match_fill_accepts_matching_torrent() {
+        let request = request();
+        assert!(match_fill(&request, &candidate(&request)).is_empty());
+    }
+
+    #[test]
+    fn test_match_fill_reports_mismatches() {
+        let request = request();
+        let mut torrent = candidate(&request);
+        torrent.category_id = Uuid::new_v4();
+        torrent.external_ids.imdb_id = None;
+        if let Some(quality) = torrent.quality.as_mut() {
+            quality.resolution = Some("720p".to_string());
+        }
+
+        let mismatches = match_fill(&request, &torrent);
+        assert_eq!(mismatches.len(), 3);
+        assert_eq!(mismatches[0], FillMismatch::Category);
+        assert!(matches!(
+            &mismatches[1],
+            FillMismatch::ExternalId { source, actual: None, .. } if source == "imdb_id"
+        ));
+        assert!(matches!(
+            &mismatches[2],
+            FillMismatch::Quality { field, .. } if field == "resolution"
+        ));
+    }
+
+    #[test]
+    fn test_pro_rata_refunds() {
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        let c = Uuid::new_v4();
+        let contributions = [(a, 500), (b, 300), (c, 200)];
+
+        // Full refund returns each contribution
+        assert_eq!(
+            pro_rata_refunds(&contributions, 1000),
+            vec![(a, 500), (b, 300), (c, 200)]
+        );
+
+        // Partial pool is split proportionally and adds up exactly
+        let refunds = pro_rata_refunds(&[(a, 1), (b, 1), (c, 1)], 2);
+        assert_eq!(refunds.iter().map(|(_, r)| r).sum::<i64>(), 2);
+
+        let refunds = pro_rata_refunds(&contributions, 900);
+        assert_eq!(refunds, vec![(a, 450), (b, 270), (c, 180)]);
+    }
 }
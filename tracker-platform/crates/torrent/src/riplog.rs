@@ -0,0 +1,1170 @@
+//! Rip log verification for lossless music uploads
+//!
+//! Uploaders of lossless music attach the EAC or XLD log (and usually the
+//! cue sheet) produced while ripping the CD. This module:
+//! - Decodes logs (EAC writes UTF-16LE)
+//! - Parses ripper settings and per-track results
+//! - Checks test/copy CRCs, AccurateRip results and read errors
+//! - Checks the log's self-signature (EAC log checksum, XLD signature)
+//! - Computes a 0-100 log score from a fixed deduction table
+//! - Cross-checks logs and cue sheets against the torrent's file list
+//!
+//! The signatures are produced with the rippers' own undocumented schemes,
+//! so cryptographic verification is delegated to a [`LogSignatureVerifier`]
+//! (typically a wrapper around an external logchecker). Without one, a
+//! well-formed signature is reported as [`ChecksumStatus::Unverified`] and
+//! scored like a missing one; a malformed or truncated one, or text
+//! appended after it, is always [`ChecksumStatus::Invalid`].
+//!
+//! The resulting score is stored with the torrent, indexed for search and
+//! used by [`can_trump`] to decide whether a new rip replaces an existing one.
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+use crate::{
+    cue::CueSheet,
+    files::{FileType, TorrentFileInfo},
+    rules::{RuleViolation, ViolationCode},
+};
+
+/// Score deductions
+const DEDUCT_NOT_SECURE: i16 = 20;
+const DEDUCT_ACCURATE_STREAM: i16 = 5;
+const DEDUCT_AUDIO_CACHE: i16 = 10;
+const DEDUCT_C2_POINTERS: i16 = 10;
+const DEDUCT_GAP_HANDLING: i16 = 10;
+const DEDUCT_NULL_SAMPLES: i16 = 5;
+const DEDUCT_RANGE_RIP: i16 = 30;
+const DEDUCT_NO_TEST_COPY: i16 = 10;
+const DEDUCT_CRC_MISMATCH: i16 = 30;
+const DEDUCT_TRACK_ERRORS: i16 = 20;
+const DEDUCT_NO_CHECKSUM: i16 = 15;
+const DEDUCT_UNKNOWN_SETTING: i16 = 1;
+
+/// Lossless audio extensions that call for a rip log
+const LOSSLESS_EXTENSIONS: &[&str] = &["flac", "ape", "wav", "alac", "aiff", "wv"];
+
+/// Ripping software that produced a log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "rip_log_ripper", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Ripper {
+    /// Exact Audio Copy
+    Eac,
+
+    /// X Lossless Decoder
+    Xld,
+}
+
+/// State of a log's self-signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "log_checksum_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumStatus {
+    /// Log carries no signature
+    Missing,
+
+    /// Signature is well-formed but could not be checked
+    Unverified,
+
+    /// Signature checked and matches
+    Valid,
+
+    /// Signature malformed, mismatched, or the log was edited after signing
+    Invalid,
+}
+
+/// Cryptographic verification of a log's self-signature
+pub trait LogSignatureVerifier: Send + Sync {
+    /// Check the signature of a log; `Ok(false)` means the log was altered
+    fn verify(&self, ripper: Ripper, log: &str) -> Result<bool>;
+}
+
+/// Per-track results from a log
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackReport {
+    /// Track number
+    pub number: u32,
+
+    /// CRC of the test pass
+    pub test_crc: Option<String>,
+
+    /// CRC of the copy pass
+    pub copy_crc: Option<String>,
+
+    /// AccurateRip result (`None` if not in the database)
+    pub accurately_ripped: Option<bool>,
+
+    /// Suspicious positions or read errors reported for the track
+    pub errors: u32,
+}
+
+impl TrackReport {
+    /// Whether the test and copy CRCs disagree
+    pub fn crc_mismatch(&self) -> bool {
+        match (&self.test_crc, &self.copy_crc) {
+            (Some(test), Some(copy)) => !test.eq_ignore_ascii_case(copy),
+            _ => false,
+        }
+    }
+}
+
+/// Parsed rip log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RipLog {
+    /// Ripping software
+    pub ripper: Ripper,
+
+    /// Ripper version line
+    pub version: Option<String>,
+
+    /// Drive used
+    pub drive: Option<String>,
+
+    /// Secure (or paranoia) read mode
+    pub secure_mode: Option<bool>,
+
+    /// EAC "Utilize accurate stream"
+    pub accurate_stream: Option<bool>,
+
+    /// Audio cache defeated
+    pub defeat_audio_cache: Option<bool>,
+
+    /// C2 error pointers used
+    pub c2_pointers: Option<bool>,
+
+    /// Gaps detected (rather than left undetected)
+    pub gaps_detected: Option<bool>,
+
+    /// EAC "Null samples used in CRC calculations"
+    pub null_samples: Option<bool>,
+
+    /// Ripped as a range rather than per track
+    pub range_rip: bool,
+
+    /// Per-track results
+    pub tracks: Vec<TrackReport>,
+
+    /// Signature text (EAC checksum or XLD signature block)
+    pub signature: Option<String>,
+}
+
+/// A single score deduction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deduction {
+    /// Points deducted
+    pub points: i16,
+
+    /// Reason
+    pub reason: String,
+}
+
+/// Verification result for one log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogReport {
+    /// Parsed log
+    pub log: RipLog,
+
+    /// Signature state
+    pub checksum: ChecksumStatus,
+
+    /// Score (0-100)
+    pub score: i16,
+
+    /// Deductions that make up the score
+    pub deductions: Vec<Deduction>,
+
+    /// SHA-256 of the log text (hex)
+    pub sha256: String,
+}
+
+/// Verification result for an upload's logs and cue sheets
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RipVerification {
+    /// Per-log reports
+    pub logs: Vec<LogReport>,
+
+    /// Parsed cue sheets
+    pub cue_sheets: Vec<CueSheet>,
+
+    /// Problems found while verifying
+    pub violations: Vec<RuleViolation>,
+}
+
+impl RipVerification {
+    /// Torrent log score: the lowest score across its logs
+    pub fn log_score(&self) -> Option<i16> {
+        self.logs.iter().map(|l| l.score).min()
+    }
+
+    /// Whether at least one cue sheet was supplied
+    pub fn has_cue(&self) -> bool {
+        !self.cue_sheets.is_empty()
+    }
+
+    /// Summary used for search and trumping
+    pub fn summary(&self) -> RipSummary {
+        RipSummary {
+            log_score: self.log_score(),
+            has_cue: self.has_cue(),
+        }
+    }
+}
+
+/// Stored log score and cue state of a torrent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RipSummary {
+    /// Log score (`None` without a log)
+    pub log_score: Option<i16>,
+
+    /// Whether a cue sheet was supplied
+    pub has_cue: bool,
+}
+
+/// Whether a rip replaces an existing rip of the same release
+///
+/// A higher log score trumps; on equal scores, adding a cue sheet trumps.
+/// A torrent with a log always trumps one without.
+pub fn can_trump(candidate: &RipSummary, existing: &RipSummary) -> bool {
+    match (candidate.log_score, existing.log_score) {
+        (None, _) => false,
+        (Some(_), None) => true,
+        (Some(new), Some(old)) if new != old => new > old,
+        _ => candidate.has_cue && !existing.has_cue,
+    }
+}
+
+/// Decode an uploaded log or cue file
+///
+/// EAC writes UTF-16LE with a BOM; XLD and most cue sheets are UTF-8. Text
+/// that is neither is decoded as Latin-1 so no bytes are lost.
+pub fn decode_log(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|c| from([c[0], c[1]])).collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        utf16(rest, u16::from_be_bytes)
+    } else {
+        let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+        match std::str::from_utf8(bytes) {
+            Ok(text) => text.to_string(),
+            Err(_) => bytes.iter().map(|&b| b as char).collect(),
+        }
+    }
+}
+
+fn track_header() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^Track\s+(\d+)$").unwrap())
+}
+
+fn eac_checksum() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?m)^==== Log checksum (\S+) ====\s*$").unwrap())
+}
+
+const XLD_SIGNATURE_BEGIN: &str = "-----BEGIN XLD SIGNATURE-----";
+const XLD_SIGNATURE_END: &str = "-----END XLD SIGNATURE-----";
+
+fn yes_no(value: &str) -> Option<bool> {
+    let value = value.trim();
+    let word = value.split_whitespace().next().unwrap_or("");
+    match word.to_ascii_lowercase().as_str() {
+        "yes" | "ok" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+impl RipLog {
+    /// Parse an EAC or XLD log
+    pub fn parse(text: &str) -> Result<Self> {
+        let first = text
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .unwrap_or("");
+
+        let ripper = if first.starts_with("Exact Audio Copy")
+            || first.starts_with("EAC extraction logfile")
+        {
+            Ripper::Eac
+        } else if first.starts_with("X Lossless Decoder") {
+            Ripper::Xld
+        } else {
+            return Err(anyhow!("Not an EAC or XLD log"));
+        };
+
+        let mut log = RipLog {
+            ripper,
+            version: Some(first.to_string()),
+            drive: None,
+            secure_mode: None,
+            accurate_stream: None,
+            defeat_audio_cache: None,
+            c2_pointers: None,
+            gaps_detected: None,
+            null_samples: None,
+            range_rip: false,
+            tracks: Vec::new(),
+            signature: None,
+        };
+
+        for raw in text.lines() {
+            let line = raw.trim();
+
+            if let Some(caps) = track_header().captures(line) {
+                log.tracks.push(TrackReport {
+                    number: caps[1].parse()?,
+                    ..Default::default()
+                });
+                continue;
+            }
+
+            if line.starts_with("Range status and errors") || line.starts_with("Selected range") {
+                log.range_rip = true;
+                continue;
+            }
+
+            match ripper {
+                Ripper::Eac => log.parse_eac_line(line),
+                Ripper::Xld => log.parse_xld_line(line),
+            }
+        }
+
+        log.signature = match ripper {
+            Ripper::Eac => eac_checksum()
+                .captures_iter(text)
+                .last()
+                .map(|caps| caps[1].to_string()),
+            Ripper::Xld => text.find(XLD_SIGNATURE_BEGIN).map(|start| {
+                text[start + XLD_SIGNATURE_BEGIN.len()..]
+                    .split(XLD_SIGNATURE_END)
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .to_string()
+            }),
+        };
+
+        if log.tracks.is_empty() && !log.range_rip {
+            return Err(anyhow!("Log contains no tracks"));
+        }
+
+        Ok(log)
+    }
+
+    fn parse_eac_line(&mut self, line: &str) {
+        if let Some(track) = self.tracks.last_mut() {
+            if let Some(crc) = line.strip_prefix("Test CRC") {
+                track.test_crc = Some(crc.trim().to_string());
+            } else if let Some(crc) = line.strip_prefix("Copy CRC") {
+                track.copy_crc = Some(crc.trim().to_string());
+            } else if line.starts_with("Accurately ripped") {
+                track.accurately_ripped = Some(true);
+            } else if line.starts_with("Cannot be verified as accurate") {
+                track.accurately_ripped = Some(false);
+            } else if line.starts_with("Suspicious position") || line.starts_with("Missing samples")
+            {
+                track.errors += 1;
+            }
+            return;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            return;
+        };
+
+        match key.trim() {
+            "Used drive" => {
+                let drive = value.split("Adapter:").next().unwrap_or(value);
+                self.drive = Some(drive.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+            "Read mode" => self.secure_mode = Some(value.trim().starts_with("Secure")),
+            "Utilize accurate stream" => self.accurate_stream = yes_no(value),
+            "Defeat audio cache" => self.defeat_audio_cache = yes_no(value),
+            "Make use of C2 pointers" => self.c2_pointers = yes_no(value),
+            "Null samples used in CRC calculations" => self.null_samples = yes_no(value),
+            "Gap handling" => {
+                self.gaps_detected = Some(!value.trim().starts_with("Not detected"));
+            }
+            _ => {}
+        }
+    }
+
+    fn parse_xld_line(&mut self, line: &str) {
+        let Some((key, value)) = line.split_once(':') else {
+            if let Some(track) = self.tracks.last_mut() {
+                if line.starts_with("->Accurately ripped") {
+                    track.accurately_ripped = Some(true);
+                } else if line.starts_with("->Rip may not be accurate") {
+                    track.accurately_ripped = Some(false);
+                }
+            }
+            return;
+        };
+        let key = key.trim();
+        let value = value.trim();
+
+        if let Some(track) = self.tracks.last_mut() {
+            match key {
+                "CRC32 hash (test run)" => track.test_crc = Some(value.to_string()),
+                "CRC32 hash" => track.copy_crc = Some(value.to_string()),
+                "Read error"
+                | "Skipped (treated as error)"
+                | "Inconsistency in error sectors"
+                | "Damaged sector count" => {
+                    track.errors += value.parse::<u32>().unwrap_or(0);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        match key {
+            "Used drive" => self.drive = Some(value.to_string()),
+            "Ripper mode" => self.secure_mode = Some(value.starts_with("XLD Secure Ripper")),
+            "Use cdparanoia mode" => self.secure_mode = yes_no(value),
+            "Disable audio cache" => self.defeat_audio_cache = yes_no(value),
+            "Make use of C2 Error Pointers" => self.c2_pointers = yes_no(value),
+            "Gap status" => self.gaps_detected = Some(!value.starts_with("Not analyzed")),
+            _ => {}
+        }
+    }
+
+    /// Check the log's self-signature
+    pub fn check_signature(
+        &self,
+        text: &str,
+        verifier: Option<&dyn LogSignatureVerifier>,
+    ) -> ChecksumStatus {
+        let Some(ref signature) = self.signature else {
+            // A lone BEGIN marker or checksum line we could not read
+            let marker = match self.ripper {
+                Ripper::Eac => "Log checksum",
+                Ripper::Xld => XLD_SIGNATURE_BEGIN,
+            };
+            return if text.contains(marker) {
+                ChecksumStatus::Invalid
+            } else {
+                ChecksumStatus::Missing
+            };
+        };
+
+        let well_formed = match self.ripper {
+            Ripper::Eac => {
+                let trailing = eac_checksum()
+                    .find_iter(text)
+                    .last()
+                    .map_or("", |m| &text[m.end()..]);
+                signature.len() == 64
+                    && signature.chars().all(|c| c.is_ascii_hexdigit())
+                    && trailing.trim().is_empty()
+            }
+            Ripper::Xld => {
+                let trailing = text.rsplit(XLD_SIGNATURE_END).next().unwrap_or("");
+                !signature.is_empty()
+                    && text.contains(XLD_SIGNATURE_END)
+                    && trailing.trim().is_empty()
+            }
+        };
+
+        if !well_formed {
+            return ChecksumStatus::Invalid;
+        }
+
+        match verifier.map(|v| v.verify(self.ripper, text)) {
+            Some(Ok(true)) => ChecksumStatus::Valid,
+            Some(Ok(false)) => ChecksumStatus::Invalid,
+            Some(Err(e)) => {
+                tracing::warn!("Log signature verification failed: {}", e);
+                ChecksumStatus::Unverified
+            }
+            None => ChecksumStatus::Unverified,
+        }
+    }
+
+    /// Score the log
+    ///
+    /// Starts at 100 and applies the deduction table. An invalid signature
+    /// means the log was edited, which scores 0.
+    pub fn score(&self, checksum: ChecksumStatus) -> (i16, Vec<Deduction>) {
+        let mut deductions = Vec::new();
+        let mut deduct =
+            |points: i16, reason: String| deductions.push(Deduction { points, reason });
+
+        // An unchecked signature proves nothing, so it costs as much as none
+        match checksum {
+            ChecksumStatus::Invalid => {
+                deduct(100, "Log signature is invalid (edited log)".to_string())
+            }
+            ChecksumStatus::Missing => {
+                deduct(DEDUCT_NO_CHECKSUM, "Log has no checksum".to_string())
+            }
+            ChecksumStatus::Unverified => deduct(
+                DEDUCT_NO_CHECKSUM,
+                "Log checksum could not be verified".to_string(),
+            ),
+            ChecksumStatus::Valid => {}
+        }
+
+        let mut setting = |value: Option<bool>, good: bool, points: i16, name: &str| match value {
+            Some(v) if v == good => {}
+            Some(_) => deduct(points, format!("{}: bad setting", name)),
+            None => deduct(
+                DEDUCT_UNKNOWN_SETTING,
+                format!("{}: could not verify", name),
+            ),
+        };
+
+        setting(
+            self.secure_mode,
+            true,
+            DEDUCT_NOT_SECURE,
+            "Read mode not secure",
+        );
+        setting(
+            self.defeat_audio_cache,
+            true,
+            DEDUCT_AUDIO_CACHE,
+            "Audio cache not defeated",
+        );
+        setting(
+            self.c2_pointers,
+            false,
+            DEDUCT_C2_POINTERS,
+            "C2 pointers used",
+        );
+        if !self.range_rip {
+            setting(
+                self.gaps_detected,
+                true,
+                DEDUCT_GAP_HANDLING,
+                "Gaps not detected",
+            );
+        }
+        if self.ripper == Ripper::Eac {
+            setting(
+                self.accurate_stream,
+                true,
+                DEDUCT_ACCURATE_STREAM,
+                "Accurate stream not used",
+            );
+            setting(
+                self.null_samples,
+                true,
+                DEDUCT_NULL_SAMPLES,
+                "Null samples not used in CRC",
+            );
+        }
+
+        if self.range_rip {
+            deduct(DEDUCT_RANGE_RIP, "Range rip".to_string());
+        }
+
+        if self.tracks.iter().any(|t| t.test_crc.is_none()) {
+            deduct(DEDUCT_NO_TEST_COPY, "Test and copy not used".to_string());
+        }
+
+        for track in &self.tracks {
+            if track.crc_mismatch() {
+                deduct(
+                    DEDUCT_CRC_MISMATCH,
+                    format!("Track {}: test and copy CRCs differ", track.number),
+                );
+            }
+            if track.errors > 0 {
+                deduct(
+                    DEDUCT_TRACK_ERRORS,
+                    format!("Track {}: {} read error(s)", track.number, track.errors),
+                );
+            }
+        }
+
+        let total: i32 = deductions.iter().map(|d| d.points as i32).sum();
+        let score = (100 - total).max(0) as i16;
+
+        (score, deductions)
+    }
+}
+
+/// Verify an upload's logs and cue sheets against its file list
+pub fn verify_rip(
+    logs: &[String],
+    cue_sheets: &[String],
+    files: &[TorrentFileInfo],
+    verifier: Option<&dyn LogSignatureVerifier>,
+) -> RipVerification {
+    let mut verification = RipVerification::default();
+
+    for (idx, text) in logs.iter().enumerate() {
+        let field = format!("rip_logs[{}]", idx);
+
+        let log = match RipLog::parse(text) {
+            Ok(log) => log,
+            Err(e) => {
+                verification.violations.push(
+                    RuleViolation::error(
+                        ViolationCode::InvalidRipLog,
+                        format!("Unreadable rip log: {}", e),
+                    )
+                    .at(field),
+                );
+                continue;
+            }
+        };
+
+        let checksum = log.check_signature(text, verifier);
+        if checksum == ChecksumStatus::Invalid {
+            verification.violations.push(
+                RuleViolation::error(
+                    ViolationCode::EditedRipLog,
+                    "Rip log signature is invalid; logs must not be edited",
+                )
+                .at(field),
+            );
+        }
+
+        let (score, deductions) = log.score(checksum);
+        verification.logs.push(LogReport {
+            log,
+            checksum,
+            score,
+            deductions,
+            sha256: hex::encode(Sha256::digest(text.as_bytes())),
+        });
+    }
+
+    for (idx, text) in cue_sheets.iter().enumerate() {
+        let field = format!("cue_sheets[{}]", idx);
+
+        let sheet = match CueSheet::parse(text) {
+            Ok(sheet) => sheet,
+            Err(e) => {
+                verification.violations.push(
+                    RuleViolation::error(
+                        ViolationCode::InvalidCueSheet,
+                        format!("Unreadable cue sheet: {}", e),
+                    )
+                    .at(field),
+                );
+                continue;
+            }
+        };
+
+        for problem in sheet.check_consistency() {
+            verification
+                .violations
+                .push(RuleViolation::warning(ViolationCode::RipLogMismatch, problem).at(&field));
+        }
+
+        // Image rips reference a single file we cannot match by track
+        if sheet.files.len() > 1 {
+            for missing in sheet.missing_files(files) {
+                verification.violations.push(
+                    RuleViolation::warning(
+                        ViolationCode::RipLogMismatch,
+                        format!(
+                            "Cue sheet references {} which is not in the torrent",
+                            missing
+                        ),
+                    )
+                    .at(&field),
+                );
+            }
+        }
+
+        verification.cue_sheets.push(sheet);
+    }
+
+    // Logs and cue sheets should describe the same tracks
+    let log_tracks: usize = verification.logs.iter().map(|l| l.log.tracks.len()).sum();
+    let cue_tracks: usize = verification
+        .cue_sheets
+        .iter()
+        .map(CueSheet::track_count)
+        .sum();
+    if !verification.logs.is_empty()
+        && verification.logs.len() == verification.cue_sheets.len()
+        && log_tracks != cue_tracks
+    {
+        verification.violations.push(RuleViolation::warning(
+            ViolationCode::RipLogMismatch,
+            format!(
+                "Logs list {} tracks but cue sheets list {}",
+                log_tracks, cue_tracks
+            ),
+        ));
+    }
+
+    let has_lossless = files.iter().any(|f| {
+        f.file_type == FileType::Audio
+            && f.extension
+                .as_deref()
+                .map_or(false, |ext| LOSSLESS_EXTENSIONS.contains(&ext))
+    });
+    let log_files = files
+        .iter()
+        .filter(|f| f.extension.as_deref() == Some("log"))
+        .count();
+
+    if has_lossless && logs.is_empty() && log_files > 0 {
+        verification.violations.push(RuleViolation::warning(
+            ViolationCode::RipLogMismatch,
+            "Torrent contains rip logs but none were attached to the upload",
+        ));
+    } else if log_files > 0 && logs.len() != log_files {
+        verification.violations.push(RuleViolation::warning(
+            ViolationCode::RipLogMismatch,
+            format!(
+                "Torrent contains {} log file(s) but {} were attached",
+                log_files,
+                logs.len()
+            ),
+        ));
+    }
+
+    verification
+}
+
+/// Stored rip log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredRipLog {
+    /// Log ID
+    pub id: Uuid,
+
+    /// Torrent ID
+    pub torrent_id: Uuid,
+
+    /// Ripping software
+    pub ripper: Ripper,
+
+    /// Score (0-100)
+    pub score: i16,
+
+    /// Signature state
+    pub checksum_status: ChecksumStatus,
+
+    /// Deductions that make up the score
+    pub deductions: sqlx::types::Json<Vec<Deduction>>,
+
+    /// Original log text
+    pub log_text: String,
+}
+
+/// Rip log service
+pub struct RipLogService {
+    pool: PgPool,
+    verifier: Option<Arc<dyn LogSignatureVerifier>>,
+}
+
+impl RipLogService {
+    /// Create a new rip log service
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            verifier: None,
+        }
+    }
+
+    /// Use a verifier for log signatures
+    pub fn with_verifier(mut self, verifier: Arc<dyn LogSignatureVerifier>) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    /// Verify logs and cue sheets for an upload
+    pub fn verify(
+        &self,
+        logs: &[String],
+        cue_sheets: &[String],
+        files: &[TorrentFileInfo],
+    ) -> RipVerification {
+        verify_rip(logs, cue_sheets, files, self.verifier.as_deref())
+    }
+
+    /// Store logs, cue sheets and the resulting score for a torrent
+    ///
+    /// Runs in the upload's transaction, so a torrent is never visible
+    /// without its log score.
+    pub async fn store(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        torrent_id: Uuid,
+        logs: &[String],
+        cue_sheets: &[String],
+        verification: &RipVerification,
+    ) -> Result<()> {
+        // Reports line up with the logs that parsed; keep the text with them
+        let parsed = logs.iter().filter(|text| RipLog::parse(text).is_ok());
+        for (position, (report, text)) in verification.logs.iter().zip(parsed).enumerate() {
+            sqlx::query!(
+                r#"
+                INSERT INTO torrent_rip_logs (
+                    torrent_id, position, ripper, score, checksum_status,
+                    deductions, log_text, log_sha256
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+                torrent_id,
+                position as i32,
+                report.log.ripper as Ripper,
+                report.score,
+                report.checksum as ChecksumStatus,
+                serde_json::to_value(&report.deductions)?,
+                text,
+                report.sha256,
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        for (position, text) in cue_sheets.iter().enumerate() {
+            sqlx::query!(
+                r#"
+                INSERT INTO torrent_cue_sheets (torrent_id, position, content)
+                VALUES ($1, $2, $3)
+                "#,
+                torrent_id,
+                position as i32,
+                text,
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE torrent_metadata
+            SET log_score = $2, has_log = $3, has_cue = $4
+            WHERE id = $1
+            "#,
+            torrent_id,
+            verification.log_score(),
+            !verification.logs.is_empty(),
+            verification.has_cue(),
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the stored logs for a torrent
+    pub async fn get_logs(&self, torrent_id: Uuid) -> Result<Vec<StoredRipLog>> {
+        let logs = sqlx::query_as!(
+            StoredRipLog,
+            r#"
+            SELECT
+                id, torrent_id,
+                ripper as "ripper: Ripper",
+                score,
+                checksum_status as "checksum_status: ChecksumStatus",
+                deductions as "deductions: sqlx::types::Json<Vec<Deduction>>",
+                log_text
+            FROM torrent_rip_logs
+            WHERE torrent_id = $1
+            ORDER BY position
+            "#,
+            torrent_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(logs)
+    }
+
+    /// Get a torrent's log score and cue state
+    pub async fn get_summary(&self, torrent_id: Uuid) -> Result<RipSummary> {
+        let record = sqlx::query!(
+            r#"
+            SELECT log_score, has_cue
+            FROM torrent_metadata
+            WHERE id = $1
+            "#,
+            torrent_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| anyhow!("Torrent not found"))?;
+
+        Ok(RipSummary {
+            log_score: record.log_score,
+            has_cue: record.has_cue,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EAC_LOG: &str = "Exact Audio Copy V1.6 from 23. November 2020
+
+EAC extraction logfile from 2. March 2021, 18:42
+
+Sample Artist / Sample Album
+
+Used drive  : PLEXTOR DVDR   PX-716A   Adapter: 1  ID: 0
+
+Read mode               : Secure
+Utilize accurate stream : Yes
+Defeat audio cache      : Yes
+Make use of C2 pointers : No
+
+Read offset correction                      : 30
+Overread into Lead-In and Lead-Out          : No
+Fill up missing offset samples with silence : Yes
+Delete leading and trailing silent blocks   : No
+Null samples used in CRC calculations       : Yes
+Used interface                              : Native Win32 interface for Win NT & 2000
+Gap handling                                : Appended to previous track
+
+Track  1
+
+     Filename C:\\Rips\\01 - Opening.wav
+
+     Peak level 98.8 %
+     Extraction speed 4.5 X
+     Track quality 100.0 %
+     Test CRC 1A2B3C4D
+     Copy CRC 1A2B3C4D
+     Accurately ripped (confidence 5)  [ABCDEF12]  (AR v2)
+     Copy OK
+
+Track  2
+
+     Filename C:\\Rips\\02 - Closing.wav
+
+     Peak level 100.0 %
+     Extraction speed 4.1 X
+     Track quality 100.0 %
+     Test CRC 5E6F7A8B
+     Copy CRC 5E6F7A8B
+     Accurately ripped (confidence 5)  [12ABCDEF]  (AR v2)
+     Copy OK
+
+All tracks accurately ripped
+
+No errors occurred
+
+End of status report
+
+==== Log checksum 9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08 ====
+";
+
+    const XLD_LOG: &str = "X Lossless Decoder version 20210911 (155.1)
+
+XLD extraction logfile from 2021-03-02 18:42:00 +0000
+
+Sample Artist / Sample Album
+
+Used drive : PIONEER BD-RW BDR-XD05 (revision 1.10)
+Ripper mode             : XLD Secure Ripper
+Disable audio cache     : OK
+Make use of C2 Error Pointers : NO
+Read offset correction  : 667
+Max retry count         : 20
+Gap status              : Analyzed, Appended
+
+Track 01
+    Filename : /Users/sample/Rips/01 - Opening.flac
+    CRC32 hash (test run)  : 1A2B3C4D
+    CRC32 hash             : 1A2B3C4D
+    AccurateRip v2 signature : ABCDEF12
+        ->Accurately ripped (v2, confidence 5/5)
+    Statistics
+        Read error                           : 0
+        Jitter error (maybe fixed)           : 0
+        Damaged sector count                 : 0
+
+Track 02
+    Filename : /Users/sample/Rips/02 - Closing.flac
+    CRC32 hash (test run)  : 5E6F7A8B
+    CRC32 hash             : 99999999
+    AccurateRip v2 signature : 12ABCDEF
+        ->Rip may not be accurate.
+    Statistics
+        Read error                           : 2
+        Jitter error (maybe fixed)           : 0
+        Damaged sector count                 : 0
+
+Some inconsistencies found
+
+End of status report
+
+-----BEGIN XLD SIGNATURE-----
+Q+-UTE8nqv8mBDdpW8ddQ1dd9sGm5v2lJpr6P0kd3Q7R2jwh5PUBQ0fKJTeakIa
+-----END XLD SIGNATURE-----
+";
+
+    const CUE: &str = "FILE \"01 - Opening.flac\" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+FILE \"02 - Closing.flac\" WAVE
+  TRACK 02 AUDIO
+    INDEX 01 00:00:00
+";
+
+    fn flac(path: &str) -> TorrentFileInfo {
+        TorrentFileInfo {
+            path: path.to_string(),
+            size: 25_000_000,
+            extension: Some("flac".to_string()),
+            file_type: FileType::Audio,
+            media_type: None,
+            is_sample: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_eac_log() {
+        let log = RipLog::parse(EAC_LOG).unwrap();
+
+        assert_eq!(log.ripper, Ripper::Eac);
+        assert_eq!(log.drive.as_deref(), Some("PLEXTOR DVDR PX-716A"));
+        assert_eq!(log.secure_mode, Some(true));
+        assert_eq!(log.c2_pointers, Some(false));
+        assert_eq!(log.gaps_detected, Some(true));
+        assert_eq!(log.tracks.len(), 2);
+        assert_eq!(log.tracks[1].copy_crc.as_deref(), Some("5E6F7A8B"));
+        assert_eq!(log.tracks[0].accurately_ripped, Some(true));
+
+        let checksum = log.check_signature(EAC_LOG, None);
+        assert_eq!(checksum, ChecksumStatus::Unverified);
+
+        let (score, deductions) = log.score(ChecksumStatus::Valid);
+        assert_eq!(score, 100, "unexpected deductions: {:?}", deductions);
+
+        // An unchecked signature scores no better than a missing one
+        assert_eq!(log.score(checksum).0, 100 - DEDUCT_NO_CHECKSUM);
+    }
+
+    #[test]
+    fn test_parse_xld_log() {
+        let log = RipLog::parse(XLD_LOG).unwrap();
+
+        assert_eq!(log.ripper, Ripper::Xld);
+        assert_eq!(log.secure_mode, Some(true));
+        assert_eq!(log.defeat_audio_cache, Some(true));
+        assert_eq!(log.tracks.len(), 2);
+        assert!(!log.tracks[0].crc_mismatch());
+        assert!(log.tracks[1].crc_mismatch());
+        assert_eq!(log.tracks[1].errors, 2);
+        assert_eq!(log.tracks[1].accurately_ripped, Some(false));
+
+        let checksum = log.check_signature(XLD_LOG, None);
+        assert_eq!(checksum, ChecksumStatus::Unverified);
+
+        let (score, _) = log.score(checksum);
+        assert_eq!(
+            score,
+            100 - DEDUCT_NO_CHECKSUM - DEDUCT_CRC_MISMATCH - DEDUCT_TRACK_ERRORS
+        );
+    }
+
+    #[test]
+    fn test_edited_and_unsigned_logs() {
+        let appended = format!("{}\nCopy OK\n", EAC_LOG);
+        let log = RipLog::parse(&appended).unwrap();
+        assert_eq!(
+            log.check_signature(&appended, None),
+            ChecksumStatus::Invalid
+        );
+        assert_eq!(log.score(ChecksumStatus::Invalid).0, 0);
+
+        let unsigned = EAC_LOG.split("==== Log checksum").next().unwrap();
+        let log = RipLog::parse(unsigned).unwrap();
+        let checksum = log.check_signature(unsigned, None);
+        assert_eq!(checksum, ChecksumStatus::Missing);
+        assert_eq!(log.score(checksum).0, 100 - DEDUCT_NO_CHECKSUM);
+
+        struct Rejecting;
+        impl LogSignatureVerifier for Rejecting {
+            fn verify(&self, _ripper: Ripper, _log: &str) -> Result<bool> {
+                Ok(false)
+            }
+        }
+        let log = RipLog::parse(EAC_LOG).unwrap();
+        assert_eq!(
+            log.check_signature(EAC_LOG, Some(&Rejecting)),
+            ChecksumStatus::Invalid
+        );
+    }
+
+    #[test]
+    fn test_decode_utf16_log() {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "Exact Audio Copy".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        assert_eq!(decode_log(&bytes), "Exact Audio Copy");
+        assert_eq!(decode_log(b"X Lossless Decoder"), "X Lossless Decoder");
+        assert_eq!(decode_log(&[0x41, 0xE9]), "A\u{e9}");
+    }
+
+    #[test]
+    fn test_verify_rip() {
+        let files = vec![
+            flac("Album/01 - Opening.flac"),
+            flac("Album/02 - Closing.flac"),
+        ];
+
+        let verification = verify_rip(&[EAC_LOG.to_string()], &[CUE.to_string()], &files, None);
+        assert!(
+            verification.violations.is_empty(),
+            "{:?}",
+            verification.violations
+        );
+        assert_eq!(
+            verification.summary(),
+            RipSummary {
+                log_score: Some(100 - DEDUCT_NO_CHECKSUM),
+                has_cue: true
+            }
+        );
+
+        let verification = verify_rip(&["not a log".to_string()], &[], &files, None);
+        assert_eq!(
+            verification.violations[0].code,
+            ViolationCode::InvalidRipLog
+        );
+        assert_eq!(verification.log_score(), None);
+    }
+
+    #[test]
+    fn test_can_trump() {
+        let no_log = RipSummary {
+            log_score: None,
+            has_cue: false,
+        };
+        let partial = RipSummary {
+            log_score: Some(80),
+            has_cue: true,
+        };
+        let perfect = RipSummary {
+            log_score: Some(100),
+            has_cue: false,
+        };
+        let perfect_cue = RipSummary {
+            log_score: Some(100),
+            has_cue: true,
+        };
+
+        assert!(can_trump(&partial, &no_log));
+        assert!(can_trump(&perfect, &partial));
+        assert!(can_trump(&perfect_cue, &perfect));
+        assert!(!can_trump(&perfect, &perfect_cue));
+        assert!(!can_trump(&perfect, &perfect));
+        assert!(!can_trump(&no_log, &partial));
+    }
+}
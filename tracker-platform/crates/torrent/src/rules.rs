@@ -0,0 +1,781 @@
+//! Upload rules engine
+//!
+//! Each category can carry its own declarative ruleset, stored as JSON in the
+//! `upload_rules` table and edited by staff. A row without a category is the
+//! site-wide default; categories without their own row fall back to it, and
+//! if there is no default row the built-in rules apply (which match the
+//! limits uploads were always held to).
+//!
+//! Rules cover:
+//! - Allowed file extensions and media containers
+//! - Required files (e.g. an NFO, or a cue sheet and rip log for music)
+//! - A naming pattern for the release name
+//! - Minimum and maximum total size
+//! - Banned release groups
+//! - Quality fields that must be recognised in the release name
+//!
+//! Evaluation produces structured [`RuleViolation`]s rather than strings, so
+//! clients can point at the offending file or field.
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{
+    files::{FileType, TorrentFileInfo},
+    metadata::QualityInfo,
+};
+
+/// How long a loaded ruleset is served from cache
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Violation severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Blocks the upload
+    Error,
+
+    /// Reported to the uploader, upload proceeds
+    Warning,
+}
+
+/// Machine-readable violation code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationCode {
+    EmptyFileList,
+    InvalidFileSize,
+    SmallFile,
+    ExecutableFile,
+    DuplicatePath,
+    NoMediaFiles,
+    DisallowedExtension,
+    DisallowedContainer,
+    MissingRequiredFile,
+    NamePattern,
+    SizeTooSmall,
+    SizeTooLarge,
+    BannedReleaseGroup,
+    MissingQualityField,
+    InvalidRipLog,
+    EditedRipLog,
+    InvalidCueSheet,
+    RipLogMismatch,
+    InvalidMediaInfo,
+    QualityMismatch,
+}
+
+/// A single rule violation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleViolation {
+    /// Violation code
+    pub code: ViolationCode,
+
+    /// Severity
+    pub severity: Severity,
+
+    /// Human-readable message
+    pub message: String,
+
+    /// File path or field the violation refers to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl RuleViolation {
+    /// Create an error-level violation
+    pub fn error(code: ViolationCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            severity: Severity::Error,
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    /// Create a warning-level violation
+    pub fn warning(code: ViolationCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            severity: Severity::Warning,
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    /// Attach the file path or field the violation refers to
+    pub fn at(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Whether this violation blocks the upload
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Quality fields a ruleset can require
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityField {
+    Resolution,
+    VideoCodec,
+    AudioCodec,
+    Source,
+    Container,
+    AudioChannels,
+    Hdr,
+    ReleaseGroup,
+}
+
+impl QualityField {
+    /// Field name as used in violations
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QualityField::Resolution => "resolution",
+            QualityField::VideoCodec => "video_codec",
+            QualityField::AudioCodec => "audio_codec",
+            QualityField::Source => "source",
+            QualityField::Container => "container",
+            QualityField::AudioChannels => "audio_channels",
+            QualityField::Hdr => "hdr",
+            QualityField::ReleaseGroup => "release_group",
+        }
+    }
+
+    /// Value of this field in parsed quality information
+    pub fn value<'a>(&self, quality: &'a QualityInfo) -> Option<&'a str> {
+        match self {
+            QualityField::Resolution => quality.resolution.as_deref(),
+            QualityField::VideoCodec => quality.video_codec.as_deref(),
+            QualityField::AudioCodec => quality.audio_codec.as_deref(),
+            QualityField::Source => quality.source.as_deref(),
+            QualityField::Container => quality.container.as_deref(),
+            QualityField::AudioChannels => quality.audio_channels.as_deref(),
+            QualityField::Hdr => quality.hdr.as_deref(),
+            QualityField::ReleaseGroup => quality.release_group.as_deref(),
+        }
+    }
+}
+
+/// A file the torrent must contain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequiredFile {
+    /// Any file with one of these extensions satisfies the rule
+    pub extensions: Vec<String>,
+
+    /// Description shown to the uploader (e.g. "EAC/XLD log")
+    pub description: String,
+}
+
+/// Declarative upload ruleset for a category
+///
+/// Stored as JSON; missing fields take their default so staff can save a
+/// partial ruleset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadRuleSet {
+    /// Allowed file extensions (lowercase, without dot; empty allows any)
+    pub allowed_extensions: Vec<String>,
+
+    /// Allowed containers for video and audio files (empty allows any)
+    pub allowed_containers: Vec<String>,
+
+    /// Files that must be present
+    pub required_files: Vec<RequiredFile>,
+
+    /// Regular expression the release name must match
+    pub name_pattern: Option<String>,
+
+    /// Minimum total size in bytes
+    pub min_size: Option<i64>,
+
+    /// Maximum total size in bytes
+    pub max_size: Option<i64>,
+
+    /// Release groups not allowed in this category (case-insensitive)
+    pub banned_release_groups: Vec<String>,
+
+    /// Quality fields that must be recognised in the release name
+    pub required_quality_fields: Vec<QualityField>,
+
+    /// Warn when the torrent contains executables
+    pub warn_executables: bool,
+
+    /// Warn when the torrent contains no media files
+    pub warn_no_media: bool,
+
+    /// Files smaller than this produce a warning (bytes)
+    pub small_file_warning_bytes: i64,
+}
+
+impl Default for UploadRuleSet {
+    fn default() -> Self {
+        Self {
+            allowed_extensions: Vec::new(),
+            allowed_containers: Vec::new(),
+            required_files: Vec::new(),
+            name_pattern: None,
+            min_size: None,
+            max_size: Some(500 * 1024 * 1024 * 1024), // 500GB
+            banned_release_groups: Vec::new(),
+            required_quality_fields: Vec::new(),
+            warn_executables: true,
+            warn_no_media: true,
+            small_file_warning_bytes: 100,
+        }
+    }
+}
+
+impl UploadRuleSet {
+    /// Check that the ruleset itself is well-formed
+    pub fn validate(&self) -> Result<()> {
+        if let Some(ref pattern) = self.name_pattern {
+            Regex::new(pattern).map_err(|e| anyhow!("Invalid name pattern: {}", e))?;
+        }
+
+        if let (Some(min), Some(max)) = (self.min_size, self.max_size) {
+            if min > max {
+                return Err(anyhow!("Minimum size exceeds maximum size"));
+            }
+        }
+
+        if self.small_file_warning_bytes < 0 {
+            return Err(anyhow!("Small file threshold cannot be negative"));
+        }
+
+        if self.required_files.iter().any(|r| r.extensions.is_empty()) {
+            return Err(anyhow!("Required file rules need at least one extension"));
+        }
+
+        Ok(())
+    }
+
+    /// Whether a file extension is allowed
+    pub fn allows_extension(&self, extension: Option<&str>) -> bool {
+        if self.allowed_extensions.is_empty() {
+            return true;
+        }
+
+        extension.map_or(false, |ext| {
+            self.allowed_extensions
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(ext))
+        })
+    }
+
+    /// Whether a media container is allowed
+    pub fn allows_container(&self, container: Option<&str>) -> bool {
+        if self.allowed_containers.is_empty() {
+            return true;
+        }
+
+        container.map_or(false, |c| {
+            self.allowed_containers
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(c))
+        })
+    }
+
+    /// Check extension, container and required-file rules
+    ///
+    /// Size and structural checks live in [`crate::files::validate_file_list`].
+    pub fn check_file_types(&self, files: &[TorrentFileInfo]) -> Vec<RuleViolation> {
+        let mut violations = Vec::new();
+
+        for file in files {
+            let extension = file.extension.as_deref();
+
+            if !self.allows_extension(extension) {
+                violations.push(
+                    RuleViolation::error(
+                        ViolationCode::DisallowedExtension,
+                        format!(
+                            "File type .{} is not allowed in this category",
+                            extension.unwrap_or("")
+                        ),
+                    )
+                    .at(&file.path),
+                );
+            } else if matches!(file.file_type, FileType::Video | FileType::Audio)
+                && !self.allows_container(extension)
+            {
+                violations.push(
+                    RuleViolation::error(
+                        ViolationCode::DisallowedContainer,
+                        format!(
+                            "Container {} is not allowed in this category",
+                            extension.unwrap_or("(none)")
+                        ),
+                    )
+                    .at(&file.path),
+                );
+            }
+        }
+
+        for required in &self.required_files {
+            let present = files.iter().any(|f| {
+                f.extension.as_deref().map_or(false, |ext| {
+                    required
+                        .extensions
+                        .iter()
+                        .any(|r| r.eq_ignore_ascii_case(ext))
+                })
+            });
+
+            if !present {
+                violations.push(RuleViolation::error(
+                    ViolationCode::MissingRequiredFile,
+                    format!("Missing required file: {}", required.description),
+                ));
+            }
+        }
+
+        violations
+    }
+}
+
+/// A ruleset with its name pattern compiled
+///
+/// This is what the rules service caches, so the pattern is compiled once
+/// per load rather than on every upload. Dereferences to the ruleset.
+#[derive(Debug, Clone)]
+pub struct CompiledRuleSet {
+    rules: UploadRuleSet,
+    name_pattern: Option<Regex>,
+}
+
+impl CompiledRuleSet {
+    /// Compile a ruleset
+    ///
+    /// Saved rulesets are validated, so an invalid name pattern only gets
+    /// here if the row was edited by hand; it is logged and ignored.
+    pub fn new(rules: UploadRuleSet) -> Self {
+        let name_pattern = rules.name_pattern.as_deref().and_then(|pattern| {
+            Regex::new(pattern)
+                .map_err(|e| {
+                    tracing::warn!("Ignoring invalid upload name pattern {:?}: {}", pattern, e);
+                })
+                .ok()
+        });
+
+        Self {
+            rules,
+            name_pattern,
+        }
+    }
+
+    /// Check naming, release group and quality rules
+    pub fn check_release(&self, name: &str, quality: &QualityInfo) -> Vec<RuleViolation> {
+        let mut violations = Vec::new();
+
+        if let Some(ref pattern) = self.name_pattern {
+            if !pattern.is_match(name) {
+                violations.push(
+                    RuleViolation::error(
+                        ViolationCode::NamePattern,
+                        "Release name does not follow the naming rules for this category",
+                    )
+                    .at("name"),
+                );
+            }
+        }
+
+        if let Some(ref group) = quality.release_group {
+            if self
+                .banned_release_groups
+                .iter()
+                .any(|banned| banned.eq_ignore_ascii_case(group))
+            {
+                violations.push(
+                    RuleViolation::error(
+                        ViolationCode::BannedReleaseGroup,
+                        format!("Releases from {} are not allowed", group),
+                    )
+                    .at("release_group"),
+                );
+            }
+        }
+
+        for field in &self.required_quality_fields {
+            if field.value(quality).map_or(true, |v| v.trim().is_empty()) {
+                violations.push(
+                    RuleViolation::error(
+                        ViolationCode::MissingQualityField,
+                        format!(
+                            "Release name must state the {}",
+                            field.as_str().replace('_', " ")
+                        ),
+                    )
+                    .at(field.as_str()),
+                );
+            }
+        }
+
+        violations
+    }
+}
+
+impl std::ops::Deref for CompiledRuleSet {
+    type Target = UploadRuleSet;
+
+    fn deref(&self) -> &UploadRuleSet {
+        &self.rules
+    }
+}
+
+/// Upload rules stored for a category
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredRuleSet {
+    /// Category ID (`None` for the site-wide default)
+    pub category_id: Option<i32>,
+
+    /// Ruleset
+    pub rules: UploadRuleSet,
+
+    /// Staff member who last edited the rules
+    pub updated_by: Option<Uuid>,
+
+    /// Last edit
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+struct CachedRuleSet {
+    rules: Arc<CompiledRuleSet>,
+    loaded_at: Instant,
+}
+
+/// Upload rules service
+///
+/// Loads rulesets from the database and caches them per category. Cloning
+/// the service shares the cache.
+#[derive(Clone)]
+pub struct UploadRulesService {
+    pool: PgPool,
+    cache: Arc<RwLock<HashMap<i32, CachedRuleSet>>>,
+    ttl: Duration,
+}
+
+impl UploadRulesService {
+    /// Create a new upload rules service
+    pub fn new(pool: PgPool) -> Self {
+        Self::with_ttl(pool, DEFAULT_CACHE_TTL)
+    }
+
+    /// Create a new upload rules service with a custom cache TTL
+    pub fn with_ttl(pool: PgPool, ttl: Duration) -> Self {
+        Self {
+            pool,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    /// Get the effective ruleset for a category
+    ///
+    /// Falls back to the site-wide default row, then to the built-in rules.
+    pub async fn rules_for_category(&self, category_id: i32) -> Result<Arc<CompiledRuleSet>> {
+        if let Some(cached) = self.cache.read().await.get(&category_id) {
+            if cached.loaded_at.elapsed() < self.ttl {
+                return Ok(cached.rules.clone());
+            }
+        }
+
+        let record = sqlx::query!(
+            r#"
+            SELECT rules
+            FROM upload_rules
+            WHERE category_id = $1 OR category_id IS NULL
+            ORDER BY category_id NULLS LAST
+            LIMIT 1
+            "#,
+            category_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let rules = match record {
+            Some(record) => serde_json::from_value(record.rules)?,
+            None => UploadRuleSet::default(),
+        };
+        let rules = Arc::new(CompiledRuleSet::new(rules));
+
+        self.cache.write().await.insert(
+            category_id,
+            CachedRuleSet {
+                rules: rules.clone(),
+                loaded_at: Instant::now(),
+            },
+        );
+
+        Ok(rules)
+    }
+
+    /// List all stored rulesets
+    pub async fn list_rules(&self) -> Result<Vec<StoredRuleSet>> {
+        let records = sqlx::query!(
+            r#"
+            SELECT category_id, rules, updated_by, updated_at
+            FROM upload_rules
+            ORDER BY category_id NULLS FIRST
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        records
+            .into_iter()
+            .map(|r| {
+                Ok(StoredRuleSet {
+                    category_id: r.category_id,
+                    rules: serde_json::from_value(r.rules)?,
+                    updated_by: r.updated_by,
+                    updated_at: r.updated_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Create or replace the rules for a category
+    ///
+    /// # Arguments
+    ///
+    /// * `category_id` - Category, or `None` for the site-wide default
+    /// * `rules` - New ruleset
+    /// * `updated_by` - Staff member making the change
+    pub async fn save_rules(
+        &self,
+        category_id: Option<i32>,
+        rules: &UploadRuleSet,
+        updated_by: Uuid,
+    ) -> Result<()> {
+        rules.validate()?;
+
+        let value = serde_json::to_value(rules)?;
+        let mut tx = self.pool.begin().await?;
+
+        let updated = sqlx::query!(
+            r#"
+            UPDATE upload_rules
+            SET rules = $2, updated_by = $3, updated_at = NOW()
+            WHERE category_id IS NOT DISTINCT FROM $1
+            "#,
+            category_id,
+            value,
+            updated_by
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            sqlx::query!(
+                r#"
+                INSERT INTO upload_rules (category_id, rules, updated_by, updated_at)
+                VALUES ($1, $2, $3, NOW())
+                "#,
+                category_id,
+                value,
+                updated_by
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        tracing::info!(
+            "Upload rules for {} updated by {}",
+            category_id.map_or_else(|| "default".to_string(), |id| id.to_string()),
+            updated_by
+        );
+
+        // The default row affects every category without its own rules
+        self.invalidate().await;
+
+        Ok(())
+    }
+
+    /// Remove the rules for a category, falling back to the default
+    pub async fn delete_rules(&self, category_id: Option<i32>) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM upload_rules
+            WHERE category_id IS NOT DISTINCT FROM $1
+            "#,
+            category_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.invalidate().await;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Drop all cached rulesets
+    pub async fn invalidate(&self) {
+        self.cache.write().await.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::detect_file_type;
+
+    fn file(path: &str, size: i64) -> TorrentFileInfo {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        let file_type = detect_file_type(&extension);
+
+        TorrentFileInfo {
+            path: path.to_string(),
+            size,
+            extension,
+            file_type,
+            media_type: None,
+            is_sample: false,
+        }
+    }
+
+    fn music_rules() -> UploadRuleSet {
+        UploadRuleSet {
+            allowed_extensions: ["flac", "cue", "log", "jpg", "nfo"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            required_files: vec![
+                RequiredFile {
+                    extensions: vec!["cue".to_string()],
+                    description: "cue sheet".to_string(),
+                },
+                RequiredFile {
+                    extensions: vec!["log".to_string()],
+                    description: "EAC/XLD log".to_string(),
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_partial_ruleset_deserializes_with_defaults() {
+        let rules: UploadRuleSet =
+            serde_json::from_str(r#"{"banned_release_groups": ["BADGRP"]}"#).unwrap();
+
+        assert_eq!(rules.banned_release_groups, vec!["BADGRP".to_string()]);
+        assert_eq!(rules.max_size, UploadRuleSet::default().max_size);
+        assert!(rules.warn_executables);
+    }
+
+    #[test]
+    fn test_validate_ruleset() {
+        assert!(UploadRuleSet::default().validate().is_ok());
+
+        let bad_pattern = UploadRuleSet {
+            name_pattern: Some("([".to_string()),
+            ..Default::default()
+        };
+        assert!(bad_pattern.validate().is_err());
+
+        let bad_sizes = UploadRuleSet {
+            min_size: Some(10),
+            max_size: Some(5),
+            ..Default::default()
+        };
+        assert!(bad_sizes.validate().is_err());
+    }
+
+    #[test]
+    fn test_required_and_allowed_files() {
+        let rules = music_rules();
+
+        let complete = vec![
+            file("Album/01.flac", 30_000_000),
+            file("Album/Album.cue", 1_000),
+            file("Album/Album.log", 4_000),
+        ];
+        assert!(rules.check_file_types(&complete).is_empty());
+
+        let incomplete = vec![
+            file("Album/01.mp3", 8_000_000),
+            file("Album/Album.cue", 1_000),
+        ];
+        let violations = rules.check_file_types(&incomplete);
+        let codes: Vec<_> = violations.iter().map(|v| v.code).collect();
+
+        assert_eq!(
+            codes,
+            vec![
+                ViolationCode::DisallowedExtension,
+                ViolationCode::MissingRequiredFile
+            ]
+        );
+        assert_eq!(violations[0].path.as_deref(), Some("Album/01.mp3"));
+        assert!(violations.iter().all(RuleViolation::is_error));
+    }
+
+    #[test]
+    fn test_allowed_containers_only_apply_to_media() {
+        let rules = UploadRuleSet {
+            allowed_containers: vec!["mkv".to_string()],
+            ..Default::default()
+        };
+
+        let files = vec![
+            file("Movie/movie.avi", 1_000_000),
+            file("Movie/movie.nfo", 500),
+        ];
+        let violations = rules.check_file_types(&files);
+
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].code, ViolationCode::DisallowedContainer);
+    }
+
+    #[test]
+    fn test_release_rules() {
+        let rules = UploadRuleSet {
+            name_pattern: Some(r"^[\w.\-]+$".to_string()),
+            banned_release_groups: vec!["badgrp".to_string()],
+            required_quality_fields: vec![QualityField::Resolution, QualityField::Source],
+            ..Default::default()
+        };
+
+        let quality = QualityInfo {
+            resolution: Some("1080p".to_string()),
+            video_codec: None,
+            audio_codec: None,
+            source: None,
+            container: None,
+            audio_channels: None,
+            bitrate: None,
+            hdr: None,
+            release_group: Some("BADGRP".to_string()),
+            is_scene: false,
+        };
+
+        let violations =
+            CompiledRuleSet::new(rules).check_release("Movie Name 2024 1080p-BADGRP", &quality);
+        let codes: Vec<_> = violations.iter().map(|v| v.code).collect();
+
+        assert_eq!(
+            codes,
+            vec![
+                ViolationCode::NamePattern,
+                ViolationCode::BannedReleaseGroup,
+                ViolationCode::MissingQualityField,
+            ]
+        );
+        assert_eq!(violations[2].path.as_deref(), Some("source"));
+    }
+}
@@ -12,6 +12,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::tags::TagTaxonomyService;
+
 /// Searchable torrent document for Meilisearch
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TorrentSearchDocument {
@@ -57,6 +59,18 @@ pub struct TorrentSearchDocument {
     /// Quality information (resolution, codec, source)
     pub quality: Option<QualitySearchInfo>,
 
+    /// Track data from MediaInfo
+    pub mediainfo: Option<MediaInfoSearch>,
+
+    /// Rip log score (music, 0-100)
+    pub log_score: Option<i16>,
+
+    /// Has a rip log
+    pub has_log: bool,
+
+    /// Has a cue sheet
+    pub has_cue: bool,
+
     /// External IDs for linking
     pub external_ids: ExternalIdsSearch,
 
@@ -97,6 +111,36 @@ pub struct QualitySearchInfo {
     pub source: Option<String>,
 }
 
+/// MediaInfo track data for search
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MediaInfoSearch {
+    pub resolution: Option<String>,
+    pub video_codec: Option<String>,
+    pub hdr_formats: Vec<String>,
+    pub bit_depth: Option<u8>,
+    pub audio_codecs: Vec<String>,
+    pub audio_languages: Vec<String>,
+    pub subtitle_languages: Vec<String>,
+}
+
+impl From<&crate::mediainfo::MediaInfoReport> for MediaInfoSearch {
+    fn from(report: &crate::mediainfo::MediaInfoReport) -> Self {
+        Self {
+            resolution: report.resolution().map(str::to_string),
+            video_codec: report.video_codec().map(str::to_string),
+            hdr_formats: report
+                .video
+                .iter()
+                .filter_map(|v| v.hdr_format.clone())
+                .collect(),
+            bit_depth: report.primary_video().and_then(|v| v.bit_depth),
+            audio_codecs: report.audio_codecs(),
+            audio_languages: report.audio_languages(),
+            subtitle_languages: report.subtitle_languages(),
+        }
+    }
+}
+
 /// External IDs for search
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExternalIdsSearch {
@@ -111,19 +155,29 @@ pub struct SearchService {
     pool: PgPool,
     client: Client,
     index_name: String,
+    tags: TagTaxonomyService,
 }
 
 impl SearchService {
     /// Create new search service
     pub fn new(pool: PgPool, meilisearch_url: &str, api_key: &str, index_name: String) -> Self {
         let client = Client::new(meilisearch_url, Some(api_key));
+        let tags = TagTaxonomyService::new(pool.clone());
         Self {
             pool,
             client,
             index_name,
+            tags,
         }
     }
 
+    /// Share a tag taxonomy service, so taxonomy edits reach the indexer
+    /// without waiting for its cache to expire
+    pub fn with_tags(mut self, tags: TagTaxonomyService) -> Self {
+        self.tags = tags;
+        self
+    }
+
     /// Initialize Meilisearch index with proper settings
     pub async fn initialize_index(&self) -> Result<()> {
         let index = self.get_index().await?;
@@ -153,6 +207,14 @@ impl SearchService {
                 "tags",
                 "quality.resolution",
                 "quality.source",
+                "mediainfo.resolution",
+                "mediainfo.hdr_formats",
+                "mediainfo.audio_codecs",
+                "mediainfo.audio_languages",
+                "mediainfo.subtitle_languages",
+                "log_score",
+                "has_log",
+                "has_cue",
                 "seeders",
                 "leechers",
             ])
@@ -257,6 +319,8 @@ impl SearchService {
                 t.id, t.name, t.info_hash, t.total_size, t.created_at,
                 tm.description, tm.tags, tm.year, tm.imdb_rating,
                 tm.quality, tm.external_ids, tm.is_featured, tm.is_sticky,
+                tm.log_score, tm.has_log, tm.has_cue,
+                mi.report as "mediainfo_report?",
                 tm.media_type as "media_type: crate::metadata::MediaType",
                 c.name as category_name, c.id as category_id,
                 u.username as uploader_name, u.id as uploader_id,
@@ -271,9 +335,10 @@ impl SearchService {
             JOIN users u ON u.id = t.uploader_id
             LEFT JOIN torrent_files tf ON tf.torrent_id = t.id
             LEFT JOIN torrent_stats ts ON ts.torrent_id = t.id
+            LEFT JOIN torrent_mediainfo mi ON mi.torrent_id = t.id
             WHERE t.id = $1
             AND t.moderation_status = 'approved'
-            GROUP BY t.id, tm.id, c.id, u.id, ts.seeders, ts.leechers
+            GROUP BY t.id, tm.id, c.id, u.id, ts.seeders, ts.leechers, mi.torrent_id
             "#,
             torrent_id
         )
@@ -293,6 +358,15 @@ impl SearchService {
             source: q.source,
         });
 
+        // Parse MediaInfo tracks
+        let mediainfo = record
+            .mediainfo_report
+            .and_then(|v| serde_json::from_value::<crate::mediainfo::MediaInfoReport>(v).ok())
+            .map(|report| MediaInfoSearch::from(&report));
+
+        // Index canonical tags plus everything they imply
+        let tags = self.tags.taxonomy().await?.expand(&record.tags);
+
         // Parse external IDs
         let external_ids: crate::metadata::ExternalIds = record
             .external_ids
@@ -321,7 +395,7 @@ impl SearchService {
             description: record.description,
             category: record.category_name,
             category_id: record.category_id.to_string(),
-            tags: record.tags,
+            tags,
             info_hash: record.info_hash,
             total_size: record.total_size,
             total_size_formatted,
@@ -330,6 +404,10 @@ impl SearchService {
             uploader_id: record.uploader_id.to_string(),
             media_type: format!("{:?}", record.media_type),
             quality: quality_search,
+            mediainfo,
+            log_score: record.log_score,
+            has_log: record.has_log,
+            has_cue: record.has_cue,
             external_ids: external_ids_search,
             year: record.year,
             imdb_rating: record.imdb_rating,
@@ -0,0 +1,569 @@
+//! Tag taxonomy
+//!
+//! Free-form tags drift into near-duplicates (`sci-fi`, `scifi`,
+//! `science.fiction`). Staff curate a taxonomy on top of them:
+//!
+//! - **Aliases** redirect a tag to its canonical name
+//! - **Implications** add tags automatically (`anime` implies `animation`)
+//! - **Bans** reject a tag outright
+//! - **Merges** move every vote and upload tag from one tag to another and
+//!   leave an alias behind
+//!
+//! Tag votes also decay with age, so early votes on a torrent do not outweigh
+//! the current consensus forever.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Default cache lifetime for the taxonomy
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Maximum tag length after normalization
+pub const MAX_TAG_LENGTH: usize = 100;
+
+/// Age at which a tag vote counts for half
+pub const VOTE_HALF_LIFE_DAYS: f64 = 180.0;
+
+/// Normalize a tag name
+///
+/// Lowercases, turns spaces and underscores into dots and drops anything
+/// but letters, digits, dots and dashes. Returns `None` if nothing is left.
+pub fn normalize_tag(name: &str) -> Option<String> {
+    let mut tag = String::with_capacity(name.len());
+
+    for c in name.trim().chars().flat_map(char::to_lowercase) {
+        let c = match c {
+            ' ' | '_' | '\t' => '.',
+            c if c.is_alphanumeric() || c == '.' || c == '-' => c,
+            _ => continue,
+        };
+        // Collapse runs of separators
+        if c == '.' && (tag.is_empty() || tag.ends_with('.')) {
+            continue;
+        }
+        tag.push(c);
+    }
+
+    let tag = tag.trim_end_matches('.');
+    if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH {
+        None
+    } else {
+        Some(tag.to_string())
+    }
+}
+
+/// Weight of a vote of the given age
+pub fn decayed_weight(vote: i32, age_days: f64) -> f64 {
+    f64::from(vote) * 0.5f64.powf(age_days.max(0.0) / VOTE_HALF_LIFE_DAYS)
+}
+
+/// Aliases, implications and bans
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TagTaxonomy {
+    /// Alias to canonical tag
+    pub aliases: HashMap<String, String>,
+
+    /// Tag to the tags it implies
+    pub implications: HashMap<String, Vec<String>>,
+
+    /// Banned tags
+    pub banned: HashSet<String>,
+}
+
+impl TagTaxonomy {
+    /// Resolve a tag to its canonical name
+    ///
+    /// Returns `None` for banned or unusable tags.
+    pub fn canonical(&self, name: &str) -> Option<String> {
+        let tag = normalize_tag(name)?;
+        if self.banned.contains(&tag) {
+            return None;
+        }
+
+        let canonical = self.aliases.get(&tag).cloned().unwrap_or(tag);
+        if self.banned.contains(&canonical) {
+            return None;
+        }
+
+        Some(canonical)
+    }
+
+    /// Whether `tag` implies `implied`, directly or transitively
+    pub fn implies(&self, tag: &str, implied: &str) -> bool {
+        self.closure(tag).iter().any(|t| t == implied)
+    }
+
+    /// Tags implied by a tag, transitively and excluding itself
+    fn closure(&self, tag: &str) -> Vec<String> {
+        let mut seen = HashSet::from([tag.to_string()]);
+        let mut queue = VecDeque::from([tag.to_string()]);
+        let mut implied = Vec::new();
+
+        while let Some(current) = queue.pop_front() {
+            for next in self.implications.get(&current).into_iter().flatten() {
+                if seen.insert(next.clone()) {
+                    implied.push(next.clone());
+                    queue.push_back(next.clone());
+                }
+            }
+        }
+
+        implied
+    }
+
+    /// Canonicalize a tag list
+    ///
+    /// Drops banned tags and duplicates, keeping the original order.
+    pub fn canonicalize(&self, tags: &[String]) -> Vec<String> {
+        let mut result: Vec<String> = Vec::with_capacity(tags.len());
+        for tag in tags.iter().filter_map(|t| self.canonical(t)) {
+            if !result.contains(&tag) {
+                result.push(tag);
+            }
+        }
+        result
+    }
+
+    /// Canonicalize a tag list and add every implied tag
+    pub fn expand(&self, tags: &[String]) -> Vec<String> {
+        let explicit = self.canonicalize(tags);
+        let mut result = explicit.clone();
+
+        for tag in &explicit {
+            for implied in self.closure(tag) {
+                if !self.banned.contains(&implied) && !result.contains(&implied) {
+                    result.push(implied);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Result of merging one tag into another
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeReport {
+    /// Votes moved to the target tag
+    pub votes_moved: u64,
+
+    /// Votes dropped because the user had already voted on the target tag
+    pub votes_dropped: u64,
+
+    /// Torrents whose upload tags were rewritten
+    pub torrents_updated: u64,
+}
+
+struct CachedTaxonomy {
+    taxonomy: Arc<TagTaxonomy>,
+    loaded_at: Instant,
+}
+
+/// Tag taxonomy service
+///
+/// Cloning the service shares the cache.
+#[derive(Clone)]
+pub struct TagTaxonomyService {
+    pool: PgPool,
+    cache: Arc<RwLock<Option<CachedTaxonomy>>>,
+    ttl: Duration,
+}
+
+impl TagTaxonomyService {
+    /// Create a new tag taxonomy service
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            cache: Arc::new(RwLock::new(None)),
+            ttl: DEFAULT_CACHE_TTL,
+        }
+    }
+
+    /// Get the current taxonomy
+    pub async fn taxonomy(&self) -> Result<Arc<TagTaxonomy>> {
+        if let Some(cached) = self.cache.read().await.as_ref() {
+            if cached.loaded_at.elapsed() < self.ttl {
+                return Ok(cached.taxonomy.clone());
+            }
+        }
+
+        let taxonomy = Arc::new(self.load().await?);
+        *self.cache.write().await = Some(CachedTaxonomy {
+            taxonomy: taxonomy.clone(),
+            loaded_at: Instant::now(),
+        });
+
+        Ok(taxonomy)
+    }
+
+    async fn load(&self) -> Result<TagTaxonomy> {
+        let aliases = sqlx::query!("SELECT alias, canonical FROM tag_aliases")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let implications = sqlx::query!("SELECT tag, implied FROM tag_implications")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let banned = sqlx::query_scalar!("SELECT tag FROM banned_tags")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut taxonomy = TagTaxonomy {
+            aliases: aliases.into_iter().map(|r| (r.alias, r.canonical)).collect(),
+            banned: banned.into_iter().collect(),
+            ..Default::default()
+        };
+        for r in implications {
+            taxonomy.implications.entry(r.tag).or_default().push(r.implied);
+        }
+
+        Ok(taxonomy)
+    }
+
+    /// Drop the cached taxonomy
+    pub async fn invalidate(&self) {
+        *self.cache.write().await = None;
+    }
+
+    /// Merge one tag into another
+    ///
+    /// Moves votes and upload tags from `from` to `into` and records `from`
+    /// as an alias, so later uses of it are redirected.
+    pub async fn merge_tags(&self, from: &str, into: &str, staff_id: Uuid) -> Result<MergeReport> {
+        let taxonomy = self.taxonomy().await?;
+
+        let from = normalize_tag(from).ok_or_else(|| anyhow!("Invalid tag: {}", from))?;
+        let into = taxonomy
+            .canonical(into)
+            .ok_or_else(|| anyhow!("Invalid or banned tag: {}", into))?;
+        if from == into {
+            return Err(anyhow!("Cannot merge a tag into itself"));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let moved = sqlx::query!(
+            r#"
+            INSERT INTO torrent_tags (torrent_id, tag_name, user_id, vote, created_at, updated_at)
+            SELECT torrent_id, $2, user_id, vote, created_at, updated_at
+            FROM torrent_tags
+            WHERE tag_name = $1
+            ON CONFLICT (torrent_id, tag_name, user_id) DO NOTHING
+            "#,
+            from,
+            into
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let removed = sqlx::query!("DELETE FROM torrent_tags WHERE tag_name = $1", from)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        Self::queue_reindex(&mut tx, &from).await?;
+
+        let torrents_updated = sqlx::query!(
+            r#"
+            UPDATE torrent_metadata
+            SET tags = ARRAY(
+                SELECT DISTINCT t FROM unnest(array_replace(tags, $1, $2)) t
+            )
+            WHERE $1 = ANY(tags)
+            "#,
+            from,
+            into
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        // Existing aliases of the old tag now point at the new one
+        sqlx::query!(
+            "UPDATE tag_aliases SET canonical = $2 WHERE canonical = $1",
+            from,
+            into
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO tag_aliases (alias, canonical, created_by)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (alias) DO UPDATE SET canonical = $2, created_by = $3, created_at = NOW()
+            "#,
+            from,
+            into,
+            staff_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Implications follow the tag to its new name
+        sqlx::query!(
+            r#"
+            INSERT INTO tag_implications (tag, implied, created_by)
+            SELECT
+                CASE WHEN tag = $1 THEN $2 ELSE tag END,
+                CASE WHEN implied = $1 THEN $2 ELSE implied END,
+                created_by
+            FROM tag_implications
+            WHERE (tag = $1 OR implied = $1)
+            AND NOT (tag = $2 OR implied = $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            from,
+            into
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM tag_implications WHERE tag = $1 OR implied = $1",
+            from
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        self.invalidate().await;
+
+        tracing::info!("Tag '{}' merged into '{}' by {}", from, into, staff_id);
+
+        Ok(MergeReport {
+            votes_moved: moved,
+            votes_dropped: removed.saturating_sub(moved),
+            torrents_updated,
+        })
+    }
+
+    /// Remove an alias
+    pub async fn remove_alias(&self, alias: &str) -> Result<bool> {
+        let alias = normalize_tag(alias).ok_or_else(|| anyhow!("Invalid tag: {}", alias))?;
+
+        let result = sqlx::query!("DELETE FROM tag_aliases WHERE alias = $1", alias)
+            .execute(&self.pool)
+            .await?;
+
+        self.invalidate().await;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Add an implication rule
+    ///
+    /// Rules that would make a tag imply itself are rejected.
+    pub async fn add_implication(&self, tag: &str, implied: &str, staff_id: Uuid) -> Result<()> {
+        let taxonomy = self.taxonomy().await?;
+
+        let tag = taxonomy
+            .canonical(tag)
+            .ok_or_else(|| anyhow!("Invalid or banned tag: {}", tag))?;
+        let implied = taxonomy
+            .canonical(implied)
+            .ok_or_else(|| anyhow!("Invalid or banned tag: {}", implied))?;
+
+        if tag == implied || taxonomy.implies(&implied, &tag) {
+            return Err(anyhow!("'{}' implying '{}' would create a cycle", tag, implied));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO tag_implications (tag, implied, created_by)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+            tag,
+            implied,
+            staff_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Self::queue_reindex(&mut tx, &tag).await?;
+
+        tx.commit().await?;
+        self.invalidate().await;
+
+        Ok(())
+    }
+
+    /// Remove an implication rule
+    pub async fn remove_implication(&self, tag: &str, implied: &str) -> Result<bool> {
+        let (Some(tag), Some(implied)) = (normalize_tag(tag), normalize_tag(implied)) else {
+            return Ok(false);
+        };
+
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query!(
+            "DELETE FROM tag_implications WHERE tag = $1 AND implied = $2",
+            tag,
+            implied
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Self::queue_reindex(&mut tx, &tag).await?;
+
+        tx.commit().await?;
+        self.invalidate().await;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Ban a tag
+    ///
+    /// Removes its votes, upload tags, aliases and implications.
+    pub async fn ban_tag(&self, tag: &str, reason: Option<String>, staff_id: Uuid) -> Result<()> {
+        let tag = normalize_tag(tag).ok_or_else(|| anyhow!("Invalid tag: {}", tag))?;
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO banned_tags (tag, reason, created_by)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (tag) DO UPDATE SET reason = $2, created_by = $3, created_at = NOW()
+            "#,
+            tag,
+            reason,
+            staff_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        Self::queue_reindex(&mut tx, &tag).await?;
+
+        sqlx::query!("DELETE FROM torrent_tags WHERE tag_name = $1", tag)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!(
+            "UPDATE torrent_metadata SET tags = array_remove(tags, $1) WHERE $1 = ANY(tags)",
+            tag
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM tag_aliases WHERE alias = $1 OR canonical = $1",
+            tag
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM tag_implications WHERE tag = $1 OR implied = $1",
+            tag
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        self.invalidate().await;
+
+        tracing::info!("Tag '{}' banned by {}", tag, staff_id);
+
+        Ok(())
+    }
+
+    /// Lift a tag ban
+    pub async fn unban_tag(&self, tag: &str) -> Result<bool> {
+        let tag = normalize_tag(tag).ok_or_else(|| anyhow!("Invalid tag: {}", tag))?;
+
+        let result = sqlx::query!("DELETE FROM banned_tags WHERE tag = $1", tag)
+            .execute(&self.pool)
+            .await?;
+
+        self.invalidate().await;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Queue torrents carrying a tag for reindexing
+    async fn queue_reindex(tx: &mut Transaction<'_, Postgres>, tag: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO search_index_queue (id, torrent_id, status)
+            SELECT gen_random_uuid(), id, 'pending'
+            FROM torrent_metadata
+            WHERE $1 = ANY(tags)
+            ON CONFLICT (torrent_id) DO UPDATE SET status = 'pending'
+            "#,
+            tag
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn taxonomy() -> TagTaxonomy {
+        let mut taxonomy = TagTaxonomy::default();
+        taxonomy.aliases.insert("scifi".into(), "science.fiction".into());
+        taxonomy.aliases.insert("sci-fi".into(), "science.fiction".into());
+        taxonomy.implications.insert("anime".into(), vec!["animation".into()]);
+        taxonomy.implications.insert("animation".into(), vec!["cartoon".into()]);
+        taxonomy.banned.insert("spam".into());
+        taxonomy
+    }
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(normalize_tag("  Science Fiction "), Some("science.fiction".into()));
+        assert_eq!(normalize_tag("Sci-Fi!"), Some("sci-fi".into()));
+        assert_eq!(normalize_tag("hip__hop"), Some("hip.hop".into()));
+        assert_eq!(normalize_tag("***"), None);
+        assert_eq!(normalize_tag(&"a".repeat(101)), None);
+    }
+
+    #[test]
+    fn test_canonical_tags() {
+        let taxonomy = taxonomy();
+
+        assert_eq!(taxonomy.canonical("SciFi"), Some("science.fiction".into()));
+        assert_eq!(taxonomy.canonical("science fiction"), Some("science.fiction".into()));
+        assert_eq!(taxonomy.canonical("spam"), None);
+
+        let tags = vec!["sci-fi".to_string(), "scifi".to_string(), "spam".to_string()];
+        assert_eq!(taxonomy.canonicalize(&tags), vec!["science.fiction".to_string()]);
+    }
+
+    #[test]
+    fn test_expand_implications() {
+        let mut taxonomy = taxonomy();
+
+        let expanded = taxonomy.expand(&["anime".to_string(), "action".to_string()]);
+        assert_eq!(expanded, vec!["anime", "action", "animation", "cartoon"]);
+        assert!(taxonomy.implies("anime", "cartoon"));
+        assert!(!taxonomy.implies("cartoon", "anime"));
+
+        // Cycles terminate
+        taxonomy.implications.insert("cartoon".into(), vec!["anime".into()]);
+        assert_eq!(taxonomy.expand(&["anime".to_string()]).len(), 3);
+    }
+
+    #[test]
+    fn test_decayed_weight() {
+        assert_eq!(decayed_weight(1, 0.0), 1.0);
+        assert!((decayed_weight(1, VOTE_HALF_LIFE_DAYS) - 0.5).abs() < 1e-9);
+        assert!((decayed_weight(-1, 2.0 * VOTE_HALF_LIFE_DAYS) + 0.25).abs() < 1e-9);
+    }
+}
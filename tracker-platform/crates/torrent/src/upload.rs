@@ -8,8 +8,15 @@
 //! 5. Calculate total size
 //! 6. Support multi-file and single-file torrents
 //! 7. Validate announce URL
-//! 8. Store in database with PENDING moderation status
-//! 9. Queue for search indexing
+//! 8. Apply the category's upload rules
+//! 9. Verify rip logs and cue sheets for music
+//! 10. Cross-check pasted MediaInfo against the release name
+//! 11. Clean up the NFO and mine it for IMDb/TMDB links
+//! 12. Store in database with PENDING moderation status
+//! 13. Queue for search indexing
+//!
+//! Imports from other trackers go through the same checks but are stored
+//! as approved with their original upload time.
 
 use anyhow::{anyhow, Context, Result};
 use axum::{
@@ -18,6 +25,7 @@ use axum::{
     response::IntoResponse,
     Json,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use uuid::Uuid;
@@ -26,8 +34,13 @@ use validator::Validate;
 use crate::{
     bencode::{Torrent, TorrentInfo},
     files::{parse_file_list, validate_file_list, TorrentFileInfo},
-    metadata::{determine_media_type, parse_quality_from_name, QualityInfo},
+    mediainfo::{MediaInfoReport, MediaInfoService, QualityMismatch},
+    metadata::{determine_media_type, parse_nfo, parse_quality_from_name, QualityInfo},
     moderation::{AutoApprovalRules, ModerationService, ModerationStatus},
+    nfo::{extract_links, strip_control_sequences, NfoLinks, NfoService, TmdbKind},
+    riplog::{decode_log, RipLogService, RipVerification},
+    rules::{RuleViolation, UploadRulesService, ViolationCode},
+    tags::TagTaxonomyService,
 };
 
 /// Maximum torrent file size (1MB)
@@ -53,6 +66,15 @@ pub struct UploadRequest {
     /// NFO file content (optional)
     pub nfo_content: Option<String>,
 
+    /// EAC/XLD rip logs (music)
+    pub rip_logs: Option<Vec<String>>,
+
+    /// Cue sheets (music)
+    pub cue_sheets: Option<Vec<String>>,
+
+    /// MediaInfo text output for the main file
+    pub mediainfo: Option<String>,
+
     /// External IDs
     pub tmdb_id: Option<i64>,
     pub imdb_id: Option<String>,
@@ -87,6 +109,31 @@ pub struct UploadResponse {
 
     /// Message
     pub message: String,
+
+    /// Rule warnings that did not block the upload
+    pub warnings: Vec<RuleViolation>,
+}
+
+/// A parsed torrent that passed the upload checks
+#[derive(Debug, Clone)]
+pub struct CheckedTorrent {
+    pub torrent_info: TorrentInfo,
+    pub file_list: Vec<TorrentFileInfo>,
+    pub quality: QualityInfo,
+    pub media_type: crate::metadata::MediaType,
+    /// Rule warnings that did not block the upload
+    pub violations: Vec<RuleViolation>,
+    pub rip_verification: Option<RipVerification>,
+    pub mediainfo: Option<MediaInfoReport>,
+    pub quality_mismatches: Vec<QualityMismatch>,
+}
+
+/// Upload rejected by the category's upload rules
+#[derive(Debug, thiserror::Error)]
+#[error("Upload violates the upload rules for this category")]
+pub struct UploadRejected {
+    /// All violations, including warnings
+    pub violations: Vec<RuleViolation>,
 }
 
 /// Upload error
@@ -94,6 +141,8 @@ pub struct UploadResponse {
 pub struct UploadError {
     pub error: String,
     pub details: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violations: Option<Vec<RuleViolation>>,
 }
 
 impl IntoResponse for UploadError {
@@ -107,6 +156,11 @@ impl IntoResponse for UploadError {
 pub struct UploadService {
     pool: PgPool,
     moderation: ModerationService,
+    rules: UploadRulesService,
+    rip_logs: RipLogService,
+    mediainfo: MediaInfoService,
+    nfo: NfoService,
+    tags: TagTaxonomyService,
     auto_approval_rules: AutoApprovalRules,
 }
 
@@ -114,20 +168,59 @@ impl UploadService {
     /// Create new upload service
     pub fn new(pool: PgPool, auto_approval_rules: AutoApprovalRules) -> Self {
         let moderation = ModerationService::new(pool.clone());
+        let rules = UploadRulesService::new(pool.clone());
+        let rip_logs = RipLogService::new(pool.clone());
+        let mediainfo = MediaInfoService::new(pool.clone());
+        let nfo = NfoService::new(pool.clone());
+        let tags = TagTaxonomyService::new(pool.clone());
         Self {
             pool,
             moderation,
+            rules,
+            rip_logs,
+            mediainfo,
+            nfo,
+            tags,
             auto_approval_rules,
         }
     }
 
-    /// Process torrent upload
-    pub async fn upload_torrent(
+    /// Get upload rules service
+    pub fn rules(&self) -> &UploadRulesService {
+        &self.rules
+    }
+
+    /// Get rip log service
+    pub fn rip_logs(&self) -> &RipLogService {
+        &self.rip_logs
+    }
+
+    /// Get MediaInfo service
+    pub fn mediainfo(&self) -> &MediaInfoService {
+        &self.mediainfo
+    }
+
+    /// Get NFO service
+    pub fn nfo(&self) -> &NfoService {
+        &self.nfo
+    }
+
+    /// Get tag taxonomy service
+    pub fn tags(&self) -> &TagTaxonomyService {
+        &self.tags
+    }
+
+    /// Parse a .torrent file and run every upload check against it
+    ///
+    /// Covers everything except auto-approval: size limit, structure,
+    /// duplicates, announce URL, the category's upload rules, rip logs and
+    /// the MediaInfo cross-check. Fails with [`UploadRejected`] when any
+    /// rule violation is an error.
+    pub async fn check_torrent(
         &self,
-        user_id: Uuid,
-        torrent_data: Vec<u8>,
-        request: UploadRequest,
-    ) -> Result<UploadResponse> {
+        torrent_data: &[u8],
+        request: &UploadRequest,
+    ) -> Result<CheckedTorrent> {
         // Validate request
         request.validate().context("Invalid upload request")?;
 
@@ -140,7 +233,7 @@ impl UploadService {
         }
 
         // Parse torrent file
-        let torrent_info = Torrent::parse(&torrent_data)
+        let torrent_info = Torrent::parse(torrent_data)
             .context("Failed to parse torrent file")?;
 
         // Validate torrent structure
@@ -168,62 +261,111 @@ impl UploadService {
             &torrent_info.torrent.info.name,
         )?;
 
-        // Validate file list
-        let file_validation = validate_file_list(&file_list);
-        if !file_validation.is_valid {
-            return Err(anyhow!(
-                "File list validation failed: {}",
-                file_validation.errors.join(", ")
-            ));
-        }
-
         // Get category information
         let category = self.get_category(request.category_id).await?;
 
-        // Determine media type
-        let file_stats = crate::files::calculate_statistics(&file_list);
-        let media_type = determine_media_type(&category.name, &file_stats);
+        // Apply the category's upload rules
+        let rules_category_id = self.torrent_category_id(&category.slug).await?;
+        let rules = self.rules.rules_for_category(rules_category_id).await?;
+        let file_validation = validate_file_list(&file_list, &rules);
 
-        // Parse quality information from name
         let torrent_name = request.name.as_ref()
             .unwrap_or(&torrent_info.torrent.info.name);
         let quality = parse_quality_from_name(torrent_name);
 
-        // Check auto-approval eligibility
-        let auto_approved = self
-            .moderation
-            .check_auto_approval(user_id, &self.auto_approval_rules)
-            .await?;
+        let mut violations = file_validation.violations;
+        violations.extend(rules.check_release(torrent_name, &quality));
 
-        let moderation_status = if auto_approved {
-            ModerationStatus::Approved
+        // Determine media type
+        let file_stats = crate::files::calculate_statistics(&file_list);
+        let media_type = determine_media_type(&category.name, &file_stats);
+
+        // Verify rip logs and cue sheets
+        let rip_logs = request.rip_logs.as_deref().unwrap_or_default();
+        let cue_sheets = request.cue_sheets.as_deref().unwrap_or_default();
+        let rip_verification = if media_type == crate::metadata::MediaType::Music {
+            let verification = self.rip_logs.verify(rip_logs, cue_sheets, &file_list);
+            violations.extend(verification.violations.iter().cloned());
+            Some(verification)
         } else {
-            ModerationStatus::Pending
+            None
         };
 
-        // Create torrent ID
-        let torrent_id = Uuid::new_v4();
+        // Cross-check MediaInfo against the release name
+        let mut quality_mismatches = Vec::new();
+        let mediainfo = match request.mediainfo.as_deref() {
+            Some(text) if !text.trim().is_empty() => match MediaInfoReport::parse(text) {
+                Ok(report) => {
+                    quality_mismatches = report.cross_check(&quality);
+                    for mismatch in &quality_mismatches {
+                        violations.push(
+                            RuleViolation::warning(
+                                ViolationCode::QualityMismatch,
+                                format!(
+                                    "Release name claims {} but MediaInfo shows {}",
+                                    mismatch.claimed, mismatch.actual
+                                ),
+                            )
+                            .at(mismatch.field.as_str()),
+                        );
+                    }
+                    Some(report)
+                }
+                Err(e) => {
+                    violations.push(
+                        RuleViolation::error(
+                            ViolationCode::InvalidMediaInfo,
+                            format!("Could not read MediaInfo: {}", e),
+                        )
+                        .at("mediainfo"),
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
 
-        // Insert into database
-        self.insert_torrent(
-            torrent_id,
-            user_id,
-            &torrent_info,
-            &request,
-            &file_list,
-            &quality,
+        if violations.iter().any(RuleViolation::is_error) {
+            return Err(UploadRejected { violations }.into());
+        }
+
+        Ok(CheckedTorrent {
+            torrent_info,
+            file_list,
+            quality,
             media_type,
-            moderation_status,
-        )
-        .await?;
+            violations,
+            rip_verification,
+            mediainfo,
+            quality_mismatches,
+        })
+    }
 
-        // Store .torrent file
-        self.store_torrent_file(torrent_id, &torrent_data).await?;
+    /// Process torrent upload
+    pub async fn upload_torrent(
+        &self,
+        user_id: Uuid,
+        torrent_data: Vec<u8>,
+        mut request: UploadRequest,
+    ) -> Result<UploadResponse> {
+        let checked = self.check_torrent(&torrent_data, &request).await?;
 
-        // Queue for search indexing
-        if moderation_status == ModerationStatus::Approved {
-            self.queue_for_indexing(torrent_id).await?;
-        }
+        // Check auto-approval eligibility; quality mismatches go to a moderator
+        let auto_approved = checked.quality_mismatches.is_empty()
+            && self
+                .moderation
+                .check_auto_approval(user_id, &self.auto_approval_rules)
+                .await?;
+
+        let moderation_status = if auto_approved {
+            ModerationStatus::Approved
+        } else {
+            ModerationStatus::Pending
+        };
+
+        let torrent_id = self
+            .store(user_id, &torrent_data, &mut request, &checked, moderation_status, None)
+            .await?;
 
         let message = if auto_approved {
             "Torrent uploaded and auto-approved successfully".to_string()
@@ -233,25 +375,129 @@ impl UploadService {
 
         Ok(UploadResponse {
             torrent_id,
-            info_hash: torrent_info.info_hash,
+            info_hash: checked.torrent_info.info_hash,
             moderation_status,
             auto_approved,
             message,
+            warnings: checked.violations,
+        })
+    }
+
+    /// Import a torrent from another tracker's database
+    ///
+    /// Runs the same checks as a normal upload but skips moderation: the
+    /// torrent was already live on the source site, so it is stored as
+    /// approved with its original upload time.
+    pub async fn import_torrent(
+        &self,
+        user_id: Uuid,
+        torrent_data: Vec<u8>,
+        mut request: UploadRequest,
+        created_at: DateTime<Utc>,
+    ) -> Result<UploadResponse> {
+        let checked = self.check_torrent(&torrent_data, &request).await?;
+
+        let moderation_status = ModerationStatus::Approved;
+        let torrent_id = self
+            .store(
+                user_id,
+                &torrent_data,
+                &mut request,
+                &checked,
+                moderation_status,
+                Some(created_at),
+            )
+            .await?;
+
+        Ok(UploadResponse {
+            torrent_id,
+            info_hash: checked.torrent_info.info_hash,
+            moderation_status,
+            auto_approved: false,
+            message: "Torrent imported successfully".to_string(),
+            warnings: checked.violations,
         })
     }
 
-    /// Insert torrent into database
+    /// Store a checked torrent and everything attached to it
+    async fn store(
+        &self,
+        user_id: Uuid,
+        torrent_data: &[u8],
+        request: &mut UploadRequest,
+        checked: &CheckedTorrent,
+        moderation_status: ModerationStatus,
+        created_at: Option<DateTime<Utc>>,
+    ) -> Result<Uuid> {
+        // Resolve tag aliases and drop banned tags
+        if let Some(tags) = request.tags.take() {
+            request.tags = Some(self.tags.taxonomy().await?.canonicalize(&tags));
+        }
+
+        // Clean up the NFO and fill in external IDs it links to
+        let nfo_links = match request.nfo_content.take() {
+            Some(nfo) if !nfo.trim().is_empty() => {
+                let nfo = strip_control_sequences(&nfo);
+                let links = extract_links(&nfo);
+
+                if request.imdb_id.is_none() {
+                    request.imdb_id = links.imdb_ids.first().cloned();
+                }
+                if request.tmdb_id.is_none() {
+                    request.tmdb_id = TmdbKind::for_media_type(checked.media_type)
+                        .and_then(|kind| links.tmdb_id(kind));
+                }
+
+                request.nfo_content = Some(nfo);
+                links
+            }
+            _ => Default::default(),
+        };
+
+        // Insert into database
+        let torrent_id = self
+            .insert_torrent(
+                user_id,
+                request,
+                checked,
+                &nfo_links,
+                moderation_status,
+                created_at.unwrap_or_else(Utc::now),
+            )
+            .await?;
+
+        // Store .torrent file
+        self.store_torrent_file(torrent_id, torrent_data).await?;
+
+        // Queue for search indexing
+        if moderation_status == ModerationStatus::Approved {
+            self.queue_for_indexing(torrent_id).await?;
+        }
+
+        Ok(torrent_id)
+    }
+
+    /// Insert torrent into database, returning its new ID
+    ///
+    /// Rip logs, MediaInfo and NFO links are written in the same transaction.
     async fn insert_torrent(
         &self,
-        torrent_id: Uuid,
         user_id: Uuid,
-        torrent_info: &TorrentInfo,
         request: &UploadRequest,
-        file_list: &[TorrentFileInfo],
-        quality: &QualityInfo,
-        media_type: crate::metadata::MediaType,
+        checked: &CheckedTorrent,
+        nfo_links: &NfoLinks,
         moderation_status: ModerationStatus,
-    ) -> Result<()> {
+        created_at: DateTime<Utc>,
+    ) -> Result<Uuid> {
+        let CheckedTorrent {
+            torrent_info,
+            file_list,
+            quality,
+            media_type,
+            ..
+        } = checked;
+
+        let torrent_id = Uuid::new_v4();
         let mut tx = self.pool.begin().await?;
 
         let torrent_name = request.name.as_ref()
@@ -267,7 +513,7 @@ impl UploadService {
                 moderation_status, created_at, is_multi_file,
                 announce_url, anonymous
             ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NOW(), $11, $12, $13
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14
             )
             "#,
             torrent_id,
@@ -280,6 +526,7 @@ impl UploadService {
             user_id,
             request.category_id,
             moderation_status as ModerationStatus,
+            created_at,
             torrent_info.is_multi_file,
             torrent_info.torrent.announce,
             request.anonymous.unwrap_or(false),
@@ -341,7 +588,7 @@ impl UploadService {
             torrent_name,
             request.description,
             request.category_id,
-            media_type as crate::metadata::MediaType,
+            *media_type as crate::metadata::MediaType,
             request.tags.as_ref().map(|t| t.as_slice()).unwrap_or(&[]),
             serde_json::to_value(quality)?,
             serde_json::to_value(&external_ids)?,
@@ -352,9 +599,36 @@ impl UploadService {
         .execute(&mut *tx)
         .await?;
 
+        // Store rip logs and log score
+        if let Some(ref verification) = checked.rip_verification {
+            let rip_logs = request.rip_logs.as_deref().unwrap_or_default();
+            let cue_sheets = request.cue_sheets.as_deref().unwrap_or_default();
+            self.rip_logs
+                .store(&mut tx, torrent_id, rip_logs, cue_sheets, verification)
+                .await?;
+        }
+
+        // Store MediaInfo
+        if let (Some(text), Some(report)) = (request.mediainfo.as_deref(), &checked.mediainfo) {
+            self.mediainfo
+                .store(
+                    &mut tx,
+                    torrent_id,
+                    text,
+                    report,
+                    &checked.quality_mismatches,
+                )
+                .await?;
+        }
+
+        // Store NFO links for media enrichment
+        if !nfo_links.is_empty() {
+            self.nfo.store_links(&mut tx, torrent_id, nfo_links).await?;
+        }
+
         tx.commit().await?;
 
-        Ok(())
+        Ok(torrent_id)
     }
 
     /// Store .torrent file (could be filesystem, S3, etc.)
@@ -395,6 +669,22 @@ impl UploadService {
         Ok(())
     }
 
+    /// ID of a category's `torrent_categories` row, which upload rules are
+    /// keyed by
+    async fn torrent_category_id(&self, slug: &str) -> Result<i32> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT id
+            FROM torrent_categories
+            WHERE slug = $1
+            "#,
+            slug
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| anyhow!("Category not found"))
+    }
+
     /// Get category by ID
     async fn get_category(&self, category_id: Uuid) -> Result<crate::metadata::Category> {
         let record = sqlx::query!(
@@ -432,6 +722,9 @@ pub async fn upload_handler(
 ) -> Result<Json<UploadResponse>, UploadError> {
     let mut torrent_data: Option<Vec<u8>> = None;
     let mut request: Option<UploadRequest> = None;
+    let mut rip_logs: Vec<String> = Vec::new();
+    let mut cue_sheets: Vec<String> = Vec::new();
+    let mut nfo_content = None;
 
     // Parse multipart form
     while let Some(field) = multipart
@@ -440,6 +733,7 @@ pub async fn upload_handler(
         .map_err(|e| UploadError {
             error: format!("Failed to read multipart field: {}", e),
             details: None,
+            violations: None,
         })?
     {
         let name = field.name().unwrap_or("").to_string();
@@ -449,19 +743,49 @@ pub async fn upload_handler(
                 let data = field.bytes().await.map_err(|e| UploadError {
                     error: format!("Failed to read torrent file: {}", e),
                     details: None,
+                    violations: None,
                 })?;
 
                 torrent_data = Some(data.to_vec());
             }
+            "log" | "cue" => {
+                let data = field.bytes().await.map_err(|e| UploadError {
+                    error: format!("Failed to read {} file: {}", name, e),
+                    details: None,
+                    violations: None,
+                })?;
+
+                let text = decode_log(&data);
+                if name == "log" {
+                    rip_logs.push(text);
+                } else {
+                    cue_sheets.push(text);
+                }
+            }
+            "nfo" => {
+                let data = field.bytes().await.map_err(|e| UploadError {
+                    error: format!("Failed to read NFO file: {}", e),
+                    details: None,
+                    violations: None,
+                })?;
+
+                nfo_content = Some(parse_nfo(&data).map_err(|e| UploadError {
+                    error: format!("Invalid NFO file: {}", e),
+                    details: None,
+                    violations: None,
+                })?);
+            }
             "data" => {
                 let data = field.text().await.map_err(|e| UploadError {
                     error: format!("Failed to read request data: {}", e),
                     details: None,
+                    violations: None,
                 })?;
 
                 request = Some(serde_json::from_str(&data).map_err(|e| UploadError {
                     error: format!("Invalid request data: {}", e),
                     details: None,
+                    violations: None,
                 })?);
             }
             _ => {}
@@ -472,20 +796,41 @@ pub async fn upload_handler(
     let torrent_data = torrent_data.ok_or_else(|| UploadError {
         error: "Missing torrent file".to_string(),
         details: None,
+        violations: None,
     })?;
 
-    let request = request.ok_or_else(|| UploadError {
+    let mut request = request.ok_or_else(|| UploadError {
         error: "Missing request data".to_string(),
         details: None,
+        violations: None,
     })?;
 
+    // Log and cue files uploaded as form fields
+    if !rip_logs.is_empty() {
+        request.rip_logs.get_or_insert_with(Vec::new).extend(rip_logs);
+    }
+    if !cue_sheets.is_empty() {
+        request.cue_sheets.get_or_insert_with(Vec::new).extend(cue_sheets);
+    }
+    if nfo_content.is_some() {
+        request.nfo_content = nfo_content;
+    }
+
     // Process upload
     let response = service
         .upload_torrent(user_id, torrent_data, request)
         .await
-        .map_err(|e| UploadError {
-            error: e.to_string(),
-            details: Some(vec![format!("{:?}", e)]),
+        .map_err(|e| match e.downcast::<UploadRejected>() {
+            Ok(rejected) => UploadError {
+                error: rejected.to_string(),
+                details: None,
+                violations: Some(rejected.violations),
+            },
+            Err(e) => UploadError {
+                error: e.to_string(),
+                details: Some(vec![format!("{:?}", e)]),
+                violations: None,
+            },
         })?;
 
     Ok(Json(response))
@@ -503,6 +848,9 @@ mod tests {
             category_id: Uuid::new_v4(),
             tags: None,
             nfo_content: None,
+            rip_logs: None,
+            cue_sheets: None,
+            mediainfo: None,
             tmdb_id: None,
             imdb_id: None,
             tvdb_id: None,
@@ -0,0 +1,413 @@
+//! Tracker Admin API
+//!
+//! Staff endpoints for looking inside the live `PeerManager`: listing a
+//! torrent's peers, kicking a peer, purging a swarm, showing the largest
+//! swarms and listing a user's active peers across all torrents.
+//!
+//! Everything here works on copies taken from the in-memory swarms. Scans
+//! that walk every swarm run on the blocking thread pool so they never stall
+//! the async workers serving announces.
+
+use crate::batch::TorrentUpdate;
+use crate::connectability::Connectability;
+use crate::peer::{Peer, SwarmSummary};
+use crate::protocol::{InfoHash, PeerId};
+use crate::TrackerService;
+use auth::middleware::{AuthError, AuthState, AuthUser};
+use auth::permissions::Permission;
+use axum::{
+    extract::{FromRef, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+/// Default number of swarms returned by the top swarms endpoint
+const DEFAULT_TOP_SWARMS: usize = 25;
+
+/// Maximum number of swarms returned by the top swarms endpoint
+const MAX_TOP_SWARMS: usize = 500;
+
+/// State for the admin router
+#[derive(Clone)]
+pub struct AdminState {
+    pub service: Arc<TrackerService>,
+    pub auth: AuthState,
+}
+
+impl FromRef<AdminState> for AuthState {
+    fn from_ref(state: &AdminState) -> Self {
+        state.auth.clone()
+    }
+}
+
+/// Admin API error
+#[derive(Debug)]
+pub enum AdminError {
+    /// Caller is not staff or lacks a permission
+    Auth(AuthError),
+    /// Malformed path or query parameter
+    BadRequest(String),
+    /// Torrent or peer is not being tracked
+    NotFound(String),
+    /// Background scan failed
+    Internal(String),
+}
+
+impl From<AuthError> for AdminError {
+    fn from(e: AuthError) -> Self {
+        AdminError::Auth(e)
+    }
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            AdminError::Auth(e) => return e.into_response(),
+            AdminError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            AdminError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            AdminError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+        };
+
+        (status, Json(serde_json::json!({ "error": message }))).into_response()
+    }
+}
+
+/// A peer as shown to staff
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminPeerView {
+    /// Peer ID as hex
+    pub peer_id: String,
+    /// Client name from the user agent, or the peer ID prefix
+    pub client: Option<String>,
+    pub user_id: Option<Uuid>,
+    /// Only present for staff with `ViewUserIPs`
+    pub ip: Option<String>,
+    /// Only present for staff with `ViewUserIPs`
+    pub port: Option<u16>,
+    pub uploaded: u64,
+    pub downloaded: u64,
+    pub left: u64,
+    pub is_seeder: bool,
+    pub upload_speed: u64,
+    pub download_speed: u64,
+    pub connectable: Connectability,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl AdminPeerView {
+    /// Builds the view, dropping the endpoint unless the caller may see IPs
+    pub fn from_peer(peer: &Peer, show_ip: bool) -> Self {
+        let client = peer
+            .user_agent
+            .clone()
+            .or_else(|| peer.peer_id.client_prefix().map(str::to_string));
+
+        Self {
+            peer_id: hex_encode(peer.peer_id.as_bytes()),
+            client,
+            user_id: peer.user_id,
+            ip: show_ip.then(|| peer.ip.to_string()),
+            port: show_ip.then_some(peer.port),
+            uploaded: peer.uploaded,
+            downloaded: peer.downloaded,
+            left: peer.left,
+            is_seeder: peer.is_seeder,
+            upload_speed: peer.upload_speed,
+            download_speed: peer.download_speed,
+            connectable: peer.connectable,
+            last_seen: peer.last_seen,
+        }
+    }
+}
+
+/// A user's peer on some torrent
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminUserPeerView {
+    pub info_hash: String,
+    #[serde(flatten)]
+    pub peer: AdminPeerView,
+}
+
+/// Swarm size as shown to staff
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminSwarmView {
+    pub info_hash: String,
+    pub seeders: u64,
+    pub leechers: u64,
+    pub completed: u64,
+    pub peers: u64,
+}
+
+impl From<SwarmSummary> for AdminSwarmView {
+    fn from(summary: SwarmSummary) -> Self {
+        Self {
+            info_hash: summary.info_hash.to_hex(),
+            seeders: summary.seeders,
+            leechers: summary.leechers,
+            completed: summary.completed,
+            peers: summary.peers(),
+        }
+    }
+}
+
+/// Result of a kick or purge
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminRemovalResult {
+    pub removed: usize,
+}
+
+/// Top swarms query parameters
+#[derive(Debug, Deserialize)]
+pub struct TopSwarmsQuery {
+    pub limit: Option<usize>,
+}
+
+/// Builds the admin router
+///
+/// Mount under an admin prefix, e.g. `/admin/tracker`.
+pub fn routes<S>(service: Arc<TrackerService>, auth: AuthState) -> Router<S> {
+    Router::new()
+        .route("/swarms/top", get(handle_top_swarms))
+        .route(
+            "/torrents/:info_hash/peers",
+            get(handle_list_peers).delete(handle_purge_swarm),
+        )
+        .route(
+            "/torrents/:info_hash/peers/:peer_id",
+            delete(handle_kick_peer),
+        )
+        .route("/users/:user_id/peers", get(handle_user_peers))
+        .with_state(AdminState { service, auth })
+}
+
+/// Rejects callers that are not tracker staff
+fn require_staff(user: &AuthUser) -> Result<(), AdminError> {
+    if user.has_any_permission(&[Permission::SiteAdmin, Permission::TorrentModerator]) {
+        Ok(())
+    } else {
+        Err(AuthError::forbidden().into())
+    }
+}
+
+/// Whether the caller may see peer IP addresses
+fn can_view_ips(user: &AuthUser) -> bool {
+    user.has_any_permission(&[Permission::SiteAdmin, Permission::ViewUserIPs])
+}
+
+fn parse_info_hash(s: &str) -> Result<InfoHash, AdminError> {
+    InfoHash::from_hex(s).map_err(|e| AdminError::BadRequest(format!("Invalid info_hash: {}", e)))
+}
+
+fn parse_peer_id(s: &str) -> Result<PeerId, AdminError> {
+    // Byte slicing below would panic inside a multibyte character
+    if s.len() != 40 || !s.is_ascii() {
+        return Err(AdminError::BadRequest(
+            "Peer ID must be 40 hex characters".to_string(),
+        ));
+    }
+
+    let mut bytes = [0u8; 20];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16)
+            .map_err(|_| AdminError::BadRequest("Invalid peer ID".to_string()))?;
+    }
+
+    Ok(PeerId::new(bytes))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Queues a stats update so the database reflects a kick or purge
+fn queue_swarm_stats(service: &TrackerService, info_hash: InfoHash) {
+    if let Some((seeders, leechers, _)) = service.peer_manager().get_stats(&info_hash) {
+        service.batch_writer().queue_torrent_update(TorrentUpdate {
+            info_hash,
+            seeders: seeders as i32,
+            leechers: leechers as i32,
+            completed_delta: 0,
+        });
+    }
+}
+
+/// GET /torrents/:info_hash/peers
+pub async fn handle_list_peers(
+    State(state): State<AdminState>,
+    user: AuthUser,
+    Path(info_hash): Path<String>,
+) -> Result<Json<Vec<AdminPeerView>>, AdminError> {
+    require_staff(&user)?;
+    let info_hash = parse_info_hash(&info_hash)?;
+    let show_ip = can_view_ips(&user);
+
+    let peers = state
+        .service
+        .peer_manager()
+        .swarm_peers(&info_hash)
+        .ok_or_else(|| AdminError::NotFound("Torrent has no active swarm".to_string()))?;
+
+    Ok(Json(
+        peers
+            .iter()
+            .map(|peer| AdminPeerView::from_peer(peer, show_ip))
+            .collect(),
+    ))
+}
+
+/// DELETE /torrents/:info_hash/peers/:peer_id
+pub async fn handle_kick_peer(
+    State(state): State<AdminState>,
+    user: AuthUser,
+    Path((info_hash, peer_id)): Path<(String, String)>,
+) -> Result<Json<AdminRemovalResult>, AdminError> {
+    require_staff(&user)?;
+    let info_hash = parse_info_hash(&info_hash)?;
+    let peer_id = parse_peer_id(&peer_id)?;
+
+    // The client will be re-added on its next announce; this only drops it
+    // from current peer lists
+    state
+        .service
+        .peer_manager()
+        .kick_peer(&info_hash, &peer_id)
+        .ok_or_else(|| AdminError::NotFound("Peer not found".to_string()))?;
+
+    queue_swarm_stats(&state.service, info_hash);
+    info!(
+        "Peer {} kicked from {} by {}",
+        peer_id, info_hash, user.user_id
+    );
+
+    Ok(Json(AdminRemovalResult { removed: 1 }))
+}
+
+/// DELETE /torrents/:info_hash/peers
+pub async fn handle_purge_swarm(
+    State(state): State<AdminState>,
+    user: AuthUser,
+    Path(info_hash): Path<String>,
+) -> Result<Json<AdminRemovalResult>, AdminError> {
+    require_staff(&user)?;
+    let info_hash = parse_info_hash(&info_hash)?;
+
+    let removed = state
+        .service
+        .peer_manager()
+        .purge_swarm(&info_hash)
+        .ok_or_else(|| AdminError::NotFound("Torrent has no active swarm".to_string()))?;
+
+    queue_swarm_stats(&state.service, info_hash);
+    info!(
+        "Swarm {} purged by {} ({} peers)",
+        info_hash, user.user_id, removed
+    );
+
+    Ok(Json(AdminRemovalResult { removed }))
+}
+
+/// GET /swarms/top?limit=N
+pub async fn handle_top_swarms(
+    State(state): State<AdminState>,
+    user: AuthUser,
+    Query(query): Query<TopSwarmsQuery>,
+) -> Result<Json<Vec<AdminSwarmView>>, AdminError> {
+    require_staff(&user)?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_TOP_SWARMS)
+        .min(MAX_TOP_SWARMS);
+
+    let service = Arc::clone(&state.service);
+    let top = tokio::task::spawn_blocking(move || service.peer_manager().top_swarms(limit))
+        .await
+        .map_err(|e| AdminError::Internal(e.to_string()))?;
+
+    Ok(Json(top.into_iter().map(AdminSwarmView::from).collect()))
+}
+
+/// GET /users/:user_id/peers
+pub async fn handle_user_peers(
+    State(state): State<AdminState>,
+    user: AuthUser,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<Vec<AdminUserPeerView>>, AdminError> {
+    require_staff(&user)?;
+    let show_ip = can_view_ips(&user);
+
+    let service = Arc::clone(&state.service);
+    let peers = tokio::task::spawn_blocking(move || service.peer_manager().peers_for_user(user_id))
+        .await
+        .map_err(|e| AdminError::Internal(e.to_string()))?;
+
+    Ok(Json(
+        peers
+            .iter()
+            .map(|(info_hash, peer)| AdminUserPeerView {
+                info_hash: info_hash.to_hex(),
+                peer: AdminPeerView::from_peer(peer, show_ip),
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn staff(permissions: Vec<Permission>) -> AuthUser {
+        AuthUser {
+            user_id: Uuid::new_v4(),
+            permissions,
+            token_id: Uuid::new_v4(),
+        }
+    }
+
+    #[test]
+    fn test_peer_view_hides_ip_without_permission() {
+        let peer = Peer::new(
+            PeerId::new(*b"-qB4500-xxxxxxxxxxxx"),
+            None,
+            "192.168.1.1".parse().unwrap(),
+            6881,
+            0,
+            0,
+            0,
+        );
+
+        let hidden = AdminPeerView::from_peer(&peer, false);
+        assert!(hidden.ip.is_none());
+        assert!(hidden.port.is_none());
+        assert_eq!(hidden.client.as_deref(), Some("qB4500"));
+
+        let shown = AdminPeerView::from_peer(&peer, true);
+        assert_eq!(shown.ip.as_deref(), Some("192.168.1.1"));
+        assert_eq!(shown.port, Some(6881));
+    }
+
+    #[test]
+    fn test_staff_checks() {
+        assert!(require_staff(&staff(vec![Permission::TorrentModerator])).is_ok());
+        assert!(require_staff(&staff(vec![Permission::Download])).is_err());
+
+        assert!(can_view_ips(&staff(vec![Permission::ViewUserIPs])));
+        assert!(!can_view_ips(&staff(vec![Permission::TorrentModerator])));
+    }
+
+    #[test]
+    fn test_parse_peer_id() {
+        let peer_id = parse_peer_id(&"ab".repeat(20)).unwrap();
+        assert_eq!(peer_id.as_bytes(), &[0xab; 20]);
+
+        assert!(parse_peer_id("abc").is_err());
+        assert!(parse_peer_id(&"zz".repeat(20)).is_err());
+        assert!(parse_peer_id(&"é".repeat(20)).is_err());
+    }
+}
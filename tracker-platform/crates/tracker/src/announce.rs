@@ -5,18 +5,19 @@
 //!
 //! Target latency: <10ms for optimal client experience
 
-use crate::batch::{PeerUpdate, TorrentUpdate};
+use crate::batch::{PeerUpdate, SnatchRecord, TorrentUpdate};
+use crate::connectability::Connectability;
 use crate::peer::Peer;
-use crate::protocol::{BencodeResponse, CompactPeerV4, Event, InfoHash, PeerId};
+use crate::protocol::{BencodeResponse, Event, InfoHash, PeerId};
 use crate::statistics::{RequestTimer, RequestType};
 use crate::TrackerService;
 use axum::{
-    extract::{Query, State},
+    extract::{ConnectInfo, Query, State},
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 use serde::Deserialize;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tracing::{debug, warn};
 use uuid::Uuid;
@@ -202,12 +203,22 @@ impl AnnounceHandler {
                 // Increment completed count
                 swarm.increment_completed();
                 debug!("Peer completed: {} for {}", peer_id, info_hash);
+
+                // Record a snatch for known users that actually finished
+                if let (Some(user_id), 0) = (user_id, params.left) {
+                    self.service.batch_writer().queue_snatch(SnatchRecord {
+                        info_hash,
+                        user_id,
+                        uploaded: params.uploaded,
+                        downloaded: params.downloaded,
+                    });
+                }
             }
             _ => {}
         }
 
         // Create or update peer
-        let peer = Peer::new(
+        let mut peer = Peer::new(
             peer_id,
             user_id,
             peer_ip,
@@ -217,11 +228,35 @@ impl AnnounceHandler {
             params.left,
         );
 
+        // Reuse a cached probe result for this endpoint if there is one
+        let peer_addr = SocketAddr::new(peer_ip, params.port);
+        let connectability = self.service.connectability();
+        peer.connectable = connectability
+            .cached(&peer_addr)
+            .unwrap_or(Connectability::Unknown);
+
         let is_seeder = peer.is_seeder;
 
         // Update swarm
         swarm.upsert_peer(peer.clone());
 
+        // Probe the endpoint in the background; the result lands on the peer
+        // and is persisted with its next announce. Only the address the
+        // request actually came from is dialled, never a client-supplied one.
+        if peer_ip == client_ip && connectability.try_reserve(peer_addr) {
+            let service = Arc::clone(&self.service);
+            tokio::spawn(async move {
+                let status = service.connectability().probe(peer_addr, info_hash).await;
+                service.peer_manager().set_connectability(
+                    info_hash,
+                    &peer_addr.ip(),
+                    peer_addr.port(),
+                    status,
+                );
+                debug!("Connectability of {} for {}: {:?}", peer_addr, info_hash, status);
+            });
+        }
+
         // Queue database update (batched write)
         self.service.batch_writer().queue_peer_update(PeerUpdate {
             info_hash,
@@ -234,6 +269,7 @@ impl AnnounceHandler {
             left: params.left,
             is_seeder,
             user_agent: params.user_agent.clone(),
+            connectable: peer.connectable.as_db_value(),
         });
 
         // Queue torrent stats update
@@ -249,7 +285,12 @@ impl AnnounceHandler {
             .map(|n| n.max(0) as usize)
             .unwrap_or(DEFAULT_NUMWANT);
 
-        let peers = swarm.select_peers(is_seeder, is_ipv6, numwant);
+        let peers = swarm.select_peers(
+            is_seeder,
+            is_ipv6,
+            numwant,
+            connectability.deprioritize_unconnectable(),
+        );
 
         // Build response
         let response = self.build_announce_response(
@@ -263,11 +304,33 @@ impl AnnounceHandler {
     }
 
     /// Authenticates a passkey and returns the user ID
+    ///
+    /// Passkeys of banned, disabled and deleted accounts are rejected.
     async fn authenticate_passkey(&self, passkey: &str) -> Result<Uuid, AnnounceError> {
-        // TODO: Implement actual passkey validation against database
-        // For now, try to parse as UUID
-        passkey.parse()
-            .map_err(|_| AnnounceError::unauthorized("Invalid passkey"))
+        if passkey.len() != 32 || !passkey.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(AnnounceError::unauthorized("Invalid passkey"));
+        }
+
+        let row: Option<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT id
+            FROM users
+            WHERE passkey = $1
+              AND is_active = true
+              AND is_banned = false
+              AND deleted_at IS NULL
+            "#,
+        )
+        .bind(passkey)
+        .fetch_optional(self.service.db_pool().as_ref())
+        .await
+        .map_err(|e| {
+            warn!("Passkey lookup failed: {}", e);
+            AnnounceError::new("Tracker unavailable", StatusCode::SERVICE_UNAVAILABLE)
+        })?;
+
+        row.map(|(user_id,)| user_id)
+            .ok_or_else(|| AnnounceError::unauthorized("Invalid passkey"))
     }
 
     /// Builds the announce response
@@ -343,16 +406,16 @@ impl AnnounceHandler {
 /// HTTP handler for announce requests
 ///
 /// Extracts parameters from the query string and the client's IP address,
-/// then delegates to AnnounceHandler.
+/// then delegates to AnnounceHandler. The client IP is the connection's peer
+/// address, so the router must be served with connect info.
 pub async fn handle_announce(
     State(service): State<Arc<TrackerService>>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     Query(params): Query<AnnounceRequest>,
 ) -> Result<Response, AnnounceError> {
-    // TODO: Extract real client IP from headers (X-Forwarded-For, X-Real-IP)
-    // For now, use a placeholder
-    let client_ip: IpAddr = "127.0.0.1".parse().unwrap();
+    let client_ip = remote_addr.ip();
 
-    let handler = AnnounceHandler::new(service);
+    let handler = AnnounceHandler::new(Arc::clone(&service));
 
     match handler.handle(params, client_ip).await {
         Ok(response) => Ok((
@@ -12,7 +12,7 @@
 
 use crate::protocol::{InfoHash, PeerId};
 use crate::statistics::TrackerStatistics;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use parking_lot::Mutex;
 use sqlx::PgPool;
 use std::collections::HashMap;
@@ -20,7 +20,7 @@ use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
 /// Default flush interval - how often to write batched updates to database
@@ -29,6 +29,12 @@ pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(3);
 /// Default batch size threshold - flush if batch exceeds this size
 pub const DEFAULT_BATCH_SIZE_THRESHOLD: usize = 1000;
 
+/// Most seed time credited to a snatch by a single announce (seconds)
+///
+/// Twice the default announce interval, so a client that goes away and comes
+/// back much later is not credited for the gap.
+pub const MAX_SEED_TIME_CREDIT: i64 = 3600;
+
 /// Represents a peer update to be written to the database
 #[derive(Debug, Clone)]
 pub struct PeerUpdate {
@@ -61,6 +67,9 @@ pub struct PeerUpdate {
 
     /// User agent string
     pub user_agent: Option<String>,
+
+    /// Latest connectability probe result (None if not yet probed)
+    pub connectable: Option<bool>,
 }
 
 /// Represents a torrent statistics update
@@ -79,6 +88,22 @@ pub struct TorrentUpdate {
     pub completed_delta: i32,
 }
 
+/// Represents a completed download to be recorded as a snatch
+#[derive(Debug, Clone)]
+pub struct SnatchRecord {
+    /// Info hash of the torrent
+    pub info_hash: InfoHash,
+
+    /// User who completed the torrent
+    pub user_id: Uuid,
+
+    /// Bytes uploaded as reported on the completing announce
+    pub uploaded: u64,
+
+    /// Bytes downloaded as reported on the completing announce
+    pub downloaded: u64,
+}
+
 /// Batched database writer
 ///
 /// Buffers updates in memory and periodically flushes them to the database
@@ -96,6 +121,9 @@ pub struct BatchWriter {
     /// Buffer of pending torrent updates (keyed by info_hash for deduplication)
     torrent_buffer: Arc<Mutex<HashMap<InfoHash, TorrentUpdate>>>,
 
+    /// Buffer of pending snatches (keyed by torrent and user for deduplication)
+    snatch_buffer: Arc<Mutex<HashMap<(InfoHash, Uuid), SnatchRecord>>>,
+
     /// Flush interval
     flush_interval: Duration,
 
@@ -111,6 +139,7 @@ impl BatchWriter {
             statistics,
             peer_buffer: Arc::new(Mutex::new(Vec::new())),
             torrent_buffer: Arc::new(Mutex::new(HashMap::new())),
+            snatch_buffer: Arc::new(Mutex::new(HashMap::new())),
             flush_interval: DEFAULT_FLUSH_INTERVAL,
             batch_size_threshold: DEFAULT_BATCH_SIZE_THRESHOLD,
         }
@@ -128,6 +157,7 @@ impl BatchWriter {
             statistics,
             peer_buffer: Arc::new(Mutex::new(Vec::new())),
             torrent_buffer: Arc::new(Mutex::new(HashMap::new())),
+            snatch_buffer: Arc::new(Mutex::new(HashMap::new())),
             flush_interval,
             batch_size_threshold,
         }
@@ -165,6 +195,17 @@ impl BatchWriter {
         buffer.insert(update.info_hash, update);
     }
 
+    /// Adds a snatch to the buffer
+    ///
+    /// Only the first completion per user and torrent is kept; the database
+    /// also ignores snatches that were already recorded.
+    pub fn queue_snatch(&self, snatch: SnatchRecord) {
+        let mut buffer = self.snatch_buffer.lock();
+        buffer
+            .entry((snatch.info_hash, snatch.user_id))
+            .or_insert(snatch);
+    }
+
     /// Flushes all pending peer updates to the database
     ///
     /// Uses a single multi-row INSERT statement for efficiency.
@@ -188,34 +229,50 @@ impl BatchWriter {
         // Using ON CONFLICT to handle upserts efficiently
         let query = r#"
             INSERT INTO peers (
-                info_hash, peer_id, user_id, ip, port,
-                uploaded, downloaded, "left", is_seeder,
-                user_agent, last_seen
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NOW())
-            ON CONFLICT (info_hash, peer_id, ip, port)
+                torrent_id, peer_id, user_id, ip_address, port,
+                uploaded, downloaded, left_bytes, is_seeder,
+                user_agent, is_connectable, connectable_checked_at, last_announce_at
+            )
+            SELECT
+                t.id, $2, $3, $4::INET, $5, $6, $7, $8, $9, $10, $11,
+                CASE WHEN $11::BOOLEAN IS NULL THEN NULL ELSE NOW() END,
+                NOW()
+            FROM torrents t
+            WHERE t.info_hash = $1
+            ON CONFLICT (torrent_id, user_id, ip_address, peer_id)
             DO UPDATE SET
+                port = EXCLUDED.port,
                 uploaded = EXCLUDED.uploaded,
                 downloaded = EXCLUDED.downloaded,
-                "left" = EXCLUDED."left",
+                left_bytes = EXCLUDED.left_bytes,
                 is_seeder = EXCLUDED.is_seeder,
                 user_agent = EXCLUDED.user_agent,
-                last_seen = NOW()
+                is_connectable = COALESCE(EXCLUDED.is_connectable, peers.is_connectable),
+                connectable_checked_at = COALESCE(
+                    EXCLUDED.connectable_checked_at,
+                    peers.connectable_checked_at
+                ),
+                announces_count = peers.announces_count + 1,
+                last_announce_at = NOW(),
+                updated_at = NOW()
         "#;
 
         // Execute updates in batches (PostgreSQL has parameter limits)
-        const PARAMS_PER_UPDATE: usize = 10;
+        const PARAMS_PER_UPDATE: usize = 11;
         const MAX_PARAMS: usize = 65535; // PostgreSQL limit
         const BATCH_SIZE: usize = MAX_PARAMS / PARAMS_PER_UPDATE;
 
         let mut total_written = 0;
+        let mut failed = 0;
 
         for chunk in updates.chunks(BATCH_SIZE) {
-            for update in chunk {
+            // Peers are stored per user; anonymous announces are not persisted
+            for update in chunk.iter().filter(|update| update.user_id.is_some()) {
                 // Execute individual update
                 // In production, this should use a proper batch query builder
-                sqlx::query(query)
-                    .bind(update.info_hash.as_bytes().as_slice())
-                    .bind(update.peer_id.as_bytes().as_slice())
+                let written = sqlx::query(query)
+                    .bind(update.info_hash.to_hex())
+                    .bind(update.peer_id.to_hex())
                     .bind(update.user_id)
                     .bind(update.ip.to_string())
                     .bind(update.port as i32)
@@ -224,10 +281,28 @@ impl BatchWriter {
                     .bind(update.left as i64)
                     .bind(update.is_seeder)
                     .bind(&update.user_agent)
+                    .bind(update.connectable)
                     .execute(&*self.db_pool)
-                    .await?;
+                    .await;
+
+                match written {
+                    Ok(_) => total_written += 1,
+                    Err(e) => {
+                        error!("Failed to write peer update: {}", e);
+                        failed += 1;
+                    }
+                }
 
-                total_written += 1;
+                // Seed time belongs to the snatch, so it is credited even if
+                // the peer row could not be written
+                if update.is_seeder {
+                    if let Some(user_id) = update.user_id {
+                        if let Err(e) = self.credit_seed_time(update.info_hash, user_id).await {
+                            error!("Failed to credit seed time: {}", e);
+                            failed += 1;
+                        }
+                    }
+                }
             }
         }
 
@@ -242,6 +317,10 @@ impl BatchWriter {
         // Update statistics
         self.statistics.record_batch_write(total_written, elapsed);
 
+        if failed > 0 {
+            return Err(anyhow!("{} of {} peer writes failed", failed, count));
+        }
+
         Ok(())
     }
 
@@ -264,14 +343,15 @@ impl BatchWriter {
 
         let query = r#"
             INSERT INTO torrent_stats (
-                info_hash, seeders, leechers, completed, last_updated
-            ) VALUES ($1, $2, $3, $4, NOW())
+                info_hash, seeders, leechers, completed, last_updated, last_seeded_at
+            ) VALUES ($1, $2, $3, $4, NOW(), CASE WHEN $2 > 0 THEN NOW() END)
             ON CONFLICT (info_hash)
             DO UPDATE SET
                 seeders = EXCLUDED.seeders,
                 leechers = EXCLUDED.leechers,
                 completed = torrent_stats.completed + EXCLUDED.completed,
-                last_updated = NOW()
+                last_updated = NOW(),
+                last_seeded_at = COALESCE(EXCLUDED.last_seeded_at, torrent_stats.last_seeded_at)
         "#;
 
         for update in updates.values() {
@@ -290,15 +370,137 @@ impl BatchWriter {
         Ok(())
     }
 
-    /// Flushes all pending updates (both peers and torrents)
+    /// Credits seed time to a user's snatch of a torrent
+    ///
+    /// Adds the time since the previous seeding announce, capped at
+    /// `MAX_SEED_TIME_CREDIT`. Does nothing if the user has not snatched it.
+    async fn credit_seed_time(&self, info_hash: InfoHash, user_id: Uuid) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE snatched s
+            SET seed_time = s.seed_time + COALESCE(
+                    LEAST(EXTRACT(EPOCH FROM NOW() - s.last_seeded_at)::BIGINT, $3),
+                    0
+                ),
+                last_seeded_at = NOW()
+            FROM torrents t
+            WHERE t.info_hash = $1
+              AND s.torrent_id = t.id
+              AND s.user_id = $2
+            "#,
+        )
+        .bind(info_hash.to_hex())
+        .bind(user_id)
+        .bind(MAX_SEED_TIME_CREDIT)
+        .execute(&*self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Flushes all pending snatches to the database
+    ///
+    /// A snatch is inserted at most once per user and torrent. Only newly
+    /// inserted snatches bump the torrent's completion count and the user's
+    /// snatch count.
+    async fn flush_snatches(&self) -> Result<()> {
+        let snatches = {
+            let mut buffer = self.snatch_buffer.lock();
+            std::mem::take(&mut *buffer)
+        };
+
+        if snatches.is_empty() {
+            return Ok(());
+        }
+
+        let count = snatches.len();
+        debug!("Flushing {} snatches to database", count);
+
+        let start = std::time::Instant::now();
+
+        let query = r#"
+            WITH torrent AS (
+                SELECT id FROM torrents WHERE info_hash = $1
+            ),
+            inserted AS (
+                INSERT INTO snatched (
+                    user_id, torrent_id, uploaded, downloaded, time_left,
+                    last_seeded_at, snatched_at
+                )
+                SELECT
+                    $2, torrent.id, $3, $4,
+                    COALESCE((
+                        SELECT EXTRACT(EPOCH FROM NOW() - MIN(ph.time))::BIGINT
+                        FROM peer_history ph
+                        WHERE ph.torrent_id = torrent.id AND ph.user_id = $2
+                    ), 0),
+                    NOW(), NOW()
+                FROM torrent
+                ON CONFLICT (user_id, torrent_id) DO NOTHING
+                RETURNING torrent_id, user_id
+            ),
+            bump_torrent AS (
+                UPDATE torrents
+                SET times_completed = times_completed + 1
+                WHERE id IN (SELECT torrent_id FROM inserted)
+            ),
+            bump_user AS (
+                UPDATE user_statistics
+                SET torrents_snatched = torrents_snatched + 1
+                WHERE user_id IN (SELECT user_id FROM inserted)
+            )
+            SELECT COUNT(*) FROM inserted
+        "#;
+
+        let mut recorded = 0;
+        let mut pending = snatches.into_iter();
+
+        while let Some((key, snatch)) = pending.next() {
+            let inserted = sqlx::query_scalar::<_, i64>(query)
+                .bind(snatch.info_hash.to_hex())
+                .bind(snatch.user_id)
+                .bind(snatch.uploaded as i64)
+                .bind(snatch.downloaded as i64)
+                .fetch_one(&*self.db_pool)
+                .await;
+
+            match inserted {
+                Ok(inserted) => recorded += inserted,
+                Err(e) => {
+                    // Put this and the remaining snatches back for the next
+                    // flush; they predate anything queued since
+                    let mut buffer = self.snatch_buffer.lock();
+                    for (key, snatch) in std::iter::once((key, snatch)).chain(pending) {
+                        buffer.insert(key, snatch);
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+
+        let elapsed = start.elapsed();
+        info!(
+            "Flushed {} snatches in {:?} ({} new)",
+            count, elapsed, recorded
+        );
+
+        Ok(())
+    }
+
+    /// Flushes all pending updates (peers, torrents and snatches)
+    ///
+    /// Each buffer is flushed to completion even if another one fails; the
+    /// first error is returned.
     pub async fn flush_all(&self) -> Result<()> {
         // Flush in parallel
         let peer_result = self.flush_peer_updates();
         let torrent_result = self.flush_torrent_updates();
+        let snatch_result = self.flush_snatches();
 
-        tokio::try_join!(peer_result, torrent_result)?;
+        let (peer_result, torrent_result, snatch_result) =
+            tokio::join!(peer_result, torrent_result, snatch_result);
 
-        Ok(())
+        peer_result.and(torrent_result).and(snatch_result)
     }
 
     /// Runs the batch writer's main loop
@@ -319,11 +521,12 @@ impl BatchWriter {
             // Check buffer sizes
             let peer_count = self.peer_buffer.lock().len();
             let torrent_count = self.torrent_buffer.lock().len();
+            let snatch_count = self.snatch_buffer.lock().len();
 
-            if peer_count > 0 || torrent_count > 0 {
+            if peer_count > 0 || torrent_count > 0 || snatch_count > 0 {
                 debug!(
-                    "Periodic flush: {} peer updates, {} torrent updates",
-                    peer_count, torrent_count
+                    "Periodic flush: {} peer updates, {} torrent updates, {} snatches",
+                    peer_count, torrent_count, snatch_count
                 );
 
                 if let Err(e) = self.flush_all().await {
@@ -344,6 +547,11 @@ impl BatchWriter {
         self.torrent_buffer.lock().len()
     }
 
+    /// Returns the current snatch buffer size
+    pub fn snatch_buffer_size(&self) -> usize {
+        self.snatch_buffer.lock().len()
+    }
+
     /// Helper method to clone fields needed for flushing
     fn clone_for_flush(&self) -> Self {
         Self {
@@ -351,6 +559,7 @@ impl BatchWriter {
             statistics: Arc::clone(&self.statistics),
             peer_buffer: Arc::clone(&self.peer_buffer),
             torrent_buffer: Arc::clone(&self.torrent_buffer),
+            snatch_buffer: Arc::clone(&self.snatch_buffer),
             flush_interval: self.flush_interval,
             batch_size_threshold: self.batch_size_threshold,
         }
@@ -365,6 +574,7 @@ impl Clone for BatchWriter {
             statistics: Arc::clone(&self.statistics),
             peer_buffer: Arc::clone(&self.peer_buffer),
             torrent_buffer: Arc::clone(&self.torrent_buffer),
+            snatch_buffer: Arc::clone(&self.snatch_buffer),
             flush_interval: self.flush_interval,
             batch_size_threshold: self.batch_size_threshold,
         }
@@ -395,6 +605,7 @@ mod tests {
             left: 2000,
             is_seeder: false,
             user_agent: Some("TestClient/1.0".to_string()),
+            connectable: None,
         };
 
         assert_eq!(update.port, 6881);
@@ -413,4 +624,53 @@ mod tests {
         assert_eq!(update.seeders, 10);
         assert_eq!(update.leechers, 5);
     }
+
+    #[tokio::test]
+    async fn test_queue_snatch_deduplicates() {
+        let pool = PgPool::connect_lazy("postgres://localhost/tracker_test").unwrap();
+        let writer = BatchWriter::new(Arc::new(pool), Arc::new(TrackerStatistics::new()));
+
+        let user_id = Uuid::new_v4();
+        let snatch = |uploaded| SnatchRecord {
+            info_hash: InfoHash::new([1u8; 20]),
+            user_id,
+            uploaded,
+            downloaded: 1000,
+        };
+
+        writer.queue_snatch(snatch(10));
+        writer.queue_snatch(snatch(20));
+        writer.queue_snatch(SnatchRecord {
+            user_id: Uuid::new_v4(),
+            ..snatch(30)
+        });
+
+        assert_eq!(writer.snatch_buffer_size(), 2);
+
+        // The first completion wins
+        let buffer = writer.snatch_buffer.lock();
+        assert_eq!(buffer[&(InfoHash::new([1u8; 20]), user_id)].uploaded, 10);
+    }
+
+    #[tokio::test]
+    async fn test_failed_snatch_flush_keeps_buffer() {
+        // Nothing listens here, so every write fails
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(200))
+            .connect_lazy("postgres://localhost:1/tracker_test")
+            .unwrap();
+        let writer = BatchWriter::new(Arc::new(pool), Arc::new(TrackerStatistics::new()));
+
+        for byte in 1..=3u8 {
+            writer.queue_snatch(SnatchRecord {
+                info_hash: InfoHash::new([byte; 20]),
+                user_id: Uuid::new_v4(),
+                uploaded: 0,
+                downloaded: 1000,
+            });
+        }
+
+        assert!(writer.flush_all().await.is_err());
+        assert_eq!(writer.snatch_buffer_size(), 3);
+    }
 }
@@ -0,0 +1,531 @@
+//! Peer Connectability Probing
+//!
+//! This module checks whether a peer's announced endpoint is reachable from
+//! the outside by opening a TCP connection and performing a BitTorrent
+//! handshake. Private trackers use the result to warn users whose client port
+//! is closed.
+//!
+//! Probes run asynchronously off the announce path. Results are cached per
+//! endpoint, and both the probe rate and the number of concurrent probes are
+//! limited so that an announce flood cannot turn the tracker into a port
+//! scanner. Only globally routable addresses are probed, so peers cannot
+//! point the tracker at loopback, private or link-local services.
+
+use crate::protocol::{InfoHash, PeerId};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::time;
+use tracing::debug;
+
+/// Default time allowed for connecting and completing the handshake
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default lifetime of a cached probe result
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(3600); // 1 hour
+
+/// Default number of probes allowed to run at the same time
+pub const DEFAULT_MAX_CONCURRENT_PROBES: usize = 64;
+
+/// Default number of new probes started per second
+pub const DEFAULT_MAX_PROBES_PER_SECOND: u32 = 100;
+
+/// Protocol identifier sent at the start of every BitTorrent handshake
+const PROTOCOL_STRING: &[u8; 19] = b"BitTorrent protocol";
+
+/// Length of a full handshake: pstrlen + pstr + reserved + info_hash + peer_id
+const HANDSHAKE_LEN: usize = 1 + 19 + 8 + 20 + 20;
+
+/// Peer ID the tracker presents when probing
+const PROBE_PEER_ID: [u8; 20] = *b"-TP0100-connectcheck";
+
+/// Connectability state of a peer endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Connectability {
+    /// No probe has completed yet
+    #[default]
+    Unknown,
+    /// The peer completed a BitTorrent handshake
+    Connectable,
+    /// The connection was refused, timed out or the handshake was invalid
+    Unconnectable,
+}
+
+impl Connectability {
+    /// Converts to the nullable `peers.is_connectable` column value
+    #[inline]
+    pub fn as_db_value(&self) -> Option<bool> {
+        match self {
+            Connectability::Unknown => None,
+            Connectability::Connectable => Some(true),
+            Connectability::Unconnectable => Some(false),
+        }
+    }
+}
+
+/// Connectability probe settings
+#[derive(Debug, Clone)]
+pub struct ProbeConfig {
+    /// Whether probing is enabled at all
+    pub enabled: bool,
+
+    /// Time allowed for connecting and completing the handshake
+    pub timeout: Duration,
+
+    /// How long a probe result is reused before the endpoint is probed again
+    pub cache_ttl: Duration,
+
+    /// Maximum number of probes running at once
+    pub max_concurrent: usize,
+
+    /// Maximum number of probes started per second
+    pub max_per_second: u32,
+
+    /// Whether unconnectable peers are handed out last in announce responses
+    pub deprioritize_unconnectable: bool,
+
+    /// Whether loopback, private and other non-global addresses may be
+    /// probed; only for tests and isolated development setups
+    pub allow_non_global: bool,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout: DEFAULT_PROBE_TIMEOUT,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            max_concurrent: DEFAULT_MAX_CONCURRENT_PROBES,
+            max_per_second: DEFAULT_MAX_PROBES_PER_SECOND,
+            deprioritize_unconnectable: false,
+            allow_non_global: false,
+        }
+    }
+}
+
+/// A cached probe result
+#[derive(Debug, Clone, Copy)]
+struct CachedResult {
+    status: Connectability,
+    checked_at: Instant,
+}
+
+/// Fixed one-second window used for rate limiting
+#[derive(Debug)]
+struct RateWindow {
+    started_at: Instant,
+    count: u32,
+}
+
+/// Asynchronous connectability checker
+///
+/// Shared by all announce handlers. `try_reserve` is cheap and is meant to be
+/// called inline on the announce path; `probe` does the network I/O and should
+/// be run from a spawned task.
+pub struct ConnectabilityChecker {
+    /// Probe settings
+    config: ProbeConfig,
+
+    /// Cached results keyed by endpoint
+    cache: DashMap<SocketAddr, CachedResult>,
+
+    /// Endpoints with a probe currently reserved or running
+    in_flight: DashMap<SocketAddr, ()>,
+
+    /// Limits the number of concurrent probes
+    semaphore: Semaphore,
+
+    /// Limits the number of probes started per second
+    window: Mutex<RateWindow>,
+}
+
+impl ConnectabilityChecker {
+    /// Creates a new checker with the given settings
+    pub fn new(config: ProbeConfig) -> Self {
+        Self {
+            semaphore: Semaphore::new(config.max_concurrent.max(1)),
+            config,
+            cache: DashMap::new(),
+            in_flight: DashMap::new(),
+            window: Mutex::new(RateWindow {
+                started_at: Instant::now(),
+                count: 0,
+            }),
+        }
+    }
+
+    /// Returns the probe settings
+    #[inline]
+    pub fn config(&self) -> &ProbeConfig {
+        &self.config
+    }
+
+    /// Whether unconnectable peers should be handed out last
+    #[inline]
+    pub fn deprioritize_unconnectable(&self) -> bool {
+        self.config.deprioritize_unconnectable
+    }
+
+    /// Returns the cached result for an endpoint if it has not expired
+    pub fn cached(&self, addr: &SocketAddr) -> Option<Connectability> {
+        let entry = self.cache.get(addr)?;
+
+        if entry.checked_at.elapsed() < self.config.cache_ttl {
+            Some(entry.status)
+        } else {
+            None
+        }
+    }
+
+    /// Reserves a probe slot for an endpoint
+    ///
+    /// Returns false if probing is disabled, the address is not globally
+    /// routable, the endpoint has a fresh cached result, a probe for it is
+    /// already pending, or the per-second budget is used up. A successful
+    /// reservation must be followed by `probe`.
+    pub fn try_reserve(&self, addr: SocketAddr) -> bool {
+        if !self.config.enabled || !self.is_probeable(&addr) || self.cached(&addr).is_some() {
+            return false;
+        }
+
+        if self.in_flight.contains_key(&addr) {
+            return false;
+        }
+
+        {
+            let mut window = self.window.lock();
+
+            if window.started_at.elapsed() >= Duration::from_secs(1) {
+                window.started_at = Instant::now();
+                window.count = 0;
+            }
+
+            if window.count >= self.config.max_per_second {
+                return false;
+            }
+
+            window.count += 1;
+        }
+
+        self.in_flight.insert(addr, ()).is_none()
+    }
+
+    /// Probes an endpoint and caches the result
+    ///
+    /// The handshake carries the torrent's info hash so that clients which
+    /// drop connections for unknown torrents still answer.
+    pub async fn probe(&self, addr: SocketAddr, info_hash: InfoHash) -> Connectability {
+        if !self.is_probeable(&addr) {
+            self.in_flight.remove(&addr);
+            return Connectability::Unknown;
+        }
+
+        let status = match self.semaphore.acquire().await {
+            Ok(_permit) => {
+                match time::timeout(self.config.timeout, handshake(addr, &info_hash)).await {
+                    Ok(Ok(())) => Connectability::Connectable,
+                    Ok(Err(e)) => {
+                        debug!("Connectability probe to {} failed: {}", addr, e);
+                        Connectability::Unconnectable
+                    }
+                    Err(_) => {
+                        debug!("Connectability probe to {} timed out", addr);
+                        Connectability::Unconnectable
+                    }
+                }
+            }
+            // The semaphore is never closed, but don't cache anything if it is
+            Err(_) => {
+                self.in_flight.remove(&addr);
+                return Connectability::Unknown;
+            }
+        };
+
+        self.cache.insert(
+            addr,
+            CachedResult {
+                status,
+                checked_at: Instant::now(),
+            },
+        );
+        self.in_flight.remove(&addr);
+
+        status
+    }
+
+    /// Whether the endpoint may be dialled under the current settings
+    fn is_probeable(&self, addr: &SocketAddr) -> bool {
+        addr.port() != 0 && (self.config.allow_non_global || is_global(&addr.ip()))
+    }
+
+    /// Removes expired entries from the result cache
+    ///
+    /// Should be called periodically alongside peer expiry
+    pub fn cleanup_expired(&self) -> usize {
+        let ttl = self.config.cache_ttl;
+        let before = self.cache.len();
+        self.cache.retain(|_, entry| entry.checked_at.elapsed() < ttl);
+        before - self.cache.len()
+    }
+}
+
+impl Default for ConnectabilityChecker {
+    fn default() -> Self {
+        Self::new(ProbeConfig::default())
+    }
+}
+
+/// Whether an address is globally routable
+///
+/// Rejects loopback, unspecified, private, shared (CGNAT), link-local,
+/// benchmarking, documentation, reserved, broadcast and multicast ranges.
+/// IPv4-mapped and IPv4-compatible IPv6 addresses are judged as IPv4.
+pub fn is_global(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_global_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4() {
+            Some(v4) if !v6.is_loopback() && !v6.is_unspecified() => is_global_v4(&v4),
+            _ => is_global_v6(v6),
+        },
+    }
+}
+
+fn is_global_v4(ip: &Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8 "this network"
+        || a == 0
+        // 100.64.0.0/10 shared address space
+        || (a == 100 && (b & 0xc0) == 64)
+        // 192.0.0.0/24 protocol assignments
+        || (a == 192 && b == 0 && c == 0)
+        // 198.18.0.0/15 benchmarking
+        || (a == 198 && (b & 0xfe) == 18)
+        // 240.0.0.0/4 reserved
+        || a >= 240)
+}
+
+fn is_global_v6(ip: &Ipv6Addr) -> bool {
+    let segments = ip.segments();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7 unique local
+        || (segments[0] & 0xfe00) == 0xfc00
+        // fe80::/10 link-local
+        || (segments[0] & 0xffc0) == 0xfe80
+        // 2001:db8::/32 documentation
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+        // 64:ff9b:1::/48 local-use NAT64
+        || (segments[0] == 0x0064 && segments[1] == 0xff9b && segments[2] == 0x0001)
+        // 100::/64 discard-only
+        || (segments[0] == 0x0100 && segments[1..4] == [0, 0, 0]))
+}
+
+/// Builds the handshake the tracker sends to a peer
+fn build_handshake(info_hash: &InfoHash, peer_id: &PeerId) -> [u8; HANDSHAKE_LEN] {
+    let mut bytes = [0u8; HANDSHAKE_LEN];
+    bytes[0] = PROTOCOL_STRING.len() as u8;
+    bytes[1..20].copy_from_slice(PROTOCOL_STRING);
+    // bytes[20..28] are the reserved extension bits, left zeroed
+    bytes[28..48].copy_from_slice(info_hash.as_bytes());
+    bytes[48..68].copy_from_slice(peer_id.as_bytes());
+    bytes
+}
+
+/// Connects to a peer and checks that it answers with a matching handshake
+async fn handshake(addr: SocketAddr, info_hash: &InfoHash) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(addr).await?;
+
+    stream
+        .write_all(&build_handshake(info_hash, &PeerId::new(PROBE_PEER_ID)))
+        .await?;
+
+    // The peer ID in the reply is not needed, only protocol and info hash
+    let mut reply = [0u8; 48];
+    stream.read_exact(&mut reply).await?;
+
+    if reply[0] as usize != PROTOCOL_STRING.len() || &reply[1..20] != PROTOCOL_STRING {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "not a BitTorrent handshake",
+        ));
+    }
+
+    if &reply[28..48] != info_hash.as_bytes() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "info hash mismatch",
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Starts a local listener that answers one handshake by echoing it back
+    async fn spawn_peer(reply_info_hash: Option<InfoHash>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; HANDSHAKE_LEN];
+            socket.read_exact(&mut request).await.unwrap();
+
+            if let Some(info_hash) = reply_info_hash {
+                request[28..48].copy_from_slice(info_hash.as_bytes());
+            }
+            socket.write_all(&request).await.unwrap();
+        });
+
+        addr
+    }
+
+    #[test]
+    fn test_build_handshake() {
+        let info_hash = InfoHash::new([7u8; 20]);
+        let handshake = build_handshake(&info_hash, &PeerId::new(PROBE_PEER_ID));
+
+        assert_eq!(handshake[0], 19);
+        assert_eq!(&handshake[1..20], b"BitTorrent protocol");
+        assert_eq!(&handshake[20..28], &[0u8; 8]);
+        assert_eq!(&handshake[28..48], info_hash.as_bytes());
+        assert_eq!(&handshake[48..68], &PROBE_PEER_ID);
+    }
+
+    #[test]
+    fn test_connectability_db_value() {
+        assert_eq!(Connectability::Unknown.as_db_value(), None);
+        assert_eq!(Connectability::Connectable.as_db_value(), Some(true));
+        assert_eq!(Connectability::Unconnectable.as_db_value(), Some(false));
+    }
+
+    /// Checker that may probe the local test listeners
+    fn local_checker() -> ConnectabilityChecker {
+        ConnectabilityChecker::new(ProbeConfig {
+            allow_non_global: true,
+            ..ProbeConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_probe_local_listener() {
+        let info_hash = InfoHash::new([1u8; 20]);
+        let addr = spawn_peer(None).await;
+        let checker = local_checker();
+
+        assert!(checker.try_reserve(addr));
+        assert_eq!(checker.probe(addr, info_hash).await, Connectability::Connectable);
+        assert_eq!(checker.cached(&addr), Some(Connectability::Connectable));
+
+        // Cached endpoints are not probed again
+        assert!(!checker.try_reserve(addr));
+    }
+
+    #[tokio::test]
+    async fn test_probe_wrong_info_hash() {
+        let addr = spawn_peer(Some(InfoHash::new([2u8; 20]))).await;
+        let checker = local_checker();
+
+        let status = checker.probe(addr, InfoHash::new([1u8; 20])).await;
+        assert_eq!(status, Connectability::Unconnectable);
+    }
+
+    #[tokio::test]
+    async fn test_probe_closed_port() {
+        // Bind and drop a listener to get a port nothing is listening on
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let checker = local_checker();
+
+        let status = checker.probe(addr, InfoHash::new([1u8; 20])).await;
+        assert_eq!(status, Connectability::Unconnectable);
+    }
+
+    #[test]
+    fn test_try_reserve_rate_limit() {
+        let checker = ConnectabilityChecker::new(ProbeConfig {
+            max_per_second: 2,
+            ..ProbeConfig::default()
+        });
+
+        assert!(checker.try_reserve("8.8.8.1:6881".parse().unwrap()));
+        // Duplicate reservations for a pending endpoint are rejected
+        assert!(!checker.try_reserve("8.8.8.1:6881".parse().unwrap()));
+        assert!(checker.try_reserve("8.8.8.2:6881".parse().unwrap()));
+        assert!(!checker.try_reserve("8.8.8.3:6881".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_non_global_addresses_not_probed() {
+        let checker = ConnectabilityChecker::default();
+
+        for addr in [
+            "127.0.0.1:6881",
+            "10.1.2.3:6881",
+            "192.168.1.1:6881",
+            "169.254.169.254:80",
+            "100.64.0.1:6881",
+            "0.0.0.0:6881",
+            "[::1]:6881",
+            "[fd00::1]:6881",
+            "[fe80::1]:6881",
+            "[::ffff:127.0.0.1]:6881",
+        ] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            assert!(!checker.try_reserve(addr), "{} was reserved", addr);
+            assert_eq!(
+                checker.probe(addr, InfoHash::new([1u8; 20])).await,
+                Connectability::Unknown
+            );
+        }
+
+        assert!(checker.try_reserve("203.0.114.1:6881".parse().unwrap()));
+        assert!(checker.try_reserve("[2a00:1450::1]:6881".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_is_global() {
+        assert!(is_global(&"1.1.1.1".parse().unwrap()));
+        assert!(is_global(&"2606:4700::1111".parse().unwrap()));
+        assert!(is_global(&"::ffff:8.8.8.8".parse().unwrap()));
+        assert!(!is_global(&"198.19.0.1".parse().unwrap()));
+        assert!(!is_global(&"203.0.113.7".parse().unwrap()));
+        assert!(!is_global(&"255.255.255.255".parse().unwrap()));
+        assert!(!is_global(&"240.0.0.1".parse().unwrap()));
+        assert!(!is_global(&"2001:db8::1".parse().unwrap()));
+        assert!(!is_global(&"ff02::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_try_reserve_disabled() {
+        let checker = ConnectabilityChecker::new(ProbeConfig {
+            enabled: false,
+            ..ProbeConfig::default()
+        });
+
+        assert!(!checker.try_reserve("8.8.8.1:6881".parse().unwrap()));
+    }
+}
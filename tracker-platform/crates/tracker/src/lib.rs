@@ -1 +1,121 @@
-pub fn placeholder() {}
+//! BitTorrent Tracker Service
+//!
+//! This crate implements the HTTP tracker for the unified tracker platform.
+//!
+//! # Architecture
+//!
+//! The crate is organized into modules:
+//!
+//! - `admin`: Staff endpoints for inspecting and pruning live swarms
+//! - `announce`: Announce request handling
+//! - `batch`: Batched database writes for peers, torrents and snatches
+//! - `connectability`: Asynchronous peer connectability probing
+//! - `peer`: In-memory swarms and peer selection
+//! - `protocol`: Info hashes, peer IDs and bencode responses
+//! - `scrape`: Scrape request handling
+//! - `scrape_policy`: Scrape privacy rules and the full scrape
+//! - `statistics`: Prometheus metrics
+
+pub mod admin;
+pub mod announce;
+pub mod batch;
+pub mod connectability;
+pub mod peer;
+pub mod protocol;
+pub mod scrape;
+pub mod scrape_policy;
+pub mod statistics;
+
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use batch::BatchWriter;
+use connectability::{ConnectabilityChecker, ProbeConfig};
+use peer::PeerManager;
+use scrape_policy::{ScrapeConfig, ScrapePolicy};
+use statistics::TrackerStatistics;
+
+/// Tracker service
+///
+/// Shared by the announce and scrape handlers. The batch writer's flush loop
+/// and the scrape policy's rebuild loop are not started here; spawn them
+/// alongside the other background jobs.
+pub struct TrackerService {
+    db_pool: Arc<PgPool>,
+    peer_manager: Arc<PeerManager>,
+    batch_writer: Arc<BatchWriter>,
+    statistics: Arc<TrackerStatistics>,
+    connectability: ConnectabilityChecker,
+    scrape_policy: Arc<ScrapePolicy>,
+}
+
+impl TrackerService {
+    /// Creates a tracker service with default probe settings
+    pub fn new(db_pool: PgPool) -> Self {
+        Self::with_probe_config(db_pool, ProbeConfig::default())
+    }
+
+    /// Creates a tracker service with custom probe settings
+    pub fn with_probe_config(db_pool: PgPool, probe_config: ProbeConfig) -> Self {
+        Self::with_config(db_pool, probe_config, ScrapeConfig::default())
+    }
+
+    /// Creates a tracker service with custom probe and scrape settings
+    pub fn with_config(
+        db_pool: PgPool,
+        probe_config: ProbeConfig,
+        scrape_config: ScrapeConfig,
+    ) -> Self {
+        let statistics = Arc::new(TrackerStatistics::new());
+        let db_pool = Arc::new(db_pool);
+        let batch_writer = Arc::new(BatchWriter::new(
+            Arc::clone(&db_pool),
+            Arc::clone(&statistics),
+        ));
+
+        Self {
+            db_pool,
+            peer_manager: Arc::new(PeerManager::new()),
+            batch_writer,
+            statistics,
+            connectability: ConnectabilityChecker::new(probe_config),
+            scrape_policy: Arc::new(ScrapePolicy::new(scrape_config)),
+        }
+    }
+
+    /// Returns the database pool
+    #[inline]
+    pub fn db_pool(&self) -> &Arc<PgPool> {
+        &self.db_pool
+    }
+
+    /// Returns the in-memory swarms
+    #[inline]
+    pub fn peer_manager(&self) -> &Arc<PeerManager> {
+        &self.peer_manager
+    }
+
+    /// Returns the batched database writer
+    #[inline]
+    pub fn batch_writer(&self) -> &Arc<BatchWriter> {
+        &self.batch_writer
+    }
+
+    /// Returns the tracker metrics
+    #[inline]
+    pub fn statistics(&self) -> &Arc<TrackerStatistics> {
+        &self.statistics
+    }
+
+    /// Returns the connectability checker
+    #[inline]
+    pub fn connectability(&self) -> &ConnectabilityChecker {
+        &self.connectability
+    }
+
+    /// Returns the scrape policy
+    #[inline]
+    pub fn scrape_policy(&self) -> &Arc<ScrapePolicy> {
+        &self.scrape_policy
+    }
+}
@@ -4,11 +4,12 @@
 //! efficient concurrent data structures (DashMap) to allow lock-free reads and
 //! writes from multiple threads.
 
+use crate::connectability::Connectability;
 use crate::protocol::{InfoHash, PeerId, CompactPeerV4, CompactPeerV6};
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::net::IpAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 use uuid::Uuid;
@@ -54,6 +55,18 @@ pub struct Peer {
 
     /// User agent string if provided
     pub user_agent: Option<String>,
+
+    /// Result of the most recent connectability probe
+    #[serde(default)]
+    pub connectable: Connectability,
+
+    /// Upload speed since the previous announce (bytes/sec)
+    #[serde(default)]
+    pub upload_speed: u64,
+
+    /// Download speed since the previous announce (bytes/sec)
+    #[serde(default)]
+    pub download_speed: u64,
 }
 
 impl Peer {
@@ -79,12 +92,26 @@ impl Peer {
             last_seen: Utc::now(),
             is_seeder: left == 0,
             user_agent: None,
+            connectable: Connectability::Unknown,
+            upload_speed: 0,
+            download_speed: 0,
         }
     }
 
     /// Updates peer statistics from an announce request
+    ///
+    /// Also derives transfer speeds from the byte deltas since the previous
+    /// announce. Counters that went backwards (client restart) yield zero.
     #[inline]
     pub fn update(&mut self, uploaded: u64, downloaded: u64, left: u64) {
+        let elapsed = Utc::now()
+            .signed_duration_since(self.last_seen)
+            .num_seconds();
+        if elapsed > 0 {
+            self.upload_speed = uploaded.saturating_sub(self.uploaded) / elapsed as u64;
+            self.download_speed = downloaded.saturating_sub(self.downloaded) / elapsed as u64;
+        }
+
         self.uploaded = uploaded;
         self.downloaded = downloaded;
         self.left = left;
@@ -210,6 +237,66 @@ impl Swarm {
         }
     }
 
+    /// Removes a peer by its peer ID
+    ///
+    /// Requires a scan of the swarm since peers are keyed by endpoint
+    pub fn remove_peer_by_id(&self, peer_id: &PeerId) -> Option<Peer> {
+        let key = self
+            .peers
+            .iter()
+            .find(|entry| entry.value().peer_id == *peer_id)
+            .map(|entry| entry.key().clone())?;
+
+        let (_, peer) = self.peers.remove(&key)?;
+        if peer.is_seeder {
+            self.seeder_count.fetch_sub(1, Ordering::Relaxed);
+        } else {
+            self.leecher_count.fetch_sub(1, Ordering::Relaxed);
+        }
+        Some(peer)
+    }
+
+    /// Removes every peer from the swarm
+    ///
+    /// Returns the number of peers removed. The completed count is kept.
+    pub fn purge(&self) -> usize {
+        let mut removed = 0;
+
+        self.peers.retain(|_, peer| {
+            if peer.is_seeder {
+                self.seeder_count.fetch_sub(1, Ordering::Relaxed);
+            } else {
+                self.leecher_count.fetch_sub(1, Ordering::Relaxed);
+            }
+            removed += 1;
+            false
+        });
+
+        removed
+    }
+
+    /// Returns a copy of all peers in the swarm
+    ///
+    /// Clones entry by entry so no shard lock is held for long
+    pub fn peers_snapshot(&self) -> Vec<Peer> {
+        self.peers.iter().map(|entry| entry.value().clone()).collect()
+    }
+
+    /// Records the result of a connectability probe for a peer
+    ///
+    /// Returns false if the peer has left the swarm in the meantime
+    pub fn set_connectability(&self, ip: &IpAddr, port: u16, status: Connectability) -> bool {
+        let key = Self::peer_key(ip, port);
+
+        match self.peers.get_mut(&key) {
+            Some(mut peer) => {
+                peer.connectable = status;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Marks a download as completed
     #[inline]
     pub fn increment_completed(&self) {
@@ -246,32 +333,42 @@ impl Swarm {
     /// - Prefers peers of the opposite type (seeders get leechers, vice versa)
     /// - Returns up to MAX_PEERS_RETURNED peers
     /// - Filters by IP version (v4 or v6)
-    pub fn select_peers(&self, is_seeder: bool, ipv6: bool, numwant: usize) -> Vec<Peer> {
+    /// - Optionally hands out unconnectable peers only when nothing else is left
+    pub fn select_peers(
+        &self,
+        is_seeder: bool,
+        ipv6: bool,
+        numwant: usize,
+        deprioritize_unconnectable: bool,
+    ) -> Vec<Peer> {
         let numwant = numwant.min(MAX_PEERS_RETURNED);
         let mut selected = Vec::with_capacity(numwant);
 
-        // First pass: select peers of opposite type
-        let prefer_seeders = !is_seeder;
-        for entry in self.peers.iter() {
-            if selected.len() >= numwant {
-                break;
-            }
-
-            let peer = entry.value();
-
-            // Filter by IP version
+        // Peers that are never worth returning for this request
+        let eligible = |peer: &Peer| {
             let ip_matches = match (&peer.ip, ipv6) {
                 (IpAddr::V4(_), false) => true,
                 (IpAddr::V6(_), true) => true,
                 _ => false,
             };
 
-            if !ip_matches {
-                continue;
+            ip_matches && !peer.is_expired()
+        };
+
+        let deferred = |peer: &Peer| {
+            deprioritize_unconnectable && peer.connectable == Connectability::Unconnectable
+        };
+
+        // First pass: select connectable peers of opposite type
+        let prefer_seeders = !is_seeder;
+        for entry in self.peers.iter() {
+            if selected.len() >= numwant {
+                break;
             }
 
-            // Skip expired peers
-            if peer.is_expired() {
+            let peer = entry.value();
+
+            if !eligible(peer) || deferred(peer) {
                 continue;
             }
 
@@ -281,7 +378,7 @@ impl Swarm {
             }
         }
 
-        // Second pass: fill with any remaining peers if needed
+        // Second pass: fill with any remaining connectable peers if needed
         if selected.len() < numwant {
             for entry in self.peers.iter() {
                 if selected.len() >= numwant {
@@ -290,14 +387,7 @@ impl Swarm {
 
                 let peer = entry.value();
 
-                // Filter by IP version
-                let ip_matches = match (&peer.ip, ipv6) {
-                    (IpAddr::V4(_), false) => true,
-                    (IpAddr::V6(_), true) => true,
-                    _ => false,
-                };
-
-                if !ip_matches || peer.is_expired() {
+                if !eligible(peer) || deferred(peer) {
                     continue;
                 }
 
@@ -308,6 +398,21 @@ impl Swarm {
             }
         }
 
+        // Last resort: unconnectable peers can still reach out to others
+        if deprioritize_unconnectable && selected.len() < numwant {
+            for entry in self.peers.iter() {
+                if selected.len() >= numwant {
+                    break;
+                }
+
+                let peer = entry.value();
+
+                if eligible(peer) && deferred(peer) {
+                    selected.push(peer.clone());
+                }
+            }
+        }
+
         selected
     }
 
@@ -342,6 +447,33 @@ impl Default for Swarm {
     }
 }
 
+/// Size summary of a swarm, used by the admin API
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwarmSummary {
+    pub info_hash: InfoHash,
+    pub seeders: u64,
+    pub leechers: u64,
+    pub completed: u64,
+}
+
+impl SwarmSummary {
+    /// Total number of peers in the swarm
+    #[inline]
+    pub fn peers(&self) -> u64 {
+        self.seeders + self.leechers
+    }
+}
+
+/// Seeding users of a swarm, used by the bonus accrual job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedingSwarm {
+    pub info_hash: InfoHash,
+    pub seeders: u64,
+    pub leechers: u64,
+    /// Distinct users seeding, however many clients each one runs
+    pub seeding_users: Vec<Uuid>,
+}
+
 /// Peer manager coordinates all swarms across all torrents
 ///
 /// Uses a DashMap with InfoHash as key for lock-free concurrent access
@@ -359,7 +491,10 @@ impl PeerManager {
     }
 
     /// Gets or creates a swarm for the given info hash
-    pub fn get_or_create_swarm(&self, info_hash: InfoHash) -> dashmap::mapref::one::Ref<InfoHash, Swarm> {
+    pub fn get_or_create_swarm(
+        &self,
+        info_hash: InfoHash,
+    ) -> dashmap::mapref::one::Ref<'_, InfoHash, Swarm> {
         self.swarms.entry(info_hash).or_insert_with(Swarm::new);
         self.swarms.get(&info_hash).unwrap()
     }
@@ -375,6 +510,106 @@ impl PeerManager {
         self.swarms.get(&info_hash)?.remove_peer(ip, port)
     }
 
+    /// Kicks a peer out of a swarm by peer ID
+    pub fn kick_peer(&self, info_hash: &InfoHash, peer_id: &PeerId) -> Option<Peer> {
+        self.swarms.get(info_hash)?.remove_peer_by_id(peer_id)
+    }
+
+    /// Removes all peers of a swarm, returning how many were removed
+    pub fn purge_swarm(&self, info_hash: &InfoHash) -> Option<usize> {
+        self.swarms.get(info_hash).map(|swarm| swarm.purge())
+    }
+
+    /// Returns a copy of the peers in a swarm
+    pub fn swarm_peers(&self, info_hash: &InfoHash) -> Option<Vec<Peer>> {
+        self.swarms.get(info_hash).map(|swarm| swarm.peers_snapshot())
+    }
+
+    /// Returns the largest swarms by peer count
+    ///
+    /// Only reads the cached counters, so it never touches peer maps
+    pub fn top_swarms(&self, limit: usize) -> Vec<SwarmSummary> {
+        let mut swarms: Vec<SwarmSummary> = self
+            .swarms
+            .iter()
+            .map(|entry| {
+                let swarm = entry.value();
+                SwarmSummary {
+                    info_hash: *entry.key(),
+                    seeders: swarm.seeder_count(),
+                    leechers: swarm.leecher_count(),
+                    completed: swarm.completed_count(),
+                }
+            })
+            .collect();
+
+        swarms.sort_unstable_by(|a, b| b.peers().cmp(&a.peers()));
+        swarms.truncate(limit);
+        swarms
+    }
+
+    /// Returns every active peer belonging to a user across all swarms
+    pub fn peers_for_user(&self, user_id: Uuid) -> Vec<(InfoHash, Peer)> {
+        let mut result = Vec::new();
+
+        for swarm in self.swarms.iter() {
+            for peer in swarm.value().peers.iter() {
+                if peer.user_id == Some(user_id) {
+                    result.push((*swarm.key(), peer.value().clone()));
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Returns every swarm with at least one known seeding user
+    ///
+    /// Walks all swarms; call from a blocking task.
+    pub fn seeding_snapshot(&self) -> Vec<SeedingSwarm> {
+        let mut result = Vec::new();
+
+        for entry in self.swarms.iter() {
+            let swarm = entry.value();
+            let mut seeding_users: Vec<Uuid> = swarm
+                .peers
+                .iter()
+                .filter(|peer| peer.is_seeder && !peer.is_expired())
+                .filter_map(|peer| peer.user_id)
+                .collect();
+
+            if seeding_users.is_empty() {
+                continue;
+            }
+
+            seeding_users.sort_unstable();
+            seeding_users.dedup();
+
+            result.push(SeedingSwarm {
+                info_hash: *entry.key(),
+                seeders: swarm.seeder_count(),
+                leechers: swarm.leecher_count(),
+                seeding_users,
+            });
+        }
+
+        result
+    }
+
+    /// Stores a connectability probe result on a peer
+    pub fn set_connectability(
+        &self,
+        info_hash: InfoHash,
+        ip: &IpAddr,
+        port: u16,
+        status: Connectability,
+    ) -> bool {
+        self.swarms
+            .get(&info_hash)
+            .map(|swarm| swarm.set_connectability(ip, port, status))
+            .unwrap_or(false)
+    }
+
     /// Gets statistics for a torrent
     pub fn get_stats(&self, info_hash: &InfoHash) -> Option<(u64, u64, u64)> {
         self.swarms.get(info_hash).map(|swarm| {
@@ -486,16 +721,141 @@ mod tests {
         }
 
         // Leecher should get seeders
-        let peers = swarm.select_peers(false, false, 5);
+        let peers = swarm.select_peers(false, false, 5, false);
         assert!(peers.len() <= 5);
         assert!(peers.iter().all(|p| p.is_seeder));
 
         // Seeder should get leechers
-        let peers = swarm.select_peers(true, false, 5);
+        let peers = swarm.select_peers(true, false, 5, false);
         assert!(peers.len() <= 5);
         assert!(peers.iter().all(|p| !p.is_seeder));
     }
 
+    #[test]
+    fn test_peer_selection_deprioritizes_unconnectable() {
+        let swarm = Swarm::new();
+
+        for i in 0..4 {
+            let mut peer = create_test_peer(&format!("192.168.1.{}", i), 6881, 0);
+            peer.peer_id = PeerId::new([i as u8; 20]);
+            if i < 2 {
+                peer.connectable = Connectability::Unconnectable;
+            }
+            swarm.upsert_peer(peer);
+        }
+
+        // Connectable peers are handed out first
+        let peers = swarm.select_peers(false, false, 2, true);
+        assert_eq!(peers.len(), 2);
+        assert!(peers.iter().all(|p| p.connectable != Connectability::Unconnectable));
+
+        // Unconnectable peers still fill the list when nothing else is left
+        let peers = swarm.select_peers(false, false, 4, true);
+        assert_eq!(peers.len(), 4);
+    }
+
+    #[test]
+    fn test_set_connectability_survives_reannounce() {
+        let swarm = Swarm::new();
+        let peer = create_test_peer("192.168.1.1", 6881, 1000);
+
+        swarm.upsert_peer(peer.clone());
+        assert!(swarm.set_connectability(&peer.ip, peer.port, Connectability::Connectable));
+
+        // A regular announce must not reset the probe result
+        swarm.upsert_peer(peer.clone());
+        let selected = swarm.select_peers(true, false, 1, false);
+        assert_eq!(selected[0].connectable, Connectability::Connectable);
+
+        swarm.remove_peer(&peer.ip, peer.port);
+        assert!(!swarm.set_connectability(&peer.ip, peer.port, Connectability::Unconnectable));
+    }
+
+    #[test]
+    fn test_peer_speed_from_deltas() {
+        let mut peer = create_test_peer("192.168.1.1", 6881, 1000);
+        peer.last_seen = Utc::now() - chrono::Duration::seconds(10);
+
+        peer.update(10_000, 5_000, 500);
+        assert_eq!(peer.upload_speed, 1_000);
+        assert_eq!(peer.download_speed, 500);
+
+        // Counters reset by a client restart must not underflow
+        peer.last_seen = Utc::now() - chrono::Duration::seconds(10);
+        peer.update(0, 0, 500);
+        assert_eq!(peer.upload_speed, 0);
+    }
+
+    #[test]
+    fn test_kick_and_purge() {
+        let manager = PeerManager::new();
+        let info_hash = InfoHash::new([1u8; 20]);
+
+        for i in 0..3u8 {
+            let mut peer = create_test_peer(&format!("192.168.1.{}", i), 6881, 0);
+            peer.peer_id = PeerId::new([i; 20]);
+            manager.upsert_peer(info_hash, peer);
+        }
+
+        let kicked = manager.kick_peer(&info_hash, &PeerId::new([1u8; 20]));
+        assert!(kicked.is_some());
+        assert_eq!(manager.get_stats(&info_hash), Some((2, 0, 0)));
+        assert!(manager.kick_peer(&info_hash, &PeerId::new([1u8; 20])).is_none());
+
+        assert_eq!(manager.purge_swarm(&info_hash), Some(2));
+        assert_eq!(manager.get_stats(&info_hash), Some((0, 0, 0)));
+    }
+
+    #[test]
+    fn test_top_swarms_and_user_peers() {
+        let manager = PeerManager::new();
+        let user_id = Uuid::new_v4();
+        let small = InfoHash::new([1u8; 20]);
+        let large = InfoHash::new([2u8; 20]);
+
+        manager.upsert_peer(small, create_test_peer("192.168.1.1", 6881, 0));
+        for i in 0..3 {
+            let mut peer = create_test_peer(&format!("192.168.2.{}", i), 6881, 1000);
+            peer.user_id = Some(user_id);
+            manager.upsert_peer(large, peer);
+        }
+
+        let top = manager.top_swarms(1);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].info_hash, large);
+        assert_eq!(top[0].peers(), 3);
+
+        let peers = manager.peers_for_user(user_id);
+        assert_eq!(peers.len(), 3);
+        assert!(peers.iter().all(|(hash, _)| *hash == large));
+    }
+
+    #[test]
+    fn test_seeding_snapshot_dedups_users() {
+        let manager = PeerManager::new();
+        let info_hash = InfoHash::new([1u8; 20]);
+        let user_id = Uuid::new_v4();
+
+        // Same user seeding from two clients
+        for i in 0..2 {
+            let mut peer = create_test_peer(&format!("192.168.1.{}", i), 6881, 0);
+            peer.user_id = Some(user_id);
+            manager.upsert_peer(info_hash, peer);
+        }
+
+        // A leecher and an anonymous seeder are not credited
+        let mut leecher = create_test_peer("192.168.1.10", 6881, 1000);
+        leecher.user_id = Some(Uuid::new_v4());
+        manager.upsert_peer(info_hash, leecher);
+        manager.upsert_peer(info_hash, create_test_peer("192.168.1.11", 6881, 0));
+
+        let snapshot = manager.seeding_snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].seeding_users, vec![user_id]);
+        assert_eq!(snapshot[0].seeders, 3);
+        assert_eq!(snapshot[0].leechers, 1);
+    }
+
     #[test]
     fn test_peer_manager() {
         let manager = PeerManager::new();
@@ -103,6 +103,11 @@ impl PeerId {
         &self.0
     }
 
+    /// Converts to a hex string representation
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
     /// Extracts the client identifier prefix if present
     pub fn client_prefix(&self) -> Option<&str> {
         if self.0[0] == b'-' && self.0[7] == b'-' {
@@ -3,18 +3,24 @@
 //! This module handles scrape requests from BitTorrent clients. Scrape
 //! requests allow clients to efficiently query statistics for multiple
 //! torrents without announcing.
+//!
+//! Privacy rules (passkey requirement, rate limits, hidden torrents) and the
+//! optional full scrape live in `scrape_policy`.
 
 use crate::protocol::{BencodeResponse, InfoHash};
+use crate::scrape_policy::FullScrapeSnapshot;
 use crate::statistics::{RequestTimer, RequestType};
 use crate::TrackerService;
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
 use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tracing::{debug, warn};
+use uuid::Uuid;
 
 /// Maximum number of torrents allowed in a single scrape request
 const MAX_SCRAPE_TORRENTS: usize = 100;
@@ -54,6 +60,14 @@ impl ScrapeError {
         Self::new(message, StatusCode::BAD_REQUEST)
     }
 
+    fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(message, StatusCode::UNAUTHORIZED)
+    }
+
+    fn rate_limited(message: impl Into<String>) -> Self {
+        Self::new(message, StatusCode::TOO_MANY_REQUESTS)
+    }
+
     fn to_bencode(&self) -> Vec<u8> {
         let mut response = BencodeResponse::with_capacity(128);
         response.start_dict();
@@ -82,6 +96,15 @@ pub struct TorrentStats {
     pub downloaded: i64,  // Number of completed downloads
 }
 
+/// Body of a successful scrape
+#[derive(Debug)]
+pub enum ScrapeBody {
+    /// Bencoded response for an explicit list of info hashes
+    Partial(Vec<u8>),
+    /// Prebuilt gzip-compressed dump of every visible torrent
+    Full(Arc<FullScrapeSnapshot>),
+}
+
 /// Scrape request handler
 pub struct ScrapeHandler {
     service: Arc<TrackerService>,
@@ -93,21 +116,50 @@ impl ScrapeHandler {
     }
 
     /// Processes a scrape request
-    pub async fn handle(&self, params: ScrapeRequest) -> Result<Vec<u8>, ScrapeError> {
+    pub async fn handle(
+        &self,
+        params: ScrapeRequest,
+        client_ip: IpAddr,
+    ) -> Result<ScrapeBody, ScrapeError> {
         // Start request timer (automatically records latency on drop)
         let _timer = RequestTimer::new(
             &self.service.statistics(),
             RequestType::Scrape,
         );
 
+        let policy = self.service.scrape_policy();
+
+        // Authenticate, then rate limit per user and client address
+        let user_id = match &params.passkey {
+            Some(passkey) => Some(self.authenticate_passkey(passkey).await?),
+            None if policy.config().require_passkey => {
+                return Err(ScrapeError::unauthorized("Passkey required"));
+            }
+            None => None,
+        };
+
+        if !policy.check_rate_limit(user_id, client_ip) {
+            return Err(ScrapeError::rate_limited("Scrape rate limit exceeded"));
+        }
+
         // Get info hashes from request
         let info_hash_strs = params.info_hashes.unwrap_or_default();
 
-        // Validate number of torrents
+        // Without info hashes, serve the full scrape if it is enabled
         if info_hash_strs.is_empty() {
-            return Err(ScrapeError::bad_request("No info_hash provided"));
+            if !policy.config().full_scrape_enabled {
+                return Err(ScrapeError::bad_request("No info_hash provided"));
+            }
+
+            return policy
+                .full_scrape()
+                .map(ScrapeBody::Full)
+                .ok_or_else(|| {
+                    ScrapeError::new("Full scrape not available yet", StatusCode::SERVICE_UNAVAILABLE)
+                });
         }
 
+        // Validate number of torrents
         if info_hash_strs.len() > MAX_SCRAPE_TORRENTS {
             return Err(ScrapeError::bad_request(format!(
                 "Too many info_hashes (max: {})",
@@ -132,10 +184,15 @@ impl ScrapeHandler {
             return Err(ScrapeError::bad_request("No valid info_hash provided"));
         }
 
-        // Collect statistics for each torrent
+        // Collect statistics for each torrent. Unapproved and deleted
+        // torrents are left out entirely, as if they were never registered.
         let mut stats_map: Vec<(InfoHash, Option<TorrentStats>)> = Vec::with_capacity(info_hashes.len());
 
         for info_hash in info_hashes {
+            if !policy.visibility().is_visible(&info_hash) {
+                continue;
+            }
+
             let stats = self.get_torrent_stats(&info_hash).await;
             stats_map.push((info_hash, stats));
         }
@@ -145,7 +202,37 @@ impl ScrapeHandler {
 
         debug!("Scrape request processed for {} torrents", stats_map.len());
 
-        Ok(response)
+        Ok(ScrapeBody::Partial(response))
+    }
+
+    /// Authenticates a passkey and returns the user ID
+    ///
+    /// Passkeys of banned, disabled and deleted accounts are rejected.
+    async fn authenticate_passkey(&self, passkey: &str) -> Result<Uuid, ScrapeError> {
+        if passkey.len() != 32 || !passkey.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(ScrapeError::unauthorized("Invalid passkey"));
+        }
+
+        let row: Option<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT id
+            FROM users
+            WHERE passkey = $1
+              AND is_active = true
+              AND is_banned = false
+              AND deleted_at IS NULL
+            "#,
+        )
+        .bind(passkey)
+        .fetch_optional(self.service.db_pool().as_ref())
+        .await
+        .map_err(|e| {
+            warn!("Passkey lookup failed: {}", e);
+            ScrapeError::new("Tracker unavailable", StatusCode::SERVICE_UNAVAILABLE)
+        })?;
+
+        row.map(|(user_id,)| user_id)
+            .ok_or_else(|| ScrapeError::unauthorized("Invalid passkey"))
     }
 
     /// Gets statistics for a specific torrent
@@ -239,18 +326,47 @@ impl ScrapeHandler {
 /// HTTP handler for scrape requests
 ///
 /// Extracts parameters from the query string and delegates to ScrapeHandler.
+/// Full scrapes are sent gzip-encoded when the client accepts it. The client
+/// IP is the connection's peer address, so the router must be served with
+/// connect info.
 pub async fn handle_scrape(
     State(service): State<Arc<TrackerService>>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Query(params): Query<ScrapeRequest>,
 ) -> Result<Response, ScrapeError> {
-    let handler = ScrapeHandler::new(service);
+    let handler = ScrapeHandler::new(service.clone());
 
-    match handler.handle(params).await {
-        Ok(response) => Ok((
+    match handler.handle(params, remote_addr.ip()).await {
+        Ok(ScrapeBody::Partial(response)) => Ok((
             StatusCode::OK,
             [("Content-Type", "text/plain")],
             response,
         ).into_response()),
+        Ok(ScrapeBody::Full(snapshot)) => {
+            let accepts_gzip = headers
+                .get(header::ACCEPT_ENCODING)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.contains("gzip"))
+                .unwrap_or(false);
+
+            if accepts_gzip {
+                Ok((
+                    StatusCode::OK,
+                    [("Content-Type", "text/plain"), ("Content-Encoding", "gzip")],
+                    snapshot.gzipped.clone(),
+                ).into_response())
+            } else {
+                let body = snapshot.decompressed().map_err(|e| {
+                    ScrapeError::new(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+                })?;
+                Ok((
+                    StatusCode::OK,
+                    [("Content-Type", "text/plain")],
+                    body,
+                ).into_response())
+            }
+        }
         Err(e) => {
             warn!("Scrape error: {}", e.message);
             service.statistics().record_failure("scrape", &e.message);
@@ -271,6 +387,12 @@ mod tests {
         assert_eq!(bencode, expected);
     }
 
+    #[test]
+    fn test_scrape_error_status() {
+        assert_eq!(ScrapeError::unauthorized("x").status, StatusCode::UNAUTHORIZED);
+        assert_eq!(ScrapeError::rate_limited("x").status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
     #[test]
     fn test_max_scrape_torrents() {
         assert!(MAX_SCRAPE_TORRENTS > 0);
@@ -0,0 +1,449 @@
+//! Scrape Privacy and Full Scrape
+//!
+//! This module holds the policy applied to scrape requests on a private
+//! deployment:
+//!
+//! - Passkey requirement and rate limiting per user, or per client address
+//!   for anonymous scrapes
+//! - Visibility filtering, so unapproved or deleted torrents never show up
+//! - An optional full scrape, served from a cached, periodically rebuilt,
+//!   gzip-compressed bencoded dump of every visible torrent
+//!
+//! The visibility set and the full scrape are rebuilt in the background, so
+//! the scrape path itself only does in-memory lookups.
+
+use crate::peer::PeerManager;
+use crate::protocol::{BencodeResponse, InfoHash};
+use anyhow::Result;
+use dashmap::DashMap;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use parking_lot::RwLock;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Default interval between visibility refreshes and full scrape rebuilds
+pub const DEFAULT_REBUILD_INTERVAL: Duration = Duration::from_secs(300); // 5 minutes
+
+/// Default number of scrapes allowed per user (or anonymous address) per window
+pub const DEFAULT_SCRAPES_PER_WINDOW: u32 = 30;
+
+/// Default rate limit window
+pub const DEFAULT_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Scrape policy settings
+#[derive(Debug, Clone)]
+pub struct ScrapeConfig {
+    /// Reject scrapes without a valid passkey (private deployments)
+    pub require_passkey: bool,
+
+    /// Serve a full scrape when no info_hash is given
+    pub full_scrape_enabled: bool,
+
+    /// How often the visibility set and full scrape are rebuilt
+    pub rebuild_interval: Duration,
+
+    /// Scrapes allowed per user (or anonymous address) per window
+    pub scrapes_per_window: u32,
+
+    /// Rate limit window length
+    pub rate_window: Duration,
+}
+
+impl Default for ScrapeConfig {
+    fn default() -> Self {
+        Self {
+            require_passkey: true,
+            full_scrape_enabled: false,
+            rebuild_interval: DEFAULT_REBUILD_INTERVAL,
+            scrapes_per_window: DEFAULT_SCRAPES_PER_WINDOW,
+            rate_window: DEFAULT_RATE_WINDOW,
+        }
+    }
+}
+
+/// Set of info hashes that may be reported to users
+///
+/// Only approved, active and non-deleted torrents are visible. Until the first
+/// load completes every torrent is treated as hidden, so a cold start never
+/// leaks statistics for torrents that should not be listed.
+pub struct TorrentVisibility {
+    visible: RwLock<HashSet<InfoHash>>,
+    loaded: AtomicBool,
+}
+
+impl TorrentVisibility {
+    /// Creates an empty visibility set
+    pub fn new() -> Self {
+        Self {
+            visible: RwLock::new(HashSet::new()),
+            loaded: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns true if the torrent may be reported in scrapes
+    #[inline]
+    pub fn is_visible(&self, info_hash: &InfoHash) -> bool {
+        self.loaded.load(Ordering::Acquire) && self.visible.read().contains(info_hash)
+    }
+
+    /// Returns the number of visible torrents
+    pub fn len(&self) -> usize {
+        self.visible.read().len()
+    }
+
+    /// Returns true if no torrent is visible
+    pub fn is_empty(&self) -> bool {
+        self.visible.read().is_empty()
+    }
+
+    /// Returns a copy of the visible info hashes
+    pub fn snapshot(&self) -> Vec<InfoHash> {
+        self.visible.read().iter().copied().collect()
+    }
+
+    /// Replaces the visible set
+    pub fn replace(&self, info_hashes: HashSet<InfoHash>) {
+        *self.visible.write() = info_hashes;
+        self.loaded.store(true, Ordering::Release);
+    }
+
+    /// Reloads the visible set from the database
+    pub async fn refresh(&self, db_pool: &PgPool) -> Result<usize> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT info_hash
+            FROM torrents
+            WHERE moderation_status = 'approved'
+              AND is_active = true
+              AND deleted_at IS NULL
+            "#,
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        let info_hashes: HashSet<InfoHash> = rows
+            .iter()
+            .filter_map(|(hex,)| InfoHash::from_hex(hex.trim()).ok())
+            .collect();
+
+        let count = info_hashes.len();
+        self.replace(info_hashes);
+
+        Ok(count)
+    }
+}
+
+impl Default for TorrentVisibility {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Who a scrape is counted against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ScrapeClient {
+    User(Uuid),
+    Address(IpAddr),
+}
+
+/// Fixed-window scrape rate limiter
+///
+/// Keyed by the user the passkey resolved to, so neither rotating passkeys
+/// nor scraping from several addresses multiplies the budget. Anonymous
+/// scrapes are counted per address.
+pub struct ScrapeRateLimiter {
+    limit: u32,
+    window: Duration,
+    counters: DashMap<ScrapeClient, (Instant, u32)>,
+}
+
+impl ScrapeRateLimiter {
+    /// Creates a limiter allowing `limit` scrapes per `window`
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            counters: DashMap::new(),
+        }
+    }
+
+    /// Counts a scrape and returns false if the caller is over its limit
+    pub fn check(&self, user_id: Option<Uuid>, ip: IpAddr) -> bool {
+        let client = match user_id {
+            Some(user_id) => ScrapeClient::User(user_id),
+            None => ScrapeClient::Address(ip),
+        };
+
+        let mut entry = self
+            .counters
+            .entry(client)
+            .or_insert_with(|| (Instant::now(), 0));
+
+        let (started_at, count) = entry.value_mut();
+
+        if started_at.elapsed() >= self.window {
+            *started_at = Instant::now();
+            *count = 0;
+        }
+
+        if *count >= self.limit {
+            return false;
+        }
+
+        *count += 1;
+        true
+    }
+
+    /// Drops counters whose window has passed
+    pub fn cleanup_expired(&self) -> usize {
+        let before = self.counters.len();
+        self.counters
+            .retain(|_, (started_at, _)| started_at.elapsed() < self.window);
+        before - self.counters.len()
+    }
+}
+
+/// A prebuilt, gzip-compressed full scrape response
+#[derive(Debug)]
+pub struct FullScrapeSnapshot {
+    /// Gzip-compressed bencoded scrape response
+    pub gzipped: Vec<u8>,
+
+    /// Number of torrents in the dump
+    pub torrent_count: usize,
+
+    /// When the dump was built
+    pub built_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl FullScrapeSnapshot {
+    /// Builds a snapshot for the given torrents using live swarm statistics
+    ///
+    /// Torrents without an active swarm are reported with zero counts.
+    pub fn build(info_hashes: &[InfoHash], peer_manager: &PeerManager) -> Result<Self> {
+        // Bencoded dictionaries must have their keys sorted as raw bytes
+        let mut info_hashes = info_hashes.to_vec();
+        info_hashes.sort_unstable_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+
+        let mut response = BencodeResponse::with_capacity(64 + info_hashes.len() * 80);
+        response.start_dict();
+        response.write_key("files");
+        response.start_dict();
+
+        for info_hash in &info_hashes {
+            let (seeders, leechers, completed) =
+                peer_manager.get_stats(info_hash).unwrap_or((0, 0, 0));
+
+            response.write_bytes(info_hash.as_bytes());
+            response.start_dict();
+            response.write_key("complete");
+            response.write_int(seeders as i64);
+            response.write_key("downloaded");
+            response.write_int(completed as i64);
+            response.write_key("incomplete");
+            response.write_int(leechers as i64);
+            response.end_dict();
+        }
+
+        response.end_dict();
+        response.end_dict();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&response.build())?;
+
+        Ok(Self {
+            gzipped: encoder.finish()?,
+            torrent_count: info_hashes.len(),
+            built_at: chrono::Utc::now(),
+        })
+    }
+
+    /// Returns the uncompressed body for clients that do not accept gzip
+    pub fn decompressed(&self) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        GzDecoder::new(self.gzipped.as_slice()).read_to_end(&mut body)?;
+        Ok(body)
+    }
+}
+
+/// Scrape policy shared by all scrape handlers
+pub struct ScrapePolicy {
+    config: ScrapeConfig,
+    visibility: TorrentVisibility,
+    rate_limiter: ScrapeRateLimiter,
+    full_scrape: RwLock<Option<Arc<FullScrapeSnapshot>>>,
+}
+
+impl ScrapePolicy {
+    /// Creates a new scrape policy
+    pub fn new(config: ScrapeConfig) -> Self {
+        Self {
+            rate_limiter: ScrapeRateLimiter::new(config.scrapes_per_window, config.rate_window),
+            config,
+            visibility: TorrentVisibility::new(),
+            full_scrape: RwLock::new(None),
+        }
+    }
+
+    /// Returns the policy settings
+    #[inline]
+    pub fn config(&self) -> &ScrapeConfig {
+        &self.config
+    }
+
+    /// Returns the visibility set
+    #[inline]
+    pub fn visibility(&self) -> &TorrentVisibility {
+        &self.visibility
+    }
+
+    /// Counts a scrape against the caller's rate limit
+    #[inline]
+    pub fn check_rate_limit(&self, user_id: Option<Uuid>, ip: IpAddr) -> bool {
+        self.rate_limiter.check(user_id, ip)
+    }
+
+    /// Returns the latest full scrape, if one has been built
+    pub fn full_scrape(&self) -> Option<Arc<FullScrapeSnapshot>> {
+        self.full_scrape.read().clone()
+    }
+
+    /// Refreshes the visibility set and rebuilds the full scrape
+    pub async fn rebuild(&self, db_pool: &PgPool, peer_manager: &PeerManager) -> Result<()> {
+        let visible = self.visibility.refresh(db_pool).await?;
+
+        if self.config.full_scrape_enabled {
+            let snapshot = FullScrapeSnapshot::build(&self.visibility.snapshot(), peer_manager)?;
+            info!(
+                "Rebuilt full scrape: {} torrents, {} bytes gzipped",
+                snapshot.torrent_count,
+                snapshot.gzipped.len()
+            );
+            *self.full_scrape.write() = Some(Arc::new(snapshot));
+        }
+
+        self.rate_limiter.cleanup_expired();
+
+        info!("Scrape visibility refreshed: {} visible torrents", visible);
+        Ok(())
+    }
+
+    /// Runs the periodic rebuild loop
+    ///
+    /// This should be spawned as a background task.
+    pub async fn run(self: Arc<Self>, db_pool: Arc<PgPool>, peer_manager: Arc<PeerManager>) {
+        let mut interval = time::interval(self.config.rebuild_interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.rebuild(&db_pool, &peer_manager).await {
+                error!("Failed to rebuild scrape policy: {}", e);
+                // Keep serving the previous snapshot
+            }
+        }
+    }
+}
+
+impl Default for ScrapePolicy {
+    fn default() -> Self {
+        Self::new(ScrapeConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer::Peer;
+    use crate::protocol::PeerId;
+
+    #[test]
+    fn test_visibility_fails_closed_until_loaded() {
+        let visibility = TorrentVisibility::new();
+        let info_hash = InfoHash::new([1u8; 20]);
+        assert!(!visibility.is_visible(&info_hash));
+
+        visibility.replace([info_hash].into_iter().collect());
+        assert!(visibility.is_visible(&info_hash));
+        assert!(!visibility.is_visible(&InfoHash::new([2u8; 20])));
+    }
+
+    #[test]
+    fn test_rate_limiter_per_user() {
+        let limiter = ScrapeRateLimiter::new(2, Duration::from_secs(60));
+        let user = Some(Uuid::new_v4());
+        let ip: IpAddr = "203.0.114.1".parse().unwrap();
+
+        assert!(limiter.check(user, ip));
+        assert!(limiter.check(user, ip));
+        assert!(!limiter.check(user, ip));
+
+        // Switching address does not reset a user's budget
+        assert!(!limiter.check(user, "203.0.114.2".parse().unwrap()));
+
+        // Other users and anonymous scrapes have their own budget
+        assert!(limiter.check(Some(Uuid::new_v4()), ip));
+        assert!(limiter.check(None, ip));
+    }
+
+    #[test]
+    fn test_rate_limiter_anonymous_per_address() {
+        let limiter = ScrapeRateLimiter::new(1, Duration::from_secs(60));
+        let ip: IpAddr = "203.0.114.1".parse().unwrap();
+
+        assert!(limiter.check(None, ip));
+        assert!(!limiter.check(None, ip));
+        assert!(limiter.check(None, "203.0.114.2".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_rate_limiter_window_reset() {
+        let limiter = ScrapeRateLimiter::new(1, Duration::from_millis(1));
+        let ip: IpAddr = "203.0.114.1".parse().unwrap();
+
+        assert!(limiter.check(None, ip));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(limiter.check(None, ip));
+    }
+
+    #[test]
+    fn test_full_scrape_snapshot() {
+        let manager = PeerManager::new();
+        let active = InfoHash::new([2u8; 20]);
+        let idle = InfoHash::new([1u8; 20]);
+
+        manager.upsert_peer(
+            active,
+            Peer::new(
+                PeerId::new([3u8; 20]),
+                None,
+                "192.168.1.1".parse().unwrap(),
+                6881,
+                0,
+                0,
+                0,
+            ),
+        );
+
+        let snapshot = FullScrapeSnapshot::build(&[active, idle], &manager).unwrap();
+        assert_eq!(snapshot.torrent_count, 2);
+
+        let body = snapshot.decompressed().unwrap();
+        let mut expected = b"d5:filesd20:".to_vec();
+        expected.extend_from_slice(idle.as_bytes());
+        expected.extend_from_slice(b"d8:completei0e10:downloadedi0e10:incompletei0ee20:");
+        expected.extend_from_slice(active.as_bytes());
+        expected.extend_from_slice(b"d8:completei1e10:downloadedi0e10:incompletei0eeee");
+        assert_eq!(body, expected);
+    }
+}
@@ -9,8 +9,7 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use prometheus::{
-    Counter, CounterVec, Encoder, Histogram, HistogramOpts, HistogramVec, IntGauge, IntGaugeVec,
-    Opts, Registry, TextEncoder,
+    Counter, CounterVec, Encoder, Histogram, HistogramOpts, IntGauge, Opts, Registry, TextEncoder,
 };
 use std::sync::Arc;
 use std::time::Duration;
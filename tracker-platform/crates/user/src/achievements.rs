@@ -514,6 +514,73 @@ impl AchievementService {
         Ok(awarded)
     }
 
+    /// Check and award download achievements
+    ///
+    /// Progress is the number of torrents the user has snatched according to
+    /// the tracker, so fetching .torrent files does not count.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user ID
+    pub async fn check_snatch_achievements(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<UserAchievement>, AchievementError> {
+        let snatch_count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*)::INTEGER as "count!"
+            FROM snatched
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        // Get download achievements
+        let achievements = sqlx::query_as!(
+            Achievement,
+            r#"
+            SELECT
+                id,
+                name,
+                description,
+                category as "category: AchievementCategory",
+                rarity as "rarity: Rarity",
+                icon,
+                hidden,
+                points,
+                target_value,
+                active,
+                created_at,
+                updated_at
+            FROM achievements
+            WHERE category = 'download' AND active = true
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut awarded = Vec::new();
+
+        for achievement in achievements {
+            if let Some(target) = achievement.target_value {
+                if snatch_count >= target {
+                    if let Ok(user_achievement) =
+                        self.award_achievement(user_id, achievement.id).await
+                    {
+                        awarded.push(user_achievement);
+                    }
+                } else {
+                    self.update_progress(user_id, achievement.id, snatch_count)
+                        .await?;
+                }
+            }
+        }
+
+        Ok(awarded)
+    }
+
     /// Check and award ratio achievements
     ///
     /// # Arguments
@@ -15,7 +15,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -169,6 +169,43 @@ impl BonusRule {
     }
 }
 
+/// Calculate the bonus earned per hour of seeding a torrent
+///
+/// Appended amounts from every matching rule are summed, then multiplied by
+/// the product of all matching multiplier rules.
+pub fn hourly_bonus(
+    rules: &[BonusRule],
+    torrent_age_hours: i32,
+    torrent_size: i64,
+    seeders: i32,
+    leechers: i32,
+    is_personal_release: bool,
+) -> f64 {
+    let mut bonus = 0.0;
+    let mut multiplier = 1.0;
+
+    for rule in rules {
+        if rule.applies_to(
+            torrent_age_hours,
+            torrent_size,
+            seeders,
+            leechers,
+            is_personal_release,
+        ) {
+            match rule.operation {
+                BonusOperation::Append => {
+                    bonus += rule.bonus_amount;
+                }
+                BonusOperation::Multiply => {
+                    multiplier *= rule.bonus_amount;
+                }
+            }
+        }
+    }
+
+    bonus * multiplier
+}
+
 /// Bonus transaction type
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -185,6 +222,20 @@ pub enum BonusTransactionType {
     TipReceived,
     /// Manual adjustment by admin
     ManualAdjustment,
+    /// Bounty put on a reseed request
+    ReseedBounty,
+    /// Bounty received for reseeding a dead torrent
+    ReseedReward,
+    /// Reseed bounty returned to the requester
+    ReseedRefund,
+    /// Bounty put on a torrent request
+    RequestBounty,
+    /// Bounty received for filling a torrent request
+    RequestReward,
+    /// Request bounty returned to a contributor
+    RequestRefund,
+    /// Request reward taken back after a fill was rejected
+    RequestClawback,
 }
 
 /// Bonus transaction record
@@ -304,33 +355,16 @@ impl BonusService {
         // Get applicable rules
         let rules = self.get_active_rules().await?;
 
-        // Calculate bonus using rules
-        let mut bonus = 0.0;
-        let mut multiplier = 1.0;
-
-        for rule in rules {
-            if rule.applies_to(
-                torrent_age_hours,
-                torrent.size,
-                torrent.seeders as i32,
-                torrent.leechers as i32,
-                is_personal_release,
-            ) {
-                match rule.operation {
-                    BonusOperation::Append => {
-                        bonus += rule.bonus_amount;
-                    }
-                    BonusOperation::Multiply => {
-                        multiplier *= rule.bonus_amount;
-                    }
-                }
-            }
-        }
-
-        // Apply multiplier and duration
-        bonus *= multiplier * duration_hours;
+        let hourly = hourly_bonus(
+            &rules,
+            torrent_age_hours,
+            torrent.size,
+            torrent.seeders as i32,
+            torrent.leechers as i32,
+            is_personal_release,
+        );
 
-        Ok(bonus)
+        Ok(hourly * duration_hours)
     }
 
     /// Calculate total bonus for all user's active seeds
@@ -385,13 +419,40 @@ impl BonusService {
         transaction_type: BonusTransactionType,
         torrent_id: Option<Uuid>,
         description: String,
+    ) -> Result<BonusTransaction, BonusError> {
+        let mut tx = self.db.begin().await?;
+        let transaction = self
+            .award_bonus_in_tx(
+                &mut tx,
+                user_id,
+                amount,
+                transaction_type,
+                torrent_id,
+                description,
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(transaction)
+    }
+
+    /// Award bonus to a user inside the caller's transaction
+    ///
+    /// Lets callers commit the award together with the change that earned
+    /// it. Arguments are as for [`BonusService::award_bonus`].
+    pub async fn award_bonus_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        amount: f64,
+        transaction_type: BonusTransactionType,
+        torrent_id: Option<Uuid>,
+        description: String,
     ) -> Result<BonusTransaction, BonusError> {
         if amount <= 0.0 {
             return Err(BonusError::InvalidAmount(amount));
         }
 
-        let mut tx = self.db.begin().await?;
-
         // Update user's bonus balance
         let new_balance = sqlx::query_scalar!(
             r#"
@@ -403,7 +464,7 @@ impl BonusService {
             user_id,
             amount
         )
-        .fetch_one(&mut *tx)
+        .fetch_one(&mut **tx)
         .await?;
 
         // Record transaction
@@ -432,11 +493,9 @@ impl BonusService {
             torrent_id,
             description
         )
-        .fetch_one(&mut *tx)
+        .fetch_one(&mut **tx)
         .await?;
 
-        tx.commit().await?;
-
         Ok(transaction)
     }
 
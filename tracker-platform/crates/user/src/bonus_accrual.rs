@@ -0,0 +1,477 @@
+//! Periodic seeding bonus accrual
+//!
+//! Credits bonus points to users for the torrents they are currently seeding.
+//! The caller supplies a snapshot of the tracker's live swarms (seeder and
+//! leecher counts plus the distinct users seeding each torrent); the job
+//! evaluates the active bonus rules against each torrent's age and size and
+//! the live counts, and awards one transaction per user per period.
+//!
+//! Periods are aligned to the accrual interval. Each payout is claimed in
+//! `bonus_accruals` in the same transaction that awards it, so re-running a
+//! period never credits a user twice and a failed award leaves no claim
+//! behind. A user seeding a torrent from several clients is only counted
+//! once, since the snapshot lists distinct users.
+//!
+//! Payouts are rounded to hundredths of a point, the precision balances are
+//! kept at, and recorded in `bonus_accruals` as whole hundredths.
+
+use crate::bonus::{hourly_bonus, BonusError, BonusRule, BonusService, BonusTransactionType};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Default accrual interval
+pub const DEFAULT_ACCRUAL_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Live seeding state of one torrent, taken from the tracker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeedingTorrent {
+    /// Hex-encoded info hash
+    pub info_hash: String,
+
+    /// Live seeder count
+    pub seeders: i32,
+
+    /// Live leecher count
+    pub leechers: i32,
+
+    /// Distinct users currently seeding
+    pub seeding_users: Vec<Uuid>,
+}
+
+/// Torrent metadata needed by the bonus rules
+#[derive(Debug, Clone)]
+pub struct AccrualTorrent {
+    pub id: Uuid,
+    pub info_hash: String,
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+    pub uploader_id: Uuid,
+}
+
+/// Bonus owed to one user for a period
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserPayout {
+    /// User ID
+    pub user_id: Uuid,
+
+    /// Points to award, rounded to hundredths
+    pub amount: f64,
+
+    /// Torrents that earned points
+    pub torrents_seeded: i32,
+}
+
+impl UserPayout {
+    /// Amount in whole hundredths of a point
+    pub fn hundredths(&self) -> i64 {
+        (self.amount * 100.0).round() as i64
+    }
+}
+
+/// Outcome of an accrual run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccrualReport {
+    /// Period this run credited
+    pub period_start: DateTime<Utc>,
+
+    /// Whether this was a preview only
+    pub dry_run: bool,
+
+    /// Payouts that were (or would be) awarded
+    pub payouts: Vec<UserPayout>,
+
+    /// Sum of all payouts
+    pub total_amount: f64,
+
+    /// Users skipped because they were already credited for the period
+    pub already_credited: usize,
+}
+
+/// Seeding bonus accrual job
+pub struct BonusAccrualJob {
+    db: PgPool,
+    bonus: BonusService,
+    interval: Duration,
+}
+
+impl BonusAccrualJob {
+    /// Create a new accrual job with the default interval
+    pub fn new(db: PgPool) -> Self {
+        Self::with_interval(db, DEFAULT_ACCRUAL_INTERVAL)
+    }
+
+    /// Create a new accrual job with a custom interval
+    pub fn with_interval(db: PgPool, interval: Duration) -> Self {
+        Self {
+            bonus: BonusService::new(db.clone()),
+            db,
+            interval,
+        }
+    }
+
+    /// Accrual interval
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Start of the period containing `now`
+    pub fn period_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let secs = self.interval.as_secs().max(1) as i64;
+        let start = now.timestamp() - now.timestamp().rem_euclid(secs);
+        Utc.timestamp_opt(start, 0).single().unwrap_or(now)
+    }
+
+    /// Run the accrual for the period containing `now`
+    ///
+    /// With `dry_run` set, computes the payouts and reports which users were
+    /// already credited, but writes nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `swarms` - Live seeding snapshot from the tracker
+    /// * `now` - Current time, used to pick the period
+    /// * `dry_run` - Preview only
+    pub async fn run(
+        &self,
+        swarms: &[SeedingTorrent],
+        now: DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<AccrualReport, BonusError> {
+        let period_start = self.period_start(now);
+        let rules = self.bonus.get_active_rules().await?;
+        let torrents = self.load_torrents(swarms).await?;
+
+        let hours = self.interval.as_secs_f64() / 3600.0;
+        let planned = plan_payouts(&rules, &torrents, swarms, now, hours);
+
+        let mut payouts = Vec::with_capacity(planned.len());
+        let mut already_credited = 0;
+
+        for payout in planned {
+            if dry_run {
+                if self.is_credited(period_start, payout.user_id).await? {
+                    already_credited += 1;
+                } else {
+                    payouts.push(payout);
+                }
+                continue;
+            }
+
+            // Dropping the transaction rolls back the claim, so a failed
+            // award is retried on the next run
+            let mut tx = self.db.begin().await?;
+            if !Self::claim(&mut tx, period_start, &payout).await? {
+                already_credited += 1;
+                continue;
+            }
+
+            if let Err(e) = self.award(&mut tx, period_start, &payout).await {
+                warn!("Failed to award seeding bonus to {}: {}", payout.user_id, e);
+                continue;
+            }
+            tx.commit().await?;
+
+            payouts.push(payout);
+        }
+
+        let total_amount = payouts.iter().map(|p| p.amount).sum();
+
+        info!(
+            "Seeding bonus for period {}{}: {} users, {:.2} points, {} already credited",
+            period_start,
+            if dry_run { " (dry run)" } else { "" },
+            payouts.len(),
+            total_amount,
+            already_credited
+        );
+
+        Ok(AccrualReport {
+            period_start,
+            dry_run,
+            payouts,
+            total_amount,
+            already_credited,
+        })
+    }
+
+    /// Load metadata for the torrents in the snapshot
+    async fn load_torrents(
+        &self,
+        swarms: &[SeedingTorrent],
+    ) -> Result<HashMap<String, AccrualTorrent>, BonusError> {
+        let hashes: Vec<String> = swarms.iter().map(|s| s.info_hash.clone()).collect();
+
+        let torrents = sqlx::query_as!(
+            AccrualTorrent,
+            r#"
+            SELECT id, info_hash, size, created_at, uploader_id
+            FROM torrents
+            WHERE info_hash = ANY($1)
+            "#,
+            &hashes
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(torrents
+            .into_iter()
+            .map(|t| (t.info_hash.clone(), t))
+            .collect())
+    }
+
+    async fn is_credited(
+        &self,
+        period_start: DateTime<Utc>,
+        user_id: Uuid,
+    ) -> Result<bool, BonusError> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM bonus_accruals WHERE period_start = $1 AND user_id = $2
+            ) as "exists!"
+            "#,
+            period_start,
+            user_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(exists)
+    }
+
+    /// Claim a user's payout for a period; false if it was already claimed
+    async fn claim(
+        tx: &mut Transaction<'_, Postgres>,
+        period_start: DateTime<Utc>,
+        payout: &UserPayout,
+    ) -> Result<bool, BonusError> {
+        let claimed = sqlx::query_scalar!(
+            r#"
+            INSERT INTO bonus_accruals (period_start, user_id, amount_hundredths, torrents_seeded)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (period_start, user_id) DO NOTHING
+            RETURNING user_id
+            "#,
+            period_start,
+            payout.user_id,
+            payout.hundredths(),
+            payout.torrents_seeded
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(claimed.is_some())
+    }
+
+    async fn award(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        period_start: DateTime<Utc>,
+        payout: &UserPayout,
+    ) -> Result<(), BonusError> {
+        let transaction = self
+            .bonus
+            .award_bonus_in_tx(
+                tx,
+                payout.user_id,
+                payout.amount,
+                BonusTransactionType::Earned,
+                None,
+                format!(
+                    "Seeding bonus for {} torrent(s), period starting {}",
+                    payout.torrents_seeded, period_start
+                ),
+            )
+            .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE bonus_accruals
+            SET transaction_id = $3
+            WHERE period_start = $1 AND user_id = $2
+            "#,
+            period_start,
+            payout.user_id,
+            transaction.id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Compute each user's payout for one period
+///
+/// Torrents missing from `torrents` (unknown to the site) earn nothing, and
+/// users whose total rounds to zero are left out.
+pub fn plan_payouts(
+    rules: &[BonusRule],
+    torrents: &HashMap<String, AccrualTorrent>,
+    swarms: &[SeedingTorrent],
+    now: DateTime<Utc>,
+    hours: f64,
+) -> Vec<UserPayout> {
+    let mut totals: BTreeMap<Uuid, (f64, i32)> = BTreeMap::new();
+
+    for swarm in swarms {
+        let Some(torrent) = torrents.get(&swarm.info_hash) else {
+            continue;
+        };
+
+        let age_hours = (now - torrent.created_at).num_hours() as i32;
+
+        for user_id in &swarm.seeding_users {
+            let amount = hourly_bonus(
+                rules,
+                age_hours,
+                torrent.size,
+                swarm.seeders,
+                swarm.leechers,
+                torrent.uploader_id == *user_id,
+            ) * hours;
+
+            if amount > 0.0 {
+                let entry = totals.entry(*user_id).or_insert((0.0, 0));
+                entry.0 += amount;
+                entry.1 += 1;
+            }
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(user_id, (amount, torrents_seeded))| UserPayout {
+            user_id,
+            amount: (amount * 100.0).round() / 100.0,
+            torrents_seeded,
+        })
+        .filter(|payout| payout.hundredths() > 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bonus::BonusOperation;
+
+    fn rule(amount: f64, operation: BonusOperation, personal_release: bool) -> BonusRule {
+        BonusRule {
+            id: Uuid::new_v4(),
+            name: "Test Rule".to_string(),
+            description: "Test".to_string(),
+            active: true,
+            min_torrent_age_hours: None,
+            max_torrent_age_hours: None,
+            min_torrent_size: None,
+            max_torrent_size: None,
+            min_seeders: None,
+            max_seeders: Some(10),
+            min_leechers: None,
+            personal_release,
+            bonus_amount: amount,
+            operation,
+            priority: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn torrent(info_hash: &str, uploader_id: Uuid) -> AccrualTorrent {
+        AccrualTorrent {
+            id: Uuid::new_v4(),
+            info_hash: info_hash.to_string(),
+            size: 1024 * 1024 * 1024,
+            created_at: Utc::now() - chrono::Duration::days(1),
+            uploader_id,
+        }
+    }
+
+    #[test]
+    fn test_plan_payouts() {
+        let uploader = Uuid::new_v4();
+        let seeder = Uuid::new_v4();
+        let rules = vec![
+            rule(1.0, BonusOperation::Append, false),
+            rule(2.0, BonusOperation::Multiply, true),
+        ];
+
+        let torrents: HashMap<_, _> = [
+            ("aa".to_string(), torrent("aa", uploader)),
+            ("bb".to_string(), torrent("bb", uploader)),
+        ]
+        .into_iter()
+        .collect();
+
+        let swarms = vec![
+            SeedingTorrent {
+                info_hash: "aa".to_string(),
+                seeders: 2,
+                leechers: 1,
+                seeding_users: vec![uploader, seeder],
+            },
+            // Too many seeders for the rules
+            SeedingTorrent {
+                info_hash: "bb".to_string(),
+                seeders: 50,
+                leechers: 0,
+                seeding_users: vec![seeder],
+            },
+            // Not a site torrent
+            SeedingTorrent {
+                info_hash: "cc".to_string(),
+                seeders: 1,
+                leechers: 0,
+                seeding_users: vec![seeder],
+            },
+        ];
+
+        let payouts = plan_payouts(&rules, &torrents, &swarms, Utc::now(), 0.5);
+        assert_eq!(payouts.len(), 2);
+
+        let by_user: HashMap<_, _> = payouts.iter().map(|p| (p.user_id, p)).collect();
+        assert_eq!(by_user[&uploader].amount, 1.0);
+        assert_eq!(by_user[&seeder].amount, 0.5);
+        assert_eq!(by_user[&seeder].torrents_seeded, 1);
+        assert_eq!(by_user[&seeder].hundredths(), 50);
+    }
+
+    #[test]
+    fn test_plan_payouts_rounds_to_hundredths() {
+        let user = Uuid::new_v4();
+        let rules = vec![rule(1.0, BonusOperation::Append, false)];
+        let torrents: HashMap<_, _> = [("aa".to_string(), torrent("aa", Uuid::new_v4()))]
+            .into_iter()
+            .collect();
+        let swarms = vec![SeedingTorrent {
+            info_hash: "aa".to_string(),
+            seeders: 1,
+            leechers: 0,
+            seeding_users: vec![user],
+        }];
+
+        // A third of a point is paid as 0.33
+        let payouts = plan_payouts(&rules, &torrents, &swarms, Utc::now(), 1.0 / 3.0);
+        assert_eq!(payouts[0].amount, 0.33);
+        assert_eq!(payouts[0].hundredths(), 33);
+
+        // Less than half a hundredth is not paid at all
+        assert!(plan_payouts(&rules, &torrents, &swarms, Utc::now(), 0.001).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_period_start_is_aligned() {
+        let pool = PgPool::connect_lazy("postgres://localhost/test").unwrap();
+        let job = BonusAccrualJob::with_interval(pool, Duration::from_secs(3600));
+
+        let now = Utc.with_ymd_and_hms(2025, 1, 5, 10, 42, 17).unwrap();
+        let start = job.period_start(now);
+        assert_eq!(start, Utc.with_ymd_and_hms(2025, 1, 5, 10, 0, 0).unwrap());
+        assert_eq!(job.period_start(start), start);
+    }
+}
@@ -0,0 +1,987 @@
+//! Automatic user class promotion and demotion
+//!
+//! The classes users can be promoted into are listed in
+//! `user_class_requirements`, each with the thresholds a member must meet:
+//! uploaded amount, ratio, account age, upload count, achievement points
+//! and active warnings. Ordered by class level they form a ladder, and each
+//! run moves a user on the ladder to the highest class whose requirements
+//! they meet.
+//!
+//! Promotions apply at once. A user who falls below their class is sent a
+//! PM and has the class's grace period to recover before being demoted;
+//! recovering in time clears the notice. Users in classes off the ladder
+//! (staff, VIPs, banned) and users whose class staff pinned are never
+//! moved. Every change is explained to the user in a PM from the system
+//! account and logged in `user_class_changes`.
+
+use crate::statistics::UserStatistics;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Class promotion errors
+#[derive(Debug, Error)]
+pub enum ClassPromotionError {
+    #[error("User not found: {0}")]
+    UserNotFound(Uuid),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Thresholds for membership of one class on the ladder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassRequirement {
+    /// Class (user group) ID
+    pub group_id: i32,
+
+    /// Class display name
+    pub class_name: String,
+
+    /// Class level; higher is more senior
+    pub level: i32,
+
+    /// Minimum uploaded bytes
+    pub min_uploaded: i64,
+
+    /// Minimum ratio
+    pub min_ratio: f64,
+
+    /// Minimum account age in days
+    pub min_account_age_days: i32,
+
+    /// Minimum number of uploads
+    pub min_uploads: i32,
+
+    /// Minimum achievement points
+    pub min_achievement_points: i32,
+
+    /// Most active warnings a member may have
+    pub max_active_warnings: i32,
+
+    /// Days a member below the thresholds has before being demoted
+    pub demotion_grace_days: i32,
+}
+
+impl ClassRequirement {
+    /// Requirements the user does not meet, in words; empty if all are met
+    pub fn unmet(&self, standing: &UserStanding, now: DateTime<Utc>) -> Vec<String> {
+        let stats = &standing.statistics;
+        let mut unmet = Vec::new();
+
+        if stats.uploaded < self.min_uploaded {
+            unmet.push(format!(
+                "upload at least {} (you have {})",
+                format_bytes(self.min_uploaded),
+                format_bytes(stats.uploaded)
+            ));
+        }
+
+        let ratio = UserStatistics::calculate_ratio(stats.uploaded, stats.downloaded);
+        if ratio < self.min_ratio {
+            unmet.push(format!(
+                "keep a ratio of at least {:.2} (yours is {:.2})",
+                self.min_ratio, ratio
+            ));
+        }
+
+        let age_days = (now - standing.joined_at).num_days();
+        if age_days < self.min_account_age_days as i64 {
+            unmet.push(format!(
+                "have an account at least {} days old (yours is {} days)",
+                self.min_account_age_days, age_days
+            ));
+        }
+
+        if stats.upload_count < self.min_uploads {
+            unmet.push(format!(
+                "upload at least {} torrents (you have {})",
+                self.min_uploads, stats.upload_count
+            ));
+        }
+
+        if standing.achievement_points < self.min_achievement_points {
+            unmet.push(format!(
+                "earn at least {} achievement points (you have {})",
+                self.min_achievement_points, standing.achievement_points
+            ));
+        }
+
+        if standing.active_warnings > self.max_active_warnings {
+            unmet.push(if self.max_active_warnings == 0 {
+                "have no active warnings".to_string()
+            } else {
+                format!(
+                    "have at most {} active warnings (you have {})",
+                    self.max_active_warnings, standing.active_warnings
+                )
+            });
+        }
+
+        unmet
+    }
+
+    /// Whether the user meets every requirement
+    pub fn is_met(&self, standing: &UserStanding, now: DateTime<Utc>) -> bool {
+        self.unmet(standing, now).is_empty()
+    }
+}
+
+/// Everything the rules look at for one user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserStanding {
+    /// User ID
+    pub user_id: Uuid,
+
+    /// Username
+    pub username: String,
+
+    /// Current class (user group) ID
+    pub group_id: i32,
+
+    /// Account creation timestamp
+    pub joined_at: DateTime<Utc>,
+
+    /// Transfer and upload statistics
+    pub statistics: UserStatistics,
+
+    /// Total points of awarded achievements
+    pub achievement_points: i32,
+
+    /// Warnings that are active, unrevoked and unexpired
+    pub active_warnings: i32,
+}
+
+/// A pending demotion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DemotionNotice {
+    /// User ID
+    pub user_id: Uuid,
+
+    /// Class the user was in when warned
+    pub from_group_id: i32,
+
+    /// When the user was warned
+    pub notified_at: DateTime<Utc>,
+
+    /// When the user will be demoted unless they recover
+    pub demote_after: DateTime<Utc>,
+}
+
+/// Classes users move between, lowest level first
+#[derive(Debug, Clone, Default)]
+pub struct ClassLadder {
+    steps: Vec<ClassRequirement>,
+}
+
+impl ClassLadder {
+    /// Build a ladder from the requirements of its classes
+    pub fn new(mut steps: Vec<ClassRequirement>) -> Self {
+        steps.sort_by_key(|step| step.level);
+        Self { steps }
+    }
+
+    /// Classes on the ladder, lowest level first
+    pub fn steps(&self) -> &[ClassRequirement] {
+        &self.steps
+    }
+
+    /// The ladder step for a class, if it is on the ladder
+    pub fn step(&self, group_id: i32) -> Option<&ClassRequirement> {
+        self.steps.iter().find(|step| step.group_id == group_id)
+    }
+
+    /// The class the user qualifies for
+    ///
+    /// This is the highest class whose requirements they meet, or the
+    /// bottom of the ladder if they meet none. `None` if the user's class is
+    /// not on the ladder.
+    pub fn target(&self, standing: &UserStanding, now: DateTime<Utc>) -> Option<&ClassRequirement> {
+        self.step(standing.group_id)?;
+
+        self.steps
+            .iter()
+            .rev()
+            .find(|step| step.is_met(standing, now))
+            .or_else(|| self.steps.first())
+    }
+}
+
+/// What the job does for one user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum ClassAction {
+    /// Move up to a higher class
+    Promote { to_group_id: i32 },
+    /// Below the class's requirements; warn and start the grace period
+    Warn {
+        to_group_id: i32,
+        demote_after: DateTime<Utc>,
+    },
+    /// Grace period over; move down
+    Demote { to_group_id: i32 },
+    /// Back within the requirements while a demotion was pending
+    Recovered,
+}
+
+/// Decide what to do for a user
+///
+/// Returns `None` if nothing changes, including while a demotion is still
+/// within its grace period. A notice given for a class the user has since
+/// left is ignored.
+pub fn decide(
+    ladder: &ClassLadder,
+    standing: &UserStanding,
+    notice: Option<&DemotionNotice>,
+    now: DateTime<Utc>,
+) -> Option<ClassAction> {
+    let notice = notice.filter(|notice| notice.from_group_id == standing.group_id);
+    let current = ladder.step(standing.group_id)?;
+    let target = ladder.target(standing, now)?;
+
+    if target.level > current.level {
+        return Some(ClassAction::Promote {
+            to_group_id: target.group_id,
+        });
+    }
+
+    if target.level == current.level {
+        return notice.map(|_| ClassAction::Recovered);
+    }
+
+    match notice {
+        None if current.demotion_grace_days == 0 => Some(ClassAction::Demote {
+            to_group_id: target.group_id,
+        }),
+        None => Some(ClassAction::Warn {
+            to_group_id: target.group_id,
+            demote_after: now + Duration::days(current.demotion_grace_days as i64),
+        }),
+        Some(notice) if now >= notice.demote_after => Some(ClassAction::Demote {
+            to_group_id: target.group_id,
+        }),
+        Some(_) => None,
+    }
+}
+
+/// A change for one user in a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassChange {
+    /// User ID
+    pub user_id: Uuid,
+
+    /// Username
+    pub username: String,
+
+    /// Class before the run
+    pub from_group_id: i32,
+
+    /// What was (or would be) done
+    pub action: ClassAction,
+
+    /// Requirements of the user's current class they no longer meet
+    pub unmet: Vec<String>,
+}
+
+/// Outcome of a promotion run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassPromotionReport {
+    /// When the run happened
+    pub run_at: DateTime<Utc>,
+
+    /// Whether this was a preview only
+    pub dry_run: bool,
+
+    /// Users on the ladder that were evaluated
+    pub evaluated: usize,
+
+    /// Users on the ladder skipped because staff pinned their class
+    pub pinned: i64,
+
+    /// Changes that were (or would be) made
+    pub changes: Vec<ClassChange>,
+}
+
+/// Subject and body of the PM explaining an action
+pub fn class_change_message(
+    action: &ClassAction,
+    from: &ClassRequirement,
+    to: Option<&ClassRequirement>,
+    unmet: &[String],
+) -> (String, String) {
+    let to_name = to.map(|c| c.class_name.as_str()).unwrap_or("a lower class");
+    let requirements = unmet
+        .iter()
+        .map(|r| format!("- {}", r))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    match action {
+        ClassAction::Promote { .. } => (
+            format!("You have been promoted to {}", to_name),
+            format!(
+                "Congratulations! You now meet the requirements for {} and have been \
+                 promoted from {}.\n\nKeep it up: if you fall below the requirements \
+                 you may be demoted again.",
+                to_name, from.class_name
+            ),
+        ),
+        ClassAction::Warn { demote_after, .. } => (
+            format!("Your {} class is at risk", from.class_name),
+            format!(
+                "You no longer meet the requirements for {}. To keep it you need to:\n\n\
+                 {}\n\nIf you still fall short on {}, you will be moved to {}.",
+                from.class_name,
+                requirements,
+                demote_after.format("%Y-%m-%d %H:%M UTC"),
+                to_name
+            ),
+        ),
+        ClassAction::Demote { .. } => (
+            format!("You have been moved to {}", to_name),
+            format!(
+                "Your grace period ended and you still don't meet the requirements for \
+                 {}, so you have been moved to {}. To get it back you need to:\n\n{}\n\n\
+                 You will be promoted automatically once you do.",
+                from.class_name, to_name, requirements
+            ),
+        ),
+        ClassAction::Recovered => (
+            format!("Your {} class is safe", from.class_name),
+            format!(
+                "You meet the requirements for {} again, so the pending demotion has \
+                 been cancelled.",
+                from.class_name
+            ),
+        ),
+    }
+}
+
+/// Row for a user's standing
+struct StandingRow {
+    user_id: Uuid,
+    username: String,
+    group_id: i32,
+    joined_at: DateTime<Utc>,
+    uploaded: i64,
+    downloaded: i64,
+    ratio: f64,
+    seedbonus: f64,
+    active_seeding: i32,
+    active_leeching: i32,
+    snatched_count: i32,
+    upload_count: i32,
+    avg_seed_time: i64,
+    total_peer_time: i64,
+    last_seen: Option<DateTime<Utc>>,
+    stats_created_at: DateTime<Utc>,
+    stats_updated_at: DateTime<Utc>,
+    achievement_points: i32,
+    active_warnings: i32,
+}
+
+impl From<StandingRow> for UserStanding {
+    fn from(row: StandingRow) -> Self {
+        Self {
+            user_id: row.user_id,
+            username: row.username,
+            group_id: row.group_id,
+            joined_at: row.joined_at,
+            statistics: UserStatistics {
+                user_id: row.user_id,
+                uploaded: row.uploaded,
+                downloaded: row.downloaded,
+                ratio: row.ratio,
+                seedbonus: row.seedbonus,
+                active_seeding: row.active_seeding,
+                active_leeching: row.active_leeching,
+                snatched_count: row.snatched_count,
+                upload_count: row.upload_count,
+                avg_seed_time: row.avg_seed_time,
+                total_peer_time: row.total_peer_time,
+                last_seen: row.last_seen,
+                created_at: row.stats_created_at,
+                updated_at: row.stats_updated_at,
+            },
+            achievement_points: row.achievement_points,
+            active_warnings: row.active_warnings,
+        }
+    }
+}
+
+/// Scheduled class promotion and demotion job
+pub struct ClassPromotionJob {
+    db: PgPool,
+    system_user_id: Uuid,
+    permissions: Option<Arc<auth::PermissionResolver>>,
+}
+
+impl ClassPromotionJob {
+    /// Create a new job
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - Database pool
+    /// * `system_user_id` - Account the PMs are sent from
+    pub fn new(db: PgPool, system_user_id: Uuid) -> Self {
+        Self {
+            db,
+            system_user_id,
+            permissions: None,
+        }
+    }
+
+    /// Drop moved users' cached permissions so their new class applies at once
+    pub fn with_permission_resolver(mut self, resolver: Arc<auth::PermissionResolver>) -> Self {
+        self.permissions = Some(resolver);
+        self
+    }
+
+    /// Evaluate every user on the ladder and apply promotions and demotions
+    ///
+    /// With `dry_run` set, reports who would be promoted, warned or demoted
+    /// but writes nothing and sends no PMs.
+    pub async fn run(
+        &self,
+        now: DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<ClassPromotionReport, ClassPromotionError> {
+        let ladder = self.load_ladder().await?;
+        let group_ids: Vec<i32> = ladder.steps().iter().map(|s| s.group_id).collect();
+
+        let standings = self.load_standings(&group_ids).await?;
+        let notices = self.load_notices().await?;
+        let pinned = self.count_pinned(&group_ids).await?;
+
+        let mut changes = Vec::new();
+
+        for standing in &standings {
+            let notice = notices.get(&standing.user_id);
+            let Some(action) = decide(&ladder, standing, notice, now) else {
+                continue;
+            };
+
+            // Checked by decide
+            let Some(current) = ladder.step(standing.group_id) else {
+                continue;
+            };
+
+            let change = ClassChange {
+                user_id: standing.user_id,
+                username: standing.username.clone(),
+                from_group_id: standing.group_id,
+                unmet: current.unmet(standing, now),
+                action,
+            };
+
+            if !dry_run {
+                if let Err(e) = self.apply(&ladder, &change).await {
+                    warn!("Failed to apply class change for {}: {}", change.user_id, e);
+                    continue;
+                }
+            }
+
+            changes.push(change);
+        }
+
+        info!(
+            "Class promotion run{}: {} users evaluated, {} changes, {} pinned",
+            if dry_run { " (dry run)" } else { "" },
+            standings.len(),
+            changes.len(),
+            pinned
+        );
+
+        Ok(ClassPromotionReport {
+            run_at: now,
+            dry_run,
+            evaluated: standings.len(),
+            pinned,
+            changes,
+        })
+    }
+
+    /// Pin or unpin a user's class
+    ///
+    /// Pinned users are skipped by the job, and any pending demotion is
+    /// cancelled.
+    pub async fn set_pinned(
+        &self,
+        staff_id: Uuid,
+        user_id: Uuid,
+        pinned: bool,
+    ) -> Result<(), ClassPromotionError> {
+        let result = sqlx::query!(
+            "UPDATE users SET class_pinned = $2, updated_at = NOW() WHERE id = $1",
+            user_id,
+            pinned
+        )
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ClassPromotionError::UserNotFound(user_id));
+        }
+
+        if pinned {
+            sqlx::query!(
+                "DELETE FROM user_class_demotion_notices WHERE user_id = $1",
+                user_id
+            )
+            .execute(&self.db)
+            .await?;
+        }
+
+        info!(
+            "Staff {} {} the class of user {}",
+            staff_id,
+            if pinned { "pinned" } else { "unpinned" },
+            user_id
+        );
+
+        Ok(())
+    }
+
+    /// Load the ladder
+    pub async fn load_ladder(&self) -> Result<ClassLadder, ClassPromotionError> {
+        let steps = sqlx::query_as!(
+            ClassRequirement,
+            r#"
+            SELECT r.group_id, g.display_name AS class_name, g.level,
+                   r.min_uploaded, r.min_ratio, r.min_account_age_days, r.min_uploads,
+                   r.min_achievement_points, r.max_active_warnings, r.demotion_grace_days
+            FROM user_class_requirements r
+            JOIN user_groups g ON g.id = r.group_id
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(ClassLadder::new(steps))
+    }
+
+    async fn load_standings(
+        &self,
+        group_ids: &[i32],
+    ) -> Result<Vec<UserStanding>, ClassPromotionError> {
+        let rows = sqlx::query_as!(
+            StandingRow,
+            r#"
+            SELECT
+                u.id AS user_id,
+                u.username,
+                u.group_id,
+                u.created_at AS joined_at,
+                s.uploaded,
+                s.downloaded,
+                s.ratio,
+                s.seedbonus,
+                s.active_seeding,
+                s.active_leeching,
+                s.snatched_count,
+                s.upload_count,
+                s.avg_seed_time,
+                s.total_peer_time,
+                s.last_seen,
+                s.created_at AS stats_created_at,
+                s.updated_at AS stats_updated_at,
+                (SELECT COALESCE(SUM(a.points), 0)::INT
+                 FROM user_achievements ua
+                 JOIN achievements a ON ua.achievement_id = a.id
+                 WHERE ua.user_id = u.id AND ua.awarded = true) AS "achievement_points!",
+                (SELECT COUNT(*)::INT
+                 FROM warnings w
+                 WHERE w.user_id = u.id AND w.is_active AND NOT w.revoked
+                   AND (w.expires_at IS NULL OR w.expires_at > NOW())) AS "active_warnings!"
+            FROM users u
+            JOIN user_statistics s ON s.user_id = u.id
+            WHERE u.group_id = ANY($1)
+              AND NOT u.class_pinned
+              AND u.is_active
+              AND NOT u.is_banned
+              AND u.deleted_at IS NULL
+            "#,
+            group_ids
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows.into_iter().map(UserStanding::from).collect())
+    }
+
+    async fn load_notices(&self) -> Result<HashMap<Uuid, DemotionNotice>, ClassPromotionError> {
+        let notices = sqlx::query_as!(
+            DemotionNotice,
+            r#"
+            SELECT user_id, from_group_id, notified_at, demote_after
+            FROM user_class_demotion_notices
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(notices.into_iter().map(|n| (n.user_id, n)).collect())
+    }
+
+    async fn count_pinned(&self, group_ids: &[i32]) -> Result<i64, ClassPromotionError> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM users
+            WHERE group_id = ANY($1) AND class_pinned
+            "#,
+            group_ids
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn apply(
+        &self,
+        ladder: &ClassLadder,
+        change: &ClassChange,
+    ) -> Result<(), ClassPromotionError> {
+        let Some(from) = ladder.step(change.from_group_id) else {
+            return Ok(());
+        };
+        let to = match &change.action {
+            ClassAction::Promote { to_group_id }
+            | ClassAction::Warn { to_group_id, .. }
+            | ClassAction::Demote { to_group_id } => ladder.step(*to_group_id),
+            ClassAction::Recovered => None,
+        };
+        let (subject, body) = class_change_message(&change.action, from, to, &change.unmet);
+
+        let mut tx = self.db.begin().await?;
+
+        match &change.action {
+            ClassAction::Promote { to_group_id } | ClassAction::Demote { to_group_id } => {
+                let direction = if matches!(change.action, ClassAction::Promote { .. }) {
+                    "promotion"
+                } else {
+                    "demotion"
+                };
+
+                sqlx::query!(
+                    "UPDATE users SET group_id = $2, updated_at = NOW() WHERE id = $1",
+                    change.user_id,
+                    to_group_id
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query!(
+                    "DELETE FROM user_class_demotion_notices WHERE user_id = $1",
+                    change.user_id
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query!(
+                    r#"
+                    INSERT INTO user_class_changes
+                        (user_id, from_group_id, to_group_id, direction, reason)
+                    VALUES ($1, $2, $3, $4, $5)
+                    "#,
+                    change.user_id,
+                    change.from_group_id,
+                    to_group_id,
+                    direction,
+                    subject
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            ClassAction::Warn { demote_after, .. } => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO user_class_demotion_notices (user_id, from_group_id, demote_after)
+                    VALUES ($1, $2, $3)
+                    ON CONFLICT (user_id) DO UPDATE
+                    SET from_group_id = EXCLUDED.from_group_id,
+                        notified_at = NOW(),
+                        demote_after = EXCLUDED.demote_after
+                    "#,
+                    change.user_id,
+                    change.from_group_id,
+                    demote_after
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            ClassAction::Recovered => {
+                sqlx::query!(
+                    "DELETE FROM user_class_demotion_notices WHERE user_id = $1",
+                    change.user_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO private_messages (sender_id, recipient_id, subject, body)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            self.system_user_id,
+            change.user_id,
+            subject,
+            body
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        if matches!(
+            change.action,
+            ClassAction::Promote { .. } | ClassAction::Demote { .. }
+        ) {
+            if let Some(permissions) = &self.permissions {
+                if let Err(e) = permissions.invalidate_user(change.user_id).await {
+                    // The cache entry expires on its own shortly
+                    warn!(
+                        "Failed to invalidate permissions for {}: {}",
+                        change.user_id, e
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Format a byte count for a PM, e.g. `25.00 GiB`
+fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: i64 = 1024 * 1024 * 1024;
+
+    fn requirement(group_id: i32, level: i32, min_uploaded: i64, grace: i32) -> ClassRequirement {
+        ClassRequirement {
+            group_id,
+            class_name: format!("Class {}", group_id),
+            level,
+            min_uploaded,
+            min_ratio: if min_uploaded > 0 { 1.05 } else { 0.0 },
+            min_account_age_days: 0,
+            min_uploads: 0,
+            min_achievement_points: 0,
+            max_active_warnings: if min_uploaded > 0 { 0 } else { 100 },
+            demotion_grace_days: grace,
+        }
+    }
+
+    fn ladder() -> ClassLadder {
+        ClassLadder::new(vec![
+            requirement(4, 4, 100 * GIB, 7),
+            requirement(2, 2, 0, 7),
+            requirement(3, 3, 25 * GIB, 7),
+        ])
+    }
+
+    fn standing(group_id: i32, uploaded: i64, downloaded: i64) -> UserStanding {
+        let now = Utc::now();
+        UserStanding {
+            user_id: Uuid::new_v4(),
+            username: "member".to_string(),
+            group_id,
+            joined_at: now - Duration::days(365),
+            statistics: UserStatistics {
+                user_id: Uuid::new_v4(),
+                uploaded,
+                downloaded,
+                ratio: UserStatistics::calculate_ratio(uploaded, downloaded),
+                seedbonus: 0.0,
+                active_seeding: 0,
+                active_leeching: 0,
+                snatched_count: 0,
+                upload_count: 0,
+                avg_seed_time: 0,
+                total_peer_time: 0,
+                last_seen: None,
+                created_at: now,
+                updated_at: now,
+            },
+            achievement_points: 0,
+            active_warnings: 0,
+        }
+    }
+
+    #[test]
+    fn test_promotes_to_highest_met_class() {
+        let now = Utc::now();
+        let user = standing(2, 150 * GIB, 10 * GIB);
+
+        assert_eq!(
+            decide(&ladder(), &user, None, now),
+            Some(ClassAction::Promote { to_group_id: 4 })
+        );
+    }
+
+    #[test]
+    fn test_users_off_the_ladder_are_left_alone() {
+        let user = standing(15, 0, 100 * GIB);
+        assert_eq!(decide(&ladder(), &user, None, Utc::now()), None);
+    }
+
+    #[test]
+    fn test_demotion_waits_for_grace_period() {
+        let now = Utc::now();
+        let ladder = ladder();
+        let user = standing(3, 30 * GIB, 60 * GIB);
+
+        let action = decide(&ladder, &user, None, now).unwrap();
+        let ClassAction::Warn {
+            to_group_id,
+            demote_after,
+        } = action
+        else {
+            panic!("expected a warning, got {:?}", action);
+        };
+        assert_eq!(to_group_id, 2);
+        assert_eq!(demote_after, now + Duration::days(7));
+
+        let notice = DemotionNotice {
+            user_id: user.user_id,
+            from_group_id: 3,
+            notified_at: now,
+            demote_after,
+        };
+        assert_eq!(
+            decide(&ladder, &user, Some(&notice), now + Duration::days(3)),
+            None
+        );
+        assert_eq!(
+            decide(&ladder, &user, Some(&notice), now + Duration::days(8)),
+            Some(ClassAction::Demote { to_group_id: 2 })
+        );
+
+        // Recovering in time cancels the demotion
+        let recovered = standing(3, 30 * GIB, 20 * GIB);
+        assert_eq!(
+            decide(&ladder, &recovered, Some(&notice), now + Duration::days(3)),
+            Some(ClassAction::Recovered)
+        );
+    }
+
+    #[test]
+    fn test_notice_for_another_class_is_ignored() {
+        let now = Utc::now();
+        let user = standing(3, 30 * GIB, 60 * GIB);
+
+        // Warned while in class 4, then moved to class 3 by staff
+        let stale = DemotionNotice {
+            user_id: user.user_id,
+            from_group_id: 4,
+            notified_at: now - Duration::days(10),
+            demote_after: now - Duration::days(3),
+        };
+
+        assert!(matches!(
+            decide(&ladder(), &user, Some(&stale), now),
+            Some(ClassAction::Warn { to_group_id: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_no_grace_period_demotes_at_once() {
+        let ladder = ClassLadder::new(vec![
+            requirement(2, 2, 0, 0),
+            requirement(3, 3, 25 * GIB, 0),
+        ]);
+        let user = standing(3, GIB, GIB);
+
+        assert_eq!(
+            decide(&ladder, &user, None, Utc::now()),
+            Some(ClassAction::Demote { to_group_id: 2 })
+        );
+    }
+
+    #[test]
+    fn test_warnings_block_promotion() {
+        let now = Utc::now();
+        let mut user = standing(2, 30 * GIB, 10 * GIB);
+        assert!(ladder().step(3).unwrap().is_met(&user, now));
+
+        user.active_warnings = 1;
+        let unmet = ladder().step(3).unwrap().unmet(&user, now);
+        assert_eq!(unmet, vec!["have no active warnings".to_string()]);
+        assert_eq!(decide(&ladder(), &user, None, now), None);
+    }
+
+    #[test]
+    fn test_unmet_requirements_are_described() {
+        let now = Utc::now();
+        let mut requirement = requirement(3, 3, 25 * GIB, 7);
+        requirement.min_account_age_days = 14;
+        requirement.min_achievement_points = 50;
+
+        let mut user = standing(2, 5 * GIB, 10 * GIB);
+        user.joined_at = now - Duration::days(3);
+
+        let unmet = requirement.unmet(&user, now);
+        assert_eq!(unmet.len(), 4);
+        assert!(unmet[0].contains("25.00 GiB"));
+        assert!(unmet[1].contains("0.50"));
+        assert!(unmet[2].contains("3 days"));
+        assert!(unmet[3].contains("50 achievement points"));
+    }
+
+    #[test]
+    fn test_class_change_message() {
+        let from = requirement(3, 3, 25 * GIB, 7);
+        let to = requirement(2, 2, 0, 7);
+        let unmet = vec!["keep a ratio of at least 1.05 (yours is 0.50)".to_string()];
+
+        let (subject, body) = class_change_message(
+            &ClassAction::Demote { to_group_id: 2 },
+            &from,
+            Some(&to),
+            &unmet,
+        );
+        assert_eq!(subject, "You have been moved to Class 2");
+        assert!(body.contains("- keep a ratio of at least 1.05"));
+
+        let (subject, _) = class_change_message(
+            &ClassAction::Promote { to_group_id: 3 },
+            &to,
+            Some(&from),
+            &[],
+        );
+        assert_eq!(subject, "You have been promoted to Class 3");
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(25 * GIB), "25.00 GiB");
+    }
+}
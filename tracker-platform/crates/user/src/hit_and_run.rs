@@ -0,0 +1,205 @@
+//! Hit-and-run detection
+//!
+//! A hit-and-run is a snatch that was not seeded for long enough. Snatches
+//! come from the tracker's `snatched` table, which records completions
+//! reported by clients (not .torrent file downloads), together with the seed
+//! time credited by later seeding announces.
+//!
+//! Each snatch gets a grace period after completion. Once it has passed, the
+//! snatch must have accumulated the minimum seed time or it counts as a
+//! hit-and-run.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Hit-and-run errors
+#[derive(Debug, Error)]
+pub enum HitAndRunError {
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Hit-and-run rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HitAndRunRules {
+    /// Seed time required per snatch (seconds)
+    pub min_seed_time: i64,
+
+    /// Time after completion before a snatch is judged (seconds)
+    pub grace_period: i64,
+}
+
+impl Default for HitAndRunRules {
+    fn default() -> Self {
+        Self {
+            min_seed_time: 72 * 3600,
+            grace_period: 14 * 24 * 3600,
+        }
+    }
+}
+
+/// Status of a single snatch under the hit-and-run rules
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum HitAndRunStatus {
+    /// Seeded for the required time
+    Satisfied,
+    /// Still within the grace period
+    Pending {
+        /// Seed time still required (seconds)
+        remaining_seed_time: i64,
+        /// When the grace period ends
+        deadline: DateTime<Utc>,
+    },
+    /// Grace period passed without enough seed time
+    HitAndRun,
+}
+
+impl HitAndRunRules {
+    /// Evaluates a snatch at the given time
+    pub fn evaluate(
+        &self,
+        snatched_at: DateTime<Utc>,
+        seed_time: i64,
+        now: DateTime<Utc>,
+    ) -> HitAndRunStatus {
+        if seed_time >= self.min_seed_time {
+            return HitAndRunStatus::Satisfied;
+        }
+
+        let deadline = snatched_at + Duration::seconds(self.grace_period);
+        if now < deadline {
+            HitAndRunStatus::Pending {
+                remaining_seed_time: self.min_seed_time - seed_time,
+                deadline,
+            }
+        } else {
+            HitAndRunStatus::HitAndRun
+        }
+    }
+}
+
+/// A snatch that has not met the seeding requirement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HitAndRun {
+    /// User ID
+    pub user_id: Uuid,
+
+    /// Torrent ID
+    pub torrent_id: Uuid,
+
+    /// Torrent name
+    pub torrent_name: String,
+
+    /// Seconds seeded since completion
+    pub seed_time: i64,
+
+    /// Last seeding announce, if any
+    pub last_seeded_at: Option<DateTime<Utc>>,
+
+    /// Completion timestamp
+    pub snatched_at: DateTime<Utc>,
+}
+
+/// Hit-and-run service
+pub struct HitAndRunService {
+    db: PgPool,
+    rules: HitAndRunRules,
+}
+
+impl HitAndRunService {
+    /// Create a new hit-and-run service with the default rules
+    pub fn new(db: PgPool) -> Self {
+        Self::with_rules(db, HitAndRunRules::default())
+    }
+
+    /// Create a new hit-and-run service with custom rules
+    pub fn with_rules(db: PgPool, rules: HitAndRunRules) -> Self {
+        Self { db, rules }
+    }
+
+    /// The rules this service applies
+    pub fn rules(&self) -> &HitAndRunRules {
+        &self.rules
+    }
+
+    /// Get a user's hit-and-runs
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user ID
+    pub async fn get_user_hit_and_runs(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<HitAndRun>, HitAndRunError> {
+        let hit_and_runs = sqlx::query_as!(
+            HitAndRun,
+            r#"
+            SELECT
+                s.user_id,
+                s.torrent_id,
+                t.name as torrent_name,
+                s.seed_time,
+                s.last_seeded_at,
+                s.snatched_at
+            FROM snatched s
+            JOIN torrents t ON s.torrent_id = t.id
+            WHERE s.user_id = $1
+              AND s.seed_time < $2
+              AND s.snatched_at < NOW() - make_interval(secs => $3)
+            ORDER BY s.snatched_at DESC
+            "#,
+            user_id,
+            self.rules.min_seed_time,
+            self.rules.grace_period as f64
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(hit_and_runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_satisfied() {
+        let rules = HitAndRunRules::default();
+        let now = Utc::now();
+
+        assert_eq!(
+            rules.evaluate(now, rules.min_seed_time, now),
+            HitAndRunStatus::Satisfied
+        );
+    }
+
+    #[test]
+    fn test_evaluate_pending_and_hit_and_run() {
+        let rules = HitAndRunRules {
+            min_seed_time: 3600,
+            grace_period: 86400,
+        };
+        let snatched_at = Utc::now();
+
+        match rules.evaluate(snatched_at, 600, snatched_at + Duration::hours(1)) {
+            HitAndRunStatus::Pending {
+                remaining_seed_time,
+                deadline,
+            } => {
+                assert_eq!(remaining_seed_time, 3000);
+                assert_eq!(deadline, snatched_at + Duration::days(1));
+            }
+            other => panic!("expected pending, got {:?}", other),
+        }
+
+        assert_eq!(
+            rules.evaluate(snatched_at, 600, snatched_at + Duration::days(2)),
+            HitAndRunStatus::HitAndRun
+        );
+    }
+}
@@ -6,10 +6,13 @@
 //! - Invitation quota by user class
 //! - Track invite success rate
 //! - Disable invites for bad inviters
+//! - Email invitation codes to invitees
 
+use auth::{EmailError, EmailService};
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -39,6 +42,9 @@ pub enum InviteError {
 
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
+
+    #[error("Email error: {0}")]
+    Email(#[from] EmailError),
 }
 
 /// Invitation code status
@@ -149,12 +155,19 @@ pub struct InviteStats {
 /// Invite service for managing invitations
 pub struct InviteService {
     db: PgPool,
+    email: Option<Arc<EmailService>>,
 }
 
 impl InviteService {
     /// Create a new invite service
     pub fn new(db: PgPool) -> Self {
-        Self { db }
+        Self { db, email: None }
+    }
+
+    /// Email invitation codes through `email`
+    pub fn with_email(mut self, email: Arc<EmailService>) -> Self {
+        self.email = Some(email);
+        self
     }
 
     /// Generate a new invitation code
@@ -269,6 +282,59 @@ impl InviteService {
         Ok(used_invitation)
     }
 
+    /// Email an unused invitation to `to`
+    ///
+    /// Only the inviter can send their invitation. Returns whether an email
+    /// was queued: nothing is sent without an email service, or when `to`
+    /// is suppressed or has unsubscribed from invites.
+    pub async fn email_invite(
+        &self,
+        inviter_id: Uuid,
+        code: &str,
+        to: &str,
+    ) -> Result<bool, InviteError> {
+        let invitation = self.get_invite_by_code(code).await?;
+        if invitation.inviter_id != inviter_id {
+            return Err(InviteError::NotFound(code.to_string()));
+        }
+        if !invitation.is_valid() {
+            return Err(match invitation.status {
+                InviteStatus::Used => InviteError::AlreadyUsed,
+                _ => InviteError::Expired,
+            });
+        }
+
+        let Some(email) = &self.email else {
+            tracing::warn!("No email service; invitation {} not emailed", code);
+            return Ok(false);
+        };
+
+        // The invitee has no account yet, so write in the inviter's language
+        let inviter = sqlx::query!(
+            r#"
+            SELECT username, COALESCE(locale, 'en') as "locale!"
+            FROM users
+            WHERE id = $1
+            "#,
+            inviter_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(InviteError::UserNotFound(inviter_id))?;
+
+        let queued = email
+            .send_invite(
+                to,
+                &inviter.locale,
+                &inviter.username,
+                code,
+                invitation.message.as_deref(),
+            )
+            .await?;
+
+        Ok(queued.is_some())
+    }
+
     /// Get invitation by code
     ///
     /// # Arguments
@@ -7,7 +7,9 @@
 //! - **Statistics**: Upload/download tracking, ratio calculation, and activity metrics
 //! - **Seedbonus System**: Rule-based bonus earning system (Unit3d pattern)
 //! - **Freeleech System**: Three-tier freeleech with tokens and temporary windows
+//! - **Reseed Requests**: Ask previous snatchers to revive dead torrents, with optional bounties
 //! - **Achievements**: Badge/achievement system with progress tracking
+//! - **Class Promotion**: Scheduled promotion and demotion between user classes, with grace periods
 //! - **Privacy Controls**: Granular privacy settings (Gazelle paranoia system)
 //! - **Invitation System**: Invite tree tracking and quota management
 //! - **Social Features**: Follow/unfollow users and activity feeds
@@ -21,10 +23,12 @@
 //!    - Statistics calculation and tracking
 //!    - Privacy settings and enforcement
 //!
-//! 2. **Incentive Layer** (`bonus`, `freeleech`, `achievements`)
+//! 2. **Incentive Layer** (`bonus`, `freeleech`, `achievements`, `reseed`, `class_promotion`)
 //!    - Seedbonus earning and spending
 //!    - Freeleech token system
 //!    - Achievement tracking and awards
+//!    - Automatic class promotion and demotion
+//!    - Reseed requests and bounties
 //!
 //! 3. **Social Layer** (`invites`, `follow`)
 //!    - Invitation system with tree tracking
@@ -198,9 +202,13 @@
 //! - `user_statistics`: Upload/download statistics and tracking
 //! - `bonus_transactions`: Seedbonus earning and spending history
 //! - `bonus_rules`: Rules for calculating seedbonus
+//! - `bonus_accruals`: Per-period seeding bonus payouts
 //! - `freeleech_tokens`: Personal freeleech token inventory
 //! - `achievements`: Achievement definitions
 //! - `user_achievements`: User achievement progress and awards
+//! - `user_class_requirements`: Thresholds for the classes users are promoted between
+//! - `user_class_demotion_notices`, `user_class_changes`: Pending demotions and the change log
+//! - `snatched`: Announce-derived completions (written by the tracker)
 //! - `privacy_settings`: User privacy preferences
 //! - `invitations`: Invitation codes and tracking
 //! - `user_follows`: User follow relationships
@@ -213,11 +221,15 @@ pub use uuid::Uuid;
 // Module declarations
 pub mod achievements;
 pub mod bonus;
+pub mod bonus_accrual;
+pub mod class_promotion;
 pub mod follow;
 pub mod freeleech;
+pub mod hit_and_run;
 pub mod invites;
 pub mod privacy;
 pub mod profile;
+pub mod reseed;
 pub mod statistics;
 
 // Re-export key types for convenience
@@ -228,15 +240,28 @@ pub use achievements::{
 pub use bonus::{
     BonusError, BonusOperation, BonusRule, BonusService, BonusTransaction, BonusTransactionType,
 };
+pub use bonus_accrual::{AccrualReport, BonusAccrualJob, SeedingTorrent, UserPayout};
+pub use class_promotion::{
+    ClassAction, ClassChange, ClassLadder, ClassPromotionError, ClassPromotionJob,
+    ClassPromotionReport, ClassRequirement, DemotionNotice, UserStanding,
+};
 pub use follow::{FollowError, FollowService, UserFollow};
 pub use freeleech::{
     FreeleechError, FreeleechService, FreeleechToken, FreeleechType, TokenStatus,
 };
+pub use hit_and_run::{
+    HitAndRun, HitAndRunError, HitAndRunRules, HitAndRunService, HitAndRunStatus,
+};
 pub use invites::{Invitation, InviteError, InviteService, InviteTree};
 pub use privacy::{PrivacyError, PrivacyLevel, PrivacyService, PrivacySettings};
 pub use profile::{ProfileError, ProfileService, UpdateProfileRequest, UserProfile};
+pub use reseed::{
+    DeadTorrent, ReseedError, ReseedPolicy, ReseedRequest, ReseedResolution, ReseedService,
+    ReseedStatus,
+};
 pub use statistics::{
-    PeerTime, StatisticsError, StatisticsService, UploadDownloadHistory, UserStatistics,
+    ActiveTorrent, PeerTime, SnatchedTorrent, StatisticsError, StatisticsService,
+    UploadDownloadHistory, UserStatistics,
 };
 
 /// Library version
@@ -249,11 +274,15 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub mod user {
     pub use crate::achievements::*;
     pub use crate::bonus::*;
+    pub use crate::bonus_accrual::*;
+    pub use crate::class_promotion::*;
     pub use crate::follow::*;
     pub use crate::freeleech::*;
+    pub use crate::hit_and_run::*;
     pub use crate::invites::*;
     pub use crate::privacy::*;
     pub use crate::profile::*;
+    pub use crate::reseed::*;
     pub use crate::statistics::*;
 }
 
@@ -274,6 +303,7 @@ mod tests {
         let _: Result<(), BonusError> = Ok(());
         let _: Result<(), FreeleechError> = Ok(());
         let _: Result<(), AchievementError> = Ok(());
+        let _: Result<(), ClassPromotionError> = Ok(());
         let _: Result<(), PrivacyError> = Ok(());
         let _: Result<(), InviteError> = Ok(());
         let _: Result<(), FollowError> = Ok(());
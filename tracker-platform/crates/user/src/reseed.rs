@@ -0,0 +1,764 @@
+//! Reseed requests
+//!
+//! A torrent with no seeders can only come back if someone who has the data
+//! starts seeding again. A reseed request emails the uploader and previous
+//! snatchers (from the tracker's `snatched` table), followed by users who
+//! fetched the .torrent file but never reported a completion, through the
+//! transactional email queue; they can unsubscribe from the `reseeds`
+//! category. Requests are rate-limited per torrent and per requester.
+//!
+//! The requester can attach a bonus bounty. It is taken through
+//! `BonusService` when the request is made and paid to a user the tracker
+//! sees seeding the torrent again, or refunded if the request expires.
+//!
+//! Seeder counts come from `torrent_stats`, which the tracker's batch writer
+//! updates on every flush; `last_seeded_at` there records when the torrent
+//! last had a seeder and drives the staff dead-torrent list.
+
+use auth::EmailService;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::bonus::{BonusError, BonusService, BonusTransactionType};
+use crate::bonus_accrual::SeedingTorrent;
+
+/// Reseed request errors
+#[derive(Debug, Error)]
+pub enum ReseedError {
+    #[error("Torrent not found: {0}")]
+    TorrentNotFound(Uuid),
+
+    #[error("Torrent {0} still has seeders")]
+    NotDead(Uuid),
+
+    #[error("A reseed request is already open for torrent {0}")]
+    AlreadyOpen(Uuid),
+
+    #[error("Reseed was requested recently, try again after {retry_after}")]
+    RateLimited { retry_after: DateTime<Utc> },
+
+    #[error("Invalid bounty: {0}")]
+    InvalidBounty(f64),
+
+    #[error("Reseed request not found: {0}")]
+    RequestNotFound(Uuid),
+
+    #[error("Bonus error: {0}")]
+    Bonus(#[from] BonusError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Limits on reseed requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReseedPolicy {
+    /// Minimum time between requests for the same torrent (hours)
+    pub torrent_cooldown_hours: i64,
+
+    /// Requests one user may make per day
+    pub max_requests_per_user_per_day: i64,
+
+    /// Users notified per request
+    pub max_recipients: i64,
+
+    /// Largest bounty a requester may attach
+    pub max_bounty: f64,
+
+    /// Days before an unfulfilled request expires and its bounty is refunded
+    pub expiry_days: i64,
+}
+
+impl Default for ReseedPolicy {
+    fn default() -> Self {
+        Self {
+            torrent_cooldown_hours: 7 * 24,
+            max_requests_per_user_per_day: 5,
+            max_recipients: 200,
+            max_bounty: 100_000.0,
+            expiry_days: 30,
+        }
+    }
+}
+
+impl ReseedPolicy {
+    /// When a torrent last requested at `last_request` may be requested again
+    ///
+    /// Returns `None` if a new request is allowed at `now`.
+    pub fn retry_after(
+        &self,
+        last_request: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let next = last_request? + Duration::hours(self.torrent_cooldown_hours);
+        (now < next).then_some(next)
+    }
+
+    /// Check an optional bounty against the policy
+    pub fn check_bounty(&self, bounty: Option<f64>) -> Result<f64, ReseedError> {
+        match bounty {
+            None => Ok(0.0),
+            Some(b) if b.is_finite() && b > 0.0 && b <= self.max_bounty => Ok(b),
+            Some(b) => Err(ReseedError::InvalidBounty(b)),
+        }
+    }
+}
+
+/// Reseed request status
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReseedStatus {
+    /// Waiting for a seeder
+    Open,
+    /// Someone seeded the torrent again
+    Fulfilled,
+    /// Expired without a seeder; bounty refunded
+    Expired,
+}
+
+/// A reseed request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReseedRequest {
+    /// Request ID
+    pub id: Uuid,
+
+    /// Torrent ID
+    pub torrent_id: Uuid,
+
+    /// Requesting user
+    pub requester_id: Uuid,
+
+    /// Bonus bounty held for the reseeder
+    pub bounty: f64,
+
+    /// Status
+    pub status: ReseedStatus,
+
+    /// Users asked to reseed
+    pub notified_count: i32,
+
+    /// User who reseeded, if known
+    pub fulfilled_by: Option<Uuid>,
+
+    /// When the torrent was seen seeding again
+    pub fulfilled_at: Option<DateTime<Utc>>,
+
+    /// When the request expires
+    pub expires_at: DateTime<Utc>,
+
+    /// Created at timestamp
+    pub created_at: DateTime<Utc>,
+}
+
+/// A torrent without seeders, for the staff list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadTorrent {
+    /// Torrent ID
+    pub torrent_id: Uuid,
+
+    /// Torrent name
+    pub name: String,
+
+    /// Uploader
+    pub uploader_id: Uuid,
+
+    /// Torrent size in bytes
+    pub size: i64,
+
+    /// Completed downloads
+    pub snatches: i64,
+
+    /// Last time the tracker saw a seeder (upload time if never seeded)
+    pub dead_since: DateTime<Utc>,
+
+    /// Most recent reseed request, if any
+    pub last_reseed_request_at: Option<DateTime<Utc>>,
+
+    /// Whether a reseed request is currently open
+    pub reseed_open: bool,
+}
+
+/// Outcome of a reseed request resolved by [`ReseedService::resolve`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReseedResolution {
+    /// Request ID
+    pub request_id: Uuid,
+
+    /// Torrent ID
+    pub torrent_id: Uuid,
+
+    /// New status
+    pub status: ReseedStatus,
+
+    /// Who received the bounty, or the requester for a refund
+    pub paid_to: Option<Uuid>,
+
+    /// Bounty paid out or refunded
+    pub amount: f64,
+}
+
+/// Reseed request service
+pub struct ReseedService {
+    db: PgPool,
+    bonus: BonusService,
+    email: Option<Arc<EmailService>>,
+    policy: ReseedPolicy,
+}
+
+impl ReseedService {
+    /// Create a new reseed service with the default policy
+    pub fn new(db: PgPool) -> Self {
+        Self::with_policy(db, ReseedPolicy::default())
+    }
+
+    /// Create a new reseed service with a custom policy
+    pub fn with_policy(db: PgPool, policy: ReseedPolicy) -> Self {
+        let bonus = BonusService::new(db.clone());
+        Self {
+            db,
+            bonus,
+            email: None,
+            policy,
+        }
+    }
+
+    /// Email reseed requests through `email`
+    ///
+    /// Without it requests are still recorded, but nobody is asked.
+    pub fn with_email(mut self, email: Arc<EmailService>) -> Self {
+        self.email = Some(email);
+        self
+    }
+
+    /// The policy this service applies
+    pub fn policy(&self) -> &ReseedPolicy {
+        &self.policy
+    }
+
+    /// Request a reseed of a dead torrent
+    ///
+    /// # Arguments
+    ///
+    /// * `torrent_id` - The torrent to revive
+    /// * `requester_id` - The requesting user
+    /// * `bounty` - Optional bonus points offered to the reseeder
+    pub async fn request_reseed(
+        &self,
+        torrent_id: Uuid,
+        requester_id: Uuid,
+        bounty: Option<f64>,
+    ) -> Result<ReseedRequest, ReseedError> {
+        let bounty = self.policy.check_bounty(bounty)?;
+
+        let torrent = sqlx::query!(
+            r#"
+            SELECT
+                t.name,
+                COALESCE(ts.seeders, 0) as "seeders!",
+                (SELECT MAX(created_at) FROM reseed_requests WHERE torrent_id = t.id) as last_request,
+                EXISTS(
+                    SELECT 1 FROM reseed_requests
+                    WHERE torrent_id = t.id AND status = 'open'
+                ) as "open!"
+            FROM torrents t
+            LEFT JOIN torrent_stats ts ON ts.info_hash = decode(t.info_hash, 'hex')
+            WHERE t.id = $1
+            "#,
+            torrent_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(ReseedError::TorrentNotFound(torrent_id))?;
+
+        if torrent.seeders > 0 {
+            return Err(ReseedError::NotDead(torrent_id));
+        }
+        if torrent.open {
+            return Err(ReseedError::AlreadyOpen(torrent_id));
+        }
+
+        let now = Utc::now();
+        if let Some(retry_after) = self.policy.retry_after(torrent.last_request, now) {
+            return Err(ReseedError::RateLimited { retry_after });
+        }
+
+        let requests_today = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM reseed_requests
+            WHERE requester_id = $1 AND created_at > NOW() - INTERVAL '1 day'
+            "#,
+            requester_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        if requests_today >= self.policy.max_requests_per_user_per_day {
+            return Err(ReseedError::RateLimited {
+                retry_after: now + Duration::days(1),
+            });
+        }
+
+        // Take the bounty up front; it is refunded if the request expires
+        if bounty > 0.0 {
+            self.bonus
+                .deduct_bonus(
+                    requester_id,
+                    bounty,
+                    BonusTransactionType::ReseedBounty,
+                    format!("Reseed bounty for {}", torrent.name),
+                )
+                .await?;
+        }
+
+        match self
+            .insert_request(torrent_id, requester_id, bounty, now)
+            .await
+        {
+            Ok((request, recipients)) => {
+                self.ask_to_reseed(&request, &torrent.name, &recipients)
+                    .await;
+                Ok(request)
+            }
+            Err(e) => {
+                if bounty > 0.0 {
+                    self.bonus
+                        .award_bonus(
+                            requester_id,
+                            bounty,
+                            BonusTransactionType::ReseedRefund,
+                            Some(torrent_id),
+                            format!("Refund of reseed bounty for {}", torrent.name),
+                        )
+                        .await?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Insert a request and pick everyone who may still have the data
+    async fn insert_request(
+        &self,
+        torrent_id: Uuid,
+        requester_id: Uuid,
+        bounty: f64,
+        now: DateTime<Utc>,
+    ) -> Result<(ReseedRequest, Vec<Recipient>), ReseedError> {
+        let mut tx = self.db.begin().await?;
+        let request_id = Uuid::new_v4();
+
+        // The partial unique index on open requests catches concurrent
+        // requests for the same torrent
+        sqlx::query!(
+            r#"
+            INSERT INTO reseed_requests
+                (id, torrent_id, requester_id, bounty, status, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, 'open', $5, $6)
+            "#,
+            request_id,
+            torrent_id,
+            requester_id,
+            bounty,
+            now + Duration::days(self.policy.expiry_days),
+            now
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                ReseedError::AlreadyOpen(torrent_id)
+            }
+            e => ReseedError::Database(e),
+        })?;
+
+        // Uploader first, then snatchers, then users who only fetched the
+        // .torrent file; most recent first within each group
+        let recipients = sqlx::query_as!(
+            Recipient,
+            r#"
+            SELECT u.id as user_id, u.email, COALESCE(u.locale, 'en') as "locale!"
+            FROM (
+                SELECT DISTINCT ON (user_id) user_id, priority, seen_at
+                FROM (
+                    SELECT uploader_id as user_id, 0 as priority, created_at as seen_at
+                    FROM torrents WHERE id = $1
+                    UNION ALL
+                    SELECT user_id, 1, snatched_at
+                    FROM snatched WHERE torrent_id = $1
+                    UNION ALL
+                    SELECT user_id, 2, MAX(downloaded_at)
+                    FROM torrent_downloads WHERE torrent_id = $1
+                    GROUP BY user_id
+                ) candidates
+                WHERE user_id <> $2
+                ORDER BY user_id, priority
+            ) c
+            JOIN users u ON u.id = c.user_id AND u.is_active AND NOT u.is_banned
+            ORDER BY c.priority, c.seen_at DESC
+            LIMIT $3
+            "#,
+            torrent_id,
+            requester_id,
+            self.policy.max_recipients
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let request = sqlx::query_as!(
+            ReseedRequest,
+            r#"
+            UPDATE reseed_requests
+            SET notified_count = $2
+            WHERE id = $1
+            RETURNING
+                id,
+                torrent_id,
+                requester_id,
+                bounty,
+                status as "status: ReseedStatus",
+                notified_count,
+                fulfilled_by,
+                fulfilled_at,
+                expires_at,
+                created_at
+            "#,
+            request_id,
+            recipients.len() as i32
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok((request, recipients))
+    }
+
+    /// Queue the reseed emails for a new request
+    ///
+    /// The request stands even if some emails can't be queued.
+    async fn ask_to_reseed(
+        &self,
+        request: &ReseedRequest,
+        torrent_name: &str,
+        recipients: &[Recipient],
+    ) {
+        let Some(email) = &self.email else {
+            tracing::warn!(
+                "No email service; {} users not asked to reseed {}",
+                recipients.len(),
+                request.torrent_id
+            );
+            return;
+        };
+
+        for recipient in recipients {
+            if let Err(e) = email
+                .send_reseed_request(
+                    recipient.user_id,
+                    &recipient.email,
+                    &recipient.locale,
+                    request.torrent_id,
+                    torrent_name,
+                    request.bounty,
+                )
+                .await
+            {
+                tracing::warn!(
+                    "Failed to queue reseed request for {} to {}: {}",
+                    request.torrent_id,
+                    recipient.user_id,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Get the reseed requests for a torrent, newest first
+    pub async fn get_torrent_requests(
+        &self,
+        torrent_id: Uuid,
+    ) -> Result<Vec<ReseedRequest>, ReseedError> {
+        let requests = sqlx::query_as!(
+            ReseedRequest,
+            r#"
+            SELECT
+                id,
+                torrent_id,
+                requester_id,
+                bounty,
+                status as "status: ReseedStatus",
+                notified_count,
+                fulfilled_by,
+                fulfilled_at,
+                expires_at,
+                created_at
+            FROM reseed_requests
+            WHERE torrent_id = $1
+            ORDER BY created_at DESC
+            "#,
+            torrent_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(requests)
+    }
+
+    /// List torrents that have had no seeders for at least `min_days`
+    ///
+    /// # Arguments
+    ///
+    /// * `min_days` - Minimum days without a seeder
+    /// * `limit` - Maximum number of results
+    /// * `offset` - Offset for pagination
+    pub async fn dead_torrents(
+        &self,
+        min_days: i64,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DeadTorrent>, ReseedError> {
+        let torrents = sqlx::query_as!(
+            DeadTorrent,
+            r#"
+            SELECT
+                t.id as torrent_id,
+                t.name,
+                t.uploader_id,
+                t.total_size as size,
+                (SELECT COUNT(*) FROM snatched s WHERE s.torrent_id = t.id) as "snatches!",
+                COALESCE(ts.last_seeded_at, t.created_at) as "dead_since!",
+                (SELECT MAX(created_at) FROM reseed_requests r WHERE r.torrent_id = t.id)
+                    as last_reseed_request_at,
+                EXISTS(
+                    SELECT 1 FROM reseed_requests r
+                    WHERE r.torrent_id = t.id AND r.status = 'open'
+                ) as "reseed_open!"
+            FROM torrents t
+            LEFT JOIN torrent_stats ts ON ts.info_hash = decode(t.info_hash, 'hex')
+            WHERE t.moderation_status = 'approved'
+              AND COALESCE(ts.seeders, 0) = 0
+              AND COALESCE(ts.last_seeded_at, t.created_at) < NOW() - make_interval(days => $1)
+            ORDER BY COALESCE(ts.last_seeded_at, t.created_at) ASC
+            LIMIT $2 OFFSET $3
+            "#,
+            min_days as i32,
+            limit,
+            offset
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(torrents)
+    }
+
+    /// Settle open requests: pay bounties for revived torrents and refund
+    /// expired ones
+    ///
+    /// `swarms` is the tracker's live seeding snapshot; a request is
+    /// fulfilled once its torrent has a seeder there. Each request is closed
+    /// and paid in one transaction. Intended for a periodic job.
+    pub async fn resolve(
+        &self,
+        swarms: &[SeedingTorrent],
+    ) -> Result<Vec<ReseedResolution>, ReseedError> {
+        let mut resolutions = Vec::new();
+
+        let seeding: HashMap<String, &[Uuid]> = swarms
+            .iter()
+            .filter(|swarm| !swarm.seeding_users.is_empty())
+            .map(|swarm| {
+                (
+                    swarm.info_hash.to_ascii_lowercase(),
+                    swarm.seeding_users.as_slice(),
+                )
+            })
+            .collect();
+
+        let open = sqlx::query!(
+            r#"
+            SELECT
+                r.id,
+                r.torrent_id,
+                r.requester_id,
+                r.bounty,
+                r.expires_at,
+                t.name as torrent_name,
+                t.info_hash
+            FROM reseed_requests r
+            JOIN torrents t ON t.id = r.torrent_id
+            WHERE r.status = 'open'
+            "#
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let now = Utc::now();
+        for request in open {
+            let seeders = seeding.get(&request.info_hash.to_ascii_lowercase());
+
+            let (status, reseeder, paid_to, transaction_type, description) = match seeders {
+                // Revived: any seeder other than the requester gets the
+                // bounty. Nobody else to pay (the requester seeded it):
+                // refund.
+                Some(users) => match pick_reseeder(users, request.requester_id) {
+                    Some(reseeder) => (
+                        ReseedStatus::Fulfilled,
+                        Some(reseeder),
+                        reseeder,
+                        BonusTransactionType::ReseedReward,
+                        format!("Reseed bounty for {}", request.torrent_name),
+                    ),
+                    None => (
+                        ReseedStatus::Fulfilled,
+                        None,
+                        request.requester_id,
+                        BonusTransactionType::ReseedRefund,
+                        format!("Refund of reseed bounty for {}", request.torrent_name),
+                    ),
+                },
+                None if request.expires_at < now => (
+                    ReseedStatus::Expired,
+                    None,
+                    request.requester_id,
+                    BonusTransactionType::ReseedRefund,
+                    format!(
+                        "Refund of expired reseed bounty for {}",
+                        request.torrent_name
+                    ),
+                ),
+                None => continue,
+            };
+
+            let mut tx = self.db.begin().await?;
+            if !Self::close(&mut tx, request.id, status, reseeder).await? {
+                continue;
+            }
+            if request.bounty > 0.0 {
+                self.bonus
+                    .award_bonus_in_tx(
+                        &mut tx,
+                        paid_to,
+                        request.bounty,
+                        transaction_type,
+                        Some(request.torrent_id),
+                        description,
+                    )
+                    .await?;
+            }
+            tx.commit().await?;
+
+            resolutions.push(ReseedResolution {
+                request_id: request.id,
+                torrent_id: request.torrent_id,
+                status,
+                paid_to: (request.bounty > 0.0).then_some(paid_to),
+                amount: request.bounty,
+            });
+        }
+
+        Ok(resolutions)
+    }
+
+    /// Move an open request to a final status
+    ///
+    /// Returns false if another run already closed it, so the bounty is
+    /// only ever paid once.
+    async fn close(
+        tx: &mut Transaction<'_, Postgres>,
+        request_id: Uuid,
+        status: ReseedStatus,
+        fulfilled_by: Option<Uuid>,
+    ) -> Result<bool, ReseedError> {
+        let fulfilled = status == ReseedStatus::Fulfilled;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE reseed_requests
+            SET status = CASE WHEN $2 THEN 'fulfilled' ELSE 'expired' END,
+                fulfilled_by = $3,
+                fulfilled_at = CASE WHEN $2 THEN NOW() END
+            WHERE id = $1 AND status = 'open'
+            "#,
+            request_id,
+            fulfilled,
+            fulfilled_by
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// A user asked to reseed
+struct Recipient {
+    user_id: Uuid,
+    email: String,
+    locale: String,
+}
+
+/// Who earns the bounty among a torrent's current seeders
+///
+/// The requester can't earn their own bounty. Seeders come sorted, so the
+/// choice is stable across runs.
+fn pick_reseeder(seeding_users: &[Uuid], requester_id: Uuid) -> Option<Uuid> {
+    seeding_users
+        .iter()
+        .copied()
+        .find(|user_id| *user_id != requester_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after() {
+        let policy = ReseedPolicy {
+            torrent_cooldown_hours: 24,
+            ..Default::default()
+        };
+        let now = Utc::now();
+
+        assert_eq!(policy.retry_after(None, now), None);
+        assert_eq!(
+            policy.retry_after(Some(now - Duration::hours(25)), now),
+            None
+        );
+        assert_eq!(
+            policy.retry_after(Some(now - Duration::hours(1)), now),
+            Some(now + Duration::hours(23))
+        );
+    }
+
+    #[test]
+    fn test_check_bounty() {
+        let policy = ReseedPolicy {
+            max_bounty: 1000.0,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.check_bounty(None).unwrap(), 0.0);
+        assert_eq!(policy.check_bounty(Some(500.0)).unwrap(), 500.0);
+        assert!(policy.check_bounty(Some(0.0)).is_err());
+        assert!(policy.check_bounty(Some(-10.0)).is_err());
+        assert!(policy.check_bounty(Some(1000.5)).is_err());
+        assert!(policy.check_bounty(Some(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn test_pick_reseeder_skips_requester() {
+        let requester = Uuid::new_v4();
+        let seeder = Uuid::new_v4();
+
+        assert_eq!(pick_reseeder(&[requester, seeder], requester), Some(seeder));
+        assert_eq!(pick_reseeder(&[requester], requester), None);
+        assert_eq!(pick_reseeder(&[], requester), None);
+    }
+}
@@ -6,6 +6,7 @@
 //! - Seedbonus points
 //! - Active seeding count
 //! - Snatched torrents list
+//! - Active torrents list with connectability
 //! - Upload/download history charts
 //! - Peer time tracking
 
@@ -127,6 +128,9 @@ pub struct PeerTime {
 }
 
 /// Snatched torrent entry
+///
+/// Snatches are recorded by the tracker when a user's client reports
+/// completion with nothing left to download, once per user and torrent.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SnatchedTorrent {
     /// Torrent ID
@@ -135,22 +139,56 @@ pub struct SnatchedTorrent {
     /// Torrent name
     pub torrent_name: String,
 
-    /// Uploaded bytes for this torrent
+    /// Uploaded bytes reported on completion
     pub uploaded: i64,
 
-    /// Downloaded bytes for this torrent
+    /// Downloaded bytes reported on completion
     pub downloaded: i64,
 
-    /// Time left (in seconds) when snatched
+    /// Seconds from the first announce to completion
     pub time_left: i64,
 
-    /// Seed time (in seconds)
+    /// Seconds seeded since completion
     pub seed_time: i64,
 
     /// Snatched at timestamp
     pub snatched_at: DateTime<Utc>,
 }
 
+/// Active (currently announced) torrent entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveTorrent {
+    /// Torrent ID
+    pub torrent_id: Uuid,
+
+    /// Torrent name
+    pub torrent_name: String,
+
+    /// Whether this client is seeding
+    pub is_seeder: bool,
+
+    /// Uploaded bytes this session
+    pub uploaded: i64,
+
+    /// Downloaded bytes this session
+    pub downloaded: i64,
+
+    /// Bytes left to download
+    pub left_bytes: i64,
+
+    /// Port the client announced
+    pub port: i32,
+
+    /// BitTorrent client
+    pub user_agent: Option<String>,
+
+    /// Whether the tracker could reach the client (None if not yet probed)
+    pub is_connectable: Option<bool>,
+
+    /// Last announce timestamp
+    pub last_announce_at: DateTime<Utc>,
+}
+
 /// Statistics service for managing user statistics
 pub struct StatisticsService {
     db: PgPool,
@@ -464,6 +502,84 @@ impl StatisticsService {
         Ok(snatched)
     }
 
+    /// Count a user's snatches
+    ///
+    /// Counts the announce-derived snatch records rather than the cached
+    /// counter, so it is always consistent with the snatch list.
+    pub async fn count_snatches(&self, user_id: Uuid) -> Result<i64, StatisticsError> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM snatched
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Users who snatched anything at or after `since`
+    pub async fn users_with_snatches_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Uuid>, StatisticsError> {
+        let users = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT user_id
+            FROM snatched
+            WHERE snatched_at >= $1
+            "#,
+            since
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(users)
+    }
+
+    /// Get currently active torrents with the tracker's connectability result
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user ID
+    ///
+    /// # Returns
+    ///
+    /// Returns one entry per announcing client, most recently announced first
+    pub async fn get_active_torrents(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<ActiveTorrent>, StatisticsError> {
+        let active = sqlx::query_as!(
+            ActiveTorrent,
+            r#"
+            SELECT
+                p.torrent_id,
+                t.name as torrent_name,
+                p.is_seeder,
+                p.uploaded,
+                p.downloaded,
+                p.left_bytes,
+                p.port,
+                p.user_agent,
+                p.is_connectable,
+                p.last_announce_at
+            FROM peers p
+            JOIN torrents t ON p.torrent_id = t.id
+            WHERE p.user_id = $1
+            ORDER BY p.last_announce_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(active)
+    }
+
     /// Get peer time for a specific torrent
     ///
     /// # Arguments
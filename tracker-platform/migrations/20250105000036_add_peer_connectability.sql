@@ -0,0 +1,16 @@
+-- Add connectability probe state to peers
+-- NULL means the peer's endpoint has not been probed yet
+
+ALTER TABLE peers ALTER COLUMN is_connectable DROP NOT NULL;
+ALTER TABLE peers ALTER COLUMN is_connectable SET DEFAULT NULL;
+
+ALTER TABLE peers ADD COLUMN connectable_checked_at TIMESTAMP WITH TIME ZONE;
+
+-- Existing rows were never actually probed
+UPDATE peers SET is_connectable = NULL;
+
+-- Index for "your client is not connectable" warnings
+CREATE INDEX idx_peers_user_unconnectable ON peers(user_id) WHERE is_connectable = false;
+
+COMMENT ON COLUMN peers.is_connectable IS 'Result of the last TCP handshake probe (NULL if not yet probed)';
+COMMENT ON COLUMN peers.connectable_checked_at IS 'When the connectability probe result was last written';
@@ -0,0 +1,39 @@
+-- Create snatched table
+-- One row per user per torrent, written by the tracker when a peer reports
+-- event=completed with left=0. This is the source of truth for snatch lists,
+-- snatch-based achievements and hit-and-run rules.
+
+CREATE TABLE snatched (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    torrent_id UUID NOT NULL REFERENCES torrents(id) ON DELETE CASCADE,
+
+    -- Client totals reported on the completing announce
+    uploaded BIGINT NOT NULL DEFAULT 0,
+    downloaded BIGINT NOT NULL DEFAULT 0,
+
+    -- Seconds between the user's first announce for the torrent and completion
+    time_left BIGINT NOT NULL DEFAULT 0,
+
+    -- Seconds spent seeding since completion (credited by seeding announces)
+    seed_time BIGINT NOT NULL DEFAULT 0,
+    last_seeded_at TIMESTAMP WITH TIME ZONE,
+
+    snatched_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (user_id, torrent_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_snatched_user_id ON snatched(user_id, snatched_at DESC);
+CREATE INDEX idx_snatched_torrent_id ON snatched(torrent_id, snatched_at DESC);
+
+-- Index for scans by completion time
+CREATE INDEX idx_snatched_seed_time ON snatched(snatched_at, seed_time);
+
+COMMENT ON TABLE snatched IS 'Announce-derived completions, deduplicated per user and torrent';
+COMMENT ON COLUMN snatched.time_left IS 'Seconds from first announce to completion';
+COMMENT ON COLUMN snatched.seed_time IS 'Seconds seeded after completion, capped per announce';
+
+-- times_completed is now incremented by the tracker on a user's first snatch,
+-- not when the .torrent file is downloaded
+COMMENT ON COLUMN torrents.times_completed IS 'Number of distinct users who completed the torrent';
@@ -0,0 +1,26 @@
+-- Create bonus_accruals table
+-- One row per user per accrual period; the primary key makes the seeding
+-- bonus job idempotent when a period is retried or run on several nodes
+
+CREATE TABLE bonus_accruals (
+    period_start TIMESTAMP WITH TIME ZONE NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    -- Payout
+    amount_hundredths BIGINT NOT NULL,
+    torrents_seeded INTEGER NOT NULL,
+    transaction_id UUID REFERENCES bonus_transactions(id) ON DELETE SET NULL,
+
+    -- Timestamps
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (period_start, user_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_bonus_accruals_user_id ON bonus_accruals(user_id, period_start DESC);
+
+COMMENT ON TABLE bonus_accruals IS 'Seeding bonus payouts per accrual period (prevents double credit)';
+COMMENT ON COLUMN bonus_accruals.period_start IS 'Start of the accrual interval, aligned to the interval length';
+COMMENT ON COLUMN bonus_accruals.amount_hundredths IS 'Points awarded, in hundredths of a point';
+COMMENT ON COLUMN bonus_accruals.torrents_seeded IS 'Distinct torrents the user seeded during the period';
@@ -0,0 +1,49 @@
+-- Add request fill verification and bounty escrow
+-- Fills hold the bounty until a challenge window passes; a rejected fill
+-- reopens the request and reverses any payout
+
+-- Accepted quality for fills (lists of resolutions, sources, codecs)
+ALTER TABLE torrent_requests
+    ADD COLUMN quality_requirements JSONB NOT NULL DEFAULT '{}'::jsonb;
+
+-- Refunds to contributors; the amount is pro rata on expiry
+ALTER TABLE torrent_request_bounties
+    ADD COLUMN refunded BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN refunded_amount BIGINT;
+
+CREATE TYPE request_fill_status AS ENUM ('pending', 'paid', 'rejected');
+
+CREATE TABLE request_fills (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    request_id UUID NOT NULL REFERENCES torrent_requests(id) ON DELETE CASCADE,
+    torrent_id UUID NOT NULL REFERENCES torrents(id) ON DELETE CASCADE,
+    filler_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    -- Escrow
+    bounty BIGINT NOT NULL,
+    status request_fill_status NOT NULL DEFAULT 'pending',
+    challenge_until TIMESTAMP WITH TIME ZONE NOT NULL,
+    paid_at TIMESTAMP WITH TIME ZONE,
+
+    -- Rejection
+    rejected_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    rejected_at TIMESTAMP WITH TIME ZONE,
+    rejection_reason TEXT,
+
+    -- Timestamps
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE INDEX idx_request_fills_request_id ON request_fills(request_id);
+CREATE INDEX idx_request_fills_filler_id ON request_fills(filler_id);
+
+-- At most one live fill per request
+CREATE UNIQUE INDEX idx_request_fills_active ON request_fills(request_id) WHERE status <> 'rejected';
+
+-- Index for the escrow release job
+CREATE INDEX idx_request_fills_pending ON request_fills(challenge_until) WHERE status = 'pending';
+
+COMMENT ON TABLE request_fills IS 'Request fills and their bounty escrow';
+COMMENT ON COLUMN request_fills.challenge_until IS 'Bounty is paid out after this unless the fill is rejected';
+COMMENT ON COLUMN torrent_requests.quality_requirements IS 'Accepted quality values for fills; empty lists accept anything';
@@ -0,0 +1,25 @@
+-- Create upload rules table
+-- Declarative upload rules per category; the row without a category is the
+-- site-wide default used by categories that have no rules of their own
+
+CREATE TABLE upload_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    category_id INTEGER REFERENCES torrent_categories(id) ON DELETE CASCADE,
+
+    -- Ruleset (allowed extensions, required files, name pattern, sizes, ...)
+    rules JSONB NOT NULL DEFAULT '{}'::jsonb,
+
+    -- Audit
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE UNIQUE INDEX idx_upload_rules_category_id ON upload_rules(category_id) WHERE category_id IS NOT NULL;
+
+-- At most one site-wide default
+CREATE UNIQUE INDEX idx_upload_rules_default ON upload_rules((category_id IS NULL)) WHERE category_id IS NULL;
+
+COMMENT ON TABLE upload_rules IS 'Admin-editable upload rules per category';
+COMMENT ON COLUMN upload_rules.category_id IS 'NULL for the site-wide default ruleset';
+COMMENT ON COLUMN upload_rules.rules IS 'Serialized UploadRuleSet; missing fields use built-in defaults';
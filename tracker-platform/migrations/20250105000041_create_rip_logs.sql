@@ -0,0 +1,51 @@
+-- Create rip log tables
+-- EAC/XLD logs and cue sheets attached to lossless music uploads, with the
+-- log score used for search filters and trumping
+
+CREATE TYPE rip_log_ripper AS ENUM ('eac', 'xld');
+CREATE TYPE log_checksum_status AS ENUM ('missing', 'unverified', 'valid', 'invalid');
+
+CREATE TABLE torrent_rip_logs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    torrent_id UUID NOT NULL REFERENCES torrents(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+
+    -- Verification result
+    ripper rip_log_ripper NOT NULL,
+    score SMALLINT NOT NULL CHECK (score BETWEEN 0 AND 100),
+    checksum_status log_checksum_status NOT NULL,
+    deductions JSONB NOT NULL DEFAULT '[]'::jsonb,
+
+    -- Original log
+    log_text TEXT NOT NULL,
+    log_sha256 CHAR(64) NOT NULL,
+
+    -- Timestamps
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE (torrent_id, position)
+);
+
+CREATE TABLE torrent_cue_sheets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    torrent_id UUID NOT NULL REFERENCES torrents(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE (torrent_id, position)
+);
+
+-- Torrent-level summary
+ALTER TABLE torrent_metadata
+    ADD COLUMN log_score SMALLINT,
+    ADD COLUMN has_log BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN has_cue BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Create indexes
+CREATE INDEX idx_torrent_rip_logs_sha256 ON torrent_rip_logs(log_sha256);
+CREATE INDEX idx_torrent_metadata_log_score ON torrent_metadata(log_score) WHERE log_score IS NOT NULL;
+
+COMMENT ON TABLE torrent_rip_logs IS 'EAC/XLD rip logs with their verification result';
+COMMENT ON COLUMN torrent_rip_logs.checksum_status IS 'State of the log self-signature; invalid means the log was edited';
+COMMENT ON COLUMN torrent_metadata.log_score IS 'Lowest rip log score of the torrent (0-100), NULL without a log';
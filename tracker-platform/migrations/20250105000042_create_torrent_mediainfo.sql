@@ -0,0 +1,24 @@
+-- Create torrent MediaInfo table
+-- Parsed MediaInfo output pasted at upload, with the quality claims from the
+-- release name that it contradicts
+
+CREATE TABLE torrent_mediainfo (
+    torrent_id UUID PRIMARY KEY REFERENCES torrents(id) ON DELETE CASCADE,
+
+    -- Original text and parsed tracks
+    raw_text TEXT NOT NULL,
+    report JSONB NOT NULL,
+
+    -- Name/MediaInfo disagreements flagged to moderators
+    mismatches JSONB NOT NULL DEFAULT '[]'::jsonb,
+
+    -- Timestamps
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE INDEX idx_torrent_mediainfo_flagged ON torrent_mediainfo(torrent_id) WHERE mismatches <> '[]'::jsonb;
+
+COMMENT ON TABLE torrent_mediainfo IS 'MediaInfo submitted with uploads';
+COMMENT ON COLUMN torrent_mediainfo.mismatches IS 'Release-name quality claims contradicted by the MediaInfo';
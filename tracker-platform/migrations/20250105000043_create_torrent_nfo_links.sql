@@ -0,0 +1,25 @@
+-- Create torrent NFO links table
+-- IMDb and TMDB links found in a torrent's NFO, used by the media enricher
+-- before it falls back to searching by release name
+
+CREATE TABLE torrent_nfo_links (
+    torrent_id UUID NOT NULL REFERENCES torrents(id) ON DELETE CASCADE,
+
+    -- Link target
+    source VARCHAR(16) NOT NULL CHECK (source IN ('imdb', 'tmdb_movie', 'tmdb_tv')),
+    external_id VARCHAR(32) NOT NULL,
+
+    -- Order of appearance in the NFO
+    position INTEGER NOT NULL DEFAULT 0,
+
+    -- Timestamps
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (torrent_id, source, external_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_torrent_nfo_links_external ON torrent_nfo_links(source, external_id);
+
+COMMENT ON TABLE torrent_nfo_links IS 'External links mined from torrent NFOs';
+COMMENT ON COLUMN torrent_nfo_links.position IS 'Order the link appears in the NFO; the first link is preferred';
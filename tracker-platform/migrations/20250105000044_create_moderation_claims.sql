@@ -0,0 +1,54 @@
+-- Create moderation queue tables
+-- Moderator claims on queue items, per-category review checklists and the
+-- columns needed to track time in queue and escalation
+
+CREATE TABLE IF NOT EXISTS moderation_actions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    torrent_id UUID NOT NULL REFERENCES torrents(id) ON DELETE CASCADE,
+    moderator_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    previous_status VARCHAR(20) NOT NULL,
+    new_status VARCHAR(20) NOT NULL,
+    reason TEXT,
+    rejection_category VARCHAR(32),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Checklist answers and time in queue recorded with each action
+ALTER TABLE moderation_actions
+    ADD COLUMN IF NOT EXISTS checklist JSONB,
+    ADD COLUMN IF NOT EXISTS queue_seconds BIGINT;
+
+-- Escalation of items waiting past the SLA
+ALTER TABLE torrents ADD COLUMN escalated_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE moderation_claims (
+    torrent_id UUID PRIMARY KEY REFERENCES torrents(id) ON DELETE CASCADE,
+    moderator_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    claimed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE moderation_checklists (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    category_id INTEGER REFERENCES torrent_categories(id) ON DELETE CASCADE,
+
+    -- Checklist items (key, label, required)
+    items JSONB NOT NULL DEFAULT '[]'::jsonb,
+
+    -- Audit
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE INDEX IF NOT EXISTS idx_moderation_actions_torrent_id ON moderation_actions(torrent_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_moderation_actions_moderator ON moderation_actions(moderator_id, created_at DESC);
+CREATE INDEX idx_moderation_claims_moderator ON moderation_claims(moderator_id);
+CREATE INDEX idx_torrents_escalated ON torrents(escalated_at) WHERE escalated_at IS NOT NULL;
+CREATE UNIQUE INDEX idx_moderation_checklists_category_id ON moderation_checklists(category_id) WHERE category_id IS NOT NULL;
+CREATE UNIQUE INDEX idx_moderation_checklists_default ON moderation_checklists((category_id IS NULL)) WHERE category_id IS NULL;
+
+COMMENT ON TABLE moderation_claims IS 'Queue items a moderator is currently reviewing';
+COMMENT ON TABLE moderation_checklists IS 'Review checklists per category; NULL category is the site-wide default';
+COMMENT ON COLUMN moderation_actions.queue_seconds IS 'Seconds since upload when the action was taken';
+COMMENT ON COLUMN torrents.escalated_at IS 'When the pending torrent passed the escalation threshold';
@@ -0,0 +1,45 @@
+-- Create torrent revisions table
+-- Versioned snapshots of torrent metadata with a field-level diff per edit,
+-- and the notifications sent to uploaders and snatchers for significant edits
+
+CREATE TABLE torrent_revisions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    torrent_id UUID NOT NULL REFERENCES torrents(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+
+    -- Editor (NULL for the original upload)
+    editor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+
+    -- Full metadata after this revision and the changes that produced it
+    snapshot JSONB NOT NULL,
+    changes JSONB NOT NULL DEFAULT '[]'::jsonb,
+    summary TEXT,
+
+    -- Version restored by a rollback
+    rollback_of INTEGER,
+
+    significant BOOLEAN NOT NULL DEFAULT false,
+
+    -- Timestamps
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE (torrent_id, version)
+);
+
+CREATE TABLE torrent_edit_notifications (
+    revision_id UUID NOT NULL REFERENCES torrent_revisions(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    read_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (revision_id, user_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_torrent_revisions_editor_id ON torrent_revisions(editor_id, created_at DESC);
+CREATE INDEX idx_torrent_edit_notifications_unread ON torrent_edit_notifications(user_id, created_at DESC) WHERE read_at IS NULL;
+
+COMMENT ON TABLE torrent_revisions IS 'Append-only edit history of torrent metadata';
+COMMENT ON COLUMN torrent_revisions.snapshot IS 'Serialized TorrentSnapshot (metadata and listing category) after the edit; rollbacks restore this';
+COMMENT ON COLUMN torrent_revisions.significant IS 'Edit touched name, category, media type, quality or external IDs';
+COMMENT ON TABLE torrent_edit_notifications IS 'Significant-edit notices for uploaders and snatchers';
@@ -0,0 +1,42 @@
+-- Create tag taxonomy tables
+-- Staff-curated aliases, implications and bans on top of free-form voted tags
+
+CREATE TABLE tag_aliases (
+    alias VARCHAR(100) PRIMARY KEY,
+    canonical VARCHAR(100) NOT NULL,
+
+    -- Audit
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CHECK (alias <> canonical)
+);
+
+CREATE TABLE tag_implications (
+    tag VARCHAR(100) NOT NULL,
+    implied VARCHAR(100) NOT NULL,
+
+    -- Audit
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (tag, implied),
+    CHECK (tag <> implied)
+);
+
+CREATE TABLE banned_tags (
+    tag VARCHAR(100) PRIMARY KEY,
+    reason TEXT,
+
+    -- Audit
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE INDEX idx_tag_aliases_canonical ON tag_aliases(canonical);
+CREATE INDEX idx_tag_implications_implied ON tag_implications(implied);
+
+COMMENT ON TABLE tag_aliases IS 'Tag names redirected to a canonical tag';
+COMMENT ON TABLE tag_implications IS 'Tags added automatically when another tag is present';
+COMMENT ON TABLE banned_tags IS 'Tags that may not be applied';
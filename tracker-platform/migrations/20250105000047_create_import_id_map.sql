@@ -0,0 +1,21 @@
+-- Create import_id_map table
+-- Maps record IDs from an imported Gazelle/UNIT3D database to platform IDs.
+-- Written by the tracker-import tool; lets interrupted imports resume.
+
+CREATE TABLE import_id_map (
+    source VARCHAR(20) NOT NULL, -- gazelle, unit3d
+    entity VARCHAR(30) NOT NULL, -- user, torrent, topic, ...
+    source_id VARCHAR(64) NOT NULL,
+    target_id UUID NOT NULL,
+
+    imported_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (source, entity, source_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_import_id_map_target ON import_id_map(entity, target_id);
+
+COMMENT ON TABLE import_id_map IS 'Source tracker IDs of imported records';
+COMMENT ON COLUMN import_id_map.source_id IS 'Primary key of the record on the source site';
+COMMENT ON COLUMN import_id_map.target_id IS 'ID of the imported row on this platform';
@@ -0,0 +1,42 @@
+-- Create reseed request tables
+-- Requests to revive torrents without seeders
+
+-- Swarm counters written by the tracker's batch writer, keyed by raw info hash
+CREATE TABLE IF NOT EXISTS torrent_stats (
+    info_hash BYTEA PRIMARY KEY,
+    seeders INTEGER NOT NULL DEFAULT 0,
+    leechers INTEGER NOT NULL DEFAULT 0,
+    completed BIGINT NOT NULL DEFAULT 0,
+    last_updated TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE torrent_stats ADD COLUMN IF NOT EXISTS last_seeded_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE reseed_requests (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    torrent_id UUID NOT NULL REFERENCES torrents(id) ON DELETE CASCADE,
+    requester_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    -- Bonus points held for the reseeder
+    bounty DOUBLE PRECISION NOT NULL DEFAULT 0 CHECK (bounty >= 0),
+
+    status VARCHAR(20) NOT NULL DEFAULT 'open', -- open, fulfilled, expired
+    notified_count INTEGER NOT NULL DEFAULT 0, -- users emailed
+
+    -- Resolution
+    fulfilled_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    fulfilled_at TIMESTAMP WITH TIME ZONE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE INDEX idx_torrent_stats_dead ON torrent_stats(last_seeded_at) WHERE seeders = 0;
+CREATE INDEX idx_reseed_requests_torrent_id ON reseed_requests(torrent_id, created_at DESC);
+CREATE INDEX idx_reseed_requests_requester_id ON reseed_requests(requester_id, created_at DESC);
+CREATE UNIQUE INDEX idx_reseed_requests_open ON reseed_requests(torrent_id) WHERE status = 'open';
+
+COMMENT ON TABLE reseed_requests IS 'Requests to bring torrents without seeders back';
+COMMENT ON COLUMN reseed_requests.bounty IS 'Bonus points taken from the requester, paid to the reseeder or refunded on expiry';
+COMMENT ON COLUMN torrent_stats.last_seeded_at IS 'Last batch flush that saw at least one seeder';
@@ -0,0 +1,27 @@
+-- Create WebAuthn credentials table
+-- Security keys and passkeys registered by users
+
+CREATE TABLE webauthn_credentials (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+
+    -- Credential ID assigned by the authenticator
+    credential_id BYTEA NOT NULL UNIQUE,
+    -- Uncompressed SEC1 P-256 public key (ES256)
+    public_key BYTEA NOT NULL,
+    sign_count BIGINT NOT NULL DEFAULT 0,
+    transports TEXT[] NOT NULL DEFAULT '{}',
+
+    -- Discoverable, user-verified credential usable without a password
+    is_passkey BOOLEAN NOT NULL DEFAULT false,
+
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP WITH TIME ZONE
+);
+
+-- Create indexes
+CREATE INDEX idx_webauthn_credentials_user_id ON webauthn_credentials(user_id);
+
+COMMENT ON TABLE webauthn_credentials IS 'WebAuthn security keys and passkeys, used as a second factor or for passwordless login';
+COMMENT ON COLUMN webauthn_credentials.sign_count IS 'Last authenticator signature counter; a counter that does not increase indicates a cloned key';
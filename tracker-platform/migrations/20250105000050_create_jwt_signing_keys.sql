@@ -0,0 +1,23 @@
+-- Create JWT signing keys table
+-- Asymmetric keys that sign JWTs, rotated on a schedule
+
+CREATE TABLE jwt_signing_keys (
+    -- Sent as the `kid` header of tokens signed with this key
+    kid VARCHAR(64) PRIMARY KEY,
+    algorithm VARCHAR(16) NOT NULL CHECK (algorithm IN ('EdDSA', 'RS256')),
+    -- PKCS#8 (Ed25519) or PKCS#1 (RSA) DER
+    private_key BYTEA NOT NULL,
+
+    -- Published in the JWKS from creation, signs from activates_at
+    activates_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    -- Stops verifying and is deleted after this; NULL while current
+    retires_at TIMESTAMP WITH TIME ZONE,
+
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE INDEX idx_jwt_signing_keys_activates_at ON jwt_signing_keys(activates_at);
+
+COMMENT ON TABLE jwt_signing_keys IS 'JWT signing keys; public halves are served at /.well-known/jwks.json';
+COMMENT ON COLUMN jwt_signing_keys.private_key IS 'Private key material; restrict access to this table';
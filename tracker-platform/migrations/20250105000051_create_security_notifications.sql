@@ -0,0 +1,22 @@
+-- Create security notifications table
+-- Account security events shown to the user, e.g. a replayed refresh token
+
+CREATE TABLE security_notifications (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    event VARCHAR(50) NOT NULL,
+    message TEXT NOT NULL,
+
+    -- Where the event came from, if known
+    ip_address INET,
+    user_agent TEXT,
+
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    read_at TIMESTAMP WITH TIME ZONE
+);
+
+-- Create indexes
+CREATE INDEX idx_security_notifications_unread ON security_notifications(user_id, created_at DESC) WHERE read_at IS NULL;
+
+COMMENT ON TABLE security_notifications IS 'Account security events shown to the user on their next visit';
+COMMENT ON COLUMN security_notifications.event IS 'Event kind: refresh_token_reuse';
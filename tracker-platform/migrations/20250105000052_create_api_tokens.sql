@@ -0,0 +1,31 @@
+-- Create API tokens table
+-- Personal tokens users create for scripts and automation
+
+CREATE TABLE api_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+
+    -- Only the SHA-256 of the token is stored; the prefix is kept for display
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    token_prefix VARCHAR(16) NOT NULL,
+
+    scopes TEXT[] NOT NULL,
+    allowed_ips TEXT[] NOT NULL DEFAULT '{}',
+
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    last_used_ip INET,
+
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+
+    CONSTRAINT api_tokens_scopes_not_empty CHECK (cardinality(scopes) > 0)
+);
+
+-- Create indexes
+CREATE INDEX idx_api_tokens_user_active ON api_tokens(user_id) WHERE revoked_at IS NULL;
+
+COMMENT ON TABLE api_tokens IS 'Personal API tokens for scripts and automation, hashed at rest';
+COMMENT ON COLUMN api_tokens.scopes IS 'Granted scopes: read_torrents, download, upload, post';
+COMMENT ON COLUMN api_tokens.allowed_ips IS 'IP addresses or CIDR ranges the token may be used from; empty means any';
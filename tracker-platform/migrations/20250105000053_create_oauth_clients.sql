@@ -0,0 +1,36 @@
+-- Create OAuth clients and consents tables
+-- Sister sites that sign members in through the OAuth 2.1 / OpenID Connect provider
+
+CREATE TABLE oauth_clients (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    client_id VARCHAR(64) NOT NULL UNIQUE,
+    name VARCHAR(100) NOT NULL,
+
+    -- SHA-256 of the client secret; NULL for public clients (PKCE only)
+    client_secret_hash VARCHAR(64),
+
+    redirect_uris TEXT[] NOT NULL,
+    allowed_scopes TEXT[] NOT NULL,
+
+    created_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+
+    CONSTRAINT oauth_clients_redirect_uris_not_empty CHECK (cardinality(redirect_uris) > 0)
+);
+
+CREATE TABLE oauth_consents (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    client_id VARCHAR(64) NOT NULL REFERENCES oauth_clients(client_id) ON DELETE CASCADE,
+    scopes TEXT[] NOT NULL,
+    granted_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (user_id, client_id)
+);
+
+-- Create indexes
+CREATE INDEX idx_oauth_consents_client ON oauth_consents(client_id);
+
+COMMENT ON TABLE oauth_clients IS 'OAuth/OIDC client applications registered by admins';
+COMMENT ON COLUMN oauth_clients.allowed_scopes IS 'Scopes the client may request: openid, profile, email or a permission name';
+COMMENT ON TABLE oauth_consents IS 'Scopes each user has approved per client, so consent is only asked once';
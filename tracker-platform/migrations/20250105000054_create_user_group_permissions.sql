@@ -0,0 +1,103 @@
+-- Create user_group_permissions and user_permission_overrides tables
+-- Permissions each user class carries, and per-user grants and denials on top
+
+CREATE TABLE user_group_permissions (
+    group_id INTEGER NOT NULL REFERENCES user_groups(id) ON DELETE CASCADE,
+    permission VARCHAR(50) NOT NULL,
+
+    PRIMARY KEY (group_id, permission)
+);
+
+CREATE TABLE user_permission_overrides (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    permission VARCHAR(50) NOT NULL,
+
+    -- true grants the permission, false denies it (denials always win)
+    granted BOOLEAN NOT NULL,
+    reason TEXT,
+
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE,
+
+    PRIMARY KEY (user_id, permission)
+);
+
+-- Create indexes
+CREATE INDEX idx_user_permission_overrides_expires ON user_permission_overrides(expires_at)
+    WHERE expires_at IS NOT NULL;
+
+-- Seed the default groups with the permissions of the built-in roles
+INSERT INTO user_group_permissions (group_id, permission)
+SELECT g.id, p.permission
+FROM user_groups g
+JOIN (VALUES
+    ('validating', 'create_forum_post'),
+    ('validating', 'vote_request'),
+    ('validating', 'download'),
+
+    ('member', 'upload_torrent'),
+    ('member', 'create_forum_post'),
+    ('member', 'create_request'),
+    ('member', 'vote_request'),
+    ('member', 'fill_request'),
+    ('member', 'download'),
+
+    ('administrator', 'site_admin'),
+    ('sysop', 'site_admin')
+) AS p(group_name, permission) ON p.group_name = g.name;
+
+-- Power user and above get the power user set
+INSERT INTO user_group_permissions (group_id, permission)
+SELECT g.id, p.permission
+FROM user_groups g
+CROSS JOIN (VALUES
+    ('upload_torrent'),
+    ('create_forum_post'),
+    ('create_request'),
+    ('vote_request'),
+    ('fill_request'),
+    ('api_access'),
+    ('download'),
+    ('auto_download'),
+    ('bypass_upload_wait'),
+    ('send_invites')
+) AS p(permission)
+WHERE g.level >= 3 AND g.level < 20;
+
+-- Forum moderators and moderators
+INSERT INTO user_group_permissions (group_id, permission)
+SELECT g.id, p.permission
+FROM user_groups g
+CROSS JOIN (VALUES
+    ('forum_moderator'),
+    ('delete_forum_posts'),
+    ('edit_forum_posts'),
+    ('warn_users')
+) AS p(permission)
+WHERE g.level >= 10 AND g.level < 20;
+
+INSERT INTO user_group_permissions (group_id, permission)
+SELECT g.id, p.permission
+FROM user_groups g
+CROSS JOIN (VALUES
+    ('site_logs'),
+    ('view_user_sessions'),
+    ('torrent_moderator'),
+    ('delete_torrents'),
+    ('edit_torrents'),
+    ('ban_users'),
+    ('view_user_i_ps'),
+    ('view_statistics')
+) AS p(permission)
+WHERE g.level >= 15 AND g.level < 20;
+
+-- Groups immune from ratio watch
+INSERT INTO user_group_permissions (group_id, permission)
+SELECT id, 'immunity_automated' FROM user_groups
+WHERE is_immune AND level < 20;
+
+COMMENT ON TABLE user_group_permissions IS 'Permissions granted to every member of a user class';
+COMMENT ON COLUMN user_group_permissions.permission IS 'Permission identifier, e.g. upload_torrent';
+COMMENT ON TABLE user_permission_overrides IS 'Permissions granted to or denied from a single user';
+COMMENT ON COLUMN user_permission_overrides.expires_at IS 'When the override lapses; NULL for permanent';
@@ -0,0 +1,61 @@
+-- Create automatic class promotion tables
+-- Thresholds for the classes users are promoted into and demoted out of
+
+CREATE TABLE user_class_requirements (
+    group_id INTEGER PRIMARY KEY REFERENCES user_groups(id) ON DELETE CASCADE,
+
+    -- Thresholds a member must meet
+    min_uploaded BIGINT NOT NULL DEFAULT 0, -- Bytes
+    min_ratio DOUBLE PRECISION NOT NULL DEFAULT 0,
+    min_account_age_days INTEGER NOT NULL DEFAULT 0,
+    min_uploads INTEGER NOT NULL DEFAULT 0,
+    min_achievement_points INTEGER NOT NULL DEFAULT 0,
+    max_active_warnings INTEGER NOT NULL DEFAULT 0,
+
+    -- Days a member who falls below the thresholds has to recover
+    demotion_grace_days INTEGER NOT NULL DEFAULT 7 CHECK (demotion_grace_days >= 0),
+
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Staff can pin a user's class so the promotion job leaves it alone
+ALTER TABLE users ADD COLUMN class_pinned BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE user_class_demotion_notices (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    from_group_id INTEGER NOT NULL REFERENCES user_groups(id) ON DELETE CASCADE,
+    notified_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    demote_after TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE user_class_changes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    from_group_id INTEGER NOT NULL REFERENCES user_groups(id) ON DELETE CASCADE,
+    to_group_id INTEGER NOT NULL REFERENCES user_groups(id) ON DELETE CASCADE,
+    direction VARCHAR(10) NOT NULL, -- promotion, demotion
+    reason TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE INDEX idx_users_class_pinned ON users(class_pinned) WHERE class_pinned = true;
+CREATE INDEX idx_user_class_changes_user ON user_class_changes(user_id, created_at DESC);
+
+-- Default ladder (matching Gazelle's thresholds)
+INSERT INTO user_class_requirements
+    (group_id, min_uploaded, min_ratio, min_account_age_days, min_uploads, max_active_warnings)
+SELECT g.id, r.min_uploaded, r.min_ratio, r.min_account_age_days, r.min_uploads, r.max_active_warnings
+FROM user_groups g
+JOIN (VALUES
+    ('member', 0::BIGINT, 0.0::DOUBLE PRECISION, 0, 0, 100),
+    ('power_user', 25::BIGINT * 1024 * 1024 * 1024, 1.05::DOUBLE PRECISION, 14, 0, 0),
+    ('elite', 100::BIGINT * 1024 * 1024 * 1024, 1.05::DOUBLE PRECISION, 28, 50, 0),
+    ('torrent_master', 500::BIGINT * 1024 * 1024 * 1024, 1.05::DOUBLE PRECISION, 56, 500, 0)
+) AS r(name, min_uploaded, min_ratio, min_account_age_days, min_uploads, max_active_warnings)
+    ON r.name = g.name;
+
+COMMENT ON TABLE user_class_requirements IS 'Classes the promotion job moves users between, with their thresholds';
+COMMENT ON COLUMN users.class_pinned IS 'Class set by staff; exempt from automatic promotion and demotion';
+COMMENT ON TABLE user_class_demotion_notices IS 'Users below their class thresholds, and when they will be demoted';
+COMMENT ON TABLE user_class_changes IS 'Automatic promotions and demotions';
@@ -0,0 +1,54 @@
+-- Create transactional email tables
+-- Outbound queue, bounce suppression list and per-category unsubscribes
+
+CREATE TABLE email_outbox (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    to_address VARCHAR(255) NOT NULL,
+
+    template VARCHAR(30) NOT NULL, -- verification, password_reset, invite, new_login, topic_digest, reseed_request
+    category VARCHAR(20) NOT NULL, -- account, security_alerts, digests, invites, reseeds
+    locale VARCHAR(10) NOT NULL DEFAULT 'en',
+
+    -- Rendered when queued, so template changes never alter a queued mail
+    subject TEXT NOT NULL,
+    text_body TEXT NOT NULL,
+    html_body TEXT NOT NULL,
+    list_unsubscribe TEXT,
+
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, sending, sent, failed, suppressed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    locked_until TIMESTAMP WITH TIME ZONE,
+    last_error TEXT,
+
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sent_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE TABLE email_suppressions (
+    address VARCHAR(255) PRIMARY KEY,
+    soft_bounces INTEGER NOT NULL DEFAULT 0,
+    suppressed BOOLEAN NOT NULL DEFAULT false,
+    reason TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE email_unsubscribes (
+    address VARCHAR(255) NOT NULL,
+    category VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (address, category)
+);
+
+-- Create indexes
+CREATE INDEX idx_email_outbox_due ON email_outbox(next_attempt_at)
+    WHERE status IN ('pending', 'sending');
+CREATE INDEX idx_email_outbox_to_address ON email_outbox(to_address);
+CREATE INDEX idx_email_outbox_user ON email_outbox(user_id, created_at DESC);
+
+COMMENT ON TABLE email_outbox IS 'Rendered transactional emails waiting for, or done with, delivery';
+COMMENT ON COLUMN email_outbox.locked_until IS 'Claim held by a dispatcher; reclaimed if it expires mid-send';
+COMMENT ON TABLE email_suppressions IS 'Bounce history per address; suppressed addresses receive no mail';
+COMMENT ON TABLE email_unsubscribes IS 'Email categories an address has opted out of';
@@ -0,0 +1,23 @@
+-- Create password reset and breached password screening tables
+-- Single-use reset tokens, and flags forcing a password check or reset at next login
+
+ALTER TABLE users ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE users ADD COLUMN password_breach_check_pending BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE users ADD COLUMN password_changed_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE password_reset_tokens (
+    token_hash VARCHAR(64) PRIMARY KEY, -- SHA-256 of the token, hex
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE INDEX idx_users_password_breach_check_pending ON users(password_breach_check_pending)
+    WHERE password_breach_check_pending = true;
+CREATE INDEX idx_password_reset_tokens_user ON password_reset_tokens(user_id);
+
+COMMENT ON COLUMN users.password_reset_required IS 'Password must be reset before the next password login';
+COMMENT ON COLUMN users.password_breach_check_pending IS 'Check the password against the breach corpus at next login';
+COMMENT ON TABLE password_reset_tokens IS 'Password reset links sent by email; each works once';
@@ -0,0 +1,49 @@
+-- Create login risk tables
+-- Known devices, sign-in history for location checks, and email approvals of risky sign-ins
+
+CREATE TABLE known_devices (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    device_hash VARCHAR(64) NOT NULL, -- SHA-256 of the normalized user agent, hex
+    description VARCHAR(200) NOT NULL,
+    first_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_ip_address INET,
+    PRIMARY KEY (user_id, device_hash)
+);
+
+CREATE TABLE login_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    device_hash VARCHAR(64) NOT NULL,
+    ip_address INET NOT NULL,
+    country_code CHAR(2),
+    location VARCHAR(200),
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    signals TEXT[] NOT NULL DEFAULT '{}', -- new_device, new_country, impossible_travel
+    -- SHA-256 of the "this wasn't me" token sent in the alert, hex
+    report_token_hash VARCHAR(64) UNIQUE,
+    reported_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE login_confirmations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE, -- SHA-256 of the emailed token, hex
+    device_hash VARCHAR(64) NOT NULL,
+    ip_address INET NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    approved_at TIMESTAMP WITH TIME ZONE,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create indexes
+CREATE INDEX idx_login_events_user_created ON login_events(user_id, created_at DESC);
+CREATE INDEX idx_login_events_user_country ON login_events(user_id, country_code);
+CREATE INDEX idx_login_confirmations_user ON login_confirmations(user_id);
+
+COMMENT ON TABLE known_devices IS 'Devices each user has signed in from';
+COMMENT ON TABLE login_events IS 'Successful sign-ins with resolved location and risk signals';
+COMMENT ON TABLE login_confirmations IS 'Risky sign-ins waiting for approval by email; each works once';
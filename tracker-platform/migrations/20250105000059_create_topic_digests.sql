@@ -0,0 +1,10 @@
+-- Create topic_digests table
+-- When each user was last emailed a digest of new posts in subscribed topics
+
+CREATE TABLE topic_digests (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- Posts up to this time have been covered by a digest
+    last_sent_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+COMMENT ON TABLE topic_digests IS 'Cut-off of the last topic digest emailed to each user';